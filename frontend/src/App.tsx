@@ -3,13 +3,19 @@ import type { CSSProperties, FormEvent, ReactNode } from 'react'
 import {
   Activity,
   Boxes,
+  CalendarClock,
   ChevronLeft,
   ChevronRight,
   ChevronsLeft,
   ChevronsRight,
   ChartSpline,
+  Download,
   Database,
+  Film,
+  FileMusic,
+  FileWarning,
   Gift,
+  History,
   Images,
   KeyRound,
   Link2,
@@ -26,6 +32,7 @@ import {
   ShieldCheck,
   ShoppingBag,
   Trash2,
+  Trophy,
   UserPlus,
   UserRound,
   Users,
@@ -52,23 +59,55 @@ import {
 } from '@/components/ui/table'
 import {
   adminApi,
+  runOperation,
   type AdminChartTop,
+  type AdminEvent,
+  type AdminRedeemBatch,
+  type AdminEventLadder,
   type AdminActionResult,
   type AdminOperation,
   type AdminScoreRow,
   type AdminSession,
   type AdminUserSummary,
+  type AdminUserSaves,
+  type AdminImpersonationToken,
+  type AdminUserDevices,
+  type AdminUserPurchases,
+  type AdminUserDownloadQuota,
+  type AnomalyRow,
+  type BundleRow,
+  type SongUploadResult,
+  type ApiLogRow,
+  type ScoreAuditFlag,
+  type ScoreAuditRow,
+  type ImpersonationLogRow,
+  type OperationJob,
+  type ChartMismatchReport,
+  type ReplayRow,
+  type ChartAnalyticsRow,
+  type ChartAnalyticsSort,
+  type ChartConstantProposal,
+  type GameConstant,
   type AdminUserScores,
+  type PttHistory,
+  type PttHistoryPoint,
+  type Recent30Diff,
+  type Recent30Entry,
   type DashboardData,
+  type EventPayload,
   type ItemPayload,
   type ItemRow,
   type PageData,
   type PresentDeliverPayload,
   type PresentPayload,
+  type PresentPublishPayload,
+  type PresentRow,
+  type PresentSort,
   type PurchaseItemPayload,
   type PurchaseItemRow,
   type PurchasePayload,
   type PurchaseRow,
+  type RedeemBatchPayload,
   type RedeemPayload,
   type ScoreDeletePayload,
   type ScoreImages,
@@ -83,6 +122,8 @@ import {
 import { cn } from '@/lib/utils'
 
 const defaultAppTitle = 'Arcaea Server'
+const textareaClass =
+  'min-h-28 w-full rounded-md border border-input bg-transparent px-3 py-2 font-mono text-sm shadow-xs outline-none placeholder:text-muted-foreground focus-visible:border-ring focus-visible:ring-2 focus-visible:ring-ring/50'
 const githubUrl = 'https://github.com/YinMo19/Arcaea_server_rs'
 
 type LoginPosition = 'left' | 'center' | 'right'
@@ -138,27 +179,57 @@ type View =
   | 'playerScores'
   | 'scoreImages'
   | 'chartTop'
+  | 'chartAnalytics'
+  | 'anomalies'
+  | 'scoreAudits'
+  | 'replays'
+  | 'requestLog'
   | 'userTicket'
   | 'userPassword'
   | 'userCreate'
   | 'userBan'
   | 'userPurchase'
+  | 'userSaves'
+  | 'userImpersonate'
   | 'scoreDelete'
+  | 'presents'
   | 'presentCreate'
+  | 'presentPublish'
   | 'presentDeliver'
   | 'presentDelete'
   | 'redeemCreate'
   | 'redeemDelete'
   | 'redeemUsers'
+  | 'redeemBatches'
+  | 'events'
+  | 'eventLadder'
   | 'songs'
+  | 'chartMismatches'
+  | 'songUpload'
+  | 'bundles'
   | 'items'
   | 'purchases'
   | 'purchaseItems'
+  | 'gameConstants'
 
 type MaintenanceView =
   | 'refreshSongFileCache'
+  | 'refreshSongFileCacheIncremental'
   | 'refreshContentBundleCache'
   | 'refreshAllScoreRating'
+  | 'backfillScoreV2'
+  | 'refreshChartAnalytics'
+  | 'estimateChartConstants'
+  | 'scanScoreAnomalies'
+  | 'reloadGameConstants'
+  | 'reloadSonglist'
+  | 'syncChartsFromSonglist'
+  | 'purgeSongplayTokens'
+  | 'recalculateWorldProgress'
+  | 'rebuildRecent30'
+  | 'vacuumExpiredPresents'
+  | 'exportUserData'
+  | 'reloadConfig'
 
 type MaintenanceOperationConfig = {
   operation: AdminOperation
@@ -166,6 +237,7 @@ type MaintenanceOperationConfig = {
   description: string
   buttonLabel: string
   confirmText?: string
+  userId?: 'optional' | 'required'
 }
 
 const maintenanceOperations: Record<MaintenanceView, MaintenanceOperationConfig> = {
@@ -175,6 +247,12 @@ const maintenanceOperations: Record<MaintenanceView, MaintenanceOperationConfig>
     description: '重新扫描歌曲文件 hash 缓存',
     buttonLabel: '刷新 Song Hash',
   },
+  refreshSongFileCacheIncremental: {
+    operation: 'refresh_song_file_cache_incremental',
+    title: '增量刷新 Song Hash',
+    description: '只重新计算新增或修改过的歌曲文件 hash，并移除已删除文件',
+    buttonLabel: '增量刷新',
+  },
   refreshContentBundleCache: {
     operation: 'refresh_content_bundle_cache',
     title: '刷新 Bundle',
@@ -188,6 +266,90 @@ const maintenanceOperations: Record<MaintenanceView, MaintenanceOperationConfig>
     buttonLabel: '重算 Rating',
     confirmText: '重算所有成绩 Rating?',
   },
+  backfillScoreV2: {
+    operation: 'backfill_score_v2',
+    title: '补全 score_v2',
+    description: '为缺少 score_v2 的最佳成绩补算 EX 分数，并重算世界排名分',
+    buttonLabel: '补全 score_v2',
+    confirmText: '补算所有缺失的 score_v2?',
+  },
+  refreshChartAnalytics: {
+    operation: 'refresh_chart_analytics',
+    title: '刷新谱面分析',
+    description: '重新统计各谱面的分数分布与体感定数',
+    buttonLabel: '刷新谱面分析',
+  },
+  estimateChartConstants: {
+    operation: 'estimate_chart_constants',
+    title: '估算未定级定数',
+    description: '根据已有成绩为定数为 -1 的谱面生成定数建议',
+    buttonLabel: '估算定数',
+  },
+  scanScoreAnomalies: {
+    operation: 'scan_score_anomalies',
+    title: '扫描异常成绩',
+    description: '立即重新扫描游玩记录并更新异常审核队列',
+    buttonLabel: '扫描异常',
+  },
+  reloadGameConstants: {
+    operation: 'reload_game_constants',
+    title: '重载游戏常量',
+    description: '重新读取 game_constants 表中的平衡参数',
+    buttonLabel: '重载常量',
+  },
+  reloadSonglist: {
+    operation: 'reload_songlist',
+    title: '重载 songlist',
+    description: '重新读取 songlist，同步其中填写的定数并清空下载 hash 缓存',
+    buttonLabel: '重载 songlist',
+  },
+  syncChartsFromSonglist: {
+    operation: 'sync_charts_from_songlist',
+    title: '从 songlist 同步谱面',
+    description: '按 songlist 写入 chart 表的曲名与各难度定数，未填写定数的曲目保持不变',
+    buttonLabel: '同步谱面',
+  },
+  purgeSongplayTokens: {
+    operation: 'purge_songplay_tokens',
+    title: '清理游玩 Token',
+    description: '删除超过 24 小时未完成的游玩 token',
+    buttonLabel: '清理 Token',
+  },
+  recalculateWorldProgress: {
+    operation: 'recalculate_world_progress',
+    title: '重算世界进度',
+    description: '按当前地图文件修正玩家的世界模式进度，并补发已通过格子的解锁奖励',
+    buttonLabel: '重算进度',
+    userId: 'required',
+  },
+  rebuildRecent30: {
+    operation: 'rebuild_recent30',
+    title: '重建 Recent 30',
+    description: '根据游玩记录重建 Recent 30 并更新潜力值，留空玩家 ID 时处理全部玩家',
+    buttonLabel: '重建 Recent 30',
+    confirmText: '重建 Recent 30?',
+    userId: 'optional',
+  },
+  vacuumExpiredPresents: {
+    operation: 'vacuum_expired_presents',
+    title: '清理过期奖励',
+    description: '删除已过期的奖励及其物品和未领取记录',
+    buttonLabel: '清理奖励',
+    confirmText: '删除所有已过期奖励?',
+  },
+  exportUserData: {
+    operation: 'export_user_data',
+    title: '导出玩家数据',
+    description: '为玩家生成个人数据导出文件（资料、成绩、世界进度、奖励与登录记录），完成后通知玩家下载',
+    buttonLabel: '导出数据',
+    userId: 'required',
+  },
+  reloadConfig: {
+    operation: 'reload_config',
+    title: '重载配置',
+    description: '重新读取 Rocket.toml 并校验，应用下载链接前缀、下载限制与限流规则等可热重载的配置',
+    buttonLabel: '重载配置',
+  },
 }
 
 type NavItem = {
@@ -208,6 +370,7 @@ const navSections: Array<{ label: string; items: NavItem[] }> = [
       { id: 'playerScores', label: '玩家成绩', icon: ChartSpline },
       { id: 'chartTop', label: '单曲榜', icon: Search },
       { id: 'redeemUsers', label: '兑换使用者', icon: Users },
+      { id: 'requestLog', label: '请求日志', icon: History },
     ],
   },
   {
@@ -219,6 +382,8 @@ const navSections: Array<{ label: string; items: NavItem[] }> = [
       { id: 'userPassword', label: '重置密码', icon: KeyRound },
       { id: 'userBan', label: '封禁用户', icon: ShieldAlert },
       { id: 'userPurchase', label: '购买权限', icon: ShoppingBag },
+      { id: 'userSaves', label: '存档版本', icon: History },
+      { id: 'userImpersonate', label: '代登录', icon: UserRound },
     ],
   },
   {
@@ -226,12 +391,18 @@ const navSections: Array<{ label: string; items: NavItem[] }> = [
     items: [
       { id: 'scoreImages', label: '成绩图', icon: Images },
       { id: 'scoreDelete', label: '删除成绩', icon: Trash2 },
+      { id: 'chartAnalytics', label: '谱面分析', icon: ChartSpline },
+      { id: 'anomalies', label: '异常审核', icon: ShieldAlert },
+      { id: 'scoreAudits', label: '成绩审计', icon: FileWarning },
+      { id: 'replays', label: '成绩回放', icon: Film },
     ],
   },
   {
     label: '奖励',
     items: [
+      { id: 'presents', label: '奖励列表', icon: Gift },
       { id: 'presentCreate', label: '新增奖励', icon: Plus },
+      { id: 'presentPublish', label: '新增并分发', icon: PackagePlus },
       { id: 'presentDeliver', label: '分发奖励', icon: PackagePlus },
       { id: 'presentDelete', label: '删除奖励', icon: Trash2 },
     ],
@@ -241,12 +412,23 @@ const navSections: Array<{ label: string; items: NavItem[] }> = [
     items: [
       { id: 'redeemCreate', label: '新增兑换码', icon: Plus },
       { id: 'redeemDelete', label: '删除兑换码', icon: Trash2 },
+      { id: 'redeemBatches', label: '兑换码批次', icon: Boxes },
+    ],
+  },
+  {
+    label: '活动',
+    items: [
+      { id: 'events', label: '活动管理', icon: CalendarClock },
+      { id: 'eventLadder', label: '活动排行', icon: Trophy },
     ],
   },
   {
     label: '数据表',
     items: [
       { id: 'songs', label: '歌曲', icon: Music2 },
+      { id: 'chartMismatches', label: '曲目对账', icon: FileWarning },
+      { id: 'songUpload', label: '上传歌曲', icon: FileMusic },
+      { id: 'bundles', label: '内容包', icon: PackagePlus },
       { id: 'items', label: '物品', icon: Boxes },
       { id: 'purchases', label: '购买项', icon: ShoppingBag },
       { id: 'purchaseItems', label: '购买物品', icon: Link2 },
@@ -256,8 +438,23 @@ const navSections: Array<{ label: string; items: NavItem[] }> = [
     label: '维护',
     items: [
       { id: 'refreshSongFileCache', label: '刷新 Song Hash', icon: RefreshCcw },
+      { id: 'refreshSongFileCacheIncremental', label: '增量刷新 Hash', icon: RefreshCcw },
       { id: 'refreshContentBundleCache', label: '刷新 Bundle', icon: RefreshCcw },
       { id: 'refreshAllScoreRating', label: '重算 Rating', icon: RefreshCcw },
+      { id: 'backfillScoreV2', label: '补全 score_v2', icon: RefreshCcw },
+      { id: 'refreshChartAnalytics', label: '刷新谱面分析', icon: RefreshCcw },
+      { id: 'estimateChartConstants', label: '估算定数', icon: RefreshCcw },
+      { id: 'scanScoreAnomalies', label: '扫描异常', icon: RefreshCcw },
+      { id: 'gameConstants', label: '游戏常量', icon: Database },
+      { id: 'reloadGameConstants', label: '重载常量', icon: RefreshCcw },
+      { id: 'reloadSonglist', label: '重载 songlist', icon: RefreshCcw },
+      { id: 'syncChartsFromSonglist', label: '同步谱面', icon: RefreshCcw },
+      { id: 'purgeSongplayTokens', label: '清理游玩 Token', icon: RefreshCcw },
+      { id: 'recalculateWorldProgress', label: '重算世界进度', icon: RefreshCcw },
+      { id: 'rebuildRecent30', label: '重建 Recent 30', icon: RefreshCcw },
+      { id: 'vacuumExpiredPresents', label: '清理过期奖励', icon: RefreshCcw },
+      { id: 'exportUserData', label: '导出玩家数据', icon: RefreshCcw },
+      { id: 'reloadConfig', label: '重载配置', icon: RefreshCcw },
     ],
   },
 ]
@@ -390,6 +587,7 @@ type PresentForm = {
   itemId: string
   itemType: string
   amount: string
+  webOnly: boolean
 }
 
 const emptyPresentForm: PresentForm = {
@@ -399,6 +597,27 @@ const emptyPresentForm: PresentForm = {
   itemId: '',
   itemType: '',
   amount: '1',
+  webOnly: false,
+}
+
+type PresentPublishForm = {
+  presentId: string
+  expireTs: string
+  description: string
+  items: string
+  userIds: string
+  allUsers: boolean
+  webOnly: boolean
+}
+
+const emptyPresentPublishForm: PresentPublishForm = {
+  presentId: '',
+  expireTs: '',
+  description: '',
+  items: '',
+  userIds: '',
+  allUsers: false,
+  webOnly: false,
 }
 
 type PresentDeliverForm = UserSelectorForm & {
@@ -412,6 +631,30 @@ const emptyPresentDeliverForm: PresentDeliverForm = {
   allUsers: false,
 }
 
+type EventForm = {
+  eventId: string
+  name: string
+  description: string
+  startTs: string
+  endTs: string
+  charts: string
+  rewardTiers: string
+  communityMilestones: string
+  available: boolean
+}
+
+const emptyEventForm: EventForm = {
+  eventId: '',
+  name: '',
+  description: '',
+  startTs: '',
+  endTs: '',
+  charts: '',
+  rewardTiers: '',
+  communityMilestones: '',
+  available: true,
+}
+
 type RedeemForm = {
   code: string
   randomAmount: string
@@ -615,25 +858,43 @@ function App() {
         <main className="px-4 py-5 sm:px-6">
           {isAdmin && activeView === 'dashboard' && <DashboardView />}
           {isAdmin && isMaintenanceView(activeView) && (
-            <MaintenanceOperationView config={maintenanceOperations[activeView]} />
+            <MaintenanceOperationView
+              key={activeView}
+              config={maintenanceOperations[activeView]}
+            />
           )}
           {isAdmin && activeView === 'users' && <UsersView />}
           {activeView === 'checkin' && <CheckinView />}
           {activeView === 'playerScores' && <PlayerScoresView isAdmin={isAdmin} />}
           {activeView === 'scoreImages' && <ScoreImagesView isAdmin={isAdmin} />}
           {activeView === 'chartTop' && <ChartTopView />}
+          {isAdmin && activeView === 'chartAnalytics' && <ChartAnalyticsView />}
+          {isAdmin && activeView === 'anomalies' && <AnomaliesView />}
+          {isAdmin && activeView === 'scoreAudits' && <ScoreAuditsView />}
+          {isAdmin && activeView === 'replays' && <ReplaysView />}
+          {isAdmin && activeView === 'requestLog' && <RequestLogView />}
+          {isAdmin && activeView === 'chartMismatches' && <ChartMismatchesView />}
+          {isAdmin && activeView === 'songUpload' && <SongUploadView />}
+          {isAdmin && activeView === 'bundles' && <BundlesView />}
           {isAdmin && activeView === 'userTicket' && <UserTicketView />}
           {isAdmin && activeView === 'userPassword' && <UserPasswordView />}
           {isAdmin && activeView === 'userCreate' && <UserCreateView />}
           {isAdmin && activeView === 'userBan' && <UserBanView />}
           {isAdmin && activeView === 'userPurchase' && <UserPurchaseView />}
+          {isAdmin && activeView === 'userSaves' && <UserSavesView />}
+          {isAdmin && activeView === 'userImpersonate' && <UserImpersonateView />}
           {isAdmin && activeView === 'scoreDelete' && <ScoreDeleteView />}
+          {isAdmin && activeView === 'presents' && <PresentsView />}
           {isAdmin && activeView === 'presentCreate' && <PresentCreateView />}
+          {isAdmin && activeView === 'presentPublish' && <PresentPublishView />}
           {isAdmin && activeView === 'presentDeliver' && <PresentDeliverView />}
           {isAdmin && activeView === 'presentDelete' && <PresentDeleteView />}
           {isAdmin && activeView === 'redeemCreate' && <RedeemCreateView />}
           {isAdmin && activeView === 'redeemDelete' && <RedeemDeleteView />}
           {isAdmin && activeView === 'redeemUsers' && <RedeemUsersView />}
+          {isAdmin && activeView === 'redeemBatches' && <RedeemBatchesView />}
+          {isAdmin && activeView === 'events' && <EventsView />}
+          {isAdmin && activeView === 'eventLadder' && <EventLadderView />}
           {activeView === 'songs' && (
             <SongsView
               isAdmin={isAdmin}
@@ -643,6 +904,7 @@ function App() {
           {activeView === 'items' && <ItemsView isAdmin={isAdmin} />}
           {activeView === 'purchases' && <PurchasesView isAdmin={isAdmin} />}
           {isAdmin && activeView === 'purchaseItems' && <PurchaseItemsView />}
+          {isAdmin && activeView === 'gameConstants' && <GameConstantsView />}
         </main>
       </div>
     </div>
@@ -863,6 +1125,18 @@ function DashboardView() {
         />
       </div>
 
+      {data.songFileRefresh && (
+        <Card>
+          <CardHeader>
+            <CardTitle>歌曲文件哈希</CardTitle>
+            <CardDescription>
+              {data.songFileRefresh.refreshedAt} 刷新：重新计算 {data.songFileRefresh.hashed}
+              ，未变化 {data.songFileRefresh.unchanged}，移除 {data.songFileRefresh.removed}
+            </CardDescription>
+          </CardHeader>
+        </Card>
+      )}
+
       <Card>
         <CardHeader className="flex-row items-center justify-between">
           <div>
@@ -903,6 +1177,208 @@ function DashboardView() {
   )
 }
 
+function UserDevicesPanel({ userId }: { userId: number }) {
+  const [devices, setDevices] = useState<AdminUserDevices>()
+  const [error, setError] = useState('')
+
+  useEffect(() => {
+    adminApi
+      .userDevices({ user_id: userId })
+      .then(setDevices)
+      .catch((reason) => setError(errorMessage(reason)))
+  }, [userId])
+
+  if (error) {
+    return <div className="text-sm text-destructive">{error}</div>
+  }
+  if (!devices) {
+    return <LoaderCircle className="size-4 animate-spin text-muted-foreground" />
+  }
+
+  return (
+    <div className="grid gap-3 rounded-md border bg-background p-3 text-sm">
+      <div className="grid gap-1">
+        <div className="font-medium">设备记录</div>
+        {devices.devices.length === 0 ? (
+          <div className="text-muted-foreground">没有设备记录</div>
+        ) : (
+          devices.devices.map((device) => (
+            <div
+              key={`${device.kind}:${device.value}`}
+              className="flex flex-wrap items-center gap-2 text-muted-foreground"
+            >
+              <Badge variant="outline">{device.kind}</Badge>
+              <span className="font-mono text-foreground">{device.value}</span>
+              <span>{device.deviceModel || '-'}</span>
+              <span>
+                {device.firstSeen} ~ {device.lastSeen}
+              </span>
+              <span>{device.lastIp || '-'}</span>
+              <span>{device.seenCount} 次</span>
+            </div>
+          ))
+        )}
+      </div>
+      <div className="grid gap-1">
+        <div className="font-medium">共用设备的账号</div>
+        {devices.linkedAccounts.length === 0 ? (
+          <div className="text-muted-foreground">没有关联账号</div>
+        ) : (
+          devices.linkedAccounts.map((account) => (
+            <div key={account.userId} className="flex flex-wrap items-center gap-2">
+              <span className="font-mono">{account.userId}</span>
+              <span className="font-medium">{account.name || '-'}</span>
+              <span className="text-muted-foreground">{account.userCode || '-'}</span>
+              <Badge variant={account.banned ? 'destructive' : 'secondary'}>
+                {account.banned ? '封禁' : '正常'}
+              </Badge>
+              <span className="font-mono text-xs text-muted-foreground">
+                {account.shared.join(', ')}
+              </span>
+              <span className="text-muted-foreground">{account.lastSeen}</span>
+            </div>
+          ))
+        )}
+      </div>
+    </div>
+  )
+}
+
+function UserPurchasesPanel({ userId }: { userId: number }) {
+  const [history, setHistory] = useState<AdminUserPurchases>()
+  const [error, setError] = useState('')
+
+  useEffect(() => {
+    adminApi
+      .userPurchases({ user_id: userId })
+      .then(setHistory)
+      .catch((reason) => setError(errorMessage(reason)))
+  }, [userId])
+
+  if (error) {
+    return <div className="text-sm text-destructive">{error}</div>
+  }
+  if (!history) {
+    return <LoaderCircle className="size-4 animate-spin text-muted-foreground" />
+  }
+
+  return (
+    <div className="grid gap-1 rounded-md border bg-background p-3 text-sm">
+      <div className="font-medium">购买记录</div>
+      {history.purchases.length === 0 ? (
+        <div className="text-muted-foreground">没有购买记录</div>
+      ) : (
+        history.purchases.map((purchase, index) => (
+          <div
+            key={`${purchase.time}:${purchase.purchaseName}:${index}`}
+            className="flex flex-wrap items-center gap-2 text-muted-foreground"
+          >
+            <Badge variant="outline">{purchase.kind}</Badge>
+            <span className="font-mono text-foreground">{purchase.purchaseName}</span>
+            <span>
+              {purchase.price} {purchase.currency}
+            </span>
+            <span>{purchase.time}</span>
+          </div>
+        ))
+      )}
+    </div>
+  )
+}
+
+function formatBytes(bytes: number) {
+  if (bytes < 1024 * 1024) {
+    return `${(bytes / 1024).toFixed(1)} KiB`
+  }
+  if (bytes < 1024 * 1024 * 1024) {
+    return `${(bytes / 1024 / 1024).toFixed(1)} MiB`
+  }
+  return `${(bytes / 1024 / 1024 / 1024).toFixed(2)} GiB`
+}
+
+function UserDownloadQuotaPanel({ userId }: { userId: number }) {
+  const [quota, setQuota] = useState<AdminUserDownloadQuota>()
+  const [timesLimit, setTimesLimit] = useState('')
+  const [bytesLimit, setBytesLimit] = useState('')
+  const [error, setError] = useState('')
+  const [saving, setSaving] = useState(false)
+
+  const show = useCallback((value: AdminUserDownloadQuota) => {
+    setQuota(value)
+    setTimesLimit(value.isOverride ? String(value.timesLimit) : '')
+    setBytesLimit(value.isOverride ? String(value.bytesLimit) : '')
+  }, [])
+
+  useEffect(() => {
+    adminApi
+      .userDownloadQuota({ user_id: userId })
+      .then(show)
+      .catch((reason) => setError(errorMessage(reason)))
+  }, [userId, show])
+
+  function update(reset: boolean) {
+    setSaving(true)
+    setError('')
+    adminApi
+      .updateUserDownloadQuota({
+        user_id: userId,
+        reset,
+        times_limit: timesLimit.trim() ? Number(timesLimit) : null,
+        bytes_limit: bytesLimit.trim() ? Number(bytesLimit) : null,
+      })
+      .then(show)
+      .catch((reason) => setError(errorMessage(reason)))
+      .finally(() => setSaving(false))
+  }
+
+  if (!quota) {
+    return error ? (
+      <div className="text-sm text-destructive">{error}</div>
+    ) : (
+      <LoaderCircle className="size-4 animate-spin text-muted-foreground" />
+    )
+  }
+
+  return (
+    <div className="grid gap-2 rounded-md border bg-background p-3 text-sm">
+      <div className="font-medium">24 小时下载额度</div>
+      <div className="flex flex-wrap items-center gap-2 text-muted-foreground">
+        <span>
+          文件 {quota.downloadCount} / {quota.timesLimit || '不限'}
+        </span>
+        <span>
+          流量 {formatBytes(quota.downloadBytes)} /{' '}
+          {quota.bytesLimit ? formatBytes(quota.bytesLimit) : '不限'}
+        </span>
+        {quota.isOverride && <Badge variant="secondary">单独限额</Badge>}
+      </div>
+      <div className="flex flex-wrap items-center gap-2">
+        <Input
+          className="w-36"
+          inputMode="numeric"
+          placeholder="文件数上限"
+          value={timesLimit}
+          onChange={(event) => setTimesLimit(event.target.value)}
+        />
+        <Input
+          className="w-44"
+          inputMode="numeric"
+          placeholder="字节数上限"
+          value={bytesLimit}
+          onChange={(event) => setBytesLimit(event.target.value)}
+        />
+        <Button size="sm" variant="outline" disabled={saving} onClick={() => update(false)}>
+          保存限额
+        </Button>
+        <Button size="sm" variant="outline" disabled={saving} onClick={() => update(true)}>
+          重置用量
+        </Button>
+      </div>
+      {error && <div className="text-destructive">{error}</div>}
+    </div>
+  )
+}
+
 function UsersView() {
   const [query, setQuery] = useState('')
   const [status, setStatus] = useState('')
@@ -910,6 +1386,8 @@ function UsersView() {
   const [state, setState] = useState<LoadState>('loading')
   const [action, setAction] = useState<ActionState>(emptyAction)
   const [updatingUserId, setUpdatingUserId] = useState<number>()
+  const [devicesUserId, setDevicesUserId] = useState<number>()
+  const [purchasesUserId, setPurchasesUserId] = useState<number>()
   const pagination = useServerPagination(rows, defaultTablePageSize)
   const { setMeta } = pagination
 
@@ -931,6 +1409,28 @@ function UsersView() {
     load(true, 1, pagination.pageSize)
   }
 
+  async function toggleShadowBan(row: UserRow) {
+    const enabled = !row.shadowBanned
+    const prompt = enabled
+      ? `影子封禁 ${row.name || row.userId}？该玩家仍可正常游玩，但成绩不会出现在排行榜中，也不会被匹配到连线对局。`
+      : `解除 ${row.name || row.userId} 的影子封禁？`
+    if (!confirm(prompt)) {
+      return
+    }
+
+    setUpdatingUserId(row.userId)
+    setAction(emptyAction)
+    try {
+      const result = await adminApi.setShadowBan(row.userId, enabled)
+      setAction({ kind: 'success', message: result.message })
+      load(false)
+    } catch (error) {
+      setAction({ kind: 'error', message: errorMessage(error) })
+    } finally {
+      setUpdatingUserId(undefined)
+    }
+  }
+
   async function toggleChartEditor(row: UserRow) {
     if (row.isAdmin) {
       return
@@ -982,6 +1482,7 @@ function UsersView() {
           <option value="">全部状态</option>
           <option value="normal">正常</option>
           <option value="banned">封禁</option>
+          <option value="shadow">影子封禁</option>
         </select>
       }
     >
@@ -1002,51 +1503,111 @@ function UsersView() {
                 <TableHead>Ticket</TableHead>
                 <TableHead>最近游玩</TableHead>
                 <TableHead>状态</TableHead>
+                <TableHead>影子封禁</TableHead>
                 <TableHead>曲目定数权限</TableHead>
+                <TableHead>记录</TableHead>
               </TableRow>
             </TableHeader>
             <TableBody>
               {visibleRows.map((row) => (
-                <TableRow key={row.userId}>
-                  <TableCell className="font-mono">{row.userId}</TableCell>
-                  <TableCell className="font-medium">{row.name || '-'}</TableCell>
-                  <TableCell>{row.userCode || '-'}</TableCell>
-                  <TableCell>{(row.ratingPtt / 100).toFixed(2)}</TableCell>
-                  <TableCell>{row.ticket}</TableCell>
-                  <TableCell>{row.lastPlay}</TableCell>
-                  <TableCell>
-                    <Badge variant={row.banned ? 'destructive' : 'secondary'}>
-                      {row.banned ? '封禁' : '正常'}
-                    </Badge>
-                  </TableCell>
-                  <TableCell className="whitespace-nowrap">
-                    <div className="flex items-center gap-2">
-                      <Badge
-                        variant={row.canEditChartConstants ? 'secondary' : 'outline'}
-                      >
-                        {row.canEditChartConstants ? '已授权' : '未授权'}
+                <Fragment key={row.userId}>
+                  <TableRow>
+                    <TableCell className="font-mono">{row.userId}</TableCell>
+                    <TableCell className="font-medium">{row.name || '-'}</TableCell>
+                    <TableCell>{row.userCode || '-'}</TableCell>
+                    <TableCell>{(row.ratingPtt / 100).toFixed(2)}</TableCell>
+                    <TableCell>{row.ticket}</TableCell>
+                    <TableCell>{row.lastPlay}</TableCell>
+                    <TableCell>
+                      <Badge variant={row.banned ? 'destructive' : 'secondary'}>
+                        {row.banned ? '封禁' : '正常'}
                       </Badge>
+                    </TableCell>
+                    <TableCell>
                       <Button
                         type="button"
                         size="sm"
-                        variant="outline"
-                        disabled={row.isAdmin || updatingUserId === row.userId}
-                        onClick={() => toggleChartEditor(row)}
+                        variant={row.shadowBanned ? 'destructive' : 'outline'}
+                        disabled={updatingUserId === row.userId}
+                        onClick={() => toggleShadowBan(row)}
                       >
-                        {updatingUserId === row.userId ? (
-                          <LoaderCircle className="animate-spin" />
-                        ) : (
-                          <ShieldCheck />
-                        )}
-                        {row.isAdmin
-                          ? '管理员默认拥有'
-                          : row.canEditChartConstants
-                            ? '撤销'
-                            : '授予'}
+                        <ShieldAlert />
+                        {row.shadowBanned ? '已影子封禁' : '未启用'}
                       </Button>
-                    </div>
-                  </TableCell>
-                </TableRow>
+                    </TableCell>
+                    <TableCell className="whitespace-nowrap">
+                      <div className="flex items-center gap-2">
+                        <Badge
+                          variant={row.canEditChartConstants ? 'secondary' : 'outline'}
+                        >
+                          {row.canEditChartConstants ? '已授权' : '未授权'}
+                        </Badge>
+                        <Button
+                          type="button"
+                          size="sm"
+                          variant="outline"
+                          disabled={row.isAdmin || updatingUserId === row.userId}
+                          onClick={() => toggleChartEditor(row)}
+                        >
+                          {updatingUserId === row.userId ? (
+                            <LoaderCircle className="animate-spin" />
+                          ) : (
+                            <ShieldCheck />
+                          )}
+                          {row.isAdmin
+                            ? '管理员默认拥有'
+                            : row.canEditChartConstants
+                              ? '撤销'
+                              : '授予'}
+                        </Button>
+                      </div>
+                    </TableCell>
+                    <TableCell>
+                      <Button
+                        type="button"
+                        size="sm"
+                        variant={devicesUserId === row.userId ? 'secondary' : 'outline'}
+                        onClick={() =>
+                          setDevicesUserId(devicesUserId === row.userId ? undefined : row.userId)
+                        }
+                      >
+                        <Link2 />
+                        设备
+                      </Button>
+                      <Button
+                        type="button"
+                        size="sm"
+                        variant={purchasesUserId === row.userId ? 'secondary' : 'outline'}
+                        className="ml-2"
+                        onClick={() =>
+                          setPurchasesUserId(
+                            purchasesUserId === row.userId ? undefined : row.userId,
+                          )
+                        }
+                      >
+                        <ShoppingBag />
+                        购买
+                      </Button>
+                    </TableCell>
+                  </TableRow>
+                  {devicesUserId === row.userId && (
+                    <TableRow className="bg-muted/40 hover:bg-muted/40">
+                      <TableCell colSpan={10} className="p-3">
+                        <UserDevicesPanel userId={row.userId} />
+                      </TableCell>
+                    </TableRow>
+                  )}
+                  {purchasesUserId === row.userId && (
+                    <TableRow className="bg-muted/40 hover:bg-muted/40">
+                      <TableCell colSpan={10} className="p-3">
+                        <div className="grid gap-3">
+                          <UserPurchasesPanel userId={row.userId} />
+                          <UserDownloadQuotaPanel userId={row.userId} />
+                        </div>
+                      </TableCell>
+                    </TableRow>
+                  )}
+                </Fragment>
               ))}
             </TableBody>
           </Table>
@@ -1063,15 +1624,26 @@ function MaintenanceOperationView({
 }) {
   const [action, setAction] = useState<ActionState>(emptyAction)
   const [loading, setLoading] = useState(false)
+  const [userId, setUserId] = useState('')
+  const [job, setJob] = useState<OperationJob>()
 
-  async function runOperation() {
+  async function start() {
+    const parsedUserId = userId.trim() ? Number(userId) : undefined
+    if (config.userId === 'required' && parsedUserId === undefined) {
+      setAction({ kind: 'error', message: '请输入玩家 ID' })
+      return
+    }
+    if (parsedUserId !== undefined && !Number.isInteger(parsedUserId)) {
+      setAction({ kind: 'error', message: '玩家 ID 必须是整数' })
+      return
+    }
     if (config.confirmText && !confirm(config.confirmText)) {
       return
     }
     setLoading(true)
     setAction(emptyAction)
     try {
-      await adminApi.operation(config.operation)
+      await runOperation(config.operation, { userId: parsedUserId }, setJob)
       setAction({ kind: 'success', message: '操作已完成' })
     } catch (error) {
       setAction({ kind: 'error', message: errorMessage(error) })
@@ -1083,18 +1655,42 @@ function MaintenanceOperationView({
   return (
     <ActionCard title={config.title} description={config.description}>
       <div className="flex flex-wrap items-center gap-2">
+        {config.userId && (
+          <Input
+            className="w-40"
+            inputMode="numeric"
+            value={userId}
+            onChange={(event) => setUserId(event.target.value)}
+            placeholder={config.userId === 'required' ? '玩家 ID' : '玩家 ID（可选）'}
+          />
+        )}
         <Button
           type="button"
           size="sm"
           variant="outline"
           disabled={loading}
-          onClick={runOperation}
+          onClick={start}
         >
           {loading ? <LoaderCircle className="animate-spin" /> : <RefreshCcw />}
           {config.buttonLabel}
         </Button>
         <ActionMessage action={action} />
       </div>
+      {loading && job && (
+        <div className="flex items-center gap-3 text-sm text-muted-foreground">
+          <div className="h-2 w-64 overflow-hidden rounded-full bg-muted">
+            <div
+              className={cn(
+                'h-full bg-primary transition-all',
+                job.progress === null && 'w-1/3 animate-pulse',
+              )}
+              style={job.progress === null ? undefined : { width: `${job.progress * 100}%` }}
+            />
+          </div>
+          {job.progress === null ? '运行中' : `${Math.round(job.progress * 100)}%`}
+          <span className="font-mono text-xs">开始于 {job.startedAt}</span>
+        </div>
+      )}
     </ActionCard>
   )
 }
@@ -1260,7 +1856,14 @@ function ActionCard({
 function PlayerScoresView({ isAdmin }: { isAdmin: boolean }) {
   const [form, setForm] = useState({ ...emptyUserSelectorForm })
   const [scores, setScores] = useState<AdminUserScores>()
+  const [history, setHistory] = useState<PttHistory>()
+  const [historySelector, setHistorySelector] = useState<UserSelectorPayload>(
+    {},
+  )
+  const [diffRange, setDiffRange] = useState({ from: '', to: '' })
+  const [diff, setDiff] = useState<Recent30Diff>()
   const [action, setAction] = useState<ActionState>(emptyAction)
+  const [diffAction, setDiffAction] = useState<ActionState>(emptyAction)
   const [loading, setLoading] = useState(false)
   const best30Average = scores?.b30.length
     ? scores.stats.best30Sum / scores.b30.length
@@ -1274,10 +1877,23 @@ function PlayerScoresView({ isAdmin }: { isAdmin: boolean }) {
     setLoading(true)
     setAction(emptyAction)
     try {
-      const result = await adminApi.userScores({
-        ...(isAdmin ? buildUserSelectorPayload(form) : {}),
-      })
+      const selector: UserSelectorPayload = isAdmin
+        ? buildUserSelectorPayload(form)
+        : {}
+      const [result, points] = await Promise.all([
+        adminApi.userScores(selector),
+        adminApi.userPttHistory({ ...selector, days: 90 }),
+      ])
       setScores(result)
+      setHistory(points)
+      setHistorySelector(selector)
+      setDiff(undefined)
+      setDiffAction(emptyAction)
+      const days = points.points.map((point) => String(point.day))
+      setDiffRange({
+        from: days.length > 1 ? days[days.length - 2] : (days[0] ?? ''),
+        to: days[days.length - 1] ?? '',
+      })
       setAction({
         kind: 'success',
         message: `${result.user.name || result.user.userId} · B30 ${result.b30.length} · R10 ${result.r10.length}`,
@@ -1289,6 +1905,28 @@ function PlayerScoresView({ isAdmin }: { isAdmin: boolean }) {
     }
   }
 
+  async function onDiff() {
+    if (!diffRange.from || !diffRange.to) {
+      return
+    }
+    setDiffAction(emptyAction)
+    try {
+      const result = await adminApi.userRecent30Diff(
+        historySelector,
+        Number(diffRange.from),
+        Number(diffRange.to),
+      )
+      setDiff(result)
+      setDiffAction({
+        kind: 'success',
+        message: `+${result.added.length} · -${result.removed.length} · =${result.unchanged}`,
+      })
+    } catch (error) {
+      setDiff(undefined)
+      setDiffAction({ kind: 'error', message: errorMessage(error) })
+    }
+  }
+
   return (
     <ActionCard
       title="玩家成绩"
@@ -1328,10 +1966,186 @@ function PlayerScoresView({ isAdmin }: { isAdmin: boolean }) {
           </div>
         </div>
       )}
+      {history && (
+        <div className="mt-4 grid gap-3">
+          <div className="text-sm font-medium">潜力值历史 · 90 天</div>
+          <PttHistoryChart points={history.points} />
+          {history.points.length > 0 && (
+            <div className="flex flex-wrap items-center gap-2">
+              {(['from', 'to'] as const).map((key) => (
+                <select
+                  key={key}
+                  className="h-9 rounded-md border bg-background px-3 text-sm"
+                  value={diffRange[key]}
+                  onChange={(event) =>
+                    setDiffRange({ ...diffRange, [key]: event.target.value })
+                  }
+                >
+                  {history.points.map((point) => (
+                    <option key={point.day} value={String(point.day)}>
+                      {point.date}
+                    </option>
+                  ))}
+                </select>
+              ))}
+              <Button type="button" variant="outline" size="sm" onClick={onDiff}>
+                对比 Recent 30
+              </Button>
+              <ActionMessage action={diffAction} />
+            </div>
+          )}
+          {diff && (
+            <div className="grid gap-3 xl:grid-cols-2">
+              <Recent30Section
+                title={`新增 · ${diff.to.date}`}
+                entries={diff.added}
+              />
+              <Recent30Section
+                title={`移出 · ${diff.from.date}`}
+                entries={diff.removed}
+              />
+            </div>
+          )}
+        </div>
+      )}
     </ActionCard>
   )
 }
 
+function PttHistoryChart({ points }: { points: PttHistoryPoint[] }) {
+  if (points.length === 0) {
+    return (
+      <div className="flex min-h-28 items-center justify-center rounded-md border border-dashed text-sm text-muted-foreground">
+        暂无快照
+      </div>
+    )
+  }
+
+  const width = 640
+  const height = 160
+  const padding = 8
+  const values = points.map((point) => point.potential)
+  const min = Math.min(...values)
+  const max = Math.max(...values)
+  const span = max - min || 1
+  const x = (index: number) =>
+    points.length === 1
+      ? width / 2
+      : padding + (index * (width - padding * 2)) / (points.length - 1)
+  const y = (value: number) =>
+    height - padding - ((value - min) * (height - padding * 2)) / span
+  const path = points
+    .map((point, index) => `${index ? 'L' : 'M'}${x(index)},${y(point.potential)}`)
+    .join(' ')
+
+  return (
+    <div className="grid gap-1 rounded-md border p-3">
+      <div className="flex justify-between font-mono text-xs text-muted-foreground">
+        <span>
+          {points[0].date} · {points[0].potential.toFixed(2)}
+        </span>
+        <span>
+          {min.toFixed(2)} – {max.toFixed(2)}
+        </span>
+        <span>
+          {points[points.length - 1].date} ·{' '}
+          {points[points.length - 1].potential.toFixed(2)}
+        </span>
+      </div>
+      <svg
+        className="h-40 w-full"
+        viewBox={`0 0 ${width} ${height}`}
+        preserveAspectRatio="none"
+      >
+        <path
+          d={path}
+          fill="none"
+          stroke="currentColor"
+          strokeWidth={2}
+          vectorEffect="non-scaling-stroke"
+        />
+        {points.map((point, index) => (
+          <circle
+            key={point.day}
+            cx={x(index)}
+            cy={y(point.potential)}
+            r={3}
+            className="fill-primary"
+          >
+            <title>
+              {point.date} · PTT {point.potential.toFixed(4)} · B30{' '}
+              {point.best30.toFixed(4)} · R10 {point.recent10.toFixed(4)}
+            </title>
+          </circle>
+        ))}
+      </svg>
+    </div>
+  )
+}
+
+function Recent30Section({
+  title,
+  entries,
+}: {
+  title: string
+  entries: Recent30Entry[]
+}) {
+  return (
+    <div className="grid gap-2">
+      <div className="flex items-center justify-between gap-2">
+        <div className="text-sm font-medium">{title}</div>
+        <div className="font-mono text-xs text-muted-foreground">
+          {entries.length}
+        </div>
+      </div>
+      {entries.length === 0 ? (
+        <div className="flex min-h-16 items-center justify-center rounded-md border border-dashed text-sm text-muted-foreground">
+          无变化
+        </div>
+      ) : (
+        <div className="overflow-x-auto rounded-md border">
+          <Table className="w-full text-xs">
+            <TableHeader>
+              <TableRow>
+                <TableHead className="px-1.5">Song</TableHead>
+                <TableHead className="px-1.5">Diff</TableHead>
+                <TableHead className="px-1.5">Score</TableHead>
+                <TableHead className="px-1.5">Clear</TableHead>
+                <TableHead className="px-1.5">Rating</TableHead>
+                <TableHead className="px-1.5">Time</TableHead>
+              </TableRow>
+            </TableHeader>
+            <TableBody>
+              {entries.map((entry) => (
+                <TableRow
+                  key={`${entry.songId}:${entry.difficulty}:${entry.timePlayed}`}
+                >
+                  <TableCell className="px-1.5 py-2 font-mono">
+                    {entry.songId}
+                  </TableCell>
+                  <TableCell className="whitespace-nowrap px-1.5 py-2">
+                    {difficultyLabel(entry.difficulty)}
+                  </TableCell>
+                  <TableCell className="whitespace-nowrap px-1.5 py-2 font-mono">
+                    {entry.score.toLocaleString()}
+                  </TableCell>
+                  <TableCell className="px-1.5 py-2">{entry.clearType}</TableCell>
+                  <TableCell className="whitespace-nowrap px-1.5 py-2 font-mono">
+                    {entry.rating.toFixed(4)}
+                  </TableCell>
+                  <TableCell className="break-all px-1.5 py-2 font-mono">
+                    {entry.timePlayed}
+                  </TableCell>
+                </TableRow>
+              ))}
+            </TableBody>
+          </Table>
+        </div>
+      )}
+    </div>
+  )
+}
+
 function ScoreImagesView({ isAdmin }: { isAdmin: boolean }) {
   const [form, setForm] = useState({ ...emptyUserSelectorForm })
   const [result, setResult] = useState<ScoreImages>()
@@ -1894,6 +2708,233 @@ function UserBanView() {
   )
 }
 
+function UserSavesView() {
+  const [form, setForm] = useState<UserSelectorForm>(emptyUserSelectorForm)
+  const [saves, setSaves] = useState<AdminUserSaves>()
+  const [action, setAction] = useState<ActionState>(emptyAction)
+  const [loading, setLoading] = useState(false)
+
+  async function load(payload: UserSelectorPayload) {
+    const result = await adminApi.userSaves(payload)
+    setSaves(result)
+    return result
+  }
+
+  async function onSubmit(event: FormEvent) {
+    event.preventDefault()
+    setLoading(true)
+    setAction(emptyAction)
+    try {
+      const result = await load(buildUserSelectorPayload(form))
+      setAction({
+        kind: 'success',
+        message: `${result.user.name} · ${result.versions.length} 个历史版本`,
+      })
+    } catch (error) {
+      setAction({ kind: 'error', message: errorMessage(error) })
+    } finally {
+      setLoading(false)
+    }
+  }
+
+  async function rollback(historyId: number) {
+    if (!saves || !confirm(`将存档回滚到版本 ${historyId}?`)) {
+      return
+    }
+    setLoading(true)
+    setAction(emptyAction)
+    try {
+      const selector = { user_id: saves.user.userId }
+      const result = await adminApi.rollbackUserSave({
+        ...selector,
+        history_id: historyId,
+      })
+      await load(selector)
+      setAction({ kind: 'success', message: formatActionResult(result) })
+    } catch (error) {
+      setAction({ kind: 'error', message: errorMessage(error) })
+    } finally {
+      setLoading(false)
+    }
+  }
+
+  return (
+    <ActionCard title="存档版本" description="user_save_history">
+      <form className="grid gap-3" onSubmit={onSubmit}>
+        <UserSelectorFields
+          value={form}
+          onChange={(value) => setForm({ ...form, ...value })}
+        />
+        <div className="flex flex-wrap items-center gap-2">
+          <Button type="submit" size="sm" variant="outline" disabled={loading}>
+            {loading ? <LoaderCircle className="animate-spin" /> : <Search />}
+            查询
+          </Button>
+          <ActionMessage action={action} />
+        </div>
+      </form>
+      {saves && (
+        <p className="text-sm text-muted-foreground">
+          当前存档: {saves.currentCreatedAt ?? '无'}
+        </p>
+      )}
+      {saves && saves.versions.length > 0 && (
+        <div className="overflow-auto rounded-md border">
+          <Table>
+            <TableHeader>
+              <TableRow>
+                <TableHead>版本</TableHead>
+                <TableHead>上传时间</TableHead>
+                <TableHead>归档时间</TableHead>
+                <TableHead className="text-right">大小</TableHead>
+                <TableHead />
+              </TableRow>
+            </TableHeader>
+            <TableBody>
+              {saves.versions.map((version) => (
+                <TableRow key={version.historyId}>
+                  <TableCell className="font-mono">{version.historyId}</TableCell>
+                  <TableCell>{version.createdAt}</TableCell>
+                  <TableCell>{version.archivedAt}</TableCell>
+                  <TableCell className="text-right font-mono">
+                    {version.dataSize.toLocaleString()}
+                  </TableCell>
+                  <TableCell className="text-right">
+                    <Button
+                      size="sm"
+                      variant="outline"
+                      disabled={loading}
+                      onClick={() => rollback(version.historyId)}
+                    >
+                      <History />
+                      回滚
+                    </Button>
+                  </TableCell>
+                </TableRow>
+              ))}
+            </TableBody>
+          </Table>
+        </div>
+      )}
+    </ActionCard>
+  )
+}
+
+function UserImpersonateView() {
+  const [form, setForm] = useState<UserSelectorForm>(emptyUserSelectorForm)
+  const [reason, setReason] = useState('')
+  const [issued, setIssued] = useState<AdminImpersonationToken>()
+  const [log, setLog] = useState<ImpersonationLogRow[]>([])
+  const [action, setAction] = useState<ActionState>(emptyAction)
+  const [loading, setLoading] = useState(false)
+
+  function loadLog() {
+    adminApi
+      .impersonationLog({ page: 1, pageSize: defaultTablePageSize })
+      .then((value) => setLog(value.rows))
+      .catch(() => setLog([]))
+  }
+
+  useEffect(() => {
+    loadLog()
+  }, [])
+
+  async function onSubmit(event: FormEvent) {
+    event.preventDefault()
+    setAction(emptyAction)
+    if (!confirm('签发的 token 可以直接以该玩家身份访问游戏 API，继续?')) {
+      return
+    }
+    setLoading(true)
+    try {
+      const result = await adminApi.impersonateUser({
+        ...buildUserSelectorPayload(form),
+        reason,
+      })
+      setIssued(result)
+      setReason('')
+      setAction({ kind: 'success', message: `已为 ${result.user.name} 签发 token` })
+      loadLog()
+    } catch (error) {
+      setAction({ kind: 'error', message: errorMessage(error) })
+    } finally {
+      setLoading(false)
+    }
+  }
+
+  return (
+    <ActionCard title="代登录" description="impersonation_token">
+      <form className="grid gap-3" onSubmit={onSubmit}>
+        <UserSelectorFields
+          value={form}
+          onChange={(value) => setForm({ ...form, ...value })}
+        />
+        <div className="flex flex-wrap items-center gap-2">
+          <Input
+            className="w-96"
+            value={reason}
+            onChange={(event) => setReason(event.target.value)}
+            placeholder="原因（记入审计日志）"
+            maxLength={255}
+            required
+          />
+          <Button type="submit" size="sm" disabled={loading}>
+            {loading ? <LoaderCircle className="animate-spin" /> : <KeyRound />}
+            签发
+          </Button>
+          <ActionMessage action={action} />
+        </div>
+      </form>
+      {issued && (
+        <div className="grid gap-1 rounded-md border p-3 text-sm">
+          <div>
+            {issued.user.name} ({issued.user.userId}) · 有效期至 {issued.expiresAt}
+          </div>
+          <code className="select-all break-all font-mono text-xs">{issued.token}</code>
+        </div>
+      )}
+      {log.length > 0 && (
+        <div className="overflow-auto rounded-md border">
+          <Table>
+            <TableHeader>
+              <TableRow>
+                <TableHead>玩家</TableHead>
+                <TableHead>签发人</TableHead>
+                <TableHead>原因</TableHead>
+                <TableHead>签发时间</TableHead>
+                <TableHead>到期时间</TableHead>
+                <TableHead>IP</TableHead>
+              </TableRow>
+            </TableHeader>
+            <TableBody>
+              {log.map((row) => (
+                <TableRow key={row.tokenId}>
+                  <TableCell>
+                    <div>{row.name || '-'}</div>
+                    <div className="font-mono text-xs text-muted-foreground">{row.userId}</div>
+                  </TableCell>
+                  <TableCell>{row.adminName || row.adminUserId}</TableCell>
+                  <TableCell>{row.reason}</TableCell>
+                  <TableCell>{row.createdAt}</TableCell>
+                  <TableCell>
+                    {row.expiresAt}
+                    {row.active && (
+                      <Badge className="ml-2" variant="secondary">
+                        有效
+                      </Badge>
+                    )}
+                  </TableCell>
+                  <TableCell className="font-mono text-xs">{row.ip ?? '-'}</TableCell>
+                </TableRow>
+              ))}
+            </TableBody>
+          </Table>
+        </div>
+      )}
+    </ActionCard>
+  )
+}
+
 function UserPurchaseView() {
   const [form, setForm] = useState<UserPurchaseForm>(emptyUserPurchaseForm)
   const [action, setAction] = useState<ActionState>(emptyAction)
@@ -2034,6 +3075,109 @@ function ScoreDeleteView() {
   )
 }
 
+function PresentsView() {
+  const [query, setQuery] = useState('')
+  const [sort, setSort] = useState<PresentSort>('latest')
+  const [rows, setRows] = useState<PresentRow[]>([])
+  const [state, setState] = useState<LoadState>('loading')
+  const pagination = useServerPagination(rows, defaultTablePageSize)
+  const { setMeta } = pagination
+
+  function load(
+    showLoading = true,
+    page = pagination.page,
+    pageSize = pagination.pageSize,
+    order = sort,
+  ) {
+    if (showLoading) {
+      setState('loading')
+    }
+    adminApi
+      .presents({ q: query, sort: order, page, pageSize })
+      .then((value) => {
+        setRows(value.rows)
+        setMeta(value)
+        setState('ready')
+      })
+      .catch(() => setState('error'))
+  }
+
+  useEffect(() => {
+    adminApi
+      .presents({ page: 1, pageSize: defaultTablePageSize })
+      .then((value) => {
+        setRows(value.rows)
+        setMeta(value)
+        setState('ready')
+      })
+      .catch(() => setState('error'))
+  }, [setMeta])
+
+  return (
+    <DataPanel
+      title="奖励列表"
+      description="present"
+      state={state}
+      onSearch={() => load(true, 1)}
+      searchValue={query}
+      onSearchChange={setQuery}
+    >
+      <div className="mb-3 flex flex-wrap items-center gap-2">
+        <select
+          className="h-9 rounded-md border bg-background px-3 text-sm"
+          value={sort}
+          onChange={(event) => {
+            const next = event.target.value as PresentSort
+            setSort(next)
+            load(true, 1, pagination.pageSize, next)
+          }}
+        >
+          <option value="latest">按过期时间（新到旧）</option>
+          <option value="expire">按过期时间（旧到新）</option>
+          <option value="present_id">按 present_id</option>
+        </select>
+      </div>
+      <TableBlock
+        pagination={pagination}
+        onPageChange={(page) => load(true, page, pagination.pageSize)}
+        onPageSizeChange={(pageSize) => load(true, 1, pageSize)}
+        emptyText="没有奖励"
+        renderTable={(visibleRows) => (
+          <Table>
+            <TableHeader>
+              <TableRow>
+                <TableHead>present_id</TableHead>
+                <TableHead>说明</TableHead>
+                <TableHead>物品</TableHead>
+                <TableHead>过期时间</TableHead>
+                <TableHead className="text-right">已分发</TableHead>
+              </TableRow>
+            </TableHeader>
+            <TableBody>
+              {visibleRows.map((row) => (
+                <TableRow key={row.presentId}>
+                  <TableCell className="font-mono">
+                    {row.presentId}
+                    {row.webOnly && (
+                      <Badge className="ml-2" variant="secondary">
+                        仅网页
+                      </Badge>
+                    )}
+                  </TableCell>
+                  <TableCell>{row.description || '-'}</TableCell>
+                  <TableCell className="text-xs text-muted-foreground">{row.itemSummary}</TableCell>
+                  <TableCell>{row.expireAt}</TableCell>
+                  <TableCell className="text-right font-mono">{row.recipients}</TableCell>
+                </TableRow>
+              ))}
+            </TableBody>
+          </Table>
+        )}
+      />
+    </DataPanel>
+  )
+}
+
 function PresentCreateView() {
   const [form, setForm] = useState<PresentForm>(emptyPresentForm)
   const [action, setAction] = useState<ActionState>(emptyAction)
@@ -2051,6 +3195,7 @@ function PresentCreateView() {
         item_id: requireTrimmed(form.itemId, 'item_id'),
         item_type: requireTrimmed(form.itemType, 'type'),
         amount: form.amount,
+        web_only: form.webOnly,
       }
       const result = await adminApi.createPresent(payload)
       setForm(emptyPresentForm)
@@ -2103,6 +3248,11 @@ function PresentCreateView() {
           />
         </div>
         <div className="flex flex-wrap items-center gap-2">
+          <ToggleLabel
+            checked={form.webOnly}
+            onChange={(webOnly) => setForm({ ...form, webOnly })}
+            label="仅网页领取"
+          />
           <Button type="submit" size="sm" disabled={loading}>
             {loading ? <LoaderCircle className="animate-spin" /> : <Plus />}
             新增奖励
@@ -2114,8 +3264,39 @@ function PresentCreateView() {
   )
 }
 
-function PresentDeliverView() {
-  const [form, setForm] = useState<PresentDeliverForm>(emptyPresentDeliverForm)
+function parsePresentItems(raw: string): PresentPublishPayload['items'] {
+  return raw
+    .split('\n')
+    .map((line) => line.trim())
+    .filter(Boolean)
+    .map((line) => {
+      const [itemType, itemId, amount] = line.split(':').map((part) => part.trim())
+      if (!itemType || !itemId) {
+        throw new Error(`物品格式错误: ${line}`)
+      }
+      return {
+        item_type: itemType,
+        item_id: itemId,
+        amount: amount ? Number(amount) : undefined,
+      }
+    })
+}
+
+function parseUserIds(raw: string): number[] {
+  return raw
+    .split(/[\s,]+/)
+    .filter(Boolean)
+    .map((value) => {
+      const userId = Number(value)
+      if (!Number.isInteger(userId)) {
+        throw new Error(`user_id 格式错误: ${value}`)
+      }
+      return userId
+    })
+}
+
+function PresentPublishView() {
+  const [form, setForm] = useState<PresentPublishForm>(emptyPresentPublishForm)
   const [action, setAction] = useState<ActionState>(emptyAction)
   const [loading, setLoading] = useState(false)
 
@@ -2123,16 +3304,21 @@ function PresentDeliverView() {
     event.preventDefault()
     setAction(emptyAction)
     try {
-      const payload: PresentDeliverPayload = {
-        ...(form.allUsers ? {} : buildUserSelectorPayload(form)),
+      const payload: PresentPublishPayload = {
         present_id: requireTrimmed(form.presentId, 'present_id'),
+        expire_ts: requireTrimmed(form.expireTs, 'expire_ts'),
+        description: form.description.trim(),
+        items: parsePresentItems(form.items),
+        user_ids: form.allUsers ? [] : parseUserIds(form.userIds),
         all_users: form.allUsers,
+        web_only: form.webOnly,
       }
-      if (!confirm(`分发奖励 ${payload.present_id}?`)) {
+      if (!confirm(`新增并分发奖励 ${payload.present_id}?`)) {
         return
       }
       setLoading(true)
-      const result = await adminApi.deliverPresent(payload)
+      const result = await adminApi.publishPresent(payload)
+      setForm(emptyPresentPublishForm)
       setAction({ kind: 'success', message: formatActionResult(result) })
     } catch (error) {
       setAction({ kind: 'error', message: errorMessage(error) })
@@ -2142,44 +3328,131 @@ function PresentDeliverView() {
   }
 
   return (
-    <ActionCard title="分发奖励" description="deliverpresent">
+    <ActionCard title="新增并分发奖励" description="多物品奖励，创建后立即分发">
       <form className="grid gap-3" onSubmit={onSubmit}>
-        <ToggleLabel
-          checked={form.allUsers}
-          onChange={(checked) => setForm({ ...form, allUsers: checked })}
-          label="全部用户"
-        />
-        <Input
-          value={form.presentId}
-          onChange={(event) => setForm({ ...form, presentId: event.target.value })}
-          placeholder="present_id"
-          required
-        />
-        <UserSelectorFields
-          value={form}
-          disabled={form.allUsers}
-          onChange={(value) => setForm({ ...form, ...value })}
-        />
-        <div className="flex flex-wrap items-center gap-2">
-          <Button type="submit" size="sm" disabled={loading}>
-            {loading ? <LoaderCircle className="animate-spin" /> : <PackagePlus />}
-            分发
-          </Button>
-          <ActionMessage action={action} />
-        </div>
-      </form>
-    </ActionCard>
-  )
-}
-
-function PresentDeleteView() {
-  const [presentId, setPresentId] = useState('')
-  const [action, setAction] = useState<ActionState>(emptyAction)
-  const [loading, setLoading] = useState(false)
-
-  async function onSubmit(event: FormEvent) {
-    event.preventDefault()
-    setAction(emptyAction)
+        <div className="grid gap-3 lg:grid-cols-3">
+          <Input
+            value={form.presentId}
+            onChange={(event) => setForm({ ...form, presentId: event.target.value })}
+            placeholder="present_id"
+            required
+          />
+          <Input
+            type="datetime-local"
+            value={form.expireTs}
+            onChange={(event) => setForm({ ...form, expireTs: event.target.value })}
+            required
+          />
+          <Input
+            value={form.description}
+            onChange={(event) => setForm({ ...form, description: event.target.value })}
+            placeholder="description"
+          />
+        </div>
+        <div className="grid gap-3 lg:grid-cols-2">
+          <textarea
+            className={textareaClass}
+            value={form.items}
+            onChange={(event) => setForm({ ...form, items: event.target.value })}
+            placeholder={'type:item_id[:amount]，每行一个\nfragment:fragment:200\ncore:core_generic:5'}
+            required
+          />
+          <textarea
+            className={textareaClass}
+            value={form.userIds}
+            disabled={form.allUsers}
+            onChange={(event) => setForm({ ...form, userIds: event.target.value })}
+            placeholder="user_id，逗号或换行分隔"
+          />
+        </div>
+        <div className="flex flex-wrap items-center gap-2">
+          <ToggleLabel
+            checked={form.allUsers}
+            onChange={(allUsers) => setForm({ ...form, allUsers })}
+            label="全部用户"
+          />
+          <ToggleLabel
+            checked={form.webOnly}
+            onChange={(webOnly) => setForm({ ...form, webOnly })}
+            label="仅网页领取"
+          />
+          <Button type="submit" size="sm" disabled={loading}>
+            {loading ? <LoaderCircle className="animate-spin" /> : <PackagePlus />}
+            新增并分发
+          </Button>
+          <ActionMessage action={action} />
+        </div>
+      </form>
+    </ActionCard>
+  )
+}
+
+function PresentDeliverView() {
+  const [form, setForm] = useState<PresentDeliverForm>(emptyPresentDeliverForm)
+  const [action, setAction] = useState<ActionState>(emptyAction)
+  const [loading, setLoading] = useState(false)
+
+  async function onSubmit(event: FormEvent) {
+    event.preventDefault()
+    setAction(emptyAction)
+    try {
+      const payload: PresentDeliverPayload = {
+        ...(form.allUsers ? {} : buildUserSelectorPayload(form)),
+        present_id: requireTrimmed(form.presentId, 'present_id'),
+        all_users: form.allUsers,
+      }
+      if (!confirm(`分发奖励 ${payload.present_id}?`)) {
+        return
+      }
+      setLoading(true)
+      const result = await adminApi.deliverPresent(payload)
+      setAction({ kind: 'success', message: formatActionResult(result) })
+    } catch (error) {
+      setAction({ kind: 'error', message: errorMessage(error) })
+    } finally {
+      setLoading(false)
+    }
+  }
+
+  return (
+    <ActionCard title="分发奖励" description="deliverpresent">
+      <form className="grid gap-3" onSubmit={onSubmit}>
+        <ToggleLabel
+          checked={form.allUsers}
+          onChange={(checked) => setForm({ ...form, allUsers: checked })}
+          label="全部用户"
+        />
+        <Input
+          value={form.presentId}
+          onChange={(event) => setForm({ ...form, presentId: event.target.value })}
+          placeholder="present_id"
+          required
+        />
+        <UserSelectorFields
+          value={form}
+          disabled={form.allUsers}
+          onChange={(value) => setForm({ ...form, ...value })}
+        />
+        <div className="flex flex-wrap items-center gap-2">
+          <Button type="submit" size="sm" disabled={loading}>
+            {loading ? <LoaderCircle className="animate-spin" /> : <PackagePlus />}
+            分发
+          </Button>
+          <ActionMessage action={action} />
+        </div>
+      </form>
+    </ActionCard>
+  )
+}
+
+function PresentDeleteView() {
+  const [presentId, setPresentId] = useState('')
+  const [action, setAction] = useState<ActionState>(emptyAction)
+  const [loading, setLoading] = useState(false)
+
+  async function onSubmit(event: FormEvent) {
+    event.preventDefault()
+    setAction(emptyAction)
     try {
       const value = requireTrimmed(presentId, 'present_id')
       if (!confirm(`删除奖励 ${value}?`)) {
@@ -2264,6 +3537,7 @@ function RedeemCreateView() {
           >
             <option value="0">全局一次</option>
             <option value="1">每用户一次</option>
+            <option value="2">碎片（每用户一次）</option>
           </select>
           <Input
             value={form.itemId}
@@ -2321,86 +3595,1721 @@ function RedeemDeleteView() {
   }
 
   return (
-    <ActionCard title="删除兑换码" description="changeredeem/deleteredeem">
-      <form className="flex flex-col gap-3 sm:flex-row" onSubmit={onSubmit}>
-        <Input
-          value={code}
-          onChange={(event) => setCode(event.target.value)}
-          placeholder="code"
-          required
-        />
-        <Button type="submit" size="sm" variant="destructive" disabled={loading}>
-          {loading ? <LoaderCircle className="animate-spin" /> : <Trash2 />}
-          删除
-        </Button>
-      </form>
-      <ActionMessage action={action} />
-    </ActionCard>
+    <ActionCard title="删除兑换码" description="changeredeem/deleteredeem">
+      <form className="flex flex-col gap-3 sm:flex-row" onSubmit={onSubmit}>
+        <Input
+          value={code}
+          onChange={(event) => setCode(event.target.value)}
+          placeholder="code"
+          required
+        />
+        <Button type="submit" size="sm" variant="destructive" disabled={loading}>
+          {loading ? <LoaderCircle className="animate-spin" /> : <Trash2 />}
+          删除
+        </Button>
+      </form>
+      <ActionMessage action={action} />
+    </ActionCard>
+  )
+}
+
+function RedeemUsersView() {
+  const [code, setCode] = useState('')
+  const [users, setUsers] = useState<AdminUserSummary[]>([])
+  const [action, setAction] = useState<ActionState>(emptyAction)
+  const [loading, setLoading] = useState(false)
+
+  async function onSubmit(event: FormEvent) {
+    event.preventDefault()
+    setLoading(true)
+    setAction(emptyAction)
+    try {
+      const result = await adminApi.redeemUsers(requireTrimmed(code, 'code'))
+      setUsers(result.users)
+      setAction({
+        kind: 'success',
+        message: `${result.code} · ${result.users.length} 个用户`,
+      })
+    } catch (error) {
+      setAction({ kind: 'error', message: errorMessage(error) })
+    } finally {
+      setLoading(false)
+    }
+  }
+
+  return (
+    <ActionCard title="兑换使用者" description="redeem/<code>">
+      <form className="flex flex-col gap-3 sm:flex-row" onSubmit={onSubmit}>
+        <Input
+          value={code}
+          onChange={(event) => setCode(event.target.value)}
+          placeholder="code"
+          required
+        />
+        <Button type="submit" size="sm" variant="outline" disabled={loading}>
+          {loading ? <LoaderCircle className="animate-spin" /> : <Search />}
+          查询
+        </Button>
+      </form>
+      <ActionMessage action={action} />
+      {users.length > 0 && (
+        <div className="max-h-64 overflow-auto rounded-md border">
+          <Table>
+            <TableHeader>
+              <TableRow>
+                <TableHead>ID</TableHead>
+                <TableHead>Name</TableHead>
+                <TableHead>User Code</TableHead>
+              </TableRow>
+            </TableHeader>
+            <TableBody>
+              {users.map((user) => (
+                <TableRow key={user.userId}>
+                  <TableCell className="font-mono">{user.userId}</TableCell>
+                  <TableCell>{user.name || '-'}</TableCell>
+                  <TableCell>{user.userCode || '-'}</TableCell>
+                </TableRow>
+              ))}
+            </TableBody>
+          </Table>
+        </div>
+      )}
+    </ActionCard>
+  )
+}
+
+type RedeemBatchForm = {
+  batchId: string
+  description: string
+  redeemType: string
+  count: string
+  items: string
+}
+
+const emptyRedeemBatchForm: RedeemBatchForm = {
+  batchId: '',
+  description: '',
+  redeemType: '0',
+  count: '',
+  items: '',
+}
+
+const redeemTypeLabels: Record<number, string> = {
+  0: '全局一次',
+  1: '每用户一次',
+  2: '碎片',
+}
+
+function GameConstantsView() {
+  const [constants, setConstants] = useState<GameConstant[]>()
+  const [state, setState] = useState<LoadState>('loading')
+  const [drafts, setDrafts] = useState<Record<string, string>>({})
+  const [action, setAction] = useState<ActionState>(emptyAction)
+
+  const load = useCallback(() => {
+    setState('loading')
+    adminApi
+      .gameConstants()
+      .then((value) => {
+        setConstants(value)
+        setDrafts({})
+        setState('ready')
+      })
+      .catch(() => setState('error'))
+  }, [])
+
+  useEffect(() => {
+    load()
+  }, [load])
+
+  async function save(constant: GameConstant, reset: boolean) {
+    setAction(emptyAction)
+    try {
+      let value: number | null = null
+      if (!reset) {
+        value = Number((drafts[constant.name] ?? '').trim())
+        if (!Number.isFinite(value)) {
+          throw new Error(`${constant.name} 必须是数字`)
+        }
+      }
+      const result = await adminApi.setGameConstant(constant.name, value)
+      setAction({ kind: 'success', message: formatActionResult(result) })
+      load()
+    } catch (error) {
+      setAction({ kind: 'error', message: errorMessage(error) })
+    }
+  }
+
+  if (!constants) {
+    return <LoadPanel state={state} onRetry={load} />
+  }
+
+  return (
+    <ActionCard
+      title="游戏常量"
+      description="覆盖值写入 game_constants 表，其他实例在下次定时重载后生效"
+    >
+      <div className="grid gap-3">
+        <ActionMessage action={action} />
+        <div className="overflow-auto rounded-md border">
+          <Table>
+            <TableHeader>
+              <TableRow>
+                <TableHead>名称</TableHead>
+                <TableHead className="text-right">当前值</TableHead>
+                <TableHead className="text-right">默认值</TableHead>
+                <TableHead>覆盖值</TableHead>
+                <TableHead />
+              </TableRow>
+            </TableHeader>
+            <TableBody>
+              {constants.map((constant) => (
+                <TableRow key={constant.name}>
+                  <TableCell className="font-mono">{constant.name}</TableCell>
+                  <TableCell className="text-right font-mono">{constant.value}</TableCell>
+                  <TableCell className="text-right font-mono">{constant.defaultValue}</TableCell>
+                  <TableCell>
+                    <Input
+                      className="w-40"
+                      value={drafts[constant.name] ?? constant.overrideValue?.toString() ?? ''}
+                      onChange={(event) =>
+                        setDrafts({ ...drafts, [constant.name]: event.target.value })
+                      }
+                      placeholder="默认"
+                    />
+                  </TableCell>
+                  <TableCell className="flex justify-end gap-2">
+                    <Button
+                      type="button"
+                      size="sm"
+                      disabled={drafts[constant.name] === undefined}
+                      onClick={() => save(constant, false)}
+                    >
+                      保存
+                    </Button>
+                    <Button
+                      type="button"
+                      size="sm"
+                      variant="outline"
+                      disabled={constant.overrideValue === null}
+                      onClick={() => save(constant, true)}
+                    >
+                      恢复默认
+                    </Button>
+                  </TableCell>
+                </TableRow>
+              ))}
+            </TableBody>
+          </Table>
+        </div>
+      </div>
+    </ActionCard>
+  )
+}
+
+function RedeemBatchesView() {
+  const [batches, setBatches] = useState<AdminRedeemBatch[]>()
+  const [state, setState] = useState<LoadState>('loading')
+  const [form, setForm] = useState<RedeemBatchForm>(emptyRedeemBatchForm)
+  const [action, setAction] = useState<ActionState>(emptyAction)
+  const [loading, setLoading] = useState(false)
+
+  const load = useCallback(() => {
+    setState('loading')
+    adminApi
+      .redeemBatches()
+      .then((value) => {
+        setBatches(value)
+        setState('ready')
+      })
+      .catch(() => setState('error'))
+  }, [])
+
+  useEffect(() => {
+    load()
+  }, [load])
+
+  async function onSubmit(event: FormEvent) {
+    event.preventDefault()
+    setLoading(true)
+    setAction(emptyAction)
+    try {
+      const payload: RedeemBatchPayload = {
+        batch_id: requireTrimmed(form.batchId, 'batch_id'),
+        description: form.description.trim(),
+        redeem_type: parseRequiredInt(form.redeemType, 'redeem_type'),
+        count: parseRequiredInt(form.count, 'count'),
+        items: parsePresentItems(form.items),
+      }
+      const result = await adminApi.createRedeemBatch(payload)
+      setForm(emptyRedeemBatchForm)
+      setAction({ kind: 'success', message: formatActionResult(result) })
+      load()
+    } catch (error) {
+      setAction({ kind: 'error', message: errorMessage(error) })
+    } finally {
+      setLoading(false)
+    }
+  }
+
+  async function toggle(batch: AdminRedeemBatch) {
+    const disabled = !batch.disabled
+    if (disabled && !confirm(`停用兑换码批次 ${batch.batchId}?`)) {
+      return
+    }
+    setAction(emptyAction)
+    try {
+      const result = await adminApi.setRedeemBatchDisabled(batch.batchId, disabled)
+      setAction({ kind: 'success', message: formatActionResult(result) })
+      load()
+    } catch (error) {
+      setAction({ kind: 'error', message: errorMessage(error) })
+    }
+  }
+
+  return (
+    <div className="grid gap-5">
+      <ActionCard title="生成兑换码批次" description="同一批次的兑换码发放相同物品">
+        <form className="grid gap-3" onSubmit={onSubmit}>
+          <div className="grid gap-3 lg:grid-cols-4">
+            <Input
+              value={form.batchId}
+              onChange={(event) => setForm({ ...form, batchId: event.target.value })}
+              placeholder="batch_id"
+              required
+            />
+            <Input
+              value={form.description}
+              onChange={(event) => setForm({ ...form, description: event.target.value })}
+              placeholder="description"
+            />
+            <select
+              className="h-9 rounded-md border bg-background px-3 text-sm"
+              value={form.redeemType}
+              onChange={(event) => setForm({ ...form, redeemType: event.target.value })}
+            >
+              <option value="0">全局一次</option>
+              <option value="1">每用户一次</option>
+              <option value="2">碎片（每用户一次）</option>
+            </select>
+            <Input
+              value={form.count}
+              onChange={(event) => setForm({ ...form, count: event.target.value })}
+              placeholder="count"
+              required
+            />
+          </div>
+          <textarea
+            className={textareaClass}
+            value={form.items}
+            onChange={(event) => setForm({ ...form, items: event.target.value })}
+            placeholder={'type:item_id[:amount]，每行一个\nfragment:fragment:200\ncore:core_generic:5'}
+            required
+          />
+          <div className="flex flex-wrap items-center gap-2">
+            <Button type="submit" size="sm" disabled={loading}>
+              {loading ? <LoaderCircle className="animate-spin" /> : <Plus />}
+              生成
+            </Button>
+            <ActionMessage action={action} />
+          </div>
+        </form>
+      </ActionCard>
+      {!batches ? (
+        <LoadPanel state={state} onRetry={load} />
+      ) : (
+        <ActionCard title="批次列表" description="停用后该批次所有兑换码均无法使用">
+          <div className="overflow-auto rounded-md border">
+            <Table>
+              <TableHeader>
+                <TableRow>
+                  <TableHead>ID</TableHead>
+                  <TableHead>说明</TableHead>
+                  <TableHead>类型</TableHead>
+                  <TableHead>物品</TableHead>
+                  <TableHead className="text-right">已兑换</TableHead>
+                  <TableHead>创建时间</TableHead>
+                  <TableHead>状态</TableHead>
+                  <TableHead />
+                </TableRow>
+              </TableHeader>
+              <TableBody>
+                {batches.map((batch) => (
+                  <TableRow key={batch.batchId}>
+                    <TableCell className="font-mono">{batch.batchId}</TableCell>
+                    <TableCell>{batch.description || '-'}</TableCell>
+                    <TableCell>{redeemTypeLabels[batch.redeemType] ?? batch.redeemType}</TableCell>
+                    <TableCell className="font-mono">
+                      {batch.items
+                        .map((item) => `${item.itemType}:${item.itemId}:${item.amount}`)
+                        .join(', ')}
+                    </TableCell>
+                    <TableCell className="text-right font-mono">
+                      {batch.redeemedCount} / {batch.codeCount}
+                    </TableCell>
+                    <TableCell>{batch.createdAt}</TableCell>
+                    <TableCell>
+                      <Badge variant={batch.disabled ? 'outline' : 'default'}>
+                        {batch.disabled ? '停用' : '启用'}
+                      </Badge>
+                    </TableCell>
+                    <TableCell className="flex justify-end gap-2">
+                      <Button asChild size="sm" variant="outline">
+                        <a href={adminApi.redeemBatchExportUrl(batch.batchId)}>
+                          <Download />
+                          导出
+                        </a>
+                      </Button>
+                      <Button
+                        type="button"
+                        size="sm"
+                        variant="outline"
+                        onClick={() => toggle(batch)}
+                      >
+                        {batch.disabled ? '启用' : '停用'}
+                      </Button>
+                    </TableCell>
+                  </TableRow>
+                ))}
+              </TableBody>
+            </Table>
+          </div>
+        </ActionCard>
+      )}
+    </div>
+  )
+}
+
+function EventsView() {
+  const [events, setEvents] = useState<AdminEvent[]>()
+  const [state, setState] = useState<LoadState>('loading')
+  const [form, setForm] = useState<EventForm>(emptyEventForm)
+  const [action, setAction] = useState<ActionState>(emptyAction)
+  const [loading, setLoading] = useState(false)
+
+  const load = useCallback(() => {
+    setState('loading')
+    adminApi
+      .events()
+      .then((value) => {
+        setEvents(value)
+        setState('ready')
+      })
+      .catch(() => setState('error'))
+  }, [])
+
+  useEffect(() => {
+    load()
+  }, [load])
+
+  function edit(event: AdminEvent) {
+    setForm({
+      eventId: event.eventId,
+      name: event.name,
+      description: event.description,
+      startTs: toDatetimeLocal(event.startTs),
+      endTs: toDatetimeLocal(event.endTs),
+      charts: event.charts,
+      rewardTiers: event.rewardTiers,
+      communityMilestones: event.communityMilestones,
+      available: event.available,
+    })
+    setAction(emptyAction)
+  }
+
+  async function onSubmit(event: FormEvent) {
+    event.preventDefault()
+    setLoading(true)
+    setAction(emptyAction)
+    try {
+      const payload: EventPayload = {
+        event_id: requireTrimmed(form.eventId, 'event_id'),
+        name: requireTrimmed(form.name, 'name'),
+        description: form.description.trim(),
+        start_ts: requireTrimmed(form.startTs, 'start_ts'),
+        end_ts: requireTrimmed(form.endTs, 'end_ts'),
+        charts: requireTrimmed(form.charts, 'charts'),
+        reward_tiers: form.rewardTiers,
+        community_milestones: form.communityMilestones,
+        available: form.available,
+      }
+      const result = await adminApi.saveEvent(payload)
+      setForm(emptyEventForm)
+      setAction({ kind: 'success', message: formatActionResult(result) })
+      load()
+    } catch (error) {
+      setAction({ kind: 'error', message: errorMessage(error) })
+    } finally {
+      setLoading(false)
+    }
+  }
+
+  async function remove(eventId: string) {
+    if (!confirm(`删除活动 ${eventId}? 玩家积分会一并删除`)) {
+      return
+    }
+    setAction(emptyAction)
+    try {
+      const result = await adminApi.deleteEvent(eventId)
+      setAction({ kind: 'success', message: formatActionResult(result) })
+      load()
+    } catch (error) {
+      setAction({ kind: 'error', message: errorMessage(error) })
+    }
+  }
+
+  return (
+    <div className="grid gap-5">
+      <ActionCard title="保存活动" description="相同 event_id 会覆盖原有配置">
+        <form className="grid gap-3" onSubmit={onSubmit}>
+          <div className="grid gap-3 lg:grid-cols-3">
+            <Input
+              value={form.eventId}
+              onChange={(event) => setForm({ ...form, eventId: event.target.value })}
+              placeholder="event_id"
+              required
+            />
+            <Input
+              value={form.name}
+              onChange={(event) => setForm({ ...form, name: event.target.value })}
+              placeholder="name"
+              required
+            />
+            <Input
+              value={form.description}
+              onChange={(event) => setForm({ ...form, description: event.target.value })}
+              placeholder="description"
+            />
+            <Input
+              type="datetime-local"
+              value={form.startTs}
+              onChange={(event) => setForm({ ...form, startTs: event.target.value })}
+              required
+            />
+            <Input
+              type="datetime-local"
+              value={form.endTs}
+              onChange={(event) => setForm({ ...form, endTs: event.target.value })}
+              required
+            />
+          </div>
+          <div className="grid gap-3 lg:grid-cols-2">
+            <textarea
+              className={textareaClass}
+              value={form.charts}
+              onChange={(event) => setForm({ ...form, charts: event.target.value })}
+              placeholder={'song_id:difficulty[:倍率]，每行一个\ngrievouslady:2:2'}
+              required
+            />
+            <textarea
+              className={textareaClass}
+              value={form.rewardTiers}
+              onChange={(event) => setForm({ ...form, rewardTiers: event.target.value })}
+              placeholder={'积分:type[:item_id]:amount，每行一个\n500:fragment:200\n2000:core:core_generic:5'}
+            />
+            <textarea
+              className={textareaClass}
+              value={form.communityMilestones}
+              onChange={(event) =>
+                setForm({ ...form, communityMilestones: event.target.value })
+              }
+              placeholder={
+                '社区目标 fragments|clears:目标值:type[:item_id]:amount，每行一个\nclears:10000:fragment:300\nfragments:500000:core:core_generic:3'
+              }
+            />
+          </div>
+          <div className="flex flex-wrap items-center gap-2">
+            <ToggleLabel
+              checked={form.available}
+              onChange={(available) => setForm({ ...form, available })}
+              label="启用"
+            />
+            <Button type="submit" size="sm" disabled={loading}>
+              {loading ? <LoaderCircle className="animate-spin" /> : <Plus />}
+              保存活动
+            </Button>
+            <Button
+              type="button"
+              size="sm"
+              variant="outline"
+              onClick={() => setForm(emptyEventForm)}
+            >
+              <X />
+              清空
+            </Button>
+            <ActionMessage action={action} />
+          </div>
+        </form>
+      </ActionCard>
+      {!events ? (
+        <LoadPanel state={state} onRetry={load} />
+      ) : (
+        <ActionCard title="活动列表" description="每次成功提交活动谱面的成绩都会累计积分">
+          <div className="overflow-auto rounded-md border">
+            <Table>
+              <TableHeader>
+                <TableRow>
+                  <TableHead>ID</TableHead>
+                  <TableHead>名称</TableHead>
+                  <TableHead>开始</TableHead>
+                  <TableHead>结束</TableHead>
+                  <TableHead>状态</TableHead>
+                  <TableHead className="text-right">参与人数</TableHead>
+                  <TableHead className="text-right">社区进度</TableHead>
+                  <TableHead />
+                </TableRow>
+              </TableHeader>
+              <TableBody>
+                {events.map((event) => (
+                  <TableRow key={event.eventId}>
+                    <TableCell className="font-mono">{event.eventId}</TableCell>
+                    <TableCell>{event.name}</TableCell>
+                    <TableCell>{event.startTime}</TableCell>
+                    <TableCell>{event.endTime}</TableCell>
+                    <TableCell>
+                      <Badge variant={event.available ? 'default' : 'outline'}>
+                        {event.available ? '启用' : '停用'}
+                      </Badge>
+                    </TableCell>
+                    <TableCell className="text-right font-mono">
+                      {event.participantCount}
+                    </TableCell>
+                    <TableCell className="text-right font-mono">
+                      {event.communityFragments} 进度 / {event.communityClears} 通关
+                    </TableCell>
+                    <TableCell className="flex justify-end gap-2">
+                      <Button
+                        type="button"
+                        size="sm"
+                        variant="outline"
+                        onClick={() => edit(event)}
+                      >
+                        <Pencil />
+                        编辑
+                      </Button>
+                      <Button
+                        type="button"
+                        size="sm"
+                        variant="destructive"
+                        onClick={() => remove(event.eventId)}
+                      >
+                        <Trash2 />
+                        删除
+                      </Button>
+                    </TableCell>
+                  </TableRow>
+                ))}
+              </TableBody>
+            </Table>
+          </div>
+        </ActionCard>
+      )}
+    </div>
+  )
+}
+
+function EventLadderView() {
+  const [form, setForm] = useState({ eventId: '', limit: '50' })
+  const [ladder, setLadder] = useState<AdminEventLadder>()
+  const [action, setAction] = useState<ActionState>(emptyAction)
+  const [loading, setLoading] = useState(false)
+
+  async function onSubmit(event: FormEvent) {
+    event.preventDefault()
+    setLoading(true)
+    setAction(emptyAction)
+    try {
+      const result = await adminApi.eventLadder({
+        event_id: requireTrimmed(form.eventId, 'event_id'),
+        limit: parseOptionalPositiveInt(form.limit, 'limit'),
+      })
+      setLadder(result)
+      setAction({
+        kind: 'success',
+        message: `${result.eventId} · ${result.entries.length} 名玩家`,
+      })
+    } catch (error) {
+      setAction({ kind: 'error', message: errorMessage(error) })
+    } finally {
+      setLoading(false)
+    }
+  }
+
+  return (
+    <ActionCard title="活动排行" description="event/<event_id>/ladder">
+      <form className="flex flex-col gap-3 sm:flex-row" onSubmit={onSubmit}>
+        <Input
+          value={form.eventId}
+          onChange={(event) => setForm({ ...form, eventId: event.target.value })}
+          placeholder="event_id"
+          required
+        />
+        <Input
+          className="sm:w-28"
+          value={form.limit}
+          onChange={(event) => setForm({ ...form, limit: event.target.value })}
+          placeholder="limit"
+        />
+        <Button type="submit" size="sm" variant="outline" disabled={loading}>
+          {loading ? <LoaderCircle className="animate-spin" /> : <Search />}
+          查询
+        </Button>
+      </form>
+      <ActionMessage action={action} />
+      {ladder && ladder.entries.length > 0 && (
+        <div className="overflow-auto rounded-md border">
+          <Table>
+            <TableHeader>
+              <TableRow>
+                <TableHead>#</TableHead>
+                <TableHead>ID</TableHead>
+                <TableHead>Name</TableHead>
+                <TableHead className="text-right">积分</TableHead>
+              </TableRow>
+            </TableHeader>
+            <TableBody>
+              {ladder.entries.map((entry) => (
+                <TableRow key={entry.userId}>
+                  <TableCell className="font-mono">{entry.rank}</TableCell>
+                  <TableCell className="font-mono">{entry.userId}</TableCell>
+                  <TableCell>{entry.name || '-'}</TableCell>
+                  <TableCell className="text-right font-mono">
+                    {entry.points.toLocaleString()}
+                  </TableCell>
+                </TableRow>
+              ))}
+            </TableBody>
+          </Table>
+        </div>
+      )}
+    </ActionCard>
+  )
+}
+
+const scoreBucketLabels = ['<9.5M', '9.5M', '9.8M', '9.9M', 'PM+']
+
+function ChartAnalyticsView() {
+  const [query, setQuery] = useState('')
+  const [sort, setSort] = useState<ChartAnalyticsSort>('deviation')
+  const [minPlayers, setMinPlayers] = useState('5')
+  const [rows, setRows] = useState<ChartAnalyticsRow[]>([])
+  const [state, setState] = useState<LoadState>('loading')
+  const [action, setAction] = useState<ActionState>(emptyAction)
+  const [refreshing, setRefreshing] = useState(false)
+  const pagination = useServerPagination(rows, defaultTablePageSize)
+  const { setMeta } = pagination
+
+  function load(showLoading = true, page = pagination.page, pageSize = pagination.pageSize) {
+    if (showLoading) {
+      setState('loading')
+    }
+    adminApi
+      .chartAnalytics({
+        q: query,
+        sort,
+        minPlayers: Number(minPlayers) || 1,
+        page,
+        pageSize,
+      })
+      .then((value) => {
+        setRows(value.rows)
+        setMeta(value)
+        setState('ready')
+      })
+      .catch(() => setState('error'))
+  }
+
+  function search() {
+    load(true, 1, pagination.pageSize)
+  }
+
+  async function refresh() {
+    setRefreshing(true)
+    setAction(emptyAction)
+    try {
+      await runOperation('refresh_chart_analytics')
+      setAction({ kind: 'success', message: '谱面分析已刷新' })
+      load(false)
+    } catch (error) {
+      setAction({ kind: 'error', message: errorMessage(error) })
+    } finally {
+      setRefreshing(false)
+    }
+  }
+
+  useEffect(() => {
+    adminApi
+      .chartAnalytics({ sort: 'deviation', minPlayers: 5, page: 1, pageSize: defaultTablePageSize })
+      .then((value) => {
+        setRows(value.rows)
+        setMeta(value)
+        setState('ready')
+      })
+      .catch(() => setState('error'))
+  }, [setMeta])
+
+  return (
+    <DataPanel
+      title="谱面分析"
+      description="体感定数 = 玩家潜力值减去该成绩相对定数的加成，取中位数"
+      state={state}
+      onSearch={search}
+      searchValue={query}
+      onSearchChange={setQuery}
+    >
+      <div className="mb-3 flex flex-wrap items-center gap-2">
+        <select
+          className="h-9 rounded-md border bg-background px-3 text-sm"
+          value={sort}
+          onChange={(event) => setSort(event.target.value as ChartAnalyticsSort)}
+        >
+          <option value="deviation">按定数偏差</option>
+          <option value="players">按游玩人数</option>
+          <option value="song">按歌曲</option>
+        </select>
+        <Input
+          className="w-32"
+          value={minPlayers}
+          onChange={(event) => setMinPlayers(event.target.value)}
+          placeholder="最少人数"
+        />
+        <Button type="button" size="sm" variant="outline" onClick={search}>
+          <Search />
+          应用
+        </Button>
+        <Button type="button" size="sm" variant="outline" disabled={refreshing} onClick={refresh}>
+          {refreshing ? <LoaderCircle className="animate-spin" /> : <RefreshCcw />}
+          重新统计
+        </Button>
+        <ActionMessage action={action} />
+      </div>
+      <TableBlock
+        pagination={pagination}
+        onPageChange={(page) => load(true, page, pagination.pageSize)}
+        onPageSizeChange={(pageSize) => load(true, 1, pageSize)}
+        emptyText="暂无分析数据，请先重新统计"
+        renderTable={(visibleRows) => (
+          <Table>
+            <TableHeader>
+              <TableRow>
+                <TableHead>Song</TableHead>
+                <TableHead>难度</TableHead>
+                <TableHead className="text-right">定数</TableHead>
+                <TableHead className="text-right">体感</TableHead>
+                <TableHead className="text-right">偏差</TableHead>
+                <TableHead className="text-right">人数</TableHead>
+                <TableHead className="text-right">平均分</TableHead>
+                <TableHead className="text-right">准确率</TableHead>
+                <TableHead className="text-right">通关率</TableHead>
+                <TableHead>分数分布</TableHead>
+              </TableRow>
+            </TableHeader>
+            <TableBody>
+              {visibleRows.map((row) => (
+                <TableRow key={`${row.songId}:${row.difficulty}`}>
+                  <TableCell>
+                    <div className="font-mono">{row.songId}</div>
+                    <div className="text-xs text-muted-foreground">{row.nameEn || '-'}</div>
+                  </TableCell>
+                  <TableCell>{difficultyLabel(row.difficulty)}</TableCell>
+                  <TableCell className="text-right font-mono">
+                    {row.constant?.toFixed(1) ?? '-'}
+                  </TableCell>
+                  <TableCell className="text-right font-mono">
+                    {row.perceivedConstant?.toFixed(2) ?? '-'}
+                    <div className="text-xs text-muted-foreground">n={row.sampleCount}</div>
+                  </TableCell>
+                  <TableCell
+                    className={cn(
+                      'text-right font-mono',
+                      row.deviation !== null && Math.abs(row.deviation) >= 0.5 && 'text-destructive',
+                    )}
+                  >
+                    {row.deviation === null
+                      ? '-'
+                      : `${row.deviation > 0 ? '+' : ''}${row.deviation.toFixed(2)}`}
+                  </TableCell>
+                  <TableCell className="text-right font-mono">{row.playerCount}</TableCell>
+                  <TableCell className="text-right font-mono">
+                    {row.avgScore.toLocaleString()}
+                  </TableCell>
+                  <TableCell className="text-right font-mono">{row.avgAccuracy.toFixed(2)}%</TableCell>
+                  <TableCell className="text-right font-mono">{row.clearRate.toFixed(2)}%</TableCell>
+                  <TableCell className="whitespace-nowrap text-xs text-muted-foreground">
+                    {row.scoreDistribution
+                      .map((count, index) => `${scoreBucketLabels[index]} ${count}`)
+                      .join(' · ')}
+                  </TableCell>
+                </TableRow>
+              ))}
+            </TableBody>
+          </Table>
+        )}
+      />
+    </DataPanel>
+  )
+}
+
+function ChartMismatchesView() {
+  const [report, setReport] = useState<ChartMismatchReport>()
+  const [state, setState] = useState<LoadState>('loading')
+  const [action, setAction] = useState<ActionState>(emptyAction)
+  const [checking, setChecking] = useState(false)
+  const [reloading, setReloading] = useState(false)
+
+  const load = useCallback(() => {
+    setState('loading')
+    adminApi
+      .chartMismatches()
+      .then((value) => {
+        setReport(value)
+        setState('ready')
+      })
+      .catch(() => setState('error'))
+  }, [])
+
+  useEffect(() => {
+    load()
+  }, [load])
+
+  async function check() {
+    setChecking(true)
+    setAction(emptyAction)
+    try {
+      await runOperation('reconcile_charts')
+      setAction({ kind: 'success', message: '对账完成' })
+      load()
+    } catch (error) {
+      setAction({ kind: 'error', message: errorMessage(error) })
+    } finally {
+      setChecking(false)
+    }
+  }
+
+  async function reloadSonglist() {
+    setReloading(true)
+    setAction(emptyAction)
+    try {
+      const value = await adminApi.reloadSonglist()
+      setReport(value)
+      setState('ready')
+      setAction({ kind: 'success', message: `已重载 songlist，更新定数 ${value.chartsUpdated} 首` })
+    } catch (error) {
+      setAction({ kind: 'error', message: errorMessage(error) })
+    } finally {
+      setReloading(false)
+    }
+  }
+
+  return (
+    <ActionCard
+      title="曲目对账"
+      description="songlist 中缺少歌曲表记录的曲目会自动补一条定数全为 -1 的占位记录"
+    >
+      <div className="flex flex-wrap items-center gap-2">
+        <Button type="button" size="sm" variant="outline" disabled={checking} onClick={check}>
+          {checking ? <LoaderCircle className="animate-spin" /> : <RefreshCcw />}
+          立即对账
+        </Button>
+        <Button
+          type="button"
+          size="sm"
+          variant="outline"
+          disabled={reloading}
+          onClick={reloadSonglist}
+        >
+          {reloading ? <LoaderCircle className="animate-spin" /> : <RefreshCcw />}
+          重载 songlist
+        </Button>
+        {report?.checkedAt && (
+          <span className="text-sm text-muted-foreground">上次对账：{report.checkedAt}</span>
+        )}
+        <ActionMessage action={action} />
+      </div>
+      {state !== 'ready' || !report ? (
+        <LoadPanel state={state} onRetry={load} />
+      ) : !report.hasSonglist ? (
+        <div className="text-sm text-muted-foreground">未加载 songlist，无法对账</div>
+      ) : (
+        <div className="grid gap-4 md:grid-cols-2">
+          <div className="rounded-md border bg-muted/30 p-4">
+            <div className="text-xs text-muted-foreground">
+              歌曲表缺失（已补占位 {report.placeholdersCreated}）
+            </div>
+            <div className="mt-2 font-mono text-sm">
+              {report.missingCharts.length > 0 ? report.missingCharts.join(', ') : '-'}
+            </div>
+          </div>
+          <div className="rounded-md border bg-muted/30 p-4">
+            <div className="text-xs text-muted-foreground">songlist 缺失</div>
+            <div className="mt-2 font-mono text-sm">
+              {report.unlistedCharts.length > 0 ? report.unlistedCharts.join(', ') : '-'}
+            </div>
+          </div>
+        </div>
+      )}
+    </ActionCard>
+  )
+}
+
+function SongUploadView() {
+  const [songPackage, setSongPackage] = useState<File | null>(null)
+  const [songId, setSongId] = useState('')
+  const [name, setName] = useState('')
+  const [insertChart, setInsertChart] = useState(true)
+  const [overwrite, setOverwrite] = useState(false)
+  const [result, setResult] = useState<SongUploadResult | null>(null)
+  const [action, setAction] = useState<ActionState>(emptyAction)
+  const [uploading, setUploading] = useState(false)
+  const [formKey, setFormKey] = useState(0)
+
+  async function submit(event: FormEvent<HTMLFormElement>) {
+    event.preventDefault()
+    if (!songPackage) {
+      setAction({ kind: 'error', message: '请选择歌曲 zip 文件' })
+      return
+    }
+    setUploading(true)
+    setAction(emptyAction)
+    try {
+      const value = await adminApi.uploadSong({
+        package: songPackage,
+        songId: songId.trim() || undefined,
+        name: name.trim() || undefined,
+        insertChart,
+        overwrite,
+      })
+      setResult(value)
+      setAction({
+        kind: 'success',
+        message: `已上传 ${value.songId}，共 ${value.files.length} 个文件${value.chartCreated ? '，并创建了谱面记录' : ''}`,
+      })
+      setSongPackage(null)
+      setSongId('')
+      setName('')
+      setFormKey((key) => key + 1)
+    } catch (error) {
+      setAction({ kind: 'error', message: errorMessage(error) })
+    } finally {
+      setUploading(false)
+    }
+  }
+
+  return (
+    <ActionCard
+      title="上传歌曲"
+      description="上传包含谱面 (.aff) 与 base.ogg 的歌曲文件夹 zip，解压后立即计算文件哈希，可同时创建定数为 -1 的谱面记录"
+    >
+      <form key={formKey} className="flex flex-wrap items-end gap-3" onSubmit={submit}>
+        <label className="grid gap-1 text-sm">
+          歌曲 zip
+          <Input
+            type="file"
+            accept=".zip,application/zip"
+            onChange={(event) => setSongPackage(event.target.files?.[0] ?? null)}
+          />
+        </label>
+        <label className="grid gap-1 text-sm">
+          Song ID
+          <Input
+            className="w-48"
+            value={songId}
+            placeholder="默认为 zip 内文件夹名"
+            onChange={(event) => setSongId(event.target.value)}
+          />
+        </label>
+        <label className="grid gap-1 text-sm">
+          曲名
+          <Input
+            className="w-48"
+            value={name}
+            placeholder="默认为 Song ID"
+            disabled={!insertChart}
+            onChange={(event) => setName(event.target.value)}
+          />
+        </label>
+        <ToggleLabel checked={insertChart} onChange={setInsertChart} label="创建谱面记录" />
+        <ToggleLabel checked={overwrite} onChange={setOverwrite} label="覆盖已有歌曲" />
+        <Button type="submit" size="sm" disabled={uploading}>
+          {uploading ? <LoaderCircle className="animate-spin" /> : <FileMusic />}
+          上传
+        </Button>
+        <ActionMessage action={action} />
+      </form>
+      {result && (
+        <Table>
+          <TableHeader>
+            <TableRow>
+              <TableHead>文件</TableHead>
+              <TableHead className="text-right">大小</TableHead>
+              <TableHead>MD5</TableHead>
+            </TableRow>
+          </TableHeader>
+          <TableBody>
+            {result.files.map((file) => (
+              <TableRow key={file.fileName}>
+                <TableCell className="font-mono">{file.fileName}</TableCell>
+                <TableCell className="text-right font-mono">{formatBytes(file.size)}</TableCell>
+                <TableCell className="font-mono text-xs">{file.md5}</TableCell>
+              </TableRow>
+            ))}
+          </TableBody>
+        </Table>
+      )}
+    </ActionCard>
+  )
+}
+
+function BundlesView() {
+  const [rows, setRows] = useState<BundleRow[]>([])
+  const [state, setState] = useState<LoadState>('loading')
+  const [action, setAction] = useState<ActionState>(emptyAction)
+  const [metadata, setMetadata] = useState<File | null>(null)
+  const [bundle, setBundle] = useState<File | null>(null)
+  const [uploading, setUploading] = useState(false)
+  const [formKey, setFormKey] = useState(0)
+
+  const load = useCallback(() => {
+    setState('loading')
+    adminApi
+      .bundles()
+      .then((value) => {
+        setRows(value)
+        setState('ready')
+      })
+      .catch(() => setState('error'))
+  }, [])
+
+  useEffect(() => {
+    load()
+  }, [load])
+
+  async function submit(event: FormEvent<HTMLFormElement>) {
+    event.preventDefault()
+    if (!metadata || !bundle) {
+      setAction({ kind: 'error', message: '请选择 bundle JSON 与 .cb 文件' })
+      return
+    }
+    setUploading(true)
+    setAction(emptyAction)
+    try {
+      const value = await adminApi.uploadBundle(metadata, bundle)
+      setAction({
+        kind: 'success',
+        message: `已上传 ${value.appVersion} 的内容包 ${value.prevVersion ? `${value.prevVersion} → ` : ''}${value.version}`,
+      })
+      setMetadata(null)
+      setBundle(null)
+      setFormKey((key) => key + 1)
+      load()
+    } catch (error) {
+      setAction({ kind: 'error', message: errorMessage(error) })
+    } finally {
+      setUploading(false)
+    }
+  }
+
+  return (
+    <ActionCard
+      title="内容包"
+      description="上传 bundle JSON 与对应的 .cb 文件，校验通过后立即下发，无需重启"
+    >
+      <form key={formKey} className="flex flex-wrap items-end gap-3" onSubmit={submit}>
+        <label className="grid gap-1 text-sm">
+          Bundle JSON
+          <Input
+            type="file"
+            accept=".json,application/json"
+            onChange={(event) => setMetadata(event.target.files?.[0] ?? null)}
+          />
+        </label>
+        <label className="grid gap-1 text-sm">
+          内容包 (.cb)
+          <Input
+            type="file"
+            accept=".cb"
+            onChange={(event) => setBundle(event.target.files?.[0] ?? null)}
+          />
+        </label>
+        <Button type="submit" size="sm" disabled={uploading}>
+          {uploading ? <LoaderCircle className="animate-spin" /> : <PackagePlus />}
+          上传
+        </Button>
+        <ActionMessage action={action} />
+      </form>
+      {state !== 'ready' ? (
+        <LoadPanel state={state} onRetry={load} />
+      ) : rows.length === 0 ? (
+        <div className="text-sm text-muted-foreground">还没有内容包</div>
+      ) : (
+        <Table>
+          <TableHeader>
+            <TableRow>
+              <TableHead>客户端版本</TableHead>
+              <TableHead>版本</TableHead>
+              <TableHead className="text-right">大小</TableHead>
+              <TableHead className="text-right">文件变化</TableHead>
+              <TableHead>路径</TableHead>
+            </TableRow>
+          </TableHeader>
+          <TableBody>
+            {rows.map((row) => (
+              <TableRow key={`${row.appVersion}:${row.prevVersion ?? ''}:${row.version}`}>
+                <TableCell className="font-mono">{row.appVersion}</TableCell>
+                <TableCell className="font-mono">
+                  {row.prevVersion ? `${row.prevVersion} → ${row.version}` : row.version}
+                  <div className="text-xs text-muted-foreground">{row.uuid}</div>
+                </TableCell>
+                <TableCell className="text-right font-mono">{formatBytes(row.bundleSize)}</TableCell>
+                <TableCell className="text-right font-mono text-xs">
+                  +{row.addedFiles} / -{row.removedFiles}
+                </TableCell>
+                <TableCell className="font-mono text-xs">{row.bundlePath}</TableCell>
+              </TableRow>
+            ))}
+          </TableBody>
+        </Table>
+      )}
+    </ActionCard>
+  )
+}
+
+function AnomaliesView() {
+  const [query, setQuery] = useState('')
+  const [includeReviewed, setIncludeReviewed] = useState(false)
+  const [rows, setRows] = useState<AnomalyRow[]>([])
+  const [state, setState] = useState<LoadState>('loading')
+  const [action, setAction] = useState<ActionState>(emptyAction)
+  const [scanning, setScanning] = useState(false)
+  const pagination = useServerPagination(rows, defaultTablePageSize)
+  const { setMeta } = pagination
+
+  function load(
+    showLoading = true,
+    page = pagination.page,
+    pageSize = pagination.pageSize,
+    reviewed = includeReviewed,
+  ) {
+    if (showLoading) {
+      setState('loading')
+    }
+    adminApi
+      .anomalies({ q: query, includeReviewed: reviewed, page, pageSize })
+      .then((value) => {
+        setRows(value.rows)
+        setMeta(value)
+        setState('ready')
+      })
+      .catch(() => setState('error'))
+  }
+
+  async function scan() {
+    setScanning(true)
+    setAction(emptyAction)
+    try {
+      await runOperation('scan_score_anomalies')
+      setAction({ kind: 'success', message: '扫描完成' })
+      load(false, 1)
+    } catch (error) {
+      setAction({ kind: 'error', message: errorMessage(error) })
+    } finally {
+      setScanning(false)
+    }
+  }
+
+  async function review(row: AnomalyRow) {
+    const note = prompt(`审核备注（${row.name || row.userId}）`, row.reviewNote)
+    if (note === null) {
+      return
+    }
+    setAction(emptyAction)
+    try {
+      const result = await adminApi.reviewAnomaly({ user_id: row.userId, note })
+      setAction({ kind: 'success', message: formatActionResult(result) })
+      load(false)
+    } catch (error) {
+      setAction({ kind: 'error', message: errorMessage(error) })
+    }
+  }
+
+  useEffect(() => {
+    adminApi
+      .anomalies({ page: 1, pageSize: defaultTablePageSize })
+      .then((value) => {
+        setRows(value.rows)
+        setMeta(value)
+        setState('ready')
+      })
+      .catch(() => setState('error'))
+  }, [setMeta])
+
+  return (
+    <DataPanel
+      title="异常审核"
+      description="异常分 = 5 × Rating 突增 + 3 × 与其他账号相同的判定 + 0.5 × 过密提交"
+      state={state}
+      onSearch={() => load(true, 1)}
+      searchValue={query}
+      onSearchChange={setQuery}
+    >
+      <div className="mb-3 flex flex-wrap items-center gap-2">
+        <label className="flex items-center gap-2 text-sm">
+          <input
+            type="checkbox"
+            checked={includeReviewed}
+            onChange={(event) => {
+              setIncludeReviewed(event.target.checked)
+              load(true, 1, pagination.pageSize, event.target.checked)
+            }}
+          />
+          显示已审核
+        </label>
+        <Button type="button" size="sm" variant="outline" disabled={scanning} onClick={scan}>
+          {scanning ? <LoaderCircle className="animate-spin" /> : <RefreshCcw />}
+          立即扫描
+        </Button>
+        <ActionMessage action={action} />
+      </div>
+      <TableBlock
+        pagination={pagination}
+        onPageChange={(page) => load(true, page, pagination.pageSize)}
+        onPageSizeChange={(pageSize) => load(true, 1, pageSize)}
+        emptyText="没有待审核的账号"
+        renderTable={(visibleRows) => (
+          <Table>
+            <TableHeader>
+              <TableRow>
+                <TableHead>玩家</TableHead>
+                <TableHead className="text-right">异常分</TableHead>
+                <TableHead className="text-right">Rating 突增</TableHead>
+                <TableHead className="text-right">相同判定</TableHead>
+                <TableHead className="text-right">过密提交</TableHead>
+                <TableHead className="text-right">游玩数</TableHead>
+                <TableHead>关联账号</TableHead>
+                <TableHead>扫描时间</TableHead>
+                <TableHead>审核</TableHead>
+                <TableHead className="w-0 text-right">操作</TableHead>
+              </TableRow>
+            </TableHeader>
+            <TableBody>
+              {visibleRows.map((row) => (
+                <TableRow key={row.userId}>
+                  <TableCell>
+                    <div>{row.name || '-'}</div>
+                    <div className="font-mono text-xs text-muted-foreground">
+                      {row.userId} · {row.userCode}
+                    </div>
+                  </TableCell>
+                  <TableCell className="text-right font-mono">{row.score.toFixed(1)}</TableCell>
+                  <TableCell className="text-right font-mono">{row.ratingSpikes}</TableCell>
+                  <TableCell className="text-right font-mono">{row.sharedFingerprints}</TableCell>
+                  <TableCell className="text-right font-mono">{row.burstPlays}</TableCell>
+                  <TableCell className="text-right font-mono">{row.playCount}</TableCell>
+                  <TableCell className="font-mono text-xs">
+                    {row.linkedUsers.length > 0 ? row.linkedUsers.join(', ') : '-'}
+                  </TableCell>
+                  <TableCell>{row.computedAt}</TableCell>
+                  <TableCell>
+                    {row.reviewedAt ? (
+                      <div>
+                        <Badge variant="secondary">{row.reviewedAt}</Badge>
+                        {row.reviewNote && (
+                          <div className="text-xs text-muted-foreground">{row.reviewNote}</div>
+                        )}
+                      </div>
+                    ) : (
+                      <Badge variant="outline">待审核</Badge>
+                    )}
+                  </TableCell>
+                  <TableCell className="w-0 whitespace-nowrap">
+                    <Button type="button" size="sm" variant="outline" onClick={() => review(row)}>
+                      <ShieldCheck />
+                      标记已审核
+                    </Button>
+                  </TableCell>
+                </TableRow>
+              ))}
+            </TableBody>
+          </Table>
+        )}
+      />
+    </DataPanel>
+  )
+}
+
+const scoreAuditFlagLabels: Record<ScoreAuditFlag, string> = {
+  shiny_exceeds_perfect: '大 Pure 多于 Pure',
+  note_count_mismatch: '物量不符',
+  score_above_max: '超过理论值',
+  clear_type_mismatch: '评级与判定不符',
+  time_travel: '时间异常',
+}
+
+function ScoreAuditsView() {
+  const [query, setQuery] = useState('')
+  const [includeReviewed, setIncludeReviewed] = useState(false)
+  const [rows, setRows] = useState<ScoreAuditRow[]>([])
+  const [state, setState] = useState<LoadState>('loading')
+  const [action, setAction] = useState<ActionState>(emptyAction)
+  const pagination = useServerPagination(rows, defaultTablePageSize)
+  const { setMeta } = pagination
+
+  function load(
+    showLoading = true,
+    page = pagination.page,
+    pageSize = pagination.pageSize,
+    reviewed = includeReviewed,
+  ) {
+    if (showLoading) {
+      setState('loading')
+    }
+    adminApi
+      .scoreAudits({ q: query, includeReviewed: reviewed, page, pageSize })
+      .then((value) => {
+        setRows(value.rows)
+        setMeta(value)
+        setState('ready')
+      })
+      .catch(() => setState('error'))
+  }
+
+  async function review(row: ScoreAuditRow) {
+    const note = prompt(`审核备注（${row.name || row.userId} · ${row.songId}）`, row.reviewNote)
+    if (note === null) {
+      return
+    }
+    setAction(emptyAction)
+    try {
+      const result = await adminApi.reviewScoreAudit({ audit_id: row.auditId, note })
+      setAction({ kind: 'success', message: formatActionResult(result) })
+      load(false)
+    } catch (error) {
+      setAction({ kind: 'error', message: errorMessage(error) })
+    }
+  }
+
+  useEffect(() => {
+    adminApi
+      .scoreAudits({ page: 1, pageSize: defaultTablePageSize })
+      .then((value) => {
+        setRows(value.rows)
+        setMeta(value)
+        setState('ready')
+      })
+      .catch(() => setState('error'))
+  }, [setMeta])
+
+  return (
+    <DataPanel
+      title="成绩审计"
+      description="判定数与物量、理论值、评级或时间不符的提交；时间异常的提交会被拒绝"
+      state={state}
+      onSearch={() => load(true, 1)}
+      searchValue={query}
+      onSearchChange={setQuery}
+    >
+      <div className="mb-3 flex flex-wrap items-center gap-2">
+        <label className="flex items-center gap-2 text-sm">
+          <input
+            type="checkbox"
+            checked={includeReviewed}
+            onChange={(event) => {
+              setIncludeReviewed(event.target.checked)
+              load(true, 1, pagination.pageSize, event.target.checked)
+            }}
+          />
+          显示已审核
+        </label>
+        <ActionMessage action={action} />
+      </div>
+      <TableBlock
+        pagination={pagination}
+        onPageChange={(page) => load(true, page, pagination.pageSize)}
+        onPageSizeChange={(pageSize) => load(true, 1, pageSize)}
+        emptyText="没有待审核的成绩"
+        renderTable={(visibleRows) => (
+          <Table>
+            <TableHeader>
+              <TableRow>
+                <TableHead>玩家</TableHead>
+                <TableHead>谱面</TableHead>
+                <TableHead className="text-right">分数</TableHead>
+                <TableHead className="text-right">判定</TableHead>
+                <TableHead>问题</TableHead>
+                <TableHead>提交时间</TableHead>
+                <TableHead>审核</TableHead>
+                <TableHead className="w-0 text-right">操作</TableHead>
+              </TableRow>
+            </TableHeader>
+            <TableBody>
+              {visibleRows.map((row) => (
+                <TableRow key={row.auditId}>
+                  <TableCell>
+                    <div>{row.name || '-'}</div>
+                    <div className="font-mono text-xs text-muted-foreground">{row.userId}</div>
+                  </TableCell>
+                  <TableCell>
+                    <div className="font-mono">{row.songId}</div>
+                    <div className="text-xs text-muted-foreground">
+                      {difficultyLabel(row.difficulty)}
+                    </div>
+                  </TableCell>
+                  <TableCell className="text-right font-mono">{row.score}</TableCell>
+                  <TableCell className="text-right font-mono text-xs">
+                    {row.perfectCount}({row.shinyPerfectCount}) / {row.nearCount} / {row.missCount}
+                  </TableCell>
+                  <TableCell>
+                    <div className="flex flex-wrap gap-1">
+                      {row.rejected && <Badge variant="destructive">已拒绝</Badge>}
+                      {row.flags.map((flag) => (
+                        <Badge key={flag} variant="outline">
+                          {scoreAuditFlagLabels[flag] ?? flag}
+                        </Badge>
+                      ))}
+                    </div>
+                  </TableCell>
+                  <TableCell>{row.createdAt}</TableCell>
+                  <TableCell>
+                    {row.reviewedAt ? (
+                      <div>
+                        <Badge variant="secondary">{row.reviewedAt}</Badge>
+                        {row.reviewNote && (
+                          <div className="text-xs text-muted-foreground">{row.reviewNote}</div>
+                        )}
+                      </div>
+                    ) : (
+                      <Badge variant="outline">待审核</Badge>
+                    )}
+                  </TableCell>
+                  <TableCell className="w-0 whitespace-nowrap">
+                    <Button type="button" size="sm" variant="outline" onClick={() => review(row)}>
+                      <ShieldCheck />
+                      标记已审核
+                    </Button>
+                  </TableCell>
+                </TableRow>
+              ))}
+            </TableBody>
+          </Table>
+        )}
+      />
+    </DataPanel>
+  )
+}
+
+function ReplaysView() {
+  const [query, setQuery] = useState('')
+  const [rows, setRows] = useState<ReplayRow[]>([])
+  const [state, setState] = useState<LoadState>('loading')
+  const pagination = useServerPagination(rows, defaultTablePageSize)
+  const { setMeta } = pagination
+
+  function load(showLoading = true, page = pagination.page, pageSize = pagination.pageSize) {
+    if (showLoading) {
+      setState('loading')
+    }
+    adminApi
+      .replays({ q: query, page, pageSize })
+      .then((value) => {
+        setRows(value.rows)
+        setMeta(value)
+        setState('ready')
+      })
+      .catch(() => setState('error'))
+  }
+
+  useEffect(() => {
+    adminApi
+      .replays({ page: 1, pageSize: defaultTablePageSize })
+      .then((value) => {
+        setRows(value.rows)
+        setMeta(value)
+        setState('ready')
+      })
+      .catch(() => setState('error'))
+  }, [setMeta])
+
+  return (
+    <DataPanel
+      title="成绩回放"
+      description="按上传时间排序，可按玩家名、User Code 或 Song ID 搜索"
+      state={state}
+      onSearch={() => load(true, 1)}
+      searchValue={query}
+      onSearchChange={setQuery}
+    >
+      <TableBlock
+        pagination={pagination}
+        onPageChange={(page) => load(true, page, pagination.pageSize)}
+        onPageSizeChange={(pageSize) => load(true, 1, pageSize)}
+        emptyText="没有上传的回放"
+        renderTable={(visibleRows) => (
+          <Table>
+            <TableHeader>
+              <TableRow>
+                <TableHead>玩家</TableHead>
+                <TableHead>歌曲</TableHead>
+                <TableHead>难度</TableHead>
+                <TableHead className="text-right">分数</TableHead>
+                <TableHead>游玩时间</TableHead>
+                <TableHead>上传时间</TableHead>
+                <TableHead className="text-right">大小</TableHead>
+                <TableHead className="w-0 text-right">操作</TableHead>
+              </TableRow>
+            </TableHeader>
+            <TableBody>
+              {visibleRows.map((row) => (
+                <TableRow key={`${row.userId}:${row.songId}:${row.difficulty}:${row.timePlayed}`}>
+                  <TableCell>
+                    <div>{row.name || '-'}</div>
+                    <div className="font-mono text-xs text-muted-foreground">{row.userId}</div>
+                  </TableCell>
+                  <TableCell>
+                    <div>{row.nameEn || row.songId}</div>
+                    <div className="font-mono text-xs text-muted-foreground">{row.songId}</div>
+                  </TableCell>
+                  <TableCell>{difficultyLabel(row.difficulty)}</TableCell>
+                  <TableCell className="text-right font-mono">{row.score ?? '-'}</TableCell>
+                  <TableCell>{row.playedAt}</TableCell>
+                  <TableCell>{row.uploadedAt}</TableCell>
+                  <TableCell className="text-right font-mono" title={row.sha256}>
+                    {(row.size / 1024).toFixed(1)} KiB
+                    {row.compression && (
+                      <div className="text-xs text-muted-foreground">{row.compression}</div>
+                    )}
+                  </TableCell>
+                  <TableCell className="w-0 whitespace-nowrap">
+                    <Button asChild size="sm" variant="outline">
+                      <a href={adminApi.replayFileUrl(row)}>
+                        <Download />
+                        下载
+                      </a>
+                    </Button>
+                  </TableCell>
+                </TableRow>
+              ))}
+            </TableBody>
+          </Table>
+        )}
+      />
+    </DataPanel>
   )
 }
 
-function RedeemUsersView() {
-  const [code, setCode] = useState('')
-  const [users, setUsers] = useState<AdminUserSummary[]>([])
-  const [action, setAction] = useState<ActionState>(emptyAction)
-  const [loading, setLoading] = useState(false)
+function RequestLogView() {
+  const [query, setQuery] = useState('')
+  const [path, setPath] = useState('')
+  const [status, setStatus] = useState('')
+  const [rows, setRows] = useState<ApiLogRow[]>([])
+  const [state, setState] = useState<LoadState>('loading')
+  const pagination = useServerPagination(rows, defaultTablePageSize)
+  const { setMeta } = pagination
 
-  async function onSubmit(event: FormEvent) {
-    event.preventDefault()
-    setLoading(true)
-    setAction(emptyAction)
-    try {
-      const result = await adminApi.redeemUsers(requireTrimmed(code, 'code'))
-      setUsers(result.users)
-      setAction({
-        kind: 'success',
-        message: `${result.code} · ${result.users.length} 个用户`,
-      })
-    } catch (error) {
-      setAction({ kind: 'error', message: errorMessage(error) })
-    } finally {
-      setLoading(false)
+  function load(showLoading = true, page = pagination.page, pageSize = pagination.pageSize) {
+    if (showLoading) {
+      setState('loading')
     }
+    const statusCode = Number(status.trim())
+    adminApi
+      .requestLog({
+        q: query,
+        path: path.trim() || undefined,
+        status: status.trim() && Number.isInteger(statusCode) ? statusCode : undefined,
+        page,
+        pageSize,
+      })
+      .then((value) => {
+        setRows(value.rows)
+        setMeta(value)
+        setState('ready')
+      })
+      .catch(() => setState('error'))
   }
 
+  useEffect(() => {
+    adminApi
+      .requestLog({ page: 1, pageSize: defaultTablePageSize })
+      .then((value) => {
+        setRows(value.rows)
+        setMeta(value)
+        setState('ready')
+      })
+      .catch(() => setState('error'))
+  }, [setMeta])
+
   return (
-    <ActionCard title="兑换使用者" description="redeem/<code>">
-      <form className="flex flex-col gap-3 sm:flex-row" onSubmit={onSubmit}>
+    <DataPanel
+      title="请求日志"
+      description="游戏 API 的请求记录，可按玩家、路径或状态码筛选"
+      state={state}
+      onSearch={() => load(true, 1)}
+      searchValue={query}
+      onSearchChange={setQuery}
+    >
+      <div className="mb-3 flex flex-wrap items-center gap-2">
         <Input
-          value={code}
-          onChange={(event) => setCode(event.target.value)}
-          placeholder="code"
-          required
+          className="w-64"
+          placeholder="路径包含"
+          value={path}
+          onChange={(event) => setPath(event.target.value)}
         />
-        <Button type="submit" size="sm" variant="outline" disabled={loading}>
-          {loading ? <LoaderCircle className="animate-spin" /> : <Search />}
-          查询
-        </Button>
-      </form>
-      <ActionMessage action={action} />
-      {users.length > 0 && (
-        <div className="max-h-64 overflow-auto rounded-md border">
+        <Input
+          className="w-28"
+          inputMode="numeric"
+          placeholder="状态码"
+          value={status}
+          onChange={(event) => setStatus(event.target.value)}
+        />
+      </div>
+      <TableBlock
+        pagination={pagination}
+        onPageChange={(page) => load(true, page, pagination.pageSize)}
+        onPageSizeChange={(pageSize) => load(true, 1, pageSize)}
+        emptyText="没有请求记录"
+        renderTable={(visibleRows) => (
           <Table>
             <TableHeader>
               <TableRow>
-                <TableHead>ID</TableHead>
-                <TableHead>Name</TableHead>
-                <TableHead>User Code</TableHead>
+                <TableHead>时间</TableHead>
+                <TableHead>请求</TableHead>
+                <TableHead>玩家</TableHead>
+                <TableHead className="text-right">状态</TableHead>
+                <TableHead className="text-right">耗时</TableHead>
+                <TableHead>IP</TableHead>
               </TableRow>
             </TableHeader>
             <TableBody>
-              {users.map((user) => (
-                <TableRow key={user.userId}>
-                  <TableCell className="font-mono">{user.userId}</TableCell>
-                  <TableCell>{user.name || '-'}</TableCell>
-                  <TableCell>{user.userCode || '-'}</TableCell>
+              {visibleRows.map((row) => (
+                <TableRow key={row.logId}>
+                  <TableCell>{row.createdAt}</TableCell>
+                  <TableCell className="font-mono text-xs">
+                    <span className="mr-2 font-semibold">{row.method}</span>
+                    {row.path}
+                  </TableCell>
+                  <TableCell>
+                    {row.userId === null ? (
+                      '-'
+                    ) : (
+                      <>
+                        <div>{row.name || '-'}</div>
+                        <div className="font-mono text-xs text-muted-foreground">{row.userId}</div>
+                      </>
+                    )}
+                  </TableCell>
+                  <TableCell className="text-right">
+                    <Badge variant={row.status >= 500 ? 'destructive' : 'outline'}>
+                      {row.status}
+                    </Badge>
+                  </TableCell>
+                  <TableCell className="text-right font-mono">{row.latencyMs} ms</TableCell>
+                  <TableCell className="font-mono text-xs">{row.ip ?? '-'}</TableCell>
                 </TableRow>
               ))}
             </TableBody>
           </Table>
-        </div>
-      )}
-    </ActionCard>
+        )}
+      />
+    </DataPanel>
   )
 }
 
@@ -2429,6 +5338,135 @@ function DifficultySelect({
   )
 }
 
+function ChartConstantProposals({
+  isAdmin,
+  onAccepted,
+}: {
+  isAdmin: boolean
+  onAccepted: () => void
+}) {
+  const [proposals, setProposals] = useState<ChartConstantProposal[]>([])
+  const [action, setAction] = useState<ActionState>(emptyAction)
+  const [loading, setLoading] = useState(false)
+
+  const load = useCallback(() => {
+    adminApi
+      .chartConstantProposals()
+      .then(setProposals)
+      .catch((error) => setAction({ kind: 'error', message: errorMessage(error) }))
+  }, [])
+
+  async function estimate() {
+    setLoading(true)
+    setAction(emptyAction)
+    try {
+      await runOperation('estimate_chart_constants')
+      setAction({ kind: 'success', message: '定数建议已更新' })
+      load()
+    } catch (error) {
+      setAction({ kind: 'error', message: errorMessage(error) })
+    } finally {
+      setLoading(false)
+    }
+  }
+
+  async function accept(proposal: ChartConstantProposal) {
+    setAction(emptyAction)
+    try {
+      await adminApi.acceptChartConstantProposal(proposal.songId, proposal.difficulty)
+      setAction({
+        kind: 'success',
+        message: `${proposal.songId} ${difficultyLabel(proposal.difficulty)} 定数已设为 ${proposal.proposedRating}`,
+      })
+      load()
+      onAccepted()
+    } catch (error) {
+      setAction({ kind: 'error', message: errorMessage(error) })
+    }
+  }
+
+  async function dismiss(proposal: ChartConstantProposal) {
+    setAction(emptyAction)
+    try {
+      await adminApi.dismissChartConstantProposal(proposal.songId, proposal.difficulty)
+      load()
+    } catch (error) {
+      setAction({ kind: 'error', message: errorMessage(error) })
+    }
+  }
+
+  useEffect(() => {
+    load()
+  }, [load])
+
+  if (proposals.length === 0 && !isAdmin) {
+    return null
+  }
+
+  return (
+    <div className="mb-5 grid gap-3 rounded-md border p-3">
+      <div className="flex flex-wrap items-center justify-between gap-3">
+        <div className="text-sm font-medium">未定级谱面的定数建议</div>
+        <div className="flex flex-wrap items-center gap-2">
+          <ActionMessage action={action} />
+          {isAdmin && (
+            <Button type="button" size="sm" variant="outline" disabled={loading} onClick={estimate}>
+              {loading ? <LoaderCircle className="animate-spin" /> : <RefreshCcw />}
+              重新估算
+            </Button>
+          )}
+        </div>
+      </div>
+      {proposals.length > 0 ? (
+        <div className="overflow-auto rounded-md border">
+          <Table>
+            <TableHeader>
+              <TableRow>
+                <TableHead>Song</TableHead>
+                <TableHead>难度</TableHead>
+                <TableHead className="text-right">建议定数</TableHead>
+                <TableHead className="text-right">样本</TableHead>
+                <TableHead>模型</TableHead>
+                <TableHead>估算时间</TableHead>
+                <TableHead className="w-0 text-right">操作</TableHead>
+              </TableRow>
+            </TableHeader>
+            <TableBody>
+              {proposals.map((proposal) => (
+                <TableRow key={`${proposal.songId}:${proposal.difficulty}`}>
+                  <TableCell>
+                    <div className="font-mono">{proposal.songId}</div>
+                    <div className="text-xs text-muted-foreground">{proposal.nameEn || '-'}</div>
+                  </TableCell>
+                  <TableCell>{difficultyLabel(proposal.difficulty)}</TableCell>
+                  <TableCell className="text-right font-mono">{proposal.proposedRating}</TableCell>
+                  <TableCell className="text-right font-mono">{proposal.sampleCount}</TableCell>
+                  <TableCell>{proposal.model}</TableCell>
+                  <TableCell>{proposal.createdAt}</TableCell>
+                  <TableCell className="w-0 whitespace-nowrap">
+                    <div className="flex justify-end gap-2">
+                      <Button type="button" size="sm" onClick={() => accept(proposal)}>
+                        <ShieldCheck />
+                        采用
+                      </Button>
+                      <Button type="button" size="sm" variant="outline" onClick={() => dismiss(proposal)}>
+                        <X />
+                        忽略
+                      </Button>
+                    </div>
+                  </TableCell>
+                </TableRow>
+              ))}
+            </TableBody>
+          </Table>
+        </div>
+      ) : (
+        <div className="text-sm text-muted-foreground">暂无定数建议</div>
+      )}
+    </div>
+  )
+}
+
 function SongsView({
   isAdmin,
   canEditConstants,
@@ -2590,6 +5628,9 @@ function SongsView({
           </div>
         </form>
       )}
+      {canEditConstants && (
+        <ChartConstantProposals isAdmin={isAdmin} onAccepted={() => load(false)} />
+      )}
       {canEditConstants && (
         <ActionMessage action={action} className="mb-3 block" />
       )}
@@ -4021,6 +7062,11 @@ function difficultyLabel(difficulty: number) {
   return ['PST', 'PRS', 'FTR', 'BYD', 'ETR'][difficulty] ?? String(difficulty)
 }
 
+function toDatetimeLocal(ts: number) {
+  const offset = new Date(ts).getTimezoneOffset() * 60_000
+  return new Date(ts - offset).toISOString().slice(0, 16)
+}
+
 function formatActionResult(result: AdminActionResult) {
   return `${result.message} · ${result.affectedRows} 行`
 }
@@ -4074,6 +7120,16 @@ function viewTitle(view: View) {
       return '成绩图'
     case 'chartTop':
       return '单曲排行榜'
+    case 'chartAnalytics':
+      return '谱面分析'
+    case 'anomalies':
+      return '异常审核'
+    case 'scoreAudits':
+      return '成绩审计'
+    case 'replays':
+      return '成绩回放'
+    case 'requestLog':
+      return '请求日志'
     case 'userTicket':
       return '记忆源点'
     case 'userPassword':
@@ -4084,10 +7140,18 @@ function viewTitle(view: View) {
       return '封禁用户'
     case 'userPurchase':
       return '购买权限'
+    case 'userSaves':
+      return '存档版本'
+    case 'userImpersonate':
+      return '代登录'
     case 'scoreDelete':
       return '删除成绩'
+    case 'presents':
+      return '奖励列表'
     case 'presentCreate':
       return '新增奖励'
+    case 'presentPublish':
+      return '新增并分发奖励'
     case 'presentDeliver':
       return '分发奖励'
     case 'presentDelete':
@@ -4098,16 +7162,30 @@ function viewTitle(view: View) {
       return '删除兑换码'
     case 'redeemUsers':
       return '兑换使用者'
+    case 'redeemBatches':
+      return '兑换码批次'
+    case 'events':
+      return '活动管理'
+    case 'eventLadder':
+      return '活动排行'
     case 'users':
       return '玩家管理'
     case 'songs':
       return '歌曲表'
+    case 'chartMismatches':
+      return '曲目对账'
+    case 'songUpload':
+      return '上传歌曲'
+    case 'bundles':
+      return '内容包'
     case 'items':
       return '物品表'
     case 'purchases':
       return '购买项'
     case 'purchaseItems':
       return '购买物品配置'
+    case 'gameConstants':
+      return '游戏常量'
   }
 }
 
@@ -4127,6 +7205,16 @@ function viewSubtitle(view: View) {
       return '生成 B30 / AP30 / Sex30 成绩图'
     case 'chartTop':
       return '查询单曲指定难度排行榜'
+    case 'chartAnalytics':
+      return '分数分布与体感定数，找出定数偏差较大的谱面'
+    case 'anomalies':
+      return '按异常分排序的待审核账号'
+    case 'scoreAudits':
+      return '提交时校验未通过的成绩'
+    case 'replays':
+      return '客户端上传的游玩回放，可下载复核'
+    case 'requestLog':
+      return '游戏 API 的请求记录，保留天数由 api_log_retention_days 决定'
     case 'userTicket':
       return '更新玩家记忆源点'
     case 'userPassword':
@@ -4137,10 +7225,18 @@ function viewSubtitle(view: View) {
       return '封禁指定玩家账号'
     case 'userPurchase':
       return '调整玩家购买权限'
+    case 'userSaves':
+      return '查看并回滚玩家云存档'
+    case 'userImpersonate':
+      return '为玩家签发短期游戏 token，用于复现客户端问题'
     case 'scoreDelete':
       return '按条件删除成绩记录'
+    case 'presents':
+      return '查看奖励定义与分发人数'
     case 'presentCreate':
       return '创建一个奖励定义'
+    case 'presentPublish':
+      return '创建多物品奖励并立即分发给指定玩家或全部玩家'
     case 'presentDeliver':
       return '向玩家分发已有奖励'
     case 'presentDelete':
@@ -4151,16 +7247,30 @@ function viewSubtitle(view: View) {
       return '删除兑换码'
     case 'redeemUsers':
       return '查询兑换码使用者'
+    case 'redeemBatches':
+      return '批量生成、停用和导出兑换码'
+    case 'events':
+      return '限时活动的谱面、时间和积分奖励'
+    case 'eventLadder':
+      return '查询活动积分排行'
     case 'users':
       return '账号状态、票券和最近游玩记录'
     case 'songs':
       return '曲目名称和谱面定数'
+    case 'chartMismatches':
+      return 'songlist 与歌曲表不一致的曲目'
+    case 'songUpload':
+      return '上传歌曲文件夹并立即计算文件哈希'
+    case 'bundles':
+      return '已下发的内容包与上传'
     case 'items':
       return '物品类型和可用状态'
     case 'purchases':
       return '购买项、价格和折扣配置'
     case 'purchaseItems':
       return '购买项和物品的关联关系'
+    case 'gameConstants':
+      return '体力、经验与世界模式等平衡参数，保存后本实例立即生效'
   }
 }
 
@@ -26,6 +26,7 @@ export type DashboardData = {
   presentCount: number
   alertCount: number
   recentOps: RecentOp[]
+  songFileRefresh: SongFileRefresh | null
 }
 
 export type PageData<T> = {
@@ -38,6 +39,7 @@ export type PageData<T> = {
 export type PageParams = {
   page: number
   pageSize: number
+  sort?: string
 }
 
 export type RecentOp = {
@@ -47,6 +49,13 @@ export type RecentOp = {
   status: string
 }
 
+export type SongFileRefresh = {
+  refreshedAt: string
+  hashed: number
+  unchanged: number
+  removed: number
+}
+
 export type UserRow = {
   userId: number
   name: string
@@ -55,6 +64,7 @@ export type UserRow = {
   ticket: number
   lastPlay: string
   banned: boolean
+  shadowBanned: boolean
   isAdmin: boolean
   canEditChartConstants: boolean
 }
@@ -164,6 +174,37 @@ export type AdminUserScores = {
   r10: AdminScoreRow[]
 }
 
+export type PttHistoryPoint = {
+  day: number
+  date: string
+  potential: number
+  best30: number
+  recent10: number
+}
+
+export type PttHistory = {
+  user: AdminUserSummary
+  points: PttHistoryPoint[]
+}
+
+export type Recent30Entry = {
+  songId: string
+  difficulty: number
+  score: number
+  clearType: number
+  rating: number
+  timePlayed: string
+}
+
+export type Recent30Diff = {
+  user: AdminUserSummary
+  from: PttHistoryPoint
+  to: PttHistoryPoint
+  added: Recent30Entry[]
+  removed: Recent30Entry[]
+  unchanged: number
+}
+
 export type ScoreImage = {
   mode: string
   title: string
@@ -183,16 +224,280 @@ export type AdminChartTop = {
   scores: AdminScoreRow[]
 }
 
+export type AdminSaveVersion = {
+  historyId: number
+  createdAt: string
+  archivedAt: string
+  dataSize: number
+}
+
+export type AdminImpersonationToken = {
+  user: AdminUserSummary
+  token: string
+  expiresAt: string
+}
+
+export type ImpersonationLogRow = {
+  tokenId: number
+  userId: number
+  name: string
+  adminUserId: number
+  adminName: string
+  reason: string
+  ip: string | null
+  createdAt: string
+  expiresAt: string
+  active: boolean
+}
+
+export type AdminUserSaves = {
+  user: AdminUserSummary
+  currentCreatedAt: string | null
+  versions: AdminSaveVersion[]
+}
+
+export type AdminDevice = {
+  kind: 'device_id' | 'install_id'
+  value: string
+  deviceModel: string
+  firstSeen: string
+  lastSeen: string
+  lastIp: string | null
+  seenCount: number
+}
+
+export type AdminLinkedAccount = {
+  userId: number
+  name: string
+  userCode: string
+  banned: boolean
+  shared: string[]
+  lastSeen: string
+}
+
+export type AdminUserDevices = {
+  user: AdminUserSummary
+  devices: AdminDevice[]
+  linkedAccounts: AdminLinkedAccount[]
+}
+
+export type AdminPurchaseLog = {
+  kind: 'pack' | 'single' | 'special'
+  purchaseName: string
+  price: number
+  currency: string
+  time: string
+}
+
+export type AdminUserPurchases = {
+  user: AdminUserSummary
+  purchases: AdminPurchaseLog[]
+}
+
+export type AdminUserDownloadQuota = {
+  user: AdminUserSummary
+  downloadCount: number
+  downloadBytes: number
+  timesLimit: number
+  bytesLimit: number
+  isOverride: boolean
+}
+
+export type UserDownloadQuotaPayload = UserSelectorPayload & {
+  reset?: boolean
+  times_limit?: number | null
+  bytes_limit?: number | null
+}
+
+export type ChartAnalyticsRow = {
+  songId: string
+  nameEn: string
+  difficulty: number
+  constant: number | null
+  playerCount: number
+  avgScore: number
+  avgAccuracy: number
+  clearRate: number
+  scoreDistribution: number[]
+  perceivedConstant: number | null
+  deviation: number | null
+  sampleCount: number
+  computedAt: string
+}
+
+export type ChartConstantProposal = {
+  songId: string
+  nameEn: string
+  difficulty: number
+  proposedRating: string
+  sampleCount: number
+  model: string
+  createdAt: string
+}
+
+export type ChartAnalyticsSort = 'deviation' | 'players' | 'song'
+
+export type PresentSort = 'latest' | 'expire' | 'present_id'
+
+export type PresentRow = {
+  presentId: string
+  description: string
+  expireAt: string
+  webOnly: boolean
+  itemSummary: string
+  recipients: number
+}
+
+export type AnomalyRow = {
+  userId: number
+  name: string
+  userCode: string
+  score: number
+  ratingSpikes: number
+  sharedFingerprints: number
+  burstPlays: number
+  playCount: number
+  linkedUsers: number[]
+  computedAt: string
+  reviewedAt: string | null
+  reviewNote: string
+}
+
+export type ScoreAuditFlag =
+  | 'shiny_exceeds_perfect'
+  | 'note_count_mismatch'
+  | 'score_above_max'
+  | 'clear_type_mismatch'
+  | 'time_travel'
+
+export type ScoreAuditRow = {
+  auditId: number
+  userId: number
+  name: string
+  songId: string
+  difficulty: number
+  score: number
+  shinyPerfectCount: number
+  perfectCount: number
+  nearCount: number
+  missCount: number
+  clearType: number
+  flags: ScoreAuditFlag[]
+  rejected: boolean
+  createdAt: string
+  reviewedAt: string | null
+  reviewNote: string
+}
+
+export type BundleRow = {
+  appVersion: string
+  version: string
+  prevVersion: string | null
+  uuid: string
+  jsonSize: number
+  bundleSize: number
+  bundlePath: string
+  addedFiles: number
+  removedFiles: number
+}
+
+export type SongUploadResult = {
+  songId: string
+  files: { fileName: string; size: number; md5: string }[]
+  chartCreated: boolean
+}
+
+export type ChartMismatchReport = {
+  hasSonglist: boolean
+  missingCharts: string[]
+  unlistedCharts: string[]
+  placeholdersCreated: number
+  chartsUpdated: number
+  checkedAt: string | null
+}
+
+export type ApiLogRow = {
+  logId: number
+  createdAt: string
+  method: string
+  path: string
+  userId: number | null
+  name: string | null
+  status: number
+  latencyMs: number
+  ip: string | null
+}
+
+export type ReplayRow = {
+  userId: number
+  name: string
+  songId: string
+  nameEn: string
+  difficulty: number
+  score: number | null
+  timePlayed: number
+  playedAt: string
+  uploadedAt: string
+  size: number
+  sha256: string
+  compression: string
+}
+
 export type AdminActionResult = {
   message: string
   affectedRows: number
 }
 
+export type GameConstant = {
+  name: string
+  value: number
+  defaultValue: number
+  overrideValue: number | null
+}
+
 export type AdminRedeemUsers = {
   code: string
   users: AdminUserSummary[]
 }
 
+export type AdminRedeemBatch = {
+  batchId: string
+  description: string
+  redeemType: number
+  codeCount: number
+  redeemedCount: number
+  disabled: boolean
+  createdAt: string
+  items: { itemId: string; itemType: string; amount: number }[]
+}
+
+export type AdminEvent = {
+  eventId: string
+  name: string
+  description: string
+  startTs: number
+  endTs: number
+  startTime: string
+  endTime: string
+  available: boolean
+  charts: string
+  rewardTiers: string
+  communityMilestones: string
+  communityFragments: number
+  communityClears: number
+  participantCount: number
+}
+
+export type AdminEventLadder = {
+  eventId: string
+  entries: Array<{
+    rank: number
+    userId: number
+    name: string
+    points: number
+  }>
+}
+
 export type UserCheckinStatus = {
   user: AdminUserSummary
   today: string
@@ -241,6 +546,17 @@ export type PresentPayload = {
   item_id: string
   item_type: string
   amount?: string
+  web_only?: boolean
+}
+
+export type PresentPublishPayload = {
+  present_id: string
+  expire_ts: string
+  description?: string
+  items: { item_id: string; item_type: string; amount?: number }[]
+  user_ids: number[]
+  all_users: boolean
+  web_only?: boolean
 }
 
 export type PresentDeliverPayload = UserSelectorPayload & {
@@ -248,6 +564,18 @@ export type PresentDeliverPayload = UserSelectorPayload & {
   all_users?: boolean
 }
 
+export type EventPayload = {
+  event_id: string
+  name: string
+  description?: string
+  start_ts: string
+  end_ts: string
+  charts: string
+  reward_tiers: string
+  community_milestones: string
+  available: boolean
+}
+
 export type RedeemPayload = {
   code?: string
   random_amount?: number
@@ -257,10 +585,44 @@ export type RedeemPayload = {
   amount?: string
 }
 
+export type RedeemBatchPayload = {
+  batch_id: string
+  description?: string
+  redeem_type: number
+  count: number
+  items: { item_id: string; item_type: string; amount?: number }[]
+}
+
 export type AdminOperation =
   | 'refresh_song_file_cache'
+  | 'refresh_song_file_cache_incremental'
   | 'refresh_content_bundle_cache'
   | 'refresh_all_score_rating'
+  | 'backfill_score_v2'
+  | 'refresh_chart_analytics'
+  | 'estimate_chart_constants'
+  | 'scan_score_anomalies'
+  | 'reload_game_constants'
+  | 'reconcile_charts'
+  | 'reload_songlist'
+  | 'sync_charts_from_songlist'
+  | 'purge_songplay_tokens'
+  | 'recalculate_world_progress'
+  | 'rebuild_recent30'
+  | 'vacuum_expired_presents'
+  | 'export_user_data'
+  | 'reload_config'
+
+export type OperationJob = {
+  id: string
+  operation: AdminOperation
+  userId: number | null
+  status: 'running' | 'succeeded' | 'failed'
+  progress: number | null
+  error: string | null
+  startedAt: string
+  finishedAt: string | null
+}
 
 async function request<T>(
   path: string,
@@ -283,6 +645,18 @@ async function request<T>(
   return data.value as T
 }
 
+async function upload<T>(path: string, body: FormData): Promise<T> {
+  // Let the browser set the multipart boundary.
+  const response = await fetch(path, { method: 'POST', credentials: 'include', body })
+
+  const data = (await response.json()) as ApiEnvelope<T>
+  if (!response.ok || !data.success) {
+    throw new Error(data.message ?? `Request failed: ${data.error_code ?? response.status}`)
+  }
+
+  return data.value as T
+}
+
 function query(params: Record<string, string | number | undefined>) {
   const search = new URLSearchParams()
   for (const [key, value] of Object.entries(params)) {
@@ -311,15 +685,19 @@ export const adminApi = {
     request<UserCheckinStatus>('/web/api/checkin', {
       method: 'POST',
     }),
-  operation: (operation: AdminOperation) =>
-    request<void>(`/web/api/operations/${operation}`, {
+  operation: (operation: AdminOperation, params: { userId?: number } = {}) =>
+    request<OperationJob>(`/web/api/operations/${operation}${query({ user_id: params.userId })}`, {
       method: 'POST',
     }),
+  operationJobs: () => request<OperationJob[]>('/web/api/operation-jobs'),
+  operationJob: (id: string) =>
+    request<OperationJob>(`/web/api/operation-jobs/${encodeURIComponent(id)}`),
   users: (params: PageParams & { q?: string; status?: string }) =>
     request<PageData<UserRow>>(
       `/web/api/users${query({
         q: params.q,
         status: params.status,
+        sort: params.sort,
         page: params.page,
         page_size: params.pageSize,
       })}`,
@@ -332,10 +710,16 @@ export const adminApi = {
         body: JSON.stringify({ enabled }),
       },
     ),
+  setShadowBan: (userId: number, enabled: boolean) =>
+    request<AdminActionResult>(`/web/api/users/${userId}/shadow-ban`, {
+      method: 'PATCH',
+      body: JSON.stringify({ enabled }),
+    }),
   songs: (params: PageParams & { q?: string }) =>
     request<PageData<SongRow>>(
       `/web/api/songs${query({
         q: params.q,
+        sort: params.sort,
         page: params.page,
         page_size: params.pageSize,
       })}`,
@@ -355,6 +739,18 @@ export const adminApi = {
       method: 'PATCH',
       body: JSON.stringify(payload),
     }),
+  chartConstantProposals: () =>
+    request<ChartConstantProposal[]>('/web/api/chart-constant-proposals'),
+  acceptChartConstantProposal: (sid: string, difficulty: number) =>
+    request<void>('/web/api/chart-constant-proposals/accept', {
+      method: 'POST',
+      body: JSON.stringify({ sid, difficulty }),
+    }),
+  dismissChartConstantProposal: (sid: string, difficulty: number) =>
+    request<void>('/web/api/chart-constant-proposals', {
+      method: 'DELETE',
+      body: JSON.stringify({ sid, difficulty }),
+    }),
   deleteSong: (sid: string) =>
     request<void>('/web/api/songs', {
       method: 'DELETE',
@@ -364,6 +760,7 @@ export const adminApi = {
     request<PageData<ItemRow>>(
       `/web/api/items${query({
         q: params.q,
+        sort: params.sort,
         page: params.page,
         page_size: params.pageSize,
       })}`,
@@ -441,6 +838,25 @@ export const adminApi = {
         user_code: params.user_code,
       })}`,
     ),
+  userPttHistory: (params: UserSelectorPayload & { days?: number }) =>
+    request<PttHistory>(
+      `/web/api/user-ptt-history${query({
+        user_id: params.user_id,
+        name: params.name,
+        user_code: params.user_code,
+        days: params.days,
+      })}`,
+    ),
+  userRecent30Diff: (params: UserSelectorPayload, from: number, to: number) =>
+    request<Recent30Diff>(
+      `/web/api/user-ptt-history/diff${query({
+        user_id: params.user_id,
+        name: params.name,
+        user_code: params.user_code,
+        from,
+        to,
+      })}`,
+    ),
   scoreImages: (params: UserSelectorPayload) =>
     request<ScoreImages>(
       `/web/api/score-images${query({
@@ -457,6 +873,96 @@ export const adminApi = {
         limit: params.limit,
       })}`,
     ),
+  chartAnalytics: (
+    params: PageParams & { q?: string; sort?: ChartAnalyticsSort; minPlayers?: number },
+  ) =>
+    request<PageData<ChartAnalyticsRow>>(
+      `/web/api/chart-analytics${query({
+        q: params.q,
+        sort: params.sort,
+        min_players: params.minPlayers,
+        page: params.page,
+        page_size: params.pageSize,
+      })}`,
+    ),
+  anomalies: (params: PageParams & { q?: string; includeReviewed?: boolean }) =>
+    request<PageData<AnomalyRow>>(
+      `/web/api/anomalies${query({
+        q: params.q,
+        include_reviewed: params.includeReviewed,
+        page: params.page,
+        page_size: params.pageSize,
+      })}`,
+    ),
+  scoreAudits: (params: PageParams & { q?: string; includeReviewed?: boolean }) =>
+    request<PageData<ScoreAuditRow>>(
+      `/web/api/score-audits${query({
+        q: params.q,
+        include_reviewed: params.includeReviewed,
+        page: params.page,
+        page_size: params.pageSize,
+      })}`,
+    ),
+  requestLog: (params: PageParams & { q?: string; path?: string; status?: number }) =>
+    request<PageData<ApiLogRow>>(
+      `/web/api/request-log${query({
+        q: params.q,
+        path: params.path,
+        status: params.status,
+        page: params.page,
+        page_size: params.pageSize,
+      })}`,
+    ),
+  replays: (params: PageParams & { q?: string }) =>
+    request<PageData<ReplayRow>>(
+      `/web/api/replays${query({
+        q: params.q,
+        page: params.page,
+        page_size: params.pageSize,
+      })}`,
+    ),
+  replayFileUrl: (row: ReplayRow) =>
+    `/web/api/replays/file${query({
+      user_id: row.userId,
+      song_id: row.songId,
+      difficulty: row.difficulty,
+      time_played: row.timePlayed,
+    })}`,
+  chartMismatches: () => request<ChartMismatchReport>('/web/api/chart-mismatches'),
+  reloadSonglist: () =>
+    request<ChartMismatchReport>('/web/api/songlist/reload', { method: 'POST' }),
+  bundles: () => request<BundleRow[]>('/web/api/bundles'),
+  uploadBundle: (metadata: File, bundle: File) => {
+    const body = new FormData()
+    body.append('metadata', metadata)
+    body.append('bundle', bundle)
+    return upload<BundleRow>('/web/api/bundles/upload', body)
+  },
+  uploadSong: (payload: {
+    package: File
+    songId?: string
+    name?: string
+    insertChart: boolean
+    overwrite: boolean
+  }) => {
+    const body = new FormData()
+    body.append('package', payload.package)
+    if (payload.songId) body.append('song_id', payload.songId)
+    if (payload.name) body.append('name', payload.name)
+    body.append('insert_chart', String(payload.insertChart))
+    body.append('overwrite', String(payload.overwrite))
+    return upload<SongUploadResult>('/web/api/songs/upload', body)
+  },
+  reviewAnomaly: (payload: { user_id: number; note?: string }) =>
+    request<AdminActionResult>('/web/api/admin-actions/anomaly-review', {
+      method: 'POST',
+      body: JSON.stringify(payload),
+    }),
+  reviewScoreAudit: (payload: { audit_id: number; note?: string }) =>
+    request<AdminActionResult>('/web/api/admin-actions/score-audit-review', {
+      method: 'POST',
+      body: JSON.stringify(payload),
+    }),
   updateUserTicket: (payload: UserTicketPayload) =>
     request<AdminActionResult>('/web/api/admin-actions/user-ticket', {
       method: 'POST',
@@ -477,6 +983,60 @@ export const adminApi = {
       method: 'POST',
       body: JSON.stringify(payload),
     }),
+  userSaves: (params: UserSelectorPayload) =>
+    request<AdminUserSaves>(
+      `/web/api/user-saves${query({
+        user_id: params.user_id,
+        name: params.name,
+        user_code: params.user_code,
+      })}`,
+    ),
+  userDevices: (params: UserSelectorPayload) =>
+    request<AdminUserDevices>(
+      `/web/api/user-devices${query({
+        user_id: params.user_id,
+        name: params.name,
+        user_code: params.user_code,
+      })}`,
+    ),
+  userPurchases: (params: UserSelectorPayload) =>
+    request<AdminUserPurchases>(
+      `/web/api/user-purchases${query({
+        user_id: params.user_id,
+        name: params.name,
+        user_code: params.user_code,
+      })}`,
+    ),
+  userDownloadQuota: (params: UserSelectorPayload) =>
+    request<AdminUserDownloadQuota>(
+      `/web/api/user-download-quota${query({
+        user_id: params.user_id,
+        name: params.name,
+        user_code: params.user_code,
+      })}`,
+    ),
+  updateUserDownloadQuota: (payload: UserDownloadQuotaPayload) =>
+    request<AdminUserDownloadQuota>('/web/api/admin-actions/user-download-quota', {
+      method: 'POST',
+      body: JSON.stringify(payload),
+    }),
+  impersonateUser: (payload: UserSelectorPayload & { reason: string }) =>
+    request<AdminImpersonationToken>('/web/api/admin-actions/user-impersonate', {
+      method: 'POST',
+      body: JSON.stringify(payload),
+    }),
+  impersonationLog: (params: PageParams) =>
+    request<PageData<ImpersonationLogRow>>(
+      `/web/api/impersonation-log${query({
+        page: params.page,
+        page_size: params.pageSize,
+      })}`,
+    ),
+  rollbackUserSave: (payload: UserSelectorPayload & { history_id: number }) =>
+    request<AdminActionResult>('/web/api/admin-actions/user-save/rollback', {
+      method: 'POST',
+      body: JSON.stringify(payload),
+    }),
   updateUserPurchase: (payload: UserPurchasePayload) =>
     request<AdminActionResult>('/web/api/admin-actions/user-purchase', {
       method: 'POST',
@@ -493,11 +1053,25 @@ export const adminApi = {
         code,
       })}`,
     ),
+  presents: (params: PageParams & { q?: string; sort?: PresentSort }) =>
+    request<PageData<PresentRow>>(
+      `/web/api/presents${query({
+        q: params.q,
+        sort: params.sort,
+        page: params.page,
+        page_size: params.pageSize,
+      })}`,
+    ),
   createPresent: (payload: PresentPayload) =>
     request<AdminActionResult>('/web/api/admin-actions/presents', {
       method: 'POST',
       body: JSON.stringify(payload),
     }),
+  publishPresent: (payload: PresentPublishPayload) =>
+    request<AdminActionResult>('/web/api/admin-actions/presents/create', {
+      method: 'POST',
+      body: JSON.stringify(payload),
+    }),
   deletePresent: (present_id: string) =>
     request<AdminActionResult>('/web/api/admin-actions/presents', {
       method: 'DELETE',
@@ -518,4 +1092,60 @@ export const adminApi = {
       method: 'DELETE',
       body: JSON.stringify({ code }),
     }),
+  redeemBatches: () => request<AdminRedeemBatch[]>('/web/api/redeem-batches'),
+  createRedeemBatch: (payload: RedeemBatchPayload) =>
+    request<AdminActionResult>('/web/api/admin-actions/redeem-batches', {
+      method: 'POST',
+      body: JSON.stringify(payload),
+    }),
+  setRedeemBatchDisabled: (batch_id: string, disabled: boolean) =>
+    request<AdminActionResult>('/web/api/admin-actions/redeem-batches/disable', {
+      method: 'POST',
+      body: JSON.stringify({ batch_id, disabled }),
+    }),
+  redeemBatchExportUrl: (batchId: string) =>
+    `/web/api/redeem-batches/export${query({ batch_id: batchId })}`,
+  gameConstants: () => request<GameConstant[]>('/web/api/game-constants'),
+  setGameConstant: (name: string, value: number | null) =>
+    request<AdminActionResult>('/web/api/admin-actions/game-constants', {
+      method: 'POST',
+      body: JSON.stringify({ name, value }),
+    }),
+  events: () => request<AdminEvent[]>('/web/api/events'),
+  eventLadder: (params: { event_id: string; limit?: number }) =>
+    request<AdminEventLadder>(
+      `/web/api/event-ladder${query({
+        event_id: params.event_id,
+        limit: params.limit,
+      })}`,
+    ),
+  saveEvent: (payload: EventPayload) =>
+    request<AdminActionResult>('/web/api/admin-actions/events', {
+      method: 'POST',
+      body: JSON.stringify(payload),
+    }),
+  deleteEvent: (event_id: string) =>
+    request<AdminActionResult>('/web/api/admin-actions/events', {
+      method: 'DELETE',
+      body: JSON.stringify({ event_id }),
+    }),
+}
+
+/** Start an operation and poll its job until it finishes; rejects when the job fails. */
+export async function runOperation(
+  operation: AdminOperation,
+  params: { userId?: number } = {},
+  onProgress?: (job: OperationJob) => void,
+) {
+  let job = await adminApi.operation(operation, params)
+  onProgress?.(job)
+  while (job.status === 'running') {
+    await new Promise((resolve) => setTimeout(resolve, 1000))
+    job = await adminApi.operationJob(job.id)
+    onProgress?.(job)
+  }
+  if (job.status === 'failed') {
+    throw new Error(job.error ?? '操作失败')
+  }
+  return job
 }
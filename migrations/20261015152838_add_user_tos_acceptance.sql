@@ -0,0 +1,8 @@
+CREATE TABLE IF NOT EXISTS user_tos_acceptance (
+  user_id INT NOT NULL,
+  version VARCHAR(64) NOT NULL,
+  accepted_at BIGINT NOT NULL,
+  ip VARCHAR(64),
+  PRIMARY KEY (user_id, version),
+  INDEX idx_user_tos_acceptance_user_time (user_id, accepted_at)
+);
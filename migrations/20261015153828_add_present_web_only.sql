@@ -0,0 +1,2 @@
+ALTER TABLE present
+  ADD COLUMN is_web_only TINYINT NOT NULL DEFAULT 0;
@@ -0,0 +1,18 @@
+CREATE TABLE IF NOT EXISTS federated_score (
+  source VARCHAR(64) NOT NULL,
+  player_key VARCHAR(64) NOT NULL,
+  player_name VARCHAR(64),
+  song_id VARCHAR(255) NOT NULL,
+  difficulty INT NOT NULL,
+  score INT NOT NULL,
+  shiny_perfect_count INT NOT NULL DEFAULT 0,
+  perfect_count INT NOT NULL DEFAULT 0,
+  near_count INT NOT NULL DEFAULT 0,
+  miss_count INT NOT NULL DEFAULT 0,
+  clear_type INT NOT NULL DEFAULT 0,
+  rating DOUBLE NOT NULL DEFAULT 0,
+  time_played BIGINT NOT NULL DEFAULT 0,
+  received_at BIGINT NOT NULL,
+  PRIMARY KEY (source, player_key, song_id, difficulty),
+  INDEX idx_federated_score_chart (song_id, difficulty, score)
+);
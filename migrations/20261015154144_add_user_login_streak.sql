@@ -0,0 +1,3 @@
+ALTER TABLE user
+  ADD COLUMN login_streak INT NOT NULL DEFAULT 0,
+  ADD COLUMN last_login_bonus_day INT NULL;
@@ -0,0 +1,29 @@
+CREATE TABLE IF NOT EXISTS achievement (
+  achievement_id VARCHAR(64) PRIMARY KEY,
+  name VARCHAR(255) NOT NULL,
+  description TEXT,
+  kind VARCHAR(32) NOT NULL,
+  target INT NOT NULL DEFAULT 1,
+  param VARCHAR(255),
+  reward_type VARCHAR(32),
+  reward_item_id VARCHAR(255),
+  reward_amount INT NOT NULL DEFAULT 0,
+  sort_order INT NOT NULL DEFAULT 0,
+  is_available TINYINT NOT NULL DEFAULT 1
+);
+
+CREATE TABLE IF NOT EXISTS user_achievement (
+  user_id INT NOT NULL,
+  achievement_id VARCHAR(64) NOT NULL,
+  progress INT NOT NULL DEFAULT 0,
+  target INT NOT NULL DEFAULT 0,
+  achieved_at BIGINT,
+  PRIMARY KEY (user_id, achievement_id)
+);
+
+INSERT IGNORE INTO achievement
+  (achievement_id, name, description, kind, target, param, reward_type, reward_item_id, reward_amount, sort_order)
+VALUES
+  ('first_pure_memory', 'First Pure Memory', 'Get a Pure Memory on any chart.', 'pure_memory_count', 1, NULL, 'memory', 'memory', 10, 10),
+  ('play_count_100', 'Regular', 'Play 100 charts.', 'play_count', 100, NULL, 'fragment', 'fragment', 500, 20),
+  ('pack_clear_base', 'Arcaea Cleared', 'Clear every chart in the Arcaea pack.', 'pack_clear', 0, 'base', 'core', 'core_generic', 5, 30);
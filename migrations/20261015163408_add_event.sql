@@ -0,0 +1,36 @@
+CREATE TABLE IF NOT EXISTS event (
+  event_id VARCHAR(64) PRIMARY KEY,
+  name VARCHAR(255) NOT NULL,
+  description TEXT,
+  start_ts BIGINT NOT NULL,
+  end_ts BIGINT NOT NULL,
+  is_available TINYINT NOT NULL DEFAULT 1
+);
+
+CREATE TABLE IF NOT EXISTS event_chart (
+  event_id VARCHAR(64) NOT NULL,
+  song_id VARCHAR(255) NOT NULL,
+  difficulty INT NOT NULL,
+  point_multiplier INT NOT NULL DEFAULT 1,
+  PRIMARY KEY (event_id, song_id, difficulty),
+  INDEX idx_event_chart_chart (song_id, difficulty)
+);
+
+CREATE TABLE IF NOT EXISTS event_reward_tier (
+  event_id VARCHAR(64) NOT NULL,
+  points INT NOT NULL,
+  reward_type VARCHAR(32) NOT NULL,
+  reward_item_id VARCHAR(255) NOT NULL,
+  reward_amount INT NOT NULL,
+  PRIMARY KEY (event_id, points)
+);
+
+CREATE TABLE IF NOT EXISTS user_event (
+  user_id INT NOT NULL,
+  event_id VARCHAR(64) NOT NULL,
+  points INT NOT NULL DEFAULT 0,
+  rewarded_points INT NOT NULL DEFAULT 0,
+  updated_at BIGINT NOT NULL DEFAULT 0,
+  PRIMARY KEY (user_id, event_id),
+  INDEX idx_user_event_ladder (event_id, points)
+);
@@ -0,0 +1,15 @@
+CREATE TABLE IF NOT EXISTS user_save_history (
+  history_id BIGINT AUTO_INCREMENT PRIMARY KEY,
+  user_id INT NOT NULL,
+  scores_data MEDIUMTEXT,
+  clearlamps_data MEDIUMTEXT,
+  clearedsongs_data MEDIUMTEXT,
+  unlocklist_data MEDIUMTEXT,
+  installid_data MEDIUMTEXT,
+  devicemodelname_data MEDIUMTEXT,
+  story_data MEDIUMTEXT,
+  createdAt BIGINT,
+  finalestate_data MEDIUMTEXT,
+  archived_at BIGINT NOT NULL,
+  INDEX idx_user_save_history_user (user_id, history_id)
+);
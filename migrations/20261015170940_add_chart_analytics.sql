@@ -0,0 +1,14 @@
+CREATE TABLE IF NOT EXISTS chart_analytics (
+  song_id VARCHAR(255) NOT NULL,
+  difficulty INT NOT NULL,
+  player_count INT NOT NULL DEFAULT 0,
+  avg_score DOUBLE NOT NULL DEFAULT 0,
+  avg_accuracy DOUBLE NOT NULL DEFAULT 0,
+  clear_rate DOUBLE NOT NULL DEFAULT 0,
+  -- JSON array of player counts per score bucket, see SCORE_BUCKETS
+  score_distribution TEXT NOT NULL,
+  perceived_constant DOUBLE,
+  sample_count INT NOT NULL DEFAULT 0,
+  computed_at BIGINT NOT NULL,
+  PRIMARY KEY (song_id, difficulty)
+);
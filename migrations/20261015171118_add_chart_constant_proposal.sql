@@ -0,0 +1,10 @@
+CREATE TABLE IF NOT EXISTS chart_constant_proposal (
+  song_id VARCHAR(255) NOT NULL,
+  difficulty INT NOT NULL,
+  -- Proposed constant in tenths, same unit as chart.rating_*
+  proposed_rating INT NOT NULL,
+  sample_count INT NOT NULL DEFAULT 0,
+  model VARCHAR(32) NOT NULL,
+  created_at BIGINT NOT NULL,
+  PRIMARY KEY (song_id, difficulty)
+);
@@ -0,0 +1,16 @@
+CREATE TABLE IF NOT EXISTS user_anomaly (
+  user_id INT PRIMARY KEY,
+  score DOUBLE NOT NULL DEFAULT 0,
+  rating_spikes INT NOT NULL DEFAULT 0,
+  shared_fingerprints INT NOT NULL DEFAULT 0,
+  burst_plays INT NOT NULL DEFAULT 0,
+  play_count INT NOT NULL DEFAULT 0,
+  -- Comma-separated user ids sharing judgement fingerprints with this user
+  linked_users TEXT NOT NULL,
+  computed_at BIGINT NOT NULL,
+  reviewed_at BIGINT,
+  -- Score at review time; a higher score on a later scan reopens the entry
+  reviewed_score DOUBLE,
+  review_note VARCHAR(255) NOT NULL DEFAULT '',
+  INDEX idx_user_anomaly_queue (reviewed_at, score)
+);
@@ -0,0 +1,13 @@
+CREATE TABLE IF NOT EXISTS user_device_fingerprint (
+  user_id INT NOT NULL,
+  -- `device_id` (login DeviceId header) or `install_id` (cloud save)
+  kind VARCHAR(16) NOT NULL,
+  value VARCHAR(255) NOT NULL,
+  device_model VARCHAR(255) NOT NULL DEFAULT '',
+  first_seen BIGINT NOT NULL,
+  last_seen BIGINT NOT NULL,
+  last_ip VARCHAR(64),
+  seen_count INT NOT NULL DEFAULT 1,
+  PRIMARY KEY (user_id, kind, value),
+  INDEX idx_user_device_fingerprint_value (kind, value)
+);
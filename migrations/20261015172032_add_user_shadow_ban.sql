@@ -0,0 +1,2 @@
+ALTER TABLE user
+  ADD COLUMN is_shadow_banned TINYINT NOT NULL DEFAULT 0;
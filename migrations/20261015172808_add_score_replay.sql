@@ -0,0 +1,13 @@
+CREATE TABLE IF NOT EXISTS score_replay (
+  user_id INT NOT NULL,
+  song_id VARCHAR(255) NOT NULL,
+  difficulty INT NOT NULL,
+  -- `time_played` of the `user_score` play the replay belongs to
+  time_played BIGINT NOT NULL,
+  storage_key VARCHAR(512) NOT NULL,
+  size INT NOT NULL,
+  sha256 CHAR(64) NOT NULL,
+  uploaded_at BIGINT NOT NULL,
+  PRIMARY KEY (user_id, song_id, difficulty, time_played),
+  INDEX idx_score_replay_uploaded_at (uploaded_at)
+);
@@ -0,0 +1,5 @@
+-- Overrides for tunable game balance values; missing names keep their defaults
+CREATE TABLE IF NOT EXISTS game_constants (
+  name VARCHAR(64) PRIMARY KEY,
+  value DOUBLE NOT NULL
+);
@@ -0,0 +1,6 @@
+-- Each download token carries its own expiry instead of sharing one global window
+ALTER TABLE download_token ADD COLUMN IF NOT EXISTS expires_at BIGINT NOT NULL DEFAULT 0;
+UPDATE download_token SET expires_at = COALESCE(time, 0) + 1000 WHERE expires_at = 0;
+
+CREATE INDEX IF NOT EXISTS idx_download_token_token ON download_token (token);
+CREATE INDEX IF NOT EXISTS idx_download_token_expires_at ON download_token (expires_at);
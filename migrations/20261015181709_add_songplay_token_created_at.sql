@@ -0,0 +1,5 @@
+-- Issue time of play tokens so abandoned ones can be purged
+ALTER TABLE songplay_token ADD COLUMN IF NOT EXISTS created_at BIGINT NOT NULL DEFAULT 0;
+UPDATE songplay_token SET created_at = UNIX_TIMESTAMP() * 1000 WHERE created_at = 0;
+
+CREATE INDEX IF NOT EXISTS idx_songplay_token_created_at ON songplay_token (created_at);
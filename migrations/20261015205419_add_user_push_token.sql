@@ -0,0 +1,10 @@
+CREATE TABLE IF NOT EXISTS user_push_token (
+  -- `fcm` or `apns`
+  platform VARCHAR(16) NOT NULL,
+  token VARCHAR(255) NOT NULL,
+  user_id INT NOT NULL,
+  created_at BIGINT NOT NULL,
+  updated_at BIGINT NOT NULL,
+  PRIMARY KEY (platform, token),
+  INDEX idx_user_push_token_user_id (user_id, updated_at)
+);
@@ -0,0 +1,13 @@
+-- Completed purchases, kept for the player's purchase history and admin review
+CREATE TABLE IF NOT EXISTS purchase_log (
+  id BIGINT NOT NULL AUTO_INCREMENT PRIMARY KEY,
+  user_id INT NOT NULL,
+  -- `pack`, `single` or `special`
+  kind VARCHAR(16) NOT NULL,
+  purchase_name VARCHAR(255) NOT NULL,
+  price INT NOT NULL,
+  -- `memory`, `fragment` or the discount ticket item that was spent
+  currency VARCHAR(64) NOT NULL,
+  time BIGINT NOT NULL,
+  INDEX idx_purchase_log_user_time (user_id, time)
+);
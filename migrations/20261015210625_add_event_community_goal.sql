@@ -0,0 +1,19 @@
+CREATE TABLE IF NOT EXISTS event_community_milestone (
+  event_id VARCHAR(64) NOT NULL,
+  -- `fragments` or `clears`
+  metric VARCHAR(16) NOT NULL,
+  target BIGINT NOT NULL,
+  reward_type VARCHAR(32) NOT NULL,
+  reward_item_id VARCHAR(255) NOT NULL,
+  reward_amount INT NOT NULL,
+  -- Set once the milestone's present has been sent to every player.
+  reached_at BIGINT,
+  PRIMARY KEY (event_id, metric, target)
+);
+
+CREATE TABLE IF NOT EXISTS event_community_progress (
+  event_id VARCHAR(64) NOT NULL,
+  metric VARCHAR(16) NOT NULL,
+  value BIGINT NOT NULL DEFAULT 0,
+  PRIMARY KEY (event_id, metric)
+);
@@ -0,0 +1,14 @@
+-- Issued with every score submission so the client can attach the replay of
+-- exactly that play.
+CREATE TABLE IF NOT EXISTS score_replay_token (
+  token CHAR(32) PRIMARY KEY,
+  user_id INT NOT NULL,
+  song_id VARCHAR(255) NOT NULL,
+  difficulty INT NOT NULL,
+  time_played BIGINT NOT NULL,
+  expires_at BIGINT NOT NULL,
+  INDEX idx_score_replay_token_user (user_id, expires_at)
+);
+
+-- `gzip`, `zlib` or `zstd`, detected from the uploaded blob.
+ALTER TABLE score_replay ADD COLUMN compression VARCHAR(8) NOT NULL DEFAULT '';
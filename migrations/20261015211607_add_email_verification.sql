@@ -0,0 +1,12 @@
+-- Existing accounts count as verified; registration clears the flag while
+-- email verification is enabled.
+ALTER TABLE user ADD COLUMN is_email_verified TINYINT NOT NULL DEFAULT 1;
+
+CREATE TABLE IF NOT EXISTS email_verification (
+  user_id INT PRIMARY KEY,
+  -- SHA-256 of the mailed code
+  code_hash CHAR(64) NOT NULL,
+  expires_at BIGINT NOT NULL,
+  sent_at BIGINT NOT NULL,
+  attempts INT NOT NULL DEFAULT 0
+);
@@ -0,0 +1,14 @@
+-- Pack and single purchases charged through an external payment provider
+CREATE TABLE IF NOT EXISTS purchase_order (
+  order_id VARCHAR(64) PRIMARY KEY,
+  user_id INT NOT NULL,
+  purchase_name VARCHAR(255) NOT NULL,
+  -- `PAYMENT_PROVIDER` name, e.g. `free` or `webhook`
+  provider VARCHAR(32) NOT NULL,
+  price INT NOT NULL,
+  -- `pending`, `paid` or `failed`
+  status VARCHAR(16) NOT NULL,
+  created_at BIGINT NOT NULL,
+  completed_at BIGINT NULL,
+  INDEX idx_purchase_order_user (user_id)
+);
@@ -0,0 +1,13 @@
+-- Redeem codes generated together by the admin panel
+CREATE TABLE IF NOT EXISTS redeem_batch (
+  batch_id VARCHAR(64) PRIMARY KEY,
+  description TEXT,
+  -- same as `redeem.type`: 0 single-use, 1 multi-use, 2 fragment
+  type INT NOT NULL,
+  code_count INT NOT NULL,
+  is_disabled TINYINT NOT NULL DEFAULT 0,
+  created_at BIGINT NOT NULL
+);
+
+ALTER TABLE redeem ADD COLUMN batch_id VARCHAR(64) NULL;
+CREATE INDEX idx_redeem_batch ON redeem (batch_id);
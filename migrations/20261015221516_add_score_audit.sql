@@ -0,0 +1,22 @@
+-- Submissions flagged by the submission-time score validator
+CREATE TABLE IF NOT EXISTS score_audit (
+  audit_id BIGINT AUTO_INCREMENT PRIMARY KEY,
+  user_id INT NOT NULL,
+  song_id VARCHAR(255) NOT NULL,
+  difficulty INT NOT NULL,
+  score INT NOT NULL,
+  shiny_perfect_count INT NOT NULL,
+  perfect_count INT NOT NULL,
+  near_count INT NOT NULL,
+  miss_count INT NOT NULL,
+  clear_type INT NOT NULL,
+  -- Comma-separated flag names, e.g. `shiny_exceeds_perfect,note_count_mismatch`
+  flags VARCHAR(255) NOT NULL,
+  -- 1 when the submission was refused instead of recorded
+  rejected TINYINT NOT NULL DEFAULT 0,
+  created_at BIGINT NOT NULL,
+  reviewed_at BIGINT,
+  review_note VARCHAR(255) NOT NULL DEFAULT '',
+  INDEX idx_score_audit_queue (reviewed_at, created_at),
+  INDEX idx_score_audit_user (user_id)
+);
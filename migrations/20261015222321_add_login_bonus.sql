@@ -0,0 +1,10 @@
+-- One row per daily login bonus granted; the streak itself stays on `user`
+CREATE TABLE IF NOT EXISTS login_bonus (
+  user_id INT NOT NULL,
+  -- Day number since the epoch, shifted by the bonus reset offset
+  bonus_day INT NOT NULL,
+  streak INT NOT NULL,
+  present_id VARCHAR(255) NOT NULL,
+  granted_at BIGINT NOT NULL,
+  PRIMARY KEY (user_id, bonus_day)
+);
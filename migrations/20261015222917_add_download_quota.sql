@@ -0,0 +1,18 @@
+-- Song file downloads per user and hour, summed over a rolling 24 hours
+CREATE TABLE IF NOT EXISTS user_download_usage (
+  user_id INT NOT NULL,
+  -- Unix time in hours
+  hour_bucket INT NOT NULL,
+  download_count INT NOT NULL DEFAULT 0,
+  download_bytes BIGINT NOT NULL DEFAULT 0,
+  PRIMARY KEY (user_id, hour_bucket)
+);
+
+-- Per-user quota overrides set from the admin panel; NULL keeps the
+-- configured `download_times_limit` / `download_bytes_limit`, 0 is unlimited
+CREATE TABLE IF NOT EXISTS user_download_quota (
+  user_id INT PRIMARY KEY,
+  times_limit INT NULL,
+  bytes_limit BIGINT NULL,
+  updated_at BIGINT NOT NULL
+);
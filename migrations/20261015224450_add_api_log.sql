@@ -0,0 +1,15 @@
+-- Game API requests kept for `api_log_retention_days`, searched from the
+-- admin panel
+CREATE TABLE IF NOT EXISTS api_log (
+  log_id BIGINT AUTO_INCREMENT PRIMARY KEY,
+  created_at BIGINT NOT NULL,
+  method VARCHAR(8) NOT NULL,
+  path VARCHAR(255) NOT NULL,
+  -- NULL when the request was not authenticated
+  user_id INT NULL,
+  status SMALLINT NOT NULL,
+  latency_ms INT NOT NULL,
+  ip VARCHAR(45) NULL,
+  INDEX idx_api_log_created_at (created_at),
+  INDEX idx_api_log_user (user_id, created_at)
+);
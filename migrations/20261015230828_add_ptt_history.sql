@@ -0,0 +1,15 @@
+-- Daily snapshots of each player's potential and recent 30 plays, kept for
+-- `ptt_history_retention_days` and charted in the admin panel
+CREATE TABLE IF NOT EXISTS ptt_history (
+  user_id INT NOT NULL,
+  -- local midnight in seconds, like user_rating.time
+  day BIGINT NOT NULL,
+  rating_ptt INT NOT NULL,
+  best30 DOUBLE NOT NULL,
+  recent10 DOUBLE NOT NULL,
+  -- JSON array of the recent30 rows at snapshot time
+  recent30 MEDIUMTEXT NOT NULL,
+  created_at BIGINT NOT NULL,
+  PRIMARY KEY (user_id, day),
+  INDEX idx_ptt_history_day (day)
+);
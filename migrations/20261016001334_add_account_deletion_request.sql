@@ -0,0 +1,10 @@
+-- Pending self-service account deletions. Confirming one anonymizes the
+-- `user` row in place, so its scores stay under the same (tombstone) id.
+CREATE TABLE IF NOT EXISTS account_deletion_request (
+  user_id INT PRIMARY KEY,
+  requested_at BIGINT NOT NULL,
+  -- End of the cooldown; the deletion cannot be confirmed before this
+  confirmable_at BIGINT NOT NULL,
+  -- Unconfirmed requests lapse at this time
+  expires_at BIGINT NOT NULL
+);
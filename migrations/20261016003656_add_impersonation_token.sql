@@ -0,0 +1,15 @@
+-- Short-lived game tokens minted by admins to act as a player while
+-- debugging. Rows stay after expiry as the audit log.
+CREATE TABLE IF NOT EXISTS impersonation_token (
+  token_id BIGINT AUTO_INCREMENT PRIMARY KEY,
+  access_token VARCHAR(255) NOT NULL,
+  user_id INT NOT NULL,
+  admin_user_id INT NOT NULL,
+  reason VARCHAR(255) NOT NULL,
+  created_at BIGINT NOT NULL,
+  expires_at BIGINT NOT NULL,
+  -- Address of the admin who minted the token
+  ip VARCHAR(45) NULL,
+  UNIQUE INDEX idx_impersonation_token (access_token),
+  INDEX idx_impersonation_token_created_at (created_at)
+);
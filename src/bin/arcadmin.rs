@@ -0,0 +1,457 @@
+//! Database maintenance commands
+//!
+//! `backup` dumps player tables to a portable JSON archive, `restore` loads
+//! such an archive into another instance and `vacuum` rebuilds tables to
+//! reclaim space. An archive maps table names to rows keyed by column name,
+//! so one can also be assembled from another server's database, e.g. the
+//! SQLite database of the Python server exported with `sqlite3 -json`.
+//! `import-python` reads that database directly instead, remapping the
+//! players whose ids or codes are taken here.
+
+use anyhow::{bail, Context, Result};
+use base64::Engine;
+use clap::{Parser, Subcommand};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use sqlx::mysql::MySqlArguments;
+use sqlx::query::Query;
+use sqlx::MySql;
+use std::collections::{BTreeMap, HashSet};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use Arcaea_server_rs::service::python_import::ImportProgress;
+use Arcaea_server_rs::service::PythonImportService;
+use Arcaea_server_rs::{Database, DbPool};
+
+/// Archive format written by this version
+const ARCHIVE_VERSION: u32 = 1;
+
+/// Player accounts, scores and world progress, parents first
+const PLAYER_TABLES: &[&str] = &[
+    "user",
+    "user_role",
+    "friend",
+    "user_char",
+    "user_char_full",
+    "user_item",
+    "user_save",
+    "user_present",
+    "user_redeem",
+    "user_course",
+    "user_mission",
+    "user_kvdata",
+    "user_custom_course",
+    "best_score",
+    "recent30",
+    "user_world",
+];
+
+/// Bookkeeping of sqlx itself, never dumped or restored
+const MIGRATIONS_TABLE: &str = "_sqlx_migrations";
+
+#[derive(Debug, Parser)]
+#[command(name = "arcadmin")]
+#[command(about = "Back up, restore and maintain the Arcaea server database")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Dump tables to a JSON archive.
+    Backup(BackupArgs),
+    /// Load a JSON archive into this database.
+    Restore(RestoreArgs),
+    /// Rebuild tables with OPTIMIZE TABLE to reclaim space.
+    Vacuum(TableArgs),
+    /// Import players from the Python server's arcaea_database.db.
+    ImportPython(ImportPythonArgs),
+}
+
+#[derive(Debug, Parser)]
+struct TableArgs {
+    /// Only these tables. Repeatable.
+    #[arg(long = "table", value_name = "TABLE")]
+    tables: Vec<String>,
+
+    /// Every table of the database instead of the player tables.
+    #[arg(long, default_value_t = false, conflicts_with = "tables")]
+    all: bool,
+}
+
+#[derive(Debug, Parser)]
+struct BackupArgs {
+    /// Archive to write, gzip compressed when it ends in `.gz`.
+    #[arg(long, short)]
+    output: PathBuf,
+
+    #[command(flatten)]
+    tables: TableArgs,
+}
+
+#[derive(Debug, Parser)]
+struct RestoreArgs {
+    /// Archive written by `backup`, gzip compressed when it ends in `.gz`.
+    #[arg(long, short)]
+    input: PathBuf,
+
+    /// Overwrite rows with the same key instead of requiring empty tables.
+    #[arg(long, default_value_t = false)]
+    replace: bool,
+}
+
+#[derive(Debug, Parser)]
+struct ImportPythonArgs {
+    /// SQLite database of the Python server.
+    #[arg(long, short, default_value = "arcaea_database.db")]
+    input: PathBuf,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Archive {
+    version: u32,
+    #[serde(default)]
+    created_at: String,
+    tables: BTreeMap<String, Vec<Map<String, Value>>>,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    dotenv::dotenv().ok();
+    let cli = Cli::parse();
+    let pool = Database::connect()
+        .await
+        .context("failed to connect to DATABASE_URL")?;
+
+    match cli.command {
+        Command::Backup(args) => backup(&pool, args).await,
+        Command::Restore(args) => restore(&pool, args).await,
+        Command::Vacuum(args) => vacuum(&pool, args).await,
+        Command::ImportPython(args) => import_python(pool, args).await,
+    }
+}
+
+async fn backup(pool: &DbPool, args: BackupArgs) -> Result<()> {
+    let tables = selected_tables(pool, &args.tables).await?;
+    let mut archive = Archive {
+        version: ARCHIVE_VERSION,
+        created_at: chrono::Utc::now().to_rfc3339(),
+        tables: BTreeMap::new(),
+    };
+
+    for table in tables {
+        let columns = table_columns(pool, &table).await?;
+        if columns.is_empty() {
+            bail!("table `{table}` does not exist");
+        }
+        let rows = dump_table(pool, &table, &columns).await?;
+        eprintln!("dumped {table}: {} rows", rows.len());
+        archive.tables.insert(table, rows);
+    }
+
+    write_archive(&args.output, &archive)?;
+    println!(
+        "wrote {} ({} tables)",
+        args.output.display(),
+        archive.tables.len()
+    );
+    Ok(())
+}
+
+async fn restore(pool: &DbPool, args: RestoreArgs) -> Result<()> {
+    let archive = read_archive(&args.input)?;
+    if archive.version > ARCHIVE_VERSION {
+        bail!(
+            "archive version {} is newer than the supported version {ARCHIVE_VERSION}",
+            archive.version
+        );
+    }
+
+    let mut tables = Vec::new();
+    for table in restore_order(&archive) {
+        let columns: HashSet<String> = table_columns(pool, table).await?.into_iter().collect();
+        if columns.is_empty() {
+            eprintln!("skipped {table}: no such table in this database");
+            continue;
+        }
+        if !args.replace {
+            let count: i64 = sqlx::query_scalar(&format!(
+                "SELECT COUNT(*) FROM {}",
+                quote_identifier(table)?
+            ))
+            .fetch_one(pool)
+            .await?;
+            if count > 0 {
+                bail!(
+                    "table `{table}` already has {count} rows; restore into a fresh database or pass --replace"
+                );
+            }
+        }
+        tables.push((table, columns));
+    }
+
+    let verb = if args.replace { "REPLACE" } else { "INSERT" };
+    let mut tx = pool.begin().await?;
+    sqlx::query!("SET FOREIGN_KEY_CHECKS = 0")
+        .execute(&mut *tx)
+        .await?;
+    for (table, columns) in tables {
+        let mut skipped_columns = HashSet::new();
+        let rows = &archive.tables[table];
+        for row in rows {
+            let fields: Vec<(&String, &Value)> = row
+                .iter()
+                .filter(|(column, _)| {
+                    columns.contains(*column) || {
+                        skipped_columns.insert(column.as_str());
+                        false
+                    }
+                })
+                .collect();
+            if fields.is_empty() {
+                continue;
+            }
+
+            let names = fields
+                .iter()
+                .map(|(column, _)| quote_identifier(column))
+                .collect::<Result<Vec<_>>>()?;
+            let sql = format!(
+                "{verb} INTO {} ({}) VALUES ({})",
+                quote_identifier(table)?,
+                names.join(", "),
+                vec!["?"; names.len()].join(", ")
+            );
+            let mut query = sqlx::query(&sql);
+            for (_, value) in &fields {
+                query = bind_value(query, value)?;
+            }
+            query
+                .execute(&mut *tx)
+                .await
+                .with_context(|| format!("failed to restore a row of `{table}`"))?;
+        }
+        if !skipped_columns.is_empty() {
+            let mut skipped: Vec<_> = skipped_columns.into_iter().collect();
+            skipped.sort_unstable();
+            eprintln!("{table}: ignored unknown columns {}", skipped.join(", "));
+        }
+        eprintln!("restored {table}: {} rows", rows.len());
+    }
+    sqlx::query!("SET FOREIGN_KEY_CHECKS = 1")
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+
+    println!("restored {}", args.input.display());
+    Ok(())
+}
+
+async fn vacuum(pool: &DbPool, args: TableArgs) -> Result<()> {
+    for table in selected_tables(pool, &args).await? {
+        sqlx::query(&format!("OPTIMIZE TABLE {}", quote_identifier(&table)?))
+            .execute(pool)
+            .await
+            .with_context(|| format!("failed to optimize `{table}`"))?;
+        println!("optimized {table}");
+    }
+    Ok(())
+}
+
+async fn import_python(pool: DbPool, args: ImportPythonArgs) -> Result<()> {
+    let report = PythonImportService::new(pool)
+        .import(&args.input, |progress: ImportProgress| {
+            if progress.users_done % 100 == 0 || progress.users_done == progress.users_total {
+                eprintln!(
+                    "imported {}/{} players",
+                    progress.users_done, progress.users_total
+                );
+            }
+        })
+        .await?;
+
+    for name in &report.users_skipped {
+        eprintln!("skipped {name}: name already exists");
+    }
+    for (old_id, user_id) in &report.user_ids {
+        if old_id != user_id {
+            println!("remapped user {old_id} -> {user_id}");
+        }
+    }
+    println!(
+        "imported {} players ({} skipped, {} new user codes): {} best scores, {} recent30 rows, \
+         {} characters, {} friendships, {} presents, {} user presents",
+        report.users_imported,
+        report.users_skipped.len(),
+        report.user_codes_regenerated,
+        report.best_scores,
+        report.recent30,
+        report.characters,
+        report.friends,
+        report.presents,
+        report.user_presents
+    );
+    Ok(())
+}
+
+/// Tables named by `args`, the player tables by default
+async fn selected_tables(pool: &DbPool, args: &TableArgs) -> Result<Vec<String>> {
+    if args.all {
+        return Ok(sqlx::query_scalar!(
+            "SELECT CAST(TABLE_NAME AS CHAR) AS `table_name!: String`
+             FROM information_schema.TABLES
+             WHERE TABLE_SCHEMA = DATABASE() AND TABLE_TYPE = 'BASE TABLE'
+                AND TABLE_NAME != ?
+             ORDER BY TABLE_NAME",
+            MIGRATIONS_TABLE
+        )
+        .fetch_all(pool)
+        .await?);
+    }
+    if args.tables.is_empty() {
+        return Ok(PLAYER_TABLES
+            .iter()
+            .map(|table| table.to_string())
+            .collect());
+    }
+    Ok(args.tables.clone())
+}
+
+/// Columns of `table` in this database, empty when it does not exist
+async fn table_columns(pool: &DbPool, table: &str) -> Result<Vec<String>> {
+    Ok(sqlx::query_scalar!(
+        "SELECT CAST(COLUMN_NAME AS CHAR) AS `column_name!: String`
+         FROM information_schema.COLUMNS
+         WHERE TABLE_SCHEMA = DATABASE() AND TABLE_NAME = ?
+         ORDER BY ORDINAL_POSITION",
+        table
+    )
+    .fetch_all(pool)
+    .await?)
+}
+
+/// Every row of `table` as a JSON object, converted by MySQL itself so
+/// no column type needs to be known here
+async fn dump_table(
+    pool: &DbPool,
+    table: &str,
+    columns: &[String],
+) -> Result<Vec<Map<String, Value>>> {
+    let pairs = columns
+        .iter()
+        .map(|column| Ok(format!("'{column}', {}", quote_identifier(column)?)))
+        .collect::<Result<Vec<_>>>()?;
+    let sql = format!(
+        "SELECT CAST(JSON_OBJECT({}) AS CHAR) FROM {}",
+        pairs.join(", "),
+        quote_identifier(table)?
+    );
+    let rows: Vec<String> = sqlx::query_scalar(&sql).fetch_all(pool).await?;
+    rows.iter()
+        .map(|row| serde_json::from_str(row).with_context(|| format!("invalid row in `{table}`")))
+        .collect()
+}
+
+/// Archive tables in restore order: player tables first, the rest by name
+fn restore_order(archive: &Archive) -> Vec<&str> {
+    let mut tables: Vec<&str> = PLAYER_TABLES
+        .iter()
+        .copied()
+        .filter(|table| archive.tables.contains_key(*table))
+        .collect();
+    tables.extend(
+        archive
+            .tables
+            .keys()
+            .map(String::as_str)
+            .filter(|table| !PLAYER_TABLES.contains(table) && *table != MIGRATIONS_TABLE),
+    );
+    tables
+}
+
+fn bind_value<'q>(
+    query: Query<'q, MySql, MySqlArguments>,
+    value: &Value,
+) -> Result<Query<'q, MySql, MySqlArguments>> {
+    Ok(match value {
+        Value::Null => query.bind(None::<String>),
+        Value::Bool(value) => query.bind(*value),
+        Value::Number(number) => match (number.as_i64(), number.as_u64()) {
+            (Some(value), _) => query.bind(value),
+            (None, Some(value)) => query.bind(value),
+            _ => query.bind(number.as_f64().unwrap_or_default()),
+        },
+        Value::String(text) => match decode_mysql_binary(text)? {
+            Some(bytes) => query.bind(bytes),
+            None => query.bind(text.clone()),
+        },
+        Value::Array(_) | Value::Object(_) => query.bind(value.to_string()),
+    })
+}
+
+/// Bytes of a binary column, which `JSON_OBJECT` writes as
+/// `base64:type<N>:<data>`
+fn decode_mysql_binary(text: &str) -> Result<Option<Vec<u8>>> {
+    let Some(rest) = text.strip_prefix("base64:type") else {
+        return Ok(None);
+    };
+    let Some((type_id, data)) = rest.split_once(':') else {
+        return Ok(None);
+    };
+    if type_id.is_empty() || !type_id.bytes().all(|byte| byte.is_ascii_digit()) {
+        return Ok(None);
+    }
+    Ok(Some(
+        base64::engine::general_purpose::STANDARD
+            .decode(data)
+            .context("invalid base64 binary value")?,
+    ))
+}
+
+fn quote_identifier(name: &str) -> Result<String> {
+    if name.is_empty()
+        || !name
+            .bytes()
+            .all(|byte| byte.is_ascii_alphanumeric() || byte == b'_')
+    {
+        bail!("invalid table or column name `{name}`");
+    }
+    Ok(format!("`{name}`"))
+}
+
+fn is_gzip(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "gz")
+}
+
+fn write_archive(path: &Path, archive: &Archive) -> Result<()> {
+    let file = BufWriter::new(
+        File::create(path).with_context(|| format!("failed to create {}", path.display()))?,
+    );
+    if is_gzip(path) {
+        let mut encoder = GzEncoder::new(file, flate2::Compression::default());
+        serde_json::to_writer(&mut encoder, archive)?;
+        encoder.finish()?.flush()?;
+    } else {
+        let mut file = file;
+        serde_json::to_writer(&mut file, archive)?;
+        file.flush()?;
+    }
+    Ok(())
+}
+
+fn read_archive(path: &Path) -> Result<Archive> {
+    let file = BufReader::new(
+        File::open(path).with_context(|| format!("failed to open {}", path.display()))?,
+    );
+    let reader: Box<dyn Read> = if is_gzip(path) {
+        Box::new(GzDecoder::new(file))
+    } else {
+        Box::new(file)
+    };
+    serde_json::from_reader(reader).with_context(|| format!("invalid archive {}", path.display()))
+}
@@ -4,12 +4,16 @@
 //! It provides:
 //! - TCP control plane (authenticated + AES-GCM encrypted JSON)
 //! - UDP data plane (binary protocol parsing)
+//! - optional Prometheus `/metrics` endpoint (`LINKPLAY_METRICS_PORT`)
 
 use aes_gcm::aead::{AeadInPlace, KeyInit};
 use aes_gcm::{Aes128Gcm, Nonce};
 use base64::engine::general_purpose::STANDARD as BASE64;
 use base64::Engine;
 use rand::{thread_rng, Rng, RngCore};
+use rocket_prometheus::prometheus::{
+    Encoder, IntCounter, IntCounterVec, IntGauge, Opts, Registry, TextEncoder,
+};
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 use std::collections::{HashMap, HashSet};
@@ -24,6 +28,7 @@ use tracing::{error, info, warn};
 
 const PROTOCOL_NAME: [u8; 2] = [0x06, 0x16];
 const PROTOCOL_VERSION: u8 = 0x0E;
+const METRICS_REFRESH_INTERVAL_SEC: u64 = 5;
 type EncryptionPayload = ([u8; 12], [u8; 16], Vec<u8>);
 
 #[derive(Debug, Clone)]
@@ -34,6 +39,8 @@ struct LinkplayConfig {
     authentication: String,
     tcp_secret_key: String,
     tcp_max_length: usize,
+    /// HTTP port of the Prometheus endpoint; 0 disables it
+    metrics_port: u16,
 
     linkplay_unlock_length: usize,
     room_time_limit_usec: i64,
@@ -42,6 +49,7 @@ struct LinkplayConfig {
     command_interval_usec: i64,
     player_pre_timeout_usec: i64,
     player_timeout_usec: i64,
+    observer_timeout_usec: i64,
 
     countdown_song_ready_usec: i64,
     countdown_song_start_usec: i64,
@@ -61,6 +69,7 @@ impl LinkplayConfig {
         let tcp_secret_key =
             env::var("LINKPLAY_TCP_SECRET_KEY").unwrap_or_else(|_| "1145141919810".to_string());
         let tcp_max_length = env_usize("LINKPLAY_TCP_MAX_LENGTH", 0x0FFF_FFFF);
+        let metrics_port = env_u16("LINKPLAY_METRICS_PORT", 0);
 
         let linkplay_unlock_length = env_usize("LINKPLAY_UNLOCK_LENGTH", 1024);
         let room_time_limit_usec = env_i64("LINKPLAY_TIME_LIMIT_USEC", 3_600_000_000);
@@ -69,6 +78,7 @@ impl LinkplayConfig {
         let command_interval_usec = env_i64("LINKPLAY_COMMAND_INTERVAL_USEC", 1_000_000);
         let player_pre_timeout_usec = env_i64("LINKPLAY_PLAYER_PRE_TIMEOUT_USEC", 3_000_000);
         let player_timeout_usec = env_i64("LINKPLAY_PLAYER_TIMEOUT_USEC", 15_000_000);
+        let observer_timeout_usec = env_i64("LINKPLAY_OBSERVER_TIMEOUT_USEC", 60_000_000);
 
         let countdown_song_ready_usec = env_i64("LINKPLAY_COUNTDOWN_SONG_READY_USEC", 4_000_000);
         let countdown_song_start_usec = env_i64("LINKPLAY_COUNTDOWN_SONG_START_USEC", 6_000_000);
@@ -85,12 +95,14 @@ impl LinkplayConfig {
             authentication,
             tcp_secret_key,
             tcp_max_length,
+            metrics_port,
             linkplay_unlock_length,
             room_time_limit_usec,
             cleanup_interval_sec,
             command_interval_usec,
             player_pre_timeout_usec,
             player_timeout_usec,
+            observer_timeout_usec,
             countdown_song_ready_usec,
             countdown_song_start_usec,
             countdown_matching_usec,
@@ -105,6 +117,88 @@ impl LinkplayConfig {
     }
 }
 
+/// Prometheus metrics of the daemon
+struct LinkplayMetrics {
+    registry: Registry,
+    rooms: IntGauge,
+    players: IntGauge,
+    observers: IntGauge,
+    udp_packets: IntCounter,
+    commands: IntCounterVec,
+    decrypt_failures: IntCounterVec,
+}
+
+impl LinkplayMetrics {
+    fn new() -> Self {
+        let registry = Registry::new_custom(Some("linkplayd".to_string()), None)
+            .expect("valid metrics namespace");
+        let rooms = IntGauge::new("rooms", "Open link play rooms").expect("valid metric");
+        let players =
+            IntGauge::new("players", "Players holding a slot in a room").expect("valid metric");
+        let observers =
+            IntGauge::new("observers", "Active read-only room observers").expect("valid metric");
+        let udp_packets =
+            IntCounter::new("udp_packets_total", "UDP packets received").expect("valid metric");
+        let commands = IntCounterVec::new(
+            Opts::new(
+                "udp_commands_total",
+                "Client commands received, by command byte",
+            ),
+            &["command"],
+        )
+        .expect("valid metric");
+        let decrypt_failures = IntCounterVec::new(
+            Opts::new(
+                "decrypt_failures_total",
+                "Payloads that failed AES-GCM decryption, by plane",
+            ),
+            &["plane"],
+        )
+        .expect("valid metric");
+
+        for collector in [
+            Box::new(rooms.clone()) as Box<dyn rocket_prometheus::prometheus::core::Collector>,
+            Box::new(players.clone()),
+            Box::new(observers.clone()),
+            Box::new(udp_packets.clone()),
+            Box::new(commands.clone()),
+            Box::new(decrypt_failures.clone()),
+        ] {
+            registry.register(collector).expect("unique metric");
+        }
+
+        Self {
+            registry,
+            rooms,
+            players,
+            observers,
+            udp_packets,
+            commands,
+            decrypt_failures,
+        }
+    }
+
+    fn refresh(&self, store: &Store) {
+        self.rooms.set(store.rooms.len() as i64);
+        self.players.set(
+            store
+                .rooms
+                .values()
+                .map(|room| room.player_num() as i64)
+                .sum(),
+        );
+        self.observers.set(store.observers.len() as i64);
+    }
+
+    fn render(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        if let Err(err) = TextEncoder::new().encode(&self.registry.gather(), &mut out) {
+            warn!("Failed to encode metrics: {err}");
+        }
+        out
+    }
+}
+
 #[derive(Debug, Clone, Default)]
 struct Score {
     difficulty: u8,
@@ -725,6 +819,33 @@ impl Room {
         }
     }
 
+    fn to_live_scores(&self) -> Vec<LiveScoreDict> {
+        self.players
+            .iter()
+            .filter(|p| p.player_id != 0)
+            .map(|p| LiveScoreDict {
+                multiplay_player_id: p.player_id,
+                name: p.name(),
+                player_state: p.player_state,
+                is_online: p.online == 1,
+                is_finished: p.finish_flag == 1,
+                song: SongScoreDict {
+                    difficulty: p.score.difficulty,
+                    score: p.score.score as i32,
+                    cleartype: p.score.cleartype,
+                    shine_perfect: p.score.shiny_perfect_count,
+                    perfect: p.score.perfect_count,
+                    near: p.score.near_count,
+                    miss: p.score.miss_count,
+                    early: p.score.early_count,
+                    late: p.score.late_count,
+                },
+                timer: p.score.timer,
+                healthy: p.score.healthy,
+            })
+            .collect()
+    }
+
     fn to_match_room_dict(&self) -> MatchRoomDict {
         MatchRoomDict {
             room_id: self.room_id,
@@ -1501,9 +1622,18 @@ struct Session {
     player_index: usize,
 }
 
+/// Read-only viewer of a room (e.g. a tournament stream overlay); it polls
+/// `observe_room` with its token and never takes a player slot.
+#[derive(Debug, Clone)]
+struct ObserverSession {
+    room_id: u64,
+    last_timestamp: i64,
+}
+
 #[derive(Debug, Default)]
 struct Store {
     sessions: HashMap<u64, Session>,
+    observers: HashMap<u64, ObserverSession>,
     rooms: HashMap<u64, Room>,
     room_code_index: HashMap<String, u64>,
     share_token_index: HashMap<String, u64>,
@@ -1742,17 +1872,22 @@ impl Store {
         })
     }
 
-    fn select_room(&self, room_code: Option<String>, share_token: Option<String>) -> Value {
-        let room = if let Some(code) = room_code {
-            let code = code.to_ascii_uppercase();
-            let room_id = self.room_code_index.get(&code).copied();
-            room_id.and_then(|id| self.rooms.get(&id))
+    fn find_room_id(&self, room_code: Option<String>, share_token: Option<String>) -> Option<u64> {
+        if let Some(code) = room_code {
+            self.room_code_index
+                .get(&code.to_ascii_uppercase())
+                .copied()
         } else if let Some(token) = share_token {
-            let room_id = self.share_token_index.get(&token).copied();
-            room_id.and_then(|id| self.rooms.get(&id))
+            self.share_token_index.get(&token).copied()
         } else {
             None
-        };
+        }
+    }
+
+    fn select_room(&self, room_code: Option<String>, share_token: Option<String>) -> Value {
+        let room = self
+            .find_room_id(room_code, share_token)
+            .and_then(|id| self.rooms.get(&id));
 
         let Some(room) = room else {
             return err_code(108);
@@ -1773,6 +1908,66 @@ impl Store {
         })
     }
 
+    /// Start observing a room, or poll it again with an existing observer token
+    fn observe_room(
+        &mut self,
+        observer_token: Option<u64>,
+        room_code: Option<String>,
+        share_token: Option<String>,
+        now: i64,
+    ) -> Value {
+        let (token, room_id) = match observer_token {
+            Some(token) => {
+                let Some(observer) = self.observers.get_mut(&token) else {
+                    return err_code(108);
+                };
+                observer.last_timestamp = now;
+                (token, observer.room_id)
+            }
+            None => {
+                let Some(room_id) = self.find_room_id(room_code, share_token) else {
+                    return err_code(108);
+                };
+                let token = self.generate_token();
+                self.observers.insert(
+                    token,
+                    ObserverSession {
+                        room_id,
+                        last_timestamp: now,
+                    },
+                );
+                (token, room_id)
+            }
+        };
+
+        let Some(room) = self.rooms.get(&room_id) else {
+            self.observers.remove(&token);
+            return err_code(108);
+        };
+        let observer_count = self
+            .observers
+            .values()
+            .filter(|observer| observer.room_id == room_id)
+            .count();
+
+        json!({
+            "code": 0,
+            "data": ObservedRoomDict {
+                observer_token: token,
+                observer_count,
+                room: room.to_room_dict(),
+                live_scores: room.to_live_scores(),
+            }
+        })
+    }
+
+    fn stop_observe(&mut self, observer_token: u64) -> Value {
+        if self.observers.remove(&observer_token).is_none() {
+            return err_code(108);
+        }
+        json!({"code": 0, "data": {}})
+    }
+
     fn get_match_rooms(&self, limit: usize) -> Value {
         let mut rooms = Vec::new();
 
@@ -1850,6 +2045,8 @@ impl Store {
         for token in stale_tokens {
             self.sessions.remove(&token);
         }
+        self.observers
+            .retain(|_, observer| observer.room_id != room_id);
     }
 
     fn cleanup(&mut self, now: i64, cfg: &LinkplayConfig) {
@@ -1888,6 +2085,11 @@ impl Store {
         for token in stale_sessions {
             self.clear_player_session(token, cfg);
         }
+
+        self.observers.retain(|_, observer| {
+            now - observer.last_timestamp < cfg.observer_timeout_usec
+                && self.rooms.contains_key(&observer.room_id)
+        });
     }
 
     fn generate_room_id(&self) -> u64 {
@@ -1903,7 +2105,9 @@ impl Store {
     }
 
     fn generate_token(&self) -> u64 {
-        unique_random_u64(|x| !self.sessions.contains_key(&x) && x != 0)
+        unique_random_u64(|x| {
+            !self.sessions.contains_key(&x) && !self.observers.contains_key(&x) && x != 0
+        })
     }
 
     fn generate_player_id(&mut self) -> u64 {
@@ -2015,6 +2219,26 @@ struct RoomDict {
     timed_mode: bool,
 }
 
+#[derive(Debug, Serialize)]
+struct LiveScoreDict {
+    multiplay_player_id: u64,
+    name: String,
+    player_state: u8,
+    is_online: bool,
+    is_finished: bool,
+    song: SongScoreDict,
+    timer: u32,
+    healthy: i32,
+}
+
+#[derive(Debug, Serialize)]
+struct ObservedRoomDict {
+    observer_token: u64,
+    observer_count: usize,
+    room: RoomDict,
+    live_scores: Vec<LiveScoreDict>,
+}
+
 #[derive(Debug, Serialize)]
 struct MatchPlayerDict {
     player_id: u64,
@@ -2045,23 +2269,36 @@ async fn main() -> io::Result<()> {
     );
 
     let state = Arc::new(RwLock::new(Store::default()));
+    let metrics = Arc::new(LinkplayMetrics::new());
 
     let tcp_state = state.clone();
     let tcp_cfg = cfg.clone();
+    let tcp_metrics = metrics.clone();
     let tcp_task = tokio::spawn(async move {
-        if let Err(err) = run_tcp_server(tcp_state, tcp_cfg).await {
+        if let Err(err) = run_tcp_server(tcp_state, tcp_cfg, tcp_metrics).await {
             error!("TCP server stopped with error: {err}");
         }
     });
 
     let udp_state = state.clone();
     let udp_cfg = cfg.clone();
+    let udp_metrics = metrics.clone();
     let udp_task = tokio::spawn(async move {
-        if let Err(err) = run_udp_server(udp_state, udp_cfg).await {
+        if let Err(err) = run_udp_server(udp_state, udp_cfg, udp_metrics).await {
             error!("UDP server stopped with error: {err}");
         }
     });
 
+    if cfg.metrics_port != 0 {
+        let metrics_state = state.clone();
+        let metrics_cfg = cfg.clone();
+        tokio::spawn(async move {
+            if let Err(err) = run_metrics_server(metrics_state, metrics_cfg, metrics).await {
+                error!("Metrics server stopped with error: {err}");
+            }
+        });
+    }
+
     let cleaner_state = state.clone();
     let cleaner_cfg = cfg.clone();
     let cleanup_task = tokio::spawn(async move {
@@ -2096,7 +2333,62 @@ async fn run_cleanup_loop(state: Arc<RwLock<Store>>, cfg: Arc<LinkplayConfig>) {
     }
 }
 
-async fn run_tcp_server(state: Arc<RwLock<Store>>, cfg: Arc<LinkplayConfig>) -> io::Result<()> {
+/// Serve `GET /metrics` in the Prometheus text format
+///
+/// Gauges are refreshed from the store every few seconds rather than on
+/// each scrape, so scrapes never wait for the store lock.
+async fn run_metrics_server(
+    state: Arc<RwLock<Store>>,
+    cfg: Arc<LinkplayConfig>,
+    metrics: Arc<LinkplayMetrics>,
+) -> io::Result<()> {
+    let addr = format!("{}:{}", cfg.host, cfg.metrics_port);
+    let listener = TcpListener::bind(&addr).await?;
+    info!("Link Play metrics listening on http://{addr}/metrics");
+
+    let refresh_metrics = metrics.clone();
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(Duration::from_secs(METRICS_REFRESH_INTERVAL_SEC));
+        loop {
+            ticker.tick().await;
+            let guard = state.read().await;
+            refresh_metrics.refresh(&guard);
+        }
+    });
+
+    loop {
+        let (mut stream, _peer) = listener.accept().await?;
+        let metrics = metrics.clone();
+        tokio::spawn(async move {
+            let mut buf = [0u8; 1024];
+            let n = match stream.read(&mut buf).await {
+                Ok(n) => n,
+                Err(_) => return,
+            };
+            let request_line = buf[..n].split(|b| *b == b'\n').next().unwrap_or_default();
+            let response = if request_line.starts_with(b"GET /metrics ") {
+                let body = metrics.render();
+                let mut response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    TextEncoder::new().format_type(),
+                    body.len()
+                )
+                .into_bytes();
+                response.extend_from_slice(&body);
+                response
+            } else {
+                b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_vec()
+            };
+            let _ = stream.write_all(&response).await;
+        });
+    }
+}
+
+async fn run_tcp_server(
+    state: Arc<RwLock<Store>>,
+    cfg: Arc<LinkplayConfig>,
+    metrics: Arc<LinkplayMetrics>,
+) -> io::Result<()> {
     let addr = format!("{}:{}", cfg.host, cfg.tcp_port);
     let listener = TcpListener::bind(&addr).await?;
     info!("Link Play TCP server listening on {addr}");
@@ -2106,8 +2398,9 @@ async fn run_tcp_server(state: Arc<RwLock<Store>>, cfg: Arc<LinkplayConfig>) ->
 
         let state = state.clone();
         let cfg = cfg.clone();
+        let metrics = metrics.clone();
         tokio::spawn(async move {
-            if let Err(err) = handle_tcp_connection(stream, state, cfg).await {
+            if let Err(err) = handle_tcp_connection(stream, state, cfg, metrics).await {
                 warn!("TCP connection closed: {err}");
             }
         });
@@ -2118,6 +2411,7 @@ async fn handle_tcp_connection(
     mut stream: TcpStream,
     state: Arc<RwLock<Store>>,
     cfg: Arc<LinkplayConfig>,
+    metrics: Arc<LinkplayMetrics>,
 ) -> io::Result<()> {
     let auth_len = cfg.authentication.len();
     let mut auth_buf = vec![0u8; auth_len];
@@ -2148,6 +2442,7 @@ async fn handle_tcp_connection(
     let plaintext = match decrypt_bytes(&cfg.tcp_aes_key(), &iv, &tag, ciphertext) {
         Ok(v) => v,
         Err(err) => {
+            metrics.decrypt_failures.with_label_values(&["tcp"]).inc();
             warn!("Failed to decrypt TCP payload: {err}");
             return Ok(());
         }
@@ -2233,11 +2528,27 @@ async fn handle_control_plane_request(
             let limit = data_get_usize(&req.data, "limit").unwrap_or(100);
             guard.get_match_rooms(limit)
         }
+        "observe_room" => {
+            let observer_token = data_get_u64(&req.data, "observer_token");
+            let room_code = data_get_string(&req.data, "room_code");
+            let share_token = data_get_string(&req.data, "share_token");
+            guard.observe_room(observer_token, room_code, share_token, now_usec())
+        }
+        "stop_observe" => {
+            let Some(observer_token) = data_get_u64(&req.data, "observer_token") else {
+                return err_code(999);
+            };
+            guard.stop_observe(observer_token)
+        }
         _ => err_code(999),
     }
 }
 
-async fn run_udp_server(state: Arc<RwLock<Store>>, cfg: Arc<LinkplayConfig>) -> io::Result<()> {
+async fn run_udp_server(
+    state: Arc<RwLock<Store>>,
+    cfg: Arc<LinkplayConfig>,
+    metrics: Arc<LinkplayMetrics>,
+) -> io::Result<()> {
     let addr = format!("{}:{}", cfg.host, cfg.udp_port);
     let socket = UdpSocket::bind(&addr).await?;
     info!("Link Play UDP server listening on {addr}");
@@ -2246,6 +2557,7 @@ async fn run_udp_server(state: Arc<RwLock<Store>>, cfg: Arc<LinkplayConfig>) ->
 
     loop {
         let (n, peer) = socket.recv_from(&mut buf).await?;
+        metrics.udp_packets.inc();
         if n < 36 {
             continue;
         }
@@ -2269,12 +2581,19 @@ async fn run_udp_server(state: Arc<RwLock<Store>>, cfg: Arc<LinkplayConfig>) ->
 
         let payload = match decrypt_bytes(&session.key, &iv, &tag, packet[36..].to_vec()) {
             Ok(v) => v,
-            Err(_) => continue,
+            Err(_) => {
+                metrics.decrypt_failures.with_label_values(&["udp"]).inc();
+                continue;
+            }
         };
 
         if payload.len() < 3 || payload[0..2] != PROTOCOL_NAME {
             continue;
         }
+        metrics
+            .commands
+            .with_label_values(&[&format!("{:02x}", payload[2])])
+            .inc();
 
         let commands = {
             let mut guard = state.write().await;
@@ -124,6 +124,10 @@ struct BundleFileMeta {
     json_md5: Option<String>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     bundle_md5: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    added_files: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    removed_files: Vec<String>,
 }
 
 #[derive(Debug)]
@@ -461,6 +465,8 @@ async fn sync_bundles(
             bundle_key,
             json_md5: Some(json_md5),
             bundle_md5: Some(bundle_md5),
+            added_files: path_list(&json_data, "added"),
+            removed_files: path_list(&json_data, "removed"),
         };
 
         let remote_bundle = remote_bundle_match(remote_manifest, &meta);
@@ -737,6 +743,21 @@ fn string_field(value: &serde_json::Value, key: &str) -> anyhow::Result<String>
         .ok_or_else(|| anyhow::anyhow!("bundle json missing `{key}`"))
 }
 
+/// Paths listed under `key` in a bundle json, as plain strings or `path` fields
+fn path_list(value: &serde_json::Value, key: &str) -> Vec<String> {
+    value
+        .get(key)
+        .and_then(|value| value.as_array())
+        .map(|entries| {
+            entries
+                .iter()
+                .filter_map(|entry| entry.as_str().or_else(|| entry["path"].as_str()))
+                .map(ToOwned::to_owned)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
 fn file_name(path: &Path) -> anyhow::Result<String> {
     path.file_name()
         .and_then(|name| name.to_str())
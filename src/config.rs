@@ -3,9 +3,13 @@ use rocket::figment::{
     providers::{Format, Toml},
     Figment, Profile,
 };
+use serde::Deserialize;
 use std::collections::HashMap;
 use std::env;
+use std::fmt;
 use std::str::FromStr;
+use std::sync::{OnceLock, RwLock};
+use std::time::Duration;
 
 macro_rules! set_from_figment {
     ($config:expr, $figment:expr, $field:ident, $key:expr, $ty:ty) => {
@@ -34,6 +38,22 @@ macro_rules! set_from_env_key {
     };
 }
 
+/// An older client version served under its own game API prefix, with the
+/// shims it needs to talk to the current route tree
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct GameApiVersion {
+    /// Prefix the version's clients use, e.g. `/coldwind/33`
+    pub prefix: String,
+    /// Response fields renamed for this version, current name -> old name
+    pub rename_fields: HashMap<String, String>,
+    /// Paths under the prefix renamed since, old path -> current path
+    pub path_aliases: HashMap<String, String>,
+    /// Paths under the prefix this server no longer serves, answered with a
+    /// fixed successful `value`
+    pub stub_endpoints: HashMap<String, serde_json::Value>,
+}
+
 /// Game server configuration constants
 #[derive(Debug, Clone)]
 pub struct Config {
@@ -43,13 +63,17 @@ pub struct Config {
     // Game API settings
     pub game_api_prefix: String,
     pub old_game_api_prefix: Vec<String>,
+    pub game_api_versions: Vec<GameApiVersion>,
     pub allow_appversion: Vec<String>,
+    pub min_app_version: String,
+    pub max_app_version: String,
 
     // Bundle settings
     pub bundle_strict_mode: bool,
 
     // World settings
     pub world_rank_max: i32,
+    pub world_rank_refresh_interval: u64,
     pub available_map: Vec<String>,
 
     // Authentication
@@ -57,6 +81,10 @@ pub struct Config {
     pub password: String,
     pub secret_key: String,
     pub api_token: String,
+    /// `bcrypt` or `sha256`; hashes of the other algorithm are replaced on
+    /// the next successful login
+    pub password_hash_algorithm: String,
+    pub password_bcrypt_cost: u32,
 
     // Download settings
     pub download_link_prefix: String,
@@ -64,11 +92,16 @@ pub struct Config {
     pub download_use_nginx_x_accel_redirect: bool,
     pub nginx_x_accel_redirect_prefix: String,
     pub bundle_nginx_x_accel_redirect_prefix: String,
+    /// Largest bundle or song package accepted by the admin uploads, in MiB
+    pub asset_upload_limit_mib: u64,
 
     // Rate limiting
     pub download_times_limit: i32,
+    pub download_bytes_limit: i64,
     pub download_time_gap_limit: i64,
     pub download_forbid_when_no_item: bool,
+    pub download_signed_url_ttl: i64,
+    pub download_token_sweep_interval: u64,
     pub bundle_download_times_limit: String,
     pub bundle_download_time_gap_limit: i64,
 
@@ -77,23 +110,67 @@ pub struct Config {
     pub login_device_number_limit: i32,
     pub allow_login_same_device: bool,
     pub allow_ban_multidevice_user_auto: bool,
+    /// Refuse logins beyond `login_device_number_limit` instead of logging
+    /// out the oldest device.
+    pub reject_login_over_device_limit: bool,
+    /// Seconds an access token stays valid after login or refresh; 0 keeps
+    /// tokens valid until the session is evicted.
+    pub login_token_ttl: i64,
 
     // Game settings
     pub allow_score_with_no_song: bool,
     pub trace_complete_ticket_reward_enabled: bool,
     pub default_memories: i32,
     pub update_with_new_character_data: bool,
+    pub sync_charts_from_songlist: bool,
+    /// Seconds between incremental rescans of the song folder, rehashing new
+    /// or changed files (0 only hashes at startup)
+    pub song_file_refresh_interval: u64,
     pub character_full_unlock: bool,
     pub world_song_full_unlock: bool,
     pub world_scenery_full_unlock: bool,
     pub save_full_unlock: bool,
     pub allow_self_account_delete: bool,
+    /// Hours between requesting an account deletion and being able to
+    /// confirm it.
+    pub account_delete_cooldown_hours: i64,
+    /// Hours after the cooldown during which the deletion can be confirmed.
+    pub account_delete_confirm_window_hours: i64,
+    /// New accounts must confirm their email with a mailed code before
+    /// they can log in.
+    pub email_verification_enabled: bool,
+    pub email_verification_code_ttl_minutes: i64,
+
+    // Terms of service
+    pub tos_version: String,
+    pub tos_url: String,
 
     // PTT calculation weights
     pub best30_weight: f64,
     pub recent10_weight: f64,
     pub invasion_start_weight: f64,
     pub invasion_hard_weight: f64,
+    /// Seconds between reloads of the `game_constants` table, so edits made
+    /// on another instance apply here too (0 only loads it at startup)
+    pub game_constants_reload_interval: u64,
+
+    // Chart constant estimation for unrated charts
+    /// `median`, `mean` or `trimmed_mean`
+    pub chart_estimate_model: String,
+    pub chart_estimate_min_samples: i32,
+
+    // Score replays
+    pub replay_max_bytes: u64,
+
+    // Global potential leaderboard
+    pub potential_ranking_size: i32,
+    pub potential_ranking_refresh_interval: u64,
+    /// Days daily potential snapshots are kept in `ptt_history`; 0 disables
+    /// them.
+    pub ptt_history_retention_days: i64,
+
+    // Score submission
+    pub score_write_queue_capacity: usize,
 
     // Social settings
     pub max_friend_count: i32,
@@ -101,6 +178,21 @@ pub struct Config {
     // Logging
     pub allow_info_log: bool,
     pub allow_warning_log: bool,
+    /// Days game API requests are kept in `api_log`; 0 disables the table.
+    pub api_log_retention_days: i64,
+
+    // Localization
+    /// Language client-facing messages fall back to, and the language of
+    /// push notifications (`en`, `zh-Hans`, `zh-Hant` or `ja`).
+    pub default_language: String,
+
+    // Operator webhook
+    /// Discord-compatible webhook that operator events are posted to; empty
+    /// disables it.
+    pub webhook_url: String,
+    /// Events posted to `webhook_url` (`registration`, `flagged_score`,
+    /// `migration_failed`, `bundle_refresh`); empty posts all of them.
+    pub webhook_events: Vec<String>,
 
     // File paths (for reference, might not be used in Rust version)
     pub world_map_folder_path: String,
@@ -108,6 +200,21 @@ pub struct Config {
     pub songlist_file_path: String,
     pub content_bundle_folder_path: String,
     pub database_init_path: String,
+    /// Local replay folder, unused when the S3 storage backend is enabled.
+    pub replay_folder_path: String,
+    /// Folder personal data exports are written to, one file per user.
+    pub user_export_folder_path: String,
+
+    // Asset storage
+    pub storage_backend: String,
+    pub s3_endpoint: Option<String>,
+    pub s3_region: String,
+    pub s3_bucket: String,
+    pub s3_access_key_id: String,
+    pub s3_secret_access_key: String,
+    pub s3_force_path_style: bool,
+    pub s3_manifest_key: String,
+    pub s3_presign_expires_seconds: u64,
 }
 
 impl Default for Config {
@@ -118,17 +225,23 @@ impl Default for Config {
 
             game_api_prefix: "/coldwind/35".to_string(),
             old_game_api_prefix: Vec::new(),
+            game_api_versions: Vec::new(),
             allow_appversion: Vec::new(),
+            min_app_version: String::new(),
+            max_app_version: String::new(),
 
             bundle_strict_mode: true,
 
             world_rank_max: 200,
+            world_rank_refresh_interval: 3600,
             available_map: Vec::new(),
 
             username: "admin".to_string(),
             password: "admin".to_string(),
             secret_key: "1145141919810".to_string(),
             api_token: String::new(),
+            password_hash_algorithm: "bcrypt".to_string(),
+            password_bcrypt_cost: 10,
 
             download_link_prefix: String::new(),
             bundle_download_link_prefix: Some(String::from(
@@ -137,10 +250,14 @@ impl Default for Config {
             download_use_nginx_x_accel_redirect: false,
             nginx_x_accel_redirect_prefix: "/nginx_download/".to_string(),
             bundle_nginx_x_accel_redirect_prefix: "/nginx_bundle_download/".to_string(),
+            asset_upload_limit_mib: 512,
 
             download_times_limit: 3000,
+            download_bytes_limit: 0,
             download_time_gap_limit: 1000,
             download_forbid_when_no_item: false,
+            download_signed_url_ttl: 0,
+            download_token_sweep_interval: 600,
             bundle_download_times_limit: "100/60 minutes".to_string(),
             bundle_download_time_gap_limit: 3000,
 
@@ -148,32 +265,70 @@ impl Default for Config {
             login_device_number_limit: 1,
             allow_login_same_device: false,
             allow_ban_multidevice_user_auto: true,
+            reject_login_over_device_limit: false,
+            login_token_ttl: 0,
 
             allow_score_with_no_song: true,
             trace_complete_ticket_reward_enabled: false,
             default_memories: 0,
             update_with_new_character_data: true,
+            sync_charts_from_songlist: false,
+            song_file_refresh_interval: 0,
             character_full_unlock: true,
             world_song_full_unlock: true,
             world_scenery_full_unlock: true,
             save_full_unlock: false,
             allow_self_account_delete: false,
+            account_delete_cooldown_hours: 24,
+            account_delete_confirm_window_hours: 72,
+            email_verification_enabled: false,
+            email_verification_code_ttl_minutes: 30,
+
+            tos_version: String::new(),
+            tos_url: String::new(),
 
             best30_weight: 1.0 / 40.0,
             recent10_weight: 1.0 / 40.0,
             invasion_start_weight: 0.1,
             invasion_hard_weight: 0.1,
+            game_constants_reload_interval: 60,
+
+            chart_estimate_model: "median".to_string(),
+            chart_estimate_min_samples: 10,
+            replay_max_bytes: 2 * 1024 * 1024,
+            potential_ranking_size: 100,
+            potential_ranking_refresh_interval: 300,
+            ptt_history_retention_days: 365,
+            score_write_queue_capacity: 1024,
 
             max_friend_count: 50,
 
             allow_info_log: false,
             allow_warning_log: false,
+            api_log_retention_days: 0,
+
+            default_language: "en".to_string(),
+
+            webhook_url: String::new(),
+            webhook_events: Vec::new(),
 
             world_map_folder_path: "./database/map/".to_string(),
             song_file_folder_path: "./database/songs/".to_string(),
             songlist_file_path: "./database/songs/songlist".to_string(),
             content_bundle_folder_path: "./database/bundle/".to_string(),
             database_init_path: "./database/init/".to_string(),
+            replay_folder_path: "./database/replays/".to_string(),
+            user_export_folder_path: "./database/exports/".to_string(),
+
+            storage_backend: "local".to_string(),
+            s3_endpoint: None,
+            s3_region: "us-east-1".to_string(),
+            s3_bucket: String::new(),
+            s3_access_key_id: String::new(),
+            s3_secret_access_key: String::new(),
+            s3_force_path_style: false,
+            s3_manifest_key: "manifest.json".to_string(),
+            s3_presign_expires_seconds: 3600,
         }
     }
 }
@@ -206,6 +361,13 @@ impl Config {
             "old_game_api_prefix",
             Vec<String>
         );
+        set_from_figment!(
+            self,
+            figment,
+            game_api_versions,
+            "game_api_versions",
+            Vec<GameApiVersion>
+        );
         set_from_figment!(
             self,
             figment,
@@ -213,6 +375,8 @@ impl Config {
             "allow_appversion",
             Vec<String>
         );
+        set_from_figment!(self, figment, min_app_version, "min_app_version", String);
+        set_from_figment!(self, figment, max_app_version, "max_app_version", String);
         set_from_figment!(
             self,
             figment,
@@ -221,11 +385,32 @@ impl Config {
             bool
         );
         set_from_figment!(self, figment, world_rank_max, "world_rank_max", i32);
+        set_from_figment!(
+            self,
+            figment,
+            world_rank_refresh_interval,
+            "world_rank_refresh_interval",
+            u64
+        );
         set_from_figment!(self, figment, available_map, "available_map", Vec<String>);
         set_from_figment!(self, figment, username, "username", String);
         set_from_figment!(self, figment, password, "password", String);
         set_from_figment!(self, figment, secret_key, "secret_key", String);
         set_from_figment!(self, figment, api_token, "api_token", String);
+        set_from_figment!(
+            self,
+            figment,
+            password_hash_algorithm,
+            "password_hash_algorithm",
+            String
+        );
+        set_from_figment!(
+            self,
+            figment,
+            password_bcrypt_cost,
+            "password_bcrypt_cost",
+            u32
+        );
         set_from_figment!(
             self,
             figment,
@@ -261,6 +446,13 @@ impl Config {
             "bundle_nginx_x_accel_redirect_prefix",
             String
         );
+        set_from_figment!(
+            self,
+            figment,
+            asset_upload_limit_mib,
+            "asset_upload_limit_mib",
+            u64
+        );
         set_from_figment!(
             self,
             figment,
@@ -268,6 +460,13 @@ impl Config {
             "download_times_limit",
             i32
         );
+        set_from_figment!(
+            self,
+            figment,
+            download_bytes_limit,
+            "download_bytes_limit",
+            i64
+        );
         set_from_figment!(
             self,
             figment,
@@ -282,6 +481,20 @@ impl Config {
             "download_forbid_when_no_item",
             bool
         );
+        set_from_figment!(
+            self,
+            figment,
+            download_signed_url_ttl,
+            "download_signed_url_ttl",
+            i64
+        );
+        set_from_figment!(
+            self,
+            figment,
+            download_token_sweep_interval,
+            "download_token_sweep_interval",
+            u64
+        );
         set_from_figment!(
             self,
             figment,
@@ -324,6 +537,14 @@ impl Config {
             "allow_ban_multidevice_user_auto",
             bool
         );
+        set_from_figment!(
+            self,
+            figment,
+            reject_login_over_device_limit,
+            "reject_login_over_device_limit",
+            bool
+        );
+        set_from_figment!(self, figment, login_token_ttl, "login_token_ttl", i64);
         set_from_figment!(
             self,
             figment,
@@ -346,6 +567,20 @@ impl Config {
             "update_with_new_character_data",
             bool
         );
+        set_from_figment!(
+            self,
+            figment,
+            sync_charts_from_songlist,
+            "sync_charts_from_songlist",
+            bool
+        );
+        set_from_figment!(
+            self,
+            figment,
+            song_file_refresh_interval,
+            "song_file_refresh_interval",
+            u64
+        );
         set_from_figment!(
             self,
             figment,
@@ -375,6 +610,36 @@ impl Config {
             "allow_self_account_delete",
             bool
         );
+        set_from_figment!(
+            self,
+            figment,
+            account_delete_cooldown_hours,
+            "account_delete_cooldown_hours",
+            i64
+        );
+        set_from_figment!(
+            self,
+            figment,
+            account_delete_confirm_window_hours,
+            "account_delete_confirm_window_hours",
+            i64
+        );
+        set_from_figment!(
+            self,
+            figment,
+            email_verification_enabled,
+            "email_verification_enabled",
+            bool
+        );
+        set_from_figment!(
+            self,
+            figment,
+            email_verification_code_ttl_minutes,
+            "email_verification_code_ttl_minutes",
+            i64
+        );
+        set_from_figment!(self, figment, tos_version, "tos_version", String);
+        set_from_figment!(self, figment, tos_url, "tos_url", String);
         set_from_figment!(self, figment, best30_weight, "best30_weight", f64);
         set_from_figment!(self, figment, recent10_weight, "recent10_weight", f64);
         set_from_figment!(
@@ -391,9 +656,69 @@ impl Config {
             "invasion_hard_weight",
             f64
         );
+        set_from_figment!(
+            self,
+            figment,
+            game_constants_reload_interval,
+            "game_constants_reload_interval",
+            u64
+        );
+        set_from_figment!(
+            self,
+            figment,
+            chart_estimate_model,
+            "chart_estimate_model",
+            String
+        );
+        set_from_figment!(
+            self,
+            figment,
+            chart_estimate_min_samples,
+            "chart_estimate_min_samples",
+            i32
+        );
+        set_from_figment!(self, figment, replay_max_bytes, "replay_max_bytes", u64);
+        set_from_figment!(
+            self,
+            figment,
+            potential_ranking_size,
+            "potential_ranking_size",
+            i32
+        );
+        set_from_figment!(
+            self,
+            figment,
+            potential_ranking_refresh_interval,
+            "potential_ranking_refresh_interval",
+            u64
+        );
+        set_from_figment!(
+            self,
+            figment,
+            ptt_history_retention_days,
+            "ptt_history_retention_days",
+            i64
+        );
+        set_from_figment!(
+            self,
+            figment,
+            score_write_queue_capacity,
+            "score_write_queue_capacity",
+            usize
+        );
         set_from_figment!(self, figment, max_friend_count, "max_friend_count", i32);
         set_from_figment!(self, figment, allow_info_log, "allow_info_log", bool);
         set_from_figment!(self, figment, allow_warning_log, "allow_warning_log", bool);
+        set_from_figment!(
+            self,
+            figment,
+            api_log_retention_days,
+            "api_log_retention_days",
+            i64
+        );
+        set_from_figment!(self, figment, default_language, "default_language", String);
+        set_from_figment!(self, figment, webhook_url, "webhook_url", String);
+        set_from_figment!(self, figment, webhook_events, "webhook_events", Vec<String>);
         set_from_figment!(
             self,
             figment,
@@ -429,6 +754,47 @@ impl Config {
             "database_init_path",
             String
         );
+        set_from_figment!(
+            self,
+            figment,
+            replay_folder_path,
+            "replay_folder_path",
+            String
+        );
+        set_from_figment!(
+            self,
+            figment,
+            user_export_folder_path,
+            "user_export_folder_path",
+            String
+        );
+        set_from_figment!(self, figment, storage_backend, "storage_backend", String);
+        set_from_figment!(self, figment, s3_endpoint, "s3_endpoint", Option<String>);
+        set_from_figment!(self, figment, s3_region, "s3_region", String);
+        set_from_figment!(self, figment, s3_bucket, "s3_bucket", String);
+        set_from_figment!(self, figment, s3_access_key_id, "s3_access_key_id", String);
+        set_from_figment!(
+            self,
+            figment,
+            s3_secret_access_key,
+            "s3_secret_access_key",
+            String
+        );
+        set_from_figment!(
+            self,
+            figment,
+            s3_force_path_style,
+            "s3_force_path_style",
+            bool
+        );
+        set_from_figment!(self, figment, s3_manifest_key, "s3_manifest_key", String);
+        set_from_figment!(
+            self,
+            figment,
+            s3_presign_expires_seconds,
+            "s3_presign_expires_seconds",
+            u64
+        );
     }
 
     fn apply_env(&mut self) {
@@ -441,52 +807,356 @@ impl Config {
         set_from_env!(self, port, u16);
         set_from_env!(self, game_api_prefix, String);
         set_from_env!(self, old_game_api_prefix, Vec<String>);
+        set_from_env!(self, game_api_versions, Vec<GameApiVersion>);
         set_from_env!(self, allow_appversion, Vec<String>);
+        set_from_env!(self, min_app_version, String);
+        set_from_env!(self, max_app_version, String);
         set_from_env!(self, bundle_strict_mode, bool);
         set_from_env!(self, world_rank_max, i32);
+        set_from_env!(self, world_rank_refresh_interval, u64);
         set_from_env!(self, available_map, Vec<String>);
         set_from_env!(self, username, String);
         set_from_env!(self, password, String);
         set_from_env!(self, secret_key, String);
         set_from_env!(self, api_token, String);
+        set_from_env!(self, password_hash_algorithm, String);
+        set_from_env!(self, password_bcrypt_cost, u32);
         set_from_env!(self, download_link_prefix, String);
         set_from_env!(self, bundle_download_link_prefix, Option<String>);
         set_from_env!(self, download_use_nginx_x_accel_redirect, bool);
         set_from_env!(self, nginx_x_accel_redirect_prefix, String);
         set_from_env!(self, bundle_nginx_x_accel_redirect_prefix, String);
+        set_from_env!(self, asset_upload_limit_mib, u64);
         set_from_env!(self, download_times_limit, i32);
+        set_from_env!(self, download_bytes_limit, i64);
         set_from_env!(self, download_time_gap_limit, i64);
         set_from_env!(self, download_forbid_when_no_item, bool);
+        set_from_env!(self, download_signed_url_ttl, i64);
+        set_from_env!(self, download_token_sweep_interval, u64);
         set_from_env!(self, bundle_download_times_limit, String);
         set_from_env!(self, bundle_download_time_gap_limit, i64);
         set_from_env!(self, disable_registration, bool);
         set_from_env!(self, login_device_number_limit, i32);
         set_from_env!(self, allow_login_same_device, bool);
         set_from_env!(self, allow_ban_multidevice_user_auto, bool);
+        set_from_env!(self, reject_login_over_device_limit, bool);
+        set_from_env!(self, login_token_ttl, i64);
         set_from_env!(self, allow_score_with_no_song, bool);
         set_from_env!(self, trace_complete_ticket_reward_enabled, bool);
         set_from_env!(self, default_memories, i32);
         set_from_env!(self, update_with_new_character_data, bool);
+        set_from_env!(self, sync_charts_from_songlist, bool);
+        set_from_env!(self, song_file_refresh_interval, u64);
         set_from_env!(self, character_full_unlock, bool);
         set_from_env!(self, world_song_full_unlock, bool);
         set_from_env!(self, world_scenery_full_unlock, bool);
         set_from_env!(self, save_full_unlock, bool);
         set_from_env!(self, allow_self_account_delete, bool);
+        set_from_env!(self, account_delete_cooldown_hours, i64);
+        set_from_env!(self, account_delete_confirm_window_hours, i64);
+        set_from_env!(self, email_verification_enabled, bool);
+        set_from_env!(self, email_verification_code_ttl_minutes, i64);
+        set_from_env!(self, tos_version, String);
+        set_from_env!(self, tos_url, String);
         set_from_env!(self, best30_weight, f64);
         set_from_env!(self, recent10_weight, f64);
         set_from_env!(self, invasion_start_weight, f64);
         set_from_env!(self, invasion_hard_weight, f64);
+        set_from_env!(self, game_constants_reload_interval, u64);
+        set_from_env!(self, chart_estimate_model, String);
+        set_from_env!(self, chart_estimate_min_samples, i32);
+        set_from_env!(self, replay_max_bytes, u64);
+        set_from_env!(self, potential_ranking_size, i32);
+        set_from_env!(self, potential_ranking_refresh_interval, u64);
+        set_from_env!(self, ptt_history_retention_days, i64);
+        set_from_env!(self, score_write_queue_capacity, usize);
         set_from_env!(self, max_friend_count, i32);
         set_from_env!(self, allow_info_log, bool);
         set_from_env!(self, allow_warning_log, bool);
+        set_from_env!(self, api_log_retention_days, i64);
+        set_from_env!(self, default_language, String);
+        set_from_env!(self, webhook_url, String);
+        set_from_env!(self, webhook_events, Vec<String>);
         set_from_env!(self, world_map_folder_path, String);
         set_from_env!(self, song_file_folder_path, String);
         set_from_env!(self, songlist_file_path, String);
         set_from_env!(self, content_bundle_folder_path, String);
         set_from_env!(self, database_init_path, String);
+        set_from_env!(self, replay_folder_path, String);
+        set_from_env!(self, user_export_folder_path, String);
+        set_from_env!(self, storage_backend, String);
+        set_from_env!(self, s3_endpoint, Option<String>);
+        set_from_env!(self, s3_region, String);
+        set_from_env!(self, s3_bucket, String);
+        set_from_env!(self, s3_access_key_id, String);
+        set_from_env!(self, s3_secret_access_key, String);
+        set_from_env!(self, s3_force_path_style, bool);
+        set_from_env!(self, s3_manifest_key, String);
+        set_from_env!(self, s3_presign_expires_seconds, u64);
     }
 }
 
+/// Problems found while validating the configuration, one readable message
+/// per setting
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigErrors(pub Vec<String>);
+
+impl fmt::Display for ConfigErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0.join("; "))
+    }
+}
+
+impl std::error::Error for ConfigErrors {}
+
+/// Whether `prefix` can be mounted as a route prefix
+fn is_valid_route_prefix(prefix: &str) -> bool {
+    let prefix = prefix.trim();
+    prefix.starts_with('/')
+        && !prefix.contains("//")
+        && prefix
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '/' | '-' | '_' | '.' | '~'))
+}
+
+/// Whether `prefix` can start a download URL: empty, an absolute path or an
+/// http(s) URL
+fn is_valid_link_prefix(prefix: &str) -> bool {
+    let prefix = prefix.trim();
+    if prefix.is_empty() || prefix.starts_with('/') {
+        return true;
+    }
+    ["http://", "https://"].iter().any(|scheme| {
+        prefix
+            .strip_prefix(scheme)
+            .is_some_and(|rest| !rest.is_empty() && !rest.starts_with('/'))
+    }) && !prefix.chars().any(char::is_whitespace)
+}
+
+/// Parse a rate limit such as `5/minute` or `100 per day` into the number of
+/// requests and the window they are counted over
+pub fn parse_rate_limit(value: &str) -> Option<(u32, Duration)> {
+    let value = value.trim().to_ascii_lowercase();
+    let (count, unit) = value
+        .split_once('/')
+        .or_else(|| value.split_once(" per "))?;
+    let count = count
+        .trim()
+        .parse::<u32>()
+        .ok()
+        .filter(|count| *count > 0)?;
+    let seconds = match unit.trim().trim_end_matches('s') {
+        "second" | "sec" => 1,
+        "minute" | "min" => 60,
+        "hour" => 3600,
+        "day" => 86400,
+        _ => return None,
+    };
+    Some((count, Duration::from_secs(seconds)))
+}
+
+impl Config {
+    /// Check settings that would otherwise fail late or silently, e.g. a game
+    /// API prefix Rocket cannot mount
+    pub fn validate(&self) -> Result<(), ConfigErrors> {
+        let mut errors = Vec::new();
+
+        if self.port == 0 {
+            errors.push("port must not be 0".to_string());
+        }
+        let prefixes = std::iter::once(&self.game_api_prefix)
+            .chain(&self.old_game_api_prefix)
+            .chain(self.game_api_versions.iter().map(|version| &version.prefix));
+        for prefix in prefixes {
+            if !is_valid_route_prefix(prefix) {
+                errors.push(format!(
+                    "game API prefix `{prefix}` must start with `/` and only contain letters, digits and `/-_.~`"
+                ));
+            }
+        }
+        if !is_valid_link_prefix(&self.download_link_prefix) {
+            errors.push(format!(
+                "download_link_prefix `{}` must be empty, an absolute path or an http(s) URL",
+                self.download_link_prefix
+            ));
+        }
+        if let Some(prefix) = &self.bundle_download_link_prefix {
+            if !is_valid_link_prefix(prefix) {
+                errors.push(format!(
+                    "bundle_download_link_prefix `{prefix}` must be empty, an absolute path or an http(s) URL"
+                ));
+            }
+        }
+        for (name, value) in [
+            ("download_times_limit", i64::from(self.download_times_limit)),
+            ("download_bytes_limit", self.download_bytes_limit),
+            (
+                "account_delete_cooldown_hours",
+                self.account_delete_cooldown_hours,
+            ),
+        ] {
+            if value < 0 {
+                errors.push(format!("{name} must not be negative, got {value}"));
+            }
+        }
+        if self.download_time_gap_limit <= 0 {
+            errors.push(format!(
+                "download_time_gap_limit must be positive, got {}",
+                self.download_time_gap_limit
+            ));
+        }
+        if !matches!(
+            self.storage_backend.trim().to_ascii_lowercase().as_str(),
+            "" | "local" | "s3"
+        ) {
+            errors.push(format!(
+                "storage_backend `{}` must be `local` or `s3`",
+                self.storage_backend
+            ));
+        }
+        if !matches!(
+            self.password_hash_algorithm
+                .trim()
+                .to_ascii_lowercase()
+                .as_str(),
+            "" | "bcrypt" | "sha256"
+        ) {
+            errors.push(format!(
+                "password_hash_algorithm `{}` must be `bcrypt` or `sha256`",
+                self.password_hash_algorithm
+            ));
+        }
+        if crate::i18n::catalog_language(&self.default_language).is_none() {
+            errors.push(format!(
+                "default_language `{}` has no message catalog",
+                self.default_language
+            ));
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigErrors(errors))
+        }
+    }
+}
+
+/// Settings that [`reload_config`] applies without a restart
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReloadableConfig {
+    pub download_link_prefix: Option<String>,
+    pub download_time_gap_limit: i64,
+    pub download_times_limit: i32,
+    pub download_bytes_limit: i64,
+    pub rate_limits: RateLimitConfig,
+}
+
+impl ReloadableConfig {
+    pub fn from_config(config: &Config, rate_limits: RateLimitConfig) -> Self {
+        let link_prefix = config.download_link_prefix.trim();
+        Self {
+            download_link_prefix: (!link_prefix.is_empty()).then(|| link_prefix.to_string()),
+            download_time_gap_limit: config.download_time_gap_limit,
+            download_times_limit: config.download_times_limit,
+            download_bytes_limit: config.download_bytes_limit,
+            rate_limits,
+        }
+    }
+
+    /// Names of the settings whose value differs in `other`
+    pub fn changed_keys(&self, other: &Self) -> Vec<&'static str> {
+        [
+            (
+                "download_link_prefix",
+                self.download_link_prefix != other.download_link_prefix,
+            ),
+            (
+                "download_time_gap_limit",
+                self.download_time_gap_limit != other.download_time_gap_limit,
+            ),
+            (
+                "download_times_limit",
+                self.download_times_limit != other.download_times_limit,
+            ),
+            (
+                "download_bytes_limit",
+                self.download_bytes_limit != other.download_bytes_limit,
+            ),
+            (
+                "game_register_ip_rate_limit",
+                self.rate_limits.game_register_ip_rate_limit
+                    != other.rate_limits.game_register_ip_rate_limit,
+            ),
+            (
+                "game_register_device_rate_limit",
+                self.rate_limits.game_register_device_rate_limit
+                    != other.rate_limits.game_register_device_rate_limit,
+            ),
+            (
+                "game_login_rate_limit",
+                self.rate_limits.game_login_rate_limit != other.rate_limits.game_login_rate_limit,
+            ),
+        ]
+        .into_iter()
+        .filter_map(|(key, changed)| changed.then_some(key))
+        .collect()
+    }
+}
+
+static RELOADABLE_CONFIG: OnceLock<RwLock<ReloadableConfig>> = OnceLock::new();
+
+fn reloadable_config_lock() -> &'static RwLock<ReloadableConfig> {
+    RELOADABLE_CONFIG.get_or_init(|| {
+        RwLock::new(ReloadableConfig::from_config(
+            &CONFIG,
+            RateLimitConfig::load(),
+        ))
+    })
+}
+
+/// Hot-reloadable settings currently in effect
+pub fn reloadable_config() -> ReloadableConfig {
+    match reloadable_config_lock().read() {
+        Ok(guard) => guard.clone(),
+        Err(poisoned) => poisoned.into_inner().clone(),
+    }
+}
+
+/// Rate limits currently in effect
+pub fn rate_limit_config() -> RateLimitConfig {
+    reloadable_config().rate_limits
+}
+
+/// Read Rocket.toml again and swap in the hot-reloadable settings
+///
+/// Environment variables are read once per process, so they keep overriding
+/// the file. Nothing is applied when the new configuration is invalid.
+/// Returns the names of the settings that changed.
+pub fn reload_config() -> Result<Vec<&'static str>, ConfigErrors> {
+    let config = Config::load();
+    let rate_limits = RateLimitConfig::load();
+    let mut errors = config.validate().err().map(|e| e.0).unwrap_or_default();
+    errors.extend(
+        rate_limits
+            .validate()
+            .err()
+            .map(|e| e.0)
+            .unwrap_or_default(),
+    );
+    if !errors.is_empty() {
+        return Err(ConfigErrors(errors));
+    }
+
+    let reloaded = ReloadableConfig::from_config(&config, rate_limits);
+    let mut current = match reloadable_config_lock().write() {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    let changed = current.changed_keys(&reloaded);
+    *current = reloaded;
+    Ok(changed)
+}
+
 /// Game constants
 pub struct Constants;
 
@@ -494,22 +1164,31 @@ impl Constants {
     /// Ban duration in days for repeated violations
     pub const BAN_TIME: [i32; 5] = [1, 3, 7, 15, 31];
 
-    /// Maximum stamina value
+    /// Default of the `max_stamina` game constant
     pub const MAX_STAMINA: i32 = 12;
 
+    /// Default of the `stamina_limit` game constant, the stamina above which
+    /// fragment and ticket stamina purchases are refused
+    pub const STAMINA_LIMIT: i32 = 999;
+
     /// Insight toggle states
     pub const INSIGHT_TOGGLE_STATES: [i32; 4] = [3, 4, 5, 6];
 
-    /// Stamina recovery time in milliseconds (30 minutes)
+    /// Insight state after Eden Append 1 unlocks the Insight character
+    pub const INSIGHT_UNLOCKED_STATE: i32 = 1;
+
+    /// Insight state after the Lephon ascent, the first toggle state
+    pub const INSIGHT_ASCENDED_STATE: i32 = 3;
+
+    /// Default of the `stamina_recover_tick` game constant, in milliseconds
+    /// (30 minutes)
     pub const STAMINA_RECOVER_TICK: i64 = 1800000;
 
-    /// Fragment stamina recovery time in milliseconds (23 hours)
+    /// Default of the `fragment_stamina_cooldown` game constant, in
+    /// milliseconds (23 hours)
     pub const FRAGSTAM_RECOVER_TICK: i64 = 23 * 3600 * 1000;
 
-    /// Course stamina cost
-    pub const COURSE_STAMINA_COST: i32 = 4;
-
-    /// Core experience points
+    /// Default of the `core_exp` game constant
     pub const CORE_EXP: i32 = 250;
 
     /// World value names
@@ -526,8 +1205,10 @@ impl Constants {
     pub const LUNA_UNCAP_BONUS_PROGRESS: i32 = 7;
     pub const AYU_UNCAP_BONUS_PROGRESS: i32 = 5;
 
-    /// Skill related constants
-    pub const SKILL_FATALIS_WORLD_LOCKED_TIME: i64 = 3600000; // 1 hour
+    /// Default of the `fatalis_world_locked_time` game constant (1 hour)
+    pub const SKILL_FATALIS_WORLD_LOCKED_TIME: i64 = 3600000;
+
+    /// Cap of the Hikari (Fatalis) stat addition
     pub const FATALIS_MAX_VALUE: i32 = 100;
 
     /// Mika skill songs
@@ -597,7 +1278,7 @@ pub const ARCAEA_DATABASE_VERSION: &str = "v0.1.0";
 pub const ARCAEA_LOG_DATABASE_VERSION: &str = "v0.1.0";
 
 /// Rate limiting configuration
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct RateLimitConfig {
     pub game_register_ip_rate_limit: String,
     pub game_register_device_rate_limit: String,
@@ -657,6 +1338,33 @@ impl RateLimitConfig {
         set_from_env!(self, game_register_device_rate_limit, String);
         set_from_env!(self, game_login_rate_limit, String);
     }
+
+    /// Check that every limit parses, see [`parse_rate_limit`]
+    pub fn validate(&self) -> Result<(), ConfigErrors> {
+        let errors: Vec<String> = [
+            (
+                "game_register_ip_rate_limit",
+                &self.game_register_ip_rate_limit,
+            ),
+            (
+                "game_register_device_rate_limit",
+                &self.game_register_device_rate_limit,
+            ),
+            ("game_login_rate_limit", &self.game_login_rate_limit),
+        ]
+        .into_iter()
+        .filter(|(_, value)| parse_rate_limit(value).is_none())
+        .map(|(name, value)| {
+            format!("{name} `{value}` must look like `5/minute` (second, minute, hour or day)")
+        })
+        .collect();
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigErrors(errors))
+        }
+    }
 }
 
 fn rocket_toml_figment() -> Figment {
@@ -717,6 +1425,18 @@ impl EnvConfigValue for Vec<String> {
     }
 }
 
+impl EnvConfigValue for Vec<GameApiVersion> {
+    fn parse_env(key: &str, value: &str) -> Option<Self> {
+        match serde_json::from_str(value.trim()) {
+            Ok(versions) => Some(versions),
+            Err(e) => {
+                log::warn!("Ignoring invalid config env {key}: {e}");
+                None
+            }
+        }
+    }
+}
+
 impl EnvConfigValue for Option<String> {
     fn parse_env(_key: &str, value: &str) -> Option<Self> {
         match value.trim().to_ascii_lowercase().as_str() {
@@ -744,7 +1464,7 @@ macro_rules! impl_from_str_env_value {
     };
 }
 
-impl_from_str_env_value!(u16, i32, i64, f64);
+impl_from_str_env_value!(u16, u32, i32, i64, u64, usize, f64);
 
 fn env_config_value<T: EnvConfigValue>(key: &str) -> Option<T> {
     let value = env::var(key).ok()?;
@@ -755,7 +1475,84 @@ fn env_config_value<T: EnvConfigValue>(key: &str) -> Option<T> {
     T::parse_env(key, &value)
 }
 
-lazy_static! {
-    /// Global rate limiting configuration
-    pub static ref RATE_LIMIT_CONFIG: RateLimitConfig = RateLimitConfig::load();
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_config() {
+        assert_eq!(Config::default().validate(), Ok(()));
+
+        let config = Config {
+            game_api_prefix: "coldwind/35".to_string(),
+            old_game_api_prefix: vec!["/join/21?".to_string()],
+            download_link_prefix: "ftp://example.com".to_string(),
+            download_times_limit: -1,
+            storage_backend: "gcs".to_string(),
+            ..Config::default()
+        };
+        let errors = config.validate().unwrap_err().0;
+        assert_eq!(errors.len(), 5);
+        assert!(errors[0].contains("`coldwind/35`"));
+        assert!(errors[1].contains("`/join/21?`"));
+        assert!(errors[2].starts_with("download_link_prefix"));
+        assert!(errors[3].starts_with("download_times_limit"));
+        assert!(errors[4].starts_with("storage_backend"));
+    }
+
+    #[test]
+    fn test_link_prefix() {
+        assert!(is_valid_link_prefix(""));
+        assert!(is_valid_link_prefix("/download"));
+        assert!(is_valid_link_prefix("https://cdn.example.com/songs/"));
+        assert!(!is_valid_link_prefix("https://"));
+        assert!(!is_valid_link_prefix("cdn.example.com"));
+    }
+
+    #[test]
+    fn test_parse_rate_limit() {
+        assert_eq!(
+            parse_rate_limit("5/minute"),
+            Some((5, Duration::from_secs(60)))
+        );
+        assert_eq!(
+            parse_rate_limit("100 per days"),
+            Some((100, Duration::from_secs(86400)))
+        );
+        assert_eq!(parse_rate_limit("0/minute"), None);
+        assert_eq!(parse_rate_limit("5/fortnight"), None);
+        assert_eq!(parse_rate_limit("often"), None);
+        assert!(RateLimitConfig::default().validate().is_ok());
+    }
+
+    #[test]
+    fn test_reloadable_changed_keys() {
+        let config = Config::default();
+        let before = ReloadableConfig::from_config(&config, RateLimitConfig::default());
+        assert_eq!(before.download_link_prefix, None);
+
+        let config = Config {
+            download_link_prefix: " https://cdn.example.com/ ".to_string(),
+            download_times_limit: config.download_times_limit + 1,
+            ..config
+        };
+        let rate_limits = RateLimitConfig {
+            game_login_rate_limit: "20/minute".to_string(),
+            ..RateLimitConfig::default()
+        };
+        let after = ReloadableConfig::from_config(&config, rate_limits);
+        assert_eq!(
+            after.download_link_prefix.as_deref(),
+            Some("https://cdn.example.com/")
+        );
+        assert_eq!(
+            before.changed_keys(&after),
+            vec![
+                "download_link_prefix",
+                "download_times_limit",
+                "game_login_rate_limit"
+            ]
+        );
+        assert!(after.changed_keys(&after).is_empty());
+    }
 }
@@ -1,9 +1,164 @@
 use rocket::serde::json::Value;
+use rocket::Request;
 use std::collections::HashMap;
 use thiserror::Error;
 
+/// Structured `extra_data` carried by official error responses
+///
+/// Each variant serializes to the keys the client reads for the dialog that
+/// belongs to the error.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ErrorExtra {
+    /// Time left on a temporary ban, in milliseconds
+    BanRemaining { remaining_ms: i64 },
+    /// Memories a purchase costs and the player's balance
+    TicketRequired { amount: i32, ticket: i32 },
+    /// Stamina a play costs, the current stamina and the seconds until
+    /// enough has recovered
+    StaminaRequired {
+        amount: i32,
+        stamina: i32,
+        cooldown_seconds: i64,
+    },
+    /// Friend list capacity that has been reached
+    FriendLimit { max_friend: i32 },
+    /// `createdAt` of the stored cloud save
+    CloudSaveConflict { created_at: i64 },
+    /// Terms of service the user still has to accept
+    TosRequired { version: String, url: String },
+}
+
+impl ErrorExtra {
+    /// Serialize into the `extra` object of the error response
+    pub fn to_map(&self) -> HashMap<String, serde_json::Value> {
+        let pairs: Vec<(&str, serde_json::Value)> = match self {
+            Self::BanRemaining { remaining_ms } => vec![("remaining_ts", (*remaining_ms).into())],
+            Self::TicketRequired { amount, ticket } => {
+                vec![("amount", (*amount).into()), ("ticket", (*ticket).into())]
+            }
+            Self::StaminaRequired {
+                amount,
+                stamina,
+                cooldown_seconds,
+            } => vec![
+                ("amount", (*amount).into()),
+                ("stamina", (*stamina).into()),
+                ("cooldown_seconds", (*cooldown_seconds).into()),
+            ],
+            Self::FriendLimit { max_friend } => vec![("max_friend", (*max_friend).into())],
+            Self::CloudSaveConflict { created_at } => vec![("createdAt", (*created_at).into())],
+            Self::TosRequired { version, url } => vec![
+                ("tos_version", version.clone().into()),
+                ("tos_url", url.clone().into()),
+            ],
+        };
+        pairs
+            .into_iter()
+            .map(|(key, value)| (key.to_string(), value))
+            .collect()
+    }
+}
+
+/// Client-facing conditions with fixed codes
+///
+/// This is the one table of which `error_code` (the client dialog), web
+/// `api_error_code` and HTTP status each condition is reported with; the
+/// matching [`ArcError`] constructors build their errors from it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// Server-side failure the client can only show as unknown
+    Internal,
+    /// Request data the action cannot be performed with
+    InvalidInput,
+    /// Score submission that fails validation
+    InvalidScore,
+    /// Resource that does not exist or is offline
+    NotFound,
+    /// World map that is locked or outside its availability window
+    MapLocked,
+    /// Not enough stamina for a world mode play
+    StaminaNotEnough,
+    /// Not enough memories for a purchase
+    TicketNotEnough,
+    /// Not enough of an item to spend
+    ItemNotEnough,
+    /// Item that cannot be claimed
+    ItemUnavailable,
+    /// Friend list is full
+    FriendLimit,
+    /// Stored cloud save is newer than the uploaded one
+    CloudSaveConflict,
+    /// Current terms of service not accepted
+    TosRequired,
+    /// Matchmaking poll from a player who is not queued
+    NotInMatchQueue,
+    /// Failure reported by the link play server
+    LinkPlay,
+}
+
+impl ErrorKind {
+    /// `(error_code, api_error_code, status)` of the condition
+    pub const fn codes(self) -> (i32, i32, u16) {
+        match self {
+            Self::Internal => (108, -999, 500),
+            Self::InvalidInput => (108, -100, 200),
+            Self::InvalidScore => (107, -100, 200),
+            Self::NotFound => (151, -999, 404),
+            Self::MapLocked => (108, -100, 200),
+            Self::StaminaNotEnough => (107, -999, 200),
+            Self::TicketNotEnough => (108, -6, 200),
+            Self::ItemNotEnough => (108, -121, 200),
+            Self::ItemUnavailable => (108, -121, 200),
+            Self::FriendLimit => (601, -1, 200),
+            Self::CloudSaveConflict => (121, -210, 200),
+            Self::TosRequired => (108, -999, 403),
+            Self::NotInMatchQueue => (999, -999, 200),
+            Self::LinkPlay => (108, -999, 400),
+        }
+    }
+
+    /// Error for this condition
+    pub fn error<S: Into<String>>(self, message: S) -> ArcError {
+        self.build(message.into(), self.codes().0, None)
+    }
+
+    /// Error for this condition carrying `extra` in the response
+    pub fn error_with<S: Into<String>>(self, message: S, extra: ErrorExtra) -> ArcError {
+        self.build(message.into(), self.codes().0, Some(extra))
+    }
+
+    fn build(self, message: String, error_code: i32, extra_data: Option<ErrorExtra>) -> ArcError {
+        let (_, api_error_code, status) = self.codes();
+        macro_rules! variant {
+            ($variant:ident) => {
+                ArcError::$variant {
+                    message,
+                    error_code,
+                    api_error_code,
+                    extra_data,
+                    status,
+                }
+            };
+        }
+        match self {
+            Self::Internal | Self::NotFound | Self::NotInMatchQueue | Self::LinkPlay => {
+                variant!(Base)
+            }
+            Self::InvalidInput | Self::InvalidScore => variant!(Input),
+            Self::MapLocked => variant!(MapLocked),
+            Self::StaminaNotEnough => variant!(StaminaNotEnough),
+            Self::TicketNotEnough => variant!(TicketNotEnough),
+            Self::ItemNotEnough => variant!(ItemNotEnough),
+            Self::ItemUnavailable => variant!(ItemUnavailable),
+            Self::FriendLimit => variant!(Friend),
+            Self::CloudSaveConflict => variant!(DataExist),
+            Self::TosRequired => variant!(NoAccess),
+        }
+    }
+}
+
 /// Main error type for the Arcaea server
-#[derive(Error, Debug)]
+#[derive(Error, Debug, Clone)]
 pub enum ArcError {
     /// Base Arcaea error
     #[error("{message}")]
@@ -11,7 +166,7 @@ pub enum ArcError {
         message: String,
         error_code: i32,
         api_error_code: i32,
-        extra_data: Option<HashMap<String, serde_json::Value>>,
+        extra_data: Option<ErrorExtra>,
         status: u16,
     },
 
@@ -21,7 +176,7 @@ pub enum ArcError {
         message: String,
         error_code: i32,
         api_error_code: i32,
-        extra_data: Option<HashMap<String, serde_json::Value>>,
+        extra_data: Option<ErrorExtra>,
         status: u16,
     },
 
@@ -31,7 +186,7 @@ pub enum ArcError {
         message: String,
         error_code: i32,
         api_error_code: i32,
-        extra_data: Option<HashMap<String, serde_json::Value>>,
+        extra_data: Option<ErrorExtra>,
         status: u16,
     },
 
@@ -41,7 +196,7 @@ pub enum ArcError {
         message: String,
         error_code: i32,
         api_error_code: i32,
-        extra_data: Option<HashMap<String, serde_json::Value>>,
+        extra_data: Option<ErrorExtra>,
         status: u16,
     },
 
@@ -51,7 +206,7 @@ pub enum ArcError {
         message: String,
         error_code: i32,
         api_error_code: i32,
-        extra_data: Option<HashMap<String, serde_json::Value>>,
+        extra_data: Option<ErrorExtra>,
         status: u16,
     },
 
@@ -61,7 +216,7 @@ pub enum ArcError {
         message: String,
         error_code: i32,
         api_error_code: i32,
-        extra_data: Option<HashMap<String, serde_json::Value>>,
+        extra_data: Option<ErrorExtra>,
         status: u16,
     },
 
@@ -71,7 +226,7 @@ pub enum ArcError {
         message: String,
         error_code: i32,
         api_error_code: i32,
-        extra_data: Option<HashMap<String, serde_json::Value>>,
+        extra_data: Option<ErrorExtra>,
         status: u16,
     },
 
@@ -81,7 +236,7 @@ pub enum ArcError {
         message: String,
         error_code: i32,
         api_error_code: i32,
-        extra_data: Option<HashMap<String, serde_json::Value>>,
+        extra_data: Option<ErrorExtra>,
         status: u16,
     },
 
@@ -91,7 +246,7 @@ pub enum ArcError {
         message: String,
         error_code: i32,
         api_error_code: i32,
-        extra_data: Option<HashMap<String, serde_json::Value>>,
+        extra_data: Option<ErrorExtra>,
         status: u16,
     },
 
@@ -101,7 +256,7 @@ pub enum ArcError {
         message: String,
         error_code: i32,
         api_error_code: i32,
-        extra_data: Option<HashMap<String, serde_json::Value>>,
+        extra_data: Option<ErrorExtra>,
         status: u16,
     },
 
@@ -111,7 +266,7 @@ pub enum ArcError {
         message: String,
         error_code: i32,
         api_error_code: i32,
-        extra_data: Option<HashMap<String, serde_json::Value>>,
+        extra_data: Option<ErrorExtra>,
         status: u16,
     },
 
@@ -121,7 +276,7 @@ pub enum ArcError {
         message: String,
         error_code: i32,
         api_error_code: i32,
-        extra_data: Option<HashMap<String, serde_json::Value>>,
+        extra_data: Option<ErrorExtra>,
         status: u16,
     },
 
@@ -131,7 +286,7 @@ pub enum ArcError {
         message: String,
         error_code: i32,
         api_error_code: i32,
-        extra_data: Option<HashMap<String, serde_json::Value>>,
+        extra_data: Option<ErrorExtra>,
         status: u16,
     },
 
@@ -141,7 +296,7 @@ pub enum ArcError {
         message: String,
         error_code: i32,
         api_error_code: i32,
-        extra_data: Option<HashMap<String, serde_json::Value>>,
+        extra_data: Option<ErrorExtra>,
         status: u16,
     },
 
@@ -151,7 +306,7 @@ pub enum ArcError {
         message: String,
         error_code: i32,
         api_error_code: i32,
-        extra_data: Option<HashMap<String, serde_json::Value>>,
+        extra_data: Option<ErrorExtra>,
         status: u16,
     },
 
@@ -161,7 +316,7 @@ pub enum ArcError {
         message: String,
         error_code: i32,
         api_error_code: i32,
-        extra_data: Option<HashMap<String, serde_json::Value>>,
+        extra_data: Option<ErrorExtra>,
         status: u16,
     },
 
@@ -171,7 +326,7 @@ pub enum ArcError {
         message: String,
         error_code: i32,
         api_error_code: i32,
-        extra_data: Option<HashMap<String, serde_json::Value>>,
+        extra_data: Option<ErrorExtra>,
         status: u16,
     },
 
@@ -195,13 +350,29 @@ pub enum ArcError {
 impl ArcError {
     /// Create a new input validation error
     pub fn input<S: Into<String>>(message: S) -> Self {
-        Self::Input {
-            message: message.into(),
-            error_code: 108,
-            api_error_code: -100,
-            extra_data: None,
-            status: 200,
-        }
+        ErrorKind::InvalidInput.error(message)
+    }
+
+    /// Create an invalid score submission error
+    pub fn invalid_score() -> Self {
+        ErrorKind::InvalidScore.error("Invalid score.")
+    }
+
+    /// Create a server-side failure error
+    pub fn internal<S: Into<String>>(message: S) -> Self {
+        ErrorKind::Internal.error(message)
+    }
+
+    /// Create a not found error for a missing or offline resource
+    pub fn not_found<S: Into<String>>(message: S) -> Self {
+        ErrorKind::NotFound.error(message)
+    }
+
+    /// Create a link play error; `error_code` is the code the link play
+    /// server answered with, if any
+    pub fn linkplay<S: Into<String>>(message: S, error_code: Option<i32>) -> Self {
+        let kind = ErrorKind::LinkPlay;
+        kind.build(message.into(), error_code.unwrap_or(kind.codes().0), None)
     }
 
     /// Create a new data exists error
@@ -215,6 +386,20 @@ impl ArcError {
         }
     }
 
+    /// Create a cloud save conflict error.
+    ///
+    /// Raised when the stored save is newer than the one the client based
+    /// its upload on; `createdAt` of the stored save is sent back so the
+    /// client can offer to download it instead.
+    pub fn cloud_save_conflict(server_created_at: i64) -> Self {
+        ErrorKind::CloudSaveConflict.error_with(
+            "Cloud save is newer than the uploaded save.",
+            ErrorExtra::CloudSaveConflict {
+                created_at: server_created_at,
+            },
+        )
+    }
+
     /// Create a new no data error
     pub fn no_data<S: Into<String>>(message: S, error_code: i32) -> Self {
         Self::NoData {
@@ -237,21 +422,41 @@ impl ArcError {
         }
     }
 
-    /// Create a new user ban error with extra data
-    pub fn user_ban<S: Into<String>>(
-        message: S,
-        error_code: i32,
-        extra_data: Option<HashMap<String, serde_json::Value>>,
-    ) -> Self {
+    /// Create a new map locked error
+    pub fn map_locked<S: Into<String>>(message: S) -> Self {
+        ErrorKind::MapLocked.error(message)
+    }
+
+    /// Create a new user ban error
+    pub fn user_ban<S: Into<String>>(message: S, error_code: i32) -> Self {
+        Self::UserBan {
+            message: message.into(),
+            error_code,
+            api_error_code: -202,
+            extra_data: None,
+            status: 200,
+        }
+    }
+
+    /// Create a temporary ban error telling the client when it ends
+    pub fn temporary_ban<S: Into<String>>(message: S, error_code: i32, remaining_ms: i64) -> Self {
         Self::UserBan {
             message: message.into(),
             error_code,
             api_error_code: -202,
-            extra_data,
+            extra_data: Some(ErrorExtra::BanRemaining { remaining_ms }),
             status: 200,
         }
     }
 
+    /// Create a terms of service error naming the version to accept
+    pub fn tos_required(version: String, url: String) -> Self {
+        ErrorKind::TosRequired.error_with(
+            "The current terms of service have not been accepted.",
+            ErrorExtra::TosRequired { version, url },
+        )
+    }
+
     /// Create a new no access error
     pub fn no_access<S: Into<String>>(message: S, error_code: i32) -> Self {
         Self::NoAccess {
@@ -263,6 +468,17 @@ impl ArcError {
         }
     }
 
+    /// Create a client version error
+    pub fn low_version<S: Into<String>>(message: S, error_code: i32) -> Self {
+        Self::LowVersion {
+            message: message.into(),
+            error_code,
+            api_error_code: -999,
+            extra_data: None,
+            status: 403,
+        }
+    }
+
     /// Create a new rate limit error
     pub fn rate_limit<S: Into<String>>(message: S, error_code: i32) -> Self {
         Self::RateLimit {
@@ -285,6 +501,14 @@ impl ArcError {
         }
     }
 
+    /// Create a full friend list error
+    pub fn friend_limit(max_friend: i32) -> Self {
+        ErrorKind::FriendLimit.error_with(
+            "The number of friends has reached the limit.",
+            ErrorExtra::FriendLimit { max_friend },
+        )
+    }
+
     /// Create a new ticket not enough error
     pub fn ticket_not_enough<S: Into<String>>(message: S, api_error_code: i32) -> Self {
         Self::TicketNotEnough {
@@ -296,6 +520,27 @@ impl ArcError {
         }
     }
 
+    /// Create a ticket not enough error carrying the price and balance
+    pub fn ticket_required(amount: i32, ticket: i32) -> Self {
+        ErrorKind::TicketNotEnough.error_with(
+            "The user does not have enough memories.",
+            ErrorExtra::TicketRequired { amount, ticket },
+        )
+    }
+
+    /// Create a stamina not enough error carrying the cost, the current
+    /// stamina and the seconds until enough has recovered
+    pub fn stamina_not_enough(amount: i32, stamina: i32, cooldown_seconds: i64) -> Self {
+        ErrorKind::StaminaNotEnough.error_with(
+            "Stamina is not enough.",
+            ErrorExtra::StaminaRequired {
+                amount,
+                stamina,
+                cooldown_seconds,
+            },
+        )
+    }
+
     /// Create a new item unavailable error
     pub fn item_unavailable<S: Into<String>>(message: S, error_code: i32) -> Self {
         Self::ItemUnavailable {
@@ -309,13 +554,12 @@ impl ArcError {
 
     /// Create a new item not enough error
     pub fn item_not_enough<S: Into<String>>(message: S) -> Self {
-        Self::ItemNotEnough {
-            message: message.into(),
-            error_code: 108,
-            api_error_code: -121,
-            extra_data: None,
-            status: 200,
-        }
+        ErrorKind::ItemNotEnough.error(message)
+    }
+
+    /// Create an error for an item that cannot be claimed
+    pub fn item_not_claimable<S: Into<String>>(message: S) -> Self {
+        ErrorKind::ItemUnavailable.error(message)
     }
 
     /// Create a rocket error
@@ -351,6 +595,33 @@ impl ArcError {
         }
     }
 
+    /// Message without the prefix of the error kind
+    pub fn message(&self) -> &str {
+        match self {
+            Self::Base { message, .. }
+            | Self::Input { message, .. }
+            | Self::DataExist { message, .. }
+            | Self::NoData { message, .. }
+            | Self::Post { message, .. }
+            | Self::UserBan { message, .. }
+            | Self::ItemNotEnough { message, .. }
+            | Self::ItemUnavailable { message, .. }
+            | Self::RedeemUnavailable { message, .. }
+            | Self::MapLocked { message, .. }
+            | Self::StaminaNotEnough { message, .. }
+            | Self::TicketNotEnough { message, .. }
+            | Self::Friend { message, .. }
+            | Self::NoAccess { message, .. }
+            | Self::LowVersion { message, .. }
+            | Self::Timeout { message, .. }
+            | Self::RateLimit { message, .. }
+            | Self::Database { message }
+            | Self::Json { message }
+            | Self::Rocket { message }
+            | Self::Io { message } => message,
+        }
+    }
+
     /// Get the error code for this error
     pub fn error_code(&self) -> i32 {
         match self {
@@ -403,8 +674,8 @@ impl ArcError {
         }
     }
 
-    /// Get the extra data for this error
-    pub fn extra_data(&self) -> Option<&HashMap<String, serde_json::Value>> {
+    /// Get the typed extra data for this error
+    pub fn extra(&self) -> Option<&ErrorExtra> {
         match self {
             Self::Base { extra_data, .. }
             | Self::Input { extra_data, .. }
@@ -428,8 +699,23 @@ impl ArcError {
             }
         }
     }
+
+    /// Get the extra data for this error as sent in the response
+    pub fn extra_data(&self) -> Option<HashMap<String, serde_json::Value>> {
+        self.extra().map(ErrorExtra::to_map)
+    }
+
+    /// Keep this error as the reason `request` failed, so the catcher for
+    /// the guard's status answers with its code and extra data
+    pub fn for_catcher(self, request: &Request<'_>) -> Self {
+        request.local_cache(|| GuardFailure(Some(self.clone())));
+        self
+    }
 }
 
+/// Error a request guard failed with, in the request-local cache
+struct GuardFailure(Option<ArcError>);
+
 impl From<sqlx::Error> for ArcError {
     fn from(err: sqlx::Error) -> Self {
         Self::Database {
@@ -457,54 +743,100 @@ impl From<std::io::Error> for ArcError {
 /// Alias for Result with ArcError
 pub type ArcResult<T> = Result<T, ArcError>;
 
+/// Catcher body: the error of the failed request guard if it left one,
+/// otherwise a generic error for the status
+fn catcher_response(request: &Request<'_>, status: u16, message: &str) -> Value {
+    let GuardFailure(Some(error)) = request.local_cache(|| GuardFailure(None)) else {
+        return rocket::serde::json::json!({
+            "success": false,
+            "error_code": status,
+            "message": message
+        });
+    };
+    let mut body = rocket::serde::json::json!({
+        "success": false,
+        "error_code": error.error_code(),
+        "message": crate::i18n::localize_error(error, &crate::i18n::request_languages(request))
+    });
+    if let Some(extra) = error.extra_data() {
+        body["extra"] = rocket::serde::json::json!(extra);
+    }
+    body
+}
+
 /// 404 Not Found handler
 #[rocket::catch(404)]
-pub fn not_found() -> Value {
-    rocket::serde::json::json!({
-        "success": false,
-        "error_code": 404,
-        "message": "Endpoint not found"
-    })
+pub fn not_found(request: &Request<'_>) -> Value {
+    catcher_response(request, 404, "Endpoint not found")
 }
 
 /// 500 Internal Server Error handler
 #[rocket::catch(500)]
-pub fn internal_error() -> Value {
-    rocket::serde::json::json!({
-        "success": false,
-        "error_code": 500,
-        "message": "Internal server error"
-    })
+pub fn internal_error(request: &Request<'_>) -> Value {
+    catcher_response(request, 500, "Internal server error")
 }
 
 /// 400 Bad Request handler
 #[rocket::catch(400)]
-pub fn bad_request() -> Value {
-    rocket::serde::json::json!({
-        "success": false,
-        "error_code": 400,
-        "message": "Bad request"
-    })
+pub fn bad_request(request: &Request<'_>) -> Value {
+    catcher_response(request, 400, "Bad request")
 }
 
 /// 401 Unauthorized handler
 #[rocket::catch(401)]
-pub fn unauthorized() -> Value {
-    rocket::serde::json::json!({
-        "success": false,
-        "error_code": 401,
-        "message": "Unauthorized"
-    })
+pub fn unauthorized(request: &Request<'_>) -> Value {
+    catcher_response(request, 401, "Unauthorized")
 }
 
 /// 403 Forbidden handler
 #[rocket::catch(403)]
-pub fn forbidden() -> Value {
-    rocket::serde::json::json!({
-        "success": false,
-        "error_code": 403,
-        "message": "Forbidden"
-    })
+pub fn forbidden(request: &Request<'_>) -> Value {
+    catcher_response(request, 403, "Forbidden")
 }
 
 // https://arcapi-v3.lowiro.com/summerfestival/36
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_extra_keys() {
+        let error = ArcError::temporary_ban("banned", 105, 3600);
+        assert_eq!(
+            error.extra_data().unwrap().get("remaining_ts"),
+            Some(&serde_json::Value::from(3600))
+        );
+
+        let error = ArcError::ticket_required(50, 20);
+        let extra = error.extra_data().unwrap();
+        assert_eq!(extra.get("amount"), Some(&serde_json::Value::from(50)));
+        assert_eq!(extra.get("ticket"), Some(&serde_json::Value::from(20)));
+        assert_eq!(error.api_error_code(), -6);
+
+        assert!(ArcError::user_ban("banned", 106).extra_data().is_none());
+    }
+
+    #[test]
+    fn test_error_kind_codes() {
+        let error = ArcError::stamina_not_enough(2, 1, 1800);
+        assert!(matches!(error, ArcError::StaminaNotEnough { .. }));
+        assert_eq!(error.error_code(), 107);
+        assert_eq!(error.status(), 200);
+        assert!(error.extra_data().unwrap().contains_key("cooldown_seconds"));
+
+        let error = ArcError::tos_required("2".to_string(), "https://example.com".to_string());
+        assert_eq!(
+            (error.error_code(), error.api_error_code(), error.status()),
+            ErrorKind::TosRequired.codes()
+        );
+
+        assert_eq!(ArcError::linkplay("full", Some(5)).error_code(), 5);
+        assert_eq!(ArcError::linkplay("closed", None).status(), 400);
+        assert_eq!(ArcError::not_found("Invalid pack_id").error_code(), 151);
+        assert_eq!(
+            ArcError::invalid_score().to_string(),
+            "Input error: Invalid score."
+        );
+    }
+}
@@ -0,0 +1,210 @@
+//! Localized client-facing messages
+//!
+//! Message catalogs are embedded from `assets/i18n/<language>.json`. Each one
+//! maps English error messages (`messages`) and single-meaning error codes
+//! (`codes`) to its language, plus the texts of push notifications
+//! (`notifications`). Lookups walk a fallback chain: the client's languages
+//! in preference order, then `default_language`, then the original English.
+
+use crate::config::CONFIG;
+use crate::error::ArcError;
+use rocket::Request;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+/// Embedded catalogs; `en` holds the base notification texts.
+const EMBEDDED_CATALOGS: [(&str, &str); 4] = [
+    ("en", include_str!("../assets/i18n/en.json")),
+    ("zh-Hans", include_str!("../assets/i18n/zh-Hans.json")),
+    ("zh-Hant", include_str!("../assets/i18n/zh-Hant.json")),
+    ("ja", include_str!("../assets/i18n/ja.json")),
+];
+
+static CATALOGS: OnceLock<HashMap<&'static str, Catalog>> = OnceLock::new();
+
+/// One language's translations.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct Catalog {
+    messages: HashMap<String, String>,
+    codes: HashMap<i32, String>,
+    notifications: HashMap<String, String>,
+}
+
+fn catalogs() -> &'static HashMap<&'static str, Catalog> {
+    CATALOGS.get_or_init(|| {
+        EMBEDDED_CATALOGS
+            .iter()
+            .map(|(language, raw)| {
+                let catalog = serde_json::from_str(raw).unwrap_or_else(|e| {
+                    log::error!("Invalid message catalog `{language}`: {e}");
+                    Catalog::default()
+                });
+                (*language, catalog)
+            })
+            .collect()
+    })
+}
+
+/// Catalog language a BCP 47 tag resolves to, e.g. `zh-TW` -> `zh-Hant`.
+pub(crate) fn catalog_language(tag: &str) -> Option<&'static str> {
+    let tag = tag.trim().replace('_', "-").to_ascii_lowercase();
+    let resolved = match tag.as_str() {
+        "zh" | "zh-cn" | "zh-sg" => "zh-hans",
+        "zh-tw" | "zh-hk" | "zh-mo" => "zh-hant",
+        tag if tag.starts_with("zh-hans") => "zh-hans",
+        tag if tag.starts_with("zh-hant") => "zh-hant",
+        tag => tag,
+    };
+    let primary = resolved.split('-').next().unwrap_or_default();
+    let language = [resolved, primary].into_iter().find_map(|candidate| {
+        EMBEDDED_CATALOGS
+            .iter()
+            .map(|(language, _)| *language)
+            .find(|language| language.eq_ignore_ascii_case(candidate))
+    });
+    language
+}
+
+/// Languages to try, most preferred first
+///
+/// An explicit language tag wins over the `Accept-Language` list, which is
+/// ordered by its `q` weights. `default_language` always closes the chain.
+pub fn language_chain(explicit: Option<&str>, accept_language: Option<&str>) -> Vec<&'static str> {
+    let mut weighted: Vec<(&str, f32)> = accept_language
+        .unwrap_or_default()
+        .split(',')
+        .filter_map(|entry| {
+            let mut parts = entry.split(';');
+            let tag = parts.next()?.trim();
+            let weight = parts
+                .find_map(|param| param.trim().strip_prefix("q="))
+                .and_then(|q| q.trim().parse::<f32>().ok())
+                .unwrap_or(1.0);
+            (!tag.is_empty() && tag != "*" && weight > 0.0).then_some((tag, weight))
+        })
+        .collect();
+    weighted.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+    let mut chain = Vec::new();
+    let tags = explicit
+        .into_iter()
+        .chain(weighted.into_iter().map(|(tag, _)| tag))
+        .chain(std::iter::once(CONFIG.default_language.as_str()));
+    for language in tags.filter_map(catalog_language) {
+        if !chain.contains(&language) {
+            chain.push(language);
+        }
+    }
+    chain
+}
+
+/// Languages of the client that sent `request`, from its `i` header (an
+/// explicit language tag) and `Accept-Language`.
+pub fn request_languages(request: &Request<'_>) -> Vec<&'static str> {
+    language_chain(
+        request.headers().get_one("i"),
+        request.headers().get_one("Accept-Language"),
+    )
+}
+
+/// Message for `error` in the first language of `languages` that has one,
+/// otherwise the error's own text.
+pub fn localize_error(error: &ArcError, languages: &[&str]) -> String {
+    let catalogs = catalogs();
+    languages
+        .iter()
+        .filter_map(|language| catalogs.get(language))
+        .find_map(|catalog| {
+            catalog
+                .messages
+                .get(error.message())
+                .or_else(|| catalog.codes.get(&error.error_code()))
+        })
+        .cloned()
+        .unwrap_or_else(|| error.to_string())
+}
+
+/// Notification text `key` in `default_language`, falling back to English,
+/// with each `{name}` placeholder replaced from `args`.
+pub fn notification_text(key: &str, args: &[(&str, &str)]) -> String {
+    let catalogs = catalogs();
+    let template = language_chain(None, None)
+        .into_iter()
+        .chain(std::iter::once("en"))
+        .filter_map(|language| catalogs.get(language))
+        .find_map(|catalog| catalog.notifications.get(key))
+        .map(String::as_str)
+        .unwrap_or(key);
+    args.iter()
+        .fold(template.to_string(), |text, (name, value)| {
+            text.replace(&format!("{{{name}}}"), value)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_catalogs_parse() {
+        let catalogs = catalogs();
+        assert_eq!(catalogs.len(), EMBEDDED_CATALOGS.len());
+        let base = &catalogs["en"];
+        for (language, catalog) in catalogs {
+            assert!(
+                !catalog.notifications.is_empty(),
+                "`{language}` has no notifications"
+            );
+            for key in catalog.notifications.keys() {
+                assert!(
+                    base.notifications.contains_key(key),
+                    "`{language}` notification `{key}` is missing from `en`"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_language_chain() {
+        assert_eq!(
+            language_chain(None, Some("fr-FR, zh-TW;q=0.8, ja;q=0.9")),
+            vec!["ja", "zh-Hant", "en"]
+        );
+        assert_eq!(
+            language_chain(Some("zh-CN"), Some("ja")),
+            vec!["zh-Hans", "ja", "en"]
+        );
+        assert_eq!(language_chain(None, Some("en-US,*;q=0.1")), vec!["en"]);
+        assert_eq!(language_chain(Some("garbage"), None), vec!["en"]);
+    }
+
+    #[test]
+    fn test_localize_error() {
+        let error = ArcError::invalid_score();
+        assert_eq!(localize_error(&error, &["ja", "en"]), "無効なスコアです。");
+        assert_eq!(
+            localize_error(&error, &["en"]),
+            "Input error: Invalid score."
+        );
+
+        let error = ArcError::friend_limit(50);
+        assert_eq!(localize_error(&error, &["zh-Hans"]), "好友数量已达上限。");
+
+        let error = ArcError::input("Something new.");
+        assert_eq!(
+            localize_error(&error, &["zh-Hant"]),
+            "Input error: Something new."
+        );
+    }
+
+    #[test]
+    fn test_notification_text() {
+        assert_eq!(
+            notification_text("room_invite_body", &[("sender", "Hikari")]),
+            "Hikari invited you to a room"
+        );
+        assert_eq!(notification_text("unknown_key", &[]), "unknown_key");
+    }
+}
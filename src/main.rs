@@ -12,26 +12,49 @@ use std::env;
 use std::time::Duration;
 use Arcaea_server_rs::constants::GAME_API_PREFIX;
 use Arcaea_server_rs::error::{bad_request, forbidden, internal_error, not_found, unauthorized};
+use Arcaea_server_rs::route::access::{access_denied, IpAccessControl};
 use Arcaea_server_rs::route::admin::{set_admin_config, AdminConfig};
+use Arcaea_server_rs::route::api_version::{api_version_stub, ApiVersionShim};
+use Arcaea_server_rs::route::client_version::{
+    client_outdated, client_unsupported, ClientVersionGate,
+};
 use Arcaea_server_rs::route::download::serve_download_file;
 use Arcaea_server_rs::route::others::bundle_download;
+use Arcaea_server_rs::route::request_log::RequestLog;
 use Arcaea_server_rs::route::CORS;
+use Arcaea_server_rs::service::event::limited_events;
 use Arcaea_server_rs::service::{
-    arc_data::arc_data_file_path_from_env, AssetInitService, AssetManager, BundleService,
-    CacheService, CharacterService, DownloadService, ItemService, MultiplayerService,
-    NotificationService, OperationManager, PresentService, PurchaseService, ScoreService,
-    StorageService, UserService, WorldService,
+    access::AccessRules,
+    aggregate::AggregateRegistry,
+    api_version::GameApiVersions,
+    arc_data::arc_data_file_path_from_env,
+    client_version::ClientVersionPolicy,
+    operations::{Operation, ReloadConfig},
+    purchase::payment_provider_from_env,
+    AchievementService, AnomalyService, ApiLogService, AssetInitService, AssetManager,
+    BundleService, CacheService, CaptchaService, CdnRegions, CharacterService, DownloadService,
+    EmailService, EventService, FederationService, GameConstantsService, HealthService,
+    ItemService, LinkplayClient, LoginBonusService, MultiplayerService, NotificationService,
+    OperationManager, OwnershipService, PasswordHasher, PresentService, ProfileService,
+    PttHistoryService, PurchaseService, PushGateway, ReplayService, SaveService, ScoreService,
+    ScoreValidator, ScoreWriteQueue, SongInfoService, StorageService, TosService,
+    UnlockBitmapService, UserCache, UserExportService, UserService, VerificationService,
+    WebLinkService, WebhookEvent, WebhookNotifier, WorldService,
 };
-use Arcaea_server_rs::{config, Database, DbPool};
+use Arcaea_server_rs::{config, Database, DbPool, ReadPool};
 
 use rocket_prometheus::PrometheusMetrics;
 
 const DEFAULT_S3_METADATA_SYNC_INTERVAL_SECONDS: u64 = 180;
+const API_LOG_SWEEP_INTERVAL: Duration = Duration::from_secs(3600);
+const PTT_HISTORY_SNAPSHOT_INTERVAL: Duration = Duration::from_secs(24 * 3600);
 const DEFAULT_GAME_API_PREFIXES: &[&str] = &["/", GAME_API_PREFIX];
 
 /// Initialize application services with database connection
 async fn init_services(
     pool: DbPool,
+    read_pool: &ReadPool,
+    webhook: &WebhookNotifier,
 ) -> (
     UserService,
     DownloadService,
@@ -46,9 +69,11 @@ async fn init_services(
     std::sync::Arc<AssetManager>,
     OperationManager,
     MultiplayerService,
+    ReplayService,
+    SongInfoService,
 ) {
     let cache_service = CacheService::from_env().await;
-    let storage_service = match StorageService::from_env().await {
+    let storage_service = match StorageService::from_config(&config::CONFIG).await {
         Ok(storage) => {
             let storage = storage.with_cache(cache_service.clone());
             if storage.is_s3() {
@@ -65,17 +90,7 @@ async fn init_services(
         }
     };
 
-    let download_link_prefix = env::var("DOWNLOAD_LINK_PREFIX")
-        .ok()
-        .filter(|s| !s.trim().is_empty())
-        .or_else(|| {
-            let v = config::CONFIG.download_link_prefix.trim();
-            if v.is_empty() {
-                None
-            } else {
-                Some(v.to_string())
-            }
-        });
+    let download_link_prefix = config::reloadable_config().download_link_prefix;
 
     let bundle_download_link_prefix = env_optional_string("BUNDLE_DOWNLOAD_LINK_PREFIX")
         .unwrap_or_else(|| config::CONFIG.bundle_download_link_prefix.clone());
@@ -98,24 +113,114 @@ async fn init_services(
     }
     log::info!("Asset cache initialized successfully");
 
-    let user_service = UserService::new(pool.clone()).with_cache(cache_service.clone());
+    if config::CONFIG.sync_charts_from_songlist {
+        log::info!("Syncing chart table from songlist...");
+        if let Err(e) = asset_manager.sync_charts_from_songlist().await {
+            log::error!("Failed to sync charts from songlist: {e}");
+            std::process::exit(1);
+        }
+    }
+
+    // S3 hashes come from the storage manifest, not from scanning files.
+    if config::CONFIG.song_file_refresh_interval > 0 && storage_service.is_none() {
+        spawn_song_file_refresh(
+            asset_manager.clone(),
+            Duration::from_secs(config::CONFIG.song_file_refresh_interval),
+        );
+    }
+
+    match PasswordHasher::from_config(&config::CONFIG) {
+        Ok(hasher) => log::info!("Hashing passwords with {:?}", hasher.algorithm()),
+        Err(e) => {
+            log::error!("Invalid password hashing config: {e}");
+            std::process::exit(1);
+        }
+    }
+
+    let login_bonus_service = match LoginBonusService::load(pool.clone()) {
+        Ok(service) => service,
+        Err(e) => {
+            log::error!("Failed to load login bonus config: {e}");
+            std::process::exit(1);
+        }
+    };
+    if login_bonus_service.is_enabled() {
+        log::info!(
+            "Daily login bonus enabled, {} day cycle",
+            login_bonus_service.cycle_length()
+        );
+    }
+    let limited_events = limited_events();
+    if !limited_events.is_empty() {
+        log::info!("Loaded {} limited-time events", limited_events.len());
+    }
+    let user_service = UserService::new(pool.clone())
+        .with_cache(cache_service.clone())
+        .with_login_bonus(login_bonus_service)
+        .with_webhook(webhook.clone());
     let download_service = DownloadService::new(
         pool.clone(),
         asset_manager.clone(),
         download_link_prefix,
-        3600, // download_time_gap_limit (1 hour)
-        100,  // download_times_limit
+        config::CONFIG.download_time_gap_limit,
+        config::CONFIG.download_times_limit,
     )
-    .with_cache(cache_service.clone());
-    let score_service = ScoreService::new(pool.clone()).with_cache(cache_service.clone());
-    let notification_service = NotificationService::new(pool.clone());
+    .with_cache(cache_service.clone())
+    .with_bytes_limit(config::CONFIG.download_bytes_limit)
+    .with_url_signing(
+        &config::CONFIG.secret_key,
+        config::CONFIG.download_signed_url_ttl,
+    );
+    #[cfg(unix)]
+    spawn_config_reload_on_sighup(download_service.clone());
+    if config::CONFIG.download_token_sweep_interval > 0 {
+        spawn_download_token_sweep(
+            download_service.clone(),
+            Duration::from_secs(config::CONFIG.download_token_sweep_interval),
+        );
+    }
+    let score_service = ScoreService::new(pool.clone())
+        .with_cache(cache_service.clone())
+        .with_read_pool(read_pool)
+        .with_webhook(webhook.clone())
+        .with_write_queue(ScoreWriteQueue::spawn(
+            pool.clone(),
+            config::CONFIG.score_write_queue_capacity,
+        ));
+    if config::CONFIG.potential_ranking_refresh_interval > 0 {
+        spawn_potential_ranking_refresh(
+            ScoreService::new(pool.clone())
+                .with_cache(cache_service.clone())
+                .with_read_pool(read_pool),
+            Duration::from_secs(config::CONFIG.potential_ranking_refresh_interval),
+        );
+    }
+    if config::CONFIG.world_rank_refresh_interval > 0 {
+        spawn_world_rank_refresh(
+            ScoreService::new(pool.clone())
+                .with_cache(cache_service.clone())
+                .with_read_pool(read_pool),
+            Duration::from_secs(config::CONFIG.world_rank_refresh_interval),
+        );
+    }
+    let push_gateway = match PushGateway::from_env(pool.clone()) {
+        Ok(gateway) => gateway,
+        Err(e) => {
+            log::error!("Failed to initialize push gateway: {e}");
+            std::process::exit(1);
+        }
+    };
+    log::info!("Push delivery mode: {:?}", push_gateway.mode());
+    let notification_service =
+        NotificationService::new(pool.clone()).with_push(push_gateway.clone());
     let item_service = ItemService::new(pool.clone());
     let bundle_service = BundleService::new(
         pool.clone(),
         std::path::PathBuf::from("bundles"),
         bundle_download_link_prefix,
     )
-    .with_storage(storage_service.clone());
+    .with_storage(storage_service.clone())
+    .with_webhook(webhook.clone());
 
     // Initialize bundle service
     log::info!("Initializing bundle service...");
@@ -173,13 +278,38 @@ async fn init_services(
 
     let present_service = PresentService::new(pool.clone());
     let world_service = WorldService::new(pool.clone()).with_cache(cache_service.clone());
-    let purchase_service = PurchaseService::new(pool.clone()).with_cache(cache_service);
+    let payment_provider = match payment_provider_from_env() {
+        Ok(provider) => provider,
+        Err(e) => {
+            log::error!("Failed to initialize payment provider: {e}");
+            std::process::exit(1);
+        }
+    };
+    if let Some(provider) = &payment_provider {
+        log::info!(
+            "Purchases are paid through the `{}` provider",
+            provider.name()
+        );
+    }
+    let song_info_service = SongInfoService::new(pool.clone())
+        .with_asset_manager(asset_manager.clone())
+        .with_cache(cache_service.clone());
+    let purchase_service = PurchaseService::new(pool.clone())
+        .with_cache(cache_service)
+        .with_payment_provider(payment_provider);
     let multiplayer_service = MultiplayerService::new(pool.clone());
+    let replay_service = ReplayService::new(
+        pool.clone(),
+        std::path::PathBuf::from(config::CONFIG.replay_folder_path.trim()),
+    )
+    .with_storage(storage_service);
     let operation_manager = OperationManager::new(
         asset_manager.clone(),
         std::sync::Arc::new(bundle_service.clone()),
         pool.clone(),
-    );
+    )
+    .with_push(push_gateway.clone())
+    .with_download_service(download_service.clone());
 
     (
         user_service,
@@ -195,6 +325,8 @@ async fn init_services(
         asset_manager,
         operation_manager,
         multiplayer_service,
+        replay_service,
+        song_info_service,
     )
 }
 
@@ -238,19 +370,255 @@ fn spawn_s3_metadata_sync(bundle_service: BundleService, interval: Duration) {
     });
 }
 
+fn spawn_download_token_sweep(download_service: DownloadService, interval: Duration) {
+    log::info!(
+        "Download token sweep loop enabled, interval: {} seconds",
+        interval.as_secs()
+    );
+
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(interval).await;
+            match download_service.clear_expired_download_tokens().await {
+                Ok(purged) => log::debug!("Download token sweep purged {purged} rows"),
+                Err(e) => log::error!("Download token sweep failed: {e}"),
+            }
+            match download_service.clear_expired_download_usage().await {
+                Ok(purged) => log::debug!("Download usage sweep purged {purged} rows"),
+                Err(e) => log::error!("Download usage sweep failed: {e}"),
+            }
+        }
+    });
+}
+
+/// Reload the hot-reloadable configuration whenever the process gets SIGHUP
+#[cfg(unix)]
+fn spawn_config_reload_on_sighup(download_service: DownloadService) {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut hangups = match signal(SignalKind::hangup()) {
+        Ok(hangups) => hangups,
+        Err(e) => {
+            log::warn!("Failed to listen for SIGHUP, config reload on signal disabled: {e}");
+            return;
+        }
+    };
+
+    tokio::spawn(async move {
+        while hangups.recv().await.is_some() {
+            log::info!("SIGHUP received, reloading configuration");
+            let reload = ReloadConfig::new(Some(download_service.clone()));
+            if let Err(e) = reload.execute().await {
+                log::error!("Configuration reload failed: {e}");
+            }
+        }
+    });
+}
+
+fn spawn_song_file_refresh(asset_manager: std::sync::Arc<AssetManager>, interval: Duration) {
+    log::info!(
+        "Song file refresh loop enabled, interval: {} seconds",
+        interval.as_secs()
+    );
+
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(interval).await;
+            if let Err(e) = asset_manager.refresh_song_files_incremental().await {
+                log::error!("Song file refresh failed: {e}");
+            }
+        }
+    });
+}
+
+fn spawn_game_constants_reload(game_constants_service: GameConstantsService, interval: Duration) {
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(interval).await;
+            if let Err(e) = game_constants_service.reload().await {
+                log::error!("Game constants reload failed: {e}");
+            }
+        }
+    });
+}
+
+fn spawn_api_log_sweep(api_log_service: ApiLogService) {
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(API_LOG_SWEEP_INTERVAL).await;
+            match api_log_service.clear_expired().await {
+                Ok(purged) => log::debug!("API log sweep purged {purged} rows"),
+                Err(e) => log::error!("API log sweep failed: {e}"),
+            }
+        }
+    });
+}
+
+fn spawn_ptt_history_snapshot(ptt_history_service: PttHistoryService) {
+    tokio::spawn(async move {
+        loop {
+            match ptt_history_service.take_snapshots().await {
+                Ok(written) => log::info!("PTT history snapshot taken for {written} users"),
+                Err(e) => log::error!("PTT history snapshot failed: {e}"),
+            }
+            match ptt_history_service.clear_expired().await {
+                Ok(purged) => log::debug!("PTT history sweep purged {purged} rows"),
+                Err(e) => log::error!("PTT history sweep failed: {e}"),
+            }
+            tokio::time::sleep(PTT_HISTORY_SNAPSHOT_INTERVAL).await;
+        }
+    });
+}
+
+fn spawn_potential_ranking_refresh(score_service: ScoreService, interval: Duration) {
+    log::info!(
+        "Potential ranking refresh loop enabled, interval: {} seconds",
+        interval.as_secs()
+    );
+
+    tokio::spawn(async move {
+        loop {
+            match score_service.refresh_potential_ranking().await {
+                Ok(ranking) => log::debug!(
+                    "Potential ranking refreshed with {} players",
+                    ranking.ranks.len()
+                ),
+                Err(e) => log::error!("Potential ranking refresh failed: {e}"),
+            }
+            tokio::time::sleep(interval).await;
+        }
+    });
+}
+
+fn spawn_world_rank_refresh(score_service: ScoreService, interval: Duration) {
+    log::info!(
+        "World rank refresh loop enabled, interval: {} seconds",
+        interval.as_secs()
+    );
+
+    tokio::spawn(async move {
+        loop {
+            match score_service.recalculate_world_rank_scores(None).await {
+                Ok(changed) => log::debug!("World rank scores recomputed, {changed} users changed"),
+                Err(e) => log::error!("World rank score recompute failed: {e}"),
+            }
+            match score_service.refresh_world_ranking().await {
+                Ok(ranking) => log::debug!(
+                    "World ranking refreshed with {} players",
+                    ranking.ranks.len()
+                ),
+                Err(e) => log::error!("World ranking refresh failed: {e}"),
+            }
+            tokio::time::sleep(interval).await;
+        }
+    });
+}
+
+fn spawn_anomaly_scan(anomaly_service: AnomalyService, interval: Duration) {
+    log::info!(
+        "Score anomaly scan loop enabled, interval: {} seconds",
+        interval.as_secs()
+    );
+
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(interval).await;
+            match anomaly_service.scan().await {
+                Ok(flagged) => log::info!("Score anomaly scan flagged {flagged} accounts"),
+                Err(e) => log::error!("Score anomaly scan failed: {e}"),
+            }
+        }
+    });
+}
+
+fn spawn_federation_push(federation_service: FederationService) {
+    let interval = federation_service.push_interval();
+    log::info!(
+        "Federation push loop enabled, interval: {} seconds",
+        interval.as_secs()
+    );
+
+    tokio::spawn(async move {
+        loop {
+            federation_service.push_once().await;
+            tokio::time::sleep(interval).await;
+        }
+    });
+}
+
 /// Configure the Rocket application
+/// Refuse to start on a configuration that would only fail later
+fn validate_config() {
+    let mut errors = config::CONFIG
+        .validate()
+        .err()
+        .map(|e| e.0)
+        .unwrap_or_default();
+    errors.extend(
+        config::rate_limit_config()
+            .validate()
+            .err()
+            .map(|e| e.0)
+            .unwrap_or_default(),
+    );
+    if !errors.is_empty() {
+        for error in &errors {
+            log::error!("Invalid configuration: {error}");
+        }
+        std::process::exit(1);
+    }
+}
+
 async fn configure_rocket() -> Rocket<Build> {
+    validate_config();
     let prometheus = PrometheusMetrics::new();
+    if let Err(e) = UserCache::global().register_metrics(prometheus.registry()) {
+        log::warn!("Failed to register user cache metrics: {e}");
+    }
+    let webhook = match WebhookNotifier::from_config(
+        &config::CONFIG.webhook_url,
+        &config::CONFIG.webhook_events,
+    ) {
+        Ok(webhook) => webhook,
+        Err(e) => {
+            log::error!("Failed to initialize operator webhook: {e}");
+            std::process::exit(1);
+        }
+    };
+    if webhook.is_enabled() {
+        log::info!("Operator webhook enabled: {webhook:?}");
+    }
     let pool = match Database::connect().await {
         Ok(pool) => {
             log::info!("Database connection established");
             pool
         }
+        Err(sqlx::Error::Migrate(e)) => {
+            log::error!("Failed to run database migrations: {e}");
+            webhook
+                .notify_now(WebhookEvent::MigrationFailed {
+                    error: e.to_string(),
+                })
+                .await;
+            std::process::exit(1);
+        }
         Err(e) => {
             log::error!("Failed to connect to database: {e}");
             std::process::exit(1);
         }
     };
+    let read_pool = match Database::connect_read(&pool).await {
+        Ok(read_pool) => {
+            if env::var("DATABASE_READ_URL").is_ok_and(|url| !url.trim().is_empty()) {
+                log::info!("Read replica connection established");
+            }
+            read_pool
+        }
+        Err(e) => {
+            log::error!("Failed to connect to read replica: {e}");
+            std::process::exit(1);
+        }
+    };
     let (
         user_service,
         download_service,
@@ -265,24 +633,139 @@ async fn configure_rocket() -> Rocket<Build> {
         asset_manager,
         operation_manager,
         multiplayer_service,
-    ) = init_services(pool.clone()).await;
+        replay_service,
+        song_info_service,
+    ) = init_services(pool.clone(), &read_pool, &webhook).await;
+    let email_service = match EmailService::from_env() {
+        Ok(service) => service,
+        Err(e) => {
+            log::error!("Failed to initialize email service: {e}");
+            std::process::exit(1);
+        }
+    };
+    log::info!("Email delivery mode: {:?}", email_service.mode());
+    let verification_service = VerificationService::new(pool.clone(), email_service.clone());
+    if verification_service.is_enabled() && !email_service.is_enabled() {
+        log::warn!(
+            "Email verification is enabled but email delivery is disabled; new accounts cannot be verified"
+        );
+    }
+    let captcha_service = match CaptchaService::from_env() {
+        Ok(service) => service,
+        Err(e) => {
+            log::error!("Failed to initialize captcha service: {e}");
+            std::process::exit(1);
+        }
+    };
+    log::info!("Registration captcha: {:?}", captcha_service.provider());
+    let tos_service = TosService::new(pool.clone());
+    let save_service = SaveService::new(pool.clone());
+    let user_export_service = UserExportService::new(
+        pool.clone(),
+        std::path::PathBuf::from(config::CONFIG.user_export_folder_path.trim()),
+    );
+    let profile_service = ProfileService::new(pool.clone());
+    if tos_service.is_enabled() {
+        log::info!(
+            "Terms of service acceptance required: {}",
+            tos_service.current_version()
+        );
+    }
+    let federation_service = match FederationService::from_env(pool.clone()) {
+        Ok(service) => service,
+        Err(e) => {
+            log::error!("Failed to load federation config: {e}");
+            std::process::exit(1);
+        }
+    };
+    if federation_service.is_enabled() {
+        log::info!(
+            "Leaderboard federation enabled as `{}`, peers: {:?}",
+            federation_service.server_id(),
+            federation_service.peers()
+        );
+        if !federation_service.peers().is_empty() {
+            spawn_federation_push(federation_service.clone());
+        }
+    }
+    let achievement_service = AchievementService::new(pool.clone(), asset_manager.clone());
+    let event_service = EventService::new(pool.clone()).with_push(push_gateway);
+    let ownership_service = OwnershipService::new(pool.clone(), asset_manager.clone());
+    let unlock_bitmap_service = UnlockBitmapService::new(asset_manager.clone());
+    match GameConstantsService::new(pool.clone()).reload().await {
+        Ok(applied) => log::info!("Game constants loaded with {applied} overrides"),
+        Err(e) => log::warn!("Failed to load game constants, using defaults: {e}"),
+    }
+    if config::CONFIG.game_constants_reload_interval > 0 {
+        spawn_game_constants_reload(
+            GameConstantsService::new(pool.clone()),
+            Duration::from_secs(config::CONFIG.game_constants_reload_interval),
+        );
+    }
+    let anomaly_service = AnomalyService::from_env(pool.clone());
+    if let Some(interval) = anomaly_service.scan_interval() {
+        spawn_anomaly_scan(anomaly_service.clone(), interval);
+    }
+    let score_validator = ScoreValidator::new(pool.clone());
+    let access_rules = match AccessRules::from_env() {
+        Ok(rules) => rules,
+        Err(e) => {
+            log::error!("Failed to load IP access rules: {e}");
+            std::process::exit(1);
+        }
+    };
+    let cdn_regions = match CdnRegions::from_env() {
+        Ok(regions) => regions,
+        Err(e) => {
+            log::error!("Failed to load CDN regions: {e}");
+            std::process::exit(1);
+        }
+    };
+    if cdn_regions.is_enabled() {
+        log::info!("Download mirror regions: {:?}", cdn_regions.region_names());
+    }
+    let api_log_service = ApiLogService::spawn(pool.clone(), config::CONFIG.api_log_retention_days);
+    if api_log_service.is_enabled() {
+        spawn_api_log_sweep(api_log_service.clone());
+    }
+    let ptt_history_service =
+        PttHistoryService::new(pool.clone(), config::CONFIG.ptt_history_retention_days);
+    if ptt_history_service.is_enabled() {
+        spawn_ptt_history_snapshot(ptt_history_service.clone());
+    }
+    let health_service = HealthService::new(pool.clone())
+        .with_asset_manager(asset_manager.clone())
+        .with_bundle_service(bundle_service.clone())
+        .with_linkplay(LinkplayClient::from_env());
     log::info!("Services initialized");
 
+    // Bundle and song package uploads are the only large multipart bodies.
+    let asset_upload_limit = config::CONFIG.asset_upload_limit_mib.mebibytes();
     let figment = rocket::Config::figment()
         .merge(("cli_colors", false))
         .merge((
             "limits",
             Limits::new()
                 .limit("form", 16.mebibytes())
-                .limit("data-form", 16.mebibytes()),
+                .limit("data-form", asset_upload_limit.max(16.mebibytes()))
+                .limit("file", asset_upload_limit),
         ));
     let game_api_prefixes = game_api_prefixes(&figment);
-    let trailing_slash_paths = multiplayer_trailing_slash_paths(&game_api_prefixes);
+    let api_versions = GameApiVersions::from_config();
+    let version_prefixes = api_versions
+        .prefixes()
+        .into_iter()
+        .filter(|prefix| !game_api_prefixes.contains(prefix))
+        .collect::<Vec<_>>();
+    let logged_prefixes = [game_api_prefixes.as_slice(), version_prefixes.as_slice()].concat();
+    let trailing_slash_paths = multiplayer_trailing_slash_paths(&logged_prefixes);
     set_admin_config(admin_config(&figment));
     log::info!("Game API prefixes: {}", game_api_prefixes.join(", "));
 
     let mut rocket = rocket::custom(figment)
         .attach(CORS)
+        // Before every fairing that rewrites the path.
+        .attach(RequestLog::new(api_log_service, logged_prefixes))
         .attach(AdHoc::on_request(
             "Normalize Python client trailing slashes",
             move |request, _| {
@@ -302,6 +785,7 @@ async fn configure_rocket() -> Rocket<Build> {
             },
         ))
         .manage(pool)
+        .manage(read_pool)
         .manage(user_service)
         .manage(download_service)
         .manage(score_service)
@@ -315,11 +799,50 @@ async fn configure_rocket() -> Rocket<Build> {
         .manage(asset_manager)
         .manage(operation_manager)
         .manage(multiplayer_service)
+        .manage(email_service)
+        .manage(verification_service)
+        .manage(captcha_service)
+        .manage(tos_service)
+        .manage(profile_service)
+        .manage(WebLinkService::new())
+        .manage(AggregateRegistry::game())
+        .manage(federation_service.clone())
+        .manage(achievement_service)
+        .manage(event_service)
+        .manage(ownership_service)
+        .manage(unlock_bitmap_service)
+        .manage(anomaly_service)
+        .manage(score_validator)
+        .manage(replay_service)
+        .manage(save_service)
+        .manage(user_export_service)
+        .manage(cdn_regions)
+        .manage(ptt_history_service)
+        .manage(health_service)
+        .manage(song_info_service)
         // for prometheus telemetry
         .attach(prometheus.clone())
         .mount("/metrics", prometheus)
+        .mount("/", Arcaea_server_rs::route::health::routes())
+        .mount("/api", Arcaea_server_rs::route::song::routes())
         .mount("/web", Arcaea_server_rs::route::admin::routes())
-        .mount("/", rocket::routes![bundle_download, serve_download_file])
+        .mount(
+            "/api/v1/admin",
+            Arcaea_server_rs::route::admin::api_v1_routes(),
+        )
+        .mount("/profile", Arcaea_server_rs::route::profile::routes())
+        .mount("/me", Arcaea_server_rs::route::player::routes())
+        .mount(
+            "/",
+            rocket::routes![
+                bundle_download,
+                serve_download_file,
+                access_denied,
+                client_outdated,
+                client_unsupported,
+                api_version_stub
+            ],
+        )
         .register(
             "/",
             rocket::catchers![
@@ -331,11 +854,49 @@ async fn configure_rocket() -> Rocket<Build> {
             ],
         );
 
-    for prefix in &game_api_prefixes {
+    if federation_service.is_enabled() {
+        rocket = rocket.mount("/federation", Arcaea_server_rs::route::federation::routes());
+    }
+
+    if access_rules.is_active() {
+        log::info!("IP access rules enabled: {access_rules:?}");
+        rocket = rocket.attach(IpAccessControl::new(access_rules));
+    }
+
+    let version_policy = ClientVersionPolicy::from_config();
+    if version_policy.is_active() {
+        log::info!("Client version gate enabled: {version_policy:?}");
+        let gated_prefixes = game_api_prefixes
+            .iter()
+            .chain(&version_prefixes)
+            .cloned()
+            .chain(
+                Arcaea_server_rs::constants::OLD_GAME_API_PREFIX
+                    .iter()
+                    .map(|prefix| prefix.to_string())
+                    .chain(config::CONFIG.old_game_api_prefix.iter().cloned())
+                    .map(|prefix| normalize_prefix(&prefix))
+                    .filter(|prefix| !prefix.is_empty()),
+            )
+            .collect();
+        rocket = rocket.attach(ClientVersionGate::new(version_policy, gated_prefixes));
+    }
+
+    // Attached after the version gate, which must see the original path.
+    if api_versions.is_active() {
+        log::info!(
+            "Versioned game API prefixes: {}",
+            api_versions.prefixes().join(", ")
+        );
+        rocket = rocket.attach(ApiVersionShim::new(api_versions));
+    }
+
+    for prefix in game_api_prefixes.iter().chain(&version_prefixes) {
         rocket = mount_game_api_routes(rocket, prefix);
     }
 
-    let mut seen_old_prefixes = HashSet::new();
+    // A prefix configured as a supported version is not a legacy one.
+    let mut seen_old_prefixes = version_prefixes.into_iter().collect::<HashSet<_>>();
     for prefix in Arcaea_server_rs::constants::OLD_GAME_API_PREFIX {
         let p = normalize_prefix(prefix);
         if !p.is_empty() && seen_old_prefixes.insert(p.clone()) {
@@ -553,6 +1114,7 @@ fn mount_game_api_routes(mut rocket: Rocket<Build>, prefix: &str) -> Rocket<Buil
             Arcaea_server_rs::route::multiplayer::routes(),
         )
         .mount(prefix.clone(), Arcaea_server_rs::route::present::routes())
+        .mount(prefix.clone(), Arcaea_server_rs::route::event::routes())
         .mount(prefix.clone(), Arcaea_server_rs::route::world::routes())
         .mount(prefix, Arcaea_server_rs::route::purchase::routes());
 
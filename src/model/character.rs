@@ -27,6 +27,15 @@ impl Level {
         }
     }
 
+    /// Level cap of a character: 20, or 30 once uncapped
+    pub fn max_level_for(is_uncapped: bool) -> i32 {
+        if is_uncapped {
+            30
+        } else {
+            20
+        }
+    }
+
     /// Get experience required for current level
     pub fn level_exp(&self) -> i32 {
         LEVEL_STEPS.get(&self.level).copied().unwrap_or(0)
@@ -573,7 +582,7 @@ impl From<(Character, UserCharacter)> for CharacterInfo {
         let mut level = Level::new();
         level.level = user_char.level;
         level.exp = user_char.exp;
-        level.max_level = if user_char.is_uncapped() { 30 } else { 20 };
+        level.max_level = Level::max_level_for(user_char.is_uncapped());
 
         let mut frag = CharacterValue::new();
         frag.set_parameter(
@@ -623,7 +632,7 @@ impl From<(Character, UserCharacterFull)> for CharacterInfo {
         let mut level = Level::new();
         level.level = user_char.level;
         level.exp = user_char.exp;
-        level.max_level = if user_char.is_uncapped() { 30 } else { 20 };
+        level.max_level = Level::max_level_for(user_char.is_uncapped());
 
         let mut frag = CharacterValue::new();
         frag.set_parameter(
@@ -10,6 +10,8 @@ pub struct DownloadToken {
     pub file_name: String,
     pub token: String,
     pub time: i64,
+    /// Unix seconds after which the token is rejected.
+    pub expires_at: i64,
 }
 
 /// Song play token model representing the songplay_token table
@@ -30,6 +32,8 @@ pub struct SongplayToken {
     pub skill_cytusii_flag: Option<String>,
     pub skill_chinatsu_flag: Option<String>,
     pub invasion_flag: i32,
+    /// Issue time in milliseconds, refreshed on every course stage.
+    pub created_at: i64,
 }
 
 /// Best score model representing the best_score table
@@ -134,6 +138,8 @@ pub struct ScoreSubmission {
     pub clear_type: i32,
     pub beyond_gauge: i32,
     pub submission_hash: String,
+    /// Completion time reported by the client, in seconds.
+    pub time_played: Option<i64>,
     pub combo_interval_bonus: Option<i32>,
     pub hp_interval_bonus: Option<i32>,
     pub fever_bonus: Option<i32>,
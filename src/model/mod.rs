@@ -11,8 +11,9 @@ pub mod world;
 
 // Re-export commonly used types for convenience
 pub use user::{
-    AuthResponse, Login, LoginRequest, NewUser, RegisterResponse, User, UserAuth, UserCodeMapping,
-    UserCredentials, UserExists, UserInfo, UserLoginDevice, UserLoginDto, UserRegisterDto,
+    AccountDeletionRequest, AuthResponse, ImpersonationToken, Login, LoginRequest, NewUser,
+    RegisterResponse, User, UserAuth, UserCodeMapping, UserCredentials, UserDevice, UserExists,
+    UserInfo, UserLoginDevice, UserLoginDto, UserLoginSession, UserRegisterDto, UserSaveVersion,
 };
 
 pub use character::{
@@ -26,25 +27,30 @@ pub use download::{
     SongplayToken, WorldTokenRequest, WorldTokenResponse,
 };
 
-pub use score::{Potential, Recent30Tuple, Score, UserPlay, UserScore};
+pub use score::{
+    Potential, PotentialRankEntry, PotentialRanking, Recent30Tuple, Score, UserPlay, UserScore,
+    UserWorldRank, WorldRankEntry, WorldRanking,
+};
 
 pub use notification::{
     NewNotification, Notification, NotificationResponse, RoomInviteNotification,
 };
 
 pub use present::{
-    CreatePresentItem, CreatePresentRequest, Present, PresentItem, PresentListResponse, UserPresent,
+    CreatePresentItem, CreatePresentRequest, LoginBonusItem, LoginBonusStatus, Present,
+    PresentItem, PresentListResponse, UserPresent,
 };
 
 pub use purchase::{
     BundleItem, BundlePurchase, PackPurchaseRequest, PackSinglePurchaseResponse, Purchase,
-    PurchaseItem, PurchaseList, RedeemRequest, RedeemResponse, SinglePurchaseRequest,
-    SpecialItemPurchaseRequest, SpecialItemPurchaseResponse, StaminaPurchaseResponse,
+    PurchaseItem, PurchaseList, PurchaseLogEntry, RedeemBatch, RedeemItem, RedeemKind,
+    RedeemRequest, RedeemResponse, SinglePurchaseRequest, SpecialItemPurchaseRequest,
+    SpecialItemPurchaseResponse, StaminaPurchaseResponse,
 };
 
 pub use world::{
-    MapEnterResponse, MapParser, Stamina, StepItem, StepReward, UserMap, UserWorldEntry,
-    WorldAllResponse, WorldMap, WorldMapInfo, WorldMapResponse, WorldStep,
+    MapEnterResponse, Stamina, StepItem, StepReward, UserMap, UserWorldEntry, WorldAllResponse,
+    WorldMap, WorldMapInfo, WorldMapResponse, WorldStep,
 };
 
 pub use others::{
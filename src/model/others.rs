@@ -70,10 +70,42 @@ pub struct AggregateResponse {
 }
 
 /// Aggregate value structure
+///
+/// A failed call carries `success: false` and its error code instead of a
+/// value, so one failing call does not fail the whole batch.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AggregateValue {
     pub id: Option<serde_json::Value>,
-    pub value: serde_json::Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub success: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_code: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub extra: Option<HashMap<String, serde_json::Value>>,
+}
+
+impl AggregateValue {
+    pub fn ok(id: Option<serde_json::Value>, value: serde_json::Value) -> Self {
+        Self {
+            id,
+            value: Some(value),
+            success: None,
+            error_code: None,
+            extra: None,
+        }
+    }
+
+    pub fn error(id: Option<serde_json::Value>, error: &crate::error::ArcError) -> Self {
+        Self {
+            id,
+            value: None,
+            success: Some(false),
+            error_code: Some(error.error_code()),
+            extra: error.extra_data(),
+        }
+    }
 }
 
 /// Implement Responder for AggregateResponse
@@ -111,3 +111,25 @@ impl From<CreatePresentItem> for PresentItem {
         }
     }
 }
+
+/// One reward item of a login bonus day
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LoginBonusItem {
+    #[serde(rename = "type")]
+    pub item_type: String,
+    pub item_id: String,
+    pub amount: i32,
+}
+
+/// Login bonus progress shown in `user/me`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoginBonusStatus {
+    /// Streak counting today's bonus, whether already claimed or still due
+    pub streak: i32,
+    /// One-based day of the reward calendar for the current streak
+    pub day: usize,
+    pub cycle_length: usize,
+    pub claimed_today: bool,
+    /// Rewards of today's bonus, or of the next one when it is still due
+    pub rewards: Vec<LoginBonusItem>,
+}
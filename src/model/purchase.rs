@@ -267,12 +267,79 @@ pub struct StaminaPurchaseResponse {
     pub world_mode_locked_end_ts: i64,
 }
 
+/// A completed purchase recorded in `purchase_log`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PurchaseLogEntry {
+    /// `pack`, `single` or `special`
+    pub kind: String,
+    pub purchase_name: String,
+    /// Amount paid in `currency`
+    pub price: i32,
+    /// `memory`, `fragment` or the discount ticket item that was spent
+    pub currency: String,
+    pub time: i64,
+}
+
 /// Redeem response
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RedeemResponse {
     pub coupon: String,
 }
 
+/// How often a redeem code can be claimed, stored in `redeem.type`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RedeemKind {
+    /// The first claim uses the code up for everyone
+    SingleUse,
+    /// Every player can claim the code once
+    MultiUse,
+    /// Like `MultiUse`, but only grants fragments and is reported as a coupon
+    Fragment,
+}
+
+impl RedeemKind {
+    pub fn from_i32(value: i32) -> Option<Self> {
+        match value {
+            0 => Some(Self::SingleUse),
+            1 => Some(Self::MultiUse),
+            2 => Some(Self::Fragment),
+            _ => None,
+        }
+    }
+
+    pub fn as_i32(self) -> i32 {
+        match self {
+            Self::SingleUse => 0,
+            Self::MultiUse => 1,
+            Self::Fragment => 2,
+        }
+    }
+}
+
+/// Item granted by a redeem code
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RedeemItem {
+    pub item_id: String,
+    #[serde(rename = "type")]
+    pub item_type: String,
+    pub amount: i32,
+}
+
+/// A batch of redeem codes generated together
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RedeemBatch {
+    pub batch_id: String,
+    pub description: String,
+    pub kind: RedeemKind,
+    pub code_count: i64,
+    /// Codes of the batch claimed at least once
+    pub redeemed_count: i64,
+    pub is_disabled: bool,
+    pub created_at: i64,
+    pub items: Vec<RedeemItem>,
+}
+
 /// Purchase request structures
 #[derive(Debug, Clone, Deserialize)]
 pub struct PackPurchaseRequest {
@@ -1,4 +1,4 @@
-use crate::config::CONFIG;
+use crate::service::game_constants::game_constants;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::collections::HashMap;
@@ -533,7 +533,8 @@ impl Potential {
 
     /// Calculate user's potential value
     pub fn value(&self) -> f64 {
-        self.calculate_value(CONFIG.best30_weight, CONFIG.recent10_weight)
+        let constants = game_constants();
+        self.calculate_value(constants.best30_weight, constants.recent10_weight)
     }
 
     /// Calculate user's potential value with custom weights
@@ -762,6 +763,62 @@ pub struct RankingScoreRowComplete {
     pub song_name: Option<String>,
 }
 
+/// One player on the global potential leaderboard
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PotentialRankEntry {
+    pub rank: i32,
+    pub user_id: i32,
+    pub name: String,
+    pub character: i32,
+    pub is_char_uncapped: bool,
+    pub is_char_uncapped_override: bool,
+    /// Potential times 100, as stored in `user.rating_ptt`
+    pub rating: i32,
+    /// Average rating of the best 30 scores
+    pub best30: f64,
+    /// Average rating of the best 10 recent plays
+    pub recent10: f64,
+}
+
+/// Snapshot of the global potential leaderboard
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PotentialRanking {
+    /// Milliseconds timestamp of the refresh that produced this snapshot
+    pub refreshed_at: i64,
+    pub ranks: Vec<PotentialRankEntry>,
+}
+
+/// One player on the world rank leaderboard
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorldRankEntry {
+    /// Players with the same score share a rank
+    pub rank: i32,
+    pub user_id: i32,
+    pub name: String,
+    pub character: i32,
+    pub is_char_uncapped: bool,
+    pub is_char_uncapped_override: bool,
+    pub world_rank_score: i32,
+}
+
+/// Snapshot of the world rank leaderboard
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct WorldRanking {
+    /// Milliseconds timestamp of the refresh that produced this snapshot
+    pub refreshed_at: i64,
+    pub ranks: Vec<WorldRankEntry>,
+}
+
+/// A player's place on the world rank leaderboard, with the top of it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UserWorldRank {
+    /// 0 when unranked or below `world_rank_max`
+    pub rank: i32,
+    pub world_rank_score: i32,
+    #[serde(flatten)]
+    pub ranking: WorldRanking,
+}
+
 impl RankingScoreRow {
     /// Convert to UserScore with rank
     pub fn to_user_score_with_rank(&self, rank: Option<i32>) -> UserScore {
@@ -81,6 +81,8 @@ pub struct UserLoginDto {
     pub name: String,
     pub password: String,
     pub device_id: Option<String>,
+    /// Client `User-Agent`, kept with the device fingerprint.
+    pub device_model: Option<String>,
 }
 
 /// User authentication token data
@@ -90,12 +92,21 @@ pub struct UserAuth {
     pub token: String,
 }
 
+/// Game access token minted by an admin to act as a player
+#[derive(Debug, Clone, Serialize)]
+pub struct ImpersonationToken {
+    pub user_id: i32,
+    pub token: String,
+    pub expires_at: i64,
+}
+
 /// Minimal user data for validation queries
 #[derive(Debug, Clone, FromRow)]
 pub struct UserCredentials {
     pub user_id: i32,
     pub password: Option<String>,
     pub ban_flag: Option<String>,
+    pub is_email_verified: i8,
 }
 
 /// User existence check result
@@ -116,6 +127,42 @@ pub struct UserLoginDevice {
     pub login_device: Option<String>,
 }
 
+/// Active login session of a user, without its access token
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct UserLoginSession {
+    pub login_time: Option<i64>,
+    pub login_ip: Option<String>,
+    pub login_device: Option<String>,
+}
+
+/// Device with active login sessions, grouped from the `login` table
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct UserDevice {
+    pub device_id: Option<String>,
+    pub last_login_time: Option<i64>,
+    pub last_login_ip: Option<String>,
+    pub session_count: usize,
+}
+
+/// Pending self-service account deletion
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, FromRow)]
+pub struct AccountDeletionRequest {
+    pub requested_at: i64,
+    /// The deletion can be confirmed from this time on
+    pub confirmable_at: i64,
+    /// The request lapses if it is not confirmed by this time
+    pub expires_at: i64,
+}
+
+/// Archived cloud save version, without its data columns
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct UserSaveVersion {
+    pub history_id: i64,
+    pub created_at: Option<i64>,
+    pub archived_at: i64,
+    pub data_size: i64,
+}
+
 /// User settings structure
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UserSettings {
@@ -221,6 +268,10 @@ pub struct UserInfo {
     pub cores: Vec<UserCoreInfo>,
     pub recent_score: Vec<UserRecentScore>,
     pub has_email: bool,
+
+    // Daily login bonus, absent when the bonus is disabled
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub login_bonus: Option<crate::model::LoginBonusStatus>,
 }
 
 /// User for insertion (new user registration)
@@ -356,6 +407,7 @@ impl From<User> for UserInfo {
             cores: Vec::new(),        // TODO: Load from user cores
             recent_score: Vec::new(), // TODO: Load from recent scores
             has_email: user.email.as_ref().is_some_and(|e| !e.is_empty()),
+            login_bonus: None,
         }
     }
 }
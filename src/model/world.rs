@@ -1,5 +1,4 @@
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
 
 /// Raw world-map asset shape used for JSON file deserialization.
 #[derive(Debug, Clone, Deserialize)]
@@ -128,7 +127,29 @@ pub struct WorldMap {
     pub steps: Vec<WorldStep>,
 }
 
+/// Capture of a beyond map whose data does not set `beyond_health`.
+pub const DEFAULT_BEYOND_HEALTH: i32 = 100;
+
+/// Stamina per play on a map whose data does not set `stamina_cost`.
+pub const DEFAULT_STAMINA_COST: i32 = 1;
+
 impl WorldMap {
+    /// Total capture of a beyond map.
+    pub fn beyond_health_value(&self) -> f64 {
+        self.beyond_health.unwrap_or(DEFAULT_BEYOND_HEALTH) as f64
+    }
+
+    /// Stamina spent per play.
+    pub fn stamina_cost_value(&self) -> i32 {
+        self.stamina_cost.unwrap_or(DEFAULT_STAMINA_COST)
+    }
+
+    /// Whether the map can be played at `now_ms`; an `available_from` of -1
+    /// means the map has no start time.
+    pub fn is_available_at(&self, now_ms: i64) -> bool {
+        (self.available_from < 0 || self.available_from <= now_ms) && now_ms < self.available_to
+    }
+
     /// Get rewards from all steps
     pub fn get_rewards(&self) -> Vec<StepReward> {
         let mut rewards = Vec::new();
@@ -430,41 +451,3 @@ impl Stamina {
         std::cmp::min(self.stamina + stamina_recovered, max_stamina)
     }
 }
-
-/// Map parser constants (would be loaded from files)
-#[derive(Debug, Clone)]
-pub struct MapParser {
-    pub map_id_path: HashMap<String, String>,
-    pub world_info: HashMap<String, WorldMapInfo>,
-    pub chapter_info: HashMap<i32, Vec<String>>,
-    pub chapter_info_without_repeatable: HashMap<i32, Vec<String>>,
-}
-
-impl Default for MapParser {
-    fn default() -> Self {
-        Self::new()
-    }
-}
-
-impl MapParser {
-    /// Create new map parser instance
-    pub fn new() -> Self {
-        Self {
-            map_id_path: HashMap::new(),
-            world_info: HashMap::new(),
-            chapter_info: HashMap::new(),
-            chapter_info_without_repeatable: HashMap::new(),
-        }
-    }
-
-    /// Parse world map files (placeholder - would load from JSON files)
-    pub fn parse(&mut self) {
-        // This would scan the world map folder and load all JSON files
-        // For now, this is a placeholder
-    }
-
-    /// Get world info for a specific map (placeholder)
-    pub fn get_world_info(&self, map_id: &str) -> Option<&WorldMapInfo> {
-        self.world_info.get(map_id)
-    }
-}
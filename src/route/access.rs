@@ -0,0 +1,76 @@
+//! IP / GeoIP access rules for the game API.
+//!
+//! Rejected requests are rerouted to [`access_denied`] so they never reach a
+//! game route; paths listed in `IP_RULES_EXEMPT_PREFIXES` (the admin panel,
+//! admin API and health probes by default) are left untouched.
+
+use crate::error::ArcError;
+use crate::route::common::RouteResult;
+use crate::service::access::AccessRules;
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::uri::Origin;
+use rocket::http::Method;
+use rocket::{get, Data, Request};
+use std::net::IpAddr;
+use std::sync::Arc;
+
+/// Internal path that denied requests are rewritten to.
+pub const ACCESS_DENIED_PATH: &str = "/__access_denied";
+
+/// Fairing that applies [`AccessRules`] before routing.
+pub struct IpAccessControl {
+    rules: Arc<AccessRules>,
+}
+
+impl IpAccessControl {
+    pub fn new(rules: AccessRules) -> Self {
+        Self {
+            rules: Arc::new(rules),
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl Fairing for IpAccessControl {
+    fn info(&self) -> Info {
+        Info {
+            name: "IP access rules",
+            kind: Kind::Request,
+        }
+    }
+
+    async fn on_request(&self, request: &mut Request<'_>, _: &mut Data<'_>) {
+        let path = request.uri().path().as_str();
+        if path == ACCESS_DENIED_PATH || self.rules.is_exempt(path) {
+            return;
+        }
+
+        let Some(ip) = request_ip(request) else {
+            return;
+        };
+
+        if let Err(denial) = self.rules.check(ip) {
+            log::info!("Rejected request from {ip} to `{path}`: {denial:?}");
+            request.set_method(Method::Get);
+            request.set_uri(Origin::parse(ACCESS_DENIED_PATH).expect("valid static path"));
+        }
+    }
+}
+
+/// Resolve the client address for access rules and request logs.
+///
+/// Uses Rocket's `client_ip`, so a forwarded-for header is only believed when
+/// `ip_header` names one; leave it `false` unless every request arrives
+/// through a trusted reverse proxy that overwrites that header.
+pub(crate) fn request_ip(request: &Request<'_>) -> Option<IpAddr> {
+    request.client_ip()
+}
+
+/// Response for requests rejected by [`IpAccessControl`].
+#[get("/__access_denied")]
+pub async fn access_denied() -> RouteResult<()> {
+    Err(ArcError::no_access(
+        "Access from your network or region is not allowed.",
+        -4,
+    ))
+}
@@ -0,0 +1,256 @@
+//! Score anomaly review queues: accounts flagged by the offline anomaly scan,
+//! ranked by anomaly score, and single submissions flagged by the score
+//! validator, with marking either reviewed.
+
+use rocket::serde::json::Json;
+use rocket::{get, post, State};
+
+use crate::error::ArcError;
+use crate::route::common::{success_return, RouteResult};
+use crate::service::{AnomalyService, ScoreValidator};
+use crate::{DbPool, ReadPool};
+
+use super::helpers::{
+    clamp_page, clean_query_value, format_timestamp, normalize_optional_text, page_response,
+};
+use super::models::{
+    AdminActionResponse, AdminAnomalyReviewPayload, AdminAnomalyRowView, AdminPageResponse,
+    AdminScoreAuditReviewPayload, AdminScoreAuditRowView, PageQuery,
+};
+use super::session::{require_admin_api, PowerGuard, WebAuth};
+use super::SELECT_POWER;
+
+async fn load_admin_anomalies(
+    q: Option<&str>,
+    include_reviewed: bool,
+    page: i64,
+    page_size: i64,
+    pool: &DbPool,
+) -> Result<AdminPageResponse<AdminAnomalyRowView>, ArcError> {
+    let like = clean_query_value(q).map(|query| format!("%{query}%"));
+    let total = sqlx::query_scalar!(
+        "SELECT COUNT(*) FROM user_anomaly a
+         LEFT JOIN user u ON u.user_id = a.user_id
+         WHERE (? OR a.reviewed_at IS NULL)
+           AND (? IS NULL OR u.name LIKE ? OR u.user_code LIKE ?)",
+        include_reviewed,
+        like,
+        like,
+        like
+    )
+    .fetch_one(pool)
+    .await
+    .map_err(|err| ArcError::input(format!("查询异常队列失败: {err}")))?;
+    let (page, offset) = clamp_page(page, page_size, total);
+
+    let rows = sqlx::query!(
+        "SELECT a.user_id, u.name, u.user_code, a.score, a.rating_spikes,
+                a.shared_fingerprints, a.burst_plays, a.play_count, a.linked_users,
+                a.computed_at, a.reviewed_at, a.review_note
+         FROM user_anomaly a
+         LEFT JOIN user u ON u.user_id = a.user_id
+         WHERE (? OR a.reviewed_at IS NULL)
+           AND (? IS NULL OR u.name LIKE ? OR u.user_code LIKE ?)
+         ORDER BY a.reviewed_at IS NOT NULL, a.score DESC, a.user_id
+         LIMIT ? OFFSET ?",
+        include_reviewed,
+        like,
+        like,
+        like,
+        page_size,
+        offset
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|err| ArcError::input(format!("查询异常队列失败: {err}")))?
+    .into_iter()
+    .map(|row| AdminAnomalyRowView {
+        user_id: row.user_id,
+        name: row.name.unwrap_or_default(),
+        user_code: row.user_code.unwrap_or_default(),
+        score: row.score,
+        rating_spikes: row.rating_spikes,
+        shared_fingerprints: row.shared_fingerprints,
+        burst_plays: row.burst_plays,
+        play_count: row.play_count,
+        linked_users: row
+            .linked_users
+            .split(',')
+            .filter_map(|id| id.trim().parse().ok())
+            .collect(),
+        computed_at: format_timestamp(Some(row.computed_at)),
+        reviewed_at: row.reviewed_at.map(|ts| format_timestamp(Some(ts))),
+        review_note: row.review_note,
+    })
+    .collect();
+
+    Ok(page_response(rows, total, page, page_size))
+}
+
+#[get("/api/anomalies?<q>&<include_reviewed>&<paging..>")]
+pub(super) async fn admin_api_anomalies(
+    q: Option<&str>,
+    include_reviewed: Option<bool>,
+    paging: PageQuery,
+    pool: &State<ReadPool>,
+    guard: PowerGuard,
+) -> RouteResult<AdminPageResponse<AdminAnomalyRowView>> {
+    guard.require(SELECT_POWER)?;
+    let (page, page_size) = paging.normalized();
+    Ok(success_return(
+        load_admin_anomalies(
+            q,
+            include_reviewed.unwrap_or(false),
+            page,
+            page_size,
+            pool.inner(),
+        )
+        .await?,
+    ))
+}
+
+#[post(
+    "/api/admin-actions/anomaly-review",
+    format = "json",
+    data = "<payload>"
+)]
+pub(super) async fn admin_api_anomaly_review(
+    payload: Json<AdminAnomalyReviewPayload>,
+    pool: &State<DbPool>,
+    anomaly_service: &State<AnomalyService>,
+    auth: WebAuth<'_>,
+) -> RouteResult<AdminActionResponse> {
+    require_admin_api(auth, pool.inner()).await?;
+    let note = normalize_optional_text(payload.note.as_deref(), 255);
+    let affected_rows = anomaly_service
+        .mark_reviewed(payload.user_id, &note)
+        .await?;
+    if affected_rows == 0 {
+        return Err(ArcError::no_data("异常记录不存在", -2));
+    }
+    Ok(success_return(AdminActionResponse {
+        message: format!("已标记玩家 {} 为已审核", payload.user_id),
+        affected_rows,
+    }))
+}
+
+async fn load_admin_score_audits(
+    q: Option<&str>,
+    include_reviewed: bool,
+    page: i64,
+    page_size: i64,
+    pool: &DbPool,
+) -> Result<AdminPageResponse<AdminScoreAuditRowView>, ArcError> {
+    let like = clean_query_value(q).map(|query| format!("%{query}%"));
+    let total = sqlx::query_scalar!(
+        "SELECT COUNT(*) FROM score_audit a
+         LEFT JOIN user u ON u.user_id = a.user_id
+         WHERE (? OR a.reviewed_at IS NULL)
+           AND (? IS NULL OR u.name LIKE ? OR u.user_code LIKE ? OR a.song_id LIKE ?)",
+        include_reviewed,
+        like,
+        like,
+        like,
+        like
+    )
+    .fetch_one(pool)
+    .await
+    .map_err(|err| ArcError::input(format!("查询成绩审计失败: {err}")))?;
+    let (page, offset) = clamp_page(page, page_size, total);
+
+    let rows = sqlx::query!(
+        "SELECT a.audit_id, a.user_id, u.name, a.song_id, a.difficulty, a.score,
+                a.shiny_perfect_count, a.perfect_count, a.near_count, a.miss_count,
+                a.clear_type, a.flags, a.rejected, a.created_at, a.reviewed_at, a.review_note
+         FROM score_audit a
+         LEFT JOIN user u ON u.user_id = a.user_id
+         WHERE (? OR a.reviewed_at IS NULL)
+           AND (? IS NULL OR u.name LIKE ? OR u.user_code LIKE ? OR a.song_id LIKE ?)
+         ORDER BY a.reviewed_at IS NOT NULL, a.created_at DESC
+         LIMIT ? OFFSET ?",
+        include_reviewed,
+        like,
+        like,
+        like,
+        like,
+        page_size,
+        offset
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|err| ArcError::input(format!("查询成绩审计失败: {err}")))?
+    .into_iter()
+    .map(|row| AdminScoreAuditRowView {
+        audit_id: row.audit_id,
+        user_id: row.user_id,
+        name: row.name.unwrap_or_default(),
+        song_id: row.song_id,
+        difficulty: row.difficulty,
+        score: row.score,
+        shiny_perfect_count: row.shiny_perfect_count,
+        perfect_count: row.perfect_count,
+        near_count: row.near_count,
+        miss_count: row.miss_count,
+        clear_type: row.clear_type,
+        flags: row
+            .flags
+            .split(',')
+            .filter(|flag| !flag.is_empty())
+            .map(str::to_string)
+            .collect(),
+        rejected: row.rejected != 0,
+        created_at: format_timestamp(Some(row.created_at)),
+        reviewed_at: row.reviewed_at.map(|ts| format_timestamp(Some(ts))),
+        review_note: row.review_note,
+    })
+    .collect();
+
+    Ok(page_response(rows, total, page, page_size))
+}
+
+#[get("/api/score-audits?<q>&<include_reviewed>&<paging..>")]
+pub(super) async fn admin_api_score_audits(
+    q: Option<&str>,
+    include_reviewed: Option<bool>,
+    paging: PageQuery,
+    pool: &State<ReadPool>,
+    guard: PowerGuard,
+) -> RouteResult<AdminPageResponse<AdminScoreAuditRowView>> {
+    guard.require(SELECT_POWER)?;
+    let (page, page_size) = paging.normalized();
+    Ok(success_return(
+        load_admin_score_audits(
+            q,
+            include_reviewed.unwrap_or(false),
+            page,
+            page_size,
+            pool.inner(),
+        )
+        .await?,
+    ))
+}
+
+#[post(
+    "/api/admin-actions/score-audit-review",
+    format = "json",
+    data = "<payload>"
+)]
+pub(super) async fn admin_api_score_audit_review(
+    payload: Json<AdminScoreAuditReviewPayload>,
+    pool: &State<DbPool>,
+    score_validator: &State<ScoreValidator>,
+    auth: WebAuth<'_>,
+) -> RouteResult<AdminActionResponse> {
+    require_admin_api(auth, pool.inner()).await?;
+    let note = normalize_optional_text(payload.note.as_deref(), 255);
+    let affected_rows = score_validator
+        .mark_reviewed(payload.audit_id, &note)
+        .await?;
+    if affected_rows == 0 {
+        return Err(ArcError::no_data("审计记录不存在", -2));
+    }
+    Ok(success_return(AdminActionResponse {
+        message: format!("已标记成绩审计 {} 为已审核", payload.audit_id),
+        affected_rows,
+    }))
+}
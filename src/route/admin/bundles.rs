@@ -0,0 +1,66 @@
+//! Content bundles: the bundles the server currently serves, and uploads of
+//! new ones without shell access to the bundle folder.
+
+use rocket::form::Form;
+use rocket::{get, post, State};
+
+use crate::route::common::{success_return, RouteResult};
+use crate::service::bundle::ContentBundle;
+use crate::service::BundleService;
+use crate::DbPool;
+
+use super::helpers::read_text_file;
+use super::models::{AdminBundleUploadForm, AdminBundleView};
+use super::session::{require_admin_api, PowerGuard, WebAuth};
+use super::SELECT_POWER;
+
+fn bundle_view(bundle: ContentBundle) -> AdminBundleView {
+    AdminBundleView {
+        prev_version: bundle.prev_version.filter(|prev| prev != "0.0.0"),
+        app_version: bundle.app_version,
+        version: bundle.version,
+        uuid: bundle.uuid,
+        json_size: bundle.json_size,
+        bundle_size: bundle.bundle_size,
+        bundle_path: bundle.bundle_path,
+        added_files: bundle.patch.added.len(),
+        removed_files: bundle.patch.removed.len(),
+    }
+}
+
+#[get("/api/bundles")]
+pub(super) async fn admin_api_bundles(
+    bundle_service: &State<BundleService>,
+    guard: PowerGuard,
+) -> RouteResult<Vec<AdminBundleView>> {
+    guard.require(SELECT_POWER)?;
+    let bundles = bundle_service.list_bundles().await;
+    Ok(success_return(
+        bundles.into_iter().map(bundle_view).collect(),
+    ))
+}
+
+/// Multipart upload of a bundle (`bundle`) with its JSON (`metadata`); the
+/// bundle is served as soon as the response is sent.
+#[post("/api/bundles/upload", data = "<upload>")]
+pub(super) async fn admin_api_bundle_upload(
+    upload: Form<AdminBundleUploadForm<'_>>,
+    bundle_service: &State<BundleService>,
+    pool: &State<DbPool>,
+    auth: WebAuth<'_>,
+) -> RouteResult<AdminBundleView> {
+    require_admin_api(auth, pool.inner()).await?;
+
+    let AdminBundleUploadForm {
+        metadata,
+        mut bundle,
+    } = upload.into_inner();
+    let metadata = read_text_file(&metadata).await?;
+    let bundle = bundle_service.add_bundle(&metadata, &mut bundle).await?;
+    log::info!(
+        "Bundle {} uploaded for app version {}",
+        bundle.version,
+        bundle.app_version
+    );
+    Ok(success_return(bundle_view(bundle)))
+}
@@ -1,27 +1,40 @@
 //! Catalog data tables: songs (charts), items, purchases and purchase-items.
-//! Covers list loading with pagination plus create/update/delete for each.
+//! Covers list loading with pagination plus create/update/delete for each,
+//! accepting estimated constants for unrated charts, song package uploads,
+//! and the songlist / chart table mismatch report.
 
 use chrono::{Local, NaiveDateTime, TimeZone};
-use rocket::http::CookieJar;
+use rocket::form::Form;
 use rocket::serde::json::Json;
 use rocket::{delete, get, patch, post, State};
 use std::collections::HashMap;
+use std::sync::Arc;
 
+use crate::error::ArcError;
 use crate::route::common::{success_return, success_return_no_value, EmptyResponse, RouteResult};
+use crate::service::asset_manager::{ChartMismatchReport, InstalledSong};
+use crate::service::song_package::SongPackage;
+use crate::service::{AssetManager, UserCache};
 use crate::utils::sql_placeholders;
 use crate::DbPool;
 
 use super::helpers::{
-    admin_api_input_error, clamp_page, like_filter, normalize_page, page_response,
+    admin_api_input_error, clamp_page, clean_optional_payload_text, format_timestamp, like_filter,
+    page_response, read_upload,
 };
 use super::models::{
     AdminItemDeletePayload, AdminItemPayload, AdminPageResponse, AdminPurchaseDeletePayload,
     AdminPurchaseItemDeletePayload, AdminPurchaseItemPayload, AdminPurchasePayload,
-    AdminSongDeletePayload, AdminSongInput, AdminSongPayload, ChartConstantsPayload, ChartDbRow,
-    ItemDbRow, ItemRowView, PurchaseDbRow, PurchaseItemDbRow, PurchaseItemRowView, PurchaseRowView,
+    AdminSongDeletePayload, AdminSongFileView, AdminSongInput, AdminSongPayload,
+    AdminSongUploadForm, AdminSongUploadView, ChartConstantProposalPayload,
+    ChartConstantProposalView, ChartConstantsPayload, ChartDbRow, ChartMismatchView, ItemDbRow,
+    ItemRowView, PageQuery, PurchaseDbRow, PurchaseItemDbRow, PurchaseItemRowView, PurchaseRowView,
     SongRowView,
 };
-use super::session::{require_admin_api, require_chart_constant_edit_api, require_web_session};
+use super::session::{
+    require_admin_api, require_chart_constant_edit_api, require_web_session, PowerGuard, WebAuth,
+};
+use super::SELECT_POWER;
 
 // Parsing helpers local to the catalog
 
@@ -173,6 +186,7 @@ fn purchase_item_db_row_to_view(row: PurchaseItemDbRow) -> PurchaseItemRowView {
 
 async fn load_admin_songs(
     q: Option<&str>,
+    sort: Option<&str>,
     page: i64,
     page_size: i64,
     pool: &DbPool,
@@ -187,10 +201,14 @@ async fn load_admin_songs(
     let total = count_query.fetch_one(pool).await.unwrap_or(0);
     let (page, offset) = clamp_page(page, page_size, total);
 
+    let order_sql = match sort {
+        Some("name") => "name ASC, song_id ASC",
+        _ => "song_id ASC",
+    };
     let row_sql = format!(
         "SELECT *
          FROM chart{where_sql}
-         ORDER BY song_id ASC
+         ORDER BY {order_sql}
          LIMIT ? OFFSET ?"
     );
     let mut rows_query = sqlx::query_as::<_, ChartDbRow>(&row_sql);
@@ -212,6 +230,7 @@ async fn load_admin_songs(
 
 async fn load_admin_items(
     q: Option<&str>,
+    sort: Option<&str>,
     page: i64,
     page_size: i64,
     pool: &DbPool,
@@ -226,10 +245,14 @@ async fn load_admin_items(
     let total = count_query.fetch_one(pool).await.unwrap_or(0);
     let (page, offset) = clamp_page(page, page_size, total);
 
+    let order_sql = match sort {
+        Some("item_id") => "item_id, type",
+        _ => "type, item_id",
+    };
     let row_sql = format!(
         "SELECT *
          FROM item{where_sql}
-         ORDER BY type, item_id
+         ORDER BY {order_sql}
          LIMIT ? OFFSET ?"
     );
     let mut rows_query = sqlx::query_as::<_, ItemDbRow>(&row_sql);
@@ -444,6 +467,7 @@ async fn update_song(pool: &DbPool, input: AdminSongInput<'_>) -> Result<(), Str
         return Err("歌曲不存在".to_string());
     }
 
+    UserCache::global().invalidate_chart(&sid);
     Ok(())
 }
 
@@ -485,6 +509,138 @@ async fn update_chart_constants(
     .await
     .map_err(|err| format!("更新失败: {err}"))?;
 
+    UserCache::global().invalidate_chart(&sid);
+    Ok(())
+}
+
+/// Proposals from the `estimate_chart_constants` operation whose chart is
+/// still unrated.
+async fn load_chart_constant_proposals(
+    pool: &DbPool,
+) -> Result<Vec<ChartConstantProposalView>, String> {
+    let rows = sqlx::query!(
+        "SELECT p.song_id, p.difficulty, c.name, p.proposed_rating, p.sample_count, p.model,
+                p.created_at
+         FROM chart_constant_proposal p
+         JOIN chart c ON c.song_id = p.song_id
+         WHERE COALESCE(CASE p.difficulty
+                 WHEN 0 THEN c.rating_pst
+                 WHEN 1 THEN c.rating_prs
+                 WHEN 2 THEN c.rating_ftr
+                 WHEN 3 THEN c.rating_byn
+                 WHEN 4 THEN c.rating_etr
+             END, -1) <= 0
+         ORDER BY p.sample_count DESC, p.song_id, p.difficulty"
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|err| format!("查询失败: {err}"))?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| ChartConstantProposalView {
+            song_id: row.song_id,
+            name_en: row.name.unwrap_or_default(),
+            difficulty: row.difficulty,
+            proposed_rating: format_rating_input_tenths(Some(row.proposed_rating)),
+            sample_count: row.sample_count,
+            model: row.model,
+            created_at: format_timestamp(Some(row.created_at)),
+        })
+        .collect())
+}
+
+/// Write a proposed constant into `chart` and drop the proposal.
+async fn accept_chart_constant_proposal(
+    pool: &DbPool,
+    payload: &ChartConstantProposalPayload,
+) -> Result<(), String> {
+    let sid = normalize_chart_text(&payload.sid, "song_id")?;
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|err| format!("更新失败: {err}"))?;
+    let proposed_rating = sqlx::query_scalar!(
+        "SELECT proposed_rating FROM chart_constant_proposal
+         WHERE song_id = ? AND difficulty = ?",
+        sid,
+        payload.difficulty
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|err| format!("查询失败: {err}"))?
+    .ok_or_else(|| "定数建议不存在".to_string())?;
+
+    let update = match payload.difficulty {
+        0 => sqlx::query!(
+            "UPDATE chart SET rating_pst = ? WHERE song_id = ?",
+            proposed_rating,
+            sid
+        ),
+        1 => sqlx::query!(
+            "UPDATE chart SET rating_prs = ? WHERE song_id = ?",
+            proposed_rating,
+            sid
+        ),
+        2 => sqlx::query!(
+            "UPDATE chart SET rating_ftr = ? WHERE song_id = ?",
+            proposed_rating,
+            sid
+        ),
+        3 => sqlx::query!(
+            "UPDATE chart SET rating_byn = ? WHERE song_id = ?",
+            proposed_rating,
+            sid
+        ),
+        4 => sqlx::query!(
+            "UPDATE chart SET rating_etr = ? WHERE song_id = ?",
+            proposed_rating,
+            sid
+        ),
+        _ => return Err("difficulty 非法".to_string()),
+    };
+    let done = update
+        .execute(&mut *tx)
+        .await
+        .map_err(|err| format!("更新失败: {err}"))?;
+    if done.rows_affected() == 0 {
+        return Err("歌曲不存在".to_string());
+    }
+
+    sqlx::query!(
+        "DELETE FROM chart_constant_proposal WHERE song_id = ? AND difficulty = ?",
+        sid,
+        payload.difficulty
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|err| format!("更新失败: {err}"))?;
+    tx.commit()
+        .await
+        .map_err(|err| format!("更新失败: {err}"))?;
+
+    UserCache::global().invalidate_chart(&sid);
+    Ok(())
+}
+
+async fn dismiss_chart_constant_proposal(
+    pool: &DbPool,
+    payload: &ChartConstantProposalPayload,
+) -> Result<(), String> {
+    let sid = normalize_chart_text(&payload.sid, "song_id")?;
+    let done = sqlx::query!(
+        "DELETE FROM chart_constant_proposal WHERE song_id = ? AND difficulty = ?",
+        sid,
+        payload.difficulty
+    )
+    .execute(pool)
+    .await
+    .map_err(|err| format!("删除失败: {err}"))?;
+
+    if done.rows_affected() == 0 {
+        return Err("定数建议不存在".to_string());
+    }
+
     Ok(())
 }
 
@@ -499,6 +655,7 @@ async fn delete_song(pool: &DbPool, sid_raw: &str) -> Result<(), String> {
         return Err("歌曲不存在".to_string());
     }
 
+    UserCache::global().invalidate_chart(&sid);
     Ok(())
 }
 
@@ -858,61 +1015,57 @@ async fn delete_purchase_item(
 
 // Route handlers
 
-#[get("/api/songs?<q>&<page>&<page_size>")]
+#[get("/api/songs?<q>&<paging..>")]
 pub(super) async fn admin_api_songs(
     q: Option<&str>,
-    page: Option<i64>,
-    page_size: Option<i64>,
+    paging: PageQuery,
     pool: &State<DbPool>,
-    cookies: &CookieJar<'_>,
+    auth: WebAuth<'_>,
 ) -> RouteResult<AdminPageResponse<SongRowView>> {
-    require_chart_constant_edit_api(cookies, pool.inner()).await?;
-    let (page, page_size) = normalize_page(page, page_size);
+    require_chart_constant_edit_api(auth, pool.inner()).await?;
+    let (page, page_size) = paging.normalized();
     Ok(success_return(
-        load_admin_songs(q, page, page_size, pool.inner()).await,
+        load_admin_songs(q, paging.sort(), page, page_size, pool.inner()).await,
     ))
 }
 
-#[get("/api/items?<q>&<page>&<page_size>")]
+#[get("/api/items?<q>&<paging..>")]
 pub(super) async fn admin_api_items(
     q: Option<&str>,
-    page: Option<i64>,
-    page_size: Option<i64>,
+    paging: PageQuery,
     pool: &State<DbPool>,
-    cookies: &CookieJar<'_>,
+    auth: WebAuth<'_>,
 ) -> RouteResult<AdminPageResponse<ItemRowView>> {
-    require_web_session(cookies, pool.inner()).await?;
-    let (page, page_size) = normalize_page(page, page_size);
+    require_web_session(auth, pool.inner()).await?;
+    let (page, page_size) = paging.normalized();
     Ok(success_return(
-        load_admin_items(q, page, page_size, pool.inner()).await,
+        load_admin_items(q, paging.sort(), page, page_size, pool.inner()).await,
     ))
 }
 
-#[get("/api/purchases?<pq>&<page>&<page_size>")]
+#[get("/api/purchases?<pq>&<paging..>")]
 pub(super) async fn admin_api_purchases(
     pq: Option<&str>,
-    page: Option<i64>,
-    page_size: Option<i64>,
+    paging: PageQuery,
     pool: &State<DbPool>,
-    cookies: &CookieJar<'_>,
+    auth: WebAuth<'_>,
 ) -> RouteResult<AdminPageResponse<PurchaseRowView>> {
-    require_web_session(cookies, pool.inner()).await?;
-    let (page, page_size) = normalize_page(page, page_size);
+    require_web_session(auth, pool.inner()).await?;
+    let (page, page_size) = paging.normalized();
     Ok(success_return(
         load_admin_purchases(pq, page, page_size, pool.inner()).await,
     ))
 }
 
-#[get("/api/purchase-items?<iq>&<page>&<page_size>")]
+#[get("/api/purchase-items?<iq>&<paging..>")]
 pub(super) async fn admin_api_purchase_items(
     iq: Option<&str>,
-    page: Option<i64>,
-    page_size: Option<i64>,
+    paging: PageQuery,
     pool: &State<DbPool>,
-    cookies: &CookieJar<'_>,
+    guard: PowerGuard,
 ) -> RouteResult<AdminPageResponse<PurchaseItemRowView>> {
-    require_admin_api(cookies, pool.inner()).await?;
-    let (page, page_size) = normalize_page(page, page_size);
+    guard.require(SELECT_POWER)?;
+    let (page, page_size) = paging.normalized();
     Ok(success_return(
         load_admin_purchase_items(iq, page, page_size, pool.inner()).await,
     ))
@@ -922,9 +1075,9 @@ pub(super) async fn admin_api_purchase_items(
 pub(super) async fn admin_api_song_create(
     payload: Json<AdminSongPayload>,
     pool: &State<DbPool>,
-    cookies: &CookieJar<'_>,
+    auth: WebAuth<'_>,
 ) -> RouteResult<EmptyResponse> {
-    require_admin_api(cookies, pool.inner()).await?;
+    require_admin_api(auth, pool.inner()).await?;
     create_song(pool.inner(), AdminSongInput::from(&*payload))
         .await
         .map_err(admin_api_input_error)?;
@@ -936,9 +1089,9 @@ pub(super) async fn admin_api_song_update(
     sid: &str,
     payload: Json<AdminSongPayload>,
     pool: &State<DbPool>,
-    cookies: &CookieJar<'_>,
+    auth: WebAuth<'_>,
 ) -> RouteResult<EmptyResponse> {
-    require_admin_api(cookies, pool.inner()).await?;
+    require_admin_api(auth, pool.inner()).await?;
     update_song(pool.inner(), AdminSongInput::from(&*payload).with_sid(sid))
         .await
         .map_err(admin_api_input_error)?;
@@ -950,22 +1103,143 @@ pub(super) async fn admin_api_chart_constants_update(
     sid: &str,
     payload: Json<ChartConstantsPayload>,
     pool: &State<DbPool>,
-    cookies: &CookieJar<'_>,
+    auth: WebAuth<'_>,
 ) -> RouteResult<EmptyResponse> {
-    require_chart_constant_edit_api(cookies, pool.inner()).await?;
+    require_chart_constant_edit_api(auth, pool.inner()).await?;
     update_chart_constants(pool.inner(), sid, &payload)
         .await
         .map_err(admin_api_input_error)?;
     Ok(success_return_no_value())
 }
 
+#[get("/api/chart-mismatches")]
+pub(super) async fn admin_api_chart_mismatches(
+    asset_manager: &State<Arc<AssetManager>>,
+    guard: PowerGuard,
+) -> RouteResult<ChartMismatchView> {
+    guard.require(SELECT_POWER)?;
+    Ok(success_return(chart_mismatch_view(
+        asset_manager.chart_report(),
+    )))
+}
+
+/// Re-read the songlist and sync chart constants without restarting
+#[post("/api/songlist/reload")]
+pub(super) async fn admin_api_songlist_reload(
+    asset_manager: &State<Arc<AssetManager>>,
+    pool: &State<DbPool>,
+    auth: WebAuth<'_>,
+) -> RouteResult<ChartMismatchView> {
+    require_admin_api(auth, pool.inner()).await?;
+    let report = asset_manager
+        .reload_songlist()
+        .await
+        .map_err(|err| admin_api_input_error(format!("重新加载 songlist 失败: {err}")))?;
+    Ok(success_return(chart_mismatch_view(report)))
+}
+
+/// Multipart upload of a zipped song folder (`package`); its files are
+/// hashed and served as soon as the response is sent.
+#[post("/api/songs/upload", data = "<upload>")]
+pub(super) async fn admin_api_song_upload(
+    upload: Form<AdminSongUploadForm<'_>>,
+    asset_manager: &State<Arc<AssetManager>>,
+    pool: &State<DbPool>,
+    auth: WebAuth<'_>,
+) -> RouteResult<AdminSongUploadView> {
+    require_admin_api(auth, pool.inner()).await?;
+
+    let package = SongPackage::from_zip(&read_upload(&upload.package).await?)?;
+    let song_id = clean_optional_payload_text(&upload.song_id)
+        .map(str::to_string)
+        .or_else(|| package.folder.clone())
+        .ok_or_else(|| ArcError::input("song_id is required when the zip has no song folder"))?;
+    let chart_name = upload
+        .insert_chart
+        .then(|| clean_optional_payload_text(&upload.name).unwrap_or(&song_id));
+    let installed = asset_manager
+        .install_song_package(&song_id, package, upload.overwrite, chart_name)
+        .await?;
+    Ok(success_return(song_upload_view(installed)))
+}
+
+fn song_upload_view(installed: InstalledSong) -> AdminSongUploadView {
+    AdminSongUploadView {
+        song_id: installed.song_id,
+        files: installed
+            .files
+            .into_iter()
+            .map(|file| AdminSongFileView {
+                file_name: file.file_name,
+                size: file.size,
+                md5: file.md5,
+            })
+            .collect(),
+        chart_created: installed.chart_created,
+    }
+}
+
+fn chart_mismatch_view(report: ChartMismatchReport) -> ChartMismatchView {
+    ChartMismatchView {
+        has_songlist: report.has_songlist,
+        missing_charts: report.missing_charts,
+        unlisted_charts: report.unlisted_charts,
+        placeholders_created: report.placeholders_created,
+        charts_updated: report.charts_updated,
+        checked_at: (report.checked_at > 0).then(|| format_timestamp(Some(report.checked_at))),
+    }
+}
+
+#[get("/api/chart-constant-proposals")]
+pub(super) async fn admin_api_chart_constant_proposals(
+    pool: &State<DbPool>,
+    auth: WebAuth<'_>,
+) -> RouteResult<Vec<ChartConstantProposalView>> {
+    require_chart_constant_edit_api(auth, pool.inner()).await?;
+    Ok(success_return(
+        load_chart_constant_proposals(pool.inner())
+            .await
+            .map_err(admin_api_input_error)?,
+    ))
+}
+
+#[post(
+    "/api/chart-constant-proposals/accept",
+    format = "json",
+    data = "<payload>"
+)]
+pub(super) async fn admin_api_chart_constant_proposal_accept(
+    payload: Json<ChartConstantProposalPayload>,
+    pool: &State<DbPool>,
+    auth: WebAuth<'_>,
+) -> RouteResult<EmptyResponse> {
+    require_chart_constant_edit_api(auth, pool.inner()).await?;
+    accept_chart_constant_proposal(pool.inner(), &payload)
+        .await
+        .map_err(admin_api_input_error)?;
+    Ok(success_return_no_value())
+}
+
+#[delete("/api/chart-constant-proposals", format = "json", data = "<payload>")]
+pub(super) async fn admin_api_chart_constant_proposal_dismiss(
+    payload: Json<ChartConstantProposalPayload>,
+    pool: &State<DbPool>,
+    auth: WebAuth<'_>,
+) -> RouteResult<EmptyResponse> {
+    require_chart_constant_edit_api(auth, pool.inner()).await?;
+    dismiss_chart_constant_proposal(pool.inner(), &payload)
+        .await
+        .map_err(admin_api_input_error)?;
+    Ok(success_return_no_value())
+}
+
 #[delete("/api/songs", format = "json", data = "<payload>")]
 pub(super) async fn admin_api_song_delete(
     payload: Json<AdminSongDeletePayload>,
     pool: &State<DbPool>,
-    cookies: &CookieJar<'_>,
+    auth: WebAuth<'_>,
 ) -> RouteResult<EmptyResponse> {
-    require_admin_api(cookies, pool.inner()).await?;
+    require_admin_api(auth, pool.inner()).await?;
     delete_song(pool.inner(), &payload.sid)
         .await
         .map_err(admin_api_input_error)?;
@@ -976,9 +1250,9 @@ pub(super) async fn admin_api_song_delete(
 pub(super) async fn admin_api_item_create(
     payload: Json<AdminItemPayload>,
     pool: &State<DbPool>,
-    cookies: &CookieJar<'_>,
+    auth: WebAuth<'_>,
 ) -> RouteResult<EmptyResponse> {
-    require_admin_api(cookies, pool.inner()).await?;
+    require_admin_api(auth, pool.inner()).await?;
     create_item(
         pool.inner(),
         &payload.item_id,
@@ -994,9 +1268,9 @@ pub(super) async fn admin_api_item_create(
 pub(super) async fn admin_api_item_update(
     payload: Json<AdminItemPayload>,
     pool: &State<DbPool>,
-    cookies: &CookieJar<'_>,
+    auth: WebAuth<'_>,
 ) -> RouteResult<EmptyResponse> {
-    require_admin_api(cookies, pool.inner()).await?;
+    require_admin_api(auth, pool.inner()).await?;
     update_item(
         pool.inner(),
         &payload.item_id,
@@ -1012,9 +1286,9 @@ pub(super) async fn admin_api_item_update(
 pub(super) async fn admin_api_item_delete(
     payload: Json<AdminItemDeletePayload>,
     pool: &State<DbPool>,
-    cookies: &CookieJar<'_>,
+    auth: WebAuth<'_>,
 ) -> RouteResult<EmptyResponse> {
-    require_admin_api(cookies, pool.inner()).await?;
+    require_admin_api(auth, pool.inner()).await?;
     delete_item(pool.inner(), &payload.item_id, &payload.item_type)
         .await
         .map_err(admin_api_input_error)?;
@@ -1025,9 +1299,9 @@ pub(super) async fn admin_api_item_delete(
 pub(super) async fn admin_api_purchase_create(
     payload: Json<AdminPurchasePayload>,
     pool: &State<DbPool>,
-    cookies: &CookieJar<'_>,
+    auth: WebAuth<'_>,
 ) -> RouteResult<EmptyResponse> {
-    require_admin_api(cookies, pool.inner()).await?;
+    require_admin_api(auth, pool.inner()).await?;
     create_purchase(
         pool.inner(),
         &payload.purchase_name,
@@ -1047,9 +1321,9 @@ pub(super) async fn admin_api_purchase_update(
     purchase_name: &str,
     payload: Json<AdminPurchasePayload>,
     pool: &State<DbPool>,
-    cookies: &CookieJar<'_>,
+    auth: WebAuth<'_>,
 ) -> RouteResult<EmptyResponse> {
-    require_admin_api(cookies, pool.inner()).await?;
+    require_admin_api(auth, pool.inner()).await?;
     update_purchase(
         pool.inner(),
         purchase_name,
@@ -1068,9 +1342,9 @@ pub(super) async fn admin_api_purchase_update(
 pub(super) async fn admin_api_purchase_delete(
     payload: Json<AdminPurchaseDeletePayload>,
     pool: &State<DbPool>,
-    cookies: &CookieJar<'_>,
+    auth: WebAuth<'_>,
 ) -> RouteResult<EmptyResponse> {
-    require_admin_api(cookies, pool.inner()).await?;
+    require_admin_api(auth, pool.inner()).await?;
     delete_purchase(pool.inner(), &payload.purchase_name)
         .await
         .map_err(admin_api_input_error)?;
@@ -1081,9 +1355,9 @@ pub(super) async fn admin_api_purchase_delete(
 pub(super) async fn admin_api_purchase_item_create(
     payload: Json<AdminPurchaseItemPayload>,
     pool: &State<DbPool>,
-    cookies: &CookieJar<'_>,
+    auth: WebAuth<'_>,
 ) -> RouteResult<EmptyResponse> {
-    require_admin_api(cookies, pool.inner()).await?;
+    require_admin_api(auth, pool.inner()).await?;
     create_purchase_item(
         pool.inner(),
         &payload.purchase_name,
@@ -1100,9 +1374,9 @@ pub(super) async fn admin_api_purchase_item_create(
 pub(super) async fn admin_api_purchase_item_update(
     payload: Json<AdminPurchaseItemPayload>,
     pool: &State<DbPool>,
-    cookies: &CookieJar<'_>,
+    auth: WebAuth<'_>,
 ) -> RouteResult<EmptyResponse> {
-    require_admin_api(cookies, pool.inner()).await?;
+    require_admin_api(auth, pool.inner()).await?;
     update_purchase_item(
         pool.inner(),
         &payload.purchase_name,
@@ -1119,9 +1393,9 @@ pub(super) async fn admin_api_purchase_item_update(
 pub(super) async fn admin_api_purchase_item_delete(
     payload: Json<AdminPurchaseItemDeletePayload>,
     pool: &State<DbPool>,
-    cookies: &CookieJar<'_>,
+    auth: WebAuth<'_>,
 ) -> RouteResult<EmptyResponse> {
-    require_admin_api(cookies, pool.inner()).await?;
+    require_admin_api(auth, pool.inner()).await?;
     delete_purchase_item(
         pool.inner(),
         &payload.purchase_name,
@@ -1,22 +1,31 @@
-//! Dashboard overview metrics, daily check-in and admin maintenance operations.
+//! Dashboard overview metrics, daily check-in, admin maintenance operations and
+//! the game constant editor.
 
 use chrono::{Local, NaiveDate};
 use rand::Rng;
-use rocket::http::CookieJar;
+use rocket::serde::json::Json;
 use rocket::{get, post, State};
+use std::sync::Arc;
 
 use crate::error::ArcError;
-use crate::route::common::{success_return, success_return_no_value, EmptyResponse, RouteResult};
-use crate::service::OperationManager;
+use crate::route::common::{success_return, RouteResult};
+use crate::service::operations::{OperationJob, OperationParams};
+use crate::service::{AssetManager, GameConstantEntry, GameConstantsService, OperationManager};
 use crate::DbPool;
 
 use super::helpers::format_timestamp;
 use super::models::{
-    AdminDashboardApiResponse, RecentLoginRow, RecentOpView, UserCheckinResponse, WebSession,
+    AdminActionResponse, AdminDashboardApiResponse, AdminGameConstantPayload,
+    AdminOperationJobView, RecentLoginRow, RecentOpView, SongFileRefreshView, UserCheckinResponse,
+    WebSession,
 };
-use super::session::{require_admin_api, require_web_session};
+use super::session::{require_admin_api, require_web_session, PowerGuard, WebAuth};
+use super::SELECT_POWER;
 
-async fn load_dashboard_api(pool: &DbPool) -> AdminDashboardApiResponse {
+async fn load_dashboard_api(
+    pool: &DbPool,
+    asset_manager: &AssetManager,
+) -> AdminDashboardApiResponse {
     let now_ms = Local::now().timestamp_millis();
     let one_day_ms = 86_400_000i64;
 
@@ -98,6 +107,14 @@ async fn load_dashboard_api(pool: &DbPool) -> AdminDashboardApiResponse {
         present_count,
         alert_count,
         recent_ops,
+        song_file_refresh: asset_manager
+            .last_file_refresh()
+            .map(|refresh| SongFileRefreshView {
+                refreshed_at: format_timestamp(Some(refresh.refreshed_at)),
+                hashed: refresh.stats.hashed,
+                unchanged: refresh.stats.unchanged,
+                removed: refresh.stats.removed,
+            }),
     }
 }
 
@@ -225,18 +242,21 @@ async fn claim_user_checkin(
 #[get("/api/dashboard")]
 pub(super) async fn admin_api_dashboard(
     pool: &State<DbPool>,
-    cookies: &CookieJar<'_>,
+    asset_manager: &State<Arc<AssetManager>>,
+    guard: PowerGuard,
 ) -> RouteResult<AdminDashboardApiResponse> {
-    require_admin_api(cookies, pool.inner()).await?;
-    Ok(success_return(load_dashboard_api(pool.inner()).await))
+    guard.require(SELECT_POWER)?;
+    Ok(success_return(
+        load_dashboard_api(pool.inner(), asset_manager.inner()).await,
+    ))
 }
 
 #[get("/api/checkin")]
 pub(super) async fn admin_api_checkin_status(
     pool: &State<DbPool>,
-    cookies: &CookieJar<'_>,
+    auth: WebAuth<'_>,
 ) -> RouteResult<UserCheckinResponse> {
-    let session = require_web_session(cookies, pool.inner()).await?;
+    let session = require_web_session(auth, pool.inner()).await?;
     Ok(success_return(
         load_user_checkin_status(&session, pool.inner()).await?,
     ))
@@ -245,30 +265,142 @@ pub(super) async fn admin_api_checkin_status(
 #[post("/api/checkin")]
 pub(super) async fn admin_api_checkin_claim(
     pool: &State<DbPool>,
-    cookies: &CookieJar<'_>,
+    auth: WebAuth<'_>,
 ) -> RouteResult<UserCheckinResponse> {
-    let session = require_web_session(cookies, pool.inner()).await?;
+    let session = require_web_session(auth, pool.inner()).await?;
     Ok(success_return(
         claim_user_checkin(&session, pool.inner()).await?,
     ))
 }
 
-#[post("/api/operations/<operation_name>")]
+fn operation_job_view(job: OperationJob) -> AdminOperationJobView {
+    AdminOperationJobView {
+        id: job.id,
+        operation: job.operation,
+        user_id: job.user_id,
+        status: job.status,
+        progress: job.progress,
+        error: job.error,
+        started_at: format_timestamp(Some(job.started_at)),
+        finished_at: job.finished_at.map(|ts| format_timestamp(Some(ts))),
+    }
+}
+
+/// Start a maintenance operation in the background; poll the returned job
+/// through `/api/operation-jobs/<id>`.
+#[post("/api/operations/<operation_name>?<user_id>")]
 pub(super) async fn admin_api_operation(
     operation_name: &str,
+    user_id: Option<i32>,
     operation_manager: &State<OperationManager>,
     pool: &State<DbPool>,
-    cookies: &CookieJar<'_>,
-) -> RouteResult<EmptyResponse> {
-    require_admin_api(cookies, pool.inner()).await?;
+    auth: WebAuth<'_>,
+) -> RouteResult<AdminOperationJobView> {
+    require_admin_api(auth, pool.inner()).await?;
 
+    let params = user_id.map(|user_id| OperationParams {
+        user_id: Some(user_id),
+        ..Default::default()
+    });
     match operation_name {
-        "refresh_song_file_cache" | "refresh_content_bundle_cache" | "refresh_all_score_rating" => {
-            operation_manager
-                .execute_operation(operation_name, None)
-                .await?;
-            Ok(success_return_no_value())
+        "refresh_song_file_cache"
+        | "refresh_song_file_cache_incremental"
+        | "refresh_content_bundle_cache"
+        | "refresh_all_score_rating"
+        | "backfill_score_v2"
+        | "refresh_chart_analytics"
+        | "estimate_chart_constants"
+        | "scan_score_anomalies"
+        | "reload_game_constants"
+        | "reconcile_charts"
+        | "reload_songlist"
+        | "sync_charts_from_songlist"
+        | "purge_songplay_tokens"
+        | "recalculate_world_progress"
+        | "rebuild_recent30"
+        | "vacuum_expired_presents"
+        | "reload_config" => {
+            let job = operation_manager.start_operation(operation_name, params)?;
+            Ok(success_return(operation_job_view(job)))
+        }
+        "export_user_data" => {
+            if params.is_none() {
+                return Err(ArcError::input("export_user_data requires a user_id"));
+            }
+            let job = operation_manager.start_operation(operation_name, params)?;
+            Ok(success_return(operation_job_view(job)))
         }
         _ => Err(ArcError::input("Unsupported admin operation")),
     }
 }
+
+#[get("/api/operation-jobs")]
+pub(super) async fn admin_api_operation_jobs(
+    operation_manager: &State<OperationManager>,
+    guard: PowerGuard,
+) -> RouteResult<Vec<AdminOperationJobView>> {
+    guard.require(SELECT_POWER)?;
+    Ok(success_return(
+        operation_manager
+            .jobs()
+            .into_iter()
+            .map(operation_job_view)
+            .collect(),
+    ))
+}
+
+#[get("/api/operation-jobs/<job_id>")]
+pub(super) async fn admin_api_operation_job(
+    job_id: &str,
+    operation_manager: &State<OperationManager>,
+    guard: PowerGuard,
+) -> RouteResult<AdminOperationJobView> {
+    guard.require(SELECT_POWER)?;
+    let job = operation_manager
+        .job(job_id)
+        .ok_or_else(|| ArcError::no_data("任务不存在", -2))?;
+    Ok(success_return(operation_job_view(job)))
+}
+
+#[get("/api/game-constants")]
+pub(super) async fn admin_api_game_constants(
+    pool: &State<DbPool>,
+    guard: PowerGuard,
+) -> RouteResult<Vec<GameConstantEntry>> {
+    guard.require(SELECT_POWER)?;
+    Ok(success_return(
+        GameConstantsService::new(pool.inner().clone())
+            .list()
+            .await?,
+    ))
+}
+
+/// Override or reset a game constant; it applies on this instance right
+/// away and on the others at their next reload.
+#[post(
+    "/api/admin-actions/game-constants",
+    format = "json",
+    data = "<payload>"
+)]
+pub(super) async fn admin_api_game_constant_update(
+    payload: Json<AdminGameConstantPayload>,
+    pool: &State<DbPool>,
+    auth: WebAuth<'_>,
+) -> RouteResult<AdminActionResponse> {
+    require_admin_api(auth, pool.inner()).await?;
+    let service = GameConstantsService::new(pool.inner().clone());
+    let message = match payload.value {
+        Some(value) => {
+            service.set(&payload.name, value).await?;
+            format!("常量 {} 已设置为 {value}", payload.name)
+        }
+        None => {
+            service.reset(&payload.name).await?;
+            format!("常量 {} 已恢复默认值", payload.name)
+        }
+    };
+    Ok(success_return(AdminActionResponse {
+        message,
+        affected_rows: 1,
+    }))
+}
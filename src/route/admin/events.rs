@@ -0,0 +1,493 @@
+//! Events (time-boxed point competitions): listing, creation/update,
+//! deletion and the per-event ladder. Events can also carry community
+//! milestones that every player's plays count towards.
+
+use rocket::serde::json::Json;
+use rocket::{delete, get, post, State};
+
+use crate::error::ArcError;
+use crate::route::common::{success_return, RouteResult};
+use crate::service::event::{CommunityMetric, EventChart, EventRewardTier, DEFAULT_LADDER_LIMIT};
+use crate::service::EventService;
+use crate::DbPool;
+
+use super::helpers::{format_timestamp, normalize_admin_required_text, parse_admin_datetime};
+use super::models::{
+    AdminActionResponse, AdminEventDeletePayload, AdminEventLadderResponse, AdminEventLadderRow,
+    AdminEventPayload, AdminEventView,
+};
+use super::session::{require_admin_api, PowerGuard, WebAuth};
+use super::SELECT_POWER;
+
+/// Parse one `song_id:difficulty[:multiplier]` per line.
+fn parse_event_charts(raw: &str) -> Result<Vec<EventChart>, ArcError> {
+    let mut charts: Vec<EventChart> = Vec::new();
+    for line in raw.lines().map(str::trim).filter(|line| !line.is_empty()) {
+        let invalid = || ArcError::input(format!("谱面格式错误: {line}"));
+        let parts = line.split(':').map(str::trim).collect::<Vec<_>>();
+        let (song_id, difficulty, multiplier) = match parts.as_slice() {
+            [song_id, difficulty] => (*song_id, *difficulty, "1"),
+            [song_id, difficulty, multiplier] => (*song_id, *difficulty, *multiplier),
+            _ => return Err(invalid()),
+        };
+        let difficulty = difficulty.parse::<i32>().map_err(|_| invalid())?;
+        let point_multiplier = multiplier.parse::<i32>().map_err(|_| invalid())?;
+        if song_id.is_empty() || !(0..=4).contains(&difficulty) || point_multiplier <= 0 {
+            return Err(invalid());
+        }
+        if charts
+            .iter()
+            .any(|chart| chart.song_id == song_id && chart.difficulty == difficulty)
+        {
+            return Err(ArcError::input(format!("谱面重复: {line}")));
+        }
+        charts.push(EventChart {
+            song_id: song_id.to_string(),
+            difficulty,
+            point_multiplier,
+        });
+    }
+    if charts.is_empty() {
+        return Err(ArcError::input("charts 不能为空"));
+    }
+    Ok(charts)
+}
+
+/// Parse one `points:type:amount` or `points:type:item_id:amount` per line.
+fn parse_event_reward_tiers(raw: &str) -> Result<Vec<EventRewardTier>, ArcError> {
+    let mut tiers: Vec<EventRewardTier> = Vec::new();
+    for line in raw.lines().map(str::trim).filter(|line| !line.is_empty()) {
+        let invalid = || ArcError::input(format!("奖励档位格式错误: {line}"));
+        let parts = line.split(':').map(str::trim).collect::<Vec<_>>();
+        let (points, reward_type, reward_item_id, amount) = match parts.as_slice() {
+            [points, reward_type, amount] => (*points, *reward_type, *reward_type, *amount),
+            [points, reward_type, item_id, amount] => (*points, *reward_type, *item_id, *amount),
+            _ => return Err(invalid()),
+        };
+        let points = points.parse::<i32>().map_err(|_| invalid())?;
+        let reward_amount = amount.parse::<i32>().map_err(|_| invalid())?;
+        if points <= 0 || reward_type.is_empty() || reward_item_id.is_empty() || reward_amount <= 0
+        {
+            return Err(invalid());
+        }
+        if tiers.iter().any(|tier| tier.points == points) {
+            return Err(ArcError::input(format!("奖励档位重复: {line}")));
+        }
+        tiers.push(EventRewardTier {
+            points,
+            reward_type: reward_type.to_string(),
+            reward_item_id: reward_item_id.to_string(),
+            reward_amount,
+        });
+    }
+    tiers.sort_by_key(|tier| tier.points);
+    Ok(tiers)
+}
+
+/// A community milestone parsed from the admin form.
+struct CommunityMilestoneInput {
+    metric: CommunityMetric,
+    target: i64,
+    reward_type: String,
+    reward_item_id: String,
+    reward_amount: i32,
+}
+
+/// Parse one `metric:target:type:amount` or `metric:target:type:item_id:amount`
+/// per line, where `metric` is `fragments` or `clears`.
+fn parse_event_community_milestones(raw: &str) -> Result<Vec<CommunityMilestoneInput>, ArcError> {
+    let mut milestones: Vec<CommunityMilestoneInput> = Vec::new();
+    for line in raw.lines().map(str::trim).filter(|line| !line.is_empty()) {
+        let invalid = || ArcError::input(format!("社区目标格式错误: {line}"));
+        let parts = line.split(':').map(str::trim).collect::<Vec<_>>();
+        let (metric, target, reward_type, reward_item_id, amount) = match parts.as_slice() {
+            [metric, target, reward_type, amount] => {
+                (*metric, *target, *reward_type, *reward_type, *amount)
+            }
+            [metric, target, reward_type, item_id, amount] => {
+                (*metric, *target, *reward_type, *item_id, *amount)
+            }
+            _ => return Err(invalid()),
+        };
+        let metric = CommunityMetric::parse(metric).ok_or_else(invalid)?;
+        let target = target.parse::<i64>().map_err(|_| invalid())?;
+        let reward_amount = amount.parse::<i32>().map_err(|_| invalid())?;
+        if target <= 0 || reward_type.is_empty() || reward_item_id.is_empty() || reward_amount <= 0
+        {
+            return Err(invalid());
+        }
+        if milestones
+            .iter()
+            .any(|milestone| milestone.metric == metric && milestone.target == target)
+        {
+            return Err(ArcError::input(format!("社区目标重复: {line}")));
+        }
+        milestones.push(CommunityMilestoneInput {
+            metric,
+            target,
+            reward_type: reward_type.to_string(),
+            reward_item_id: reward_item_id.to_string(),
+            reward_amount,
+        });
+    }
+    milestones.sort_by_key(|milestone| (milestone.metric.as_str(), milestone.target));
+    Ok(milestones)
+}
+
+async fn load_admin_events(pool: &DbPool) -> Result<Vec<AdminEventView>, ArcError> {
+    let rows = sqlx::query!(
+        "SELECT e.event_id, e.name, e.description, e.start_ts, e.end_ts, e.is_available,
+                (SELECT COUNT(*) FROM user_event ue
+                 WHERE ue.event_id = e.event_id AND ue.points > 0) AS `participant_count!: i64`,
+                COALESCE((SELECT p.value FROM event_community_progress p
+                          WHERE p.event_id = e.event_id AND p.metric = 'fragments'), 0)
+                    AS `community_fragments!: i64`,
+                COALESCE((SELECT p.value FROM event_community_progress p
+                          WHERE p.event_id = e.event_id AND p.metric = 'clears'), 0)
+                    AS `community_clears!: i64`
+         FROM event e
+         ORDER BY e.start_ts DESC, e.event_id ASC"
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|err| ArcError::input(format!("查询活动失败: {err}")))?;
+
+    let mut events = Vec::with_capacity(rows.len());
+    for row in rows {
+        let charts = sqlx::query!(
+            "SELECT song_id, difficulty, point_multiplier FROM event_chart
+             WHERE event_id = ? ORDER BY song_id, difficulty",
+            &row.event_id
+        )
+        .fetch_all(pool)
+        .await
+        .map_err(|err| ArcError::input(format!("查询活动谱面失败: {err}")))?
+        .into_iter()
+        .map(|chart| {
+            format!(
+                "{}:{}:{}",
+                chart.song_id, chart.difficulty, chart.point_multiplier
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+        let reward_tiers = sqlx::query!(
+            "SELECT points, reward_type, reward_item_id, reward_amount FROM event_reward_tier
+             WHERE event_id = ? ORDER BY points",
+            &row.event_id
+        )
+        .fetch_all(pool)
+        .await
+        .map_err(|err| ArcError::input(format!("查询活动奖励失败: {err}")))?
+        .into_iter()
+        .map(|tier| {
+            format!(
+                "{}:{}:{}:{}",
+                tier.points, tier.reward_type, tier.reward_item_id, tier.reward_amount
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+        let community_milestones = sqlx::query!(
+            "SELECT metric, target, reward_type, reward_item_id, reward_amount
+             FROM event_community_milestone
+             WHERE event_id = ? ORDER BY metric, target",
+            &row.event_id
+        )
+        .fetch_all(pool)
+        .await
+        .map_err(|err| ArcError::input(format!("查询社区目标失败: {err}")))?
+        .into_iter()
+        .map(|milestone| {
+            format!(
+                "{}:{}:{}:{}:{}",
+                milestone.metric,
+                milestone.target,
+                milestone.reward_type,
+                milestone.reward_item_id,
+                milestone.reward_amount
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+        events.push(AdminEventView {
+            start_time: format_timestamp(Some(row.start_ts)),
+            end_time: format_timestamp(Some(row.end_ts)),
+            event_id: row.event_id,
+            name: row.name,
+            description: row.description.unwrap_or_default(),
+            start_ts: row.start_ts,
+            end_ts: row.end_ts,
+            available: row.is_available != 0,
+            charts,
+            reward_tiers,
+            community_milestones,
+            community_fragments: row.community_fragments,
+            community_clears: row.community_clears,
+            participant_count: row.participant_count,
+        });
+    }
+    Ok(events)
+}
+
+async fn save_admin_event(
+    payload: &AdminEventPayload,
+    pool: &DbPool,
+) -> Result<AdminActionResponse, ArcError> {
+    let event_id = normalize_admin_required_text(&payload.event_id, "event_id", 64)?;
+    let name = normalize_admin_required_text(&payload.name, "name", 255)?;
+    let description = super::helpers::normalize_optional_text(payload.description.as_deref(), 1000);
+    let start_ts = parse_admin_datetime(payload.start_ts.as_deref(), "start_ts")?;
+    let end_ts = parse_admin_datetime(payload.end_ts.as_deref(), "end_ts")?;
+    if end_ts <= start_ts {
+        return Err(ArcError::input("end_ts 必须晚于 start_ts"));
+    }
+    let charts = parse_event_charts(&payload.charts)?;
+    let reward_tiers = parse_event_reward_tiers(&payload.reward_tiers)?;
+    let community_milestones = parse_event_community_milestones(&payload.community_milestones)?;
+    let available = payload.available.unwrap_or(true);
+
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|err| ArcError::input(format!("事务创建失败: {err}")))?;
+    let affected_rows = sqlx::query!(
+        "INSERT INTO event (event_id, name, description, start_ts, end_ts, is_available)
+         VALUES (?, ?, ?, ?, ?, ?)
+         ON DUPLICATE KEY UPDATE name = VALUES(name), description = VALUES(description),
+            start_ts = VALUES(start_ts), end_ts = VALUES(end_ts),
+            is_available = VALUES(is_available)",
+        &event_id,
+        name,
+        description,
+        start_ts,
+        end_ts,
+        available
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|err| ArcError::input(format!("保存活动失败: {err}")))?
+    .rows_affected();
+
+    sqlx::query!("DELETE FROM event_chart WHERE event_id = ?", &event_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|err| ArcError::input(format!("清理活动谱面失败: {err}")))?;
+    for chart in &charts {
+        sqlx::query!(
+            "INSERT INTO event_chart (event_id, song_id, difficulty, point_multiplier)
+             VALUES (?, ?, ?, ?)",
+            &event_id,
+            chart.song_id,
+            chart.difficulty,
+            chart.point_multiplier
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|err| ArcError::input(format!("保存活动谱面失败: {err}")))?;
+    }
+
+    sqlx::query!(
+        "DELETE FROM event_reward_tier WHERE event_id = ?",
+        &event_id
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|err| ArcError::input(format!("清理活动奖励失败: {err}")))?;
+    for tier in &reward_tiers {
+        sqlx::query!(
+            "INSERT INTO event_reward_tier
+                (event_id, points, reward_type, reward_item_id, reward_amount)
+             VALUES (?, ?, ?, ?, ?)",
+            &event_id,
+            tier.points,
+            tier.reward_type,
+            tier.reward_item_id,
+            tier.reward_amount
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|err| ArcError::input(format!("保存活动奖励失败: {err}")))?;
+    }
+
+    // Milestones that stay keep `reached_at`, so re-saving an event never
+    // sends a reached milestone again.
+    let existing_milestones = sqlx::query!(
+        "SELECT metric, target FROM event_community_milestone WHERE event_id = ?",
+        &event_id
+    )
+    .fetch_all(&mut *tx)
+    .await
+    .map_err(|err| ArcError::input(format!("查询社区目标失败: {err}")))?;
+    for existing in existing_milestones {
+        let kept = community_milestones.iter().any(|milestone| {
+            milestone.metric.as_str() == existing.metric && milestone.target == existing.target
+        });
+        if kept {
+            continue;
+        }
+        sqlx::query!(
+            "DELETE FROM event_community_milestone
+             WHERE event_id = ? AND metric = ? AND target = ?",
+            &event_id,
+            existing.metric,
+            existing.target
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|err| ArcError::input(format!("清理社区目标失败: {err}")))?;
+    }
+    for milestone in &community_milestones {
+        sqlx::query!(
+            "INSERT INTO event_community_milestone
+                (event_id, metric, target, reward_type, reward_item_id, reward_amount)
+             VALUES (?, ?, ?, ?, ?, ?)
+             ON DUPLICATE KEY UPDATE reward_type = VALUES(reward_type),
+                reward_item_id = VALUES(reward_item_id),
+                reward_amount = VALUES(reward_amount)",
+            &event_id,
+            milestone.metric.as_str(),
+            milestone.target,
+            milestone.reward_type,
+            milestone.reward_item_id,
+            milestone.reward_amount
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|err| ArcError::input(format!("保存社区目标失败: {err}")))?;
+    }
+    tx.commit()
+        .await
+        .map_err(|err| ArcError::input(format!("保存活动失败: {err}")))?;
+
+    Ok(AdminActionResponse {
+        message: format!(
+            "活动已保存: {} 个谱面, {} 个奖励档位, {} 个社区目标",
+            charts.len(),
+            reward_tiers.len(),
+            community_milestones.len()
+        ),
+        affected_rows,
+    })
+}
+
+async fn delete_admin_event(
+    payload: &AdminEventDeletePayload,
+    pool: &DbPool,
+) -> Result<AdminActionResponse, ArcError> {
+    let event_id = normalize_admin_required_text(&payload.event_id, "event_id", 64)?;
+
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|err| ArcError::input(format!("事务创建失败: {err}")))?;
+    sqlx::query!("DELETE FROM user_event WHERE event_id = ?", &event_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|err| ArcError::input(format!("删除活动积分失败: {err}")))?;
+    sqlx::query!(
+        "DELETE FROM event_community_progress WHERE event_id = ?",
+        &event_id
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|err| ArcError::input(format!("删除社区进度失败: {err}")))?;
+    sqlx::query!(
+        "DELETE FROM event_community_milestone WHERE event_id = ?",
+        &event_id
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|err| ArcError::input(format!("删除社区目标失败: {err}")))?;
+    sqlx::query!(
+        "DELETE FROM event_reward_tier WHERE event_id = ?",
+        &event_id
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|err| ArcError::input(format!("删除活动奖励失败: {err}")))?;
+    sqlx::query!("DELETE FROM event_chart WHERE event_id = ?", &event_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|err| ArcError::input(format!("删除活动谱面失败: {err}")))?;
+    let affected_rows = sqlx::query!("DELETE FROM event WHERE event_id = ?", &event_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|err| ArcError::input(format!("删除活动失败: {err}")))?
+        .rows_affected();
+    if affected_rows == 0 {
+        return Err(ArcError::no_data("活动不存在", -2));
+    }
+    tx.commit()
+        .await
+        .map_err(|err| ArcError::input(format!("删除活动失败: {err}")))?;
+
+    Ok(AdminActionResponse {
+        message: "活动已删除".to_string(),
+        affected_rows,
+    })
+}
+
+#[get("/api/events")]
+pub(super) async fn admin_api_events(
+    pool: &State<DbPool>,
+    guard: PowerGuard,
+) -> RouteResult<Vec<AdminEventView>> {
+    guard.require(SELECT_POWER)?;
+    Ok(success_return(load_admin_events(pool.inner()).await?))
+}
+
+#[get("/api/event-ladder?<event_id>&<limit>")]
+pub(super) async fn admin_api_event_ladder(
+    event_id: Option<&str>,
+    limit: Option<i64>,
+    event_service: &State<EventService>,
+    guard: PowerGuard,
+) -> RouteResult<AdminEventLadderResponse> {
+    guard.require(SELECT_POWER)?;
+    let event_id = event_id
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .ok_or_else(|| ArcError::input("需要提供 event_id"))?;
+    let entries = event_service
+        .get_ladder(event_id, limit.unwrap_or(DEFAULT_LADDER_LIMIT))
+        .await?
+        .into_iter()
+        .map(|entry| AdminEventLadderRow {
+            rank: entry.rank,
+            user_id: entry.user_id,
+            name: entry.name,
+            points: entry.points,
+        })
+        .collect();
+
+    Ok(success_return(AdminEventLadderResponse {
+        event_id: event_id.to_string(),
+        entries,
+    }))
+}
+
+#[post("/api/admin-actions/events", format = "json", data = "<payload>")]
+pub(super) async fn admin_api_event_save(
+    payload: Json<AdminEventPayload>,
+    pool: &State<DbPool>,
+    auth: WebAuth<'_>,
+) -> RouteResult<AdminActionResponse> {
+    require_admin_api(auth, pool.inner()).await?;
+    Ok(success_return(
+        save_admin_event(&payload, pool.inner()).await?,
+    ))
+}
+
+#[delete("/api/admin-actions/events", format = "json", data = "<payload>")]
+pub(super) async fn admin_api_event_delete(
+    payload: Json<AdminEventDeletePayload>,
+    pool: &State<DbPool>,
+    auth: WebAuth<'_>,
+) -> RouteResult<AdminActionResponse> {
+    require_admin_api(auth, pool.inner()).await?;
+    Ok(success_return(
+        delete_admin_event(&payload, pool.inner()).await?,
+    ))
+}
@@ -1,14 +1,16 @@
 //! Cross-cutting helpers shared across admin domain modules: timestamp
-//! formatting, pagination, SQL filter builders, user resolution and ban
-//! detection.
+//! formatting, pagination, SQL filter builders, user resolution, ban
+//! detection and reading uploaded files.
 
-use chrono::{Local, TimeZone, Utc};
+use chrono::{Local, NaiveDateTime, TimeZone, Utc};
+use rocket::fs::TempFile;
+use tokio::io::AsyncReadExt;
 
 use crate::error::ArcError;
 use crate::DbPool;
 
 use super::models::{
-    AdminPageResponse, AdminUserDbSummary, AdminUserSelectorPayload, AdminUserSummary,
+    AdminPageResponse, AdminUserDbSummary, AdminUserSelectorPayload, AdminUserSummary, PageQuery,
 };
 
 /// Format an optional millisecond timestamp as `YYYY-MM-DD HH:MM`, accepting
@@ -27,6 +29,15 @@ pub(super) fn format_timestamp(ts: Option<i64>) -> String {
         .unwrap_or_else(|| "-".to_string())
 }
 
+/// Format a local midnight in seconds as `YYYY-MM-DD`.
+pub(super) fn format_day(day: i64) -> String {
+    Local
+        .timestamp_opt(day, 0)
+        .single()
+        .map(|x| x.format("%Y-%m-%d").to_string())
+        .unwrap_or_else(|| "-".to_string())
+}
+
 /// A ban flag is `ban:<end_timestamp_ms>`; it is active while that timestamp
 /// is still in the future.
 pub(super) fn is_ban_flag_active(ban_flag: Option<&str>) -> bool {
@@ -129,12 +140,27 @@ pub(super) fn clean_query_value(value: Option<&str>) -> Option<String> {
         .map(str::to_owned)
 }
 
-pub(super) fn normalize_page(page: Option<i64>, page_size: Option<i64>) -> (i64, i64) {
+fn normalize_page(page: Option<i64>, page_size: Option<i64>) -> (i64, i64) {
     let page = page.unwrap_or(1).max(1);
     let page_size = page_size.unwrap_or(25).clamp(10, 100);
     (page, page_size)
 }
 
+impl PageQuery {
+    /// Page number and size after [`normalize_page`]
+    pub(super) fn normalized(&self) -> (i64, i64) {
+        normalize_page(self.page, self.page_size)
+    }
+
+    /// The requested sort key, ignoring blanks
+    pub(super) fn sort(&self) -> Option<&str> {
+        self.sort
+            .as_deref()
+            .map(str::trim)
+            .filter(|sort| !sort.is_empty())
+    }
+}
+
 pub(super) fn clamp_page(page: i64, page_size: i64, total: i64) -> (i64, i64) {
     let page_count = ((total.max(1) + page_size - 1) / page_size).max(1);
     let page = page.clamp(1, page_count);
@@ -180,8 +206,59 @@ pub(super) fn like_filter(query: Option<&str>, columns: &[&str]) -> (String, Vec
     )
 }
 
+/// Trim a required text field, rejecting empty values and truncating to
+/// `max_len` characters.
+pub(super) fn normalize_admin_required_text(
+    raw: &str,
+    field: &str,
+    max_len: usize,
+) -> Result<String, ArcError> {
+    let value = raw.trim();
+    if value.is_empty() {
+        return Err(ArcError::input(format!("{field} 不能为空")));
+    }
+    Ok(value.chars().take(max_len).collect())
+}
+
+/// Parse a `datetime-local` input value (`YYYY-MM-DDTHH:MM`, local time) into
+/// a millisecond timestamp.
+pub(super) fn parse_admin_datetime(raw: Option<&str>, field: &str) -> Result<i64, ArcError> {
+    let value = raw.map(str::trim).unwrap_or("");
+    if value.is_empty() {
+        return Err(ArcError::input(format!("{field} 不能为空")));
+    }
+    let naive = NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M")
+        .map_err(|_| ArcError::input(format!("{field} 时间格式错误")))?;
+    let local_dt = Local
+        .from_local_datetime(&naive)
+        .single()
+        .ok_or_else(|| ArcError::input(format!("{field} 时间非法")))?;
+    Ok(local_dt.timestamp_millis())
+}
+
 /// Adapter turning a `String` error from the catalog CRUD helpers into an
 /// [`ArcError`] for use with `map_err`.
 pub(super) fn admin_api_input_error(message: String) -> ArcError {
     ArcError::input(message)
 }
+
+/// Contents of an uploaded file
+pub(super) async fn read_upload(file: &TempFile<'_>) -> Result<Vec<u8>, ArcError> {
+    let read_error = |e: std::io::Error| ArcError::Io {
+        message: format!("Failed to read uploaded file: {e}"),
+    };
+    let reader = file.open().await.map_err(read_error)?;
+    tokio::pin!(reader);
+    let mut contents = Vec::with_capacity(file.len() as usize);
+    reader
+        .read_to_end(&mut contents)
+        .await
+        .map_err(read_error)?;
+    Ok(contents)
+}
+
+/// Contents of an uploaded text file
+pub(super) async fn read_text_file(file: &TempFile<'_>) -> Result<String, ArcError> {
+    String::from_utf8(read_upload(file).await?)
+        .map_err(|_| ArcError::input("The uploaded file is not UTF-8 text"))
+}
@@ -3,25 +3,40 @@
 //! The module is split by domain:
 //! - [`mod@models`] — request/response and DB-row types.
 //! - [`mod@helpers`] — shared formatting, pagination and query helpers.
-//! - [`mod@session`] — authentication, cookies and the `require_*` guards.
-//! - [`mod@dashboard`] — overview metrics, check-in, maintenance operations.
-//! - [`mod@users`] — player management and per-player scores.
-//! - [`mod@scores`] — score images and the chart leaderboard.
+//! - [`mod@session`] — authentication, cookies, API tokens and the `require_*`
+//!   guards.
+//! - [`mod@dashboard`] — overview metrics, check-in, maintenance operations
+//!   and the game constant editor.
+//! - [`mod@users`] — player management, per-player scores and potential
+//!   history, save versions, device fingerprints and download quotas.
+//! - [`mod@scores`] — score images, the chart leaderboard, chart analytics and
+//!   uploaded replays.
 //! - [`mod@presents`] — presents and redeem codes.
-//! - [`mod@catalog`] — song / item / purchase / purchase-item data tables.
+//! - [`mod@events`] — time-boxed events and their ladders.
+//! - [`mod@anomalies`] — review queues of the offline score anomaly scan and
+//!   of submissions flagged by the score validator.
+//! - [`mod@catalog`] — song / item / purchase / purchase-item data tables,
+//!   song package uploads and songlist mismatches.
+//! - [`mod@requests`] — recent game API requests from the access log.
+//! - [`mod@bundles`] — served content bundles and bundle uploads.
 
+mod anomalies;
+mod bundles;
 mod catalog;
 mod dashboard;
+mod events;
 mod helpers;
 mod models;
 mod presents;
+mod requests;
 mod scores;
 mod session;
 mod users;
 
 use std::sync::{OnceLock, RwLock};
 
-use rocket::{routes, Route};
+use rocket::route::{BoxFuture, Outcome};
+use rocket::{routes, Data, Request, Route};
 
 use crate::config::CONFIG;
 
@@ -33,6 +48,16 @@ pub(super) const ADMIN_ROLE: i8 = 1;
 pub(super) const USER_ROLE: i8 = 0;
 pub(super) const CHART_EDITOR_ROLE: &str = "chart_editor";
 pub(super) const CHART_CONSTANT_EDIT_POWER: &str = "web_chart_constant_edit";
+/// Read access to every admin listing (users, saves, events, ...).
+pub(super) const SELECT_POWER: &str = "select";
+/// Full per-chart leaderboards.
+pub(super) const SELECT_SONG_RANK_POWER: &str = "select_song_rank";
+/// Per-chart leaderboards, limited to the top `SONG_RANK_TOP_LIMIT` entries.
+pub(super) const SELECT_SONG_RANK_TOP_POWER: &str = "select_song_rank_top";
+pub(super) const SONG_RANK_TOP_LIMIT: i64 = 20;
+
+/// Web panel routes that only make sense with the session cookie.
+const COOKIE_ONLY_ROUTES: &[&str] = &["admin_api_login", "admin_api_logout"];
 
 /// Credentials used for first-time admin bootstrap and as a fallback.
 #[derive(Debug, Clone)]
@@ -72,19 +97,46 @@ pub fn routes() -> Vec<Route> {
         dashboard::admin_api_checkin_status,
         dashboard::admin_api_checkin_claim,
         dashboard::admin_api_operation,
+        dashboard::admin_api_operation_jobs,
+        dashboard::admin_api_operation_job,
+        dashboard::admin_api_game_constants,
+        dashboard::admin_api_game_constant_update,
         // listings
         users::admin_api_users,
         users::admin_api_chart_editor_permission,
+        users::admin_api_user_shadow_ban,
         catalog::admin_api_songs,
+        catalog::admin_api_chart_constant_proposals,
+        catalog::admin_api_chart_mismatches,
+        catalog::admin_api_songlist_reload,
         catalog::admin_api_items,
         catalog::admin_api_purchases,
         catalog::admin_api_purchase_items,
+        presents::admin_api_presents,
+        bundles::admin_api_bundles,
         // queries
         users::admin_api_user_scores,
+        users::admin_api_user_ptt_history,
+        users::admin_api_user_recent30_diff,
+        users::admin_api_user_saves,
+        users::admin_api_user_devices,
+        users::admin_api_user_purchases,
+        users::admin_api_user_download_quota,
+        users::admin_api_impersonation_log,
         scores::admin_api_score_images,
         scores::admin_api_score_image_png,
         scores::admin_api_chart_top,
+        scores::admin_api_chart_analytics,
+        scores::admin_api_replays,
+        scores::admin_api_replay_file,
         presents::admin_api_redeem_users,
+        presents::admin_api_redeem_batches,
+        presents::admin_api_redeem_batch_export,
+        events::admin_api_events,
+        events::admin_api_event_ladder,
+        anomalies::admin_api_anomalies,
+        anomalies::admin_api_score_audits,
+        requests::admin_api_request_log,
         // player actions
         users::admin_api_user_ticket,
         users::admin_api_user_password,
@@ -92,17 +144,34 @@ pub fn routes() -> Vec<Route> {
         users::admin_api_user_ban,
         users::admin_api_user_purchase,
         users::admin_api_scores_delete,
+        users::admin_api_scores_import,
+        users::admin_api_user_save_rollback,
+        users::admin_api_user_impersonate,
+        users::admin_api_user_download_quota_update,
         // presents / redeems
         presents::admin_api_present_create,
+        presents::admin_api_present_publish,
+        presents::admin_present_publish_form,
         presents::admin_api_present_delete,
         presents::admin_api_present_deliver,
         presents::admin_api_redeem_create,
         presents::admin_api_redeem_delete,
+        presents::admin_api_redeem_batch_create,
+        presents::admin_api_redeem_batch_disable,
+        // events
+        events::admin_api_event_save,
+        events::admin_api_event_delete,
+        // anomaly review
+        anomalies::admin_api_anomaly_review,
+        anomalies::admin_api_score_audit_review,
         // catalog CRUD
         catalog::admin_api_song_create,
         catalog::admin_api_song_update,
         catalog::admin_api_chart_constants_update,
+        catalog::admin_api_chart_constant_proposal_accept,
+        catalog::admin_api_chart_constant_proposal_dismiss,
         catalog::admin_api_song_delete,
+        catalog::admin_api_song_upload,
         catalog::admin_api_item_create,
         catalog::admin_api_item_update,
         catalog::admin_api_item_delete,
@@ -112,5 +181,43 @@ pub fn routes() -> Vec<Route> {
         catalog::admin_api_purchase_item_create,
         catalog::admin_api_purchase_item_update,
         catalog::admin_api_purchase_item_delete,
+        bundles::admin_api_bundle_upload,
     ]
 }
+
+/// The admin JSON API for scripts and external dashboards, mounted under
+/// `/api/v1/admin`.
+///
+/// Every `/api/...` route of [`routes`] except the cookie login/logout is
+/// served again without that prefix (`/web/api/users` becomes
+/// `/api/v1/admin/users`), next to `/token` for issuing and revoking the API
+/// tokens these requests authenticate with.
+pub fn api_v1_routes() -> Vec<Route> {
+    let mut api_routes: Vec<Route> = routes()
+        .into_iter()
+        .filter_map(|route| {
+            if COOKIE_ONLY_ROUTES.contains(&route.name.as_deref()?) {
+                return None;
+            }
+            let uri = route.uri.unmounted_origin.to_string();
+            let path = uri.strip_prefix("/api/")?;
+            let mut rebased =
+                Route::ranked(route.rank, route.method, &format!("/{path}"), placeholder);
+            rebased.name = route.name;
+            rebased.format = route.format;
+            rebased.handler = route.handler;
+            Some(rebased)
+        })
+        .collect();
+    api_routes.extend(routes![
+        session::admin_api_token_create,
+        session::admin_api_token_revoke
+    ]);
+    api_routes
+}
+
+/// Stand-in handler for [`api_v1_routes`], replaced by the original one
+/// before the route is returned.
+fn placeholder<'r>(request: &'r Request<'_>, _data: Data<'r>) -> BoxFuture<'r> {
+    Outcome::from(request, ()).pin()
+}
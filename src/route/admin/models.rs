@@ -4,12 +4,16 @@
 //! Fields are `pub(super)` so the sibling domain modules under `super::admin`
 //! can construct and read them.
 
+use rocket::fs::TempFile;
 use rocket::http::{ContentType, Status};
 use rocket::response::{Responder, Response};
+use rocket::FromForm;
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 use std::io::Cursor;
 
+use crate::service::operations::OperationJobStatus;
+
 use super::{ADMIN_ROLE, USER_ROLE};
 
 // Response view structs
@@ -33,6 +37,7 @@ pub(super) struct UserListView {
     pub(super) ticket: i32,
     pub(super) last_play: String,
     pub(super) banned: bool,
+    pub(super) shadow_banned: bool,
     pub(super) is_admin: bool,
     pub(super) can_edit_chart_constants: bool,
 }
@@ -49,6 +54,42 @@ pub(super) struct SongRowView {
     pub(super) rating_etr: String,
 }
 
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct AdminOperationJobView {
+    pub(super) id: String,
+    pub(super) operation: String,
+    pub(super) user_id: Option<i32>,
+    pub(super) status: OperationJobStatus,
+    pub(super) progress: Option<f64>,
+    pub(super) error: Option<String>,
+    pub(super) started_at: String,
+    pub(super) finished_at: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct ChartMismatchView {
+    pub(super) has_songlist: bool,
+    pub(super) missing_charts: Vec<String>,
+    pub(super) unlisted_charts: Vec<String>,
+    pub(super) placeholders_created: u64,
+    pub(super) charts_updated: u64,
+    pub(super) checked_at: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct ChartConstantProposalView {
+    pub(super) song_id: String,
+    pub(super) name_en: String,
+    pub(super) difficulty: i32,
+    pub(super) proposed_rating: String,
+    pub(super) sample_count: i32,
+    pub(super) model: String,
+    pub(super) created_at: String,
+}
+
 #[derive(Debug, Clone, Default, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub(super) struct ItemRowView {
@@ -69,6 +110,18 @@ pub(super) struct PurchaseRowView {
     pub(super) item_summary: String,
 }
 
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct PresentRowView {
+    pub(super) present_id: String,
+    pub(super) description: String,
+    pub(super) expire_at: String,
+    pub(super) web_only: bool,
+    pub(super) item_summary: String,
+    /// Players the present was delivered to
+    pub(super) recipients: i64,
+}
+
 #[derive(Debug, Clone, Default, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub(super) struct PurchaseItemRowView {
@@ -121,6 +174,275 @@ pub(super) struct AdminUserScoresResponse {
     pub(super) r10: Vec<AdminScoreRowView>,
 }
 
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct AdminPttHistoryPointView {
+    /// Local midnight in seconds, the key for diff requests
+    pub(super) day: i64,
+    pub(super) date: String,
+    pub(super) potential: f64,
+    pub(super) best30: f64,
+    pub(super) recent10: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct AdminPttHistoryResponse {
+    pub(super) user: AdminUserSummary,
+    pub(super) points: Vec<AdminPttHistoryPointView>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct AdminRecent30EntryView {
+    pub(super) song_id: String,
+    pub(super) difficulty: i32,
+    pub(super) score: i32,
+    pub(super) clear_type: i32,
+    pub(super) rating: f64,
+    pub(super) time_played: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct AdminRecent30DiffResponse {
+    pub(super) user: AdminUserSummary,
+    pub(super) from: AdminPttHistoryPointView,
+    pub(super) to: AdminPttHistoryPointView,
+    pub(super) added: Vec<AdminRecent30EntryView>,
+    pub(super) removed: Vec<AdminRecent30EntryView>,
+    pub(super) unchanged: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct AdminSaveVersionView {
+    pub(super) history_id: i64,
+    pub(super) created_at: String,
+    pub(super) archived_at: String,
+    pub(super) data_size: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct AdminUserSavesResponse {
+    pub(super) user: AdminUserSummary,
+    /// `createdAt` of the live save, `None` when the user never uploaded.
+    pub(super) current_created_at: Option<String>,
+    pub(super) versions: Vec<AdminSaveVersionView>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct AdminImpersonationTokenResponse {
+    pub(super) user: AdminUserSummary,
+    /// Game access token; shown once and only valid until `expires_at`.
+    pub(super) token: String,
+    pub(super) expires_at: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct AdminImpersonationLogView {
+    pub(super) token_id: i64,
+    pub(super) user_id: i32,
+    pub(super) name: String,
+    pub(super) admin_user_id: i32,
+    pub(super) admin_name: String,
+    pub(super) reason: String,
+    pub(super) ip: Option<String>,
+    pub(super) created_at: String,
+    pub(super) expires_at: String,
+    pub(super) active: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct AdminDeviceView {
+    /// `device_id` or `install_id`.
+    pub(super) kind: String,
+    pub(super) value: String,
+    pub(super) device_model: String,
+    pub(super) first_seen: String,
+    pub(super) last_seen: String,
+    pub(super) last_ip: Option<String>,
+    pub(super) seen_count: i32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct AdminLinkedAccountView {
+    pub(super) user_id: i32,
+    pub(super) name: String,
+    pub(super) user_code: String,
+    pub(super) banned: bool,
+    /// Fingerprints shared with the inspected user, as `kind:value`.
+    pub(super) shared: Vec<String>,
+    pub(super) last_seen: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct AdminUserDevicesResponse {
+    pub(super) user: AdminUserSummary,
+    pub(super) devices: Vec<AdminDeviceView>,
+    pub(super) linked_accounts: Vec<AdminLinkedAccountView>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct AdminPurchaseLogView {
+    /// `pack`, `single` or `special`.
+    pub(super) kind: String,
+    pub(super) purchase_name: String,
+    pub(super) price: i32,
+    pub(super) currency: String,
+    pub(super) time: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct AdminUserPurchasesResponse {
+    pub(super) user: AdminUserSummary,
+    pub(super) purchases: Vec<AdminPurchaseLogView>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct AdminUserDownloadQuotaResponse {
+    pub(super) user: AdminUserSummary,
+    pub(super) download_count: i64,
+    pub(super) download_bytes: i64,
+    pub(super) times_limit: i32,
+    pub(super) bytes_limit: i64,
+    pub(super) is_override: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct ChartAnalyticsRowView {
+    pub(super) song_id: String,
+    pub(super) name_en: String,
+    pub(super) difficulty: i32,
+    pub(super) constant: Option<f64>,
+    pub(super) player_count: i32,
+    pub(super) avg_score: i64,
+    /// Percent, two decimals.
+    pub(super) avg_accuracy: f64,
+    /// Percent, two decimals.
+    pub(super) clear_rate: f64,
+    /// Player counts for `<9.5M`, `9.5M`, `9.8M`, `9.9M` and `10M+`.
+    pub(super) score_distribution: Vec<i64>,
+    pub(super) perceived_constant: Option<f64>,
+    /// `perceived_constant - constant`; positive means harder than listed.
+    pub(super) deviation: Option<f64>,
+    pub(super) sample_count: i32,
+    pub(super) computed_at: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct AdminAnomalyRowView {
+    pub(super) user_id: i32,
+    pub(super) name: String,
+    pub(super) user_code: String,
+    pub(super) score: f64,
+    pub(super) rating_spikes: i32,
+    pub(super) shared_fingerprints: i32,
+    pub(super) burst_plays: i32,
+    pub(super) play_count: i32,
+    pub(super) linked_users: Vec<i32>,
+    pub(super) computed_at: String,
+    pub(super) reviewed_at: Option<String>,
+    pub(super) review_note: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct AdminScoreAuditRowView {
+    pub(super) audit_id: i64,
+    pub(super) user_id: i32,
+    pub(super) name: String,
+    pub(super) song_id: String,
+    pub(super) difficulty: i32,
+    pub(super) score: i32,
+    pub(super) shiny_perfect_count: i32,
+    pub(super) perfect_count: i32,
+    pub(super) near_count: i32,
+    pub(super) miss_count: i32,
+    pub(super) clear_type: i32,
+    pub(super) flags: Vec<String>,
+    pub(super) rejected: bool,
+    pub(super) created_at: String,
+    pub(super) reviewed_at: Option<String>,
+    pub(super) review_note: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct AdminApiLogRowView {
+    pub(super) log_id: i64,
+    pub(super) created_at: String,
+    pub(super) method: String,
+    pub(super) path: String,
+    pub(super) user_id: Option<i32>,
+    pub(super) name: Option<String>,
+    pub(super) status: i16,
+    pub(super) latency_ms: i32,
+    pub(super) ip: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct AdminBundleView {
+    pub(super) app_version: String,
+    pub(super) version: String,
+    /// `None` for full bundles
+    pub(super) prev_version: Option<String>,
+    pub(super) uuid: String,
+    pub(super) json_size: u64,
+    pub(super) bundle_size: u64,
+    pub(super) bundle_path: String,
+    pub(super) added_files: usize,
+    pub(super) removed_files: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct AdminSongFileView {
+    pub(super) file_name: String,
+    pub(super) size: u64,
+    pub(super) md5: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct AdminSongUploadView {
+    pub(super) song_id: String,
+    pub(super) files: Vec<AdminSongFileView>,
+    pub(super) chart_created: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct AdminReplayRowView {
+    pub(super) user_id: i32,
+    pub(super) name: String,
+    pub(super) song_id: String,
+    pub(super) name_en: String,
+    pub(super) difficulty: i32,
+    /// Score of the play, `None` when the play log row is gone.
+    pub(super) score: Option<i32>,
+    /// Raw `time_played`, needed to address the replay file.
+    pub(super) time_played: i64,
+    pub(super) played_at: String,
+    pub(super) uploaded_at: String,
+    pub(super) size: i32,
+    pub(super) sha256: String,
+    /// Empty for replays uploaded before compression was checked.
+    pub(super) compression: String,
+}
+
 #[derive(Debug, Clone, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub(super) struct AdminChartTopResponse {
@@ -144,6 +466,65 @@ pub(super) struct AdminRedeemUsersResponse {
     pub(super) users: Vec<AdminUserSummary>,
 }
 
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct AdminRedeemBatchView {
+    pub(super) batch_id: String,
+    pub(super) description: String,
+    pub(super) redeem_type: i32,
+    pub(super) code_count: i64,
+    pub(super) redeemed_count: i64,
+    pub(super) disabled: bool,
+    pub(super) created_at: String,
+    pub(super) items: Vec<AdminRedeemItemView>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct AdminRedeemItemView {
+    pub(super) item_id: String,
+    pub(super) item_type: String,
+    pub(super) amount: i32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct AdminEventView {
+    pub(super) event_id: String,
+    pub(super) name: String,
+    pub(super) description: String,
+    pub(super) start_ts: i64,
+    pub(super) end_ts: i64,
+    pub(super) start_time: String,
+    pub(super) end_time: String,
+    pub(super) available: bool,
+    /// One `song_id:difficulty:multiplier` per line.
+    pub(super) charts: String,
+    /// One `points:type:item_id:amount` per line.
+    pub(super) reward_tiers: String,
+    /// One `metric:target:type:item_id:amount` per line.
+    pub(super) community_milestones: String,
+    pub(super) community_fragments: i64,
+    pub(super) community_clears: i64,
+    pub(super) participant_count: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct AdminEventLadderRow {
+    pub(super) rank: i32,
+    pub(super) user_id: i32,
+    pub(super) name: String,
+    pub(super) points: i32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct AdminEventLadderResponse {
+    pub(super) event_id: String,
+    pub(super) entries: Vec<AdminEventLadderRow>,
+}
+
 #[derive(Debug, Clone, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub(super) struct UserCheckinResponse {
@@ -185,6 +566,25 @@ impl<'r> Responder<'r, 'static> for PngResponse {
     }
 }
 
+pub(super) struct CsvResponse {
+    pub(super) file_name: String,
+    pub(super) body: String,
+}
+
+impl<'r> Responder<'r, 'static> for CsvResponse {
+    fn respond_to(self, _: &'r rocket::Request<'_>) -> rocket::response::Result<'static> {
+        Response::build()
+            .status(Status::Ok)
+            .header(ContentType::CSV)
+            .raw_header(
+                "Content-Disposition",
+                format!("attachment; filename=\"{}\"", self.file_name),
+            )
+            .sized_body(self.body.len(), Cursor::new(self.body))
+            .ok()
+    }
+}
+
 // Request payloads
 
 #[derive(Debug, Deserialize)]
@@ -243,6 +643,12 @@ pub(super) struct AdminSongDeletePayload {
     pub(super) sid: String,
 }
 
+#[derive(Debug, Deserialize)]
+pub(super) struct ChartConstantProposalPayload {
+    pub(super) sid: String,
+    pub(super) difficulty: i32,
+}
+
 #[derive(Debug, Deserialize)]
 pub(super) struct AdminItemPayload {
     pub(super) item_id: String,
@@ -298,6 +704,11 @@ pub(super) struct ChartEditorPermissionPayload {
     pub(super) enabled: bool,
 }
 
+#[derive(Debug, Deserialize)]
+pub(super) struct ShadowBanPayload {
+    pub(super) enabled: bool,
+}
+
 #[derive(Debug, Deserialize)]
 pub(super) struct AdminUserTicketPayload {
     pub(super) user_id: Option<i32>,
@@ -315,6 +726,48 @@ pub(super) struct AdminUserPasswordPayload {
     pub(super) password: String,
 }
 
+#[derive(Debug, Deserialize)]
+pub(super) struct AdminUserImpersonatePayload {
+    pub(super) user_id: Option<i32>,
+    pub(super) name: Option<String>,
+    pub(super) user_code: Option<String>,
+    /// Why support needs the player's account, kept in the audit log.
+    pub(super) reason: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub(super) struct AdminUserSaveRollbackPayload {
+    pub(super) user_id: Option<i32>,
+    pub(super) name: Option<String>,
+    pub(super) user_code: Option<String>,
+    pub(super) history_id: i64,
+}
+
+#[derive(Debug, Deserialize)]
+pub(super) struct AdminUserDownloadQuotaPayload {
+    pub(super) user_id: Option<i32>,
+    pub(super) name: Option<String>,
+    pub(super) user_code: Option<String>,
+    /// Forget the downloads counted in the current window.
+    #[serde(default)]
+    pub(super) reset: bool,
+    /// Per-user limits; leaving both empty restores the configured ones.
+    pub(super) times_limit: Option<i32>,
+    pub(super) bytes_limit: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(super) struct AdminAnomalyReviewPayload {
+    pub(super) user_id: i32,
+    pub(super) note: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(super) struct AdminScoreAuditReviewPayload {
+    pub(super) audit_id: i64,
+    pub(super) note: Option<String>,
+}
+
 #[derive(Debug, Deserialize)]
 pub(super) struct AdminUserCreatePayload {
     pub(super) name: String,
@@ -349,6 +802,53 @@ pub(super) struct AdminPresentPayload {
     pub(super) item_id: String,
     pub(super) item_type: String,
     pub(super) amount: Option<String>,
+    /// Only claimable from the player web dashboard (`/me`).
+    #[serde(default)]
+    pub(super) web_only: bool,
+}
+
+#[derive(Debug, Deserialize, FromForm)]
+pub(super) struct AdminPresentItemPayload {
+    pub(super) item_id: String,
+    pub(super) item_type: String,
+    pub(super) amount: Option<i32>,
+}
+
+/// Content bundle upload: the `.cb` file and its bundle JSON.
+#[derive(FromForm)]
+pub(super) struct AdminBundleUploadForm<'r> {
+    pub(super) metadata: TempFile<'r>,
+    pub(super) bundle: TempFile<'r>,
+}
+
+/// Song package upload: a zip of one song folder. `song_id` defaults to
+/// the folder name in the zip; `name` is the placeholder chart name when
+/// `insert_chart` is set.
+#[derive(FromForm)]
+pub(super) struct AdminSongUploadForm<'r> {
+    pub(super) package: TempFile<'r>,
+    pub(super) song_id: Option<String>,
+    pub(super) name: Option<String>,
+    #[field(default = false)]
+    pub(super) insert_chart: bool,
+    #[field(default = false)]
+    pub(super) overwrite: bool,
+}
+
+/// Multi-item present delivered on creation, accepted as JSON or form data.
+#[derive(Debug, Deserialize, FromForm)]
+pub(super) struct AdminPresentCreatePayload {
+    pub(super) present_id: String,
+    pub(super) expire_ts: Option<String>,
+    pub(super) description: Option<String>,
+    pub(super) items: Vec<AdminPresentItemPayload>,
+    /// Recipients; ignored when `all_users` is set.
+    #[serde(default)]
+    pub(super) user_ids: Vec<i32>,
+    #[serde(default)]
+    pub(super) all_users: bool,
+    #[serde(default)]
+    pub(super) web_only: bool,
 }
 
 #[derive(Debug, Deserialize)]
@@ -380,6 +880,49 @@ pub(super) struct AdminRedeemDeletePayload {
     pub(super) code: String,
 }
 
+#[derive(Debug, Deserialize)]
+pub(super) struct AdminRedeemBatchPayload {
+    pub(super) batch_id: String,
+    pub(super) description: Option<String>,
+    /// 0 single-use, 1 multi-use, 2 fragment
+    pub(super) redeem_type: i32,
+    pub(super) count: i32,
+    pub(super) items: Vec<AdminPresentItemPayload>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(super) struct AdminRedeemBatchDisablePayload {
+    pub(super) batch_id: String,
+    pub(super) disabled: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub(super) struct AdminEventPayload {
+    pub(super) event_id: String,
+    pub(super) name: String,
+    pub(super) description: Option<String>,
+    pub(super) start_ts: Option<String>,
+    pub(super) end_ts: Option<String>,
+    pub(super) charts: String,
+    #[serde(default)]
+    pub(super) reward_tiers: String,
+    #[serde(default)]
+    pub(super) community_milestones: String,
+    pub(super) available: Option<bool>,
+}
+
+/// Override a game constant, or go back to its default when `value` is null.
+#[derive(Debug, Deserialize)]
+pub(super) struct AdminGameConstantPayload {
+    pub(super) name: String,
+    pub(super) value: Option<f64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(super) struct AdminEventDeletePayload {
+    pub(super) event_id: String,
+}
+
 #[derive(Debug, Deserialize)]
 pub(super) struct AdminUserScoreQuery {
     pub(super) user_id: Option<i32>,
@@ -407,11 +950,25 @@ pub(super) struct AdminSessionResponse {
     pub(super) user: Option<AdminUserSummary>,
 }
 
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct AdminApiTokenResponse {
+    pub(super) user_id: i32,
+    pub(super) token: String,
+}
+
 #[derive(Debug, Clone)]
 pub(super) struct WebSession {
     pub(super) user: AdminUserSummary,
     pub(super) role: i8,
     pub(super) can_edit_chart_constants: bool,
+    pub(super) powers: Vec<String>,
+}
+
+impl WebSession {
+    pub(super) fn has_power(&self, power: &str) -> bool {
+        self.powers.iter().any(|granted| granted == power)
+    }
 }
 
 #[derive(Debug, Serialize)]
@@ -424,6 +981,16 @@ pub(super) struct AdminDashboardApiResponse {
     pub(super) present_count: i64,
     pub(super) alert_count: i64,
     pub(super) recent_ops: Vec<RecentOpView>,
+    pub(super) song_file_refresh: Option<SongFileRefreshView>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct SongFileRefreshView {
+    pub(super) refreshed_at: String,
+    pub(super) hashed: usize,
+    pub(super) unchanged: usize,
+    pub(super) removed: usize,
 }
 
 #[derive(Debug, Serialize)]
@@ -435,6 +1002,17 @@ pub(super) struct AdminPageResponse<T> {
     pub(super) page_size: i64,
 }
 
+/// Paging query shared by the list endpoints: `page`, `page_size` (also
+/// accepted as `per_page`) and an endpoint specific `sort` key.
+#[derive(Debug, Default, FromForm)]
+pub(super) struct PageQuery {
+    pub(super) page: Option<i64>,
+    #[field(name = "page_size")]
+    #[field(name = "per_page")]
+    pub(super) page_size: Option<i64>,
+    pub(super) sort: Option<String>,
+}
+
 // Database row structs
 
 #[derive(FromRow)]
@@ -452,6 +1030,7 @@ pub(super) struct UserListDbRow {
     pub(super) time_played: Option<i64>,
     pub(super) password: Option<String>,
     pub(super) ban_flag: Option<String>,
+    pub(super) is_shadow_banned: i8,
     pub(super) is_admin: i64,
     pub(super) can_edit_chart_constants: i64,
 }
@@ -467,6 +1046,22 @@ pub(super) struct ChartDbRow {
     pub(super) rating_etr: Option<i32>,
 }
 
+#[derive(FromRow)]
+pub(super) struct ChartAnalyticsDbRow {
+    pub(super) song_id: String,
+    pub(super) difficulty: i32,
+    pub(super) name: Option<String>,
+    pub(super) rating: Option<i32>,
+    pub(super) player_count: i32,
+    pub(super) avg_score: f64,
+    pub(super) avg_accuracy: f64,
+    pub(super) clear_rate: f64,
+    pub(super) score_distribution: String,
+    pub(super) perceived_constant: Option<f64>,
+    pub(super) sample_count: i32,
+    pub(super) computed_at: i64,
+}
+
 #[derive(FromRow)]
 pub(super) struct ItemDbRow {
     pub(super) item_id: String,
@@ -474,6 +1069,23 @@ pub(super) struct ItemDbRow {
     pub(super) is_available: Option<i8>,
 }
 
+#[derive(FromRow)]
+pub(super) struct PresentDbRow {
+    pub(super) present_id: String,
+    pub(super) description: Option<String>,
+    pub(super) expire_ts: Option<i64>,
+    pub(super) is_web_only: i8,
+    pub(super) recipients: i64,
+}
+
+#[derive(FromRow)]
+pub(super) struct PresentItemDbRow {
+    pub(super) present_id: String,
+    pub(super) item_id: String,
+    pub(super) r#type: String,
+    pub(super) amount: Option<i32>,
+}
+
 #[derive(FromRow)]
 pub(super) struct PurchaseDbRow {
     pub(super) purchase_name: String,
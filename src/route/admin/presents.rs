@@ -1,35 +1,34 @@
-//! Presents (gift rewards) and redeem codes: creation, deletion, delivery and
-//! lookup of redeem-code users.
+//! Presents (gift rewards) and redeem codes: creation, deletion, delivery,
+//! redeem-code batches and lookup of redeem-code users.
 
-use chrono::{Local, NaiveDateTime, TimeZone};
-use rand::Rng;
-use rocket::http::CookieJar;
+use rocket::form::Form;
 use rocket::serde::json::Json;
 use rocket::{delete, get, post, State};
+use std::collections::HashMap;
 
 use crate::error::ArcError;
+use crate::model::{PresentItem, RedeemItem, RedeemKind};
 use crate::route::common::{success_return, RouteResult};
+use crate::service::present::PresentRecipients;
+use crate::service::purchase::generate_redeem_code;
+use crate::service::push::PushTarget;
+use crate::service::{NotificationService, PresentService, PurchaseService};
+use crate::utils::sql_placeholders;
 use crate::DbPool;
 
-use super::helpers::{clean_optional_payload_text, resolve_admin_user};
+use super::helpers::{
+    clamp_page, clean_optional_payload_text, format_timestamp, like_filter,
+    normalize_admin_required_text, page_response, parse_admin_datetime, resolve_admin_user,
+};
 use super::models::{
-    AdminActionResponse, AdminPresentDeletePayload, AdminPresentDeliverPayload,
-    AdminPresentPayload, AdminRedeemDeletePayload, AdminRedeemPayload, AdminRedeemUsersResponse,
-    AdminUserDbSummary, AdminUserSummary,
+    AdminActionResponse, AdminPageResponse, AdminPresentCreatePayload, AdminPresentDeletePayload,
+    AdminPresentDeliverPayload, AdminPresentPayload, AdminRedeemBatchDisablePayload,
+    AdminRedeemBatchPayload, AdminRedeemBatchView, AdminRedeemDeletePayload, AdminRedeemItemView,
+    AdminRedeemPayload, AdminRedeemUsersResponse, AdminUserDbSummary, AdminUserSummary,
+    CsvResponse, PageQuery, PresentDbRow, PresentItemDbRow, PresentRowView,
 };
-use super::session::require_admin_api;
-
-fn normalize_admin_required_text(
-    raw: &str,
-    field: &str,
-    max_len: usize,
-) -> Result<String, ArcError> {
-    let value = raw.trim();
-    if value.is_empty() {
-        return Err(ArcError::input(format!("{field} 不能为空")));
-    }
-    Ok(value.chars().take(max_len).collect())
-}
+use super::session::{require_admin_api, PowerGuard, WebAuth};
+use super::SELECT_POWER;
 
 fn parse_admin_amount(raw: Option<&str>, field: &str) -> Result<i32, ArcError> {
     let value = raw.map(str::trim).filter(|value| !value.is_empty());
@@ -46,28 +45,6 @@ fn parse_admin_amount(raw: Option<&str>, field: &str) -> Result<i32, ArcError> {
     Ok(amount)
 }
 
-fn parse_admin_expire_ts(raw: Option<&str>) -> Result<i64, ArcError> {
-    let value = raw.map(str::trim).unwrap_or("");
-    if value.is_empty() {
-        return Err(ArcError::input("expire_ts 不能为空"));
-    }
-    let naive = NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M")
-        .map_err(|_| ArcError::input("expire_ts 时间格式错误"))?;
-    let local_dt = Local
-        .from_local_datetime(&naive)
-        .single()
-        .ok_or_else(|| ArcError::input("expire_ts 时间非法"))?;
-    Ok(local_dt.timestamp_millis())
-}
-
-fn random_redeem_code() -> String {
-    const CHARS: &[u8] = b"AaBbCcDdEeFfGgHhIiJjKkLlMmNnOoPpQqRrSsTtUuVvWwXxYyZz0123456789";
-    let mut rng = rand::thread_rng();
-    (0..10)
-        .map(|_| CHARS[rng.gen_range(0..CHARS.len())] as char)
-        .collect()
-}
-
 async fn require_admin_item_exists(
     item_id: &str,
     item_type: &str,
@@ -87,6 +64,91 @@ async fn require_admin_item_exists(
     Ok(())
 }
 
+async fn load_admin_presents(
+    q: Option<&str>,
+    sort: Option<&str>,
+    page: i64,
+    page_size: i64,
+    pool: &DbPool,
+) -> AdminPageResponse<PresentRowView> {
+    let (where_sql, binds) = like_filter(q, &["present_id", "COALESCE(description, '')"]);
+
+    let count_sql = format!("SELECT COUNT(*) FROM present{where_sql}");
+    let mut count_query = sqlx::query_scalar::<_, i64>(&count_sql);
+    for value in &binds {
+        count_query = count_query.bind(value);
+    }
+    let total = count_query.fetch_one(pool).await.unwrap_or(0);
+    let (page, offset) = clamp_page(page, page_size, total);
+
+    let order_sql = match sort {
+        Some("present_id") => "present_id ASC",
+        Some("expire") => "expire_ts ASC, present_id ASC",
+        _ => "expire_ts DESC, present_id ASC",
+    };
+    let row_sql = format!(
+        "SELECT present_id, description, expire_ts, is_web_only,
+                (SELECT COUNT(*) FROM user_present up
+                 WHERE up.present_id = present.present_id) AS recipients
+         FROM present{where_sql}
+         ORDER BY {order_sql}
+         LIMIT ? OFFSET ?"
+    );
+    let mut present_query = sqlx::query_as::<_, PresentDbRow>(&row_sql);
+    for value in &binds {
+        present_query = present_query.bind(value);
+    }
+    let present_rows = present_query
+        .bind(page_size)
+        .bind(offset)
+        .fetch_all(pool)
+        .await
+        .unwrap_or_default();
+
+    let mut item_summaries: HashMap<String, Vec<String>> = HashMap::new();
+    if !present_rows.is_empty() {
+        let item_sql = format!(
+            "SELECT *
+             FROM present_item
+             WHERE present_id IN ({})
+             ORDER BY present_id ASC, item_id ASC, type ASC",
+            sql_placeholders(present_rows.len())
+        );
+        let mut item_query = sqlx::query_as::<_, PresentItemDbRow>(&item_sql);
+        for present in &present_rows {
+            item_query = item_query.bind(&present.present_id);
+        }
+        for item in item_query.fetch_all(pool).await.unwrap_or_default() {
+            item_summaries
+                .entry(item.present_id.clone())
+                .or_default()
+                .push(format!(
+                    "{}:{}x{}",
+                    item.item_id,
+                    item.r#type,
+                    item.amount.unwrap_or(1)
+                ));
+        }
+    }
+
+    let rows = present_rows
+        .into_iter()
+        .map(|present| PresentRowView {
+            item_summary: item_summaries
+                .remove(&present.present_id)
+                .map(|items| items.join(", "))
+                .unwrap_or_else(|| "-".to_string()),
+            present_id: present.present_id,
+            description: present.description.unwrap_or_default(),
+            expire_at: format_timestamp(present.expire_ts),
+            web_only: present.is_web_only != 0,
+            recipients: present.recipients,
+        })
+        .collect();
+
+    page_response(rows, total, page, page_size)
+}
+
 async fn create_admin_present(
     payload: &AdminPresentPayload,
     pool: &DbPool,
@@ -96,7 +158,7 @@ async fn create_admin_present(
     let item_id = normalize_admin_required_text(&payload.item_id, "item_id", 200)?;
     let item_type = normalize_admin_required_text(&payload.item_type, "type", 200)?;
     let amount = parse_admin_amount(payload.amount.as_deref(), "amount")?;
-    let expire_ts = parse_admin_expire_ts(payload.expire_ts.as_deref())?;
+    let expire_ts = parse_admin_datetime(payload.expire_ts.as_deref(), "expire_ts")?;
     require_admin_item_exists(&item_id, &item_type, pool).await?;
 
     let exists = sqlx::query_scalar!(
@@ -115,10 +177,11 @@ async fn create_admin_present(
         .await
         .map_err(|err| ArcError::input(format!("事务创建失败: {err}")))?;
     sqlx::query!(
-        "INSERT INTO present (present_id, expire_ts, description) VALUES (?, ?, ?)",
+        "INSERT INTO present (present_id, expire_ts, description, is_web_only) VALUES (?, ?, ?, ?)",
         &present_id,
         expire_ts,
-        description
+        description,
+        payload.web_only
     )
     .execute(&mut *tx)
     .await
@@ -144,6 +207,67 @@ async fn create_admin_present(
     })
 }
 
+async fn publish_admin_present(
+    payload: &AdminPresentCreatePayload,
+    present_service: &PresentService,
+    notification_service: &NotificationService,
+) -> Result<AdminActionResponse, ArcError> {
+    let present_id = normalize_admin_required_text(&payload.present_id, "present_id", 200)?;
+    let description = super::helpers::normalize_optional_text(payload.description.as_deref(), 200);
+    let expire_ts = parse_admin_datetime(payload.expire_ts.as_deref(), "expire_ts")?;
+    let items = payload
+        .items
+        .iter()
+        .map(|item| {
+            Ok(PresentItem {
+                present_id: present_id.clone(),
+                item_id: normalize_admin_required_text(&item.item_id, "item_id", 200)?,
+                item_type: normalize_admin_required_text(&item.item_type, "type", 200)?,
+                amount: item.amount.unwrap_or(1),
+            })
+        })
+        .collect::<Result<Vec<_>, ArcError>>()?;
+    let recipients = if payload.all_users {
+        PresentRecipients::AllUsers
+    } else {
+        PresentRecipients::Users(payload.user_ids.clone())
+    };
+
+    let delivered = present_service
+        .publish_present(
+            &present_id,
+            Some(expire_ts),
+            &description,
+            &items,
+            &recipients,
+            payload.web_only,
+        )
+        .await
+        .map_err(|err| ArcError::input(format!("新增奖励失败: {err}")))?;
+
+    if delivered > 0 {
+        match &recipients {
+            PresentRecipients::AllUsers => {
+                notification_service
+                    .notify_present_delivered(PushTarget::AllUsers, &present_id)
+                    .await?;
+            }
+            PresentRecipients::Users(users) => {
+                for user_id in users {
+                    notification_service
+                        .notify_present_delivered(PushTarget::User(*user_id), &present_id)
+                        .await?;
+                }
+            }
+        }
+    }
+
+    Ok(AdminActionResponse {
+        message: format!("奖励已新增并分发给 {delivered} 名玩家"),
+        affected_rows: delivered,
+    })
+}
+
 async fn delete_admin_present(
     payload: &AdminPresentDeletePayload,
     pool: &DbPool,
@@ -190,6 +314,7 @@ async fn delete_admin_present(
 async fn deliver_admin_present(
     payload: &AdminPresentDeliverPayload,
     pool: &DbPool,
+    notification_service: &NotificationService,
 ) -> Result<AdminActionResponse, ArcError> {
     let present_id = normalize_admin_required_text(&payload.present_id, "present_id", 200)?;
     let exists = sqlx::query_scalar!(
@@ -223,6 +348,9 @@ async fn deliver_admin_present(
         tx.commit()
             .await
             .map_err(|err| ArcError::input(format!("分发奖励失败: {err}")))?;
+        notification_service
+            .notify_present_delivered(PushTarget::AllUsers, &present_id)
+            .await?;
         done.rows_affected()
     } else {
         let user = resolve_admin_user(
@@ -232,7 +360,7 @@ async fn deliver_admin_present(
             pool,
         )
         .await?;
-        sqlx::query!(
+        let affected_rows = sqlx::query!(
             "INSERT IGNORE INTO user_present (user_id, present_id) VALUES (?, ?)",
             user.user_id,
             &present_id
@@ -240,7 +368,13 @@ async fn deliver_admin_present(
         .execute(pool)
         .await
         .map_err(|err| ArcError::input(format!("分发奖励失败: {err}")))?
-        .rows_affected()
+        .rows_affected();
+        if affected_rows > 0 {
+            notification_service
+                .notify_present_delivered(PushTarget::User(user.user_id), &present_id)
+                .await?;
+        }
+        affected_rows
     };
 
     Ok(AdminActionResponse {
@@ -266,8 +400,10 @@ async fn create_admin_redeem(
     if code.is_none() && random_amount <= 0 {
         return Err(ArcError::input("需要提供 code 或 random_amount"));
     }
-    if !(0..=1).contains(&payload.redeem_type) {
-        return Err(ArcError::input("redeem_type 必须是 0 或 1"));
+    let kind = RedeemKind::from_i32(payload.redeem_type)
+        .ok_or_else(|| ArcError::input("redeem_type 必须是 0、1 或 2"))?;
+    if kind == RedeemKind::Fragment && item_type != "fragment" {
+        return Err(ArcError::input("碎片兑换码只能发放 fragment"));
     }
 
     let mut codes = Vec::new();
@@ -291,7 +427,7 @@ async fn create_admin_redeem(
             return Err(ArcError::input("random_amount 必须在 1-1000 之间"));
         }
         while codes.len() < random_amount as usize {
-            let code = random_redeem_code();
+            let code = generate_redeem_code();
             let exists = sqlx::query_scalar!(
                 "SELECT COUNT(*) as `count!: i64` FROM redeem WHERE code = ?",
                 code
@@ -388,6 +524,46 @@ async fn delete_admin_redeem(
     })
 }
 
+async fn create_admin_redeem_batch(
+    payload: &AdminRedeemBatchPayload,
+    purchase_service: &PurchaseService,
+) -> Result<AdminActionResponse, ArcError> {
+    let batch_id = normalize_admin_required_text(&payload.batch_id, "batch_id", 64)?;
+    let description = super::helpers::normalize_optional_text(payload.description.as_deref(), 200);
+    let kind = RedeemKind::from_i32(payload.redeem_type)
+        .ok_or_else(|| ArcError::input("redeem_type 必须是 0、1 或 2"))?;
+    if payload.count <= 0 {
+        return Err(ArcError::input("count 必须大于 0"));
+    }
+    let items = payload
+        .items
+        .iter()
+        .map(|item| {
+            Ok(RedeemItem {
+                item_id: normalize_admin_required_text(&item.item_id, "item_id", 200)?,
+                item_type: normalize_admin_required_text(&item.item_type, "type", 200)?,
+                amount: item.amount.unwrap_or(1),
+            })
+        })
+        .collect::<Result<Vec<_>, ArcError>>()?;
+
+    let codes = purchase_service
+        .create_redeem_batch(
+            &batch_id,
+            &description,
+            kind,
+            payload.count as usize,
+            &items,
+        )
+        .await
+        .map_err(|err| ArcError::input(format!("生成兑换码失败: {err}")))?;
+
+    Ok(AdminActionResponse {
+        message: format!("兑换码批次 {batch_id} 已生成: {} 个", codes.len()),
+        affected_rows: codes.len() as u64,
+    })
+}
+
 async fn load_admin_redeem_users(
     code: Option<&str>,
     pool: &DbPool,
@@ -437,21 +613,56 @@ async fn load_admin_redeem_users(
 pub(super) async fn admin_api_present_create(
     payload: Json<AdminPresentPayload>,
     pool: &State<DbPool>,
-    cookies: &CookieJar<'_>,
+    auth: WebAuth<'_>,
 ) -> RouteResult<AdminActionResponse> {
-    require_admin_api(cookies, pool.inner()).await?;
+    require_admin_api(auth, pool.inner()).await?;
     Ok(success_return(
         create_admin_present(&payload, pool.inner()).await?,
     ))
 }
 
+/// Create a multi-item present and deliver it in one step (JSON)
+#[post(
+    "/api/admin-actions/presents/create",
+    format = "json",
+    data = "<payload>"
+)]
+pub(super) async fn admin_api_present_publish(
+    payload: Json<AdminPresentCreatePayload>,
+    pool: &State<DbPool>,
+    present_service: &State<PresentService>,
+    notification_service: &State<NotificationService>,
+    auth: WebAuth<'_>,
+) -> RouteResult<AdminActionResponse> {
+    require_admin_api(auth, pool.inner()).await?;
+    Ok(success_return(
+        publish_admin_present(&payload, present_service, notification_service).await?,
+    ))
+}
+
+/// Form-encoded variant of [`admin_api_present_publish`] for scripts;
+/// items are sent as `items[0].item_id`, `items[0].item_type`, ...
+#[post("/presents/create", data = "<payload>")]
+pub(super) async fn admin_present_publish_form(
+    payload: Form<AdminPresentCreatePayload>,
+    pool: &State<DbPool>,
+    present_service: &State<PresentService>,
+    notification_service: &State<NotificationService>,
+    auth: WebAuth<'_>,
+) -> RouteResult<AdminActionResponse> {
+    require_admin_api(auth, pool.inner()).await?;
+    Ok(success_return(
+        publish_admin_present(&payload, present_service, notification_service).await?,
+    ))
+}
+
 #[delete("/api/admin-actions/presents", format = "json", data = "<payload>")]
 pub(super) async fn admin_api_present_delete(
     payload: Json<AdminPresentDeletePayload>,
     pool: &State<DbPool>,
-    cookies: &CookieJar<'_>,
+    auth: WebAuth<'_>,
 ) -> RouteResult<AdminActionResponse> {
-    require_admin_api(cookies, pool.inner()).await?;
+    require_admin_api(auth, pool.inner()).await?;
     Ok(success_return(
         delete_admin_present(&payload, pool.inner()).await?,
     ))
@@ -465,11 +676,12 @@ pub(super) async fn admin_api_present_delete(
 pub(super) async fn admin_api_present_deliver(
     payload: Json<AdminPresentDeliverPayload>,
     pool: &State<DbPool>,
-    cookies: &CookieJar<'_>,
+    notification_service: &State<NotificationService>,
+    auth: WebAuth<'_>,
 ) -> RouteResult<AdminActionResponse> {
-    require_admin_api(cookies, pool.inner()).await?;
+    require_admin_api(auth, pool.inner()).await?;
     Ok(success_return(
-        deliver_admin_present(&payload, pool.inner()).await?,
+        deliver_admin_present(&payload, pool.inner(), notification_service.inner()).await?,
     ))
 }
 
@@ -477,9 +689,9 @@ pub(super) async fn admin_api_present_deliver(
 pub(super) async fn admin_api_redeem_create(
     payload: Json<AdminRedeemPayload>,
     pool: &State<DbPool>,
-    cookies: &CookieJar<'_>,
+    auth: WebAuth<'_>,
 ) -> RouteResult<AdminActionResponse> {
-    require_admin_api(cookies, pool.inner()).await?;
+    require_admin_api(auth, pool.inner()).await?;
     Ok(success_return(
         create_admin_redeem(&payload, pool.inner()).await?,
     ))
@@ -489,21 +701,143 @@ pub(super) async fn admin_api_redeem_create(
 pub(super) async fn admin_api_redeem_delete(
     payload: Json<AdminRedeemDeletePayload>,
     pool: &State<DbPool>,
-    cookies: &CookieJar<'_>,
+    auth: WebAuth<'_>,
 ) -> RouteResult<AdminActionResponse> {
-    require_admin_api(cookies, pool.inner()).await?;
+    require_admin_api(auth, pool.inner()).await?;
     Ok(success_return(
         delete_admin_redeem(&payload, pool.inner()).await?,
     ))
 }
 
+/// Generate a batch of redeem codes sharing the same items
+#[post(
+    "/api/admin-actions/redeem-batches",
+    format = "json",
+    data = "<payload>"
+)]
+pub(super) async fn admin_api_redeem_batch_create(
+    payload: Json<AdminRedeemBatchPayload>,
+    pool: &State<DbPool>,
+    purchase_service: &State<PurchaseService>,
+    auth: WebAuth<'_>,
+) -> RouteResult<AdminActionResponse> {
+    require_admin_api(auth, pool.inner()).await?;
+    Ok(success_return(
+        create_admin_redeem_batch(&payload, purchase_service).await?,
+    ))
+}
+
+/// Disable (or re-enable) every code of a redeem batch
+#[post(
+    "/api/admin-actions/redeem-batches/disable",
+    format = "json",
+    data = "<payload>"
+)]
+pub(super) async fn admin_api_redeem_batch_disable(
+    payload: Json<AdminRedeemBatchDisablePayload>,
+    pool: &State<DbPool>,
+    purchase_service: &State<PurchaseService>,
+    auth: WebAuth<'_>,
+) -> RouteResult<AdminActionResponse> {
+    require_admin_api(auth, pool.inner()).await?;
+    let batch_id = normalize_admin_required_text(&payload.batch_id, "batch_id", 64)?;
+    purchase_service
+        .set_redeem_batch_disabled(&batch_id, payload.disabled)
+        .await
+        .map_err(|err| ArcError::input(format!("更新兑换码批次失败: {err}")))?;
+    Ok(success_return(AdminActionResponse {
+        message: if payload.disabled {
+            format!("兑换码批次 {batch_id} 已停用")
+        } else {
+            format!("兑换码批次 {batch_id} 已启用")
+        },
+        affected_rows: 1,
+    }))
+}
+
+#[get("/api/presents?<q>&<paging..>")]
+pub(super) async fn admin_api_presents(
+    q: Option<&str>,
+    paging: PageQuery,
+    pool: &State<DbPool>,
+    guard: PowerGuard,
+) -> RouteResult<AdminPageResponse<PresentRowView>> {
+    guard.require(SELECT_POWER)?;
+    let (page, page_size) = paging.normalized();
+    Ok(success_return(
+        load_admin_presents(q, paging.sort(), page, page_size, pool.inner()).await,
+    ))
+}
+
+#[get("/api/redeem-batches")]
+pub(super) async fn admin_api_redeem_batches(
+    purchase_service: &State<PurchaseService>,
+    guard: PowerGuard,
+) -> RouteResult<Vec<AdminRedeemBatchView>> {
+    guard.require(SELECT_POWER)?;
+    let batches = purchase_service
+        .list_redeem_batches()
+        .await
+        .map_err(|err| ArcError::input(format!("查询兑换码批次失败: {err}")))?
+        .into_iter()
+        .map(|batch| AdminRedeemBatchView {
+            batch_id: batch.batch_id,
+            description: batch.description,
+            redeem_type: batch.kind.as_i32(),
+            code_count: batch.code_count,
+            redeemed_count: batch.redeemed_count,
+            disabled: batch.is_disabled,
+            created_at: format_timestamp(Some(batch.created_at)),
+            items: batch
+                .items
+                .into_iter()
+                .map(|item| AdminRedeemItemView {
+                    item_id: item.item_id,
+                    item_type: item.item_type,
+                    amount: item.amount,
+                })
+                .collect(),
+        })
+        .collect();
+    Ok(success_return(batches))
+}
+
+/// Download the codes of a redeem batch as CSV
+#[get("/api/redeem-batches/export?<batch_id>")]
+pub(super) async fn admin_api_redeem_batch_export(
+    batch_id: &str,
+    purchase_service: &State<PurchaseService>,
+    guard: PowerGuard,
+) -> Result<CsvResponse, ArcError> {
+    guard.require(SELECT_POWER)?;
+    let batch_id = batch_id.trim();
+    let body = purchase_service
+        .export_redeem_batch_csv(batch_id)
+        .await
+        .map_err(|err| ArcError::input(format!("导出兑换码失败: {err}")))?;
+    let file_stem: String = batch_id
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    Ok(CsvResponse {
+        file_name: format!("redeem_{file_stem}.csv"),
+        body,
+    })
+}
+
 #[get("/api/redeem-users?<code>")]
 pub(super) async fn admin_api_redeem_users(
     code: Option<&str>,
     pool: &State<DbPool>,
-    cookies: &CookieJar<'_>,
+    guard: PowerGuard,
 ) -> RouteResult<AdminRedeemUsersResponse> {
-    require_admin_api(cookies, pool.inner()).await?;
+    guard.require(SELECT_POWER)?;
     Ok(success_return(
         load_admin_redeem_users(code, pool.inner()).await?,
     ))
@@ -0,0 +1,102 @@
+//! Recent game API requests from the `api_log` access log, for tracing a
+//! player's client issues.
+
+use rocket::{get, State};
+
+use crate::error::ArcError;
+use crate::route::common::{success_return, RouteResult};
+use crate::{DbPool, ReadPool};
+
+use super::helpers::{clamp_page, clean_query_value, format_timestamp, page_response};
+use super::models::{AdminApiLogRowView, AdminPageResponse, PageQuery};
+use super::session::PowerGuard;
+use super::SELECT_POWER;
+
+async fn load_admin_api_log(
+    q: Option<&str>,
+    path: Option<&str>,
+    status: Option<u16>,
+    page: i64,
+    page_size: i64,
+    pool: &DbPool,
+) -> Result<AdminPageResponse<AdminApiLogRowView>, ArcError> {
+    let q = clean_query_value(q);
+    let user_id = q.as_deref().and_then(|query| query.parse::<i32>().ok());
+    let like = q.map(|query| format!("%{query}%"));
+    let path_like = clean_query_value(path).map(|path| format!("%{path}%"));
+    let total = sqlx::query_scalar!(
+        "SELECT COUNT(*) FROM api_log l
+         LEFT JOIN user u ON u.user_id = l.user_id
+         WHERE (? IS NULL OR l.user_id = ? OR u.name LIKE ? OR u.user_code LIKE ?)
+           AND (? IS NULL OR l.path LIKE ?)
+           AND (? IS NULL OR l.status = ?)",
+        like,
+        user_id,
+        like,
+        like,
+        path_like,
+        path_like,
+        status,
+        status
+    )
+    .fetch_one(pool)
+    .await
+    .map_err(|err| ArcError::input(format!("查询请求日志失败: {err}")))?;
+    let (page, offset) = clamp_page(page, page_size, total);
+
+    let rows = sqlx::query!(
+        "SELECT l.log_id, l.created_at, l.method, l.path, l.user_id, u.name,
+                l.status, l.latency_ms, l.ip
+         FROM api_log l
+         LEFT JOIN user u ON u.user_id = l.user_id
+         WHERE (? IS NULL OR l.user_id = ? OR u.name LIKE ? OR u.user_code LIKE ?)
+           AND (? IS NULL OR l.path LIKE ?)
+           AND (? IS NULL OR l.status = ?)
+         ORDER BY l.log_id DESC
+         LIMIT ? OFFSET ?",
+        like,
+        user_id,
+        like,
+        like,
+        path_like,
+        path_like,
+        status,
+        status,
+        page_size,
+        offset
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|err| ArcError::input(format!("查询请求日志失败: {err}")))?
+    .into_iter()
+    .map(|row| AdminApiLogRowView {
+        log_id: row.log_id,
+        created_at: format_timestamp(Some(row.created_at)),
+        method: row.method,
+        path: row.path,
+        user_id: row.user_id,
+        name: row.name,
+        status: row.status,
+        latency_ms: row.latency_ms,
+        ip: row.ip,
+    })
+    .collect();
+
+    Ok(page_response(rows, total, page, page_size))
+}
+
+#[get("/api/request-log?<q>&<path>&<status>&<paging..>")]
+pub(super) async fn admin_api_request_log(
+    q: Option<&str>,
+    path: Option<&str>,
+    status: Option<u16>,
+    paging: PageQuery,
+    pool: &State<ReadPool>,
+    guard: PowerGuard,
+) -> RouteResult<AdminPageResponse<AdminApiLogRowView>> {
+    guard.require(SELECT_POWER)?;
+    let (page, page_size) = paging.normalized();
+    Ok(success_return(
+        load_admin_api_log(q, path, status, page, page_size, pool.inner()).await?,
+    ))
+}
@@ -1,22 +1,124 @@
-//! Score visualisation: B30/AP30/Sex30 score images and the per-chart
-//! leaderboard.
+//! Score visualisation: B30/AP30/Sex30 score images, the per-chart
+//! leaderboard, chart difficulty analytics and uploaded replays.
 
-use rocket::http::CookieJar;
 use rocket::{get, State};
 
 use crate::error::ArcError;
-use crate::route::common::{success_return, RouteResult};
+use crate::route::common::{success_return, ReplayFile, RouteResult};
 use crate::service::{
-    generate_score_image_png, generate_score_images, parse_score_image_mode, ScoreImageMode,
+    generate_score_image_png, generate_score_images, parse_score_image_mode, ReplayService,
+    ScoreImageMode,
 };
-use crate::DbPool;
+use crate::{DbPool, ReadPool};
 
 use super::helpers::clean_optional_payload_text;
-use super::helpers::format_timestamp;
+use super::helpers::{clamp_page, clean_query_value, filter_sql, format_timestamp, page_response};
 use super::models::{
-    AdminChartTopResponse, AdminScoreRowView, PngResponse, ScoreImageView, ScoreImagesResponse,
+    AdminChartTopResponse, AdminPageResponse, AdminReplayRowView, AdminScoreRowView,
+    ChartAnalyticsDbRow, ChartAnalyticsRowView, PageQuery, PngResponse, ScoreImageView,
+    ScoreImagesResponse,
 };
-use super::session::{require_web_session, resolve_score_image_user};
+use super::session::{require_web_session, resolve_score_image_user, PowerGuard, WebAuth};
+use super::{
+    SELECT_POWER, SELECT_SONG_RANK_POWER, SELECT_SONG_RANK_TOP_POWER, SONG_RANK_TOP_LIMIT,
+};
+
+fn round2(value: f64) -> f64 {
+    (value * 100.0).round() / 100.0
+}
+
+fn chart_analytics_row_to_view(row: ChartAnalyticsDbRow) -> ChartAnalyticsRowView {
+    let constant = row
+        .rating
+        .filter(|rating| *rating > 0)
+        .map(|rating| rating as f64 / 10.0);
+    let deviation = constant
+        .zip(row.perceived_constant)
+        .map(|(constant, perceived)| round2(perceived - constant));
+    ChartAnalyticsRowView {
+        song_id: row.song_id,
+        name_en: row.name.unwrap_or_default(),
+        difficulty: row.difficulty,
+        constant,
+        player_count: row.player_count,
+        avg_score: row.avg_score.round() as i64,
+        avg_accuracy: round2(row.avg_accuracy * 100.0),
+        clear_rate: round2(row.clear_rate * 100.0),
+        score_distribution: serde_json::from_str(&row.score_distribution).unwrap_or_default(),
+        perceived_constant: row.perceived_constant,
+        deviation,
+        sample_count: row.sample_count,
+        computed_at: format_timestamp(Some(row.computed_at)),
+    }
+}
+
+async fn load_admin_chart_analytics(
+    q: Option<&str>,
+    sort: Option<&str>,
+    min_players: i32,
+    page: i64,
+    page_size: i64,
+    pool: &DbPool,
+) -> AdminPageResponse<ChartAnalyticsRowView> {
+    let mut filters = vec!["player_count >= ?"];
+    let like = clean_query_value(q).map(|query| format!("%{query}%"));
+    if like.is_some() {
+        filters.push("(song_id LIKE ? OR name LIKE ?)");
+    }
+    let where_sql = filter_sql(&filters);
+    let order_sql = match sort {
+        Some("players") => "player_count DESC, song_id ASC, difficulty ASC",
+        Some("song") => "song_id ASC, difficulty ASC",
+        _ => "deviation IS NULL, ABS(deviation) DESC, player_count DESC",
+    };
+    let base_sql = "SELECT ca.song_id, ca.difficulty, c.name,
+                CASE ca.difficulty
+                    WHEN 0 THEN c.rating_pst
+                    WHEN 1 THEN c.rating_prs
+                    WHEN 2 THEN c.rating_ftr
+                    WHEN 3 THEN c.rating_byn
+                    WHEN 4 THEN c.rating_etr
+                END AS rating,
+                ca.player_count, ca.avg_score, ca.avg_accuracy, ca.clear_rate,
+                ca.score_distribution, ca.perceived_constant, ca.sample_count, ca.computed_at
+         FROM chart_analytics ca
+         LEFT JOIN chart c ON c.song_id = ca.song_id";
+
+    let count_sql = format!("SELECT COUNT(*) FROM ({base_sql}) analytics{where_sql}");
+    let mut count_query = sqlx::query_scalar::<_, i64>(&count_sql).bind(min_players);
+    if let Some(like) = &like {
+        count_query = count_query.bind(like).bind(like);
+    }
+    let total = count_query.fetch_one(pool).await.unwrap_or(0);
+    let (page, offset) = clamp_page(page, page_size, total);
+
+    let row_sql = format!(
+        "SELECT song_id, difficulty, name, rating, player_count, avg_score, avg_accuracy,
+                clear_rate, score_distribution, perceived_constant, sample_count, computed_at
+         FROM (
+             SELECT analytics.*,
+                    CASE WHEN rating > 0 THEN perceived_constant - rating / 10.0 END AS deviation
+             FROM ({base_sql}) analytics
+         ) analytics{where_sql}
+         ORDER BY {order_sql}
+         LIMIT ? OFFSET ?"
+    );
+    let mut rows_query = sqlx::query_as::<_, ChartAnalyticsDbRow>(&row_sql).bind(min_players);
+    if let Some(like) = &like {
+        rows_query = rows_query.bind(like).bind(like);
+    }
+    let rows = rows_query
+        .bind(page_size)
+        .bind(offset)
+        .fetch_all(pool)
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .map(chart_analytics_row_to_view)
+        .collect();
+
+    page_response(rows, total, page, page_size)
+}
 
 async fn load_admin_chart_top(
     sid: Option<&str>,
@@ -87,24 +189,159 @@ pub(super) async fn admin_api_chart_top(
     sid: Option<&str>,
     difficulty: Option<i32>,
     limit: Option<i64>,
-    pool: &State<DbPool>,
-    cookies: &CookieJar<'_>,
+    pool: &State<ReadPool>,
+    guard: PowerGuard,
 ) -> RouteResult<AdminChartTopResponse> {
-    require_web_session(cookies, pool.inner()).await?;
+    let session = guard.require_any(&[
+        SELECT_POWER,
+        SELECT_SONG_RANK_POWER,
+        SELECT_SONG_RANK_TOP_POWER,
+    ])?;
+    let limit = if session.has_power(SELECT_POWER) || session.has_power(SELECT_SONG_RANK_POWER) {
+        limit
+    } else {
+        Some(
+            limit
+                .unwrap_or(SONG_RANK_TOP_LIMIT)
+                .min(SONG_RANK_TOP_LIMIT),
+        )
+    };
     Ok(success_return(
         load_admin_chart_top(sid, difficulty.unwrap_or(0), limit, pool.inner()).await?,
     ))
 }
 
+#[get("/api/chart-analytics?<q>&<min_players>&<paging..>")]
+pub(super) async fn admin_api_chart_analytics(
+    q: Option<&str>,
+    min_players: Option<i32>,
+    paging: PageQuery,
+    pool: &State<ReadPool>,
+    guard: PowerGuard,
+) -> RouteResult<AdminPageResponse<ChartAnalyticsRowView>> {
+    guard.require(SELECT_POWER)?;
+    let (page, page_size) = paging.normalized();
+    Ok(success_return(
+        load_admin_chart_analytics(
+            q,
+            paging.sort(),
+            min_players.unwrap_or(1).max(1),
+            page,
+            page_size,
+            pool.inner(),
+        )
+        .await,
+    ))
+}
+
+async fn load_admin_replays(
+    q: Option<&str>,
+    page: i64,
+    page_size: i64,
+    pool: &DbPool,
+) -> Result<AdminPageResponse<AdminReplayRowView>, ArcError> {
+    let like = clean_query_value(q).map(|query| format!("%{query}%"));
+    let total = sqlx::query_scalar!(
+        "SELECT COUNT(*) FROM score_replay r
+         LEFT JOIN user u ON u.user_id = r.user_id
+         WHERE ? IS NULL OR u.name LIKE ? OR u.user_code LIKE ? OR r.song_id LIKE ?",
+        like,
+        like,
+        like,
+        like
+    )
+    .fetch_one(pool)
+    .await
+    .map_err(|err| ArcError::input(format!("查询回放失败: {err}")))?;
+    let (page, offset) = clamp_page(page, page_size, total);
+
+    let rows = sqlx::query!(
+        "SELECT r.user_id, u.name, r.song_id, c.name AS chart_name, r.difficulty,
+                us.score, r.time_played, r.uploaded_at, r.size, r.sha256, r.compression
+         FROM score_replay r
+         LEFT JOIN user u ON u.user_id = r.user_id
+         LEFT JOIN chart c ON c.song_id = r.song_id
+         LEFT JOIN user_score us
+           ON us.user_id = r.user_id AND us.song_id = r.song_id
+          AND us.difficulty = r.difficulty AND us.time_played = r.time_played
+         WHERE ? IS NULL OR u.name LIKE ? OR u.user_code LIKE ? OR r.song_id LIKE ?
+         ORDER BY r.uploaded_at DESC
+         LIMIT ? OFFSET ?",
+        like,
+        like,
+        like,
+        like,
+        page_size,
+        offset
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|err| ArcError::input(format!("查询回放失败: {err}")))?
+    .into_iter()
+    .map(|row| AdminReplayRowView {
+        user_id: row.user_id,
+        name: row.name.unwrap_or_default(),
+        song_id: row.song_id,
+        name_en: row.chart_name.unwrap_or_default(),
+        difficulty: row.difficulty,
+        score: row.score,
+        time_played: row.time_played,
+        played_at: format_timestamp(Some(row.time_played)),
+        uploaded_at: format_timestamp(Some(row.uploaded_at)),
+        size: row.size,
+        sha256: row.sha256,
+        compression: row.compression,
+    })
+    .collect();
+
+    Ok(page_response(rows, total, page, page_size))
+}
+
+#[get("/api/replays?<q>&<paging..>")]
+pub(super) async fn admin_api_replays(
+    q: Option<&str>,
+    paging: PageQuery,
+    pool: &State<ReadPool>,
+    guard: PowerGuard,
+) -> RouteResult<AdminPageResponse<AdminReplayRowView>> {
+    guard.require(SELECT_POWER)?;
+    let (page, page_size) = paging.normalized();
+    Ok(success_return(
+        load_admin_replays(q, page, page_size, pool.inner()).await?,
+    ))
+}
+
+/// Replay download for the owner of the play, or any play for admins.
+#[get("/api/replays/file?<user_id>&<song_id>&<difficulty>&<time_played>")]
+pub(super) async fn admin_api_replay_file(
+    user_id: Option<i32>,
+    song_id: &str,
+    difficulty: i32,
+    time_played: i64,
+    pool: &State<DbPool>,
+    replay_service: &State<ReplayService>,
+    auth: WebAuth<'_>,
+) -> Result<ReplayFile, ArcError> {
+    let session = require_web_session(auth, pool.inner()).await?;
+    let user = resolve_score_image_user(&session, user_id, None, None, pool.inner()).await?;
+    let bytes = replay_service
+        .download(user.user_id, song_id, difficulty, time_played)
+        .await?;
+    Ok(ReplayFile {
+        file_name: format!("{}-{song_id}-{difficulty}-{time_played}.bin", user.user_id),
+        bytes,
+    })
+}
+
 #[get("/api/score-images?<user_id>&<name>&<user_code>")]
 pub(super) async fn admin_api_score_images(
     user_id: Option<i32>,
     name: Option<String>,
     user_code: Option<String>,
     pool: &State<DbPool>,
-    cookies: &CookieJar<'_>,
+    auth: WebAuth<'_>,
 ) -> RouteResult<ScoreImagesResponse> {
-    let session = require_web_session(cookies, pool.inner()).await?;
+    let session = require_web_session(auth, pool.inner()).await?;
     let user = resolve_score_image_user(
         &session,
         user_id,
@@ -143,12 +380,12 @@ pub(super) async fn admin_api_score_image_png(
     name: Option<String>,
     user_code: Option<String>,
     pool: &State<DbPool>,
-    cookies: &CookieJar<'_>,
+    auth: WebAuth<'_>,
 ) -> Result<PngResponse, ArcError> {
     let mode_slug = file_name.strip_suffix(".png").unwrap_or(file_name);
     let mode = parse_score_image_mode(mode_slug)
         .ok_or_else(|| ArcError::input("Unsupported score image mode"))?;
-    let session = require_web_session(cookies, pool.inner()).await?;
+    let session = require_web_session(auth, pool.inner()).await?;
     let user = resolve_score_image_user(
         &session,
         user_id,
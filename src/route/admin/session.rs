@@ -1,21 +1,26 @@
-//! Web admin authentication: cookie/session management, login/logout and the
-//! `require_*` guards used by every admin route handler.
+//! Web admin authentication: cookie/session management, login/logout, API
+//! tokens and the `require_*` guards used by every admin route handler.
 
 use chrono::Utc;
-use rocket::http::{Cookie, CookieJar, SameSite};
-use rocket::{get, post, State};
+use rand::Rng;
+use rocket::http::{Cookie, CookieJar, SameSite, Status};
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::{delete, get, post, State};
 use sha2::{Digest, Sha256};
 use std::env;
 use std::sync::RwLock;
 
+use crate::context::IpContext;
 use crate::error::ArcError;
 use crate::route::common::{success_return, success_return_no_value, EmptyResponse, RouteResult};
-use crate::service::UserService;
+use crate::service::password::upgrade_password_hash;
+use crate::service::{PasswordCheck, UserService};
 use crate::DbPool;
 
 use super::helpers::resolve_admin_user;
 use super::models::{
-    AdminLoginRequest, AdminSessionResponse, AdminUserSummary, WebLoginUserRow, WebSession,
+    AdminApiTokenResponse, AdminLoginRequest, AdminSessionResponse, AdminUserSummary,
+    WebLoginUserRow, WebSession,
 };
 use super::{AdminConfig, ADMIN_CONFIG, ADMIN_COOKIE, ADMIN_ROLE, CHART_CONSTANT_EDIT_POWER};
 
@@ -131,19 +136,99 @@ fn web_surface_opacity() -> f64 {
         .unwrap_or(1.0)
 }
 
-fn web_permissions(can_edit_chart_constants: bool) -> Vec<String> {
-    if can_edit_chart_constants {
-        vec![CHART_CONSTANT_EDIT_POWER.to_string()]
-    } else {
-        Vec::new()
+fn web_permissions(can_edit_chart_constants: bool, powers: &[String]) -> Vec<String> {
+    let mut permissions = powers.to_vec();
+    if can_edit_chart_constants && !permissions.iter().any(|p| p == CHART_CONSTANT_EDIT_POWER) {
+        permissions.push(CHART_CONSTANT_EDIT_POWER.to_string());
+    }
+    permissions
+}
+
+/// Credentials presented to an admin route: the signed web session cookie,
+/// or an `Authorization: Bearer` API token issued by `/api/v1/admin/token`.
+#[derive(Clone, Copy)]
+pub(super) struct WebAuth<'r> {
+    cookies: &'r CookieJar<'r>,
+    api_token: Option<&'r str>,
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for WebAuth<'r> {
+    type Error = std::convert::Infallible;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let api_token = request
+            .headers()
+            .get_one("Authorization")
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .map(str::trim)
+            .filter(|token| !token.is_empty());
+        Outcome::Success(WebAuth {
+            cookies: request.cookies(),
+            api_token,
+        })
+    }
+}
+
+fn api_token_hash(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+fn generate_api_token() -> String {
+    let mut rng = rand::thread_rng();
+    (0..32)
+        .map(|_| format!("{:02x}", rng.gen::<u8>()))
+        .collect()
+}
+
+fn web_session_from_user(user: WebLoginUserRow, powers: Vec<String>) -> WebSession {
+    let can_edit_chart_constants = user.can_edit_chart_constants();
+    WebSession {
+        role: user.web_role(),
+        can_edit_chart_constants,
+        powers,
+        user: AdminUserSummary {
+            user_id: user.user_id,
+            name: user.name.unwrap_or_default(),
+            user_code: user.user_code.unwrap_or_default(),
+        },
+    }
+}
+
+/// Session of an API token; the role and powers are re-read from
+/// `user_role`/`role_power` on every request, so revoking the admin role
+/// disables the user's tokens as well.
+async fn api_token_session(pool: &DbPool, token: &str) -> Result<Option<WebSession>, ArcError> {
+    let token_hash = api_token_hash(token);
+    let user_id = sqlx::query_scalar!("SELECT user_id FROM api_login WHERE token = ?", token_hash)
+        .fetch_optional(pool)
+        .await
+        .map_err(|err| ArcError::input(format!("查询 API token 失败: {err}")))?
+        .flatten();
+    let Some(user_id) = user_id else {
+        return Ok(None);
+    };
+
+    let Some(user) = load_web_login_user_by_id(pool, user_id).await? else {
+        return Ok(None);
+    };
+    if user.web_role() != ADMIN_ROLE || super::helpers::is_ban_flag_active(user.ban_flag.as_deref())
+    {
+        return Ok(None);
     }
+    let powers = load_web_powers(pool, user.user_id).await?;
+    Ok(Some(web_session_from_user(user, powers)))
 }
 
 pub(super) async fn current_web_session(
-    cookies: &CookieJar<'_>,
+    auth: WebAuth<'_>,
     pool: &DbPool,
 ) -> Result<Option<WebSession>, ArcError> {
-    let Some(cookie) = cookies.get(ADMIN_COOKIE) else {
+    if let Some(token) = auth.api_token {
+        return api_token_session(pool, token).await;
+    }
+
+    let Some(cookie) = auth.cookies.get(ADMIN_COOKIE) else {
         return Ok(None);
     };
     let Some((cookie_user_id, cookie_role, signature)) = parse_web_session_cookie(cookie.value())
@@ -164,24 +249,99 @@ pub(super) async fn current_web_session(
     if signature != expected {
         return Ok(None);
     }
-    let can_edit_chart_constants = user.can_edit_chart_constants();
 
-    Ok(Some(WebSession {
-        user: AdminUserSummary {
-            user_id: user.user_id,
-            name: user.name.unwrap_or_default(),
-            user_code: user.user_code.unwrap_or_default(),
-        },
-        role: user_role,
-        can_edit_chart_constants,
-    }))
+    let powers = load_web_powers(pool, user.user_id).await?;
+    Ok(Some(web_session_from_user(user, powers)))
+}
+
+/// Powers granted through `user_role`/`role_power`. Accounts without any
+/// role row are plain players and get the powers of the `user` role.
+async fn load_web_powers(pool: &DbPool, user_id: i32) -> Result<Vec<String>, ArcError> {
+    sqlx::query_scalar!(
+        r#"
+        SELECT DISTINCT rp.power_id
+        FROM role_power rp
+        WHERE rp.role_id IN (SELECT ur.role_id FROM user_role ur WHERE ur.user_id = ?)
+           OR (
+               rp.role_id = 'user'
+               AND NOT EXISTS (SELECT 1 FROM user_role ur WHERE ur.user_id = ?)
+           )
+        ORDER BY rp.power_id
+        "#,
+        user_id,
+        user_id
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|err| ArcError::input(format!("查询用户权限失败: {err}")))
+}
+
+/// Request guard resolving the web session together with its powers.
+///
+/// Handlers call [`PowerGuard::require`] with the power they need instead of
+/// checking the admin role, so a `selecter` or `chart_editor` account only
+/// reaches the endpoints its role grants.
+pub(super) struct PowerGuard {
+    session: Option<WebSession>,
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for PowerGuard {
+    type Error = ArcError;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let auth = match request.guard::<WebAuth<'r>>().await {
+            Outcome::Success(auth) => auth,
+            Outcome::Error((_, never)) => match never {},
+            Outcome::Forward(status) => return Outcome::Forward(status),
+        };
+        let pool = match request.guard::<&State<DbPool>>().await {
+            Outcome::Success(pool) => pool,
+            Outcome::Error((status, _)) => {
+                return Outcome::Error((status, ArcError::input("数据库不可用")))
+            }
+            Outcome::Forward(status) => return Outcome::Forward(status),
+        };
+        match current_web_session(auth, pool.inner()).await {
+            Ok(session) => Outcome::Success(PowerGuard { session }),
+            Err(err) => Outcome::Error((Status::InternalServerError, err)),
+        }
+    }
+}
+
+impl PowerGuard {
+    /// The logged-in session, provided it holds `power`
+    pub(super) fn require(self, power: &str) -> Result<WebSession, ArcError> {
+        let session = self.session.ok_or_else(web_unauthorized)?;
+        if session.has_power(power) {
+            Ok(session)
+        } else {
+            Err(ArcError::no_access(
+                format!("Permission `{power}` required"),
+                403,
+            ))
+        }
+    }
+
+    /// The logged-in session, provided it holds any of `powers`
+    pub(super) fn require_any(self, powers: &[&str]) -> Result<WebSession, ArcError> {
+        let session = self.session.ok_or_else(web_unauthorized)?;
+        if powers.iter().any(|power| session.has_power(power)) {
+            Ok(session)
+        } else {
+            Err(ArcError::no_access(
+                format!("One of {} required", powers.join(", ")),
+                403,
+            ))
+        }
+    }
 }
 
 pub(super) async fn require_web_session(
-    cookies: &CookieJar<'_>,
+    auth: WebAuth<'_>,
     pool: &DbPool,
 ) -> Result<WebSession, ArcError> {
-    current_web_session(cookies, pool)
+    current_web_session(auth, pool)
         .await?
         .ok_or_else(web_unauthorized)
 }
@@ -191,10 +351,10 @@ fn web_unauthorized() -> ArcError {
 }
 
 pub(super) async fn require_admin_api(
-    cookies: &CookieJar<'_>,
+    auth: WebAuth<'_>,
     pool: &DbPool,
 ) -> Result<WebSession, ArcError> {
-    let session = require_web_session(cookies, pool).await?;
+    let session = require_web_session(auth, pool).await?;
     if session.role == ADMIN_ROLE {
         Ok(session)
     } else {
@@ -203,10 +363,10 @@ pub(super) async fn require_admin_api(
 }
 
 pub(super) async fn require_chart_constant_edit_api(
-    cookies: &CookieJar<'_>,
+    auth: WebAuth<'_>,
     pool: &DbPool,
 ) -> Result<WebSession, ArcError> {
-    let session = require_web_session(cookies, pool).await?;
+    let session = require_web_session(auth, pool).await?;
     if session.role == ADMIN_ROLE || session.can_edit_chart_constants {
         Ok(session)
     } else {
@@ -385,11 +545,11 @@ async fn bootstrap_config_admin_user(
         .ok_or_else(|| ArcError::no_data("管理员用户不存在", -2))
 }
 
-fn web_session_response(user: &WebLoginUserRow) -> AdminSessionResponse {
+fn web_session_response(user: &WebLoginUserRow, powers: &[String]) -> AdminSessionResponse {
     AdminSessionResponse {
         logged_in: true,
         role: user.web_role(),
-        permissions: web_permissions(user.can_edit_chart_constants()),
+        permissions: web_permissions(user.can_edit_chart_constants(), powers),
         app_title: web_app_title(),
         login_background: web_login_background(),
         login_position: web_login_position(),
@@ -405,13 +565,13 @@ fn web_session_response(user: &WebLoginUserRow) -> AdminSessionResponse {
 
 #[get("/api/session")]
 pub(super) async fn admin_api_session(
-    cookies: &CookieJar<'_>,
+    auth: WebAuth<'_>,
     pool: &State<DbPool>,
 ) -> RouteResult<AdminSessionResponse> {
-    let session = current_web_session(cookies, pool.inner()).await?;
+    let session = current_web_session(auth, pool.inner()).await?;
     let permissions = session
         .as_ref()
-        .map(|session| web_permissions(session.can_edit_chart_constants))
+        .map(|session| web_permissions(session.can_edit_chart_constants, &session.powers))
         .unwrap_or_default();
     Ok(success_return(AdminSessionResponse {
         logged_in: session.is_some(),
@@ -426,24 +586,24 @@ pub(super) async fn admin_api_session(
     }))
 }
 
-#[post("/api/login", format = "json", data = "<payload>")]
-pub(super) async fn admin_api_login(
-    payload: rocket::serde::json::Json<AdminLoginRequest>,
-    cookies: &CookieJar<'_>,
-    pool: &State<DbPool>,
-) -> RouteResult<AdminSessionResponse> {
+/// Check web login credentials, bootstrapping the configured admin account
+/// on first use.
+async fn verify_web_login(
+    pool: &DbPool,
+    payload: &AdminLoginRequest,
+) -> Result<WebLoginUserRow, ArcError> {
     let username = payload.username.trim();
     if username.is_empty() {
         return Err(ArcError::no_access("Incorrect username or password", 401));
     }
 
-    let user = match load_web_login_user(pool.inner(), username).await? {
+    let mut user = match load_web_login_user(pool, username).await? {
         Some(user) => user,
         None => {
             let (admin_username, admin_password) = admin_credentials();
             if username == admin_username && payload.password == admin_password {
-                let password_hash = UserService::hash_password(&payload.password);
-                bootstrap_config_admin_user(pool.inner(), username, &password_hash).await?
+                let password_hash = UserService::hash_password(&payload.password).await?;
+                bootstrap_config_admin_user(pool, username, &password_hash).await?
             } else {
                 return Err(ArcError::no_access("Incorrect username or password", 401));
             }
@@ -455,12 +615,84 @@ pub(super) async fn admin_api_login(
     }
 
     let password_hash = user.password.as_deref().unwrap_or_default();
-    if password_hash.is_empty() || password_hash != UserService::hash_password(&payload.password) {
+    let check = UserService::verify_password(&payload.password, password_hash).await;
+    if !check.is_match() {
         return Err(ArcError::no_access("Incorrect username or password", 401));
     }
+    if check == PasswordCheck::Outdated {
+        // The session cookie is signed with the stored hash, so sign it
+        // with the new one.
+        match upgrade_password_hash(pool, user.user_id, &payload.password).await {
+            Ok(password_hash) => user.password = Some(password_hash),
+            Err(e) => log::warn!("Failed to re-hash password of user {}: {e}", user.user_id),
+        }
+    }
+
+    Ok(user)
+}
 
+#[post("/api/login", format = "json", data = "<payload>")]
+pub(super) async fn admin_api_login(
+    payload: rocket::serde::json::Json<AdminLoginRequest>,
+    cookies: &CookieJar<'_>,
+    pool: &State<DbPool>,
+) -> RouteResult<AdminSessionResponse> {
+    let user = verify_web_login(pool.inner(), &payload).await?;
+    let password_hash = user.password.as_deref().unwrap_or_default();
     set_admin_cookie(cookies, user.user_id, user.web_role(), password_hash);
-    Ok(success_return(web_session_response(&user)))
+    let powers = load_web_powers(pool.inner(), user.user_id).await?;
+    Ok(success_return(web_session_response(&user, &powers)))
+}
+
+/// Issue an API token for `/api/v1/admin`; only admin-role accounts get one.
+/// The token is returned once and only its hash is stored.
+#[post("/token", format = "json", data = "<payload>")]
+pub(super) async fn admin_api_token_create(
+    payload: rocket::serde::json::Json<AdminLoginRequest>,
+    client_ip: IpContext,
+    pool: &State<DbPool>,
+) -> RouteResult<AdminApiTokenResponse> {
+    let user = verify_web_login(pool.inner(), &payload).await?;
+    if user.web_role() != ADMIN_ROLE {
+        return Err(ArcError::no_access("Admin role required", 403));
+    }
+
+    let token = generate_api_token();
+    sqlx::query!(
+        "INSERT INTO api_login (user_id, token, login_time, login_ip) VALUES (?, ?, ?, ?)",
+        user.user_id,
+        api_token_hash(&token),
+        Utc::now().timestamp_millis(),
+        client_ip.ip
+    )
+    .execute(pool.inner())
+    .await
+    .map_err(|err| ArcError::input(format!("创建 API token 失败: {err}")))?;
+
+    Ok(success_return(AdminApiTokenResponse {
+        user_id: user.user_id,
+        token,
+    }))
+}
+
+/// Revoke the API token the request is authenticated with.
+#[delete("/token")]
+pub(super) async fn admin_api_token_revoke(
+    auth: WebAuth<'_>,
+    pool: &State<DbPool>,
+) -> RouteResult<EmptyResponse> {
+    let token = auth.api_token.ok_or_else(web_unauthorized)?;
+    let result = sqlx::query!(
+        "DELETE FROM api_login WHERE token = ?",
+        api_token_hash(token)
+    )
+    .execute(pool.inner())
+    .await
+    .map_err(|err| ArcError::input(format!("删除 API token 失败: {err}")))?;
+    if result.rows_affected() == 0 {
+        return Err(web_unauthorized());
+    }
+    Ok(success_return_no_value())
 }
 
 #[post("/api/logout")]
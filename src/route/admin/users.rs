@@ -1,31 +1,48 @@
 //! Player management: account actions (ticket / password / create / ban /
-//! purchase), score deletion and per-player score queries.
+//! purchase, shadow ban), score deletion and bulk import, per-player score queries, daily
+//! potential history with recent 30 diffs, cloud save version rollback,
+//! device fingerprint / linked account lookup, purchase history, download
+//! quotas and support impersonation tokens.
 
-use rocket::http::CookieJar;
+use chrono::Utc;
+use rocket::data::{Data, ToByteUnit};
 use rocket::serde::json::Json;
 use rocket::{get, patch, post, State};
+use std::net::IpAddr;
 
-use crate::config::CONFIG;
 use crate::error::ArcError;
 use crate::model::UserRegisterDto;
 use crate::route::common::{success_return, RouteResult};
-use crate::service::UserService;
+use crate::service::game_constants::game_constants;
+use crate::service::ptt_history::{diff_recent30, PttHistoryPoint, Recent30Entry};
+use crate::service::purchase::MAX_PURCHASE_HISTORY;
+use crate::service::score_import::ScoreImportReport;
+use crate::service::{
+    DownloadService, PttHistoryService, PurchaseService, SaveService, ScoreImportService,
+    ScoreService, UserService,
+};
 use crate::utils::sql_placeholders;
-use crate::DbPool;
+use crate::{DbPool, ReadPool};
 
 use super::helpers::{
-    clamp_page, clean_optional_payload_text, clean_query_value, filter_sql, format_timestamp,
-    is_admin_user_banned, page_response, resolve_admin_user,
+    clamp_page, clean_optional_payload_text, clean_query_value, filter_sql, format_day,
+    format_timestamp, is_admin_user_banned, page_response, resolve_admin_user,
 };
 use super::models::{
-    AdminActionResponse, AdminPageResponse, AdminScoreDeletePayload, AdminScoreRowView,
-    AdminUserCreatePayload, AdminUserPasswordPayload, AdminUserPurchasePayload,
-    AdminUserScoreQuery, AdminUserScoreStats, AdminUserScoresResponse, AdminUserSelectorPayload,
-    AdminUserSummary, AdminUserTicketPayload, ChartEditorPermissionPayload, UserListDbRow,
-    UserListView,
+    AdminActionResponse, AdminDeviceView, AdminImpersonationLogView,
+    AdminImpersonationTokenResponse, AdminLinkedAccountView, AdminPageResponse,
+    AdminPttHistoryPointView, AdminPttHistoryResponse, AdminPurchaseLogView,
+    AdminRecent30DiffResponse, AdminRecent30EntryView, AdminSaveVersionView,
+    AdminScoreDeletePayload, AdminScoreRowView, AdminUserCreatePayload, AdminUserDevicesResponse,
+    AdminUserDownloadQuotaPayload, AdminUserDownloadQuotaResponse, AdminUserImpersonatePayload,
+    AdminUserPasswordPayload, AdminUserPurchasePayload, AdminUserPurchasesResponse,
+    AdminUserSaveRollbackPayload, AdminUserSavesResponse, AdminUserScoreQuery, AdminUserScoreStats,
+    AdminUserScoresResponse, AdminUserSelectorPayload, AdminUserSummary, AdminUserTicketPayload,
+    ChartEditorPermissionPayload, PageQuery, ShadowBanPayload, UserListDbRow, UserListView,
+    WebSession,
 };
-use super::session::{require_admin_api, require_web_session};
-use super::{ADMIN_ROLE, CHART_EDITOR_ROLE};
+use super::session::{require_admin_api, require_web_session, PowerGuard, WebAuth};
+use super::{ADMIN_ROLE, CHART_EDITOR_ROLE, SELECT_POWER};
 
 async fn update_admin_user_ticket(
     payload: &AdminUserTicketPayload,
@@ -76,7 +93,7 @@ async fn update_admin_user_password(
         pool,
     )
     .await?;
-    let password_hash = UserService::hash_password(password);
+    let password_hash = UserService::hash_password(password).await?;
     let affected_rows = sqlx::query!(
         "UPDATE user SET password = ? WHERE user_id = ?",
         password_hash,
@@ -341,6 +358,7 @@ async fn delete_admin_scores(
 async fn load_admin_users(
     q: Option<&str>,
     status: Option<&str>,
+    sort: Option<&str>,
     page: i64,
     page_size: i64,
     pool: &DbPool,
@@ -348,7 +366,7 @@ async fn load_admin_users(
     let keyword = clean_query_value(q);
     let status = status
         .map(str::trim)
-        .filter(|value| matches!(*value, "normal" | "banned"));
+        .filter(|value| matches!(*value, "normal" | "banned" | "shadow"));
 
     let like = keyword.as_ref().map(|kw| format!("%{kw}%"));
     let keyword = keyword.as_deref();
@@ -356,6 +374,7 @@ async fn load_admin_users(
     let has_keyword = keyword.is_some();
     let is_banned_filter = matches!(status, Some("banned"));
     let is_normal_filter = matches!(status, Some("normal"));
+    let is_shadow_filter = matches!(status, Some("shadow"));
 
     let total = sqlx::query_scalar!(
         r#"
@@ -364,6 +383,7 @@ async fn load_admin_users(
         WHERE (? = 0 OR CAST(user_id AS CHAR) LIKE ? OR name LIKE ? OR user_code LIKE ?)
           AND (? = 0 OR COALESCE(password, '') = '' OR COALESCE(CAST(SUBSTRING_INDEX(NULLIF(ban_flag, ''), ':', -1) AS SIGNED), 0) > UNIX_TIMESTAMP(CURRENT_TIMESTAMP(3)) * 1000)
           AND (? = 0 OR (COALESCE(password, '') <> '' AND NOT (COALESCE(CAST(SUBSTRING_INDEX(NULLIF(ban_flag, ''), ':', -1) AS SIGNED), 0) > UNIX_TIMESTAMP(CURRENT_TIMESTAMP(3)) * 1000)))
+          AND (? = 0 OR is_shadow_banned = 1)
         "#,
         has_keyword,
         like,
@@ -371,15 +391,19 @@ async fn load_admin_users(
         like,
         is_banned_filter,
         is_normal_filter,
+        is_shadow_filter,
     )
     .fetch_one(pool)
     .await?;
     let (page, offset) = clamp_page(page, page_size, total);
+    // Anything else keeps the potential ranking order.
+    let sort = sort.filter(|sort| matches!(*sort, "user_id" | "name" | "last_play"));
 
     let rows = sqlx::query_as!(
         UserListDbRow,
         r#"
         SELECT u.user_id, u.name, u.user_code, u.rating_ptt, u.ticket, u.time_played, u.password, u.ban_flag,
+               u.is_shadow_banned,
                CAST(CASE WHEN EXISTS (
                    SELECT 1 FROM user_role admin_role
                    WHERE admin_role.user_id = u.user_id
@@ -396,7 +420,12 @@ async fn load_admin_users(
         WHERE (? = 0 OR CAST(user_id AS CHAR) LIKE ? OR name LIKE ? OR user_code LIKE ?)
           AND (? = 0 OR COALESCE(password, '') = '' OR COALESCE(CAST(SUBSTRING_INDEX(NULLIF(ban_flag, ''), ':', -1) AS SIGNED), 0) > UNIX_TIMESTAMP(CURRENT_TIMESTAMP(3)) * 1000)
           AND (? = 0 OR (COALESCE(password, '') <> '' AND NOT (COALESCE(CAST(SUBSTRING_INDEX(NULLIF(ban_flag, ''), ':', -1) AS SIGNED), 0) > UNIX_TIMESTAMP(CURRENT_TIMESTAMP(3)) * 1000)))
-        ORDER BY rating_ptt DESC, user_id ASC
+          AND (? = 0 OR is_shadow_banned = 1)
+        ORDER BY
+            CASE WHEN ? = 'name' THEN u.name END ASC,
+            CASE WHEN ? = 'last_play' THEN u.time_played END DESC,
+            CASE WHEN ? = 'user_id' THEN u.user_id END ASC,
+            rating_ptt DESC, user_id ASC
         LIMIT ? OFFSET ?
         "#,
         has_keyword,
@@ -405,6 +434,10 @@ async fn load_admin_users(
         like,
         is_banned_filter,
         is_normal_filter,
+        is_shadow_filter,
+        sort,
+        sort,
+        sort,
         page_size,
         offset,
     )
@@ -419,6 +452,7 @@ async fn load_admin_users(
             ticket: row.ticket.unwrap_or(0),
             last_play: format_timestamp(row.time_played),
             banned: is_admin_user_banned(row.password.as_deref(), row.ban_flag.as_deref()),
+            shadow_banned: row.is_shadow_banned != 0,
             is_admin: row.is_admin > 0,
             can_edit_chart_constants: row.is_admin > 0 || row.can_edit_chart_constants > 0,
         })
@@ -492,6 +526,38 @@ async fn set_chart_editor_permission(
     })
 }
 
+async fn set_admin_user_shadow_ban(
+    user_id: i32,
+    enabled: bool,
+    pool: &DbPool,
+    score_service: &ScoreService,
+) -> Result<AdminActionResponse, ArcError> {
+    let user_exists = sqlx::query_scalar!(
+        "SELECT COUNT(*) as `count!: i64` FROM user WHERE user_id = ?",
+        user_id
+    )
+    .fetch_one(pool)
+    .await
+    .map_err(|err| ArcError::input(format!("查询用户失败: {err}")))?;
+    if user_exists == 0 {
+        return Err(ArcError::no_data("玩家不存在", -2));
+    }
+
+    let affected_rows = score_service
+        .set_user_shadow_ban(user_id, enabled)
+        .await
+        .map_err(|err| ArcError::input(format!("更新影子封禁失败: {err}")))?;
+
+    Ok(AdminActionResponse {
+        message: if enabled {
+            "已影子封禁，该玩家的成绩不再出现在排行榜中".to_string()
+        } else {
+            "已解除影子封禁".to_string()
+        },
+        affected_rows,
+    })
+}
+
 async fn load_admin_user_scores(
     query: &AdminUserScoreQuery,
     pool: &DbPool,
@@ -578,10 +644,12 @@ async fn load_admin_user_scores(
 
     let best_30_sum = b30.iter().map(|score| score.rating).sum();
     let recent_10_sum = r10.iter().map(|score| score.rating).sum();
+    let constants = game_constants();
     let stats = AdminUserScoreStats {
         best_30_sum,
         recent_10_sum,
-        potential: best_30_sum * CONFIG.best30_weight + recent_10_sum * CONFIG.recent10_weight,
+        potential: best_30_sum * constants.best30_weight
+            + recent_10_sum * constants.recent10_weight,
     };
 
     Ok(AdminUserScoresResponse {
@@ -592,19 +660,18 @@ async fn load_admin_user_scores(
     })
 }
 
-#[get("/api/users?<q>&<status>&<page>&<page_size>")]
+#[get("/api/users?<q>&<status>&<paging..>")]
 pub(super) async fn admin_api_users(
     q: Option<&str>,
     status: Option<&str>,
-    page: Option<i64>,
-    page_size: Option<i64>,
-    pool: &State<DbPool>,
-    cookies: &CookieJar<'_>,
+    paging: PageQuery,
+    pool: &State<ReadPool>,
+    guard: PowerGuard,
 ) -> RouteResult<AdminPageResponse<UserListView>> {
-    require_admin_api(cookies, pool.inner()).await?;
-    let (page, page_size) = super::helpers::normalize_page(page, page_size);
+    guard.require(SELECT_POWER)?;
+    let (page, page_size) = paging.normalized();
     Ok(success_return(
-        load_admin_users(q, status, page, page_size, pool.inner()).await?,
+        load_admin_users(q, status, paging.sort(), page, page_size, pool.inner()).await?,
     ))
 }
 
@@ -617,21 +684,41 @@ pub(super) async fn admin_api_chart_editor_permission(
     user_id: i32,
     payload: Json<ChartEditorPermissionPayload>,
     pool: &State<DbPool>,
-    cookies: &CookieJar<'_>,
+    auth: WebAuth<'_>,
 ) -> RouteResult<AdminActionResponse> {
-    require_admin_api(cookies, pool.inner()).await?;
+    require_admin_api(auth, pool.inner()).await?;
     Ok(success_return(
         set_chart_editor_permission(user_id, payload.enabled, pool.inner()).await?,
     ))
 }
 
+#[patch("/api/users/<user_id>/shadow-ban", format = "json", data = "<payload>")]
+pub(super) async fn admin_api_user_shadow_ban(
+    user_id: i32,
+    payload: Json<ShadowBanPayload>,
+    pool: &State<DbPool>,
+    score_service: &State<ScoreService>,
+    auth: WebAuth<'_>,
+) -> RouteResult<AdminActionResponse> {
+    require_admin_api(auth, pool.inner()).await?;
+    Ok(success_return(
+        set_admin_user_shadow_ban(
+            user_id,
+            payload.enabled,
+            pool.inner(),
+            score_service.inner(),
+        )
+        .await?,
+    ))
+}
+
 #[post("/api/admin-actions/user-ticket", format = "json", data = "<payload>")]
 pub(super) async fn admin_api_user_ticket(
     payload: Json<AdminUserTicketPayload>,
     pool: &State<DbPool>,
-    cookies: &CookieJar<'_>,
+    auth: WebAuth<'_>,
 ) -> RouteResult<AdminActionResponse> {
-    require_admin_api(cookies, pool.inner()).await?;
+    require_admin_api(auth, pool.inner()).await?;
     Ok(success_return(
         update_admin_user_ticket(&payload, pool.inner()).await?,
     ))
@@ -645,9 +732,9 @@ pub(super) async fn admin_api_user_ticket(
 pub(super) async fn admin_api_user_password(
     payload: Json<AdminUserPasswordPayload>,
     pool: &State<DbPool>,
-    cookies: &CookieJar<'_>,
+    auth: WebAuth<'_>,
 ) -> RouteResult<AdminActionResponse> {
-    require_admin_api(cookies, pool.inner()).await?;
+    require_admin_api(auth, pool.inner()).await?;
     Ok(success_return(
         update_admin_user_password(&payload, pool.inner()).await?,
     ))
@@ -658,9 +745,9 @@ pub(super) async fn admin_api_user_create(
     payload: Json<AdminUserCreatePayload>,
     pool: &State<DbPool>,
     user_service: &State<UserService>,
-    cookies: &CookieJar<'_>,
+    auth: WebAuth<'_>,
 ) -> RouteResult<AdminUserSummary> {
-    require_admin_api(cookies, pool.inner()).await?;
+    require_admin_api(auth, pool.inner()).await?;
     Ok(success_return(
         create_admin_user(&payload, pool.inner(), user_service.inner()).await?,
     ))
@@ -670,9 +757,9 @@ pub(super) async fn admin_api_user_create(
 pub(super) async fn admin_api_user_ban(
     payload: Json<AdminUserSelectorPayload>,
     pool: &State<DbPool>,
-    cookies: &CookieJar<'_>,
+    auth: WebAuth<'_>,
 ) -> RouteResult<AdminActionResponse> {
-    require_admin_api(cookies, pool.inner()).await?;
+    require_admin_api(auth, pool.inner()).await?;
     Ok(success_return(
         ban_admin_user(&payload, pool.inner()).await?,
     ))
@@ -686,9 +773,9 @@ pub(super) async fn admin_api_user_ban(
 pub(super) async fn admin_api_user_purchase(
     payload: Json<AdminUserPurchasePayload>,
     pool: &State<DbPool>,
-    cookies: &CookieJar<'_>,
+    auth: WebAuth<'_>,
 ) -> RouteResult<AdminActionResponse> {
-    require_admin_api(cookies, pool.inner()).await?;
+    require_admin_api(auth, pool.inner()).await?;
     Ok(success_return(
         update_admin_user_purchase(&payload, pool.inner()).await?,
     ))
@@ -702,24 +789,49 @@ pub(super) async fn admin_api_user_purchase(
 pub(super) async fn admin_api_scores_delete(
     payload: Json<AdminScoreDeletePayload>,
     pool: &State<DbPool>,
-    cookies: &CookieJar<'_>,
+    auth: WebAuth<'_>,
 ) -> RouteResult<AdminActionResponse> {
-    require_admin_api(cookies, pool.inner()).await?;
+    require_admin_api(auth, pool.inner()).await?;
     Ok(success_return(
         delete_admin_scores(&payload, pool.inner()).await?,
     ))
 }
 
-#[get("/api/user-scores?<user_id>&<name>&<user_code>")]
-pub(super) async fn admin_api_user_scores(
+/// Bulk import of scores as CSV (with a header line) or a JSON array; each
+/// row is reported on its own and `dry_run` only checks the rows.
+#[post("/api/admin-actions/scores/import?<dry_run>", data = "<data>")]
+pub(super) async fn admin_api_scores_import(
+    dry_run: Option<bool>,
+    data: Data<'_>,
+    pool: &State<DbPool>,
+    score_service: &State<ScoreService>,
+    auth: WebAuth<'_>,
+) -> RouteResult<ScoreImportReport> {
+    require_admin_api(auth, pool.inner()).await?;
+
+    let body = data
+        .open(4.mebibytes())
+        .into_string()
+        .await
+        .map_err(ArcError::from)?;
+    if !body.is_complete() {
+        return Err(ArcError::input("Score import is too large."));
+    }
+
+    let report = ScoreImportService::new(pool.inner().clone())
+        .import(score_service.inner(), &body, dry_run.unwrap_or(false))
+        .await?;
+    Ok(success_return(report))
+}
+
+/// Admins may look up any player; everyone else only sees themselves.
+fn session_score_query(
+    session: WebSession,
     user_id: Option<i32>,
     name: Option<String>,
     user_code: Option<String>,
-    pool: &State<DbPool>,
-    cookies: &CookieJar<'_>,
-) -> RouteResult<AdminUserScoresResponse> {
-    let session = require_web_session(cookies, pool.inner()).await?;
-    let query = if session.role == ADMIN_ROLE {
+) -> AdminUserScoreQuery {
+    if session.role == ADMIN_ROLE {
         AdminUserScoreQuery {
             user_id,
             name,
@@ -731,8 +843,495 @@ pub(super) async fn admin_api_user_scores(
             name: None,
             user_code: None,
         }
-    };
+    }
+}
+
+#[get("/api/user-scores?<user_id>&<name>&<user_code>")]
+pub(super) async fn admin_api_user_scores(
+    user_id: Option<i32>,
+    name: Option<String>,
+    user_code: Option<String>,
+    pool: &State<DbPool>,
+    auth: WebAuth<'_>,
+) -> RouteResult<AdminUserScoresResponse> {
+    let session = require_web_session(auth, pool.inner()).await?;
+    let query = session_score_query(session, user_id, name, user_code);
     Ok(success_return(
         load_admin_user_scores(&query, pool.inner()).await?,
     ))
 }
+
+/// Days of potential history returned when none are requested
+const DEFAULT_PTT_HISTORY_DAYS: i64 = 90;
+const MAX_PTT_HISTORY_DAYS: i64 = 3650;
+
+fn ptt_history_point_view(point: PttHistoryPoint) -> AdminPttHistoryPointView {
+    AdminPttHistoryPointView {
+        date: format_day(point.day),
+        day: point.day,
+        potential: point.rating_ptt as f64 / 100.0,
+        best30: point.best30,
+        recent10: point.recent10,
+    }
+}
+
+fn recent30_entry_view(entry: Recent30Entry) -> AdminRecent30EntryView {
+    AdminRecent30EntryView {
+        song_id: entry.song_id,
+        difficulty: entry.difficulty,
+        score: entry.score,
+        clear_type: entry.clear_type,
+        rating: entry.rating,
+        time_played: format_timestamp(Some(entry.time_played)),
+    }
+}
+
+async fn resolve_score_query_user(
+    query: &AdminUserScoreQuery,
+    pool: &DbPool,
+) -> Result<AdminUserSummary, ArcError> {
+    resolve_admin_user(
+        query.user_id,
+        clean_optional_payload_text(&query.name),
+        clean_optional_payload_text(&query.user_code),
+        pool,
+    )
+    .await
+}
+
+/// Daily potential, best 30 and recent 10 averages of a player, oldest first
+#[get("/api/user-ptt-history?<user_id>&<name>&<user_code>&<days>")]
+pub(super) async fn admin_api_user_ptt_history(
+    user_id: Option<i32>,
+    name: Option<String>,
+    user_code: Option<String>,
+    days: Option<i64>,
+    pool: &State<DbPool>,
+    ptt_history_service: &State<PttHistoryService>,
+    auth: WebAuth<'_>,
+) -> RouteResult<AdminPttHistoryResponse> {
+    let session = require_web_session(auth, pool.inner()).await?;
+    let query = session_score_query(session, user_id, name, user_code);
+    let user = resolve_score_query_user(&query, pool.inner()).await?;
+    let days = days
+        .unwrap_or(DEFAULT_PTT_HISTORY_DAYS)
+        .clamp(1, MAX_PTT_HISTORY_DAYS);
+    let points = ptt_history_service
+        .history(user.user_id, days)
+        .await?
+        .into_iter()
+        .map(ptt_history_point_view)
+        .collect();
+    Ok(success_return(AdminPttHistoryResponse { user, points }))
+}
+
+/// Plays that entered and left a player's recent 30 between the snapshots
+/// of two days (`from` and `to` are the `day` of history points)
+#[get("/api/user-ptt-history/diff?<user_id>&<name>&<user_code>&<from>&<to>")]
+#[allow(clippy::too_many_arguments)]
+pub(super) async fn admin_api_user_recent30_diff(
+    user_id: Option<i32>,
+    name: Option<String>,
+    user_code: Option<String>,
+    from: i64,
+    to: i64,
+    pool: &State<DbPool>,
+    ptt_history_service: &State<PttHistoryService>,
+    auth: WebAuth<'_>,
+) -> RouteResult<AdminRecent30DiffResponse> {
+    let session = require_web_session(auth, pool.inner()).await?;
+    let query = session_score_query(session, user_id, name, user_code);
+    let user = resolve_score_query_user(&query, pool.inner()).await?;
+
+    let missing = |day: i64| ArcError::no_data(format!("{} 没有快照", format_day(day)), -2);
+    let from_snapshot = ptt_history_service
+        .snapshot(user.user_id, from)
+        .await?
+        .ok_or_else(|| missing(from))?;
+    let to_snapshot = ptt_history_service
+        .snapshot(user.user_id, to)
+        .await?
+        .ok_or_else(|| missing(to))?;
+
+    let diff = diff_recent30(&from_snapshot.recent30, &to_snapshot.recent30);
+    Ok(success_return(AdminRecent30DiffResponse {
+        user,
+        from: ptt_history_point_view(from_snapshot.point),
+        to: ptt_history_point_view(to_snapshot.point),
+        added: diff.added.into_iter().map(recent30_entry_view).collect(),
+        removed: diff.removed.into_iter().map(recent30_entry_view).collect(),
+        unchanged: diff.unchanged,
+    }))
+}
+
+async fn load_admin_user_saves(
+    user: AdminUserSummary,
+    pool: &DbPool,
+    save_service: &SaveService,
+) -> Result<AdminUserSavesResponse, ArcError> {
+    let current_created_at = sqlx::query_scalar!(
+        "SELECT createdAt FROM user_save WHERE user_id = ?",
+        user.user_id
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(|err| ArcError::input(format!("查询存档失败: {err}")))?
+    .map(format_timestamp);
+    let versions = save_service
+        .get_save_history(user.user_id)
+        .await?
+        .into_iter()
+        .map(|version| AdminSaveVersionView {
+            history_id: version.history_id,
+            created_at: format_timestamp(version.created_at),
+            archived_at: format_timestamp(Some(version.archived_at)),
+            data_size: version.data_size,
+        })
+        .collect();
+
+    Ok(AdminUserSavesResponse {
+        user,
+        current_created_at,
+        versions,
+    })
+}
+
+#[get("/api/user-saves?<user_id>&<name>&<user_code>")]
+pub(super) async fn admin_api_user_saves(
+    user_id: Option<i32>,
+    name: Option<String>,
+    user_code: Option<String>,
+    pool: &State<DbPool>,
+    save_service: &State<SaveService>,
+    guard: PowerGuard,
+) -> RouteResult<AdminUserSavesResponse> {
+    guard.require(SELECT_POWER)?;
+    let name = clean_query_value(name.as_deref());
+    let user_code = clean_query_value(user_code.as_deref());
+    let user =
+        resolve_admin_user(user_id, name.as_deref(), user_code.as_deref(), pool.inner()).await?;
+    Ok(success_return(
+        load_admin_user_saves(user, pool.inner(), save_service.inner()).await?,
+    ))
+}
+
+async fn load_admin_user_devices(
+    user: AdminUserSummary,
+    pool: &DbPool,
+) -> Result<AdminUserDevicesResponse, ArcError> {
+    let devices = sqlx::query!(
+        "SELECT kind, value, device_model, first_seen, last_seen, last_ip, seen_count
+         FROM user_device_fingerprint
+         WHERE user_id = ?
+         ORDER BY last_seen DESC",
+        user.user_id
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|err| ArcError::input(format!("查询设备记录失败: {err}")))?
+    .into_iter()
+    .map(|row| AdminDeviceView {
+        kind: row.kind,
+        value: row.value,
+        device_model: row.device_model,
+        first_seen: format_timestamp(Some(row.first_seen)),
+        last_seen: format_timestamp(Some(row.last_seen)),
+        last_ip: row.last_ip,
+        seen_count: row.seen_count,
+    })
+    .collect();
+
+    let rows = sqlx::query!(
+        "SELECT other.user_id, u.name, u.user_code, u.password, u.ban_flag,
+                other.kind, other.value, other.last_seen
+         FROM user_device_fingerprint mine
+         JOIN user_device_fingerprint other
+           ON other.kind = mine.kind AND other.value = mine.value
+          AND other.user_id <> mine.user_id
+         JOIN user u ON u.user_id = other.user_id
+         WHERE mine.user_id = ?
+         ORDER BY other.last_seen DESC",
+        user.user_id
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|err| ArcError::input(format!("查询关联账号失败: {err}")))?;
+
+    let mut linked_accounts: Vec<AdminLinkedAccountView> = Vec::new();
+    for row in rows {
+        let shared = format!("{}:{}", row.kind, row.value);
+        if let Some(account) = linked_accounts
+            .iter_mut()
+            .find(|account| account.user_id == row.user_id)
+        {
+            if !account.shared.contains(&shared) {
+                account.shared.push(shared);
+            }
+            continue;
+        }
+        linked_accounts.push(AdminLinkedAccountView {
+            user_id: row.user_id,
+            name: row.name.unwrap_or_default(),
+            user_code: row.user_code.unwrap_or_default(),
+            banned: is_admin_user_banned(row.password.as_deref(), row.ban_flag.as_deref()),
+            shared: vec![shared],
+            last_seen: format_timestamp(Some(row.last_seen)),
+        });
+    }
+
+    Ok(AdminUserDevicesResponse {
+        user,
+        devices,
+        linked_accounts,
+    })
+}
+
+#[get("/api/user-devices?<user_id>&<name>&<user_code>")]
+pub(super) async fn admin_api_user_devices(
+    user_id: Option<i32>,
+    name: Option<String>,
+    user_code: Option<String>,
+    pool: &State<DbPool>,
+    guard: PowerGuard,
+) -> RouteResult<AdminUserDevicesResponse> {
+    guard.require(SELECT_POWER)?;
+    let name = clean_query_value(name.as_deref());
+    let user_code = clean_query_value(user_code.as_deref());
+    let user =
+        resolve_admin_user(user_id, name.as_deref(), user_code.as_deref(), pool.inner()).await?;
+    Ok(success_return(
+        load_admin_user_devices(user, pool.inner()).await?,
+    ))
+}
+
+#[get("/api/user-purchases?<user_id>&<name>&<user_code>")]
+pub(super) async fn admin_api_user_purchases(
+    user_id: Option<i32>,
+    name: Option<String>,
+    user_code: Option<String>,
+    pool: &State<DbPool>,
+    purchase_service: &State<PurchaseService>,
+    guard: PowerGuard,
+) -> RouteResult<AdminUserPurchasesResponse> {
+    guard.require(SELECT_POWER)?;
+    let name = clean_query_value(name.as_deref());
+    let user_code = clean_query_value(user_code.as_deref());
+    let user =
+        resolve_admin_user(user_id, name.as_deref(), user_code.as_deref(), pool.inner()).await?;
+    let purchases = purchase_service
+        .get_purchase_history(user.user_id, MAX_PURCHASE_HISTORY)
+        .await?
+        .into_iter()
+        .map(|entry| AdminPurchaseLogView {
+            kind: entry.kind,
+            purchase_name: entry.purchase_name,
+            price: entry.price,
+            currency: entry.currency,
+            time: format_timestamp(Some(entry.time)),
+        })
+        .collect();
+    Ok(success_return(AdminUserPurchasesResponse {
+        user,
+        purchases,
+    }))
+}
+
+async fn load_admin_user_download_quota(
+    user: AdminUserSummary,
+    download_service: &DownloadService,
+) -> Result<AdminUserDownloadQuotaResponse, ArcError> {
+    let usage = download_service.download_usage(user.user_id).await?;
+    Ok(AdminUserDownloadQuotaResponse {
+        user,
+        download_count: usage.download_count,
+        download_bytes: usage.download_bytes,
+        times_limit: usage.times_limit,
+        bytes_limit: usage.bytes_limit,
+        is_override: usage.is_override,
+    })
+}
+
+#[get("/api/user-download-quota?<user_id>&<name>&<user_code>")]
+pub(super) async fn admin_api_user_download_quota(
+    user_id: Option<i32>,
+    name: Option<String>,
+    user_code: Option<String>,
+    pool: &State<DbPool>,
+    download_service: &State<DownloadService>,
+    guard: PowerGuard,
+) -> RouteResult<AdminUserDownloadQuotaResponse> {
+    guard.require(SELECT_POWER)?;
+    let name = clean_query_value(name.as_deref());
+    let user_code = clean_query_value(user_code.as_deref());
+    let user =
+        resolve_admin_user(user_id, name.as_deref(), user_code.as_deref(), pool.inner()).await?;
+    Ok(success_return(
+        load_admin_user_download_quota(user, download_service.inner()).await?,
+    ))
+}
+
+#[post(
+    "/api/admin-actions/user-download-quota",
+    format = "json",
+    data = "<payload>"
+)]
+pub(super) async fn admin_api_user_download_quota_update(
+    payload: Json<AdminUserDownloadQuotaPayload>,
+    pool: &State<DbPool>,
+    download_service: &State<DownloadService>,
+    auth: WebAuth<'_>,
+) -> RouteResult<AdminUserDownloadQuotaResponse> {
+    require_admin_api(auth, pool.inner()).await?;
+    let user = resolve_admin_user(
+        payload.user_id,
+        clean_optional_payload_text(&payload.name),
+        clean_optional_payload_text(&payload.user_code),
+        pool.inner(),
+    )
+    .await?;
+    if payload.reset {
+        download_service.reset_download_usage(user.user_id).await?;
+    }
+    download_service
+        .set_download_quota(user.user_id, payload.times_limit, payload.bytes_limit)
+        .await?;
+    Ok(success_return(
+        load_admin_user_download_quota(user, download_service.inner()).await?,
+    ))
+}
+
+#[post(
+    "/api/admin-actions/user-save/rollback",
+    format = "json",
+    data = "<payload>"
+)]
+pub(super) async fn admin_api_user_save_rollback(
+    payload: Json<AdminUserSaveRollbackPayload>,
+    pool: &State<DbPool>,
+    save_service: &State<SaveService>,
+    auth: WebAuth<'_>,
+) -> RouteResult<AdminActionResponse> {
+    require_admin_api(auth, pool.inner()).await?;
+    let user = resolve_admin_user(
+        payload.user_id,
+        clean_optional_payload_text(&payload.name),
+        clean_optional_payload_text(&payload.user_code),
+        pool.inner(),
+    )
+    .await?;
+    save_service
+        .rollback_save(user.user_id, payload.history_id)
+        .await?;
+    Ok(success_return(AdminActionResponse {
+        message: format!("已将 {} 的存档回滚到版本 {}", user.name, payload.history_id),
+        affected_rows: 1,
+    }))
+}
+
+/// Longest impersonation reason kept in the audit log.
+const MAX_IMPERSONATION_REASON_CHARS: usize = 255;
+
+/// Mint a short-lived game token for a player so support can reproduce
+/// client issues from their account; the reason is required and every token
+/// is written to the impersonation log.
+#[post(
+    "/api/admin-actions/user-impersonate",
+    format = "json",
+    data = "<payload>"
+)]
+pub(super) async fn admin_api_user_impersonate(
+    payload: Json<AdminUserImpersonatePayload>,
+    client_ip: Option<IpAddr>,
+    pool: &State<DbPool>,
+    user_service: &State<UserService>,
+    auth: WebAuth<'_>,
+) -> RouteResult<AdminImpersonationTokenResponse> {
+    let session = require_admin_api(auth, pool.inner()).await?;
+    let reason = payload.reason.trim();
+    if reason.is_empty() {
+        return Err(ArcError::input("请填写代登录原因"));
+    }
+    if reason.chars().count() > MAX_IMPERSONATION_REASON_CHARS {
+        return Err(ArcError::input(format!(
+            "代登录原因不能超过 {MAX_IMPERSONATION_REASON_CHARS} 个字符"
+        )));
+    }
+    let user = resolve_admin_user(
+        payload.user_id,
+        clean_optional_payload_text(&payload.name),
+        clean_optional_payload_text(&payload.user_code),
+        pool.inner(),
+    )
+    .await?;
+
+    let token = user_service
+        .create_impersonation_token(
+            user.user_id,
+            session.user.user_id,
+            reason,
+            client_ip.map(|ip| ip.to_string()).as_deref(),
+        )
+        .await?;
+    Ok(success_return(AdminImpersonationTokenResponse {
+        user,
+        token: token.token,
+        expires_at: format_timestamp(Some(token.expires_at)),
+    }))
+}
+
+async fn load_admin_impersonation_log(
+    page: i64,
+    page_size: i64,
+    pool: &DbPool,
+) -> Result<AdminPageResponse<AdminImpersonationLogView>, ArcError> {
+    let total = sqlx::query_scalar!("SELECT COUNT(*) FROM impersonation_token")
+        .fetch_one(pool)
+        .await
+        .map_err(|err| ArcError::input(format!("查询代登录记录失败: {err}")))?;
+    let (page, offset) = clamp_page(page, page_size, total);
+
+    let now = Utc::now().timestamp_millis();
+    let rows = sqlx::query!(
+        "SELECT t.token_id, t.user_id, u.name, t.admin_user_id, a.name AS admin_name,
+                t.reason, t.ip, t.created_at, t.expires_at
+         FROM impersonation_token t
+         LEFT JOIN user u ON u.user_id = t.user_id
+         LEFT JOIN user a ON a.user_id = t.admin_user_id
+         ORDER BY t.created_at DESC
+         LIMIT ? OFFSET ?",
+        page_size,
+        offset
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|err| ArcError::input(format!("查询代登录记录失败: {err}")))?
+    .into_iter()
+    .map(|row| AdminImpersonationLogView {
+        token_id: row.token_id,
+        user_id: row.user_id,
+        name: row.name.unwrap_or_default(),
+        admin_user_id: row.admin_user_id,
+        admin_name: row.admin_name.unwrap_or_default(),
+        reason: row.reason,
+        ip: row.ip,
+        created_at: format_timestamp(Some(row.created_at)),
+        expires_at: format_timestamp(Some(row.expires_at)),
+        active: row.expires_at > now,
+    })
+    .collect();
+
+    Ok(page_response(rows, total, page, page_size))
+}
+
+#[get("/api/impersonation-log?<paging..>")]
+pub(super) async fn admin_api_impersonation_log(
+    paging: PageQuery,
+    pool: &State<DbPool>,
+    auth: WebAuth<'_>,
+) -> RouteResult<AdminPageResponse<AdminImpersonationLogView>> {
+    require_admin_api(auth, pool.inner()).await?;
+    let (page, page_size) = paging.normalized();
+    Ok(success_return(
+        load_admin_impersonation_log(page, page_size, pool.inner()).await?,
+    ))
+}
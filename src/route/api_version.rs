@@ -0,0 +1,115 @@
+//! Per-version shims for older game clients.
+//!
+//! Requests under a prefix from `game_api_versions` are rewritten to a
+//! renamed path, or rerouted to [`api_version_stub`] for endpoints this
+//! server no longer serves; JSON responses get the version's field renames.
+
+use crate::route::common::{success_return, ApiResponse};
+use crate::service::api_version::{rename_fields, GameApiVersions, VersionShim};
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::uri::Origin;
+use rocket::http::{ContentType, Method, Status};
+use rocket::request::{self, FromRequest, Outcome};
+use rocket::{get, Data, Request, Response};
+use serde_json::Value;
+use std::io::Cursor;
+
+/// Internal path that stubbed endpoints are rewritten to.
+pub const API_VERSION_STUB_PATH: &str = "/__api_version_stub";
+
+/// Version whose prefix the request came in on, for the response phase.
+struct MatchedVersion(Option<usize>);
+
+/// Value of the stubbed endpoint the request was rewritten from.
+struct StubValue(Option<Value>);
+
+/// Guard for [`api_version_stub`], only present on rewritten requests.
+pub struct VersionStub(Value);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for VersionStub {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        match request.local_cache(|| StubValue(None)) {
+            StubValue(Some(value)) => Outcome::Success(VersionStub(value.clone())),
+            StubValue(None) => Outcome::Forward(Status::NotFound),
+        }
+    }
+}
+
+/// Fairing that applies [`GameApiVersions`] shims.
+pub struct ApiVersionShim {
+    versions: GameApiVersions,
+}
+
+impl ApiVersionShim {
+    pub fn new(versions: GameApiVersions) -> Self {
+        Self { versions }
+    }
+}
+
+#[rocket::async_trait]
+impl Fairing for ApiVersionShim {
+    fn info(&self) -> Info {
+        Info {
+            name: "Game API version shims",
+            kind: Kind::Request | Kind::Response,
+        }
+    }
+
+    async fn on_request(&self, request: &mut Request<'_>, _: &mut Data<'_>) {
+        let path = request.uri().path().as_str().to_string();
+        let Some((index, _)) = self.versions.find(&path) else {
+            return;
+        };
+        request.local_cache(|| MatchedVersion(Some(index)));
+
+        match self.versions.shim(&path) {
+            Some(VersionShim::Stub(value)) => {
+                request.local_cache(|| StubValue(Some(value)));
+                request.set_method(Method::Get);
+                request.set_uri(Origin::parse(API_VERSION_STUB_PATH).expect("valid static path"));
+            }
+            Some(VersionShim::Alias(target)) => {
+                let uri = match request.uri().query() {
+                    Some(query) => format!("{target}?{query}"),
+                    None => target,
+                };
+                match Origin::parse_owned(uri) {
+                    Ok(uri) => request.set_uri(uri),
+                    Err(e) => log::warn!("Invalid path alias for `{path}`: {e}"),
+                }
+            }
+            None => {}
+        }
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        let MatchedVersion(Some(index)) = request.local_cache(|| MatchedVersion(None)) else {
+            return;
+        };
+        let renames = self.versions.renames(*index);
+        if renames.is_empty() || response.content_type() != Some(ContentType::JSON) {
+            return;
+        }
+
+        let Ok(body) = response.body_mut().to_string().await else {
+            return;
+        };
+        let body = match serde_json::from_str::<Value>(&body) {
+            Ok(mut value) => {
+                rename_fields(&mut value, renames);
+                value.to_string()
+            }
+            Err(_) => body,
+        };
+        response.set_sized_body(body.len(), Cursor::new(body));
+    }
+}
+
+/// Fixed response for an endpoint stubbed by [`ApiVersionShim`].
+#[get("/__api_version_stub")]
+pub async fn api_version_stub(stub: VersionStub) -> ApiResponse<Value> {
+    success_return(stub.0)
+}
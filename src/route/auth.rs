@@ -1,6 +1,7 @@
 use crate::context::ClientContext;
 use crate::model::UserLoginDto;
 use crate::model::{AuthResponse, LoginRequest};
+use crate::route::common::AuthGuard;
 use crate::service::UserService;
 use crate::ArcError;
 
@@ -43,6 +44,7 @@ pub async fn login<'a>(
         name: name.to_string(),
         password: password.to_string(),
         device_id: ctx.get_header("DeviceId").cloned(),
+        device_model: ctx.user_agent.map(str::to_string),
     };
 
     let ip = ctx.get_client_ip();
@@ -59,7 +61,33 @@ pub async fn login<'a>(
     Ok(response)
 }
 
+/// Access token refresh endpoint
+///
+/// Exchanges the bearer token for a new one on the same session, restarting
+/// its `login_token_ttl` lifetime. Expired tokens are rejected by the guard
+/// with error 108, after which the client has to log in again.
+#[post("/refresh")]
+pub async fn refresh<'a>(
+    user_service: &State<UserService>,
+    _auth: AuthGuard,
+    ctx: ClientContext<'_>,
+) -> Result<AuthResponse<'a>, ArcError> {
+    let token = ctx
+        .authorization
+        .and_then(|value| value.get(7..))
+        .ok_or_else(|| ArcError::no_access("Missing Authorization header", -4))?;
+
+    let user_auth = user_service.refresh_token(token).await?;
+
+    Ok(AuthResponse {
+        success: true,
+        token_type: "Bearer",
+        user_id: user_auth.user_id,
+        access_token: user_auth.token,
+    })
+}
+
 /// Get all others routes
 pub fn routes() -> Vec<Route> {
-    routes![login]
+    routes![login, refresh]
 }
@@ -0,0 +1,80 @@
+//! Client version gating for the game API.
+//!
+//! Requests whose `AppVersion` is outside the supported range are rerouted to
+//! [`client_outdated`] or [`client_unsupported`], which return the official
+//! "update required" and "new version, please wait" errors instead of letting
+//! the client hit routes it cannot talk to.
+
+use crate::error::ArcError;
+use crate::route::common::RouteResult;
+use crate::service::client_version::{ClientVersionPolicy, VersionRejection};
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::uri::Origin;
+use rocket::http::Method;
+use rocket::{get, Data, Request};
+
+/// Internal path that outdated clients are rewritten to.
+pub const CLIENT_OUTDATED_PATH: &str = "/__client_outdated";
+/// Internal path that clients newer than the server are rewritten to.
+pub const CLIENT_UNSUPPORTED_PATH: &str = "/__client_unsupported";
+
+/// Fairing that applies a [`ClientVersionPolicy`] to game API paths.
+pub struct ClientVersionGate {
+    policy: ClientVersionPolicy,
+    prefixes: Vec<String>,
+}
+
+impl ClientVersionGate {
+    /// Gate requests under the given game API prefixes.
+    pub fn new(policy: ClientVersionPolicy, prefixes: Vec<String>) -> Self {
+        Self { policy, prefixes }
+    }
+
+    fn is_gated(&self, path: &str) -> bool {
+        self.prefixes.iter().any(|prefix| {
+            prefix == "/" || path == prefix || path.starts_with(&format!("{prefix}/"))
+        })
+    }
+}
+
+#[rocket::async_trait]
+impl Fairing for ClientVersionGate {
+    fn info(&self) -> Info {
+        Info {
+            name: "Client version gate",
+            kind: Kind::Request,
+        }
+    }
+
+    async fn on_request(&self, request: &mut Request<'_>, _: &mut Data<'_>) {
+        let path = request.uri().path().as_str();
+        if !self.is_gated(path) {
+            return;
+        }
+
+        let app_version = request.headers().get_one("AppVersion");
+        let target = match self.policy.check(app_version) {
+            Ok(()) => return,
+            Err(VersionRejection::TooOld) => CLIENT_OUTDATED_PATH,
+            Err(VersionRejection::TooNew) => CLIENT_UNSUPPORTED_PATH,
+        };
+        log::info!("Rejected client version {app_version:?} on `{path}`");
+        request.set_method(Method::Get);
+        request.set_uri(Origin::parse(target).expect("valid static path"));
+    }
+}
+
+/// Response for clients below the supported version range.
+#[get("/__client_outdated")]
+pub async fn client_outdated() -> RouteResult<()> {
+    Err(ArcError::low_version("Invalid app version", 5))
+}
+
+/// Response for clients above the supported version range.
+#[get("/__client_unsupported")]
+pub async fn client_unsupported() -> RouteResult<()> {
+    Err(ArcError::low_version(
+        "This app version is not supported by the server yet",
+        9,
+    ))
+}
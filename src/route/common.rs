@@ -1,4 +1,5 @@
 use crate::error::ArcError;
+use crate::i18n::{localize_error, request_languages};
 use rocket::http::{ContentType, Status};
 use rocket::response::Responder;
 use rocket::{Request, Response};
@@ -76,7 +77,7 @@ where
 
 /// Implement Responder for ArcError
 impl<'r> Responder<'r, 'static> for ArcError {
-    fn respond_to(self, _: &'r Request<'_>) -> rocket::response::Result<'static> {
+    fn respond_to(self, request: &'r Request<'_>) -> rocket::response::Result<'static> {
         let status = match self.status() {
             200 => Status::Ok,
             400 => Status::BadRequest,
@@ -91,8 +92,8 @@ impl<'r> Responder<'r, 'static> for ArcError {
         let error_response = ApiErrorResponse {
             success: false,
             error_code: self.error_code(),
-            message: Some(self.to_string()),
-            extra: self.extra_data().cloned(),
+            message: Some(localize_error(&self, &request_languages(request))),
+            extra: self.extra_data(),
         };
 
         let json =
@@ -149,6 +150,10 @@ use rocket::outcome::Outcome;
 /// Request guard for authentication
 use rocket::request::{self, FromRequest};
 
+/// Authenticated routes that stay reachable before the current terms of
+/// service are accepted.
+const TOS_EXEMPT_ROUTES: &[&str] = &["tos_status", "tos_accept", "logout", "user_delete"];
+
 /// Authentication guard that extracts user ID from Authorization header
 pub struct AuthGuard {
     pub user_id: i32,
@@ -172,25 +177,87 @@ impl<'r> FromRequest<'r> for AuthGuard {
                     None => {
                         return Outcome::Error((
                             Status::InternalServerError,
-                            ArcError::no_access("UserService not available", -4),
+                            ArcError::no_access("UserService not available", -4)
+                                .for_catcher(request),
                         ));
                     }
                 };
 
                 // Validate the token
-                match user_service.authenticate_token(token).await {
-                    Ok(user_id) => Outcome::Success(AuthGuard { user_id }),
-                    Err(e) => Outcome::Error((Status::Unauthorized, e)),
+                let user_id = match user_service.authenticate_token(token).await {
+                    Ok(user_id) => user_id,
+                    Err(e) => {
+                        return Outcome::Error((Status::Unauthorized, e.for_catcher(request)))
+                    }
+                };
+
+                // Require the current terms of service, except on the routes
+                // used to read and accept them.
+                if let Some(tos_service) = request.rocket().state::<crate::service::TosService>() {
+                    let exempt = request
+                        .route()
+                        .and_then(|route| route.name.as_deref())
+                        .is_some_and(|name| TOS_EXEMPT_ROUTES.contains(&name));
+                    if !exempt {
+                        if let Err(e) = tos_service.ensure_accepted(user_id).await {
+                            return Outcome::Error((Status::Forbidden, e.for_catcher(request)));
+                        }
+                    }
                 }
+
+                // The first authenticated call of the day grants the daily
+                // login bonus; failures must not block the request.
+                user_service.grant_login_bonus(user_id).await;
+
+                crate::route::request_log::set_request_user(request, user_id);
+                Outcome::Success(AuthGuard { user_id })
             }
             None => Outcome::Error((
                 Status::Unauthorized,
-                ArcError::no_access("Missing Authorization header", -4),
+                ArcError::no_access("Missing Authorization header", -4).for_catcher(request),
             )),
         }
     }
 }
 
+/// Download mirror chosen for the requesting client, `None` when no
+/// [`CdnRegions`](crate::service::CdnRegions) region matches.
+pub struct DownloadMirror<'r>(pub Option<&'r crate::service::cdn::CdnRegion>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for DownloadMirror<'r> {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        let Some(regions) = request.rocket().state::<crate::service::CdnRegions>() else {
+            return Outcome::Success(DownloadMirror(None));
+        };
+        if !regions.is_enabled() {
+            return Outcome::Success(DownloadMirror(None));
+        }
+
+        let requested = request.headers().get_one(regions.region_header());
+        let ip = match request.guard::<crate::context::IpContext>().await {
+            Outcome::Success(ctx) => ctx.ip.and_then(|ip| ip.parse().ok()),
+            _ => None,
+        };
+        Outcome::Success(DownloadMirror(regions.select(requested, ip)))
+    }
+}
+
+/// The running Rocket instance, for handlers that look up managed state by
+/// type at runtime.
+pub struct ManagedState<'r>(pub &'r rocket::Rocket<rocket::Orbit>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for ManagedState<'r> {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        Outcome::Success(ManagedState(request.rocket()))
+    }
+}
+
 /// CORS fairing for handling cross-origin requests
 use rocket::fairing::{Fairing, Info, Kind};
 use rocket::http::Header;
@@ -221,6 +288,232 @@ impl Fairing for CORS {
     }
 }
 
+/// Binary replay download, served as an attachment
+#[derive(Debug, Clone)]
+pub struct ReplayFile {
+    pub file_name: String,
+    pub bytes: Vec<u8>,
+}
+
+impl<'r> Responder<'r, 'static> for ReplayFile {
+    fn respond_to(self, _: &'r Request<'_>) -> rocket::response::Result<'static> {
+        Response::build()
+            .status(Status::Ok)
+            .header(ContentType::Binary)
+            .raw_header(
+                "Content-Disposition",
+                format!("attachment; filename=\"{}\"", self.file_name),
+            )
+            .sized_body(self.bytes.len(), Cursor::new(self.bytes))
+            .ok()
+    }
+}
+
+/// Personal data export download, served as a JSON attachment
+#[derive(Debug, Clone)]
+pub struct ExportFile {
+    pub file_name: String,
+    pub bytes: Vec<u8>,
+}
+
+impl<'r> Responder<'r, 'static> for ExportFile {
+    fn respond_to(self, _: &'r Request<'_>) -> rocket::response::Result<'static> {
+        Response::build()
+            .status(Status::Ok)
+            .header(ContentType::JSON)
+            .raw_header(
+                "Content-Disposition",
+                format!("attachment; filename=\"{}\"", self.file_name),
+            )
+            .sized_body(self.bytes.len(), Cursor::new(self.bytes))
+            .ok()
+    }
+}
+
+/// Value of the `Range` header, if the client sent one
+pub struct ByteRange<'r>(pub Option<&'r str>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for ByteRange<'r> {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        Outcome::Success(ByteRange(request.headers().get_one("Range")))
+    }
+}
+
+/// Part of a file selected by a `Range` header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RangeSelection {
+    Full,
+    /// Inclusive byte offsets
+    Partial(u64, u64),
+    Unsatisfiable,
+}
+
+/// Resolve a single `bytes=` range against a file of `len` bytes
+///
+/// Malformed headers and multi-range requests fall back to the whole file,
+/// which RFC 9110 allows servers to do.
+fn select_range(header: Option<&str>, len: u64) -> RangeSelection {
+    let Some(spec) = header.and_then(|header| header.trim().strip_prefix("bytes=")) else {
+        return RangeSelection::Full;
+    };
+    if spec.contains(',') {
+        return RangeSelection::Full;
+    }
+    let Some((start, end)) = spec.trim().split_once('-') else {
+        return RangeSelection::Full;
+    };
+
+    match (start.trim(), end.trim()) {
+        ("", suffix) => match suffix.parse::<u64>() {
+            Ok(0) => RangeSelection::Unsatisfiable,
+            Ok(_) if len == 0 => RangeSelection::Unsatisfiable,
+            Ok(suffix) => RangeSelection::Partial(len.saturating_sub(suffix), len - 1),
+            Err(_) => RangeSelection::Full,
+        },
+        (start, end) => {
+            let Ok(start) = start.parse::<u64>() else {
+                return RangeSelection::Full;
+            };
+            let end = match end {
+                "" => u64::MAX,
+                end => match end.parse::<u64>() {
+                    Ok(end) if end >= start => end,
+                    _ => return RangeSelection::Full,
+                },
+            };
+            if start >= len {
+                RangeSelection::Unsatisfiable
+            } else {
+                RangeSelection::Partial(start, end.min(len - 1))
+            }
+        }
+    }
+}
+
+/// File body cut to the selected range
+///
+/// Rocket only seeks a body to discover its size, which never happens here
+/// because the length is always preset.
+struct RangeBody(tokio::io::Take<tokio::fs::File>);
+
+impl tokio::io::AsyncRead for RangeBody {
+    fn poll_read(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        buf: &mut tokio::io::ReadBuf<'_>,
+    ) -> std::task::Poll<std::io::Result<()>> {
+        std::pin::Pin::new(&mut self.0).poll_read(cx, buf)
+    }
+}
+
+impl tokio::io::AsyncSeek for RangeBody {
+    fn start_seek(
+        mut self: std::pin::Pin<&mut Self>,
+        position: std::io::SeekFrom,
+    ) -> std::io::Result<()> {
+        std::pin::Pin::new(self.0.get_mut()).start_seek(position)
+    }
+
+    fn poll_complete(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<std::io::Result<u64>> {
+        std::pin::Pin::new(self.0.get_mut()).poll_complete(cx)
+    }
+}
+
+/// File download that honours a single `Range` request
+///
+/// Answers `206 Partial Content` for a satisfiable range, `416` for one past
+/// the end of the file and the whole file otherwise, streaming from disk
+/// either way so interrupted downloads can be resumed.
+pub struct RangedFile {
+    status: Status,
+    content_type: ContentType,
+    content_range: Option<String>,
+    body: Option<RangeBody>,
+    length: u64,
+}
+
+impl RangedFile {
+    /// Open `path` and position it at the range requested by `range`
+    pub async fn open(
+        path: impl AsRef<std::path::Path>,
+        range: &ByteRange<'_>,
+    ) -> std::io::Result<Self> {
+        use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+        let path = path.as_ref();
+        let mut file = tokio::fs::File::open(path).await?;
+        let len = file.metadata().await?.len();
+        let content_type = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .and_then(ContentType::from_extension)
+            .unwrap_or(ContentType::Binary);
+
+        let (status, content_range, start, length) = match select_range(range.0, len) {
+            RangeSelection::Full => (Status::Ok, None, 0, len),
+            RangeSelection::Partial(start, end) => (
+                Status::PartialContent,
+                Some(format!("bytes {start}-{end}/{len}")),
+                start,
+                end - start + 1,
+            ),
+            RangeSelection::Unsatisfiable => {
+                return Ok(Self {
+                    status: Status::RangeNotSatisfiable,
+                    content_type,
+                    content_range: Some(format!("bytes */{len}")),
+                    body: None,
+                    length: 0,
+                });
+            }
+        };
+        if start > 0 {
+            file.seek(std::io::SeekFrom::Start(start)).await?;
+        }
+
+        Ok(Self {
+            status,
+            content_type,
+            content_range,
+            body: Some(RangeBody(file.take(length))),
+            length,
+        })
+    }
+
+    /// Bytes sent in the response body
+    pub fn body_length(&self) -> u64 {
+        self.length
+    }
+
+    /// Whether only part of the file is sent, e.g. a resumed download
+    pub fn is_partial(&self) -> bool {
+        self.status != Status::Ok
+    }
+}
+
+impl<'r> Responder<'r, 'static> for RangedFile {
+    fn respond_to(self, _: &'r Request<'_>) -> rocket::response::Result<'static> {
+        let mut response = Response::build();
+        response
+            .status(self.status)
+            .header(self.content_type)
+            .raw_header("Accept-Ranges", "bytes");
+        if let Some(content_range) = self.content_range {
+            response.raw_header("Content-Range", content_range);
+        }
+        if let Some(body) = self.body {
+            response.sized_body(usize::try_from(self.length).ok(), body);
+        }
+        response.ok()
+    }
+}
+
 /// Result type alias for route handlers
 pub type RouteResult<T> = Result<ApiResponse<T>, ArcError>;
 
@@ -246,3 +539,72 @@ pub struct GameInfoResponse {
 /// Empty response for endpoints that don't return data
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct EmptyResponse {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_select_range_bounded() {
+        assert_eq!(
+            select_range(Some("bytes=0-99"), 1000),
+            RangeSelection::Partial(0, 99)
+        );
+        assert_eq!(
+            select_range(Some("bytes=500-"), 1000),
+            RangeSelection::Partial(500, 999)
+        );
+        // `end` past the file is clamped to the last byte.
+        assert_eq!(
+            select_range(Some("bytes=900-5000"), 1000),
+            RangeSelection::Partial(900, 999)
+        );
+    }
+
+    #[test]
+    fn test_select_range_suffix() {
+        assert_eq!(
+            select_range(Some("bytes=-100"), 1000),
+            RangeSelection::Partial(900, 999)
+        );
+        assert_eq!(
+            select_range(Some("bytes=-5000"), 1000),
+            RangeSelection::Partial(0, 999)
+        );
+        assert_eq!(
+            select_range(Some("bytes=-0"), 1000),
+            RangeSelection::Unsatisfiable
+        );
+        assert_eq!(
+            select_range(Some("bytes=-10"), 0),
+            RangeSelection::Unsatisfiable
+        );
+    }
+
+    #[test]
+    fn test_select_range_unsatisfiable() {
+        assert_eq!(
+            select_range(Some("bytes=1000-"), 1000),
+            RangeSelection::Unsatisfiable
+        );
+        assert_eq!(
+            select_range(Some("bytes=0-"), 0),
+            RangeSelection::Unsatisfiable
+        );
+    }
+
+    #[test]
+    fn test_select_range_falls_back_to_full() {
+        assert_eq!(select_range(None, 1000), RangeSelection::Full);
+        assert_eq!(
+            select_range(Some("bytes=200-100"), 1000),
+            RangeSelection::Full
+        );
+        assert_eq!(
+            select_range(Some("bytes=0-9,20-29"), 1000),
+            RangeSelection::Full
+        );
+        assert_eq!(select_range(Some("items=0-9"), 1000), RangeSelection::Full);
+        assert_eq!(select_range(Some("bytes=abc"), 1000), RangeSelection::Full);
+    }
+}
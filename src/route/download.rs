@@ -1,5 +1,5 @@
 use crate::error::{ArcError, ArcResult};
-use crate::route::common::AuthGuard;
+use crate::route::common::{AuthGuard, ByteRange, DownloadMirror, RangedFile};
 use crate::route::{success_return, RouteResult};
 use crate::service::download::DownloadService;
 use crate::service::user::UserService;
@@ -10,30 +10,45 @@ use serde_json::Value;
 ///
 /// This endpoint allows users to download song files. It supports both
 /// getting file checksums and download URLs. When URLs are requested,
-/// it enforces download rate limits.
+/// it enforces the per-user download quota (error 903).
 ///
 /// Query Parameters:
 /// - sid: List of song IDs to download (optional, defaults to all songs)
 /// - url: Whether to include download URLs (optional, defaults to true)
+/// - ttl: Lifetime of the issued links in seconds (optional, capped by the
+///   configured `download_time_gap_limit`)
 ///
 /// Returns download information including file checksums and optionally URLs
-#[get("/serve/download/me/song?<sid>&<url>")]
+#[get("/serve/download/me/song?<sid>&<url>&<ttl>")]
 pub async fn download_song(
     user_auth: AuthGuard,
     download_service: &State<DownloadService>,
     user_service: &State<UserService>,
+    mirror: DownloadMirror<'_>,
     sid: Option<Vec<String>>,
     url: Option<String>,
+    ttl: Option<i64>,
 ) -> RouteResult<Value> {
     // Parse the url parameter, default to true
     let include_urls = !matches!(url.as_deref(), Some("false") | Some("0"));
 
+    if include_urls
+        && download_service
+            .check_download_limit(user_auth.user_id)
+            .await?
+    {
+        return Err(ArcError::rate_limit(
+            "You have reached the download limit.".to_string(),
+            903,
+        ));
+    }
+
     // Get user information
     let user = user_service.get_user_info(user_auth.user_id).await?;
 
     // Generate download list
     let download_data = download_service
-        .generate_download_list(&user, sid, include_urls)
+        .generate_download_list(&user, sid, include_urls, mirror.0, ttl)
         .await?;
 
     Ok(success_return(serde_json::json!(download_data)))
@@ -50,18 +65,37 @@ pub async fn download_song(
 ///
 /// Query Parameters:
 /// - t: Download token for validation
+///
+/// Honours a single `Range` header so interrupted downloads can resume.
+/// Every response counts against the token owner's download quota, and the
+/// owner must still own the song when the file is fetched.
 #[get("/download/<song_id>/<file_name>?<t>")]
 pub async fn serve_download_file(
     download_service: &State<DownloadService>,
+    user_service: &State<UserService>,
+    range: ByteRange<'_>,
     song_id: String,
     file_name: String,
     t: String,
-) -> ArcResult<Vec<u8>> {
+) -> ArcResult<RangedFile> {
     // Validate the download token
-    let (_user_id, _token_time) = download_service
+    let (user_id, _token_time) = download_service
         .validate_download_token(&song_id, &file_name, &t)
         .await?;
 
+    if download_service.check_download_limit(user_id).await? {
+        return Err(ArcError::rate_limit(
+            "You have reached the download limit.".to_string(),
+            903,
+        ));
+    }
+
+    // A pack or single may have been refunded since the link was issued
+    let user = user_service.get_user_info(user_id).await?;
+    download_service
+        .ensure_song_downloadable(&user, &song_id)
+        .await?;
+
     // Check if the file is available for download
     if !download_service.is_available_file(&song_id, &file_name) {
         return Err(ArcError::no_access(
@@ -70,12 +104,23 @@ pub async fn serve_download_file(
         ));
     }
 
-    // Read and return the file content
+    // Stream the file content
     let file_path = format!("./songs/{song_id}/{file_name}");
-    match std::fs::read(&file_path) {
-        Ok(content) => Ok(content),
-        Err(_) => Err(ArcError::no_data("File not found".to_string(), 404)),
+    let file = RangedFile::open(&file_path, &range)
+        .await
+        .map_err(|_| ArcError::no_data("File not found".to_string(), 404))?;
+
+    // A resumed download only adds its bytes, not another file.
+    let files = if file.is_partial() { 0 } else { 1 };
+    let bytes = i64::try_from(file.body_length()).unwrap_or(i64::MAX);
+    if let Err(e) = download_service
+        .record_download(user_id, files, bytes)
+        .await
+    {
+        log::warn!("Failed to record download for user {user_id}: {e}");
     }
+
+    Ok(file)
 }
 
 /// Download routes
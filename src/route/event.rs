@@ -0,0 +1,52 @@
+use crate::route::common::{success_return, AuthGuard, RouteResult};
+use crate::service::event::{CommunityProgress, EventInfo, EventLadderEntry, DEFAULT_LADDER_LIMIT};
+use crate::service::EventService;
+use rocket::{get, routes, Route, State};
+
+/// Running events endpoint
+///
+/// Returns the events currently running, with their charts, reward tiers
+/// and the authenticated user's points.
+#[get("/event/me")]
+pub async fn event_info(
+    event_service: &State<EventService>,
+    auth: AuthGuard,
+) -> RouteResult<Vec<EventInfo>> {
+    let events = event_service.get_active_events(auth.user_id).await?;
+    Ok(success_return(events))
+}
+
+/// Event ladder endpoint
+///
+/// Returns the top players of an event by points.
+#[get("/event/<event_id>/ladder?<limit>")]
+pub async fn event_ladder(
+    event_service: &State<EventService>,
+    _auth: AuthGuard,
+    event_id: &str,
+    limit: Option<i64>,
+) -> RouteResult<Vec<EventLadderEntry>> {
+    let ladder = event_service
+        .get_ladder(event_id, limit.unwrap_or(DEFAULT_LADDER_LIMIT))
+        .await?;
+    Ok(success_return(ladder))
+}
+
+/// Community goal endpoint
+///
+/// Returns the event's shared counters with every community milestone and
+/// whether it has been reached.
+#[get("/event/<event_id>/community")]
+pub async fn event_community(
+    event_service: &State<EventService>,
+    _auth: AuthGuard,
+    event_id: &str,
+) -> RouteResult<CommunityProgress> {
+    let progress = event_service.get_community_progress(event_id).await?;
+    Ok(success_return(progress))
+}
+
+/// Get all event routes
+pub fn routes() -> Vec<Route> {
+    routes![event_info, event_ladder, event_community]
+}
@@ -0,0 +1,58 @@
+//! Leaderboard federation endpoint (`/federation`).
+//!
+//! Peers push signed batches of anonymized chart bests here; see
+//! [`FederationService`] for the payload and signing scheme.
+
+use crate::error::ArcError;
+use crate::route::common::{success_return, RouteResult};
+use crate::service::federation::SIGNATURE_HEADER;
+use crate::service::FederationService;
+use rocket::data::{Data, ToByteUnit};
+use rocket::http::Status;
+use rocket::outcome::Outcome;
+use rocket::request::{self, FromRequest, Request};
+use rocket::{post, routes, Route, State};
+use serde_json::{json, Value};
+
+/// Signature header of a federation request.
+pub struct FederationSignature(String);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for FederationSignature {
+    type Error = ArcError;
+
+    async fn from_request(request: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        match request.headers().get_one(SIGNATURE_HEADER) {
+            Some(signature) => Outcome::Success(FederationSignature(signature.to_string())),
+            None => Outcome::Error((
+                Status::Unauthorized,
+                ArcError::no_access("Missing federation signature.", 108).for_catcher(request),
+            )),
+        }
+    }
+}
+
+/// Receive chart bests from a peer
+#[post("/scores", data = "<data>")]
+pub async fn federation_scores(
+    federation_service: &State<FederationService>,
+    signature: FederationSignature,
+    data: Data<'_>,
+) -> RouteResult<Value> {
+    let body = data
+        .open(4.mebibytes())
+        .into_bytes()
+        .await
+        .map_err(ArcError::from)?;
+    if !body.is_complete() {
+        return Err(ArcError::input("Federation batch is too large."));
+    }
+
+    let stored = federation_service.ingest(&body, &signature.0).await?;
+    Ok(success_return(json!({ "stored": stored })))
+}
+
+/// Get all federation routes
+pub fn routes() -> Vec<Route> {
+    routes![federation_scores]
+}
@@ -0,0 +1,29 @@
+//! Probe endpoints (`/healthz`, `/readyz`) for load balancers and k8s.
+
+use crate::service::health::{HealthService, ReadinessReport};
+use rocket::http::Status;
+use rocket::serde::json::Json;
+use rocket::{get, routes, Route, State};
+use serde_json::{json, Value};
+
+/// Liveness: the process is up and serving requests.
+#[get("/healthz")]
+pub fn healthz() -> Json<Value> {
+    Json(json!({ "status": "ok" }))
+}
+
+/// Readiness: 200 when every configured dependency is usable, 503 otherwise.
+#[get("/readyz")]
+pub async fn readyz(health: &State<HealthService>) -> (Status, Json<ReadinessReport>) {
+    let report = health.readiness().await;
+    let status = if report.is_ready() {
+        Status::Ok
+    } else {
+        Status::ServiceUnavailable
+    };
+    (status, Json(report))
+}
+
+pub fn routes() -> Vec<Route> {
+    routes![healthz, readyz]
+}
@@ -1,17 +1,27 @@
+pub mod access;
 pub mod admin;
+pub mod api_version;
 pub mod auth;
+pub mod client_version;
 pub mod common;
 pub mod course;
 pub mod download;
+pub mod event;
+pub mod federation;
 pub mod friend;
+pub mod health;
 pub mod legacy;
 pub mod mission;
 pub mod multiplayer;
 
 pub mod others;
+pub mod player;
 pub mod present;
+pub mod profile;
 pub mod purchase;
+pub mod request_log;
 pub mod score;
+pub mod song;
 pub mod user;
 pub mod world;
 
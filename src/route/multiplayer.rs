@@ -97,6 +97,8 @@ pub async fn multiplayer_update(
 /// Room invite endpoint
 ///
 /// Python baseline: `POST /multiplayer/me/room/<room_code>/invite`
+/// The receiver must be a friend of the sender; the invite is delivered
+/// through `GET /notification/me`.
 // `/room/join/invite` can match both this route and `room_join`, so keep this
 // route at a lower priority to avoid Rocket route collision at launch.
 #[post(
@@ -111,13 +113,13 @@ pub async fn room_invite(
     room_code: String,
     request: Form<RoomInviteRequest>,
 ) -> RouteResult<EmptyResponse> {
+    let _ = multiplayer_service.user_linkplay_name(request.to).await?;
     let share_token = multiplayer_service
         .room_invite_share_token(&room_code)
         .await?;
-    let _ = multiplayer_service.user_linkplay_name(request.to).await?;
     let sender_name = multiplayer_service.user_linkplay_name(auth.user_id).await?;
     notification_service
-        .create_room_invite(auth.user_id, sender_name, request.to, share_token)
+        .send_room_invite(auth.user_id, sender_name, request.to, share_token)
         .await?;
     Ok(success_return(EmptyResponse::default()))
 }
@@ -3,19 +3,23 @@ use crate::error::ArcError;
 use crate::model::{
     AggregateCall, AggregateResponse, AggregateValue, InsightCompleteResponse, NotificationResponse,
 };
-use crate::route::common::{success_return, AuthGuard, EmptyResponse, RouteResult};
+use crate::route::common::{
+    success_return, AuthGuard, ByteRange, DownloadMirror, EmptyResponse, ManagedState, RangedFile,
+    RouteResult,
+};
 use crate::service::aggregate::*;
 use crate::service::bundle::BundleDownloadResponse;
+use crate::service::user::InsightStep;
 use crate::service::{
-    BundleService, CharacterService, DownloadService, NotificationService, PresentService,
-    PurchaseService, ScoreService, UserService, WorldService,
+    BundleService, CharacterService, NotificationHub, NotificationService, UserService,
 };
-use rocket::fs::NamedFile;
+use rocket::form::Form;
 use rocket::http::Status;
 
 use rocket::response::status;
+use rocket::response::stream::{Event, EventStream};
 use rocket::serde::json::{Json, Value};
-use rocket::{get, post, routes, Route, State};
+use rocket::{delete, get, post, routes, FromForm, Route, Shutdown, State};
 use std::collections::HashMap;
 use url::Url;
 use urlencoding::decode;
@@ -33,8 +37,7 @@ pub async fn game_info() -> RouteResult<Value> {
 
 /// User notifications endpoint
 ///
-/// Returns a list of notifications for the authenticated user.
-/// Currently returns an empty list as notification system is not implemented.
+/// Returns pending room invites for the authenticated user and clears them.
 #[get("/notification/me")]
 pub async fn notification_me(
     notification_service: &State<NotificationService>,
@@ -46,6 +49,78 @@ pub async fn notification_me(
     Ok(success_return(notifications))
 }
 
+/// Live notification stream
+///
+/// Server-sent events for the authenticated user: stored room invites first,
+/// then room invites (`room_inv`) and claimable presents (`present`) as they
+/// happen. Room invites delivered here are not stored for `/notification/me`,
+/// which stays the fallback for clients that are not connected.
+#[get("/notification/me/stream")]
+pub async fn notification_stream(
+    notification_service: &State<NotificationService>,
+    auth: AuthGuard,
+    mut shutdown: Shutdown,
+) -> Result<EventStream![], ArcError> {
+    // Subscribe before draining so nothing sent in between is lost.
+    let mut subscription = NotificationHub::global().subscribe(auth.user_id);
+    let pending = notification_service
+        .get_user_notifications(auth.user_id)
+        .await?;
+
+    Ok(EventStream! {
+        for notification in pending {
+            yield Event::json(&notification).event("room_inv");
+        }
+        loop {
+            let notification = rocket::tokio::select! {
+                notification = subscription.recv() => notification,
+                _ = &mut shutdown => break,
+            };
+            let Some(notification) = notification else {
+                break;
+            };
+            yield Event::json(&notification.data).event(notification.event);
+        }
+    })
+}
+
+/// Push token request payload
+#[derive(Debug, FromForm)]
+pub struct PushTokenRequest {
+    /// `fcm` or `apns`
+    pub platform: String,
+    pub token: String,
+}
+
+/// Push token registration endpoint
+///
+/// Registers a companion app device to be alerted about room invites and
+/// claimable presents.
+#[post("/notification/me/push_token", data = "<request>")]
+pub async fn push_token_register(
+    notification_service: &State<NotificationService>,
+    auth: AuthGuard,
+    request: Form<PushTokenRequest>,
+) -> RouteResult<EmptyResponse> {
+    notification_service
+        .register_push_token(auth.user_id, &request.platform, &request.token)
+        .await?;
+    Ok(success_return(EmptyResponse::default()))
+}
+
+/// Push token removal endpoint
+#[delete("/notification/me/push_token", data = "<request>")]
+pub async fn push_token_unregister(
+    notification_service: &State<NotificationService>,
+    auth: AuthGuard,
+    request: Form<PushTokenRequest>,
+) -> RouteResult<EmptyResponse> {
+    notification_service
+        .unregister_push_token(auth.user_id, &request.platform, &request.token)
+        .await?;
+    Ok(success_return(EmptyResponse::default()))
+}
+
 /// Content bundle endpoint
 ///
 /// Returns hot update/bundle information for the client.
@@ -53,6 +128,7 @@ pub async fn notification_me(
 #[get("/game/content_bundle")]
 pub async fn game_content_bundle(
     version_ctx: VersionContext<'_>,
+    mirror: DownloadMirror<'_>,
     bundle_service: &State<BundleService>,
 ) -> RouteResult<BundleDownloadResponse> {
     let app_version = match version_ctx.app_version {
@@ -60,18 +136,18 @@ pub async fn game_content_bundle(
         None => {
             return Ok(success_return(BundleDownloadResponse {
                 ordered_results: Vec::new(),
+                patch: None,
             }))
         }
     };
-    let ordered_results = bundle_service
-        .get_bundle_list(
+    let response = bundle_service
+        .get_bundle_update(
             app_version,
             version_ctx.bundle_version,
             version_ctx.device_id,
+            mirror.0,
         )
         .await?;
-
-    let response = BundleDownloadResponse { ordered_results };
     Ok(success_return(response))
 }
 
@@ -123,41 +199,16 @@ pub async fn finale_end(
 /// Different pack IDs trigger different rewards and state changes.
 #[post("/insight/me/complete/<pack_id>")]
 pub async fn insight_complete(
-    character_service: &State<CharacterService>,
     user_service: &State<UserService>,
     auth: AuthGuard,
     pack_id: String,
 ) -> RouteResult<InsightCompleteResponse> {
-    let new_insight_state = match pack_id.as_str() {
-        "eden_append_1" => {
-            // Grant Insight (Ascendant - 8th Seeker) character (ID: 72)
-            character_service
-                .grant_insight_ascendant(auth.user_id)
-                .await?;
-            // Update user insight_state to 1
-            user_service
-                .update_user_insight_state(auth.user_id, 1)
-                .await?;
-            1
-        }
-        "lephon" => {
-            // Update user insight_state to 3
-            user_service
-                .update_user_insight_state(auth.user_id, 3)
-                .await?;
-            3
-        }
-        _ => {
-            // Python baseline: `ArcError("Invalid pack_id", 151, status=404)`
-            return Err(ArcError::Base {
-                message: "Invalid pack_id".to_string(),
-                error_code: 151,
-                api_error_code: -999,
-                extra_data: None,
-                status: 404,
-            });
-        }
-    };
+    // Python baseline: `ArcError("Invalid pack_id", 151, status=404)`
+    let step = InsightStep::from_pack_id(&pack_id)
+        .ok_or_else(|| ArcError::not_found("Invalid pack_id"))?;
+    let new_insight_state = user_service
+        .advance_insight_state(auth.user_id, step)
+        .await?;
 
     Ok(success_return(InsightCompleteResponse {
         insight_state: new_insight_state,
@@ -198,24 +249,27 @@ pub async fn applog_me(_log_data: Json<serde_json::Value>) -> RouteResult<EmptyR
 /// Aggregate request endpoint
 ///
 /// Handles integrated requests that combine multiple API calls.
-/// Processes up to 10 requests in a single call for efficiency.
+/// Processes up to 10 requests in a single call for efficiency. Inner calls
+/// are dispatched through the managed [`AggregateRegistry`].
 ///
 /// Python baseline notes:
 /// - This endpoint itself does **not** require authentication.
-/// - Each inner endpoint may require `Authorization: Bearer <token>`.
-/// - Invalid payload / too many calls / unknown inner endpoint behaves like `error_return()`
+/// - Each inner endpoint may require `Authorization: Bearer <token>`; a
+///   missing or rejected token fails the whole batch.
+/// - Invalid payload / too many calls behaves like `error_return()`
 ///   (HTTP 500, `{"success": false, "error_code": 108}`).
+///
+/// Unlike the Python server, an unknown or failing inner call only yields an
+/// error entry (`{"id", "success": false, "error_code"}`) in `value`; the
+/// other calls still run.
 #[get("/compose/aggregate?<calls>")]
-#[allow(clippy::too_many_arguments)]
 pub async fn aggregate(
     calls: String,
+    registry: &State<AggregateRegistry>,
     user_service: &State<UserService>,
-    score_service: &State<ScoreService>,
-    download_service: &State<DownloadService>,
-    present_service: &State<PresentService>,
-    world_service: &State<WorldService>,
-    purchase_service: &State<PurchaseService>,
+    managed: ManagedState<'_>,
     ctx: ClientContext<'_>,
+    mirror: DownloadMirror<'_>,
 ) -> Result<AggregateResponse, ArcError> {
     // Parse the calls parameter as JSON.
     // If parsing fails, propagate as HTTP 500 with error_code 108 (matches Python's error_return()).
@@ -231,19 +285,23 @@ pub async fn aggregate(
 
     // Process each request
     for call in call_list {
-        let endpoint_url = Url::parse(&format!("http://localhost{}", call.endpoint))
-            .map_err(|_| ArcError::rocket_err("Unknown Error"))?;
+        let Ok(endpoint_url) = Url::parse(&format!("http://localhost{}", call.endpoint)) else {
+            let e = ArcError::rocket_err("Unknown Error");
+            response_values.push(AggregateValue::error(call.id, &e));
+            continue;
+        };
 
         let path = endpoint_url.path();
         let query_params = parse_query_params(endpoint_url.query().unwrap_or(""));
 
-        // Only a few endpoints are unauthenticated in the Python implementation.
-        let requires_auth = !matches!(
-            path,
-            "/game/info" | "/finale/progress" | "/purchase/bundle/bundle"
-        );
+        let Some(endpoint) = registry.get(path) else {
+            log::warn!("Unknown aggregate endpoint `{path}`");
+            let e = ArcError::rocket_err("Unknown Error");
+            response_values.push(AggregateValue::error(call.id, &e));
+            continue;
+        };
 
-        let user_id = if requires_auth {
+        let user_id = if endpoint.requires_auth {
             match cached_user_id {
                 Some(id) => id,
                 None => {
@@ -274,7 +332,7 @@ pub async fn aggregate(
                                 value: None,
                                 error_code: Some(e.error_code()),
                                 id: call.id,
-                                extra: e.extra_data().cloned(),
+                                extra: e.extra_data(),
                             });
                         }
                     }
@@ -284,43 +342,17 @@ pub async fn aggregate(
             0
         };
 
-        // Route to appropriate handler based on path
-        let result = match path {
-            "/user/me" => handle_user_me(user_service, user_id).await,
-            "/purchase/bundle/pack" => handle_bundle_pack(purchase_service, user_id).await,
-            "/serve/download/me/song" => {
-                handle_download_song(download_service, user_service, user_id, &query_params).await
-            }
-            "/game/info" => handle_game_info().await,
-            "/present/me" => handle_present_info(present_service, user_id).await,
-            "/world/map/me" => handle_world_all(world_service, user_id).await,
-            "/score/song/friend" => {
-                handle_song_score_friend(score_service, user_service, user_id, &query_params).await
-            }
-            "/finale/progress" => handle_finale_progress().await,
-            "/purchase/bundle/bundle" => handle_bundle_bundle().await,
-            "/purchase/bundle/single" => handle_bundle_single(purchase_service, user_id).await,
-            // Unknown endpoint: behave like Python's KeyError path (error_return(), HTTP 500).
-            _ => return Err(ArcError::rocket_err("Unknown Error")),
+        let request = AggregateRequest {
+            rocket: managed.0,
+            user_id,
+            query: &query_params,
+            mirror: mirror.0,
         };
-
-        match result {
-            Ok(value) => {
-                response_values.push(AggregateValue {
-                    id: call.id.clone(),
-                    value,
-                });
-            }
+        match (endpoint.handler)(&request).await {
+            Ok(value) => response_values.push(AggregateValue::ok(call.id, value)),
             Err(e) => {
                 log::warn!("{e}");
-                // Return error response immediately on first error
-                return Ok(AggregateResponse {
-                    success: false,
-                    value: None,
-                    error_code: Some(e.error_code()),
-                    id: call.id,
-                    extra: e.extra_data().cloned(),
-                });
+                response_values.push(AggregateValue::error(call.id, &e));
             }
         }
     }
@@ -333,20 +365,21 @@ pub async fn aggregate(
         extra: None,
     };
 
-    // log::info!("resp: {}", &serde_json::to_string(&response).unwrap());
     Ok(response)
 }
 
 /// Bundle download endpoint
 ///
 /// Serves bundle files (JSON and CB) using download tokens.
-/// Handles rate limiting for bundle files and token validation.
+/// Handles rate limiting for bundle files and token validation, and honours
+/// a single `Range` header so interrupted downloads can resume.
 #[get("/bundle_download/<token>")]
 pub async fn bundle_download(
     bundle_service: &State<BundleService>,
     token: &str,
     ctx: ClientContext<'_>,
-) -> Result<NamedFile, status::Custom<String>> {
+    range: ByteRange<'_>,
+) -> Result<RangedFile, status::Custom<String>> {
     // Get client IP for rate limiting
     let client_ip = ctx.get_client_ip().unwrap_or("127.0.0.1");
 
@@ -357,7 +390,7 @@ pub async fn bundle_download(
         Ok(file_path) => {
             // Get the bundle file path directly from the service
             match bundle_service.get_bundle_file_path(&file_path).await {
-                Ok(full_path) => match NamedFile::open(full_path).await {
+                Ok(full_path) => match RangedFile::open(full_path, &range).await {
                     Ok(file) => Ok(file),
                     Err(_) => Err(status::Custom(
                         Status::NotFound,
@@ -411,6 +444,9 @@ pub fn routes() -> Vec<Route> {
     routes![
         game_info,
         notification_me,
+        notification_stream,
+        push_token_register,
+        push_token_unregister,
         game_content_bundle,
         finale_progress,
         finale_start,
@@ -431,6 +467,9 @@ pub fn game_routes() -> Vec<Route> {
     routes![
         game_info,
         notification_me,
+        notification_stream,
+        push_token_register,
+        push_token_unregister,
         game_content_bundle,
         finale_progress,
         finale_start,
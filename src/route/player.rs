@@ -0,0 +1,702 @@
+//! Player self-service web dashboard (`/me`).
+//!
+//! Server-rendered pages where players log in with their game credentials to
+//! view scores and achievements, change password/email, manage logged-in
+//! devices and claim web-only presents. Sessions are a signed cookie scoped
+//! to `/me`, bound to the password hash so a password change ends every web
+//! session.
+//!
+//! Shared machines can log in without a password: `/me/link` shows a short
+//! code that is redeemed from a game session (`POST /me/web_link`) or from
+//! the dashboard on a device that is already logged in.
+
+use crate::config::CONFIG;
+use crate::error::ArcError;
+use crate::service::profile::{format_time, ProfileScore, ProfileService, PublicProfile};
+use crate::service::web_link::WebLinkStatus;
+use crate::service::{AchievementService, PresentService, UserService, WebLinkService};
+use crate::DbPool;
+use askama::Template;
+use rocket::form::Form;
+use rocket::http::{Cookie, CookieJar, SameSite, Status};
+use rocket::request::FlashMessage;
+use rocket::response::content::RawHtml;
+use rocket::response::{Flash, Redirect};
+use rocket::{get, post, routes, FromForm, Responder, Route, State};
+use sha2::{Digest, Sha256};
+
+use super::profile::site_title;
+
+const PLAYER_COOKIE: &str = "arcaea_player_session";
+/// Cookie holding `<code>:<secret>` of a pending short-code login.
+const LINK_COOKIE: &str = "arcaea_player_link";
+const SCORES_PAGE_SIZE: i64 = 50;
+
+/// Logged-in player resolved from the session cookie.
+struct PlayerSession {
+    user_id: i32,
+}
+
+#[derive(Responder)]
+pub enum PageResponse {
+    Page(RawHtml<String>),
+    Redirect(Redirect),
+    Flash(Flash<Redirect>),
+    Error(Status),
+}
+
+type FlashRedirect = Flash<Redirect>;
+
+fn session_signature(user_id: i32, password_hash: &str) -> String {
+    let joined = format!("{user_id}:{password_hash}:{}", CONFIG.secret_key);
+    format!("{:x}", Sha256::digest(joined.as_bytes()))
+}
+
+fn set_player_cookie(cookies: &CookieJar<'_>, user_id: i32, password_hash: &str) {
+    let value = format!("{user_id}:{}", session_signature(user_id, password_hash));
+    let mut cookie = Cookie::new(PLAYER_COOKIE, value);
+    cookie.set_http_only(true);
+    cookie.set_same_site(SameSite::Lax);
+    cookie.set_path("/me");
+    cookies.add(cookie);
+}
+
+fn clear_cookie(cookies: &CookieJar<'_>, name: &'static str) {
+    let mut cookie = Cookie::from(name);
+    cookie.set_path("/me");
+    cookies.remove(cookie);
+}
+
+/// Log the browser in as `user_id`.
+async fn start_player_session(
+    cookies: &CookieJar<'_>,
+    pool: &DbPool,
+    user_id: i32,
+) -> Result<(), ArcError> {
+    let password_hash = sqlx::query_scalar!("SELECT password FROM user WHERE user_id = ?", user_id)
+        .fetch_one(pool)
+        .await?
+        .unwrap_or_default();
+    if password_hash.is_empty() {
+        return Err(ArcError::user_ban(
+            format!("The account `{user_id}` has been banned."),
+            106,
+        ));
+    }
+
+    set_player_cookie(cookies, user_id, &password_hash);
+    Ok(())
+}
+
+async fn current_player(
+    cookies: &CookieJar<'_>,
+    pool: &DbPool,
+) -> Result<Option<PlayerSession>, ArcError> {
+    let Some(cookie) = cookies.get(PLAYER_COOKIE) else {
+        return Ok(None);
+    };
+    let Some((user_id, signature)) = cookie
+        .value()
+        .split_once(':')
+        .and_then(|(id, signature)| Some((id.parse::<i32>().ok()?, signature)))
+    else {
+        return Ok(None);
+    };
+
+    let password_hash = sqlx::query_scalar!("SELECT password FROM user WHERE user_id = ?", user_id)
+        .fetch_optional(pool)
+        .await?
+        .flatten()
+        .unwrap_or_default();
+    if password_hash.is_empty() || signature != session_signature(user_id, &password_hash) {
+        return Ok(None);
+    }
+
+    Ok(Some(PlayerSession { user_id }))
+}
+
+/// Resolve the session for a form POST, or redirect to the login page.
+async fn require_player(
+    cookies: &CookieJar<'_>,
+    pool: &DbPool,
+) -> Result<PlayerSession, FlashRedirect> {
+    match current_player(cookies, pool).await {
+        Ok(Some(session)) => Ok(session),
+        Ok(None) => Err(Flash::error(Redirect::to("/me"), "Please log in first.")),
+        Err(e) => Err(Flash::error(Redirect::to("/me"), e.to_string())),
+    }
+}
+
+fn render<T: Template>(page: T) -> PageResponse {
+    match page.render() {
+        Ok(html) => PageResponse::Page(RawHtml(html)),
+        Err(e) => {
+            log::error!("Failed to render player page: {e}");
+            PageResponse::Error(Status::InternalServerError)
+        }
+    }
+}
+
+fn page_error(e: ArcError) -> PageResponse {
+    log::error!("Failed to load player page: {e}");
+    PageResponse::Error(Status::InternalServerError)
+}
+
+fn flash_pair(flash: Option<FlashMessage<'_>>) -> Option<(String, String)> {
+    flash.map(|flash| (flash.kind().to_string(), flash.message().to_string()))
+}
+
+/// Turn a service result into a redirect back to `to` with a flash message.
+fn flash_result<T>(to: &'static str, result: Result<T, ArcError>, success: &str) -> FlashRedirect {
+    match result {
+        Ok(_) => Flash::success(Redirect::to(to), success),
+        Err(e) => Flash::error(Redirect::to(to), e.to_string()),
+    }
+}
+
+#[derive(Template)]
+#[template(path = "me/login.html")]
+struct LoginPage {
+    site_title: String,
+    logged_in: bool,
+    flash: Option<(String, String)>,
+}
+
+#[derive(Template)]
+#[template(path = "me/link.html")]
+struct LinkPage {
+    site_title: String,
+    logged_in: bool,
+    flash: Option<(String, String)>,
+    code: String,
+}
+
+#[derive(Template)]
+#[template(path = "me/index.html")]
+struct IndexPage {
+    site_title: String,
+    logged_in: bool,
+    flash: Option<(String, String)>,
+    profile: PublicProfile,
+}
+
+#[derive(Template)]
+#[template(path = "me/scores.html")]
+struct ScoresPage {
+    site_title: String,
+    logged_in: bool,
+    flash: Option<(String, String)>,
+    scores: Vec<ProfileScore>,
+    page: i64,
+    has_next: bool,
+}
+
+struct DeviceRow {
+    name: String,
+    device_id: Option<String>,
+    login_time: String,
+    login_ip: String,
+}
+
+#[derive(Template)]
+#[template(path = "me/account.html")]
+struct AccountPage {
+    site_title: String,
+    logged_in: bool,
+    flash: Option<(String, String)>,
+    devices: Vec<DeviceRow>,
+}
+
+struct PresentRow {
+    present_id: String,
+    description: String,
+    items: String,
+    expire_time: String,
+}
+
+#[derive(Template)]
+#[template(path = "me/presents.html")]
+struct PresentsPage {
+    site_title: String,
+    logged_in: bool,
+    flash: Option<(String, String)>,
+    presents: Vec<PresentRow>,
+}
+
+struct AchievementRow {
+    name: String,
+    description: String,
+    progress: String,
+    reward: String,
+    achieved_time: Option<String>,
+}
+
+#[derive(Template)]
+#[template(path = "me/achievements.html")]
+struct AchievementsPage {
+    site_title: String,
+    logged_in: bool,
+    flash: Option<(String, String)>,
+    achievements: Vec<AchievementRow>,
+}
+
+/// Dashboard overview, or the login form when not logged in
+#[get("/")]
+pub async fn player_index(
+    cookies: &CookieJar<'_>,
+    flash: Option<FlashMessage<'_>>,
+    pool: &State<DbPool>,
+    profile_service: &State<ProfileService>,
+) -> PageResponse {
+    let session = match current_player(cookies, pool.inner()).await {
+        Ok(session) => session,
+        Err(e) => return page_error(e),
+    };
+    let Some(session) = session else {
+        return render(LoginPage {
+            site_title: site_title(),
+            logged_in: false,
+            flash: flash_pair(flash),
+        });
+    };
+
+    match profile_service.get_player_profile(session.user_id).await {
+        Ok(profile) => render(IndexPage {
+            site_title: site_title(),
+            logged_in: true,
+            flash: flash_pair(flash),
+            profile,
+        }),
+        Err(e) => page_error(e),
+    }
+}
+
+#[derive(FromForm)]
+pub struct PlayerLoginForm {
+    name: String,
+    password: String,
+}
+
+/// Log in with game credentials
+#[post("/login", data = "<form>")]
+pub async fn player_login(
+    form: Form<PlayerLoginForm>,
+    cookies: &CookieJar<'_>,
+    pool: &State<DbPool>,
+    user_service: &State<UserService>,
+) -> FlashRedirect {
+    let user_id = match user_service
+        .check_credentials(form.name.trim(), &form.password)
+        .await
+    {
+        Ok(user_id) => user_id,
+        Err(ArcError::UserBan { .. }) => {
+            return Flash::error(Redirect::to("/me"), "This account is banned.")
+        }
+        Err(_) => return Flash::error(Redirect::to("/me"), "Incorrect username or password."),
+    };
+
+    let result = start_player_session(cookies, pool.inner(), user_id).await;
+    flash_result("/me", result, "Logged in.")
+}
+
+/// End the web session
+#[post("/logout")]
+pub fn player_logout(cookies: &CookieJar<'_>) -> FlashRedirect {
+    clear_cookie(cookies, PLAYER_COOKIE);
+    Flash::success(Redirect::to("/me"), "Logged out.")
+}
+
+/// Start a short-code login for this browser
+#[post("/link/start")]
+pub async fn player_link_start(
+    cookies: &CookieJar<'_>,
+    web_link_service: &State<WebLinkService>,
+) -> Redirect {
+    let ticket = web_link_service.start().await;
+    let mut cookie = Cookie::new(LINK_COOKIE, format!("{}:{}", ticket.code, ticket.secret));
+    cookie.set_http_only(true);
+    cookie.set_same_site(SameSite::Lax);
+    cookie.set_path("/me");
+    cookies.add(cookie);
+    Redirect::to("/me/link")
+}
+
+/// Show the pending short code; logs in once it has been redeemed
+#[get("/link")]
+pub async fn player_link(
+    cookies: &CookieJar<'_>,
+    flash: Option<FlashMessage<'_>>,
+    pool: &State<DbPool>,
+    web_link_service: &State<WebLinkService>,
+) -> PageResponse {
+    let Some((code, secret)) = cookies.get(LINK_COOKIE).and_then(|cookie| {
+        cookie
+            .value()
+            .split_once(':')
+            .map(|(code, secret)| (code.to_string(), secret.to_string()))
+    }) else {
+        return PageResponse::Redirect(Redirect::to("/me"));
+    };
+
+    match web_link_service.poll(&code, &secret).await {
+        WebLinkStatus::Pending => render(LinkPage {
+            site_title: site_title(),
+            logged_in: false,
+            flash: flash_pair(flash),
+            code,
+        }),
+        WebLinkStatus::Approved(user_id) => {
+            clear_cookie(cookies, LINK_COOKIE);
+            let result = start_player_session(cookies, pool.inner(), user_id).await;
+            PageResponse::Flash(flash_result("/me", result, "Logged in."))
+        }
+        WebLinkStatus::Expired => {
+            clear_cookie(cookies, LINK_COOKIE);
+            PageResponse::Flash(Flash::error(
+                Redirect::to("/me"),
+                "The login code has expired.",
+            ))
+        }
+    }
+}
+
+#[derive(FromForm)]
+pub struct LinkApproveForm {
+    code: String,
+}
+
+/// Redeem a login code shown on another browser
+#[post("/link/approve", data = "<form>")]
+pub async fn player_link_approve(
+    form: Form<LinkApproveForm>,
+    cookies: &CookieJar<'_>,
+    pool: &State<DbPool>,
+    web_link_service: &State<WebLinkService>,
+) -> FlashRedirect {
+    let session = match require_player(cookies, pool.inner()).await {
+        Ok(session) => session,
+        Err(redirect) => return redirect,
+    };
+
+    let result = web_link_service.approve(&form.code, session.user_id).await;
+    flash_result("/me/account", result, "The other browser is now logged in.")
+}
+
+/// All best scores, paginated by rating
+#[get("/scores?<page>")]
+pub async fn player_scores(
+    page: Option<i64>,
+    cookies: &CookieJar<'_>,
+    flash: Option<FlashMessage<'_>>,
+    pool: &State<DbPool>,
+    profile_service: &State<ProfileService>,
+) -> PageResponse {
+    let session = match current_player(cookies, pool.inner()).await {
+        Ok(Some(session)) => session,
+        Ok(None) => return PageResponse::Redirect(Redirect::to("/me")),
+        Err(e) => return page_error(e),
+    };
+    let page = page.unwrap_or(1).max(1);
+
+    // Fetch one extra row to know whether a next page exists.
+    let mut scores = match profile_service
+        .get_best_scores(
+            session.user_id,
+            (page - 1) * SCORES_PAGE_SIZE,
+            SCORES_PAGE_SIZE + 1,
+        )
+        .await
+    {
+        Ok(scores) => scores,
+        Err(e) => return page_error(e),
+    };
+    let has_next = scores.len() as i64 > SCORES_PAGE_SIZE;
+    scores.truncate(SCORES_PAGE_SIZE as usize);
+
+    render(ScoresPage {
+        site_title: site_title(),
+        logged_in: true,
+        flash: flash_pair(flash),
+        scores,
+        page,
+        has_next,
+    })
+}
+
+/// Password, email and device management
+#[get("/account")]
+pub async fn player_account(
+    cookies: &CookieJar<'_>,
+    flash: Option<FlashMessage<'_>>,
+    pool: &State<DbPool>,
+    user_service: &State<UserService>,
+) -> PageResponse {
+    let session = match current_player(cookies, pool.inner()).await {
+        Ok(Some(session)) => session,
+        Ok(None) => return PageResponse::Redirect(Redirect::to("/me")),
+        Err(e) => return page_error(e),
+    };
+
+    let devices = match user_service.get_login_sessions(session.user_id).await {
+        Ok(sessions) => sessions
+            .into_iter()
+            .map(|login| DeviceRow {
+                name: login
+                    .login_device
+                    .clone()
+                    .unwrap_or_else(|| "Unknown device".to_string()),
+                device_id: login.login_device,
+                login_time: format_time(login.login_time),
+                login_ip: login.login_ip.unwrap_or_default(),
+            })
+            .collect(),
+        Err(e) => return page_error(e),
+    };
+
+    render(AccountPage {
+        site_title: site_title(),
+        logged_in: true,
+        flash: flash_pair(flash),
+        devices,
+    })
+}
+
+#[derive(FromForm)]
+pub struct PasswordChangeForm {
+    current_password: String,
+    new_password: String,
+    confirm_password: String,
+}
+
+/// Change password; logs out every game session
+#[post("/account/password", data = "<form>")]
+pub async fn player_change_password(
+    form: Form<PasswordChangeForm>,
+    cookies: &CookieJar<'_>,
+    pool: &State<DbPool>,
+    user_service: &State<UserService>,
+) -> FlashRedirect {
+    let session = match require_player(cookies, pool.inner()).await {
+        Ok(session) => session,
+        Err(redirect) => return redirect,
+    };
+    if form.new_password != form.confirm_password {
+        return Flash::error(
+            Redirect::to("/me/account"),
+            "The new passwords do not match.",
+        );
+    }
+
+    if let Err(e) = user_service
+        .change_password(session.user_id, &form.current_password, &form.new_password)
+        .await
+    {
+        return Flash::error(Redirect::to("/me/account"), e.to_string());
+    }
+
+    // The session cookie is bound to the old password hash; issue a new one.
+    if let Err(e) = start_player_session(cookies, pool.inner(), session.user_id).await {
+        return Flash::error(Redirect::to("/me"), e.to_string());
+    }
+    Flash::success(
+        Redirect::to("/me/account"),
+        "Password changed. All devices have been logged out.",
+    )
+}
+
+#[derive(FromForm)]
+pub struct EmailChangeForm {
+    email: String,
+    password: String,
+}
+
+/// Change email address
+#[post("/account/email", data = "<form>")]
+pub async fn player_change_email(
+    form: Form<EmailChangeForm>,
+    cookies: &CookieJar<'_>,
+    pool: &State<DbPool>,
+    user_service: &State<UserService>,
+) -> FlashRedirect {
+    let session = match require_player(cookies, pool.inner()).await {
+        Ok(session) => session,
+        Err(redirect) => return redirect,
+    };
+
+    let result = user_service
+        .change_email(session.user_id, &form.password, form.email.trim())
+        .await;
+    flash_result("/me/account", result, "Email address changed.")
+}
+
+#[derive(FromForm)]
+pub struct DeviceRevokeForm {
+    device: Option<String>,
+}
+
+/// Log out every game session of one device
+#[post("/devices/revoke", data = "<form>")]
+pub async fn player_revoke_device(
+    form: Form<DeviceRevokeForm>,
+    cookies: &CookieJar<'_>,
+    pool: &State<DbPool>,
+    user_service: &State<UserService>,
+) -> FlashRedirect {
+    let session = match require_player(cookies, pool.inner()).await {
+        Ok(session) => session,
+        Err(redirect) => return redirect,
+    };
+
+    let result = user_service
+        .revoke_login_device(session.user_id, form.device.as_deref())
+        .await;
+    flash_result("/me/account", result, "Device logged out.")
+}
+
+/// Web-only presents waiting for the player
+#[get("/presents")]
+pub async fn player_presents(
+    cookies: &CookieJar<'_>,
+    flash: Option<FlashMessage<'_>>,
+    pool: &State<DbPool>,
+    present_service: &State<PresentService>,
+) -> PageResponse {
+    let session = match current_player(cookies, pool.inner()).await {
+        Ok(Some(session)) => session,
+        Ok(None) => return PageResponse::Redirect(Redirect::to("/me")),
+        Err(e) => return page_error(e),
+    };
+
+    let presents = match present_service.get_user_web_presents(session.user_id).await {
+        Ok(presents) => presents
+            .into_iter()
+            .map(|present| PresentRow {
+                items: present
+                    .items
+                    .unwrap_or_default()
+                    .iter()
+                    .map(|item| format!("{} x{}", item.item_id, item.amount))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                description: present
+                    .description
+                    .filter(|description| !description.is_empty())
+                    .unwrap_or_else(|| present.present_id.clone()),
+                expire_time: present
+                    .expire_ts
+                    .map(|ts| format_time(Some(ts)))
+                    .unwrap_or_else(|| "Never".to_string()),
+                present_id: present.present_id,
+            })
+            .collect(),
+        Err(e) => return page_error(e),
+    };
+
+    render(PresentsPage {
+        site_title: site_title(),
+        logged_in: true,
+        flash: flash_pair(flash),
+        presents,
+    })
+}
+
+/// Achievements with the player's progress
+#[get("/achievements")]
+pub async fn player_achievements(
+    cookies: &CookieJar<'_>,
+    flash: Option<FlashMessage<'_>>,
+    pool: &State<DbPool>,
+    achievement_service: &State<AchievementService>,
+) -> PageResponse {
+    let session = match current_player(cookies, pool.inner()).await {
+        Ok(Some(session)) => session,
+        Ok(None) => return PageResponse::Redirect(Redirect::to("/me")),
+        Err(e) => return page_error(e),
+    };
+
+    let achievements = match achievement_service
+        .get_user_achievements(session.user_id)
+        .await
+    {
+        Ok(achievements) => achievements
+            .into_iter()
+            .map(|achievement| AchievementRow {
+                progress: if achievement.target > 0 {
+                    format!("{} / {}", achievement.progress, achievement.target)
+                } else {
+                    "-".to_string()
+                },
+                reward: match &achievement.reward_type {
+                    Some(reward_type) if achievement.reward_amount > 0 => format!(
+                        "{} x{}",
+                        achievement.reward_item_id.as_deref().unwrap_or(reward_type),
+                        achievement.reward_amount
+                    ),
+                    _ => "-".to_string(),
+                },
+                achieved_time: achievement.achieved_at.map(|ts| format_time(Some(ts))),
+                name: achievement.name,
+                description: achievement.description,
+            })
+            .collect(),
+        Err(e) => return page_error(e),
+    };
+
+    render(AchievementsPage {
+        site_title: site_title(),
+        logged_in: true,
+        flash: flash_pair(flash),
+        achievements,
+    })
+}
+
+#[derive(FromForm)]
+pub struct PresentClaimForm {
+    present_id: String,
+}
+
+/// Claim a web-only present
+#[post("/presents/claim", data = "<form>")]
+pub async fn player_claim_present(
+    form: Form<PresentClaimForm>,
+    cookies: &CookieJar<'_>,
+    pool: &State<DbPool>,
+    present_service: &State<PresentService>,
+    user_service: &State<UserService>,
+) -> FlashRedirect {
+    let session = match require_player(cookies, pool.inner()).await {
+        Ok(session) => session,
+        Err(redirect) => return redirect,
+    };
+
+    let result = present_service
+        .claim_web_present(session.user_id, &form.present_id)
+        .await;
+    if result.is_ok() {
+        user_service
+            .invalidate_user_collection_cache(session.user_id)
+            .await;
+    }
+    flash_result("/me/presents", result, "Present claimed.")
+}
+
+/// Get all player dashboard routes
+pub fn routes() -> Vec<Route> {
+    routes![
+        player_index,
+        player_login,
+        player_logout,
+        player_link_start,
+        player_link,
+        player_link_approve,
+        player_scores,
+        player_account,
+        player_change_password,
+        player_change_email,
+        player_revoke_device,
+        player_presents,
+        player_claim_present,
+        player_achievements,
+    ]
+}
@@ -0,0 +1,73 @@
+//! Public player profile pages (`/profile`).
+//!
+//! Server-rendered, read-only pages for players who enabled
+//! `is_profile_public`; no login is required to view them.
+
+use crate::service::profile::{ProfileService, PublicProfile};
+use askama::Template;
+use rocket::http::Status;
+use rocket::response::content::RawHtml;
+use rocket::{get, routes, Route, State};
+use std::env;
+
+#[derive(Template)]
+#[template(path = "profile/player.html")]
+struct PlayerProfilePage {
+    site_title: String,
+    profile: PublicProfile,
+}
+
+#[derive(Template)]
+#[template(path = "profile/not_found.html")]
+struct ProfileNotFoundPage {
+    site_title: String,
+}
+
+pub(super) fn site_title() -> String {
+    env::var("TITLE")
+        .ok()
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+        .unwrap_or_else(|| "Arcaea Server".to_string())
+}
+
+fn render<T: Template>(page: T) -> Result<RawHtml<String>, Status> {
+    page.render().map(RawHtml).map_err(|e| {
+        log::error!("Failed to render profile page: {e}");
+        Status::InternalServerError
+    })
+}
+
+/// Public profile page
+///
+/// Shows B30, recent plays and potential for the player with `user_code`.
+#[get("/<user_code>")]
+pub async fn profile_page(
+    profile_service: &State<ProfileService>,
+    user_code: &str,
+) -> Result<(Status, RawHtml<String>), Status> {
+    match profile_service.get_public_profile(user_code).await {
+        Ok(profile) => Ok((
+            Status::Ok,
+            render(PlayerProfilePage {
+                site_title: site_title(),
+                profile,
+            })?,
+        )),
+        Err(e) if e.status() == 404 => Ok((
+            Status::NotFound,
+            render(ProfileNotFoundPage {
+                site_title: site_title(),
+            })?,
+        )),
+        Err(e) => {
+            log::error!("Failed to load profile `{user_code}`: {e}");
+            Err(Status::InternalServerError)
+        }
+    }
+}
+
+/// Get all public profile routes
+pub fn routes() -> Vec<Route> {
+    routes![profile_page]
+}
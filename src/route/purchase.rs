@@ -1,6 +1,12 @@
-use crate::route::common::{success_return, success_return_no_value, AuthGuard, RouteResult};
+use crate::error::ArcError;
+use crate::route::common::{
+    success_return, success_return_no_value, AuthGuard, EmptyResponse, RouteResult,
+};
+use crate::service::purchase::PAYMENT_SIGNATURE_HEADER;
 use crate::service::PurchaseService;
+use rocket::data::{Data, ToByteUnit};
 use rocket::form::Form;
+use rocket::request::{self, FromRequest, Request};
 use rocket::FromForm;
 use rocket::{get, post, routes, Route, State};
 use serde::{Deserialize, Serialize};
@@ -97,6 +103,20 @@ pub async fn bundle_bundle(purchase_service: &State<PurchaseService>) -> RouteRe
     Ok(success_return(bundles))
 }
 
+/// Get discounted pack purchases endpoint
+///
+/// Returns the packs whose discount window is open, priced for the user.
+#[get("/purchase/me/pack")]
+pub async fn discounted_packs(
+    purchase_service: &State<PurchaseService>,
+    auth: AuthGuard,
+) -> RouteResult<Vec<Value>> {
+    let packs = purchase_service
+        .get_discounted_pack_purchases(auth.user_id)
+        .await?;
+    Ok(success_return(packs))
+}
+
 /// Buy pack or single endpoint
 ///
 /// Handles the purchase of packs or singles, checking user tickets and granting items.
@@ -176,20 +196,65 @@ pub async fn redeem(
     let code = request
         .code
         .as_deref()
-        .ok_or_else(|| crate::error::ArcError::rocket_err("`code` is required"))?;
+        .ok_or_else(|| ArcError::rocket_err("`code` is required"))?;
     let result = purchase_service.redeem_code(auth.user_id, code).await?;
     Ok(success_return(result))
 }
 
+/// Signature header of a payment provider callback, if present.
+pub struct PaymentSignature(Option<String>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for PaymentSignature {
+    type Error = ArcError;
+
+    async fn from_request(request: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        request::Outcome::Success(PaymentSignature(
+            request
+                .headers()
+                .get_one(PAYMENT_SIGNATURE_HEADER)
+                .map(str::to_string),
+        ))
+    }
+}
+
+/// Payment provider callback endpoint
+///
+/// Called by the configured payment provider once an order is paid or
+/// cancelled; a paid order unlocks its pack or single.
+#[post("/purchase/callback/<provider>", data = "<data>")]
+pub async fn payment_callback(
+    purchase_service: &State<PurchaseService>,
+    provider: &str,
+    signature: PaymentSignature,
+    data: Data<'_>,
+) -> RouteResult<EmptyResponse> {
+    let body = data
+        .open(64.kibibytes())
+        .into_bytes()
+        .await
+        .map_err(ArcError::from)?;
+    if !body.is_complete() {
+        return Err(ArcError::input("Payment callback is too large."));
+    }
+
+    purchase_service
+        .handle_payment_callback(provider, &body, signature.0.as_deref())
+        .await?;
+    Ok(success_return_no_value())
+}
+
 /// Get all purchase routes
 pub fn routes() -> Vec<Route> {
     routes![
         bundle_pack,
         get_single,
         bundle_bundle,
+        discounted_packs,
         buy_pack_or_single,
         buy_special,
         purchase_stamina,
-        redeem
+        redeem,
+        payment_callback
     ]
 }
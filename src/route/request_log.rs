@@ -0,0 +1,111 @@
+//! Access log of the game API.
+//!
+//! Every request under a game API prefix is traced with its method, path,
+//! authenticated user, status and latency under the `api_access` target, and
+//! stored in `api_log` when [`ApiLogService`] is enabled. The path is the one
+//! the client sent, before any fairing rewrites it.
+
+use crate::route::access::request_ip;
+use crate::service::api_log::{ApiLogEntry, ApiLogService};
+use crate::utils::current_timestamp_ms;
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::{Data, Request, Response};
+use std::time::Instant;
+
+/// Mounts outside the game API, not logged even when a game prefix is `/`.
+const UNLOGGED_PREFIXES: &[&str] = &[
+    "/web",
+    "/api/v1/admin",
+    "/metrics",
+    "/profile",
+    "/me",
+    "/healthz",
+    "/readyz",
+];
+
+/// Fairing that records game API requests.
+pub struct RequestLog {
+    service: ApiLogService,
+    prefixes: Vec<String>,
+}
+
+/// Start of a logged request, in the request-local cache.
+struct RequestStart(Option<(Instant, String, String)>);
+
+/// User the request was authenticated as, set by `AuthGuard`.
+struct RequestUser(Option<i32>);
+
+/// Attribute the current request to `user_id` in the access log.
+pub fn set_request_user(request: &Request<'_>, user_id: i32) {
+    request.local_cache(|| RequestUser(Some(user_id)));
+}
+
+impl RequestLog {
+    /// Log requests under the given game API prefixes.
+    pub fn new(service: ApiLogService, prefixes: Vec<String>) -> Self {
+        Self { service, prefixes }
+    }
+
+    fn is_logged(&self, path: &str) -> bool {
+        let under = |prefix: &str| path == prefix || path.starts_with(&format!("{prefix}/"));
+        if UNLOGGED_PREFIXES.iter().any(|prefix| under(prefix)) {
+            return false;
+        }
+        self.prefixes
+            .iter()
+            .any(|prefix| prefix == "/" || under(prefix))
+    }
+}
+
+#[rocket::async_trait]
+impl Fairing for RequestLog {
+    fn info(&self) -> Info {
+        Info {
+            name: "Game API access log",
+            kind: Kind::Request | Kind::Response,
+        }
+    }
+
+    async fn on_request(&self, request: &mut Request<'_>, _: &mut Data<'_>) {
+        let path = request.uri().path().as_str();
+        if !self.is_logged(path) {
+            return;
+        }
+        let start = (
+            Instant::now(),
+            request.method().to_string(),
+            path.to_string(),
+        );
+        request.local_cache(|| RequestStart(Some(start)));
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        let RequestStart(Some((started_at, method, path))) =
+            request.local_cache(|| RequestStart(None))
+        else {
+            return;
+        };
+        let RequestUser(user_id) = *request.local_cache(|| RequestUser(None));
+        let latency_ms = started_at.elapsed().as_millis() as i64;
+        let status = response.status().code;
+
+        tracing::info!(
+            target: "api_access",
+            method = %method,
+            path = %path,
+            user_id = ?user_id,
+            status,
+            latency_ms,
+        );
+
+        self.service.record(ApiLogEntry {
+            method: method.clone(),
+            path: path.clone(),
+            user_id,
+            status,
+            latency_ms,
+            ip: request_ip(request).map(|ip| ip.to_string()),
+            created_at: current_timestamp_ms(),
+        });
+    }
+}
@@ -1,9 +1,13 @@
+use crate::config::CONFIG;
 use crate::error::ArcError;
 use crate::model::download::{CourseTokenRequest, ScoreSubmission, WorldTokenRequest};
-use crate::model::{CourseTokenResponse, WorldTokenResponse};
-use crate::route::common::AuthGuard;
+use crate::model::{CourseTokenResponse, PotentialRanking, UserWorldRank, WorldTokenResponse};
+use crate::route::common::{AuthGuard, ReplayFile};
 use crate::route::{success_return, RouteResult};
-use crate::service::score::ScoreService;
+use crate::service::replay::ReplayInfo;
+use crate::service::score::{LeaderboardPage, ScoreService};
+use crate::service::{AchievementService, EventService, FederationService, ReplayService};
+use rocket::data::{Data, ToByteUnit};
 use rocket::form::Form;
 use rocket::{get, post, routes, FromForm, Route, State};
 
@@ -26,6 +30,7 @@ pub struct ScoreSubmissionForm {
     pub clear_type: Option<i32>,
     pub beyond_gauge: Option<i32>,
     pub submission_hash: Option<String>,
+    pub time_played: Option<i64>,
     pub combo_interval_bonus: Option<i32>,
     pub hp_interval_bonus: Option<i32>,
     pub fever_bonus: Option<i32>,
@@ -60,6 +65,7 @@ impl TryFrom<ScoreSubmissionForm> for ScoreSubmission {
             clear_type: form.clear_type.ok_or_else(score_submission_error)?,
             beyond_gauge: form.beyond_gauge.ok_or_else(score_submission_error)?,
             submission_hash: form.submission_hash.ok_or_else(score_submission_error)?,
+            time_played: form.time_played,
             combo_interval_bonus: form.combo_interval_bonus,
             hp_interval_bonus: form.hp_interval_bonus,
             fever_bonus: form.fever_bonus,
@@ -88,7 +94,11 @@ pub fn routes() -> Vec<Route> {
         song_score_post,
         song_score_top,
         song_score_me,
-        song_score_friend
+        song_score_friend,
+        potential_rank,
+        world_rank,
+        song_score_replay_upload,
+        song_score_replay_download
     ]
 }
 
@@ -176,35 +186,99 @@ pub async fn score_token_course(
 ///
 /// This endpoint handles score submission for both world mode and course mode.
 /// It validates the score data, updates user records, calculates ratings,
-/// and manages recent30/best score records.
+/// and manages recent30/best score records. Achievement progress and event
+/// points are queued behind the play log on the score write queue; failures
+/// there do not fail the submission.
 #[post("/score/song", data = "<submission>")]
 pub async fn song_score_post(
     user_auth: AuthGuard,
     score_service: &State<ScoreService>,
+    achievement_service: &State<AchievementService>,
+    event_service: &State<EventService>,
     submission: Form<ScoreSubmissionForm>,
 ) -> RouteResult<HashMap<String, Value>> {
-    let submission = submission.into_inner().try_into()?;
+    let submission: ScoreSubmission = submission.into_inner().try_into()?;
+    let song_id = submission.song_id.clone();
+    let (difficulty, score, clear_type) = (
+        submission.difficulty,
+        submission.score,
+        submission.clear_type,
+    );
     let result = score_service
         .submit_score(user_auth.user_id, submission)
         .await?;
+    let world_progress = result
+        .get("progress")
+        .and_then(Value::as_f64)
+        .unwrap_or(0.0);
+    let user_id = user_auth.user_id;
+    let event_service = event_service.inner().clone();
+    score_service
+        .write_queue()
+        .defer("event points", async move {
+            event_service
+                .record_play(
+                    user_id,
+                    &song_id,
+                    difficulty,
+                    score,
+                    clear_type,
+                    world_progress,
+                )
+                .await
+        })
+        .await;
+    let achievement_service = achievement_service.inner().clone();
+    score_service
+        .write_queue()
+        .defer("achievements", async move {
+            achievement_service.evaluate(user_id).await.map(|_| ())
+        })
+        .await;
 
     Ok(success_return(result))
 }
 
-/// Get top 20 scores for a song
+/// Get top scores for a song
 ///
 /// This endpoint returns the highest 20 scores for a specific song and difficulty,
-/// including user information and rankings.
-#[get("/score/song?<song_id>&<difficulty>")]
+/// including user information and rankings. With federation enabled, entries
+/// from peer servers are merged in and every entry carries a `source` tag.
+///
+/// Web frontends can page through the whole chart with `offset` and `limit`
+/// (at most 100), or jump to a player with `around_user`. Every entry carries
+/// its absolute `rank`, which is the `offset` of the following page; paged
+/// results are local to this server.
+#[get("/score/song?<song_id>&<difficulty>&<offset>&<limit>&<around_user>")]
+#[allow(clippy::too_many_arguments)]
 pub async fn song_score_top(
     _user_auth: AuthGuard,
     score_service: &State<ScoreService>,
+    federation_service: &State<FederationService>,
     song_id: String,
     difficulty: i32,
+    offset: Option<i32>,
+    limit: Option<i32>,
+    around_user: Option<i32>,
 ) -> RouteResult<Vec<HashMap<String, Value>>> {
+    let page = LeaderboardPage {
+        offset,
+        limit,
+        around_user,
+    };
+    if !page.is_default() {
+        let scores = score_service
+            .get_song_leaderboard(&song_id, difficulty, page)
+            .await?;
+        return Ok(success_return(scores));
+    }
+
     let scores = score_service
         .get_song_top_scores(&song_id, difficulty)
         .await?;
+    let scores = federation_service
+        .merge_song_top(&song_id, difficulty, scores)
+        .await?;
 
     Ok(success_return(scores))
 }
@@ -244,3 +318,106 @@ pub async fn song_score_friend(
 
     Ok(success_return(scores))
 }
+
+/// Get the global potential leaderboard
+///
+/// Public endpoint for community leaderboard sites: the top players by
+/// `rating_ptt` with their best 30 / recent 10 averages, from a snapshot
+/// refreshed every `potential_ranking_refresh_interval` seconds.
+#[get("/score/rank/ptt?<limit>")]
+pub async fn potential_rank(
+    score_service: &State<ScoreService>,
+    limit: Option<i32>,
+) -> RouteResult<PotentialRanking> {
+    let ranking = score_service.get_potential_ranking(limit).await?;
+    Ok(success_return(ranking))
+}
+
+/// Get the player's world rank and the world rank leaderboard
+///
+/// `rank` is the player's place by `world_rank_score` (0 when unranked or
+/// below `world_rank_max`); `ranks` lists the top players from a snapshot
+/// refreshed every `world_rank_refresh_interval` seconds.
+#[get("/score/rank/world?<limit>")]
+pub async fn world_rank(
+    user_auth: AuthGuard,
+    score_service: &State<ScoreService>,
+    limit: Option<i32>,
+) -> RouteResult<UserWorldRank> {
+    let rank = score_service
+        .get_user_world_rank(user_auth.user_id, limit)
+        .await?;
+    Ok(success_return(rank))
+}
+
+/// Attach a replay to one of the player's plays
+///
+/// The request body is the compressed replay blob, limited to
+/// `replay_max_bytes`. The play is picked by the `replay_token` returned from
+/// score submission, or else by chart; without `time_played` the replay goes
+/// to the latest play of the chart.
+#[post(
+    "/score/replay?<replay_token>&<song_id>&<difficulty>&<time_played>",
+    data = "<data>"
+)]
+pub async fn song_score_replay_upload(
+    user_auth: AuthGuard,
+    replay_service: &State<ReplayService>,
+    replay_token: Option<String>,
+    song_id: Option<String>,
+    difficulty: Option<i32>,
+    time_played: Option<i64>,
+    data: Data<'_>,
+) -> RouteResult<ReplayInfo> {
+    let body = data
+        .open(CONFIG.replay_max_bytes.bytes())
+        .into_bytes()
+        .await
+        .map_err(ArcError::from)?;
+    if !body.is_complete() {
+        return Err(ArcError::input("Replay is too large."));
+    }
+
+    let info = match (replay_token, song_id, difficulty) {
+        (Some(token), _, _) => {
+            replay_service
+                .upload_with_token(user_auth.user_id, &token, body.into_inner())
+                .await?
+        }
+        (None, Some(song_id), Some(difficulty)) => {
+            replay_service
+                .upload(
+                    user_auth.user_id,
+                    &song_id,
+                    difficulty,
+                    time_played,
+                    body.into_inner(),
+                )
+                .await?
+        }
+        _ => {
+            return Err(ArcError::input(
+                "replay_token or song_id and difficulty are required.",
+            ))
+        }
+    };
+    Ok(success_return(info))
+}
+
+/// Download the replay of one of the player's own plays
+#[get("/score/replay?<song_id>&<difficulty>&<time_played>")]
+pub async fn song_score_replay_download(
+    user_auth: AuthGuard,
+    replay_service: &State<ReplayService>,
+    song_id: String,
+    difficulty: i32,
+    time_played: i64,
+) -> Result<ReplayFile, ArcError> {
+    let bytes = replay_service
+        .download(user_auth.user_id, &song_id, difficulty, time_played)
+        .await?;
+    Ok(ReplayFile {
+        file_name: format!("{song_id}-{difficulty}-{time_played}.bin"),
+        bytes,
+    })
+}
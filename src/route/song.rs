@@ -0,0 +1,23 @@
+//! Public song data (`/api/song`) for bots and stat sites.
+
+use crate::route::common::{success_return, RouteResult};
+use crate::service::song_info::SongDetail;
+use crate::service::SongInfoService;
+use rocket::{get, routes, Route, State};
+
+/// Song info endpoint
+///
+/// Returns chart constants, note counts and availability of a song. No login
+/// is required; answers are cached for `REDIS_SONG_INFO_TTL_SECONDS`.
+#[get("/song/<song_id>")]
+pub async fn song_info(
+    song_info_service: &State<SongInfoService>,
+    song_id: &str,
+) -> RouteResult<SongDetail> {
+    Ok(success_return(song_info_service.get_song(song_id).await?))
+}
+
+/// Get all public song routes
+pub fn routes() -> Vec<Route> {
+    routes![song_info]
+}
@@ -1,13 +1,23 @@
 use crate::config::CONFIG;
 use crate::context::ClientContext;
 use crate::error::ArcError;
-use crate::model::{RegisterResponse, UserLoginDto, UserRegisterDto};
-
-use crate::route::common::{success_return, AuthGuard, RouteResult};
-use crate::service::UserService;
+use crate::model::{RegisterResponse, UserDevice, UserLoginDto, UserRegisterDto};
+
+use crate::route::common::{
+    success_return, success_return_no_value, AuthGuard, EmptyResponse, ExportFile, RouteResult,
+};
+use crate::service::captcha::CaptchaAnswer;
+use crate::service::operations::OperationParams;
+use crate::service::purchase::MAX_PURCHASE_HISTORY;
+use crate::service::user_export::export_file_name;
+use crate::service::{
+    AchievementService, CaptchaService, DownloadService, MatchmakingJoinRequest, OperationManager,
+    OwnershipService, PurchaseService, SaveService, TosService, UnlockBitmapService,
+    UserExportService, UserService, VerificationService, WebLinkService,
+};
 use rocket::form::Form;
 use rocket::serde::json::Json;
-use rocket::{get, post, routes, FromForm, Route, State};
+use rocket::{delete, get, post, routes, FromForm, Route, State};
 use serde::Deserialize;
 use serde_json::{self, Value};
 use std::collections::HashMap;
@@ -20,19 +30,34 @@ pub struct RegisterRequest {
     pub email: String,
     pub device_id: Option<String>,
     pub is_allow_marketing_email: Option<String>,
+    pub captcha_token: Option<String>,
+    pub pow_challenge: Option<String>,
+    pub pow_nonce: Option<String>,
 }
 
 /// User registration endpoint
 ///
 /// Registers a new user account with the provided credentials.
 /// Validates input data, checks for existing users, and creates
-/// a new account with initial character data.
+/// a new account with initial character data. When a CAPTCHA provider is
+/// configured, the answer is verified before anything is written.
 #[post("/", data = "<register_info>")]
 pub async fn register(
     user_service: &State<UserService>,
+    captcha_service: &State<CaptchaService>,
+    verification_service: &State<VerificationService>,
     register_info: Form<RegisterRequest>,
     ctx: ClientContext<'_>,
 ) -> RouteResult<RegisterResponse> {
+    let captcha_answer = CaptchaAnswer {
+        token: register_info.captcha_token.clone(),
+        pow_challenge: register_info.pow_challenge.clone(),
+        pow_nonce: register_info.pow_nonce.clone(),
+    };
+    captcha_service
+        .verify(&captcha_answer, ctx.get_client_ip())
+        .await?;
+
     let register_data = UserRegisterDto {
         name: register_info.name.clone(),
         password: register_info.password.clone(),
@@ -49,15 +74,30 @@ pub async fn register(
         .clone()
         .or_else(|| ctx.get_device_id());
 
-    user_service
+    let registered = user_service
         .register_user(register_data, device_id.clone(), ip.map(|c| c.to_string()))
         .await?;
 
+    // the account stays locked until the mailed code is redeemed
+    if verification_service.is_enabled() {
+        if let Err(err) = verification_service.issue(registered.user_id).await {
+            log::error!(
+                "Failed to send verification email to user {}: {err}",
+                registered.user_id
+            );
+        }
+        return Err(ArcError::no_access(
+            "Please verify your email with the code we sent before logging in.",
+            151,
+        ));
+    }
+
     // auto login after register
     let login_data = UserLoginDto {
         name: register_info.name.clone(),
         password: register_info.password.clone(),
         device_id,
+        device_model: ctx.user_agent.map(str::to_string),
     };
     let login_auth = user_service.login_user(login_data, ip).await?;
 
@@ -69,6 +109,16 @@ pub async fn register(
     Ok(success_return(response))
 }
 
+/// Registration challenge endpoint
+///
+/// Returns the configured CAPTCHA provider and site key, or a fresh
+/// proof-of-work challenge to be solved before `POST /user`.
+#[get("/captcha")]
+pub async fn captcha_challenge(captcha_service: &State<CaptchaService>) -> RouteResult<Value> {
+    let challenge = serde_json::to_value(captcha_service.challenge())?;
+    Ok(success_return(challenge))
+}
+
 /// Get current user information endpoint
 ///
 /// Returns detailed information about the authenticated user.
@@ -87,15 +137,19 @@ pub async fn user_me(user_service: &State<UserService>, auth: AuthGuard) -> Rout
 
 /// User logout endpoint
 ///
-/// Invalidates the current access token by removing it from
-/// the login sessions table.
+/// Revokes the user's outstanding download tokens so links handed out
+/// during the session stop working.
 #[post("/logout")]
 pub async fn logout(
     _user_service: &State<UserService>,
-    _auth: AuthGuard,
+    download_service: &State<DownloadService>,
+    auth: AuthGuard,
 ) -> RouteResult<HashMap<String, Value>> {
-    // TODO: Implement token invalidation
-    // For now, return empty success response
+    // TODO: Implement access token invalidation
+    download_service
+        .revoke_user_download_tokens(auth.user_id)
+        .await?;
+
     let mut response = HashMap::new();
     response.insert(
         "message".to_string(),
@@ -291,7 +345,8 @@ pub async fn toggle_uncap(
 
 /// Character first uncap endpoint
 ///
-/// Performs the first uncap of a character using fragments.
+/// Performs the first uncap of a character, spending the cores listed in
+/// `char_item` for it.
 #[post("/me/character/<character_id>/uncap")]
 pub async fn character_first_uncap(
     user_service: &State<UserService>,
@@ -343,8 +398,8 @@ pub async fn character_exp(
 ///
 /// Retrieves user's cloud save data.
 #[get("/me/save")]
-pub async fn cloud_get(user_service: &State<UserService>, auth: AuthGuard) -> RouteResult<Value> {
-    let save_data = user_service.get_user_save_data(auth.user_id).await?;
+pub async fn cloud_get(save_service: &State<SaveService>, auth: AuthGuard) -> RouteResult<Value> {
+    let save_data = save_service.get_save_data(auth.user_id).await?;
     Ok(success_return(save_data))
 }
 
@@ -369,16 +424,19 @@ pub struct CloudSaveRequest {
     pub story_checksum: String,
     pub finalestate_data: Option<String>,
     pub finalestate_checksum: Option<String>,
+    /// `createdAt` of the save this upload was based on; a newer stored
+    /// save makes the upload fail with a conflict.
+    pub base_created_at: Option<i64>,
 }
 
 #[post("/me/save", data = "<request>")]
 pub async fn cloud_post(
-    user_service: &State<UserService>,
+    save_service: &State<SaveService>,
     auth: AuthGuard,
     request: Form<CloudSaveRequest>,
 ) -> RouteResult<Value> {
-    user_service
-        .update_user_save_data(auth.user_id, &request)
+    save_service
+        .update_save_data(auth.user_id, &request)
         .await?;
 
     let response = serde_json::json!({
@@ -416,10 +474,66 @@ pub async fn sys_set(
 
 /// User account deletion endpoint
 ///
-/// Requests deletion of the user's account.
+/// Requests deletion of the user's account. Nothing is deleted yet: the
+/// request must be confirmed through `/me/delete/confirm` once the returned
+/// `confirmable_at` has passed.
 #[post("/me/request_delete")]
 pub async fn user_delete(user_service: &State<UserService>, auth: AuthGuard) -> RouteResult<Value> {
-    user_service.delete_user_account(auth.user_id).await?;
+    let request = user_service.request_account_deletion(auth.user_id).await?;
+
+    let response = serde_json::json!({
+        "user_id": auth.user_id,
+        "requested_at": request.requested_at,
+        "confirmable_at": request.confirmable_at,
+        "expires_at": request.expires_at
+    });
+
+    Ok(success_return(response))
+}
+
+/// Account deletion status endpoint
+///
+/// Returns the pending deletion request, or `null` when there is none.
+#[get("/me/request_delete")]
+pub async fn user_delete_status(
+    user_service: &State<UserService>,
+    auth: AuthGuard,
+) -> RouteResult<Value> {
+    let request = user_service
+        .get_account_deletion_request(auth.user_id)
+        .await?;
+    Ok(success_return(serde_json::to_value(request)?))
+}
+
+/// Account deletion cancel endpoint
+#[delete("/me/request_delete")]
+pub async fn user_delete_cancel(
+    user_service: &State<UserService>,
+    auth: AuthGuard,
+) -> RouteResult<EmptyResponse> {
+    user_service.cancel_account_deletion(auth.user_id).await?;
+    Ok(success_return_no_value())
+}
+
+/// Account deletion confirmation payload
+#[derive(Debug, Deserialize, FromForm)]
+pub struct DeleteConfirmRequest {
+    pub password: String,
+}
+
+/// Account deletion confirmation endpoint
+///
+/// Anonymizes the account after checking the password. The player's scores
+/// stay on the leaderboards under a placeholder name.
+#[post("/me/delete/confirm", data = "<request>")]
+pub async fn user_delete_confirm(
+    user_service: &State<UserService>,
+    auth: AuthGuard,
+    request: Form<DeleteConfirmRequest>,
+) -> RouteResult<Value> {
+    user_service
+        .confirm_account_deletion(auth.user_id, &request.password)
+        .await?;
 
     let response = serde_json::json!({
         "user_id": auth.user_id
@@ -428,16 +542,101 @@ pub async fn user_delete(user_service: &State<UserService>, auth: AuthGuard) ->
     Ok(success_return(response))
 }
 
+/// Login device removal request payload
+#[derive(Debug, Deserialize, FromForm)]
+pub struct DeviceRemoveRequest {
+    /// Omitted to remove sessions that were created without a device id
+    pub device_id: Option<String>,
+}
+
+/// Login devices endpoint
+///
+/// Lists devices with active login sessions, most recently used first.
+#[get("/me/devices")]
+pub async fn devices_get(
+    user_service: &State<UserService>,
+    auth: AuthGuard,
+) -> RouteResult<Vec<UserDevice>> {
+    let devices = user_service.list_login_devices(auth.user_id).await?;
+    Ok(success_return(devices))
+}
+
+/// Login device removal endpoint
+///
+/// Logs the device out by invalidating every access token it holds.
+#[delete("/me/devices", data = "<request>")]
+pub async fn devices_delete(
+    user_service: &State<UserService>,
+    auth: AuthGuard,
+    request: Form<DeviceRemoveRequest>,
+) -> RouteResult<EmptyResponse> {
+    user_service
+        .kick_login_device(auth.user_id, request.device_id.as_deref())
+        .await?;
+    Ok(success_return_no_value())
+}
+
+/// Email verification code request payload
+#[derive(Debug, Deserialize, FromForm)]
+pub struct EmailVerifyRequest {
+    pub email: String,
+    pub code: String,
+}
+
+/// Email verification resend request payload
+#[derive(Debug, Deserialize, FromForm)]
+pub struct EmailResendRequest {
+    pub email: String,
+}
+
+fn verification_unavailable() -> ArcError {
+    ArcError::no_data_status("Email verification unavailable.", 151, 404)
+}
+
+/// Email verification endpoint
+///
+/// Redeems the code mailed at registration and activates the account.
+#[post("/email/verify", data = "<request>")]
+pub async fn email_verify_code(
+    verification_service: &State<VerificationService>,
+    request: Form<EmailVerifyRequest>,
+) -> RouteResult<EmptyResponse> {
+    if !verification_service.is_enabled() {
+        return Err(verification_unavailable());
+    }
+    verification_service
+        .verify(&request.email, &request.code)
+        .await?;
+    Ok(success_return_no_value())
+}
+
+/// Email verification code resend endpoint
+///
+/// Mails a new code to an unverified account. Always succeeds for unknown
+/// addresses so registered emails cannot be probed.
+#[post("/email/verify/resend", data = "<request>")]
+pub async fn email_verify_resend(
+    verification_service: &State<VerificationService>,
+    request: Form<EmailResendRequest>,
+) -> RouteResult<EmptyResponse> {
+    if !verification_service.is_enabled() {
+        return Err(verification_unavailable());
+    }
+    verification_service
+        .resend_for_email(&request.email)
+        .await?;
+    Ok(success_return_no_value())
+}
+
 /// Email verification resend endpoint
 ///
-/// Resends email verification (currently unavailable).
-#[post("/email/resend_verify")]
-pub async fn email_resend_verify() -> RouteResult<Value> {
-    Err(ArcError::no_data_status(
-        "Email verification unavailable.",
-        151,
-        404,
-    ))
+/// Legacy client path; behaves like `POST /user/email/verify/resend`.
+#[post("/email/resend_verify", data = "<request>")]
+pub async fn email_resend_verify(
+    verification_service: &State<VerificationService>,
+    request: Form<EmailResendRequest>,
+) -> RouteResult<EmptyResponse> {
+    email_verify_resend(verification_service, request).await
 }
 
 /// Email verification status endpoint
@@ -452,6 +651,188 @@ pub async fn email_verify() -> RouteResult<Value> {
     ))
 }
 
+/// Terms-of-service accept request payload
+#[derive(Debug, Deserialize, FromForm)]
+pub struct TosAcceptRequest {
+    pub version: String,
+}
+
+/// Terms-of-service status endpoint
+///
+/// Returns the current version, the version the user last accepted and
+/// whether re-acceptance is required.
+#[get("/me/tos")]
+pub async fn tos_status(tos_service: &State<TosService>, auth: AuthGuard) -> RouteResult<Value> {
+    let status = tos_service.get_status(auth.user_id).await?;
+    Ok(success_return(serde_json::to_value(status)?))
+}
+
+/// Terms-of-service acceptance endpoint
+///
+/// Records that the user accepted the current version.
+#[post("/me/tos/accept", data = "<request>")]
+pub async fn tos_accept(
+    tos_service: &State<TosService>,
+    auth: AuthGuard,
+    ctx: ClientContext<'_>,
+    request: Form<TosAcceptRequest>,
+) -> RouteResult<Value> {
+    tos_service
+        .accept(auth.user_id, &request.version, ctx.get_client_ip())
+        .await?;
+    let status = tos_service.get_status(auth.user_id).await?;
+    Ok(success_return(serde_json::to_value(status)?))
+}
+
+/// Web link request payload
+#[derive(Debug, Deserialize, FromForm)]
+pub struct WebLinkRequest {
+    pub code: String,
+}
+
+/// Web login link endpoint
+///
+/// Redeems the short code shown on the player web dashboard, logging that
+/// browser in as the current user.
+#[post("/me/web_link", data = "<request>")]
+pub async fn web_link(
+    web_link_service: &State<WebLinkService>,
+    auth: AuthGuard,
+    request: Form<WebLinkRequest>,
+) -> RouteResult<EmptyResponse> {
+    web_link_service
+        .approve(&request.code, auth.user_id)
+        .await?;
+    Ok(success_return_no_value())
+}
+
+/// Achievement list endpoint
+///
+/// Returns every available achievement with the user's progress.
+#[get("/me/achievements")]
+pub async fn achievements(
+    achievement_service: &State<AchievementService>,
+    auth: AuthGuard,
+) -> RouteResult<Value> {
+    let achievements = achievement_service
+        .get_user_achievements(auth.user_id)
+        .await?;
+    Ok(success_return(serde_json::to_value(achievements)?))
+}
+
+/// Content ownership endpoint
+///
+/// Returns which packs, singles and world songs the user owns and which
+/// ones available on the server are still missing.
+#[get("/me/ownership")]
+pub async fn ownership(
+    ownership_service: &State<OwnershipService>,
+    auth: AuthGuard,
+) -> RouteResult<Value> {
+    let ownership = ownership_service
+        .get_content_ownership(auth.user_id)
+        .await?;
+    Ok(success_return(serde_json::to_value(ownership)?))
+}
+
+/// Song unlock endpoint
+///
+/// Returns the link play `song_unlock` bitmap the server computes from the
+/// user's packs, singles and world songs.
+#[get("/me/song_unlock")]
+pub async fn song_unlock(
+    unlock_bitmap_service: &State<UnlockBitmapService>,
+    user_service: &State<UserService>,
+    auth: AuthGuard,
+) -> RouteResult<Value> {
+    let user = user_service.get_user_info(auth.user_id).await?;
+    let report = unlock_bitmap_service.report(&user, None);
+    Ok(success_return(serde_json::to_value(report)?))
+}
+
+/// Song unlock comparison endpoint
+///
+/// Takes the `clientSongMap` the client sends to link play and lists the
+/// charts on which it differs from the server-side bitmap.
+#[post("/me/song_unlock", data = "<request>")]
+pub async fn song_unlock_compare(
+    unlock_bitmap_service: &State<UnlockBitmapService>,
+    user_service: &State<UserService>,
+    auth: AuthGuard,
+    request: Json<MatchmakingJoinRequest>,
+) -> RouteResult<Value> {
+    let user = user_service.get_user_info(auth.user_id).await?;
+    let report = unlock_bitmap_service.report(&user, Some(&request.client_song_map));
+    Ok(success_return(serde_json::to_value(report)?))
+}
+
+/// Purchase history endpoint
+///
+/// Returns the user's completed pack, single and special item purchases,
+/// newest first.
+#[get("/me/purchases?<limit>")]
+pub async fn purchases(
+    purchase_service: &State<PurchaseService>,
+    auth: AuthGuard,
+    limit: Option<i64>,
+) -> RouteResult<Value> {
+    let history = purchase_service
+        .get_purchase_history(auth.user_id, limit.unwrap_or(MAX_PURCHASE_HISTORY))
+        .await?;
+    Ok(success_return(serde_json::to_value(history)?))
+}
+
+/// Data export request endpoint
+///
+/// Starts assembling the user's personal data export in the background; the
+/// user is notified when it can be downloaded. Requesting again while an
+/// export is running returns the running job.
+#[post("/me/export")]
+pub async fn export_request(
+    operation_manager: &State<OperationManager>,
+    auth: AuthGuard,
+) -> RouteResult<Value> {
+    let job = operation_manager.start_operation(
+        "export_user_data",
+        Some(OperationParams {
+            user_id: Some(auth.user_id),
+            ..Default::default()
+        }),
+    )?;
+    Ok(success_return(serde_json::json!({ "job": job })))
+}
+
+/// Data export status endpoint
+///
+/// Returns the latest export job of the user and the export ready for
+/// download, if any.
+#[get("/me/export")]
+pub async fn export_status(
+    operation_manager: &State<OperationManager>,
+    user_export_service: &State<UserExportService>,
+    auth: AuthGuard,
+) -> RouteResult<Value> {
+    let job = operation_manager.latest_job("export_user_data", auth.user_id);
+    let export = user_export_service.export_info(auth.user_id).await?;
+    Ok(success_return(serde_json::json!({
+        "job": job,
+        "export": export,
+    })))
+}
+
+/// Data export download endpoint
+#[get("/me/export/download")]
+pub async fn export_download(
+    user_export_service: &State<UserExportService>,
+    auth: AuthGuard,
+) -> Result<ExportFile, ArcError> {
+    let bytes = user_export_service.read_export(auth.user_id).await?;
+    Ok(ExportFile {
+        file_name: export_file_name(auth.user_id),
+        bytes,
+    })
+}
+
 /// Get all user routes
 pub fn routes() -> Vec<Route> {
     let mut routes = routes![
@@ -469,12 +850,30 @@ pub fn routes() -> Vec<Route> {
         cloud_post,
         sys_set,
         user_delete,
+        user_delete_status,
+        user_delete_cancel,
+        user_delete_confirm,
+        devices_get,
+        devices_delete,
         email_resend_verify,
-        email_verify
+        email_verify,
+        email_verify_code,
+        email_verify_resend,
+        tos_status,
+        tos_accept,
+        web_link,
+        achievements,
+        ownership,
+        song_unlock,
+        song_unlock_compare,
+        purchases,
+        export_request,
+        export_status,
+        export_download
     ];
 
     if !CONFIG.disable_registration {
-        routes.extend(routes![register]);
+        routes.extend(routes![register, captcha_challenge]);
     }
 
     routes
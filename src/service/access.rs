@@ -0,0 +1,281 @@
+use crate::error::{ArcError, ArcResult};
+use crate::utils::env_list;
+use std::env;
+use std::net::IpAddr;
+use std::str::FromStr;
+
+/// Paths left open unless `IP_RULES_EXEMPT_PREFIXES` overrides them: the admin
+/// panel, the admin API and the health probes.
+const DEFAULT_EXEMPT_PREFIXES: &[&str] = &["/web", "/api/v1/admin", "/healthz", "/readyz"];
+
+/// An IPv4 or IPv6 network in CIDR notation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IpNetwork {
+    addr: IpAddr,
+    prefix_len: u8,
+}
+
+impl IpNetwork {
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.addr, ip) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                prefix_matches(&net.octets(), &ip.octets(), self.prefix_len)
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                prefix_matches(&net.octets(), &ip.octets(), self.prefix_len)
+            }
+            (IpAddr::V6(net), IpAddr::V4(ip)) => {
+                self.prefix_len >= 96
+                    && net.to_ipv4_mapped().is_some_and(|net| {
+                        prefix_matches(&net.octets(), &ip.octets(), self.prefix_len - 96)
+                    })
+            }
+            (IpAddr::V4(_), IpAddr::V6(ip)) => ip
+                .to_ipv4_mapped()
+                .is_some_and(|ip| self.contains(IpAddr::V4(ip))),
+        }
+    }
+}
+
+impl FromStr for IpNetwork {
+    type Err = ArcError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let value = value.trim();
+        let invalid = || ArcError::input(format!("Invalid CIDR `{value}`"));
+        let (addr, prefix_len) = match value.split_once('/') {
+            Some((addr, len)) => {
+                let addr = addr.parse::<IpAddr>().map_err(|_| invalid())?;
+                (addr, len.parse::<u8>().map_err(|_| invalid())?)
+            }
+            None => {
+                let addr = value.parse::<IpAddr>().map_err(|_| invalid())?;
+                let len = if addr.is_ipv4() { 32 } else { 128 };
+                (addr, len)
+            }
+        };
+
+        let max_len = if addr.is_ipv4() { 32 } else { 128 };
+        if prefix_len > max_len {
+            return Err(invalid());
+        }
+        Ok(Self { addr, prefix_len })
+    }
+}
+
+fn prefix_matches(net: &[u8], ip: &[u8], prefix_len: u8) -> bool {
+    let full_bytes = (prefix_len / 8) as usize;
+    let rest_bits = prefix_len % 8;
+    if net[..full_bytes] != ip[..full_bytes] {
+        return false;
+    }
+    if rest_bits == 0 {
+        return true;
+    }
+    let mask = 0xffu8 << (8 - rest_bits);
+    (net[full_bytes] & mask) == (ip[full_bytes] & mask)
+}
+
+/// Why a request was refused by [`AccessRules`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AccessDenial {
+    DeniedNetwork,
+    NotAllowedNetwork,
+    DeniedCountry(String),
+    NotAllowedCountry(Option<String>),
+}
+
+/// CIDR and GeoIP country rules applied to game API requests.
+///
+/// Evaluation order: a deny CIDR always wins; a matching allow CIDR always
+/// passes; a non-empty allow list rejects everything else. Otherwise the
+/// country rules decide, using the GeoIP database when one is configured.
+pub struct AccessRules {
+    allow_networks: Vec<IpNetwork>,
+    deny_networks: Vec<IpNetwork>,
+    allow_countries: Vec<String>,
+    deny_countries: Vec<String>,
+    exempt_prefixes: Vec<String>,
+    geoip: Option<maxminddb::Reader<Vec<u8>>>,
+}
+
+impl std::fmt::Debug for AccessRules {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AccessRules")
+            .field("allow_networks", &self.allow_networks)
+            .field("deny_networks", &self.deny_networks)
+            .field("allow_countries", &self.allow_countries)
+            .field("deny_countries", &self.deny_countries)
+            .field("exempt_prefixes", &self.exempt_prefixes)
+            .field("has_geoip", &self.geoip.is_some())
+            .finish()
+    }
+}
+
+impl AccessRules {
+    pub fn from_env() -> ArcResult<Self> {
+        let geoip = match env::var("GEOIP_DATABASE")
+            .ok()
+            .filter(|value| !value.trim().is_empty())
+        {
+            Some(path) => Some(maxminddb::Reader::open_readfile(&path).map_err(|e| {
+                ArcError::input(format!("Failed to open GeoIP database `{path}`: {e}"))
+            })?),
+            None => None,
+        };
+
+        let exempt_prefixes = match env::var("IP_RULES_EXEMPT_PREFIXES") {
+            Ok(value) => split_list(&value),
+            Err(_) => DEFAULT_EXEMPT_PREFIXES
+                .iter()
+                .map(|prefix| prefix.to_string())
+                .collect(),
+        };
+
+        let rules = Self {
+            allow_networks: parse_networks(&env_list("IP_ALLOW_CIDRS"))?,
+            deny_networks: parse_networks(&env_list("IP_DENY_CIDRS"))?,
+            allow_countries: normalize_countries(env_list("GEOIP_ALLOW_COUNTRIES")),
+            deny_countries: normalize_countries(env_list("GEOIP_DENY_COUNTRIES")),
+            exempt_prefixes,
+            geoip,
+        };
+
+        if rules.geoip.is_none()
+            && !(rules.allow_countries.is_empty() && rules.deny_countries.is_empty())
+        {
+            return Err(ArcError::input(
+                "GEOIP_DATABASE is required for country access rules",
+            ));
+        }
+
+        Ok(rules)
+    }
+
+    /// Whether any rule is configured at all.
+    pub fn is_active(&self) -> bool {
+        !(self.allow_networks.is_empty()
+            && self.deny_networks.is_empty()
+            && self.allow_countries.is_empty()
+            && self.deny_countries.is_empty())
+    }
+
+    pub fn is_exempt(&self, path: &str) -> bool {
+        self.exempt_prefixes
+            .iter()
+            .any(|prefix| path == prefix || path.starts_with(&format!("{prefix}/")))
+    }
+
+    pub fn check(&self, ip: IpAddr) -> Result<(), AccessDenial> {
+        if self.deny_networks.iter().any(|net| net.contains(ip)) {
+            return Err(AccessDenial::DeniedNetwork);
+        }
+        if self.allow_networks.iter().any(|net| net.contains(ip)) {
+            return Ok(());
+        }
+        if !self.allow_networks.is_empty() {
+            return Err(AccessDenial::NotAllowedNetwork);
+        }
+
+        if self.allow_countries.is_empty() && self.deny_countries.is_empty() {
+            return Ok(());
+        }
+
+        let country = self.lookup_country(ip);
+        self.check_country(country.as_deref())
+    }
+
+    fn check_country(&self, country: Option<&str>) -> Result<(), AccessDenial> {
+        if let Some(country) = country {
+            if self.deny_countries.iter().any(|c| c == country) {
+                return Err(AccessDenial::DeniedCountry(country.to_string()));
+            }
+        }
+        if !self.allow_countries.is_empty()
+            && !country.is_some_and(|country| self.allow_countries.iter().any(|c| c == country))
+        {
+            return Err(AccessDenial::NotAllowedCountry(country.map(str::to_string)));
+        }
+        Ok(())
+    }
+
+    fn lookup_country(&self, ip: IpAddr) -> Option<String> {
+        let reader = self.geoip.as_ref()?;
+        let record: maxminddb::geoip2::Country = reader.lookup(ip).ok()?;
+        record
+            .country
+            .and_then(|country| country.iso_code)
+            .map(|code| code.to_ascii_uppercase())
+    }
+}
+
+fn parse_networks(values: &[String]) -> ArcResult<Vec<IpNetwork>> {
+    values.iter().map(|value| value.parse()).collect()
+}
+
+fn normalize_countries(values: Vec<String>) -> Vec<String> {
+    values
+        .into_iter()
+        .map(|value| value.to_ascii_uppercase())
+        .collect()
+}
+
+fn split_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(|part| part.trim().to_string())
+        .filter(|part| !part.is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(allow: &[&str], deny: &[&str]) -> AccessRules {
+        AccessRules {
+            allow_networks: allow.iter().map(|v| v.parse().unwrap()).collect(),
+            deny_networks: deny.iter().map(|v| v.parse().unwrap()).collect(),
+            allow_countries: Vec::new(),
+            deny_countries: Vec::new(),
+            exempt_prefixes: vec!["/web".to_string()],
+            geoip: None,
+        }
+    }
+
+    #[test]
+    fn test_cidr_contains() {
+        let net: IpNetwork = "10.1.0.0/16".parse().unwrap();
+        assert!(net.contains("10.1.255.3".parse().unwrap()));
+        assert!(!net.contains("10.2.0.1".parse().unwrap()));
+
+        let net: IpNetwork = "192.168.1.128/25".parse().unwrap();
+        assert!(net.contains("192.168.1.200".parse().unwrap()));
+        assert!(!net.contains("192.168.1.100".parse().unwrap()));
+
+        let net: IpNetwork = "2001:db8::/32".parse().unwrap();
+        assert!(net.contains("2001:db8:1::1".parse().unwrap()));
+        assert!(!net.contains("10.1.0.1".parse().unwrap()));
+
+        assert!("10.0.0.0/33".parse::<IpNetwork>().is_err());
+    }
+
+    #[test]
+    fn test_rule_precedence() {
+        let r = rules(&["10.0.0.0/8"], &["10.9.0.0/16"]);
+        assert_eq!(r.check("10.1.2.3".parse().unwrap()), Ok(()));
+        assert_eq!(
+            r.check("10.9.2.3".parse().unwrap()),
+            Err(AccessDenial::DeniedNetwork)
+        );
+        assert_eq!(
+            r.check("8.8.8.8".parse().unwrap()),
+            Err(AccessDenial::NotAllowedNetwork)
+        );
+
+        let r = rules(&[], &["1.2.3.4"]);
+        assert_eq!(r.check("8.8.8.8".parse().unwrap()), Ok(()));
+        assert!(r.is_exempt("/web/login"));
+        assert!(!r.is_exempt("/website"));
+    }
+}
@@ -0,0 +1,418 @@
+use crate::error::ArcResult;
+use crate::model::PresentItem;
+use crate::service::{AssetManager, PresentService};
+use crate::DbPool;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+const CLEAR_TYPE_PURE_MEMORY: i32 = 3;
+
+/// What an achievement measures.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AchievementKind {
+    /// Number of recorded plays.
+    PlayCount,
+    /// Number of charts with a Pure Memory best clear.
+    PureMemoryCount,
+    /// Charts cleared in the pack named by `param`; the target is every
+    /// chart of the pack.
+    PackClear,
+}
+
+impl AchievementKind {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "play_count" => Some(Self::PlayCount),
+            "pure_memory_count" => Some(Self::PureMemoryCount),
+            "pack_clear" => Some(Self::PackClear),
+            _ => None,
+        }
+    }
+}
+
+/// An achievement definition from the `achievement` table.
+#[derive(Debug, Clone)]
+struct AchievementDefinition {
+    achievement_id: String,
+    kind: AchievementKind,
+    target: i32,
+    param: Option<String>,
+    reward_type: Option<String>,
+    reward_item_id: Option<String>,
+    reward_amount: i32,
+}
+
+/// An achievement together with a user's progress on it.
+#[derive(Debug, Clone, Serialize)]
+pub struct UserAchievement {
+    pub achievement_id: String,
+    pub name: String,
+    pub description: String,
+    pub progress: i32,
+    pub target: i32,
+    pub achieved: bool,
+    pub achieved_at: Option<i64>,
+    pub reward_type: Option<String>,
+    pub reward_item_id: Option<String>,
+    pub reward_amount: i32,
+}
+
+/// Achievements evaluated from score data and rewarded through presents.
+///
+/// Progress is re-evaluated after every score submission. Once an
+/// achievement is reached it is frozen and its reward is sent to the
+/// user's present box.
+#[derive(Clone)]
+pub struct AchievementService {
+    pool: DbPool,
+    asset_manager: Arc<AssetManager>,
+    present_service: Arc<PresentService>,
+}
+
+impl AchievementService {
+    pub fn new(pool: DbPool, asset_manager: Arc<AssetManager>) -> Self {
+        Self {
+            present_service: Arc::new(PresentService::new(pool.clone())),
+            pool,
+            asset_manager,
+        }
+    }
+
+    /// List every available achievement with the user's progress.
+    pub async fn get_user_achievements(&self, user_id: i32) -> ArcResult<Vec<UserAchievement>> {
+        let rows = sqlx::query!(
+            "SELECT a.achievement_id, a.name, a.description, a.target AS definition_target,
+                    a.reward_type, a.reward_item_id, a.reward_amount,
+                    ua.progress AS `progress?`, ua.target AS `target?`,
+                    ua.achieved_at AS `achieved_at?`
+             FROM achievement a
+             LEFT JOIN user_achievement ua
+               ON ua.achievement_id = a.achievement_id AND ua.user_id = ?
+             WHERE a.is_available = 1
+             ORDER BY a.sort_order, a.achievement_id",
+            user_id
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| UserAchievement {
+                achievement_id: row.achievement_id,
+                name: row.name,
+                description: row.description.unwrap_or_default(),
+                progress: row.progress.unwrap_or(0),
+                target: row.target.unwrap_or(row.definition_target),
+                achieved: row.achieved_at.is_some(),
+                achieved_at: row.achieved_at,
+                reward_type: row.reward_type,
+                reward_item_id: row.reward_item_id,
+                reward_amount: row.reward_amount,
+            })
+            .collect())
+    }
+
+    /// Update progress on all unfinished achievements and grant rewards
+    /// for the ones reached.
+    ///
+    /// Returns the ids of newly achieved achievements.
+    pub async fn evaluate(&self, user_id: i32) -> ArcResult<Vec<String>> {
+        let definitions = self.pending_definitions(user_id).await?;
+        if definitions.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut play_count = None;
+        let mut pure_memory_count = None;
+        let mut pack_data = None;
+        let mut achieved = Vec::new();
+
+        for definition in definitions {
+            let (progress, target) = match definition.kind {
+                AchievementKind::PlayCount => {
+                    if play_count.is_none() {
+                        play_count = Some(self.play_count(user_id).await?);
+                    }
+                    (play_count.unwrap_or(0), definition.target)
+                }
+                AchievementKind::PureMemoryCount => {
+                    if pure_memory_count.is_none() {
+                        pure_memory_count = Some(self.pure_memory_count(user_id).await?);
+                    }
+                    (pure_memory_count.unwrap_or(0), definition.target)
+                }
+                AchievementKind::PackClear => {
+                    let Some(pack_songs) = definition
+                        .param
+                        .as_deref()
+                        .and_then(|pack_id| self.asset_manager.get_pack_song_ids(pack_id))
+                    else {
+                        continue;
+                    };
+                    if pack_data.is_none() {
+                        pack_data = Some(self.pack_data(user_id).await?);
+                    }
+                    let (charts, cleared) = pack_data.as_ref().unwrap();
+                    pack_progress(&pack_songs, charts, cleared)
+                }
+            };
+
+            if self
+                .record_progress(user_id, &definition, progress, target)
+                .await?
+            {
+                achieved.push(definition.achievement_id);
+            }
+        }
+
+        Ok(achieved)
+    }
+
+    async fn pending_definitions(&self, user_id: i32) -> ArcResult<Vec<AchievementDefinition>> {
+        let rows = sqlx::query!(
+            "SELECT a.achievement_id, a.kind, a.target, a.param,
+                    a.reward_type, a.reward_item_id, a.reward_amount
+             FROM achievement a
+             LEFT JOIN user_achievement ua
+               ON ua.achievement_id = a.achievement_id AND ua.user_id = ?
+             WHERE a.is_available = 1 AND ua.achieved_at IS NULL",
+            user_id
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .filter_map(|row| {
+                let Some(kind) = AchievementKind::parse(&row.kind) else {
+                    log::warn!(
+                        "Skipping achievement `{}` with unknown kind `{}`",
+                        row.achievement_id,
+                        row.kind
+                    );
+                    return None;
+                };
+                Some(AchievementDefinition {
+                    achievement_id: row.achievement_id,
+                    kind,
+                    target: row.target,
+                    param: row.param,
+                    reward_type: row.reward_type,
+                    reward_item_id: row.reward_item_id,
+                    reward_amount: row.reward_amount,
+                })
+            })
+            .collect())
+    }
+
+    async fn play_count(&self, user_id: i32) -> ArcResult<i32> {
+        let count =
+            sqlx::query_scalar!("SELECT COUNT(*) FROM user_score WHERE user_id = ?", user_id)
+                .fetch_one(&self.pool)
+                .await?;
+        Ok(count as i32)
+    }
+
+    async fn pure_memory_count(&self, user_id: i32) -> ArcResult<i32> {
+        let count = sqlx::query_scalar!(
+            "SELECT COUNT(*) FROM best_score WHERE user_id = ? AND best_clear_type = ?",
+            user_id,
+            CLEAR_TYPE_PURE_MEMORY
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(count as i32)
+    }
+
+    /// Charts known to the server and the charts the user has cleared.
+    async fn pack_data(
+        &self,
+        user_id: i32,
+    ) -> ArcResult<(HashMap<String, Vec<i32>>, HashSet<(String, i32)>)> {
+        let charts = sqlx::query!(
+            "SELECT song_id, rating_pst, rating_prs, rating_ftr, rating_byn, rating_etr FROM chart"
+        )
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(|row| {
+            let difficulties = [
+                row.rating_pst,
+                row.rating_prs,
+                row.rating_ftr,
+                row.rating_byn,
+                row.rating_etr,
+            ]
+            .into_iter()
+            .enumerate()
+            .filter(|(_, rating)| rating.is_some_and(|rating| rating >= 0))
+            .map(|(difficulty, _)| difficulty as i32)
+            .collect();
+            (row.song_id, difficulties)
+        })
+        .collect();
+
+        let cleared = sqlx::query!(
+            "SELECT song_id, difficulty FROM best_score
+             WHERE user_id = ? AND best_clear_type > 0",
+            user_id
+        )
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(|row| (row.song_id, row.difficulty))
+        .collect();
+
+        Ok((charts, cleared))
+    }
+
+    /// Store progress; returns whether the achievement was reached just now.
+    async fn record_progress(
+        &self,
+        user_id: i32,
+        definition: &AchievementDefinition,
+        progress: i32,
+        target: i32,
+    ) -> ArcResult<bool> {
+        let progress = progress.min(target.max(0));
+        sqlx::query!(
+            "INSERT INTO user_achievement (user_id, achievement_id, progress, target)
+             VALUES (?, ?, ?, ?)
+             ON DUPLICATE KEY UPDATE
+                progress = IF(achieved_at IS NULL, VALUES(progress), progress),
+                target = IF(achieved_at IS NULL, VALUES(target), target)",
+            user_id,
+            definition.achievement_id,
+            progress,
+            target
+        )
+        .execute(&self.pool)
+        .await?;
+
+        if target <= 0 || progress < target {
+            return Ok(false);
+        }
+
+        // The conditional update makes concurrent evaluations reward once.
+        let now = chrono::Utc::now().timestamp_millis();
+        let reached = sqlx::query!(
+            "UPDATE user_achievement SET achieved_at = ?
+             WHERE user_id = ? AND achievement_id = ? AND achieved_at IS NULL",
+            now,
+            user_id,
+            definition.achievement_id
+        )
+        .execute(&self.pool)
+        .await?
+        .rows_affected();
+        if reached == 0 {
+            return Ok(false);
+        }
+
+        self.grant_reward(user_id, definition).await?;
+        Ok(true)
+    }
+
+    async fn grant_reward(
+        &self,
+        user_id: i32,
+        definition: &AchievementDefinition,
+    ) -> ArcResult<()> {
+        let Some(reward_type) = definition.reward_type.as_deref().filter(|t| !t.is_empty()) else {
+            return Ok(());
+        };
+        if definition.reward_amount <= 0 {
+            return Ok(());
+        }
+
+        let present_id = format!("achievement_{}_{user_id}", definition.achievement_id);
+        let item = PresentItem {
+            present_id: present_id.clone(),
+            item_id: definition
+                .reward_item_id
+                .clone()
+                .filter(|id| !id.is_empty())
+                .unwrap_or_else(|| reward_type.to_string()),
+            item_type: reward_type.to_string(),
+            amount: definition.reward_amount,
+        };
+        self.present_service
+            .create_present(
+                &present_id,
+                None,
+                &format!("Achievement reward: {}", definition.achievement_id),
+                vec![item],
+                user_id,
+            )
+            .await
+    }
+}
+
+/// Cleared and total charts of a pack.
+fn pack_progress(
+    pack_songs: &HashSet<String>,
+    charts: &HashMap<String, Vec<i32>>,
+    cleared: &HashSet<(String, i32)>,
+) -> (i32, i32) {
+    let mut done = 0;
+    let mut total = 0;
+    for song_id in pack_songs {
+        let Some(difficulties) = charts.get(song_id) else {
+            continue;
+        };
+        for difficulty in difficulties {
+            total += 1;
+            if cleared.contains(&(song_id.clone(), *difficulty)) {
+                done += 1;
+            }
+        }
+    }
+    (done, total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_kind() {
+        assert_eq!(
+            AchievementKind::parse("play_count"),
+            Some(AchievementKind::PlayCount)
+        );
+        assert_eq!(
+            AchievementKind::parse("pack_clear"),
+            Some(AchievementKind::PackClear)
+        );
+        assert_eq!(AchievementKind::parse("unknown"), None);
+    }
+
+    #[test]
+    fn test_pack_progress() {
+        let pack_songs = ["a", "b", "missing"]
+            .into_iter()
+            .map(String::from)
+            .collect::<HashSet<_>>();
+        let charts = HashMap::from([
+            ("a".to_string(), vec![0, 1, 2]),
+            ("b".to_string(), vec![0, 1, 2, 3]),
+            ("other".to_string(), vec![0]),
+        ]);
+        let mut cleared = HashSet::from([
+            ("a".to_string(), 0),
+            ("a".to_string(), 2),
+            ("b".to_string(), 3),
+            ("other".to_string(), 0),
+        ]);
+        assert_eq!(pack_progress(&pack_songs, &charts, &cleared), (3, 7));
+
+        cleared.extend([
+            ("a".to_string(), 1),
+            ("b".to_string(), 0),
+            ("b".to_string(), 1),
+            ("b".to_string(), 2),
+        ]);
+        assert_eq!(pack_progress(&pack_songs, &charts, &cleared), (7, 7));
+    }
+}
@@ -1,12 +1,158 @@
-use crate::error::ArcError;
+use crate::error::{ArcError, ArcResult};
+use crate::service::cdn::CdnRegion;
+use crate::service::event::limited_events;
 
 use crate::service::{
-    DownloadService, PresentService, PurchaseService, ScoreService, UserService, WorldService,
+    CourseService, DownloadService, EventService, NotificationService, PresentService,
+    PurchaseService, ScoreService, UserService, WorldService,
 };
 
 use crate::Constants;
+use crate::ReadPool;
 
+use rocket::{Orbit, Rocket};
 use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+
+pub type AggregateFuture<'a> =
+    Pin<Box<dyn Future<Output = ArcResult<serde_json::Value>> + Send + 'a>>;
+
+/// Handler of one aggregatable endpoint
+pub type AggregateHandler = for<'a> fn(&'a AggregateRequest<'a>) -> AggregateFuture<'a>;
+
+/// One inner call of `/compose/aggregate`, as seen by its handler
+pub struct AggregateRequest<'a> {
+    pub rocket: &'a Rocket<Orbit>,
+    /// Authenticated user, 0 for endpoints registered without auth
+    pub user_id: i32,
+    pub query: &'a HashMap<String, String>,
+    pub mirror: Option<&'a CdnRegion>,
+}
+
+impl<'a> AggregateRequest<'a> {
+    /// Managed service the handler needs
+    pub fn state<T: Send + Sync + 'static>(&self) -> ArcResult<&'a T> {
+        self.rocket.state::<T>().ok_or_else(|| {
+            ArcError::rocket_err(format!("{} is not managed", std::any::type_name::<T>()))
+        })
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct AggregateEndpoint {
+    pub requires_auth: bool,
+    pub handler: AggregateHandler,
+}
+
+/// Internal GET endpoints callable through `/compose/aggregate`, keyed by path
+///
+/// Handlers look their services up from Rocket state, so registering an
+/// endpoint does not change the aggregate route itself.
+#[derive(Clone, Default)]
+pub struct AggregateRegistry {
+    endpoints: HashMap<&'static str, AggregateEndpoint>,
+}
+
+impl AggregateRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register an endpoint that needs the caller's bearer token
+    pub fn register(self, path: &'static str, handler: AggregateHandler) -> Self {
+        self.insert(path, true, handler)
+    }
+
+    /// Register an endpoint that answers without authentication
+    pub fn register_public(self, path: &'static str, handler: AggregateHandler) -> Self {
+        self.insert(path, false, handler)
+    }
+
+    fn insert(
+        mut self,
+        path: &'static str,
+        requires_auth: bool,
+        handler: AggregateHandler,
+    ) -> Self {
+        self.endpoints.insert(
+            path,
+            AggregateEndpoint {
+                requires_auth,
+                handler,
+            },
+        );
+        self
+    }
+
+    pub fn get(&self, path: &str) -> Option<AggregateEndpoint> {
+        self.endpoints.get(path).copied()
+    }
+
+    pub fn paths(&self) -> Vec<&'static str> {
+        let mut paths = self.endpoints.keys().copied().collect::<Vec<_>>();
+        paths.sort_unstable();
+        paths
+    }
+
+    /// Endpoints of the game API
+    pub fn game() -> Self {
+        Self::new()
+            .register_public("/game/info", |_| Box::pin(handle_game_info()))
+            .register_public("/finale/progress", |_| Box::pin(handle_finale_progress()))
+            .register_public("/purchase/bundle/bundle", |_| {
+                Box::pin(handle_bundle_bundle())
+            })
+            .register("/user/me", |req| {
+                Box::pin(async move { handle_user_me(req.state()?, req.user_id).await })
+            })
+            .register("/friend/me", |req| {
+                Box::pin(async move { handle_friend_me(req.state()?, req.user_id).await })
+            })
+            .register("/present/me", |req| {
+                Box::pin(async move { handle_present_info(req.state()?, req.user_id).await })
+            })
+            .register("/world/map/me", |req| {
+                Box::pin(async move { handle_world_all(req.state()?, req.user_id).await })
+            })
+            .register("/course/me", |req| {
+                Box::pin(async move { handle_course_me(req.state()?, req.user_id).await })
+            })
+            .register("/event/me", |req| {
+                Box::pin(async move { handle_event_me(req.state()?, req.user_id).await })
+            })
+            .register("/notification/me", |req| {
+                Box::pin(async move { handle_notification_me(req.state()?, req.user_id).await })
+            })
+            .register("/purchase/bundle/pack", |req| {
+                Box::pin(async move { handle_bundle_pack(req.state()?, req.user_id).await })
+            })
+            .register("/purchase/bundle/single", |req| {
+                Box::pin(async move { handle_bundle_single(req.state()?, req.user_id).await })
+            })
+            .register("/purchase/me/pack", |req| {
+                Box::pin(async move { handle_me_pack(req.state()?, req.user_id).await })
+            })
+            .register("/score/song/friend", |req| {
+                Box::pin(async move {
+                    handle_song_score_friend(req.state()?, req.state()?, req.user_id, req.query)
+                        .await
+                })
+            })
+            .register("/serve/download/me/song", |req| {
+                Box::pin(async move {
+                    handle_download_song(
+                        req.state()?,
+                        req.state()?,
+                        req.user_id,
+                        req.query,
+                        req.mirror,
+                    )
+                    .await
+                })
+            })
+    }
+}
 
 fn python_truthy(value: &serde_json::Value) -> bool {
     match value {
@@ -35,6 +181,43 @@ pub async fn handle_user_me(
     })
 }
 
+/// Handle /friend/me endpoint
+pub async fn handle_friend_me(
+    user_service: &UserService,
+    user_id: i32,
+) -> Result<serde_json::Value, ArcError> {
+    let friends = user_service.get_user_friends(user_id).await?;
+    Ok(serde_json::json!({ "friends": friends }))
+}
+
+/// Handle /course/me endpoint
+pub async fn handle_course_me(
+    pool: &ReadPool,
+    user_id: i32,
+) -> Result<serde_json::Value, ArcError> {
+    CourseService::new((**pool).clone())
+        .get_course_me(user_id)
+        .await
+}
+
+/// Handle /event/me endpoint
+pub async fn handle_event_me(
+    event_service: &EventService,
+    user_id: i32,
+) -> Result<serde_json::Value, ArcError> {
+    let events = event_service.get_active_events(user_id).await?;
+    Ok(serde_json::to_value(events)?)
+}
+
+/// Handle /notification/me endpoint
+pub async fn handle_notification_me(
+    notification_service: &NotificationService,
+    user_id: i32,
+) -> Result<serde_json::Value, ArcError> {
+    let notifications = notification_service.get_user_notifications(user_id).await?;
+    Ok(serde_json::to_value(notifications)?)
+}
+
 /// Handle /game/info endpoint
 pub async fn handle_game_info() -> Result<serde_json::Value, ArcError> {
     let level_step = Constants::get_level_steps();
@@ -44,13 +227,17 @@ pub async fn handle_game_info() -> Result<serde_json::Value, ArcError> {
         .collect();
     level_step.sort_by(|a, b| a["level"].cmp(b["level"]));
 
+    let now = chrono::Utc::now().timestamp_millis();
+    let events = limited_events().active_at(now).collect::<Vec<_>>();
+
     Ok(serde_json::json!({"max_stamina": 12,
     "stamina_recover_tick": 1800000,
     "core_exp": 250,
-    "curr_ts": chrono::Utc::now().timestamp_millis(),
+    "curr_ts": now,
     "level_steps": level_step,
     "world_ranking_enabled": true,
-    "is_byd_chapter_unlocked": true}))
+    "is_byd_chapter_unlocked": true,
+    "events": events}))
 }
 
 /// Handle /present/me endpoint
@@ -101,6 +288,7 @@ pub async fn handle_download_song(
     user_service: &UserService,
     user_id: i32,
     query_params: &HashMap<String, String>,
+    mirror: Option<&CdnRegion>,
 ) -> Result<serde_json::Value, ArcError> {
     // Get user info for permission checking
     let user_info = user_service.get_user_info(user_id).await?;
@@ -122,6 +310,14 @@ pub async fn handle_download_song(
         None => true,
     };
 
+    let token_ttl = query_params
+        .get("ttl")
+        .map(|raw| {
+            raw.parse::<i64>()
+                .map_err(|_| ArcError::input("Invalid `ttl` query value"))
+        })
+        .transpose()?;
+
     // Check rate limiting if URLs are requested
     if url_flag && download_service.check_download_limit(user_id).await? {
         return Err(ArcError::rate_limit(
@@ -132,7 +328,7 @@ pub async fn handle_download_song(
 
     // Generate download list
     let download_songs = download_service
-        .generate_download_list(&user_info, song_ids, url_flag)
+        .generate_download_list(&user_info, song_ids, url_flag, mirror, token_ttl)
         .await?;
 
     // Convert to the expected format
@@ -166,9 +362,39 @@ pub async fn handle_bundle_single(
     })
 }
 
+/// Handle /purchase/me/pack endpoint
+pub async fn handle_me_pack(
+    purchase_service: &PurchaseService,
+    user_id: i32,
+) -> Result<serde_json::Value, ArcError> {
+    let packs = purchase_service
+        .get_discounted_pack_purchases(user_id)
+        .await?;
+    serde_json::to_value(&packs).map_err(|e| ArcError::Json {
+        message: e.to_string(),
+    })
+}
+
 /// Handle /finale/progress endpoint
 pub async fn handle_finale_progress() -> Result<serde_json::Value, ArcError> {
     Ok(serde_json::json!({
         "percentage": 100000
     }))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_game_registry() {
+        let registry = AggregateRegistry::game();
+        assert!(!registry.get("/game/info").unwrap().requires_auth);
+        assert!(registry.get("/user/me").unwrap().requires_auth);
+        for path in ["/friend/me", "/present/me", "/world/map/me"] {
+            assert!(registry.get(path).is_some(), "{path} is not aggregatable");
+        }
+        assert!(registry.get("/unknown").is_none());
+        assert!(registry.paths().windows(2).all(|w| w[0] < w[1]));
+    }
+}
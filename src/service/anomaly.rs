@@ -0,0 +1,325 @@
+use crate::error::ArcResult;
+use crate::DbPool;
+use std::collections::{BTreeSet, HashMap};
+use std::env;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const DAY_MS: i64 = 24 * 60 * 60 * 1000;
+const DEFAULT_SCAN_INTERVAL_SECONDS: u64 = 6 * 60 * 60;
+const DEFAULT_WINDOW_DAYS: i64 = 30;
+/// Plays a player needs before rating spikes are counted.
+const MIN_BASELINE_PLAYS: usize = 20;
+/// A play rating this far above the player's previous best is a spike.
+const RATING_SPIKE: f64 = 1.0;
+/// Plays submitted closer together than this cannot both be real plays.
+const MIN_PLAY_GAP_MS: i64 = 30_000;
+/// Linked accounts kept per entry.
+const MAX_LINKED_USERS: usize = 20;
+
+const RATING_SPIKE_WEIGHT: f64 = 5.0;
+const SHARED_FINGERPRINT_WEIGHT: f64 = 3.0;
+const BURST_PLAY_WEIGHT: f64 = 0.5;
+
+#[derive(Debug, Clone)]
+pub struct AnomalyConfig {
+    /// Time between background scans; `None` disables the loop.
+    pub scan_interval: Option<Duration>,
+    /// How far back plays are scanned.
+    pub window_days: i64,
+}
+
+impl AnomalyConfig {
+    /// Read `ANOMALY_SCAN_INTERVAL_SECONDS` (0 disables the background scan)
+    /// and `ANOMALY_SCAN_WINDOW_DAYS`.
+    pub fn from_env() -> Self {
+        let scan_interval = env::var("ANOMALY_SCAN_INTERVAL_SECONDS")
+            .ok()
+            .and_then(|value| value.trim().parse::<u64>().ok())
+            .unwrap_or(DEFAULT_SCAN_INTERVAL_SECONDS);
+        let window_days = env::var("ANOMALY_SCAN_WINDOW_DAYS")
+            .ok()
+            .and_then(|value| value.trim().parse::<i64>().ok())
+            .filter(|days| *days > 0)
+            .unwrap_or(DEFAULT_WINDOW_DAYS);
+
+        Self {
+            scan_interval: (scan_interval > 0).then(|| Duration::from_secs(scan_interval)),
+            window_days,
+        }
+    }
+}
+
+/// Judgement counts identifying a play; two accounts producing the same
+/// fingerprint on the same chart almost always share a score source.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Fingerprint {
+    song_id: String,
+    difficulty: i32,
+    score: i32,
+    shiny_perfect_count: i32,
+    perfect_count: i32,
+    near_count: i32,
+    miss_count: i32,
+}
+
+/// Per-user anomaly signals from one scan.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AnomalySignals {
+    pub rating_spikes: i32,
+    pub shared_fingerprints: i32,
+    pub burst_plays: i32,
+    pub play_count: i32,
+    pub linked_users: BTreeSet<i32>,
+}
+
+impl AnomalySignals {
+    pub fn score(&self) -> f64 {
+        self.rating_spikes as f64 * RATING_SPIKE_WEIGHT
+            + self.shared_fingerprints as f64 * SHARED_FINGERPRINT_WEIGHT
+            + self.burst_plays as f64 * BURST_PLAY_WEIGHT
+    }
+}
+
+#[derive(Debug, Default)]
+struct UserPlays {
+    times: Vec<i64>,
+    ratings: Vec<f64>,
+    fingerprints: BTreeSet<usize>,
+}
+
+/// Scores accounts on statistical anomalies in their play log and keeps the
+/// results in `user_anomaly` for manual review. Submission-time checks only
+/// see one play; this looks across plays and accounts.
+#[derive(Clone)]
+pub struct AnomalyService {
+    pool: DbPool,
+    config: AnomalyConfig,
+}
+
+impl AnomalyService {
+    pub fn new(pool: DbPool, config: AnomalyConfig) -> Self {
+        Self { pool, config }
+    }
+
+    pub fn from_env(pool: DbPool) -> Self {
+        Self::new(pool, AnomalyConfig::from_env())
+    }
+
+    pub fn scan_interval(&self) -> Option<Duration> {
+        self.config.scan_interval
+    }
+
+    /// Rescan the play window and refresh the review queue. Returns the
+    /// number of flagged accounts.
+    pub async fn scan(&self) -> ArcResult<usize> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as i64;
+        let since = now - self.config.window_days * DAY_MS;
+
+        let baselines = sqlx::query!(
+            "SELECT user_id, COUNT(*) AS `play_count!: i64`,
+                    COALESCE(MAX(rating), 0) AS `max_rating!: f64`
+             FROM user_score WHERE time_played < ?
+             GROUP BY user_id",
+            since
+        )
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(|row| (row.user_id, (row.play_count as usize, row.max_rating)))
+        .collect::<HashMap<_, _>>();
+
+        let rows = sqlx::query!(
+            "SELECT user_id, song_id, difficulty, time_played,
+                    COALESCE(score, 0) AS `score!: i32`,
+                    COALESCE(shiny_perfect_count, 0) AS `shiny_perfect_count!: i32`,
+                    COALESCE(perfect_count, 0) AS `perfect_count!: i32`,
+                    COALESCE(near_count, 0) AS `near_count!: i32`,
+                    COALESCE(miss_count, 0) AS `miss_count!: i32`,
+                    COALESCE(rating, 0) AS `rating!: f64`
+             FROM user_score WHERE time_played >= ?
+             ORDER BY user_id, time_played",
+            since
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut fingerprint_ids: HashMap<Fingerprint, usize> = HashMap::new();
+        let mut fingerprint_users: Vec<BTreeSet<i32>> = Vec::new();
+        let mut users: HashMap<i32, UserPlays> = HashMap::new();
+        for row in rows {
+            let plays = users.entry(row.user_id).or_default();
+            plays.times.push(row.time_played);
+            plays.ratings.push(row.rating);
+            if row.score <= 0 || row.perfect_count + row.near_count + row.miss_count <= 0 {
+                continue;
+            }
+            let fingerprint = Fingerprint {
+                song_id: row.song_id,
+                difficulty: row.difficulty,
+                score: row.score,
+                shiny_perfect_count: row.shiny_perfect_count,
+                perfect_count: row.perfect_count,
+                near_count: row.near_count,
+                miss_count: row.miss_count,
+            };
+            let next_id = fingerprint_users.len();
+            let id = *fingerprint_ids.entry(fingerprint).or_insert(next_id);
+            if id == next_id {
+                fingerprint_users.push(BTreeSet::new());
+            }
+            fingerprint_users[id].insert(row.user_id);
+            plays.fingerprints.insert(id);
+        }
+
+        let mut tx = self.pool.begin().await?;
+        let mut flagged = 0;
+        for (user_id, plays) in users {
+            let (baseline_count, baseline_max) =
+                baselines.get(&user_id).copied().unwrap_or((0, 0.0));
+            let mut signals = AnomalySignals {
+                rating_spikes: count_rating_spikes(baseline_max, baseline_count, &plays.ratings),
+                burst_plays: count_burst_plays(&plays.times),
+                play_count: plays.times.len() as i32,
+                ..Default::default()
+            };
+            for id in &plays.fingerprints {
+                let sharing = &fingerprint_users[*id];
+                if sharing.len() > 1 {
+                    signals.shared_fingerprints += 1;
+                    signals
+                        .linked_users
+                        .extend(sharing.iter().filter(|other| **other != user_id));
+                }
+            }
+
+            let score = signals.score();
+            if score <= 0.0 {
+                continue;
+            }
+            let linked_users = signals
+                .linked_users
+                .iter()
+                .take(MAX_LINKED_USERS)
+                .map(i32::to_string)
+                .collect::<Vec<_>>()
+                .join(",");
+            sqlx::query!(
+                "INSERT INTO user_anomaly
+                 (user_id, score, rating_spikes, shared_fingerprints, burst_plays, play_count,
+                  linked_users, computed_at)
+                 VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+                 ON DUPLICATE KEY UPDATE
+                 reviewed_at = IF(VALUES(score) > COALESCE(reviewed_score, 0), NULL, reviewed_at),
+                 score = VALUES(score),
+                 rating_spikes = VALUES(rating_spikes),
+                 shared_fingerprints = VALUES(shared_fingerprints),
+                 burst_plays = VALUES(burst_plays),
+                 play_count = VALUES(play_count),
+                 linked_users = VALUES(linked_users),
+                 computed_at = VALUES(computed_at)",
+                user_id,
+                score,
+                signals.rating_spikes,
+                signals.shared_fingerprints,
+                signals.burst_plays,
+                signals.play_count,
+                linked_users,
+                now
+            )
+            .execute(&mut *tx)
+            .await?;
+            flagged += 1;
+        }
+        // Unreviewed entries that were not flagged again have cleared up.
+        sqlx::query!(
+            "DELETE FROM user_anomaly WHERE computed_at < ? AND reviewed_at IS NULL",
+            now
+        )
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        Ok(flagged)
+    }
+
+    /// Mark an entry reviewed. It stays out of the queue until a later scan
+    /// scores the account higher.
+    pub async fn mark_reviewed(&self, user_id: i32, note: &str) -> ArcResult<u64> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as i64;
+        Ok(sqlx::query!(
+            "UPDATE user_anomaly
+             SET reviewed_at = ?, reviewed_score = score, review_note = ?
+             WHERE user_id = ?",
+            now,
+            note,
+            user_id
+        )
+        .execute(&self.pool)
+        .await?
+        .rows_affected())
+    }
+}
+
+/// Plays whose rating beats the running best by more than [`RATING_SPIKE`],
+/// once the player has [`MIN_BASELINE_PLAYS`] plays to compare against.
+fn count_rating_spikes(baseline_max: f64, baseline_count: usize, ratings: &[f64]) -> i32 {
+    let mut best = baseline_max;
+    let mut spikes = 0;
+    for (index, rating) in ratings.iter().enumerate() {
+        if baseline_count + index >= MIN_BASELINE_PLAYS && *rating > best + RATING_SPIKE {
+            spikes += 1;
+        }
+        best = best.max(*rating);
+    }
+    spikes
+}
+
+/// Plays submitted less than [`MIN_PLAY_GAP_MS`] after the previous one.
+/// `times` must be sorted.
+fn count_burst_plays(times: &[i64]) -> i32 {
+    times
+        .windows(2)
+        .filter(|pair| pair[1] - pair[0] < MIN_PLAY_GAP_MS)
+        .count() as i32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_count_rating_spikes() {
+        // No baseline yet: early jumps are normal progress.
+        assert_eq!(count_rating_spikes(0.0, 0, &[5.0, 9.0, 12.0]), 0);
+        // Established player jumping well past their best.
+        assert_eq!(count_rating_spikes(10.0, 50, &[10.5, 11.2, 12.5, 12.6]), 1);
+        assert_eq!(count_rating_spikes(10.0, 19, &[11.5, 13.0]), 1);
+    }
+
+    #[test]
+    fn test_count_burst_plays() {
+        assert_eq!(count_burst_plays(&[]), 0);
+        assert_eq!(
+            count_burst_plays(&[0, 120_000, 130_000, 300_000, 310_000]),
+            2
+        );
+    }
+
+    #[test]
+    fn test_anomaly_score() {
+        let signals = AnomalySignals {
+            rating_spikes: 1,
+            shared_fingerprints: 2,
+            burst_plays: 4,
+            ..Default::default()
+        };
+        assert_eq!(signals.score(), 13.0);
+        assert_eq!(AnomalySignals::default().score(), 0.0);
+    }
+}
@@ -0,0 +1,138 @@
+use crate::error::ArcResult;
+use crate::utils::current_timestamp_ms;
+use crate::DbPool;
+use tokio::sync::mpsc;
+
+const DAY_MS: i64 = 24 * 60 * 60 * 1000;
+/// Entries waiting for the writer; further requests are only traced.
+const API_LOG_QUEUE_CAPACITY: usize = 4096;
+/// Longest path stored, matching the `api_log.path` column.
+const MAX_PATH_LENGTH: usize = 255;
+
+/// One game API request.
+#[derive(Debug, Clone)]
+pub struct ApiLogEntry {
+    pub method: String,
+    pub path: String,
+    pub user_id: Option<i32>,
+    pub status: u16,
+    pub latency_ms: i64,
+    pub ip: Option<String>,
+    pub created_at: i64,
+}
+
+/// Rolling log of game API requests in `api_log`, searched from the admin
+/// panel.
+///
+/// Entries go through a bounded queue to a background writer, so logging
+/// never delays a response; when the writer falls behind, entries are
+/// dropped rather than queued without limit. Rows older than the retention
+/// are removed by [`clear_expired`](Self::clear_expired).
+#[derive(Clone)]
+pub struct ApiLogService {
+    pool: DbPool,
+    retention_days: i64,
+    sender: Option<mpsc::Sender<ApiLogEntry>>,
+}
+
+impl ApiLogService {
+    /// Start the writer, unless `retention_days` is 0 and nothing is stored.
+    pub fn spawn(pool: DbPool, retention_days: i64) -> Self {
+        let sender = (retention_days > 0).then(|| spawn_writer(pool.clone()));
+        Self {
+            pool,
+            retention_days,
+            sender,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.sender.is_some()
+    }
+
+    /// Queue `entry` for the writer.
+    pub fn record(&self, entry: ApiLogEntry) {
+        let Some(sender) = &self.sender else {
+            return;
+        };
+        if let Err(mpsc::error::TrySendError::Full(entry)) = sender.try_send(entry) {
+            log::debug!("API log queue is full, dropped {}", entry.path);
+        }
+    }
+
+    /// Delete rows older than the retention.
+    pub async fn clear_expired(&self) -> ArcResult<u64> {
+        let cutoff = current_timestamp_ms() - self.retention_days * DAY_MS;
+        Ok(
+            sqlx::query!("DELETE FROM api_log WHERE created_at < ?", cutoff)
+                .execute(&self.pool)
+                .await?
+                .rows_affected(),
+        )
+    }
+}
+
+fn spawn_writer(pool: DbPool) -> mpsc::Sender<ApiLogEntry> {
+    let (sender, mut receiver) = mpsc::channel::<ApiLogEntry>(API_LOG_QUEUE_CAPACITY);
+
+    tokio::spawn(async move {
+        while let Some(entry) = receiver.recv().await {
+            if let Err(e) = insert_entry(&pool, &entry).await {
+                log::warn!("API log write failed: {e}");
+            }
+        }
+    });
+
+    sender
+}
+
+async fn insert_entry(pool: &DbPool, entry: &ApiLogEntry) -> ArcResult<()> {
+    let path = truncate_path(&entry.path);
+    sqlx::query!(
+        "INSERT INTO api_log (created_at, method, path, user_id, status, latency_ms, ip)
+         VALUES (?, ?, ?, ?, ?, ?, ?)",
+        entry.created_at,
+        entry.method,
+        path,
+        entry.user_id,
+        entry.status,
+        entry.latency_ms.clamp(0, i32::MAX as i64) as i32,
+        entry.ip
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// `path` cut to [`MAX_PATH_LENGTH`] bytes on a character boundary.
+fn truncate_path(path: &str) -> &str {
+    if path.len() <= MAX_PATH_LENGTH {
+        return path;
+    }
+    let mut end = MAX_PATH_LENGTH;
+    while !path.is_char_boundary(end) {
+        end -= 1;
+    }
+    &path[..end]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_truncate_path() {
+        assert_eq!(
+            truncate_path("/coldwind/35/user/me"),
+            "/coldwind/35/user/me"
+        );
+
+        let long = format!("/{}", "a".repeat(300));
+        assert_eq!(truncate_path(&long).len(), MAX_PATH_LENGTH);
+
+        let multibyte = format!("/{}", "é".repeat(200));
+        let truncated = truncate_path(&multibyte);
+        assert!(truncated.len() <= MAX_PATH_LENGTH);
+        assert!(multibyte.starts_with(truncated));
+    }
+}
@@ -0,0 +1,181 @@
+use crate::config::{GameApiVersion, CONFIG};
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// What a versioned request is turned into before routing.
+#[derive(Debug, Clone, PartialEq)]
+pub enum VersionShim {
+    /// Answer with a fixed successful value.
+    Stub(Value),
+    /// Route to this path instead, prefix included.
+    Alias(String),
+}
+
+/// Game API prefixes of older client versions and their shims.
+///
+/// Each version's prefix gets the full game route tree; requests under it may
+/// be redirected to a renamed path or answered by a stub, and JSON responses
+/// have fields renamed back to the names the version expects.
+#[derive(Debug, Clone, Default)]
+pub struct GameApiVersions {
+    versions: Vec<GameApiVersion>,
+}
+
+impl GameApiVersions {
+    pub fn new(versions: Vec<GameApiVersion>) -> Self {
+        let versions = versions
+            .into_iter()
+            .filter_map(|mut version| {
+                version.prefix = normalize_prefix(&version.prefix)?;
+                Some(version)
+            })
+            .collect();
+        Self { versions }
+    }
+
+    pub fn from_config() -> Self {
+        Self::new(CONFIG.game_api_versions.clone())
+    }
+
+    pub fn is_active(&self) -> bool {
+        !self.versions.is_empty()
+    }
+
+    pub fn prefixes(&self) -> Vec<String> {
+        self.versions
+            .iter()
+            .map(|version| version.prefix.clone())
+            .collect()
+    }
+
+    /// Index of the version serving `path` and the path below its prefix.
+    pub fn find<'p>(&self, path: &'p str) -> Option<(usize, &'p str)> {
+        self.versions
+            .iter()
+            .enumerate()
+            .filter_map(|(index, version)| {
+                let rest = path.strip_prefix(version.prefix.as_str())?;
+                (rest.is_empty() || rest.starts_with('/')).then_some((index, rest))
+            })
+            // The longest prefix wins, e.g. `/coldwind/33` over `/coldwind`.
+            .max_by_key(|(index, _)| self.versions[*index].prefix.len())
+    }
+
+    /// Shim for a request to `path`, if its version has one for it.
+    pub fn shim(&self, path: &str) -> Option<VersionShim> {
+        let (index, rest) = self.find(path)?;
+        let version = &self.versions[index];
+        if let Some(value) = version.stub_endpoints.get(rest) {
+            return Some(VersionShim::Stub(value.clone()));
+        }
+        version
+            .path_aliases
+            .get(rest)
+            .map(|target| VersionShim::Alias(format!("{}{}", version.prefix, target)))
+    }
+
+    pub fn renames(&self, index: usize) -> &HashMap<String, String> {
+        &self.versions[index].rename_fields
+    }
+}
+
+fn normalize_prefix(prefix: &str) -> Option<String> {
+    let prefix = prefix.trim().trim_end_matches('/');
+    if prefix.is_empty() {
+        return None;
+    }
+    Some(if prefix.starts_with('/') {
+        prefix.to_string()
+    } else {
+        format!("/{prefix}")
+    })
+}
+
+/// Rename object keys anywhere in `value`, current name -> old name.
+pub fn rename_fields(value: &mut Value, renames: &HashMap<String, String>) {
+    match value {
+        Value::Object(map) => {
+            for (from, to) in renames {
+                if let Some(field) = map.remove(from) {
+                    map.insert(to.clone(), field);
+                }
+            }
+            for field in map.values_mut() {
+                rename_fields(field, renames);
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                rename_fields(item, renames);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn versions() -> GameApiVersions {
+        GameApiVersions::new(vec![
+            GameApiVersion {
+                prefix: "coldwind/33/".to_string(),
+                path_aliases: HashMap::from([(
+                    "/user/me/character".to_string(),
+                    "/user/me/characters".to_string(),
+                )]),
+                stub_endpoints: HashMap::from([("/finale/progress".to_string(), json!([]))]),
+                ..GameApiVersion::default()
+            },
+            GameApiVersion {
+                prefix: "/coldwind".to_string(),
+                ..GameApiVersion::default()
+            },
+            GameApiVersion::default(),
+        ])
+    }
+
+    #[test]
+    fn test_find_version() {
+        let versions = versions();
+        assert_eq!(versions.prefixes(), vec!["/coldwind/33", "/coldwind"]);
+        assert_eq!(versions.find("/coldwind/33/user/me"), Some((0, "/user/me")));
+        assert_eq!(
+            versions.find("/coldwind/34/user/me"),
+            Some((1, "/34/user/me"))
+        );
+        assert_eq!(versions.find("/coldwind33/user/me"), None);
+    }
+
+    #[test]
+    fn test_shim() {
+        let versions = versions();
+        assert_eq!(
+            versions.shim("/coldwind/33/finale/progress"),
+            Some(VersionShim::Stub(json!([])))
+        );
+        assert_eq!(
+            versions.shim("/coldwind/33/user/me/character"),
+            Some(VersionShim::Alias(
+                "/coldwind/33/user/me/characters".to_string()
+            ))
+        );
+        assert_eq!(versions.shim("/coldwind/33/user/me"), None);
+    }
+
+    #[test]
+    fn test_rename_fields() {
+        let renames = HashMap::from([("world_unlocks".to_string(), "unlocks".to_string())]);
+        let mut value = json!({
+            "success": true,
+            "value": [{ "world_unlocks": [1], "name": "a" }],
+        });
+        rename_fields(&mut value, &renames);
+        assert_eq!(
+            value,
+            json!({ "success": true, "value": [{ "unlocks": [1], "name": "a" }] })
+        );
+    }
+}
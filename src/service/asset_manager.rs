@@ -3,15 +3,22 @@
 //! This module provides functionality for parsing songlist files, managing file caches,
 //! and handling user unlock permissions similar to the Python implementation.
 
+use crate::config::CONFIG;
 use crate::error::{ArcError, ArcResult};
 use crate::model::user::UserInfo;
+use crate::service::song_package::SongPackage;
 use crate::service::storage::StorageService;
-use serde::Deserialize;
-use sqlx::MySqlPool;
+use crate::service::unlock_bitmap::set_chart_bit;
+use crate::service::user_cache::UserCache;
+use crate::utils::current_timestamp_ms;
+use crate::DbPool;
+use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, RwLock};
+use std::time::SystemTime;
 
 /// Allowed file names for song downloads
 pub const ALLOWED_FILE_NAMES: [&str; 11] = [
@@ -35,6 +42,9 @@ pub struct Difficulty {
     pub rating_class: i32,
     #[serde(rename = "audioOverride")]
     pub audio_override: Option<bool>,
+    /// Chart constant such as `9.8`; not part of the official songlist, set
+    /// by operators to keep the `chart` table in sync on reload.
+    pub constant: Option<f64>,
 }
 
 /// Additional file information in songlist
@@ -48,6 +58,9 @@ pub struct AdditionalFile {
 #[derive(Debug, Clone, Deserialize)]
 pub struct SongInfo {
     pub id: String,
+    /// Index of the song in link play `song_unlock` bitmaps
+    pub idx: Option<u32>,
+    pub title_localized: Option<HashMap<String, String>>,
     pub set: Option<String>,
     pub purchase: Option<String>,
     #[serde(rename = "remoteDl")]
@@ -76,10 +89,104 @@ pub struct SonglistCache {
     pub free_songs: HashSet<String>,
     /// Set of world songs (including difficulty variants)
     pub world_songs: HashSet<String>,
+    /// English (or first available) title per song, used for placeholder charts
+    pub titles: HashMap<String, String>,
+    /// Songlist `idx` per song and a bitmask of the rating classes it has
+    pub chart_indices: HashMap<String, (usize, u8)>,
     /// Whether songlist was successfully parsed
     pub has_songlist: bool,
 }
 
+/// How a song is obtained and which of its files this server hosts
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SongAvailability {
+    /// Whether the loaded songlist has an entry for the song.
+    pub in_songlist: bool,
+    /// Pack the song is sold in; `base` for free songs, `single` for singles.
+    pub pack: Option<String>,
+    /// Whether the song or its Beyond chart is unlocked in world mode.
+    pub world_unlock: bool,
+    /// Song files available for download, sorted.
+    pub files: Vec<String>,
+}
+
+/// Songs present on only one side of the songlist / `chart` table pair.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ChartMismatchReport {
+    /// Whether a songlist was loaded; without one nothing is compared.
+    pub has_songlist: bool,
+    /// Songlist entries that had no `chart` row.
+    pub missing_charts: Vec<String>,
+    /// `chart` rows with no songlist entry.
+    pub unlisted_charts: Vec<String>,
+    /// Placeholder rows inserted for `missing_charts`.
+    pub placeholders_created: u64,
+    /// `chart` rows inserted or updated from songlist constants by the last
+    /// songlist reload.
+    pub charts_updated: u64,
+    /// Time of the last pass in milliseconds, 0 if none ran yet.
+    pub checked_at: i64,
+}
+
+/// Split songlist and `chart` song ids into those missing from the chart
+/// table and those missing from the songlist, both sorted.
+fn diff_song_ids(
+    songlist_ids: &HashSet<String>,
+    chart_ids: &HashSet<String>,
+) -> (Vec<String>, Vec<String>) {
+    let mut missing_charts: Vec<String> = songlist_ids.difference(chart_ids).cloned().collect();
+    let mut unlisted_charts: Vec<String> = chart_ids.difference(songlist_ids).cloned().collect();
+    missing_charts.sort_unstable();
+    unlisted_charts.sort_unstable();
+    (missing_charts, unlisted_charts)
+}
+
+/// Ratings (constant x 10 per difficulty) a chart row should have after
+/// applying the songlist constants of `song`, or `None` when nothing changes.
+/// Difficulties without a constant keep their current rating.
+fn songlist_chart_ratings(song: &SongInfo, current: Option<[i32; 5]>) -> Option<[i32; 5]> {
+    let mut ratings = current.unwrap_or([-1; 5]);
+    let mut has_constant = false;
+    for difficulty in song.difficulties.iter().flatten() {
+        let (Some(constant), Ok(index)) = (
+            difficulty.constant,
+            usize::try_from(difficulty.rating_class),
+        ) else {
+            continue;
+        };
+        if let Some(rating) = ratings.get_mut(index) {
+            *rating = (constant * 10.0).round() as i32;
+            has_constant = true;
+        }
+    }
+    (has_constant && current != Some(ratings)).then_some(ratings)
+}
+
+/// English title of a song, falling back to any localized title
+fn songlist_title(song: &SongInfo) -> Option<&str> {
+    song.title_localized
+        .as_ref()
+        .and_then(|titles| titles.get("en").or_else(|| titles.values().next()))
+        .map(String::as_str)
+}
+
+/// `(ratings, name)` to write for a song's chart row, or `None` when the row
+/// is up to date; names only count as changed when `sync_names` is set
+fn songlist_chart_row(
+    song: &SongInfo,
+    current: Option<&([i32; 5], String)>,
+    sync_names: bool,
+) -> Option<([i32; 5], String)> {
+    let name = songlist_title(song).unwrap_or(&song.id).to_string();
+    let renamed = sync_names && current.is_some_and(|(_, current_name)| *current_name != name);
+    match songlist_chart_ratings(song, current.map(|(ratings, _)| *ratings)) {
+        Some(ratings) => Some((ratings, name)),
+        None if renamed => current.map(|(ratings, _)| (*ratings, name)),
+        None => None,
+    }
+}
+
 impl SonglistCache {
     /// Check if a file is available for download for a given song
     pub fn is_available_file(&self, song_id: &str, file_name: &str) -> bool {
@@ -97,12 +204,44 @@ impl SonglistCache {
         }
     }
 
+    /// Songlist side of [`SongAvailability`]; `files` is left empty
+    pub fn song_availability(&self, song_id: &str) -> SongAvailability {
+        let pack = if self.free_songs.contains(song_id) {
+            Some("base".to_string())
+        } else {
+            self.pack_info
+                .iter()
+                .filter(|(_, songs)| songs.contains(song_id))
+                .map(|(pack_id, _)| pack_id)
+                .min()
+                .cloned()
+        };
+        SongAvailability {
+            in_songlist: self.songs.contains_key(song_id),
+            pack,
+            world_unlock: self.world_songs.contains(song_id)
+                || self.world_songs.contains(&format!("{song_id}3")),
+            files: Vec::new(),
+        }
+    }
+
     /// Get user's unlocked songs based on packs, singles, and world unlocks
     pub fn get_user_unlocks(&self, user: &UserInfo) -> HashSet<String> {
+        self.get_unlocks(&user.packs, &user.singles, &user.world_songs)
+    }
+
+    /// Songs unlocked by the given packs, singles and world songs, plus the
+    /// free songs
+    pub fn get_unlocks(
+        &self,
+        packs: &[String],
+        singles: &[String],
+        world_songs: &[String],
+    ) -> HashSet<String> {
         let mut unlocks = HashSet::new();
 
         // Add pack unlocks
-        for pack_id in &user.packs {
+        for pack_id in packs {
             if let Some(pack_songs) = self.pack_info.get(pack_id) {
                 unlocks.extend(pack_songs.clone());
             }
@@ -110,14 +249,14 @@ impl SonglistCache {
 
         // Add single unlocks (from "single" pack)
         if let Some(single_pack) = self.pack_info.get("single") {
-            let user_singles: HashSet<String> = user.singles.iter().cloned().collect();
+            let user_singles: HashSet<String> = singles.iter().cloned().collect();
             let single_unlocks: HashSet<String> =
                 single_pack.intersection(&user_singles).cloned().collect();
             unlocks.extend(single_unlocks);
         }
 
         // Add world song unlocks
-        let user_world_songs: HashSet<String> = user.world_songs.iter().cloned().collect();
+        let user_world_songs: HashSet<String> = world_songs.iter().cloned().collect();
         let world_unlocks: HashSet<String> = self
             .world_songs
             .intersection(&user_world_songs)
@@ -137,6 +276,54 @@ impl SonglistCache {
         unlocks
     }
 
+    /// Link play `song_unlock` bitmap of the charts `user` can play
+    ///
+    /// Every chart of an unlocked song counts, except a Beyond chart that is
+    /// itself a world song (`<song_id>3`), which needs that world song.
+    pub fn song_unlock_bitmap(&self, user: &UserInfo, length: usize) -> Vec<u8> {
+        let unlocks = self.get_user_unlocks(user);
+        let mut bitmap = vec![0u8; length];
+        for (song_id, &(idx, rating_classes)) in &self.chart_indices {
+            if !unlocks.contains(song_id) {
+                continue;
+            }
+            let beyond_world_song = format!("{song_id}3");
+            let beyond_locked = self.world_songs.contains(&beyond_world_song)
+                && !user.world_songs.contains(&beyond_world_song);
+            for rating_class in 0..5 {
+                if rating_classes & (1 << rating_class) == 0 || (rating_class == 3 && beyond_locked)
+                {
+                    continue;
+                }
+                set_chart_bit(&mut bitmap, idx, rating_class);
+            }
+        }
+        bitmap
+    }
+
+    /// Song with songlist index `idx`
+    pub fn song_id_by_index(&self, idx: usize) -> Option<&str> {
+        self.chart_indices
+            .iter()
+            .find(|(_, (song_idx, _))| *song_idx == idx)
+            .map(|(song_id, _)| song_id.as_str())
+    }
+
+    /// Record the songlist index and rating classes of a song
+    pub fn parse_song_charts(&mut self, song: &SongInfo) {
+        let Some(idx) = song.idx else {
+            return;
+        };
+        let rating_classes = song
+            .difficulties
+            .iter()
+            .flatten()
+            .filter(|difficulty| (0..5).contains(&difficulty.rating_class))
+            .fold(0u8, |mask, difficulty| mask | 1 << difficulty.rating_class);
+        self.chart_indices
+            .insert(song.id.clone(), (idx as usize, rating_classes));
+    }
+
     /// Parse a single song's file availability into bitmap
     pub fn parse_song_availability(&mut self, song: &SongInfo) -> u32 {
         let mut bitmap = 0u32;
@@ -215,11 +402,87 @@ impl SonglistCache {
     }
 }
 
+/// Size and modification time of a song file when it was last hashed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileStamp {
+    pub len: u64,
+    pub modified: Option<SystemTime>,
+}
+
+impl FileStamp {
+    fn of(path: &Path) -> Option<Self> {
+        let metadata = fs::metadata(path).ok()?;
+        Some(Self {
+            len: metadata.len(),
+            modified: metadata.modified().ok(),
+        })
+    }
+}
+
+/// Outcome of [`AssetManager::refresh_song_files_incremental`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct IncrementalRefreshStats {
+    /// New or changed files that were hashed again
+    pub hashed: usize,
+    /// Files whose size and modification time were unchanged
+    pub unchanged: usize,
+    /// Cache entries dropped because the file is gone
+    pub removed: usize,
+}
+
+/// Last time the song file hashes were brought up to date
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SongFileRefresh {
+    /// Unix milliseconds when the refresh finished
+    pub refreshed_at: i64,
+    pub stats: IncrementalRefreshStats,
+}
+
+/// Song folder written by [`AssetManager::install_song_package`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InstalledSong {
+    pub song_id: String,
+    /// Files of the folder in package order, already hashed
+    pub files: Vec<InstalledSongFile>,
+    /// Whether a placeholder `chart` row was inserted
+    pub chart_created: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InstalledSongFile {
+    pub file_name: String,
+    pub size: u64,
+    pub md5: String,
+}
+
+/// Compare the stamps of hashed files with what is on disk now. Returns the
+/// keys to hash (new or changed) and the keys to drop (no longer on disk).
+fn plan_incremental_refresh<K: Clone + Eq + std::hash::Hash + Ord>(
+    cached: &HashMap<K, FileStamp>,
+    current: &HashMap<K, FileStamp>,
+) -> (Vec<K>, Vec<K>) {
+    let mut to_hash: Vec<K> = current
+        .iter()
+        .filter(|(key, stamp)| cached.get(*key) != Some(*stamp))
+        .map(|(key, _)| key.clone())
+        .collect();
+    let mut removed: Vec<K> = cached
+        .keys()
+        .filter(|key| !current.contains_key(*key))
+        .cloned()
+        .collect();
+    to_hash.sort_unstable();
+    removed.sort_unstable();
+    (to_hash, removed)
+}
+
 /// File cache for MD5 hashes and file listings
 #[derive(Debug, Clone, Default)]
 pub struct FileCache {
     /// Cache of file MD5 hashes: (song_id, file_name) -> md5_hash
     pub file_md5_cache: HashMap<(String, String), Option<String>>,
+    /// Stamp of each hashed file, used to skip unchanged files on refresh
+    pub file_stamps: HashMap<(String, String), FileStamp>,
     /// Cache of song file names: song_id -> Vec<file_name>
     pub song_files_cache: HashMap<String, Vec<String>>,
     /// Cache of all song IDs
@@ -230,6 +493,7 @@ impl FileCache {
     /// Clear all cached data
     pub fn clear(&mut self) {
         self.file_md5_cache.clear();
+        self.file_stamps.clear();
         self.song_files_cache.clear();
         self.all_song_ids = None;
     }
@@ -249,9 +513,10 @@ impl FileCache {
 
         let path = Path::new(song_file_folder).join(song_id).join(file_name);
         let md5_hash = if path.is_file() {
-            fs::read(&path)
-                .ok()
-                .map(|contents| format!("{:x}", md5::compute(&contents)))
+            if let Some(stamp) = FileStamp::of(&path) {
+                self.file_stamps.insert(key.clone(), stamp);
+            }
+            hash_file(&path)
         } else {
             None
         };
@@ -304,7 +569,11 @@ impl FileCache {
             for entry in entries.flatten() {
                 if let Ok(file_type) = entry.file_type() {
                     if file_type.is_dir() {
-                        if let Some(dir_name) = entry.file_name().to_str() {
+                        if let Some(dir_name) = entry
+                            .file_name()
+                            .to_str()
+                            .filter(|name| !name.starts_with('.'))
+                        {
                             song_ids.push(dir_name.to_string());
                         }
                     }
@@ -317,11 +586,27 @@ impl FileCache {
     }
 }
 
+/// Whether `song_id` can name a song folder: ASCII letters, digits and `_`,
+/// as used by the official song ids
+pub(crate) fn is_song_id(song_id: &str) -> bool {
+    !song_id.is_empty()
+        && song_id.len() <= 255
+        && song_id
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'_')
+}
+
+fn hash_file(path: &Path) -> Option<String> {
+    fs::read(path)
+        .ok()
+        .map(|contents| format!("{:x}", md5::compute(&contents)))
+}
+
 /// Main asset manager for songs and bundles
 #[allow(unused)]
 #[derive(Debug, Clone)]
 pub struct AssetManager {
-    pool: MySqlPool,
+    pool: DbPool,
     song_file_folder: PathBuf,
     songlist_file_path: PathBuf,
     bundle_folder: PathBuf,
@@ -332,6 +617,12 @@ pub struct AssetManager {
     file_cache: Arc<RwLock<FileCache>>,
     /// Optional remote object storage metadata and URL signer.
     storage: Option<Arc<StorageService>>,
+    /// Result of the last songlist / chart table reconciliation
+    chart_report: Arc<RwLock<ChartMismatchReport>>,
+    /// Set once `initialize_cache` completes, cleared by `clear_all_cache`
+    initialized: Arc<AtomicBool>,
+    /// Last full or incremental song file hash refresh
+    last_file_refresh: Arc<RwLock<Option<SongFileRefresh>>>,
 
     /// Whether to pre-calculate file hashes
     pre_calculate_hashes: bool,
@@ -340,7 +631,7 @@ pub struct AssetManager {
 impl AssetManager {
     /// Create new asset manager
     pub fn new(
-        pool: MySqlPool,
+        pool: DbPool,
         song_folder: PathBuf,
         songlist_path: PathBuf,
         bundle_folder: PathBuf,
@@ -353,6 +644,9 @@ impl AssetManager {
             songlist_cache: Arc::new(RwLock::new(SonglistCache::default())),
             file_cache: Arc::new(RwLock::new(FileCache::default())),
             storage: None,
+            chart_report: Arc::new(RwLock::new(ChartMismatchReport::default())),
+            initialized: Arc::new(AtomicBool::new(false)),
+            last_file_refresh: Arc::new(RwLock::new(None)),
             pre_calculate_hashes: true,
         }
     }
@@ -379,18 +673,33 @@ impl AssetManager {
         // Parse songlist
         self.parse_songlist().await?;
 
+        if let Err(e) = self.reconcile_charts().await {
+            log::warn!("Failed to reconcile songlist with chart table: {e}");
+        }
+
         // Pre-calculate file hashes if enabled
         if self.pre_calculate_hashes && !self.uses_s3_storage() {
-            self.pre_calculate_file_hashes().await?;
+            let hashed = self.pre_calculate_file_hashes().await?;
+            self.record_file_refresh(IncrementalRefreshStats {
+                hashed,
+                ..Default::default()
+            });
         }
 
+        self.initialized.store(true, Ordering::Release);
         log::info!("Asset cache initialization completed");
         Ok(())
     }
 
+    /// Whether the caches are populated and ready to serve
+    pub fn is_initialized(&self) -> bool {
+        self.initialized.load(Ordering::Acquire)
+    }
+
     /// Clear all caches
     pub async fn clear_all_cache(&self) {
         log::info!("Clearing all asset caches...");
+        self.initialized.store(false, Ordering::Release);
 
         {
             let mut songlist = self.songlist_cache.write().unwrap();
@@ -414,11 +723,102 @@ impl AssetManager {
         Ok(())
     }
 
-    /// Parse songlist file
-    async fn parse_songlist(&self) -> ArcResult<()> {
+    /// Refresh the song caches without rehashing the whole library: the
+    /// songlist and directory listings are rebuilt, but only files whose size
+    /// or modification time changed since they were hashed are read again.
+    pub async fn refresh_song_files_incremental(&self) -> ArcResult<IncrementalRefreshStats> {
+        if let Some(storage) = &self.storage {
+            storage.refresh_manifest().await?;
+        }
+
+        *self.songlist_cache.write().unwrap() = SonglistCache::default();
+        self.parse_songlist().await?;
+        if let Err(e) = self.reconcile_charts().await {
+            log::warn!("Failed to reconcile songlist with chart table: {e}");
+        }
+
+        if self.uses_s3_storage() {
+            return Ok(IncrementalRefreshStats::default());
+        }
+
+        // Scan the library without holding the cache lock, so downloads keep
+        // being served while large files are hashed.
+        let song_folder = self.song_file_folder.to_str().unwrap().to_string();
+        let songlist_cache = self.songlist_cache.read().unwrap().clone();
+        let mut scan = FileCache::default();
+        let song_ids = scan.get_all_song_ids(&song_folder);
+        let mut current = HashMap::new();
+        for song_id in &song_ids {
+            for file_name in scan.get_song_files(&song_folder, song_id, &songlist_cache) {
+                let path = Path::new(&song_folder).join(song_id).join(&file_name);
+                if let Some(stamp) = FileStamp::of(&path) {
+                    current.insert((song_id.clone(), file_name), stamp);
+                }
+            }
+        }
+
+        let cached = self.file_cache.read().unwrap().file_stamps.clone();
+        let (to_hash, removed) = plan_incremental_refresh(&cached, &current);
+        let hashed: Vec<_> = to_hash
+            .into_iter()
+            .map(|key| {
+                let md5 = hash_file(&Path::new(&song_folder).join(&key.0).join(&key.1));
+                (key, md5)
+            })
+            .collect();
+
+        let stats = IncrementalRefreshStats {
+            hashed: hashed.len(),
+            unchanged: current.len() - hashed.len(),
+            removed: removed.len(),
+        };
+
+        let mut file_cache = self.file_cache.write().unwrap();
+        // Misses cached for files that did not exist yet must not outlive the scan
+        file_cache
+            .file_md5_cache
+            .retain(|key, _| cached.contains_key(key));
+        for key in &removed {
+            file_cache.file_md5_cache.remove(key);
+            file_cache.file_stamps.remove(key);
+        }
+        for (key, md5) in hashed {
+            if let Some(stamp) = current.get(&key) {
+                file_cache.file_stamps.insert(key.clone(), *stamp);
+            }
+            file_cache.file_md5_cache.insert(key, md5);
+        }
+        file_cache.song_files_cache = scan.song_files_cache;
+        file_cache.all_song_ids = scan.all_song_ids;
+        drop(file_cache);
+
+        self.record_file_refresh(stats);
+        log::info!(
+            "Incremental song file refresh: {} hashed, {} unchanged, {} removed",
+            stats.hashed,
+            stats.unchanged,
+            stats.removed
+        );
+        Ok(stats)
+    }
+
+    fn record_file_refresh(&self, stats: IncrementalRefreshStats) {
+        *self.last_file_refresh.write().unwrap() = Some(SongFileRefresh {
+            refreshed_at: current_timestamp_ms(),
+            stats,
+        });
+    }
+
+    /// Last song file hash refresh, `None` before the first one or on S3
+    pub fn last_file_refresh(&self) -> Option<SongFileRefresh> {
+        *self.last_file_refresh.read().unwrap()
+    }
+
+    /// Parse songlist file into the songlist cache
+    async fn parse_songlist(&self) -> ArcResult<Option<Songlist>> {
         if !self.songlist_file_path.exists() {
             log::warn!("Songlist file not found: {:?}", self.songlist_file_path);
-            return Ok(());
+            return Ok(None);
         }
 
         let content = fs::read_to_string(&self.songlist_file_path)
@@ -435,14 +835,330 @@ impl AssetManager {
             let bitmap = cache.parse_song_availability(song);
             cache.songs.insert(song.id.clone(), bitmap);
             cache.parse_song_unlock(song);
+            cache.parse_song_charts(song);
+            if let Some(title) = songlist_title(song) {
+                cache.titles.insert(song.id.clone(), title.to_string());
+            }
         }
 
         log::info!("Parsed {} songs from songlist", songlist.songs.len());
-        Ok(())
+        Ok(Some(songlist))
+    }
+
+    /// Re-read the songlist without a restart: constants given in the
+    /// songlist are written to the `chart` table, missing charts get
+    /// placeholders and cached download hashes are dropped so new or changed
+    /// song files are picked up.
+    pub async fn reload_songlist(&self) -> ArcResult<ChartMismatchReport> {
+        if let Some(storage) = &self.storage {
+            storage.refresh_manifest().await?;
+        }
+
+        *self.songlist_cache.write().unwrap() = SonglistCache::default();
+        let songlist = self.parse_songlist().await?;
+        let charts_updated = match &songlist {
+            Some(songlist) => self.sync_chart_rows(&songlist.songs, false).await?,
+            None => 0,
+        };
+
+        let mut report = self.reconcile_charts().await?;
+        report.charts_updated = charts_updated;
+        *self.chart_report.write().unwrap() = report.clone();
+
+        self.file_cache.write().unwrap().clear();
+
+        log::info!(
+            "Songlist reloaded: {charts_updated} charts updated, {} placeholders created",
+            report.placeholders_created
+        );
+        Ok(report)
+    }
+
+    /// Upsert the `chart` table from the songlist: constants of every
+    /// difficulty and the English song names
+    ///
+    /// Songs without constants in the songlist are left alone. Returns the
+    /// number of rows written.
+    pub async fn sync_charts_from_songlist(&self) -> ArcResult<u64> {
+        let Some(songlist) = self.parse_songlist().await? else {
+            return Ok(0);
+        };
+        let written = self.sync_chart_rows(&songlist.songs, true).await?;
+        log::info!(
+            "Chart table synced from songlist: {written} of {} songs written",
+            songlist.songs.len()
+        );
+        Ok(written)
+    }
+
+    /// Insert or update `chart` rows whose songlist constants (and, with
+    /// `sync_names`, names) differ from the table, returning the number of
+    /// rows written
+    async fn sync_chart_rows(&self, songs: &[SongInfo], sync_names: bool) -> ArcResult<u64> {
+        let current: HashMap<String, ([i32; 5], String)> = sqlx::query!(
+            "SELECT song_id, name, rating_pst, rating_prs, rating_ftr, rating_byn, rating_etr FROM chart"
+        )
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(|row| {
+            let ratings = [
+                row.rating_pst.unwrap_or(-1),
+                row.rating_prs.unwrap_or(-1),
+                row.rating_ftr.unwrap_or(-1),
+                row.rating_byn.unwrap_or(-1),
+                row.rating_etr.unwrap_or(-1),
+            ];
+            (row.song_id, (ratings, row.name.unwrap_or_default()))
+        })
+        .collect();
+
+        let mut written = 0;
+        for song in songs {
+            let Some((ratings, name)) = songlist_chart_row(song, current.get(&song.id), sync_names)
+            else {
+                continue;
+            };
+            let result = if sync_names {
+                sqlx::query!(
+                    "INSERT INTO chart (song_id, name, rating_pst, rating_prs, rating_ftr, rating_byn, rating_etr)
+                     VALUES (?, ?, ?, ?, ?, ?, ?)
+                     ON DUPLICATE KEY UPDATE name = VALUES(name), rating_pst = VALUES(rating_pst),
+                        rating_prs = VALUES(rating_prs), rating_ftr = VALUES(rating_ftr),
+                        rating_byn = VALUES(rating_byn), rating_etr = VALUES(rating_etr)",
+                    song.id,
+                    name,
+                    ratings[0],
+                    ratings[1],
+                    ratings[2],
+                    ratings[3],
+                    ratings[4]
+                )
+                .execute(&self.pool)
+                .await?
+            } else {
+                sqlx::query!(
+                    "INSERT INTO chart (song_id, name, rating_pst, rating_prs, rating_ftr, rating_byn, rating_etr)
+                     VALUES (?, ?, ?, ?, ?, ?, ?)
+                     ON DUPLICATE KEY UPDATE rating_pst = VALUES(rating_pst), rating_prs = VALUES(rating_prs),
+                        rating_ftr = VALUES(rating_ftr), rating_byn = VALUES(rating_byn),
+                        rating_etr = VALUES(rating_etr)",
+                    song.id,
+                    name,
+                    ratings[0],
+                    ratings[1],
+                    ratings[2],
+                    ratings[3],
+                    ratings[4]
+                )
+                .execute(&self.pool)
+                .await?
+            };
+            written += result.rows_affected().min(1);
+        }
+        if written > 0 {
+            UserCache::global().invalidate_all_charts();
+        }
+        Ok(written)
+    }
+
+    /// Compare the songlist with the `chart` table, log every mismatch and
+    /// insert placeholder charts (all constants -1) for songlist entries
+    /// without a row, so downloads and score submissions see a known song.
+    pub async fn reconcile_charts(&self) -> ArcResult<ChartMismatchReport> {
+        let (songlist_ids, titles) = {
+            let cache = self.songlist_cache.read().unwrap();
+            if !cache.has_songlist {
+                return Ok(ChartMismatchReport::default());
+            }
+            let ids: HashSet<String> = cache.songs.keys().cloned().collect();
+            (ids, cache.titles.clone())
+        };
+
+        let chart_ids: HashSet<String> = sqlx::query_scalar!("SELECT song_id FROM chart")
+            .fetch_all(&self.pool)
+            .await?
+            .into_iter()
+            .collect();
+
+        let (missing_charts, unlisted_charts) = diff_song_ids(&songlist_ids, &chart_ids);
+
+        let mut placeholders_created = 0;
+        for song_id in &missing_charts {
+            log::warn!("Song `{song_id}` is in the songlist but has no chart row");
+            let name = titles.get(song_id).unwrap_or(song_id);
+            placeholders_created += sqlx::query!(
+                "INSERT IGNORE INTO chart (song_id, name, rating_pst, rating_prs, rating_ftr, rating_byn, rating_etr)
+                 VALUES (?, ?, -1, -1, -1, -1, -1)",
+                song_id,
+                name
+            )
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+        }
+        for song_id in &unlisted_charts {
+            log::warn!("Chart `{song_id}` has no songlist entry");
+        }
+        if placeholders_created > 0 {
+            log::info!("Created {placeholders_created} placeholder chart rows");
+        }
+
+        let report = ChartMismatchReport {
+            has_songlist: true,
+            missing_charts,
+            unlisted_charts,
+            placeholders_created,
+            charts_updated: 0,
+            checked_at: current_timestamp_ms(),
+        };
+        *self.chart_report.write().unwrap() = report.clone();
+        Ok(report)
+    }
+
+    /// Result of the last [`Self::reconcile_charts`] pass
+    pub fn chart_report(&self) -> ChartMismatchReport {
+        self.chart_report.read().unwrap().clone()
+    }
+
+    /// Unpack an uploaded song package into the song folder and hash its
+    /// files right away, so downloads serve it without a cache refresh.
+    ///
+    /// An existing folder is only replaced with `overwrite`, and then loses
+    /// the files missing from the package. With `chart_name`, a placeholder
+    /// `chart` row (all constants -1) is inserted unless the song has one.
+    pub async fn install_song_package(
+        &self,
+        song_id: &str,
+        package: SongPackage,
+        overwrite: bool,
+        chart_name: Option<&str>,
+    ) -> ArcResult<InstalledSong> {
+        if self.uses_s3_storage() {
+            return Err(ArcError::input(
+                "Songs are served from S3; upload them to the bucket and run sync_s3_manifest",
+            ));
+        }
+        if !is_song_id(song_id) {
+            return Err(ArcError::input(format!(
+                "`{song_id}` is not a valid song id"
+            )));
+        }
+        let folder = self.song_file_folder.join(song_id);
+        if folder.exists() && !overwrite {
+            return Err(ArcError::input(format!("Song `{song_id}` already exists")));
+        }
+
+        let io_error = |e: std::io::Error| ArcError::Io {
+            message: format!("Failed to store song `{song_id}`: {e}"),
+        };
+        // Files are written next to the song folder first, so a failed upload
+        // never leaves a half-written song behind.
+        let staging = self.song_file_folder.join(format!(".{song_id}.upload"));
+        let replaced = self.song_file_folder.join(format!(".{song_id}.replaced"));
+        let _ = tokio::fs::remove_dir_all(&staging).await;
+        tokio::fs::create_dir_all(&staging)
+            .await
+            .map_err(io_error)?;
+        let mut files = Vec::with_capacity(package.files.len());
+        for (file_name, contents) in &package.files {
+            if let Err(e) = tokio::fs::write(staging.join(file_name), contents).await {
+                let _ = tokio::fs::remove_dir_all(&staging).await;
+                return Err(io_error(e));
+            }
+            files.push(InstalledSongFile {
+                file_name: file_name.clone(),
+                size: contents.len() as u64,
+                md5: format!("{:x}", md5::compute(contents)),
+            });
+        }
+        drop(package);
+
+        let had_folder = folder.exists();
+        if had_folder {
+            let _ = tokio::fs::remove_dir_all(&replaced).await;
+            tokio::fs::rename(&folder, &replaced)
+                .await
+                .map_err(io_error)?;
+        }
+        if let Err(e) = tokio::fs::rename(&staging, &folder).await {
+            if had_folder {
+                let _ = tokio::fs::rename(&replaced, &folder).await;
+            }
+            let _ = tokio::fs::remove_dir_all(&staging).await;
+            return Err(io_error(e));
+        }
+        if had_folder {
+            let _ = tokio::fs::remove_dir_all(&replaced).await;
+        }
+
+        {
+            let songlist_cache = self.songlist_cache.read().unwrap().clone();
+            let mut file_cache = self.file_cache.write().unwrap();
+            file_cache
+                .file_md5_cache
+                .retain(|(cached_song, _), _| cached_song != song_id);
+            file_cache
+                .file_stamps
+                .retain(|(cached_song, _), _| cached_song != song_id);
+            for file in &files {
+                let key = (song_id.to_string(), file.file_name.clone());
+                if let Some(stamp) = FileStamp::of(&folder.join(&file.file_name)) {
+                    file_cache.file_stamps.insert(key.clone(), stamp);
+                }
+                file_cache
+                    .file_md5_cache
+                    .insert(key, Some(file.md5.clone()));
+            }
+            file_cache.song_files_cache.remove(song_id);
+            file_cache.get_song_files(
+                self.song_file_folder.to_str().unwrap(),
+                song_id,
+                &songlist_cache,
+            );
+            file_cache.all_song_ids = None;
+        }
+
+        let chart_created = match chart_name {
+            Some(name) => {
+                let inserted = sqlx::query!(
+                    "INSERT IGNORE INTO chart (song_id, name, rating_pst, rating_prs, rating_ftr, rating_byn, rating_etr)
+                     VALUES (?, ?, -1, -1, -1, -1, -1)",
+                    song_id,
+                    name
+                )
+                .execute(&self.pool)
+                .await?
+                .rows_affected()
+                    > 0;
+                if inserted {
+                    UserCache::global().invalidate_all_charts();
+                }
+                inserted
+            }
+            None => false,
+        };
+
+        log::info!(
+            "Song `{song_id}` installed with {} files{}",
+            files.len(),
+            if chart_created {
+                " and a placeholder chart"
+            } else {
+                ""
+            }
+        );
+        Ok(InstalledSong {
+            song_id: song_id.to_string(),
+            files,
+            chart_created,
+        })
     }
 
     /// Pre-calculate file hashes for all songs
-    async fn pre_calculate_file_hashes(&self) -> ArcResult<()> {
+    ///
+    /// Returns how many files were hashed.
+    async fn pre_calculate_file_hashes(&self) -> ArcResult<usize> {
         let song_ids = {
             let mut file_cache = self.file_cache.write().unwrap();
             file_cache.get_all_song_ids(self.song_file_folder.to_str().unwrap())
@@ -450,6 +1166,7 @@ impl AssetManager {
 
         let songlist_cache = self.songlist_cache.read().unwrap().clone();
         let mut file_cache = self.file_cache.write().unwrap();
+        let mut hashed = 0;
 
         for song_id in &song_ids {
             let files = file_cache.get_song_files(
@@ -465,10 +1182,11 @@ impl AssetManager {
                     file_name,
                 );
             }
+            hashed += files.len();
         }
 
         log::info!("Pre-calculated hashes for {} songs", song_ids.len());
-        Ok(())
+        Ok(hashed)
     }
 
     /// Get file MD5 hash
@@ -502,6 +1220,17 @@ impl AssetManager {
         )
     }
 
+    /// Where a song can be obtained and which of its files are hosted here
+    pub fn song_availability(&self, song_id: &str) -> SongAvailability {
+        let mut files = self.get_song_file_names(song_id);
+        files.sort_unstable();
+        let songlist_cache = self.songlist_cache.read().unwrap();
+        SongAvailability {
+            files,
+            ..songlist_cache.song_availability(song_id)
+        }
+    }
+
     /// Get all song IDs
     pub fn get_all_song_ids(&self) -> Vec<String> {
         if let Some(storage) = self.s3_storage() {
@@ -518,16 +1247,63 @@ impl AssetManager {
         songlist_cache.get_user_unlocks(user)
     }
 
+    /// Link play `song_unlock` bitmap of `user`, `None` without a songlist
+    pub fn song_unlock_bitmap(&self, user: &UserInfo, length: usize) -> Option<Vec<u8>> {
+        let songlist_cache = self.songlist_cache.read().unwrap();
+        songlist_cache
+            .has_songlist
+            .then(|| songlist_cache.song_unlock_bitmap(user, length))
+    }
+
+    /// Song with songlist index `idx`
+    pub fn song_id_by_index(&self, idx: usize) -> Option<String> {
+        let songlist_cache = self.songlist_cache.read().unwrap();
+        songlist_cache.song_id_by_index(idx).map(str::to_string)
+    }
+
+    /// Get the song IDs belonging to a pack, as listed in the songlist
+    ///
+    /// The free `base` pack is tracked separately from purchasable packs.
+    pub fn get_pack_song_ids(&self, pack_id: &str) -> Option<HashSet<String>> {
+        let songlist_cache = self.songlist_cache.read().unwrap();
+        if pack_id == "base" {
+            return Some(songlist_cache.free_songs.clone()).filter(|songs| !songs.is_empty());
+        }
+        songlist_cache.pack_info.get(pack_id).cloned()
+    }
+
+    /// Get the purchasable packs, singles and world songs listed in the songlist
+    pub fn get_songlist_content(&self) -> (HashSet<String>, HashSet<String>, HashSet<String>) {
+        let songlist_cache = self.songlist_cache.read().unwrap();
+        let packs = songlist_cache
+            .pack_info
+            .keys()
+            .filter(|pack_id| pack_id.as_str() != "single")
+            .cloned()
+            .collect();
+        let singles = songlist_cache
+            .pack_info
+            .get("single")
+            .cloned()
+            .unwrap_or_default();
+        (packs, singles, songlist_cache.world_songs.clone())
+    }
+
     /// Check if songlist is loaded
     pub fn has_songlist(&self) -> bool {
         self.songlist_cache.read().unwrap().has_songlist
     }
 
     /// Check if download should be forbidden when user has no items
+    ///
+    /// With `download_forbid_when_no_item`, players who own no pack, single
+    /// or world song cannot download anything, not even the free songs.
     pub fn should_forbid_download_when_no_item(&self, user: &UserInfo) -> bool {
-        // TODO: Add config for this setting
-        let forbid = false; // Config.DOWNLOAD_FORBID_WHEN_NO_ITEM
-        forbid && self.has_songlist() && self.get_user_unlocks(user).is_empty()
+        CONFIG.download_forbid_when_no_item
+            && self.has_songlist()
+            && user.packs.is_empty()
+            && user.singles.is_empty()
+            && user.world_songs.is_empty()
     }
 
     pub fn s3_storage(&self) -> Option<Arc<StorageService>> {
@@ -545,7 +1321,7 @@ impl AssetManager {
 /// Utility functions for asset management operations
 impl AssetManager {
     /// Create asset manager with default paths
-    pub fn with_defaults(pool: MySqlPool) -> Self {
+    pub fn with_defaults(pool: DbPool) -> Self {
         Self::new(
             pool,
             PathBuf::from("./songs"),
@@ -572,3 +1348,130 @@ impl AssetManager {
         self
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_songlist_chart_ratings() {
+        let song: SongInfo = serde_json::from_str(
+            r#"{"id": "grievouslady", "difficulties": [
+                {"ratingClass": 2, "constant": 11.3},
+                {"ratingClass": 1}
+            ]}"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            songlist_chart_ratings(&song, None),
+            Some([-1, -1, 113, -1, -1])
+        );
+        assert_eq!(
+            songlist_chart_ratings(&song, Some([40, 78, 110, -1, -1])),
+            Some([40, 78, 113, -1, -1])
+        );
+        assert_eq!(
+            songlist_chart_ratings(&song, Some([40, 78, 113, -1, -1])),
+            None
+        );
+
+        let without_constants: SongInfo = serde_json::from_str(
+            r#"{"id": "tempestissimo", "difficulties": [{"ratingClass": 3}]}"#,
+        )
+        .unwrap();
+        assert_eq!(songlist_chart_ratings(&without_constants, None), None);
+    }
+
+    #[test]
+    fn test_songlist_chart_row() {
+        let song: SongInfo = serde_json::from_str(
+            r#"{"id": "grievouslady", "title_localized": {"en": "Grievous Lady"},
+                "difficulties": [{"ratingClass": 2, "constant": 11.3}]}"#,
+        )
+        .unwrap();
+        let synced = ([-1, -1, 113, -1, -1], "Grievous Lady".to_string());
+        let renamed = ([-1, -1, 113, -1, -1], "grievouslady".to_string());
+
+        assert_eq!(songlist_chart_row(&song, None, true), Some(synced.clone()));
+        assert_eq!(songlist_chart_row(&song, Some(&synced), true), None);
+        assert_eq!(
+            songlist_chart_row(&song, Some(&renamed), true),
+            Some(synced)
+        );
+        assert_eq!(songlist_chart_row(&song, Some(&renamed), false), None);
+    }
+
+    #[test]
+    fn test_plan_incremental_refresh() {
+        let stamp = |len| FileStamp {
+            len,
+            modified: None,
+        };
+        let cached = HashMap::from([
+            ("kept", stamp(1)),
+            ("changed", stamp(2)),
+            ("gone", stamp(3)),
+        ]);
+        let current = HashMap::from([("kept", stamp(1)), ("changed", stamp(5)), ("new", stamp(4))]);
+
+        let (to_hash, removed) = plan_incremental_refresh(&cached, &current);
+        assert_eq!(to_hash, vec!["changed", "new"]);
+        assert_eq!(removed, vec!["gone"]);
+    }
+
+    #[test]
+    fn test_song_availability() {
+        let mut cache = SonglistCache::default();
+        for song in [
+            r#"{"id": "sayonarahatsukoi", "set": "base", "difficulties": [{"ratingClass": 3}]}"#,
+            r#"{"id": "grievouslady", "set": "yugamu", "purchase": "yugamu"}"#,
+            r#"{"id": "fractureray", "set": "single", "purchase": "fractureray",
+                "worldUnlock": true}"#,
+        ] {
+            let song: SongInfo = serde_json::from_str(song).unwrap();
+            let bitmap = cache.parse_song_availability(&song);
+            cache.songs.insert(song.id.clone(), bitmap);
+            cache.parse_song_unlock(&song);
+        }
+
+        let free = cache.song_availability("sayonarahatsukoi");
+        assert!(free.in_songlist);
+        assert_eq!(free.pack.as_deref(), Some("base"));
+        assert!(free.world_unlock);
+
+        let packed = cache.song_availability("grievouslady");
+        assert_eq!(packed.pack.as_deref(), Some("yugamu"));
+        assert!(!packed.world_unlock);
+
+        let single = cache.song_availability("fractureray");
+        assert_eq!(single.pack.as_deref(), Some("single"));
+        assert!(single.world_unlock);
+
+        assert_eq!(
+            cache.song_availability("unknown"),
+            SongAvailability::default()
+        );
+    }
+
+    #[test]
+    fn test_is_song_id() {
+        assert!(is_song_id("grievouslady"));
+        assert!(is_song_id("ifi_2"));
+        assert!(!is_song_id(""));
+        assert!(!is_song_id("../songs"));
+        assert!(!is_song_id(".grievouslady.upload"));
+        assert!(!is_song_id("a b"));
+    }
+
+    #[test]
+    fn test_diff_song_ids() {
+        let ids = |values: &[&str]| values.iter().map(|v| v.to_string()).collect::<HashSet<_>>();
+        let (missing, unlisted) = diff_song_ids(
+            &ids(&["tempestissimo", "grievouslady", "fractureray"]),
+            &ids(&["fractureray", "grievouslady", "oldsong", "arcahv"]),
+        );
+        assert_eq!(missing, vec!["tempestissimo"]);
+        assert_eq!(unlisted, vec!["arcahv", "oldsong"]);
+    }
+}
@@ -1,10 +1,15 @@
 use crate::error::{ArcError, ArcResult};
+use crate::service::cdn::CdnRegion;
 use crate::service::storage::{BundleFileMeta, StorageService};
+use crate::service::webhook::{WebhookEvent, WebhookNotifier};
+use crate::DbPool;
+use rocket::fs::TempFile;
 use serde::{Deserialize, Serialize};
-use sqlx::MySqlPool;
-use std::collections::{HashMap, HashSet, VecDeque};
+use std::cmp::Reverse;
+use std::collections::{BTreeSet, BinaryHeap, HashMap};
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
@@ -21,6 +26,39 @@ pub struct ContentBundle {
     pub bundle_path: String,
     pub json_url: Option<String>,
     pub bundle_url: Option<String>,
+    #[serde(default)]
+    pub patch: BundlePatch,
+}
+
+/// Files a bundle changes relative to its previous version
+///
+/// Read from the `added` and `removed` lists of the bundle JSON; `added`
+/// entries may be plain paths or objects with a `path` field.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BundlePatch {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+impl BundlePatch {
+    pub fn from_json(json_data: &serde_json::Value) -> Self {
+        let paths = |key: &str| -> Vec<String> {
+            json_data[key]
+                .as_array()
+                .map(|entries| {
+                    entries
+                        .iter()
+                        .filter_map(|entry| entry.as_str().or_else(|| entry["path"].as_str()))
+                        .map(str::to_owned)
+                        .collect()
+                })
+                .unwrap_or_default()
+        };
+        Self {
+            added: paths("added"),
+            removed: paths("removed"),
+        }
+    }
 }
 
 impl ContentBundle {
@@ -88,6 +126,7 @@ impl ContentBundle {
             bundle_path,
             json_url: None,
             bundle_url: None,
+            patch: BundlePatch::from_json(json_data),
         })
     }
 
@@ -121,22 +160,72 @@ pub struct BundleResponse {
     pub bundle_url: String,
 }
 
+/// Net effect of the bundles in an update, so a client can tell which
+/// files actually change between its version and the target
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BundlePatchSummary {
+    #[serde(rename = "fromVersion")]
+    pub from_version: String,
+    #[serde(rename = "toVersion")]
+    pub to_version: String,
+    #[serde(rename = "downloadSize")]
+    pub download_size: u64,
+    #[serde(rename = "changedFiles")]
+    pub changed_files: Vec<String>,
+    #[serde(rename = "removedFiles")]
+    pub removed_files: Vec<String>,
+}
+
+impl BundlePatchSummary {
+    /// Fold the patches of `steps`, applied in order, into one summary
+    fn from_steps(from_version: &str, to_version: &str, steps: &[ContentBundle]) -> Self {
+        let mut changed = BTreeSet::new();
+        let mut removed = BTreeSet::new();
+        for step in steps {
+            for path in &step.patch.removed {
+                changed.remove(path);
+                removed.insert(path.clone());
+            }
+            for path in &step.patch.added {
+                removed.remove(path);
+                changed.insert(path.clone());
+            }
+        }
+
+        Self {
+            from_version: from_version.to_string(),
+            to_version: to_version.to_string(),
+            download_size: steps
+                .iter()
+                .map(|step| step.json_size + step.bundle_size)
+                .sum(),
+            changed_files: changed.into_iter().collect(),
+            removed_files: removed.into_iter().collect(),
+        }
+    }
+}
+
 /// Bundle download response
 #[derive(Debug, Serialize, Deserialize)]
 pub struct BundleDownloadResponse {
     #[serde(rename = "orderedResults")]
     pub ordered_results: Vec<BundleResponse>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub patch: Option<BundlePatchSummary>,
 }
 
 /// Bundle service for managing content bundles
 #[derive(Clone)]
 pub struct BundleService {
-    pool: MySqlPool,
+    pool: DbPool,
     bundle_folder: PathBuf,
     cache: std::sync::Arc<RwLock<BundleCache>>,
+    /// Set once the bundle cache has been built
+    initialized: Arc<AtomicBool>,
     strict_mode: bool,
     download_prefix: Option<String>,
     storage: Option<Arc<StorageService>>,
+    webhook: WebhookNotifier,
 }
 
 #[derive(Debug, Clone, Default)]
@@ -147,16 +236,136 @@ struct BundleCache {
     version_tuple_bundles: HashMap<(String, String), ContentBundle>, // (version, prev_version) -> bundle
 }
 
+impl BundleCache {
+    fn bundle_count(&self) -> usize {
+        self.bundles.values().map(Vec::len).sum()
+    }
+
+    /// Sort each app version's bundles and record its newest version
+    fn sort_versions(&mut self) {
+        for (app_version, bundles) in self.bundles.iter_mut() {
+            bundles.sort_by_key(|a| a.version_tuple());
+            if let Some(last_bundle) = bundles.last() {
+                self.max_bundle_version
+                    .insert(app_version.clone(), last_bundle.version.clone());
+            }
+        }
+    }
+}
+
+/// Version fields of an uploaded bundle JSON
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct BundleUploadMeta {
+    app_version: String,
+    version: String,
+    prev_version: Option<String>,
+}
+
+impl BundleUploadMeta {
+    /// Check the fields the bundle index and clients rely on
+    fn from_json(json_data: &serde_json::Value) -> ArcResult<Self> {
+        if !json_data.is_object() {
+            return Err(ArcError::input("Bundle JSON must be an object"));
+        }
+        let field = |key: &str| -> ArcResult<String> {
+            json_data[key]
+                .as_str()
+                .map(str::trim)
+                .filter(|value| !value.is_empty())
+                .map(str::to_owned)
+                .ok_or_else(|| ArcError::input(format!("Missing {key} in bundle JSON")))
+        };
+
+        let version = field("versionNumber")?;
+        if !is_bundle_version(&version) {
+            return Err(ArcError::input(format!(
+                "versionNumber `{version}` is not major.minor.patch"
+            )));
+        }
+        let prev_version = match &json_data["previousVersionNumber"] {
+            serde_json::Value::Null => None,
+            serde_json::Value::String(prev) if prev.trim().is_empty() || prev == "0.0.0" => None,
+            serde_json::Value::String(prev) => {
+                if !is_bundle_version(prev) {
+                    return Err(ArcError::input(format!(
+                        "previousVersionNumber `{prev}` is not major.minor.patch"
+                    )));
+                }
+                if ContentBundle::parse_version(prev) >= ContentBundle::parse_version(&version) {
+                    return Err(ArcError::input(
+                        "previousVersionNumber must be lower than versionNumber",
+                    ));
+                }
+                Some(prev.clone())
+            }
+            _ => return Err(ArcError::input("previousVersionNumber must be a string")),
+        };
+
+        let app_version = field("applicationVersionNumber")?;
+        if !app_version
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'))
+        {
+            return Err(ArcError::input(format!(
+                "applicationVersionNumber `{app_version}` contains invalid characters"
+            )));
+        }
+        field("uuid")?;
+
+        for key in ["added", "removed"] {
+            match &json_data[key] {
+                serde_json::Value::Null => {}
+                serde_json::Value::Array(entries) => {
+                    let valid = entries
+                        .iter()
+                        .all(|entry| entry.is_string() || entry["path"].is_string());
+                    if !valid {
+                        return Err(ArcError::input(format!(
+                            "{key} entries must be paths or objects with a path"
+                        )));
+                    }
+                }
+                _ => return Err(ArcError::input(format!("{key} must be a list"))),
+            }
+        }
+
+        Ok(Self {
+            app_version,
+            version,
+            prev_version,
+        })
+    }
+
+    /// File name of the bundle pair, without extension
+    fn file_stem(&self) -> String {
+        match &self.prev_version {
+            Some(prev_version) => format!("{prev_version}-{}", self.version),
+            None => self.version.clone(),
+        }
+    }
+}
+
+/// `major.minor.patch` with numeric parts only
+fn is_bundle_version(version: &str) -> bool {
+    let parts = version.split('.').collect::<Vec<_>>();
+    parts.len() == 3
+        && parts
+            .iter()
+            .all(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_digit()))
+}
+
 impl BundleService {
     /// Create a new bundle service
-    pub fn new(pool: MySqlPool, bundle_folder: PathBuf, download_prefix: Option<String>) -> Self {
+    pub fn new(pool: DbPool, bundle_folder: PathBuf, download_prefix: Option<String>) -> Self {
         Self {
             pool,
             bundle_folder,
             cache: std::sync::Arc::new(RwLock::new(BundleCache::default())),
+            initialized: Arc::new(AtomicBool::new(false)),
             strict_mode: false,
             download_prefix,
             storage: None,
+            webhook: WebhookNotifier::disabled(),
         }
     }
 
@@ -165,6 +374,12 @@ impl BundleService {
         self
     }
 
+    /// Report reloads after startup and uploads as `bundle_refresh` events
+    pub fn with_webhook(mut self, webhook: WebhookNotifier) -> Self {
+        self.webhook = webhook;
+        self
+    }
+
     /// Set strict mode for bundle version checking
     pub fn set_strict_mode(&mut self, strict: bool) {
         self.strict_mode = strict;
@@ -177,16 +392,32 @@ impl BundleService {
 
     /// Initialize bundle parser by scanning bundle directory
     pub async fn initialize(&self) -> ArcResult<()> {
+        let reload = self.is_initialized();
         if self.s3_storage().is_some() {
-            return self.refresh_s3_cache().await;
+            self.refresh_s3_cache().await?;
+        } else {
+            let new_cache = self.parse_bundles()?;
+            let mut cache = self.cache.write().await;
+            *cache = new_cache;
+            self.initialized.store(true, Ordering::Release);
         }
 
-        let new_cache = self.parse_bundles()?;
-        let mut cache = self.cache.write().await;
-        *cache = new_cache;
+        if reload {
+            self.notify_refresh().await;
+        }
         Ok(())
     }
 
+    async fn notify_refresh(&self) {
+        let bundles = self.cache.read().await.bundle_count();
+        self.webhook.notify(WebhookEvent::BundleRefresh { bundles });
+    }
+
+    /// Whether the bundle cache has been built
+    pub fn is_initialized(&self) -> bool {
+        self.initialized.load(Ordering::Acquire)
+    }
+
     /// Refresh bundle cache from the current S3 manifest.
     pub async fn refresh_s3_cache(&self) -> ArcResult<()> {
         let Some(storage) = self.s3_storage() else {
@@ -197,6 +428,7 @@ impl BundleService {
         let new_cache = self.parse_s3_bundles(&storage).await?;
         let mut cache = self.cache.write().await;
         *cache = new_cache;
+        self.initialized.store(true, Ordering::Release);
         Ok(())
     }
 
@@ -211,16 +443,7 @@ impl BundleService {
         let bundle_folder = self.bundle_folder.clone();
         self.scan_directory(&bundle_folder, &mut cache)?;
 
-        // Sort bundles by version and set max versions
-        for (app_version, bundles) in cache.bundles.iter_mut() {
-            bundles.sort_by_key(|a| a.version_tuple());
-            if let Some(last_bundle) = bundles.last() {
-                cache
-                    .max_bundle_version
-                    .insert(app_version.clone(), last_bundle.version.clone());
-            }
-        }
-
+        cache.sort_versions();
         Ok(cache)
     }
 
@@ -232,15 +455,7 @@ impl BundleService {
             self.add_manifest_bundle(entry, &mut cache);
         }
 
-        for (app_version, bundles) in cache.bundles.iter_mut() {
-            bundles.sort_by_key(|a| a.version_tuple());
-            if let Some(last_bundle) = bundles.last() {
-                cache
-                    .max_bundle_version
-                    .insert(app_version.clone(), last_bundle.version.clone());
-            }
-        }
-
+        cache.sort_versions();
         Ok(cache)
     }
 
@@ -260,6 +475,10 @@ impl BundleService {
             bundle_path: entry.bundle_key,
             json_url: None,
             bundle_url: None,
+            patch: BundlePatch {
+                added: entry.added_files,
+                removed: entry.removed_files,
+            },
         };
 
         cache
@@ -368,19 +587,125 @@ impl BundleService {
         Ok(())
     }
 
-    /// Get bundle list for client update
-    pub async fn get_bundle_list(
+    /// Every indexed bundle, by app version and then version
+    pub async fn list_bundles(&self) -> Vec<ContentBundle> {
+        let cache = self.cache.read().await;
+        let mut bundles = cache
+            .bundles
+            .values()
+            .flatten()
+            .cloned()
+            .collect::<Vec<_>>();
+        bundles.sort_by(|a, b| {
+            (
+                ContentBundle::parse_version(&a.app_version),
+                a.version_tuple(),
+            )
+                .cmp(&(
+                    ContentBundle::parse_version(&b.app_version),
+                    b.version_tuple(),
+                ))
+                .then_with(|| a.prev_version.cmp(&b.prev_version))
+        });
+        bundles
+    }
+
+    /// Store an uploaded bundle and its JSON under the bundle folder and add
+    /// it to the index
+    ///
+    /// The pair is saved as `<app version>/<version>.json|.cb`, or
+    /// `<previous>-<version>` for incremental bundles. A bundle already
+    /// indexed for the same version step is rejected rather than replaced.
+    pub async fn add_bundle(
+        &self,
+        json_content: &str,
+        bundle_file: &mut TempFile<'_>,
+    ) -> ArcResult<ContentBundle> {
+        if self.s3_storage().is_some() {
+            return Err(ArcError::input(
+                "Bundles are served from S3; upload them to the bucket and run sync_s3_manifest",
+            ));
+        }
+        let json_data: serde_json::Value = serde_json::from_str(json_content)
+            .map_err(|e| ArcError::input(format!("Invalid bundle JSON: {e}")))?;
+        let meta = BundleUploadMeta::from_json(&json_data)?;
+        if bundle_file.len() == 0 {
+            return Err(ArcError::input("Bundle file is empty"));
+        }
+
+        let step = (
+            meta.version.clone(),
+            meta.prev_version
+                .clone()
+                .unwrap_or_else(|| "0.0.0".to_string()),
+        );
+        if self
+            .cache
+            .read()
+            .await
+            .version_tuple_bundles
+            .contains_key(&step)
+        {
+            return Err(ArcError::input(format!(
+                "Bundle {} -> {} already exists",
+                step.1, step.0
+            )));
+        }
+
+        let dir = self.bundle_folder.join(&meta.app_version);
+        let json_path = dir.join(format!("{}.json", meta.file_stem()));
+        let bundle_path = json_path.with_extension("cb");
+        if json_path.exists() || bundle_path.exists() {
+            return Err(ArcError::input(format!(
+                "Bundle files already exist: {json_path:?}"
+            )));
+        }
+
+        let io_error = |e: std::io::Error| ArcError::Io {
+            message: format!("Failed to store bundle: {e}"),
+        };
+        tokio::fs::create_dir_all(&dir).await.map_err(io_error)?;
+        bundle_file
+            .move_copy_to(&bundle_path)
+            .await
+            .map_err(io_error)?;
+        if let Err(e) = tokio::fs::write(&json_path, json_content).await {
+            let _ = tokio::fs::remove_file(&bundle_path).await;
+            return Err(io_error(e));
+        }
+
+        let bundle = {
+            let mut cache = self.cache.write().await;
+            self.process_bundle_json(&json_path, &mut cache)?;
+            cache.sort_versions();
+            cache
+                .version_tuple_bundles
+                .get(&step)
+                .cloned()
+                .ok_or_else(|| ArcError::internal("Uploaded bundle is missing from the index"))?
+        };
+        self.notify_refresh().await;
+        Ok(bundle)
+    }
+
+    /// Get the bundles a client needs to reach the newest content version,
+    /// with a summary of the files they change
+    pub async fn get_bundle_update(
         &self,
         app_version: &str,
         bundle_version: Option<&str>,
         device_id: Option<&str>,
-    ) -> ArcResult<Vec<BundleResponse>> {
+        mirror: Option<&CdnRegion>,
+    ) -> ArcResult<BundleDownloadResponse> {
         let cache = self.cache.read().await;
 
         if self.strict_mode {
             let empty_vec = Vec::new();
             let bundles = cache.bundles.get(app_version).unwrap_or(&empty_vec);
-            return Ok(bundles.iter().map(|b| b.to_response()).collect());
+            return Ok(BundleDownloadResponse {
+                ordered_results: bundles.iter().map(|b| b.to_response()).collect(),
+                patch: None,
+            });
         }
 
         let current_version = bundle_version.unwrap_or("0.0.0");
@@ -397,15 +722,18 @@ impl BundleService {
             .clone();
 
         if current_version == target_version.as_str() {
-            return Ok(Vec::new());
+            return Ok(BundleDownloadResponse {
+                ordered_results: Vec::new(),
+                patch: None,
+            });
         }
 
-        // Find update path using BFS
-        let update_path =
-            Self::find_update_path(&cache.next_versions, current_version, &target_version)?;
-        if update_path.is_empty() {
-            return Ok(Vec::new());
-        }
+        let update_path = Self::find_update_path(
+            &cache.version_tuple_bundles,
+            &cache.next_versions,
+            current_version,
+            &target_version,
+        )?;
 
         let mut matched_bundles = Vec::new();
         for i in 1..update_path.len() {
@@ -426,6 +754,10 @@ impl BundleService {
         }
         drop(cache);
 
+        let patch = (!matched_bundles.is_empty()).then(|| {
+            BundlePatchSummary::from_steps(current_version, &target_version, &matched_bundles)
+        });
+
         // Generate download tokens and URLs
         let mut results = Vec::new();
         let current_time = chrono::Utc::now().timestamp();
@@ -443,6 +775,8 @@ impl BundleService {
                     bundle_size: bundle.bundle_size,
                     json_key: bundle.json_path.clone(),
                     bundle_key: bundle.bundle_path.clone(),
+                    added_files: bundle.patch.added.clone(),
+                    removed_files: bundle.patch.removed.clone(),
                 };
                 bundle_with_urls.json_url = storage.presign_bundle_json(&entry).await?;
                 bundle_with_urls.bundle_url = storage.presign_bundle_file(&entry).await?;
@@ -463,49 +797,66 @@ impl BundleService {
                 .await?;
 
                 // Generate URLs
-                bundle_with_urls.json_url = Some(self.generate_download_url(&json_token));
-                bundle_with_urls.bundle_url = Some(self.generate_download_url(&bundle_token));
+                bundle_with_urls.json_url = Some(self.generate_download_url(&json_token, mirror));
+                bundle_with_urls.bundle_url =
+                    Some(self.generate_download_url(&bundle_token, mirror));
             }
 
             let response = bundle_with_urls.to_response();
             results.push(response);
         }
 
-        Ok(results)
+        Ok(BundleDownloadResponse {
+            ordered_results: results,
+            patch,
+        })
     }
 
-    /// Find update path from current version to target version using BFS
+    /// Find the update path from current version to target version that
+    /// downloads the fewest bytes, so a direct delta wins over a longer chain
+    /// only when it is actually smaller
     fn find_update_path(
+        bundles: &HashMap<(String, String), ContentBundle>,
         next_versions: &HashMap<String, Vec<String>>,
         current_version: &str,
         target_version: &str,
     ) -> ArcResult<Vec<String>> {
-        let mut queue = VecDeque::new();
-        let mut visited = HashSet::new();
-        let mut paths: HashMap<String, Vec<String>> = HashMap::new();
+        let mut best: HashMap<String, (u64, Vec<String>)> = HashMap::new();
+        let mut queue = BinaryHeap::new();
 
-        queue.push_back(current_version.to_string());
-        paths.insert(
+        best.insert(
             current_version.to_string(),
-            vec![current_version.to_string()],
+            (0, vec![current_version.to_string()]),
         );
-        visited.insert(current_version.to_string());
+        queue.push(Reverse((0u64, current_version.to_string())));
 
-        while let Some(version) = queue.pop_front() {
+        while let Some(Reverse((cost, version))) = queue.pop() {
             if version == target_version {
-                return Ok(paths.get(&version).unwrap().clone());
+                return Ok(best
+                    .remove(&version)
+                    .map(|(_, path)| path)
+                    .unwrap_or_default());
+            }
+            if best.get(&version).is_some_and(|(known, _)| *known < cost) {
+                continue;
             }
 
-            if let Some(next_list) = next_versions.get(&version) {
-                for next_version in next_list {
-                    if !visited.contains(next_version) {
-                        visited.insert(next_version.clone());
-                        let mut new_path = paths.get(&version).unwrap().clone();
-                        new_path.push(next_version.clone());
-                        paths.insert(next_version.clone(), new_path);
-                        queue.push_back(next_version.clone());
-                    }
+            for next_version in next_versions.get(&version).into_iter().flatten() {
+                let step = bundles
+                    .get(&(next_version.clone(), version.clone()))
+                    .map_or(0, |bundle| bundle.json_size + bundle.bundle_size);
+                let next_cost = cost + step;
+                if best
+                    .get(next_version)
+                    .is_some_and(|(known, _)| *known <= next_cost)
+                {
+                    continue;
                 }
+
+                let mut path = best[&version].1.clone();
+                path.push(next_version.clone());
+                best.insert(next_version.clone(), (next_cost, path));
+                queue.push(Reverse((next_cost, next_version.clone())));
             }
         }
 
@@ -545,9 +896,12 @@ impl BundleService {
         Ok(())
     }
 
-    /// Generate download URL for token
-    fn generate_download_url(&self, token: &str) -> String {
-        if let Some(prefix) = &self.download_prefix {
+    /// Generate download URL for token, on the mirror's host when it has one
+    fn generate_download_url(&self, token: &str, mirror: Option<&CdnRegion>) -> String {
+        let prefix = mirror
+            .and_then(|mirror| mirror.bundle_prefix.as_ref())
+            .or(self.download_prefix.as_ref());
+        if let Some(prefix) = prefix {
             let mut url = prefix.clone();
             if !url.ends_with('/') {
                 url.push('/');
@@ -635,3 +989,137 @@ impl BundleService {
             .cloned()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bundle(version: &str, prev_version: &str, size: u64, patch: BundlePatch) -> ContentBundle {
+        ContentBundle {
+            version: version.to_string(),
+            prev_version: Some(prev_version.to_string()),
+            app_version: "6.0.0".to_string(),
+            uuid: format!("{prev_version}-{version}"),
+            json_size: 0,
+            bundle_size: size,
+            json_path: String::new(),
+            bundle_path: String::new(),
+            json_url: None,
+            bundle_url: None,
+            patch,
+        }
+    }
+
+    type StepBundles = HashMap<(String, String), ContentBundle>;
+
+    fn graph(bundles: Vec<ContentBundle>) -> (StepBundles, HashMap<String, Vec<String>>) {
+        let mut by_step = HashMap::new();
+        let mut next_versions: HashMap<String, Vec<String>> = HashMap::new();
+        for bundle in bundles {
+            let prev_version = bundle.prev_version.clone().unwrap();
+            next_versions
+                .entry(prev_version.clone())
+                .or_default()
+                .push(bundle.version.clone());
+            by_step.insert((bundle.version.clone(), prev_version), bundle);
+        }
+        (by_step, next_versions)
+    }
+
+    #[test]
+    fn test_find_update_path_prefers_smaller_download() {
+        let (bundles, next_versions) = graph(vec![
+            bundle("1.1.0", "1.0.0", 10, BundlePatch::default()),
+            bundle("1.2.0", "1.1.0", 10, BundlePatch::default()),
+            bundle("1.2.0", "1.0.0", 500, BundlePatch::default()),
+        ]);
+        assert_eq!(
+            BundleService::find_update_path(&bundles, &next_versions, "1.0.0", "1.2.0").unwrap(),
+            vec!["1.0.0", "1.1.0", "1.2.0"]
+        );
+
+        let (bundles, next_versions) = graph(vec![
+            bundle("1.1.0", "1.0.0", 300, BundlePatch::default()),
+            bundle("1.2.0", "1.1.0", 300, BundlePatch::default()),
+            bundle("1.2.0", "1.0.0", 500, BundlePatch::default()),
+        ]);
+        assert_eq!(
+            BundleService::find_update_path(&bundles, &next_versions, "1.0.0", "1.2.0").unwrap(),
+            vec!["1.0.0", "1.2.0"]
+        );
+        assert!(
+            BundleService::find_update_path(&bundles, &next_versions, "0.9.0", "1.2.0").is_err()
+        );
+    }
+
+    #[test]
+    fn test_patch_summary_folds_steps() {
+        let patch = |added: &[&str], removed: &[&str]| BundlePatch {
+            added: added.iter().map(|s| s.to_string()).collect(),
+            removed: removed.iter().map(|s| s.to_string()).collect(),
+        };
+        let steps = vec![
+            bundle(
+                "1.1.0",
+                "1.0.0",
+                10,
+                patch(&["songs/a", "songs/b"], &["songs/c"]),
+            ),
+            bundle("1.2.0", "1.1.0", 20, patch(&["songs/c"], &["songs/b"])),
+        ];
+        let summary = BundlePatchSummary::from_steps("1.0.0", "1.2.0", &steps);
+        assert_eq!(summary.download_size, 30);
+        assert_eq!(summary.changed_files, vec!["songs/a", "songs/c"]);
+        assert_eq!(summary.removed_files, vec!["songs/b"]);
+    }
+
+    #[test]
+    fn test_bundle_upload_meta_from_json() {
+        let meta = BundleUploadMeta::from_json(&serde_json::json!({
+            "versionNumber": "1.2.0",
+            "previousVersionNumber": "1.1.0",
+            "applicationVersionNumber": "6.0.0",
+            "uuid": "a",
+            "added": [{"path": "songs/a"}, "songs/b"]
+        }))
+        .unwrap();
+        assert_eq!(meta.app_version, "6.0.0");
+        assert_eq!(meta.prev_version.as_deref(), Some("1.1.0"));
+        assert_eq!(meta.file_stem(), "1.1.0-1.2.0");
+
+        let full = BundleUploadMeta::from_json(&serde_json::json!({
+            "versionNumber": "1.0.0",
+            "previousVersionNumber": "0.0.0",
+            "applicationVersionNumber": "6.0.0",
+            "uuid": "b"
+        }))
+        .unwrap();
+        assert_eq!(full.prev_version, None);
+        assert_eq!(full.file_stem(), "1.0.0");
+
+        let invalid = [
+            serde_json::json!([]),
+            serde_json::json!({"versionNumber": "1.0", "applicationVersionNumber": "6.0.0", "uuid": "c"}),
+            serde_json::json!({"versionNumber": "1.0.0", "applicationVersionNumber": "../6", "uuid": "c"}),
+            serde_json::json!({"versionNumber": "1.0.0", "applicationVersionNumber": "6.0.0"}),
+            serde_json::json!({"versionNumber": "1.0.0", "previousVersionNumber": "1.0.0",
+                "applicationVersionNumber": "6.0.0", "uuid": "c"}),
+            serde_json::json!({"versionNumber": "1.0.0", "applicationVersionNumber": "6.0.0",
+                "uuid": "c", "removed": [7]}),
+        ];
+        for json in invalid {
+            assert!(BundleUploadMeta::from_json(&json).is_err(), "{json}");
+        }
+    }
+
+    #[test]
+    fn test_bundle_patch_from_json() {
+        let json = serde_json::json!({
+            "added": [{"path": "songs/a", "length": 3}, "songs/b", 7],
+            "removed": ["songs/c"]
+        });
+        let patch = BundlePatch::from_json(&json);
+        assert_eq!(patch.added, vec!["songs/a", "songs/b"]);
+        assert_eq!(patch.removed, vec!["songs/c"]);
+    }
+}
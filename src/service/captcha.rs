@@ -0,0 +1,350 @@
+use crate::config::CONFIG;
+use crate::error::{ArcError, ArcResult};
+use crate::utils::hex_encode;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine as _;
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::env;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+
+const HCAPTCHA_VERIFY_URL: &str = "https://api.hcaptcha.com/siteverify";
+const TURNSTILE_VERIFY_URL: &str = "https://challenges.cloudflare.com/turnstile/v0/siteverify";
+const DEFAULT_POW_DIFFICULTY: u32 = 20;
+const POW_CHALLENGE_TTL_SECONDS: i64 = 300;
+const VERIFY_TIMEOUT_SECONDS: u64 = 10;
+
+/// Anti-bot check required before creating an account.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CaptchaProvider {
+    Disabled,
+    HCaptcha,
+    Turnstile,
+    ProofOfWork,
+}
+
+#[derive(Debug, Clone)]
+pub struct CaptchaConfig {
+    pub provider: CaptchaProvider,
+    pub site_key: String,
+    pub secret: String,
+    pub pow_difficulty: u32,
+}
+
+impl CaptchaConfig {
+    pub fn from_env() -> ArcResult<Self> {
+        let provider = match env::var("CAPTCHA_PROVIDER")
+            .unwrap_or_default()
+            .trim()
+            .to_ascii_lowercase()
+            .as_str()
+        {
+            "" | "disabled" | "off" => CaptchaProvider::Disabled,
+            "hcaptcha" => CaptchaProvider::HCaptcha,
+            "turnstile" => CaptchaProvider::Turnstile,
+            "pow" => CaptchaProvider::ProofOfWork,
+            other => {
+                return Err(ArcError::input(format!(
+                    "Invalid CAPTCHA_PROVIDER `{other}`, expected `disabled`, `hcaptcha`, `turnstile` or `pow`"
+                )))
+            }
+        };
+
+        let secret = env::var("CAPTCHA_SECRET").unwrap_or_default();
+        if matches!(
+            provider,
+            CaptchaProvider::HCaptcha | CaptchaProvider::Turnstile
+        ) && secret.trim().is_empty()
+        {
+            return Err(ArcError::input(
+                "CAPTCHA_SECRET is required for hcaptcha/turnstile",
+            ));
+        }
+
+        Ok(Self {
+            provider,
+            site_key: env::var("CAPTCHA_SITE_KEY").unwrap_or_default(),
+            secret,
+            pow_difficulty: env::var("POW_DIFFICULTY")
+                .ok()
+                .and_then(|value| value.parse().ok())
+                .filter(|bits| (1..=32).contains(bits))
+                .unwrap_or(DEFAULT_POW_DIFFICULTY),
+        })
+    }
+}
+
+/// Client-submitted answer to the registration challenge.
+#[derive(Debug, Clone, Default)]
+pub struct CaptchaAnswer {
+    /// Widget response token for hCaptcha / Turnstile.
+    pub token: Option<String>,
+    /// Challenge string previously issued by [`CaptchaService::challenge`].
+    pub pow_challenge: Option<String>,
+    /// Nonce found by the client for `pow_challenge`.
+    pub pow_nonce: Option<String>,
+}
+
+/// Challenge description returned to clients before registering.
+#[derive(Debug, Clone, Serialize)]
+pub struct CaptchaChallenge {
+    pub provider: CaptchaProvider,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub site_key: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub challenge: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub difficulty: Option<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SiteVerifyResponse {
+    success: bool,
+    #[serde(default, rename = "error-codes")]
+    error_codes: Vec<String>,
+}
+
+/// Server-side CAPTCHA / proof-of-work verification.
+///
+/// Proof-of-work challenges are stateless and signed with `secret_key`; a
+/// challenge is accepted once, and only until it expires.
+#[derive(Clone)]
+pub struct CaptchaService {
+    config: Arc<CaptchaConfig>,
+    http: reqwest::Client,
+    used_challenges: Arc<Mutex<HashMap<String, i64>>>,
+}
+
+impl std::fmt::Debug for CaptchaService {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CaptchaService")
+            .field("provider", &self.config.provider)
+            .finish()
+    }
+}
+
+impl CaptchaService {
+    pub fn from_env() -> ArcResult<Self> {
+        Ok(Self::new(CaptchaConfig::from_env()?))
+    }
+
+    pub fn new(config: CaptchaConfig) -> Self {
+        Self {
+            config: Arc::new(config),
+            http: reqwest::Client::builder()
+                .timeout(Duration::from_secs(VERIFY_TIMEOUT_SECONDS))
+                .build()
+                .unwrap_or_default(),
+            used_challenges: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    pub fn provider(&self) -> CaptchaProvider {
+        self.config.provider
+    }
+
+    /// Describe what the client must solve before registering.
+    pub fn challenge(&self) -> CaptchaChallenge {
+        match self.config.provider {
+            CaptchaProvider::Disabled => CaptchaChallenge {
+                provider: CaptchaProvider::Disabled,
+                site_key: None,
+                challenge: None,
+                difficulty: None,
+            },
+            CaptchaProvider::HCaptcha | CaptchaProvider::Turnstile => CaptchaChallenge {
+                provider: self.config.provider,
+                site_key: Some(self.config.site_key.clone()),
+                challenge: None,
+                difficulty: None,
+            },
+            CaptchaProvider::ProofOfWork => CaptchaChallenge {
+                provider: CaptchaProvider::ProofOfWork,
+                site_key: None,
+                challenge: Some(issue_pow_challenge(
+                    chrono::Utc::now().timestamp(),
+                    self.config.pow_difficulty,
+                )),
+                difficulty: Some(self.config.pow_difficulty),
+            },
+        }
+    }
+
+    /// Verify the answer submitted with a registration request.
+    pub async fn verify(&self, answer: &CaptchaAnswer, remote_ip: Option<&str>) -> ArcResult<()> {
+        match self.config.provider {
+            CaptchaProvider::Disabled => Ok(()),
+            CaptchaProvider::HCaptcha => {
+                self.verify_site_token(HCAPTCHA_VERIFY_URL, answer, remote_ip)
+                    .await
+            }
+            CaptchaProvider::Turnstile => {
+                self.verify_site_token(TURNSTILE_VERIFY_URL, answer, remote_ip)
+                    .await
+            }
+            CaptchaProvider::ProofOfWork => self.verify_pow(answer).await,
+        }
+    }
+
+    async fn verify_site_token(
+        &self,
+        url: &str,
+        answer: &CaptchaAnswer,
+        remote_ip: Option<&str>,
+    ) -> ArcResult<()> {
+        let token = answer
+            .token
+            .as_deref()
+            .filter(|token| !token.trim().is_empty())
+            .ok_or_else(captcha_failed)?;
+
+        let mut form = vec![("secret", self.config.secret.as_str()), ("response", token)];
+        if let Some(ip) = remote_ip {
+            form.push(("remoteip", ip));
+        }
+
+        let response = self
+            .http
+            .post(url)
+            .form(&form)
+            .send()
+            .await
+            .map_err(|e| {
+                log::warn!("CAPTCHA verify request failed: {e}");
+                captcha_failed()
+            })?
+            .json::<SiteVerifyResponse>()
+            .await
+            .map_err(|e| {
+                log::warn!("CAPTCHA verify response invalid: {e}");
+                captcha_failed()
+            })?;
+
+        if response.success {
+            Ok(())
+        } else {
+            log::debug!("CAPTCHA rejected: {:?}", response.error_codes);
+            Err(captcha_failed())
+        }
+    }
+
+    async fn verify_pow(&self, answer: &CaptchaAnswer) -> ArcResult<()> {
+        let (Some(challenge), Some(nonce)) = (&answer.pow_challenge, &answer.pow_nonce) else {
+            return Err(captcha_failed());
+        };
+
+        let now = chrono::Utc::now().timestamp();
+        let issued_at = check_pow_solution(challenge, nonce, self.config.pow_difficulty, now)
+            .ok_or_else(captcha_failed)?;
+
+        let mut used = self.used_challenges.lock().await;
+        used.retain(|_, issued| now - *issued <= POW_CHALLENGE_TTL_SECONDS);
+        if used.insert(challenge.clone(), issued_at).is_some() {
+            return Err(captcha_failed());
+        }
+        Ok(())
+    }
+}
+
+fn captcha_failed() -> ArcError {
+    ArcError::input("Captcha verification failed.")
+}
+
+fn pow_payload_mac(payload: &str) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(CONFIG.secret_key.as_bytes())
+        .expect("HMAC accepts keys of any length");
+    mac.update(payload.as_bytes());
+    mac
+}
+
+/// Build a signed `<issued_at>.<difficulty>.<salt>.<signature>` challenge.
+fn issue_pow_challenge(issued_at: i64, difficulty: u32) -> String {
+    let mut salt = [0u8; 16];
+    rand::thread_rng().fill_bytes(&mut salt);
+    let payload = format!("{issued_at}.{difficulty}.{}", hex_encode(&salt));
+    let signature = pow_payload_mac(&payload).finalize().into_bytes();
+    format!("{payload}.{}", URL_SAFE_NO_PAD.encode(signature))
+}
+
+/// Check a proof-of-work answer and return the challenge issue time.
+///
+/// The answer is valid when `sha256("<challenge>:<nonce>")` starts with at
+/// least `min_difficulty` zero bits.
+fn check_pow_solution(challenge: &str, nonce: &str, min_difficulty: u32, now: i64) -> Option<i64> {
+    let (payload, signature) = challenge.rsplit_once('.')?;
+    let signature = URL_SAFE_NO_PAD.decode(signature).ok()?;
+    pow_payload_mac(payload).verify_slice(&signature).ok()?;
+
+    let mut parts = payload.split('.');
+    let issued_at = parts.next()?.parse::<i64>().ok()?;
+    let difficulty = parts.next()?.parse::<u32>().ok()?;
+    if difficulty < min_difficulty || !(0..=POW_CHALLENGE_TTL_SECONDS).contains(&(now - issued_at))
+    {
+        return None;
+    }
+
+    let digest = Sha256::digest(format!("{challenge}:{nonce}").as_bytes());
+    (leading_zero_bits(&digest) >= difficulty).then_some(issued_at)
+}
+
+fn leading_zero_bits(bytes: &[u8]) -> u32 {
+    let mut bits = 0;
+    for byte in bytes {
+        if *byte == 0 {
+            bits += 8;
+        } else {
+            bits += byte.leading_zeros();
+            break;
+        }
+    }
+    bits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn solve(challenge: &str, difficulty: u32) -> String {
+        (0u64..)
+            .map(|n| n.to_string())
+            .find(|nonce| {
+                let digest = Sha256::digest(format!("{challenge}:{nonce}").as_bytes());
+                leading_zero_bits(&digest) >= difficulty
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn test_pow_solution_accepted() {
+        let challenge = issue_pow_challenge(1_000, 8);
+        let nonce = solve(&challenge, 8);
+        assert_eq!(
+            check_pow_solution(&challenge, &nonce, 8, 1_010),
+            Some(1_000)
+        );
+    }
+
+    #[test]
+    fn test_pow_rejects_expired_or_tampered() {
+        let challenge = issue_pow_challenge(1_000, 8);
+        let nonce = solve(&challenge, 8);
+        assert!(check_pow_solution(&challenge, &nonce, 8, 1_000 + 301).is_none());
+        assert!(check_pow_solution(&challenge, &nonce, 9, 1_010).is_none());
+
+        let tampered = challenge.replacen("1000.8", "1000.1", 1);
+        assert!(check_pow_solution(&tampered, &nonce, 1, 1_010).is_none());
+    }
+
+    #[test]
+    fn test_leading_zero_bits() {
+        assert_eq!(leading_zero_bits(&[0, 0, 0x80]), 16);
+        assert_eq!(leading_zero_bits(&[0, 0x0f]), 12);
+        assert_eq!(leading_zero_bits(&[0xff]), 0);
+    }
+}
@@ -0,0 +1,182 @@
+use crate::error::{ArcError, ArcResult};
+use crate::service::access::IpNetwork;
+use crate::utils::env_list;
+use std::env;
+use std::net::IpAddr;
+
+/// Header a client or edge proxy may set to pick a region explicitly.
+const DEFAULT_REGION_HEADER: &str = "X-Arc-Region";
+
+/// A download mirror serving song files and bundles for part of the players.
+///
+/// Mirrors only change the host in the issued URLs; tokens are still created
+/// and checked by this server, so a mirror is typically a caching proxy in
+/// front of `/download` and `/bundle_download`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CdnRegion {
+    pub name: String,
+    pub download_prefix: Option<String>,
+    pub bundle_prefix: Option<String>,
+    networks: Vec<IpNetwork>,
+    countries: Vec<String>,
+}
+
+/// Region-specific download link prefixes, chosen per request by an explicit
+/// region header, the client network or its GeoIP country.
+pub struct CdnRegions {
+    regions: Vec<CdnRegion>,
+    region_header: String,
+    geoip: Option<maxminddb::Reader<Vec<u8>>>,
+}
+
+impl std::fmt::Debug for CdnRegions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CdnRegions")
+            .field("regions", &self.regions)
+            .field("region_header", &self.region_header)
+            .field("has_geoip", &self.geoip.is_some())
+            .finish()
+    }
+}
+
+impl CdnRegions {
+    pub fn from_env() -> ArcResult<Self> {
+        let regions = env_list("CDN_REGIONS")
+            .into_iter()
+            .map(|name| {
+                let key = name.to_ascii_uppercase().replace('-', "_");
+                Ok(CdnRegion {
+                    download_prefix: env_string(&format!("CDN_REGION_{key}_DOWNLOAD_PREFIX")),
+                    bundle_prefix: env_string(&format!("CDN_REGION_{key}_BUNDLE_PREFIX")),
+                    networks: env_list(&format!("CDN_REGION_{key}_CIDRS"))
+                        .iter()
+                        .map(|value| value.parse())
+                        .collect::<ArcResult<_>>()?,
+                    countries: env_list(&format!("CDN_REGION_{key}_COUNTRIES"))
+                        .into_iter()
+                        .map(|value| value.to_ascii_uppercase())
+                        .collect(),
+                    name,
+                })
+            })
+            .collect::<ArcResult<Vec<_>>>()?;
+
+        let needs_geoip = regions.iter().any(|region| !region.countries.is_empty());
+        let geoip = match env_string("GEOIP_DATABASE").filter(|_| needs_geoip) {
+            Some(path) => Some(maxminddb::Reader::open_readfile(&path).map_err(|e| {
+                ArcError::input(format!("Failed to open GeoIP database `{path}`: {e}"))
+            })?),
+            None if needs_geoip => {
+                return Err(ArcError::input(
+                    "GEOIP_DATABASE is required for CDN region countries",
+                ))
+            }
+            None => None,
+        };
+
+        Ok(Self {
+            regions,
+            region_header: env_string("CDN_REGION_HEADER")
+                .unwrap_or_else(|| DEFAULT_REGION_HEADER.to_string()),
+            geoip,
+        })
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.regions.is_empty()
+    }
+
+    pub fn region_names(&self) -> Vec<&str> {
+        self.regions
+            .iter()
+            .map(|region| region.name.as_str())
+            .collect()
+    }
+
+    pub fn region_header(&self) -> &str {
+        &self.region_header
+    }
+
+    /// Region for a request. A known region name in the header wins, then the
+    /// first region whose networks contain `ip`, then the first region listing
+    /// the GeoIP country of `ip`. `None` means the default prefixes.
+    pub fn select(&self, requested: Option<&str>, ip: Option<IpAddr>) -> Option<&CdnRegion> {
+        if let Some(requested) = requested.map(str::trim) {
+            if let Some(region) = self
+                .regions
+                .iter()
+                .find(|region| region.name.eq_ignore_ascii_case(requested))
+            {
+                return Some(region);
+            }
+        }
+
+        let ip = ip?;
+        if let Some(region) = self
+            .regions
+            .iter()
+            .find(|region| region.networks.iter().any(|net| net.contains(ip)))
+        {
+            return Some(region);
+        }
+
+        let country = self.lookup_country(ip)?;
+        self.regions
+            .iter()
+            .find(|region| region.countries.contains(&country))
+    }
+
+    fn lookup_country(&self, ip: IpAddr) -> Option<String> {
+        let reader = self.geoip.as_ref()?;
+        let record: maxminddb::geoip2::Country = reader.lookup(ip).ok()?;
+        record
+            .country
+            .and_then(|country| country.iso_code)
+            .map(|code| code.to_ascii_uppercase())
+    }
+}
+
+fn env_string(key: &str) -> Option<String> {
+    env::var(key)
+        .ok()
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn region(name: &str, networks: &[&str], countries: &[&str]) -> CdnRegion {
+        CdnRegion {
+            name: name.to_string(),
+            download_prefix: Some(format!("https://{name}.example.com/download/")),
+            bundle_prefix: None,
+            networks: networks.iter().map(|v| v.parse().unwrap()).collect(),
+            countries: countries.iter().map(|v| v.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_select_region() {
+        let regions = CdnRegions {
+            regions: vec![
+                region("asia", &["10.1.0.0/16"], &["JP"]),
+                region("eu", &["10.2.0.0/16"], &[]),
+            ],
+            region_header: DEFAULT_REGION_HEADER.to_string(),
+            geoip: None,
+        };
+        let name = |requested: Option<&str>, ip: &str| {
+            regions
+                .select(requested, ip.parse().ok())
+                .map(|region| region.name.as_str())
+        };
+
+        assert_eq!(name(Some("EU"), "10.1.0.1"), Some("eu"));
+        assert_eq!(name(Some("moon"), "10.1.0.1"), Some("asia"));
+        assert_eq!(name(None, "10.2.3.4"), Some("eu"));
+        assert_eq!(name(None, "8.8.8.8"), None);
+        assert_eq!(name(None, "not an ip"), None);
+    }
+}
@@ -1,15 +1,18 @@
 use crate::config::{Constants, CONFIG};
 use crate::error::{ArcError, ArcResult};
 use crate::model::{
-    Character, CharacterValue, CoreItem, Level, Skill, UserCharacter, UserCharacterInfo,
+    Character, CharacterValue, CoreItem, ItemTypes, Level, Skill, UserCharacter, UserCharacterInfo,
 };
 use crate::service::arc_data::load_arc_data_from_file;
+use crate::service::game_constants::game_constants;
+use crate::service::item::ItemService;
+use crate::service::user_cache::UserCache;
+use crate::DbPool;
 use serde_json::{json, Value};
-use sqlx::MySqlPool;
 
 /// Character service for managing character items and user character data
 pub struct CharacterService {
-    pool: MySqlPool,
+    pool: DbPool,
 }
 
 #[derive(Debug)]
@@ -40,7 +43,7 @@ struct UserCharacterStatsDbRow {
 
 impl CharacterService {
     /// Create a new character service
-    pub fn new(pool: MySqlPool) -> Self {
+    pub fn new(pool: DbPool) -> Self {
         Self { pool }
     }
 
@@ -778,13 +781,7 @@ impl CharacterService {
 
         // Can only toggle if character is actually uncapped
         if !is_uncapped {
-            return Err(ArcError::Base {
-                message: "Unknown Error".to_string(),
-                error_code: 108,
-                api_error_code: -100,
-                extra_data: None,
-                status: 200,
-            });
+            return Err(ArcError::input("Unknown Error"));
         }
 
         let new_override = !is_uncapped_override;
@@ -797,6 +794,7 @@ impl CharacterService {
         )
         .execute(&self.pool)
         .await?;
+        UserCache::global().invalidate_user(user_id);
 
         // Update character table
         if CONFIG.character_full_unlock {
@@ -830,13 +828,7 @@ impl CharacterService {
         character_id: i32,
     ) -> ArcResult<UserCharacterInfo> {
         if CONFIG.character_full_unlock {
-            return Err(ArcError::Base {
-                message: "All characters are available.".to_string(),
-                error_code: 108,
-                api_error_code: -100,
-                extra_data: None,
-                status: 200,
-            });
+            return Err(ArcError::input("All characters are available."));
         }
 
         // Get current uncap state
@@ -845,13 +837,7 @@ impl CharacterService {
             .await?;
 
         if is_uncapped {
-            return Err(ArcError::Base {
-                message: "The character has been uncapped.".to_string(),
-                error_code: 108,
-                api_error_code: -100,
-                extra_data: None,
-                status: 200,
-            });
+            return Err(ArcError::input("The character has been uncapped."));
         }
 
         // Get required cores
@@ -873,37 +859,18 @@ impl CharacterService {
                 .unwrap_or(0);
 
                 if core.amount > user_amount {
-                    return Err(ArcError::ItemNotEnough {
-                        message: "The cores are not enough.".to_string(),
-                        error_code: 108,
-                        api_error_code: -100,
-                        extra_data: None,
-                        status: 200,
-                    });
+                    return Err(ArcError::item_not_enough("The cores are not enough."));
                 }
             }
         }
 
         // Consume cores
+        let item_service = ItemService::new(self.pool.clone());
         for core in &uncap_cores {
             if core.amount > 0 {
-                sqlx::query!(
-                    "UPDATE user_item SET amount = amount - ? WHERE user_id = ? AND item_id = ? AND type = 'core'",
-                    core.amount,
-                    user_id,
-                    core.item_id
-                )
-                .execute(&self.pool)
-                .await?;
-
-                // Remove item if amount becomes 0
-                sqlx::query!(
-                    "DELETE FROM user_item WHERE user_id = ? AND item_id = ? AND type = 'core' AND amount <= 0",
-                    user_id,
-                    core.item_id
-                )
-                .execute(&self.pool)
-                .await?;
+                item_service
+                    .spend_positive_item(user_id, &core.item_id, ItemTypes::CORE, core.amount)
+                    .await?;
             }
         }
 
@@ -942,20 +909,14 @@ impl CharacterService {
         }
 
         if CONFIG.character_full_unlock {
-            return Err(ArcError::Base {
-                message: "All characters are available.".to_string(),
-                error_code: 108,
-                api_error_code: -100,
-                extra_data: None,
-                status: 200,
-            });
+            return Err(ArcError::input("All characters are available."));
         }
 
         // Get current character info
         let mut char_info = self.get_user_character_info(user_id, character_id).await?;
 
         // Set max level based on uncap state
-        char_info.level.max_level = if char_info.is_uncapped { 30 } else { 20 };
+        char_info.level.max_level = Level::max_level_for(char_info.is_uncapped);
 
         // Add experience and calculate new level
         char_info.level.add_exp(exp_addition)?;
@@ -1010,40 +971,34 @@ impl CharacterService {
         .unwrap_or(0);
 
         if (-core_amount) > user_amount {
-            return Err(ArcError::ItemNotEnough {
-                message: "Not enough core_generic.".to_string(),
-                error_code: 108,
-                api_error_code: -100,
-                extra_data: None,
-                status: 200,
-            });
+            return Err(ArcError::item_not_enough("Not enough core_generic."));
         }
 
         // Consume cores
-        sqlx::query!(
-            "UPDATE user_item SET amount = amount + ? WHERE user_id = ? AND item_id = 'core_generic' AND type = 'core'",
-            core_amount,
-            user_id
-        )
-        .execute(&self.pool)
-        .await?;
-
-        // Remove item if amount becomes 0
-        sqlx::query!(
-            "DELETE FROM user_item WHERE user_id = ? AND item_id = 'core_generic' AND type = 'core' AND amount <= 0",
-            user_id
-        )
-        .execute(&self.pool)
-        .await?;
+        ItemService::new(self.pool.clone())
+            .spend_positive_item(user_id, "core_generic", ItemTypes::CORE, -core_amount)
+            .await?;
 
         // Calculate exp to add
-        let exp_addition = (-core_amount) as f64 * Constants::CORE_EXP as f64;
+        let exp_addition = (-core_amount) as f64 * game_constants().core_exp;
 
         // Upgrade character
         self.upgrade_character(user_id, character_id, exp_addition)
             .await
     }
 
+    /// Sum of the levels of every character the user owns, used by the
+    /// Tairitsu (Tempest) skill
+    pub async fn get_user_character_level_sum(&self, user_id: i32) -> ArcResult<i32> {
+        let sum = sqlx::query_scalar!(
+            "SELECT CAST(COALESCE(SUM(level), 0) AS SIGNED) FROM user_char WHERE user_id = ?",
+            user_id
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(sum.unwrap_or(0) as i32)
+    }
+
     /// Change character skill state (for Maya) - matches Python change_skill_state
     pub async fn change_character_skill_state(
         &self,
@@ -1257,3 +1212,358 @@ impl CharacterService {
         Ok(())
     }
 }
+
+/// Tairitsu (Tempest), whose `gauge_tempest` also raises prog on normal maps
+const TEMPEST_CHARACTER_ID: i32 = 35;
+
+/// Prog bonus of Tairitsu (Tempest) when every character is maxed out
+const TEMPEST_MAX_PROG_BONUS: f64 = 60.0;
+
+/// Stat gained per triggered entry of a Cytus II or Chinatsu skill flag
+const SKILL_FLAG_STAT_BONUS: f64 = 10.0;
+
+/// What a world mode play did, as far as character skills care
+#[derive(Debug, Clone, Default)]
+pub struct SkillPlay {
+    pub song_id: String,
+    pub health: i32,
+    pub clear_type: i32,
+    pub song_grade: i32,
+    pub is_beyond: bool,
+    pub highest_health: Option<i32>,
+    pub lowest_health: Option<i32>,
+    pub combo_interval_bonus: Option<i32>,
+    pub skill_cytusii_flag: Option<String>,
+    pub skill_chinatsu_flag: Option<String>,
+    /// Level sum of the user's characters, only needed for Tairitsu (Tempest)
+    pub character_level_sum: Option<i32>,
+}
+
+/// Additions to the partner stats made by a skill before the step is calculated
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SkillStatBonus {
+    pub frag: f64,
+    pub prog: f64,
+    pub overdrive: f64,
+}
+
+impl SkillStatBonus {
+    fn add_world_value(&mut self, value: char, amount: f64) {
+        match value {
+            '0' => self.frag += amount,
+            '1' => self.prog += amount,
+            '2' => self.overdrive += amount,
+            _ => {}
+        }
+    }
+}
+
+/// Where the climb starts and what it would hand out, for skills that change
+/// the progress of a play
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SkillClimb<'a> {
+    /// Step types of the step the play started on, e.g. `randomsong`
+    pub start_step_types: &'a [String],
+    /// Whether the starting step restricts the songs that can be played
+    pub start_step_restricted: bool,
+    /// Whether the steps climbed so far give out fragments
+    pub rewards_fragment: bool,
+    /// Grade of the play, 5 being EX
+    pub song_grade: i32,
+    /// Maya's `skill_flag`
+    pub skill_flag: bool,
+    /// Whether Ayu's coin flip came up heads
+    pub lucky: bool,
+}
+
+/// Effect of a skill once the map has been climbed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SkillAfterClimb {
+    /// Hikari (Fatalis) locks world mode for a while
+    LockWorldMode,
+    /// Maya flips her `skill_flag`
+    ToggleSkillState,
+}
+
+/// Maps a displayed skill id to its effect on world mode progression.
+///
+/// Only skills that change the server side of a play are handled; gauge,
+/// visual and fragment skills like `gauge_overflow` or `frags_kou` act in
+/// the client during the song and have no effect here.
+pub struct SkillEngine;
+
+impl SkillEngine {
+    /// Stat bonus of `skill_id` for the play, applied before the step value
+    /// is calculated
+    pub fn stat_bonus(
+        skill_id: &str,
+        character: &UserCharacterInfo,
+        play: &SkillPlay,
+    ) -> SkillStatBonus {
+        let mut bonus = SkillStatBonus::default();
+        match skill_id {
+            // Overdrive rises with the recollection rate, by up to 10
+            "skill_vita" if 0 < play.health && play.health <= 100 => {
+                bonus.overdrive = play.health as f64 / 10.0;
+            }
+            // Doubles prog and overdrive on a cleared play of her songs
+            "skill_mika"
+                if play.clear_type != 0
+                    && Constants::SKILL_MIKA_SONGS.contains(&play.song_id.as_str()) =>
+            {
+                bonus.prog = character.prog_value();
+                bonus.overdrive = character.overdrive_value();
+            }
+            // One prog per combo interval reached
+            "skill_mithra" => {
+                bonus.prog = play.combo_interval_bonus.unwrap_or(0).max(0) as f64;
+            }
+            // Flag entries trigger as the recollection rate climbs
+            "skill_ilith_ivy" => {
+                if let (Some(flag), Some(highest)) = (&play.skill_cytusii_flag, play.highest_health)
+                {
+                    Self::add_flag_bonus(&mut bonus, flag, highest);
+                }
+            }
+            // Flag entries trigger as the recollection rate drops
+            "skill_hikari_vanessa" => {
+                if let (Some(flag), Some(lowest)) = (&play.skill_cytusii_flag, play.lowest_health) {
+                    Self::add_flag_bonus(&mut bonus, flag, 100 - lowest);
+                }
+            }
+            "skill_chinatsu" => {
+                if let (Some(flag), Some(highest)) =
+                    (&play.skill_chinatsu_flag, play.highest_health)
+                {
+                    Self::add_flag_bonus(&mut bonus, flag, highest);
+                }
+            }
+            _ => {}
+        }
+
+        // Prog grows with the level of every owned character on normal maps
+        if character.character_id == TEMPEST_CHARACTER_ID && !play.is_beyond {
+            bonus.prog += play
+                .character_level_sum
+                .map_or(TEMPEST_MAX_PROG_BONUS, |sum| sum as f64 / 10.0)
+                .clamp(0.0, TEMPEST_MAX_PROG_BONUS);
+        }
+        bonus
+    }
+
+    /// Whether [`SkillPlay::character_level_sum`] matters for `character`
+    pub fn uses_character_level_sum(character: &UserCharacterInfo) -> bool {
+        character.character_id == TEMPEST_CHARACTER_ID
+    }
+
+    /// Progress of the play after `skill_id`, given the `progress` it made
+    /// without the skill and the stamina / fragment / boost `step_times`
+    pub fn progress(skill_id: &str, progress: f64, step_times: f64, climb: &SkillClimb) -> f64 {
+        match skill_id {
+            // +7 when the climb hands out fragments
+            "eto_uncap" if climb.rewards_fragment => {
+                progress + Constants::ETO_UNCAP_BONUS_PROGRESS as f64 * step_times
+            }
+            // +7 when starting on a restricted step
+            "luna_uncap" if climb.start_step_restricted => {
+                progress + Constants::LUNA_UNCAP_BONUS_PROGRESS as f64 * step_times
+            }
+            // +5 or -5 at random, never below 0
+            "ayu_uncap" => {
+                let bonus = Constants::AYU_UNCAP_BONUS_PROGRESS as f64 * step_times;
+                if climb.lucky {
+                    progress + bonus
+                } else {
+                    (progress - bonus).max(0.0)
+                }
+            }
+            // Halved below EX when starting on a random song or speed limit step
+            "skill_amane" => {
+                let hindered = climb
+                    .start_step_types
+                    .iter()
+                    .any(|step_type| step_type == "randomsong" || step_type == "speedlimit");
+                if hindered && climb.song_grade < 5 {
+                    progress / 2.0
+                } else {
+                    progress
+                }
+            }
+            // Doubled while the skill is active
+            "skill_maya" if climb.skill_flag => progress * 2.0,
+            _ => progress,
+        }
+    }
+
+    /// Effect of `skill_id` after the map has been climbed
+    pub fn after_climb(skill_id: &str) -> Option<SkillAfterClimb> {
+        match skill_id {
+            "skill_fatalis" => Some(SkillAfterClimb::LockWorldMode),
+            "skill_maya" => Some(SkillAfterClimb::ToggleSkillState),
+            _ => None,
+        }
+    }
+
+    /// Add the entries of `flag` triggered at `health`, one per 20%
+    fn add_flag_bonus(bonus: &mut SkillStatBonus, flag: &str, health: i32) {
+        let triggered = (health.clamp(0, 100) / 20) as usize;
+        for value in flag.chars().take(triggered) {
+            bonus.add_world_value(value, SKILL_FLAG_STAT_BONUS);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn character(character_id: i32, stat: f64) -> UserCharacterInfo {
+        let mut value = CharacterValue::new();
+        value.set_parameter(stat, stat, stat);
+        UserCharacterInfo {
+            character_id,
+            name: String::new(),
+            char_type: 0,
+            level: Level::new(),
+            skill: Skill::new(),
+            frag: value.clone(),
+            prog: value.clone(),
+            overdrive: value,
+            is_uncapped: false,
+            is_uncapped_override: false,
+            skill_flag: false,
+            uncap_cores: Vec::new(),
+            voice: None,
+            fatalis_is_limited: false,
+        }
+    }
+
+    #[test]
+    fn test_stat_bonus_vita_and_mika() {
+        let vita = character(54, 50.0);
+        let play = SkillPlay {
+            health: 85,
+            ..Default::default()
+        };
+        assert_eq!(
+            SkillEngine::stat_bonus("skill_vita", &vita, &play).overdrive,
+            8.5
+        );
+        let failed = SkillPlay {
+            health: -1,
+            ..Default::default()
+        };
+        assert_eq!(
+            SkillEngine::stat_bonus("skill_vita", &vita, &failed),
+            SkillStatBonus::default()
+        );
+
+        let mika = character(65, 60.0);
+        let mut play = SkillPlay {
+            song_id: "cycles".to_string(),
+            clear_type: 1,
+            ..Default::default()
+        };
+        let bonus = SkillEngine::stat_bonus("skill_mika", &mika, &play);
+        assert_eq!((bonus.prog, bonus.overdrive), (60.0, 60.0));
+        play.clear_type = 0;
+        assert_eq!(
+            SkillEngine::stat_bonus("skill_mika", &mika, &play),
+            SkillStatBonus::default()
+        );
+    }
+
+    #[test]
+    fn test_stat_bonus_skill_flags() {
+        let ilith = character(69, 50.0);
+        let play = SkillPlay {
+            highest_health: Some(65),
+            lowest_health: Some(30),
+            skill_cytusii_flag: Some("12210".to_string()),
+            ..Default::default()
+        };
+        // 65% triggers the first three entries
+        assert_eq!(
+            SkillEngine::stat_bonus("skill_ilith_ivy", &ilith, &play),
+            SkillStatBonus {
+                frag: 0.0,
+                prog: 10.0,
+                overdrive: 20.0
+            }
+        );
+        // 70% lost triggers the first three entries
+        assert_eq!(
+            SkillEngine::stat_bonus("skill_hikari_vanessa", &ilith, &play),
+            SkillStatBonus {
+                frag: 0.0,
+                prog: 10.0,
+                overdrive: 20.0
+            }
+        );
+        // Gauge and fragment skills act in the client only
+        assert_eq!(
+            SkillEngine::stat_bonus("frags_kou", &ilith, &play),
+            SkillStatBonus::default()
+        );
+        assert_eq!(
+            SkillEngine::stat_bonus("gauge_overflow", &ilith, &play),
+            SkillStatBonus::default()
+        );
+    }
+
+    #[test]
+    fn test_stat_bonus_tempest() {
+        let tempest = character(TEMPEST_CHARACTER_ID, 50.0);
+        let mut play = SkillPlay {
+            character_level_sum: Some(250),
+            ..Default::default()
+        };
+        assert_eq!(
+            SkillEngine::stat_bonus("gauge_tempest", &tempest, &play).prog,
+            25.0
+        );
+        play.character_level_sum = Some(5000);
+        assert_eq!(
+            SkillEngine::stat_bonus("gauge_tempest", &tempest, &play).prog,
+            TEMPEST_MAX_PROG_BONUS
+        );
+        play.is_beyond = true;
+        assert_eq!(
+            SkillEngine::stat_bonus("gauge_tempest", &tempest, &play).prog,
+            0.0
+        );
+    }
+
+    #[test]
+    fn test_skill_progress() {
+        let random_step = vec!["randomsong".to_string()];
+        let mut climb = SkillClimb {
+            start_step_types: &random_step,
+            song_grade: 4,
+            ..Default::default()
+        };
+        assert_eq!(SkillEngine::progress("skill_amane", 10.0, 2.0, &climb), 5.0);
+        climb.song_grade = 5;
+        assert_eq!(
+            SkillEngine::progress("skill_amane", 10.0, 2.0, &climb),
+            10.0
+        );
+
+        assert_eq!(SkillEngine::progress("ayu_uncap", 4.0, 2.0, &climb), 0.0);
+        climb.lucky = true;
+        assert_eq!(SkillEngine::progress("ayu_uncap", 4.0, 2.0, &climb), 14.0);
+
+        assert_eq!(SkillEngine::progress("eto_uncap", 4.0, 2.0, &climb), 4.0);
+        climb.rewards_fragment = true;
+        assert_eq!(SkillEngine::progress("eto_uncap", 4.0, 2.0, &climb), 18.0);
+
+        assert_eq!(SkillEngine::progress("skill_maya", 4.0, 2.0, &climb), 4.0);
+        climb.skill_flag = true;
+        assert_eq!(SkillEngine::progress("skill_maya", 4.0, 2.0, &climb), 8.0);
+        assert_eq!(
+            SkillEngine::after_climb("skill_maya"),
+            Some(SkillAfterClimb::ToggleSkillState)
+        );
+        assert_eq!(SkillEngine::after_climb("frags_kou"), None);
+    }
+}
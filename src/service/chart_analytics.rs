@@ -0,0 +1,393 @@
+use crate::config::CONFIG;
+use crate::error::ArcResult;
+use crate::DbPool;
+use std::collections::HashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Lower bounds of the score buckets after the first (`< 9.5M`) one.
+pub const SCORE_BUCKETS: [i32; 4] = [9_500_000, 9_800_000, 9_900_000, 10_000_000];
+/// Plays needed before a perceived constant is reported.
+const MIN_PERCEIVED_SAMPLES: usize = 5;
+/// Plays below this score say little about the chart's constant, since the
+/// play rating formula flattens out there.
+const MIN_PERCEIVED_SCORE: i32 = 9_500_000;
+
+/// How implied constants are combined into an estimate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EstimateModel {
+    Median,
+    Mean,
+    /// Mean of the middle 80% of samples.
+    TrimmedMean,
+}
+
+impl EstimateModel {
+    pub fn parse(raw: &str) -> Option<Self> {
+        match raw.trim() {
+            "median" => Some(Self::Median),
+            "mean" => Some(Self::Mean),
+            "trimmed_mean" => Some(Self::TrimmedMean),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Median => "median",
+            Self::Mean => "mean",
+            Self::TrimmedMean => "trimmed_mean",
+        }
+    }
+}
+
+/// One player's best score on a chart, with their potential.
+struct BestScoreSample {
+    score: i32,
+    perfect_count: i32,
+    near_count: i32,
+    miss_count: i32,
+    clear_type: i32,
+    rating_ptt: i32,
+}
+
+/// Per-chart statistics accumulated from best scores.
+#[derive(Debug, Default)]
+struct ChartAccumulator {
+    player_count: usize,
+    score_sum: f64,
+    accuracy_sum: f64,
+    accuracy_count: usize,
+    clear_count: usize,
+    distribution: [i64; SCORE_BUCKETS.len() + 1],
+    perceived_samples: Vec<f64>,
+}
+
+impl ChartAccumulator {
+    fn add(&mut self, sample: &BestScoreSample) {
+        self.player_count += 1;
+        self.score_sum += sample.score as f64;
+        self.distribution[score_bucket(sample.score)] += 1;
+        if sample.clear_type > 0 {
+            self.clear_count += 1;
+        }
+        if let Some(accuracy) =
+            play_accuracy(sample.perfect_count, sample.near_count, sample.miss_count)
+        {
+            self.accuracy_sum += accuracy;
+            self.accuracy_count += 1;
+        }
+        if let Some(constant) = implied_constant(sample.score, sample.rating_ptt) {
+            self.perceived_samples.push(constant);
+        }
+    }
+}
+
+/// Builds the `chart_analytics` table from `best_score`, so operators can
+/// spot charts whose listed constant does not match how players score on
+/// them, and proposes constants for unrated charts.
+#[derive(Clone)]
+pub struct ChartAnalyticsService {
+    pool: DbPool,
+}
+
+impl ChartAnalyticsService {
+    pub fn new(pool: DbPool) -> Self {
+        Self { pool }
+    }
+
+    /// Recompute analytics for every chart with at least one best score.
+    /// Returns the number of charts written.
+    pub async fn refresh(&self) -> ArcResult<usize> {
+        let rows = sqlx::query!(
+            "SELECT b.song_id AS `song_id!`, b.difficulty AS `difficulty!`,
+                    COALESCE(b.score, 0) AS `score!: i32`,
+                    COALESCE(b.perfect_count, 0) AS `perfect_count!: i32`,
+                    COALESCE(b.near_count, 0) AS `near_count!: i32`,
+                    COALESCE(b.miss_count, 0) AS `miss_count!: i32`,
+                    COALESCE(b.best_clear_type, 0) AS `clear_type!: i32`,
+                    COALESCE(u.rating_ptt, 0) AS `rating_ptt!: i32`
+             FROM best_score b
+             JOIN user u ON u.user_id = b.user_id
+             WHERE b.song_id IS NOT NULL AND b.difficulty IS NOT NULL"
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut charts: HashMap<(String, i32), ChartAccumulator> = HashMap::new();
+        for row in rows {
+            charts
+                .entry((row.song_id, row.difficulty))
+                .or_default()
+                .add(&BestScoreSample {
+                    score: row.score,
+                    perfect_count: row.perfect_count,
+                    near_count: row.near_count,
+                    miss_count: row.miss_count,
+                    clear_type: row.clear_type,
+                    rating_ptt: row.rating_ptt,
+                });
+        }
+
+        let computed_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as i64;
+        let mut tx = self.pool.begin().await?;
+        sqlx::query!("DELETE FROM chart_analytics")
+            .execute(&mut *tx)
+            .await?;
+        let chart_count = charts.len();
+        for ((song_id, difficulty), mut chart) in charts {
+            let players = chart.player_count as f64;
+            let avg_accuracy = if chart.accuracy_count > 0 {
+                chart.accuracy_sum / chart.accuracy_count as f64
+            } else {
+                0.0
+            };
+            let sample_count = chart.perceived_samples.len() as i32;
+            let perceived_constant = perceived_constant(&mut chart.perceived_samples);
+            let score_distribution = serde_json::to_string(&chart.distribution)?;
+            sqlx::query!(
+                "INSERT INTO chart_analytics
+                 (song_id, difficulty, player_count, avg_score, avg_accuracy, clear_rate,
+                  score_distribution, perceived_constant, sample_count, computed_at)
+                 VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+                song_id,
+                difficulty,
+                chart.player_count as i32,
+                chart.score_sum / players,
+                avg_accuracy,
+                chart.clear_count as f64 / players,
+                score_distribution,
+                perceived_constant,
+                sample_count,
+                computed_at
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+
+        Ok(chart_count)
+    }
+
+    /// Propose constants for unrated charts (`rating_* <= 0` in `chart`)
+    /// from the implied constants of their best scores, using the configured
+    /// model. Proposals are rebuilt on each run and wait in
+    /// `chart_constant_proposal` until accepted in the chart editor.
+    /// Returns the number of proposals written.
+    pub async fn estimate_unrated_constants(&self) -> ArcResult<usize> {
+        let model = EstimateModel::parse(&CONFIG.chart_estimate_model).unwrap_or_else(|| {
+            log::warn!(
+                "Unknown chart_estimate_model {:?}, falling back to median",
+                CONFIG.chart_estimate_model
+            );
+            EstimateModel::Median
+        });
+        let min_samples = CONFIG.chart_estimate_min_samples.max(1) as usize;
+
+        let rows = sqlx::query!(
+            "SELECT b.song_id AS `song_id!`, b.difficulty AS `difficulty!`,
+                    COALESCE(b.score, 0) AS `score!: i32`,
+                    COALESCE(u.rating_ptt, 0) AS `rating_ptt!: i32`
+             FROM best_score b
+             JOIN user u ON u.user_id = b.user_id
+             JOIN chart c ON c.song_id = b.song_id
+             WHERE COALESCE(CASE b.difficulty
+                     WHEN 0 THEN c.rating_pst
+                     WHEN 1 THEN c.rating_prs
+                     WHEN 2 THEN c.rating_ftr
+                     WHEN 3 THEN c.rating_byn
+                     WHEN 4 THEN c.rating_etr
+                 END, -1) <= 0"
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut charts: HashMap<(String, i32), Vec<f64>> = HashMap::new();
+        for row in rows {
+            if let Some(constant) = implied_constant(row.score, row.rating_ptt) {
+                charts
+                    .entry((row.song_id, row.difficulty))
+                    .or_default()
+                    .push(constant);
+            }
+        }
+
+        let created_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as i64;
+        let mut tx = self.pool.begin().await?;
+        sqlx::query!("DELETE FROM chart_constant_proposal")
+            .execute(&mut *tx)
+            .await?;
+        let mut proposal_count = 0;
+        for ((song_id, difficulty), mut samples) in charts {
+            if samples.len() < min_samples {
+                continue;
+            }
+            let Some(estimate) = estimate_constant(&mut samples, model) else {
+                continue;
+            };
+            let proposed_rating = (estimate * 10.0).round() as i32;
+            if proposed_rating <= 0 {
+                continue;
+            }
+            sqlx::query!(
+                "INSERT INTO chart_constant_proposal
+                 (song_id, difficulty, proposed_rating, sample_count, model, created_at)
+                 VALUES (?, ?, ?, ?, ?, ?)",
+                song_id,
+                difficulty,
+                proposed_rating,
+                samples.len() as i32,
+                model.as_str(),
+                created_at
+            )
+            .execute(&mut *tx)
+            .await?;
+            proposal_count += 1;
+        }
+        tx.commit().await?;
+
+        Ok(proposal_count)
+    }
+}
+
+/// Index into the score distribution for `score`.
+fn score_bucket(score: i32) -> usize {
+    SCORE_BUCKETS
+        .iter()
+        .filter(|threshold| score >= **threshold)
+        .count()
+}
+
+/// Play rating above the chart constant for `score`, mirroring
+/// `Score::calculate_rating` without the clamp at zero.
+fn score_modifier(score: i32) -> f64 {
+    if score >= 10_000_000 {
+        2.0
+    } else if score >= 9_800_000 {
+        1.0 + (score - 9_800_000) as f64 / 200_000.0
+    } else {
+        (score - 9_500_000) as f64 / 300_000.0
+    }
+}
+
+/// Hit accuracy in `0.0..=1.0`, counting a far as half a pure.
+fn play_accuracy(perfect_count: i32, near_count: i32, miss_count: i32) -> Option<f64> {
+    let notes = perfect_count + near_count + miss_count;
+    if notes <= 0 {
+        return None;
+    }
+    Some((perfect_count as f64 + near_count as f64 / 2.0) / notes as f64)
+}
+
+/// The constant at which this play would rate exactly at the player's
+/// potential. Players tend to score near their potential on a fairly rated
+/// chart, so the median over many players approximates its real difficulty.
+fn implied_constant(score: i32, rating_ptt: i32) -> Option<f64> {
+    if rating_ptt <= 0 || score < MIN_PERCEIVED_SCORE {
+        return None;
+    }
+    Some(rating_ptt as f64 / 100.0 - score_modifier(score))
+}
+
+/// Median of the implied constants, once there are enough of them.
+fn perceived_constant(samples: &mut [f64]) -> Option<f64> {
+    if samples.len() < MIN_PERCEIVED_SAMPLES {
+        return None;
+    }
+    estimate_constant(samples, EstimateModel::Median)
+}
+
+/// Combine implied constants with `model`, rounded to two decimals.
+fn estimate_constant(samples: &mut [f64], model: EstimateModel) -> Option<f64> {
+    if samples.is_empty() {
+        return None;
+    }
+    samples.sort_by(f64::total_cmp);
+    let estimate = match model {
+        EstimateModel::Median => {
+            let mid = samples.len() / 2;
+            if samples.len() % 2 == 0 {
+                (samples[mid - 1] + samples[mid]) / 2.0
+            } else {
+                samples[mid]
+            }
+        }
+        EstimateModel::Mean => samples.iter().sum::<f64>() / samples.len() as f64,
+        EstimateModel::TrimmedMean => {
+            let trim = samples.len() / 10;
+            let kept = &samples[trim..samples.len() - trim];
+            kept.iter().sum::<f64>() / kept.len() as f64
+        }
+    };
+    Some((estimate * 100.0).round() / 100.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_score_bucket() {
+        assert_eq!(score_bucket(9_000_000), 0);
+        assert_eq!(score_bucket(9_500_000), 1);
+        assert_eq!(score_bucket(9_850_000), 2);
+        assert_eq!(score_bucket(9_999_999), 3);
+        assert_eq!(score_bucket(10_001_000), 4);
+    }
+
+    #[test]
+    fn test_implied_constant() {
+        assert_eq!(implied_constant(10_000_000, 1200), Some(10.0));
+        assert_eq!(implied_constant(9_800_000, 1200), Some(11.0));
+        assert_eq!(implied_constant(9_650_000, 1050), Some(10.0));
+        assert_eq!(implied_constant(9_400_000, 1200), None);
+        assert_eq!(implied_constant(10_000_000, 0), None);
+    }
+
+    #[test]
+    fn test_perceived_constant() {
+        assert_eq!(perceived_constant(&mut [10.0, 10.5, 9.0, 11.0]), None);
+        assert_eq!(
+            perceived_constant(&mut [10.0, 10.5, 9.0, 11.0, 12.0]),
+            Some(10.5)
+        );
+        assert_eq!(
+            perceived_constant(&mut [10.0, 10.5, 9.0, 11.0, 12.0, 9.5]),
+            Some(10.25)
+        );
+    }
+
+    #[test]
+    fn test_estimate_constant() {
+        let samples = [8.0, 9.0, 9.0, 9.5, 9.5, 10.0, 10.0, 10.5, 11.0, 20.0];
+        assert_eq!(
+            estimate_constant(&mut samples.clone(), EstimateModel::Median),
+            Some(9.75)
+        );
+        assert_eq!(
+            estimate_constant(&mut samples.clone(), EstimateModel::Mean),
+            Some(10.65)
+        );
+        assert_eq!(
+            estimate_constant(&mut samples.clone(), EstimateModel::TrimmedMean),
+            Some(9.81)
+        );
+        assert_eq!(estimate_constant(&mut [], EstimateModel::Mean), None);
+        assert_eq!(
+            EstimateModel::parse("trimmed_mean"),
+            Some(EstimateModel::TrimmedMean)
+        );
+        assert_eq!(EstimateModel::parse("mode"), None);
+    }
+
+    #[test]
+    fn test_play_accuracy() {
+        assert_eq!(play_accuracy(0, 0, 0), None);
+        assert_eq!(play_accuracy(90, 10, 0), Some(0.95));
+    }
+}
@@ -0,0 +1,119 @@
+use crate::config::CONFIG;
+
+/// Why a client version was refused by [`ClientVersionPolicy`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VersionRejection {
+    /// Missing, not in `allow_appversion` or below `min_app_version`.
+    TooOld,
+    /// Above `max_app_version`, i.e. released before the server caught up.
+    TooNew,
+}
+
+/// Supported `AppVersion` range of game clients.
+///
+/// A non-empty `allow_appversion` list only admits the exact versions listed,
+/// like the Python server. `min_app_version` / `max_app_version` are
+/// inclusive bounds compared as `major.minor.patch`; suffixes such as the `c`
+/// of `6.3.0c` are ignored and missing parts count as 0.
+#[derive(Debug, Clone, Default)]
+pub struct ClientVersionPolicy {
+    allowed: Vec<String>,
+    min: Option<(u32, u32, u32)>,
+    max: Option<(u32, u32, u32)>,
+}
+
+impl ClientVersionPolicy {
+    pub fn from_config() -> Self {
+        Self {
+            allowed: CONFIG
+                .allow_appversion
+                .iter()
+                .map(|version| version.trim().to_string())
+                .filter(|version| !version.is_empty())
+                .collect(),
+            min: parse_app_version(&CONFIG.min_app_version),
+            max: parse_app_version(&CONFIG.max_app_version),
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        !self.allowed.is_empty() || self.min.is_some() || self.max.is_some()
+    }
+
+    /// Check the `AppVersion` header of a request. Requests without the
+    /// header only fail when an allow list is configured.
+    pub fn check(&self, app_version: Option<&str>) -> Result<(), VersionRejection> {
+        let app_version = app_version.map(str::trim).filter(|v| !v.is_empty());
+        if !self.allowed.is_empty()
+            && !app_version.is_some_and(|version| self.allowed.iter().any(|v| v == version))
+        {
+            return Err(VersionRejection::TooOld);
+        }
+
+        let Some(version) = app_version.and_then(parse_app_version) else {
+            return Ok(());
+        };
+        if self.min.is_some_and(|min| version < min) {
+            return Err(VersionRejection::TooOld);
+        }
+        if self.max.is_some_and(|max| version > max) {
+            return Err(VersionRejection::TooNew);
+        }
+        Ok(())
+    }
+}
+
+/// Parse `6.3.0c` style versions into `(6, 3, 0)`.
+fn parse_app_version(version: &str) -> Option<(u32, u32, u32)> {
+    let version = version.trim();
+    if version.is_empty() {
+        return None;
+    }
+    let mut parts = version.split('.').map(|part| {
+        let digits = part
+            .trim()
+            .chars()
+            .take_while(char::is_ascii_digit)
+            .collect::<String>();
+        digits.parse::<u32>().ok()
+    });
+    let major = parts.next().flatten()?;
+    let minor = parts.next().flatten().unwrap_or(0);
+    let patch = parts.next().flatten().unwrap_or(0);
+    Some((major, minor, patch))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_app_version() {
+        assert_eq!(parse_app_version("6.3.0c"), Some((6, 3, 0)));
+        assert_eq!(parse_app_version("5.10"), Some((5, 10, 0)));
+        assert_eq!(parse_app_version(""), None);
+        assert_eq!(parse_app_version("beta"), None);
+    }
+
+    #[test]
+    fn test_version_range() {
+        let policy = ClientVersionPolicy {
+            allowed: Vec::new(),
+            min: Some((5, 10, 0)),
+            max: Some((6, 3, 0)),
+        };
+        assert_eq!(policy.check(Some("6.1.2c")), Ok(()));
+        assert_eq!(policy.check(Some("6.3.0")), Ok(()));
+        assert_eq!(policy.check(None), Ok(()));
+        assert_eq!(policy.check(Some("5.9.9")), Err(VersionRejection::TooOld));
+        assert_eq!(policy.check(Some("6.4.0")), Err(VersionRejection::TooNew));
+
+        let policy = ClientVersionPolicy {
+            allowed: vec!["6.3.0c".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(policy.check(Some("6.3.0c")), Ok(()));
+        assert_eq!(policy.check(Some("6.3.0")), Err(VersionRejection::TooOld));
+        assert_eq!(policy.check(None), Err(VersionRejection::TooOld));
+    }
+}
@@ -1,5 +1,5 @@
-use crate::config::Constants;
 use crate::error::ArcResult;
+use crate::service::game_constants::game_constants;
 use crate::DbPool;
 use serde_json::{json, Value};
 use std::cmp::Ordering;
@@ -138,7 +138,7 @@ impl CourseService {
 
         Ok(json!({
             "courses": courses,
-            "stamina_cost": Constants::COURSE_STAMINA_COST,
+            "stamina_cost": game_constants().course_stamina_cost,
             "course_skip_purchase_ticket": core_ticket,
         }))
     }
@@ -3,32 +3,153 @@
 //! This service integrates with AssetManager to provide songlist-aware download functionality,
 //! user permission checking, and dynamic cache management similar to the Python implementation.
 
+use crate::config::ReloadableConfig;
 use crate::error::{ArcError, ArcResult};
 use crate::model::download::{DownloadAudio, DownloadFile, DownloadSong};
+use crate::model::item::ItemTypes;
 use crate::model::user::UserInfo;
 use crate::service::asset_manager::AssetManager;
 use crate::service::cache::{env_ttl_seconds, CacheService};
+use crate::service::cdn::CdnRegion;
+use crate::DbPool;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
 use base64::Engine as _;
-use sqlx::MySqlPool;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
 use std::collections::{HashMap, HashSet};
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
 use std::time::{SystemTime, UNIX_EPOCH};
 
+/// Shortest lifetime a caller may ask for, so a link survives a slow start.
+const MIN_DOWNLOAD_TOKEN_TTL: i64 = 60;
+/// Rolling window, in hours, that download quotas are counted over.
+const DOWNLOAD_WINDOW_HOURS: i64 = 24;
+
+/// Lifetime of a download token in seconds. A requested TTL may only shorten
+/// the configured window, never extend it.
+fn clamp_token_ttl(requested: Option<i64>, max_ttl: i64) -> i64 {
+    let max_ttl = max_ttl.max(MIN_DOWNLOAD_TOKEN_TTL);
+    requested
+        .map(|ttl| ttl.clamp(MIN_DOWNLOAD_TOKEN_TTL, max_ttl))
+        .unwrap_or(max_ttl)
+}
+
+fn current_time_secs() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as i64
+}
+
+/// First hour bucket of the quota window ending in `hour`
+fn window_start(hour: i64) -> i64 {
+    hour - DOWNLOAD_WINDOW_HOURS + 1
+}
+
+/// Downloads of a user in the current quota window and the limits that apply
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DownloadUsage {
+    pub download_count: i64,
+    pub download_bytes: i64,
+    /// Files per window, 0 for unlimited
+    pub times_limit: i32,
+    /// Bytes per window, 0 for unlimited
+    pub bytes_limit: i64,
+    /// Whether the limits come from a per-user override
+    pub is_override: bool,
+}
+
+impl DownloadUsage {
+    pub fn is_exceeded(&self) -> bool {
+        (self.times_limit > 0 && self.download_count >= i64::from(self.times_limit))
+            || (self.bytes_limit > 0 && self.download_bytes >= self.bytes_limit)
+    }
+}
+
+/// Key and lifetime of stateless download URLs
+///
+/// A signed token is `<user_id>.<issued_at>.<expires_at>.<signature>`, where
+/// the signature is HMAC-SHA256 over those fields and the file path. Database
+/// tokens are hex digests and never contain a dot.
+#[derive(Clone)]
+struct UrlSigning {
+    key: Vec<u8>,
+    ttl: i64,
+}
+
+impl UrlSigning {
+    fn mac(
+        &self,
+        user_id: i32,
+        issued_at: i64,
+        expires_at: i64,
+        song_id: &str,
+        file_name: &str,
+    ) -> Hmac<Sha256> {
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(&self.key).expect("HMAC accepts keys of any length");
+        mac.update(format!("{user_id}:{issued_at}:{expires_at}:{song_id}/{file_name}").as_bytes());
+        mac
+    }
+
+    fn sign(
+        &self,
+        user_id: i32,
+        song_id: &str,
+        file_name: &str,
+        issued_at: i64,
+        expires_at: i64,
+    ) -> String {
+        let signature = self
+            .mac(user_id, issued_at, expires_at, song_id, file_name)
+            .finalize()
+            .into_bytes();
+        format!(
+            "{user_id}.{issued_at}.{expires_at}.{}",
+            URL_SAFE_NO_PAD.encode(signature)
+        )
+    }
+
+    /// `(user_id, issued_at, expires_at)` of a token signed for this file
+    fn verify(&self, song_id: &str, file_name: &str, token: &str) -> Option<(i32, i64, i64)> {
+        let mut parts = token.splitn(4, '.');
+        let user_id = parts.next()?.parse::<i32>().ok()?;
+        let issued_at = parts.next()?.parse::<i64>().ok()?;
+        let expires_at = parts.next()?.parse::<i64>().ok()?;
+        let signature = URL_SAFE_NO_PAD.decode(parts.next()?).ok()?;
+        self.mac(user_id, issued_at, expires_at, song_id, file_name)
+            .verify_slice(&signature)
+            .ok()?;
+        Some((user_id, issued_at, expires_at))
+    }
+}
+
+/// Link prefix and limits, replaced in place when the configuration is
+/// reloaded
+#[derive(Debug, Clone)]
+struct DownloadSettings {
+    link_prefix: Option<String>,
+    time_gap_limit: i64,
+    times_limit: i32,
+    bytes_limit: i64,
+}
+
 /// Download service for handling song file downloads and token management
+#[derive(Clone)]
 pub struct DownloadService {
-    pool: MySqlPool,
+    pool: DbPool,
     asset_manager: Arc<AssetManager>,
-    download_link_prefix: Option<String>,
-    download_time_gap_limit: i64,
-    download_times_limit: i32,
+    /// Shared by every clone, so a reload reaches the managed instance
+    settings: Arc<RwLock<DownloadSettings>>,
     cache: Option<CacheService>,
     download_list_cache_ttl_seconds: u64,
+    url_signing: Option<UrlSigning>,
 }
 
 impl DownloadService {
     /// Create a new download service instance
     pub fn new(
-        pool: MySqlPool,
+        pool: DbPool,
         asset_manager: Arc<AssetManager>,
         download_link_prefix: Option<String>,
         download_time_gap_limit: i64,
@@ -37,11 +158,15 @@ impl DownloadService {
         Self {
             pool,
             asset_manager,
-            download_link_prefix,
-            download_time_gap_limit,
-            download_times_limit,
+            settings: Arc::new(RwLock::new(DownloadSettings {
+                link_prefix: download_link_prefix,
+                time_gap_limit: download_time_gap_limit,
+                times_limit: download_times_limit,
+                bytes_limit: 0,
+            })),
             cache: None,
             download_list_cache_ttl_seconds: env_ttl_seconds("REDIS_DOWNLOAD_LIST_TTL_SECONDS", 30),
+            url_signing: None,
         }
     }
 
@@ -50,6 +175,30 @@ impl DownloadService {
         self
     }
 
+    fn settings(&self) -> DownloadSettings {
+        match self.settings.read() {
+            Ok(guard) => guard.clone(),
+            Err(poisoned) => poisoned.into_inner().clone(),
+        }
+    }
+
+    fn update_settings(&self, update: impl FnOnce(&mut DownloadSettings)) {
+        match self.settings.write() {
+            Ok(mut guard) => update(&mut guard),
+            Err(poisoned) => update(&mut poisoned.into_inner()),
+        }
+    }
+
+    /// Take over the link prefix and limits of a reloaded configuration
+    pub fn apply_reloaded_config(&self, config: &ReloadableConfig) {
+        self.update_settings(|settings| {
+            settings.link_prefix = config.download_link_prefix.clone();
+            settings.time_gap_limit = config.download_time_gap_limit;
+            settings.times_limit = config.download_times_limit;
+            settings.bytes_limit = config.download_bytes_limit;
+        });
+    }
+
     fn download_list_cache_key(user: &UserInfo, song_ids: &[String], include_urls: bool) -> String {
         let mut packs = user.packs.clone();
         packs.sort_unstable();
@@ -107,28 +256,32 @@ impl DownloadService {
         format!("{:x}", md5::compute(token_data.as_bytes()))
     }
 
-    /// Insert or update download token in database
+    /// Insert or update download token in database, valid for `ttl` seconds
     pub async fn insert_download_token(
         &self,
         user_id: i32,
         song_id: &str,
         file_name: &str,
         token: &str,
+        ttl: i64,
     ) -> ArcResult<()> {
         let current_time = SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .unwrap()
             .as_secs() as i64;
+        let expires_at = current_time + ttl;
 
         sqlx::query!(
-            "INSERT INTO download_token (user_id, song_id, file_name, token, time)
-             VALUES (?, ?, ?, ?, ?)
-             ON DUPLICATE KEY UPDATE token = VALUES(token), time = VALUES(time)",
+            "INSERT INTO download_token (user_id, song_id, file_name, token, time, expires_at)
+             VALUES (?, ?, ?, ?, ?, ?)
+             ON DUPLICATE KEY UPDATE token = VALUES(token), time = VALUES(time),
+             expires_at = VALUES(expires_at)",
             user_id,
             song_id,
             file_name,
             token,
-            current_time
+            current_time,
+            expires_at
         )
         .execute(&self.pool)
         .await?;
@@ -137,17 +290,36 @@ impl DownloadService {
     }
 
     /// Validate download token and return user_id and creation time
+    ///
+    /// A token only opens the file it was issued for; presenting it for any
+    /// other song or file is rejected. Signed tokens are checked without
+    /// touching the database.
     pub async fn validate_download_token(
         &self,
         song_id: &str,
         file_name: &str,
         token: &str,
     ) -> ArcResult<(i32, i64)> {
+        if token.contains('.') {
+            let (user_id, issued_at, expires_at) = self
+                .url_signing
+                .as_ref()
+                .and_then(|signing| signing.verify(song_id, file_name, token))
+                .ok_or_else(|| {
+                    ArcError::no_access(format!("The token `{token}` is not valid."), 403)
+                })?;
+            if current_time_secs() > expires_at {
+                return Err(ArcError::no_access(
+                    format!("The token `{token}` has expired."),
+                    403,
+                ));
+            }
+            return Ok((user_id, issued_at));
+        }
+
         let result = sqlx::query!(
-            "SELECT user_id, time FROM download_token
-             WHERE song_id = ? AND file_name = ? AND token = ? LIMIT 1",
-            song_id,
-            file_name,
+            "SELECT user_id, song_id, file_name, time, expires_at FROM download_token
+             WHERE token = ? LIMIT 1",
             token
         )
         .fetch_optional(&self.pool)
@@ -155,20 +327,21 @@ impl DownloadService {
 
         match result {
             Some(row) => {
-                let current_time = SystemTime::now()
-                    .duration_since(UNIX_EPOCH)
-                    .unwrap()
-                    .as_secs() as i64;
+                if row.song_id != song_id || row.file_name != file_name {
+                    return Err(ArcError::no_access(
+                        format!("The token `{token}` was not issued for this file."),
+                        403,
+                    ));
+                }
 
-                let token_time = row.time.unwrap_or(0);
-                if current_time - token_time > self.download_time_gap_limit {
+                if current_time_secs() > row.expires_at {
                     return Err(ArcError::no_access(
                         format!("The token `{token}` has expired."),
                         403,
                     ));
                 }
 
-                Ok((row.user_id, token_time))
+                Ok((row.user_id, row.time.unwrap_or(0)))
             }
             None => Err(ArcError::no_access(
                 format!("The token `{token}` is not valid."),
@@ -177,34 +350,216 @@ impl DownloadService {
         }
     }
 
-    /// Clear expired download tokens
-    pub async fn clear_expired_download_tokens(&self) -> ArcResult<()> {
-        let cutoff_time = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs() as i64
-            - self.download_time_gap_limit;
+    /// Keep the songs of `song_ids` that `user` may download
+    ///
+    /// With a songlist these are the free songs and those covered by the
+    /// user's packs, singles and world unlocks. Without one the pack contents
+    /// are unknown, so only singles sold in the purchase table are held back
+    /// from players who do not own them.
+    pub async fn filter_downloadable(
+        &self,
+        user: &UserInfo,
+        song_ids: Vec<String>,
+    ) -> ArcResult<Vec<String>> {
+        let unlocks = self
+            .asset_manager
+            .has_songlist()
+            .then(|| self.asset_manager.get_user_unlocks(user));
+        let purchasable_singles = if unlocks.is_none() {
+            self.purchasable_singles().await?
+        } else {
+            HashSet::new()
+        };
+        Ok(song_ids
+            .into_iter()
+            .filter(|song_id| {
+                is_downloadable(
+                    song_id,
+                    unlocks.as_ref(),
+                    &purchasable_singles,
+                    &user.singles,
+                )
+            })
+            .collect())
+    }
+
+    /// Fail with 403 unless `user` may download the files of `song_id`
+    pub async fn ensure_song_downloadable(&self, user: &UserInfo, song_id: &str) -> ArcResult<()> {
+        let allowed = !self.asset_manager.should_forbid_download_when_no_item(user)
+            && !self
+                .filter_downloadable(user, vec![song_id.to_string()])
+                .await?
+                .is_empty();
+        if !allowed {
+            return Err(ArcError::no_access(
+                format!("You do not own the song `{song_id}`."),
+                403,
+            ));
+        }
+        Ok(())
+    }
 
-        sqlx::query!("DELETE FROM download_token WHERE time < ?", cutoff_time)
+    async fn purchasable_singles(&self) -> ArcResult<HashSet<String>> {
+        let singles = sqlx::query_scalar!(
+            "SELECT DISTINCT item_id FROM purchase_item WHERE type = ?",
+            ItemTypes::SINGLE
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(singles.into_iter().flatten().collect())
+    }
+
+    /// Clear expired download tokens, returning the number of rows removed
+    pub async fn clear_expired_download_tokens(&self) -> ArcResult<u64> {
+        let result = sqlx::query!(
+            "DELETE FROM download_token WHERE expires_at < ?",
+            current_time_secs()
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Revoke every download token issued to a user, e.g. on logout
+    pub async fn revoke_user_download_tokens(&self, user_id: i32) -> ArcResult<u64> {
+        let result = sqlx::query!("DELETE FROM download_token WHERE user_id = ?", user_id)
             .execute(&self.pool)
             .await?;
 
-        Ok(())
+        Ok(result.rows_affected())
+    }
+
+    /// Clear download accounting older than the quota window
+    pub async fn clear_expired_download_usage(&self) -> ArcResult<u64> {
+        let result = sqlx::query!(
+            "DELETE FROM user_download_usage WHERE hour_bucket < ?",
+            window_start(current_time_secs() / 3600)
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Files and bytes downloaded by a user over the last 24 hours, with the
+    /// configured limits or the user's override
+    pub async fn download_usage(&self, user_id: i32) -> ArcResult<DownloadUsage> {
+        let usage = sqlx::query!(
+            "SELECT CAST(COALESCE(SUM(download_count), 0) AS SIGNED) AS `download_count!: i64`,
+                    CAST(COALESCE(SUM(download_bytes), 0) AS SIGNED) AS `download_bytes!: i64`
+             FROM user_download_usage
+             WHERE user_id = ? AND hour_bucket >= ?",
+            user_id,
+            window_start(current_time_secs() / 3600)
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        let quota = sqlx::query!(
+            "SELECT times_limit, bytes_limit FROM user_download_quota WHERE user_id = ?",
+            user_id
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        let settings = self.settings();
+        Ok(DownloadUsage {
+            download_count: usage.download_count,
+            download_bytes: usage.download_bytes,
+            times_limit: quota
+                .as_ref()
+                .and_then(|quota| quota.times_limit)
+                .unwrap_or(settings.times_limit),
+            bytes_limit: quota
+                .as_ref()
+                .and_then(|quota| quota.bytes_limit)
+                .unwrap_or(settings.bytes_limit),
+            is_override: quota.is_some(),
+        })
     }
 
     /// Check if user has reached download limit
-    /// TODO: Implement proper rate limiting similar to Python's ArcLimiter
-    pub async fn check_download_limit(&self, _user_id: i32) -> ArcResult<bool> {
-        // For now, return false (not limited)
-        // In the future, implement rate limiting based on download_times_limit
-        Ok(false)
+    pub async fn check_download_limit(&self, user_id: i32) -> ArcResult<bool> {
+        Ok(self.download_usage(user_id).await?.is_exceeded())
+    }
+
+    /// Count served song file bytes, and `files` started downloads, against
+    /// the user's quota
+    pub async fn record_download(&self, user_id: i32, files: i32, bytes: i64) -> ArcResult<()> {
+        sqlx::query!(
+            "INSERT INTO user_download_usage (user_id, hour_bucket, download_count, download_bytes)
+             VALUES (?, ?, ?, ?)
+             ON DUPLICATE KEY UPDATE download_count = download_count + VALUES(download_count),
+             download_bytes = download_bytes + VALUES(download_bytes)",
+            user_id,
+            current_time_secs() / 3600,
+            files,
+            bytes
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Forget a user's downloads in the current window
+    pub async fn reset_download_usage(&self, user_id: i32) -> ArcResult<u64> {
+        let result = sqlx::query!("DELETE FROM user_download_usage WHERE user_id = ?", user_id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected())
     }
 
-    /// Generate download URL for a file
-    pub fn generate_download_url(&self, song_id: &str, file_name: &str, token: &str) -> String {
-        if let Some(ref prefix) = self.download_link_prefix {
+    /// Override a user's limits; `None` keeps the configured limit, and
+    /// clearing both removes the override
+    pub async fn set_download_quota(
+        &self,
+        user_id: i32,
+        times_limit: Option<i32>,
+        bytes_limit: Option<i64>,
+    ) -> ArcResult<()> {
+        if times_limit.is_some_and(|limit| limit < 0) || bytes_limit.is_some_and(|limit| limit < 0)
+        {
+            return Err(ArcError::input("Download limits must not be negative"));
+        }
+        if times_limit.is_none() && bytes_limit.is_none() {
+            sqlx::query!("DELETE FROM user_download_quota WHERE user_id = ?", user_id)
+                .execute(&self.pool)
+                .await?;
+            return Ok(());
+        }
+
+        sqlx::query!(
+            "INSERT INTO user_download_quota (user_id, times_limit, bytes_limit, updated_at)
+             VALUES (?, ?, ?, ?)
+             ON DUPLICATE KEY UPDATE times_limit = VALUES(times_limit),
+             bytes_limit = VALUES(bytes_limit), updated_at = VALUES(updated_at)",
+            user_id,
+            times_limit,
+            bytes_limit,
+            current_time_secs()
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Generate download URL for a file, on the mirror's host when it has one
+    pub fn generate_download_url(
+        &self,
+        song_id: &str,
+        file_name: &str,
+        token: &str,
+        mirror: Option<&CdnRegion>,
+    ) -> String {
+        let prefix = mirror
+            .and_then(|mirror| mirror.download_prefix.clone())
+            .or_else(|| self.settings().link_prefix);
+        if let Some(prefix) = prefix {
             let prefix = if prefix.ends_with('/') {
-                prefix.clone()
+                prefix
             } else {
                 format!("{prefix}/")
             };
@@ -226,11 +581,15 @@ impl DownloadService {
     }
 
     /// Generate download list for user with proper permission checking
+    ///
+    /// `token_ttl` asks for shorter-lived links than the configured window.
     pub async fn generate_download_list(
         &self,
         user: &UserInfo,
         song_ids: Option<Vec<String>>,
         include_urls: bool,
+        mirror: Option<&CdnRegion>,
+        token_ttl: Option<i64>,
     ) -> ArcResult<HashMap<String, DownloadSong>> {
         // Check if download should be forbidden when user has no unlocked items
         if self.asset_manager.should_forbid_download_when_no_item(user) {
@@ -245,31 +604,9 @@ impl DownloadService {
             ));
         }
 
-        // Get target song IDs
-        let target_song_ids = if let Some(song_ids) = song_ids {
-            // Filter requested songs by user's unlocked songs
-            if self.asset_manager.has_songlist() {
-                let user_unlocks = self.asset_manager.get_user_unlocks(user);
-                song_ids
-                    .into_iter()
-                    .filter(|id| user_unlocks.contains(id))
-                    .collect()
-            } else {
-                song_ids
-            }
-        } else {
-            // Get all songs, filtered by user unlocks if songlist is available
-            let all_songs = self.get_all_song_ids();
-            if self.asset_manager.has_songlist() {
-                let user_unlocks = self.asset_manager.get_user_unlocks(user);
-                all_songs
-                    .into_iter()
-                    .filter(|id| user_unlocks.contains(id))
-                    .collect()
-            } else {
-                all_songs
-            }
-        };
+        // Get target song IDs, keeping only the songs the user owns
+        let song_ids = song_ids.unwrap_or_else(|| self.get_all_song_ids());
+        let target_song_ids = self.filter_downloadable(user, song_ids).await?;
 
         let s3_storage = self.asset_manager.s3_storage();
         let cacheable = !include_urls || s3_storage.is_some();
@@ -299,11 +636,11 @@ impl DownloadService {
         let mut download_songs = HashMap::new();
         let mut download_tokens = Vec::new();
         let available_song_ids: HashSet<String> = self.get_all_song_ids().into_iter().collect();
-
-        // Clear expired tokens before generating new ones
-        if include_urls && s3_storage.is_none() {
-            self.clear_expired_download_tokens().await?;
-        }
+        let issued_at = current_time_secs();
+        let ttl = match &self.url_signing {
+            Some(signing) => clamp_token_ttl(token_ttl, signing.ttl),
+            None => clamp_token_ttl(token_ttl, self.settings().time_gap_limit),
+        };
 
         for song_id in target_song_ids {
             if !available_song_ids.contains(&song_id) {
@@ -326,10 +663,20 @@ impl DownloadService {
                             storage.presign_song(&song_id, &file_name).await?,
                             None::<String>,
                         )
+                    } else if let Some(signing) = &self.url_signing {
+                        let token = signing.sign(
+                            user.user_id,
+                            &song_id,
+                            &file_name,
+                            issued_at,
+                            issued_at + ttl,
+                        );
+                        let url = self.generate_download_url(&song_id, &file_name, &token, mirror);
+                        (Some(url), Some(token))
                     } else {
                         let token =
                             self.generate_download_token(user.user_id, &song_id, &file_name);
-                        let url = self.generate_download_url(&song_id, &file_name, &token);
+                        let url = self.generate_download_url(&song_id, &file_name, &token, mirror);
                         download_tokens.push((
                             user.user_id,
                             song_id.clone(),
@@ -351,7 +698,7 @@ impl DownloadService {
         // Insert all download tokens at once if URLs are included
         if include_urls && !download_tokens.is_empty() {
             for (user_id, song_id, file_name, token) in download_tokens {
-                self.insert_download_token(user_id, &song_id, &file_name, &token)
+                self.insert_download_token(user_id, &song_id, &file_name, &token, ttl)
                     .await?;
             }
         }
@@ -465,7 +812,7 @@ impl DownloadService {
 /// Download service builder and utility methods
 impl DownloadService {
     /// Initialize download service with default configuration
-    pub fn with_defaults(pool: MySqlPool) -> Self {
+    pub fn with_defaults(pool: DbPool) -> Self {
         let asset_manager = Arc::new(AssetManager::with_defaults(pool.clone()));
         Self::new(
             pool,
@@ -477,20 +824,36 @@ impl DownloadService {
     }
 
     /// Set download link prefix
-    pub fn with_download_prefix(mut self, prefix: Option<String>) -> Self {
-        self.download_link_prefix = prefix;
+    pub fn with_download_prefix(self, prefix: Option<String>) -> Self {
+        self.update_settings(|settings| settings.link_prefix = prefix);
         self
     }
 
     /// Set download time gap limit
-    pub fn with_time_gap_limit(mut self, limit: i64) -> Self {
-        self.download_time_gap_limit = limit;
+    pub fn with_time_gap_limit(self, limit: i64) -> Self {
+        self.update_settings(|settings| settings.time_gap_limit = limit);
         self
     }
 
     /// Set download times limit
-    pub fn with_times_limit(mut self, limit: i32) -> Self {
-        self.download_times_limit = limit;
+    pub fn with_times_limit(self, limit: i32) -> Self {
+        self.update_settings(|settings| settings.times_limit = limit);
+        self
+    }
+
+    /// Limit the bytes served to a user per 24 hours; 0 disables the limit
+    pub fn with_bytes_limit(self, limit: i64) -> Self {
+        self.update_settings(|settings| settings.bytes_limit = limit);
+        self
+    }
+
+    /// Issue signed download URLs valid for at most `ttl` seconds instead of
+    /// `download_token` rows; a non-positive `ttl` keeps database tokens
+    pub fn with_url_signing(mut self, secret_key: &str, ttl: i64) -> Self {
+        self.url_signing = (ttl > 0).then(|| UrlSigning {
+            key: secret_key.as_bytes().to_vec(),
+            ttl,
+        });
         self
     }
 
@@ -499,3 +862,113 @@ impl DownloadService {
         &self.asset_manager
     }
 }
+
+/// Whether a song passes the ownership check; `unlocks` is `None` when no
+/// songlist is loaded
+fn is_downloadable(
+    song_id: &str,
+    unlocks: Option<&HashSet<String>>,
+    purchasable_singles: &HashSet<String>,
+    owned_singles: &[String],
+) -> bool {
+    match unlocks {
+        Some(unlocks) => unlocks.contains(song_id),
+        None => {
+            !purchasable_singles.contains(song_id)
+                || owned_singles.iter().any(|single| single == song_id)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_downloadable() {
+        let set = |ids: &[&str]| ids.iter().map(|id| id.to_string()).collect::<HashSet<_>>();
+        let unlocks = set(&["sayonarahatsukoi", "grievouslady"]);
+        let owned = vec!["fractureray".to_string()];
+
+        assert!(is_downloadable(
+            "grievouslady",
+            Some(&unlocks),
+            &set(&[]),
+            &[]
+        ));
+        assert!(!is_downloadable(
+            "tempestissimo",
+            Some(&unlocks),
+            &set(&[]),
+            &owned
+        ));
+
+        let singles = set(&["fractureray", "ignotus"]);
+        assert!(is_downloadable("tempestissimo", None, &singles, &owned));
+        assert!(is_downloadable("fractureray", None, &singles, &owned));
+        assert!(!is_downloadable("ignotus", None, &singles, &owned));
+    }
+
+    #[test]
+    fn test_clamp_token_ttl() {
+        assert_eq!(clamp_token_ttl(None, 1000), 1000);
+        assert_eq!(clamp_token_ttl(Some(300), 1000), 300);
+        assert_eq!(clamp_token_ttl(Some(86400), 1000), 1000);
+        assert_eq!(clamp_token_ttl(Some(0), 1000), MIN_DOWNLOAD_TOKEN_TTL);
+        assert_eq!(clamp_token_ttl(None, 10), MIN_DOWNLOAD_TOKEN_TTL);
+    }
+
+    #[test]
+    fn test_download_usage_limits() {
+        let usage = DownloadUsage {
+            download_count: 10,
+            download_bytes: 5_000,
+            times_limit: 10,
+            bytes_limit: 0,
+            is_override: false,
+        };
+        assert!(usage.is_exceeded());
+        assert!(!DownloadUsage {
+            times_limit: 11,
+            ..usage
+        }
+        .is_exceeded());
+        assert!(DownloadUsage {
+            times_limit: 0,
+            bytes_limit: 5_000,
+            ..usage
+        }
+        .is_exceeded());
+        assert!(!DownloadUsage {
+            times_limit: 0,
+            ..usage
+        }
+        .is_exceeded());
+        assert_eq!(window_start(100), 77);
+    }
+
+    #[test]
+    fn test_signed_download_token() {
+        let signing = UrlSigning {
+            key: b"secret".to_vec(),
+            ttl: 600,
+        };
+        let token = signing.sign(7, "grievouslady", "2.aff", 1000, 1600);
+        assert!(!token.contains('/') && !token.contains('+'));
+        assert_eq!(
+            signing.verify("grievouslady", "2.aff", &token),
+            Some((7, 1000, 1600))
+        );
+        assert_eq!(signing.verify("grievouslady", "3.aff", &token), None);
+        assert_eq!(
+            signing.verify("grievouslady", "2.aff", &token.replacen("1600", "9600", 1)),
+            None
+        );
+
+        let other = UrlSigning {
+            key: b"other".to_vec(),
+            ttl: 600,
+        };
+        assert_eq!(other.verify("grievouslady", "2.aff", &token), None);
+    }
+}
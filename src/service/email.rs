@@ -0,0 +1,432 @@
+use crate::error::{ArcError, ArcResult};
+use askama::Template;
+use lettre::message::{Mailbox, MultiPart};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use std::env;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+const DEFAULT_SMTP_PORT: u16 = 587;
+const DEFAULT_MAX_RETRIES: u32 = 3;
+const DEFAULT_RETRY_DELAY_SECONDS: u64 = 10;
+const DEFAULT_LINK_EXPIRE_MINUTES: i64 = 30;
+
+/// How outgoing mail is delivered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EmailMode {
+    /// Mail is silently dropped.
+    Disabled,
+    /// Mail is rendered and written to the log instead of being sent.
+    LogOnly,
+    /// Mail is delivered through the configured SMTP relay.
+    Smtp,
+}
+
+/// Transport security used for the SMTP connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SmtpSecurity {
+    None,
+    StartTls,
+    Tls,
+}
+
+#[derive(Debug, Clone)]
+pub struct EmailConfig {
+    pub mode: EmailMode,
+    pub smtp_host: String,
+    pub smtp_port: u16,
+    pub smtp_username: Option<String>,
+    pub smtp_password: Option<String>,
+    pub smtp_security: SmtpSecurity,
+    pub from_address: String,
+    pub server_name: String,
+    pub admin_recipients: Vec<String>,
+    pub max_retries: u32,
+    pub retry_delay: Duration,
+}
+
+impl EmailConfig {
+    pub fn from_env() -> ArcResult<Self> {
+        let mode = match env::var("EMAIL_MODE")
+            .unwrap_or_else(|_| "disabled".to_string())
+            .trim()
+            .to_ascii_lowercase()
+            .as_str()
+        {
+            "" | "disabled" | "off" => EmailMode::Disabled,
+            "log" | "log_only" | "dry_run" => EmailMode::LogOnly,
+            "smtp" => EmailMode::Smtp,
+            other => {
+                return Err(ArcError::input(format!(
+                    "Invalid EMAIL_MODE `{other}`, expected `disabled`, `log` or `smtp`"
+                )))
+            }
+        };
+
+        let smtp_security = match env::var("SMTP_SECURITY")
+            .unwrap_or_else(|_| "starttls".to_string())
+            .trim()
+            .to_ascii_lowercase()
+            .as_str()
+        {
+            "none" | "plain" => SmtpSecurity::None,
+            "" | "starttls" => SmtpSecurity::StartTls,
+            "tls" | "ssl" => SmtpSecurity::Tls,
+            other => {
+                return Err(ArcError::input(format!(
+                    "Invalid SMTP_SECURITY `{other}`, expected `none`, `starttls` or `tls`"
+                )))
+            }
+        };
+
+        let smtp_host = env_non_empty("SMTP_HOST").unwrap_or_default();
+        if mode == EmailMode::Smtp && smtp_host.is_empty() {
+            return Err(ArcError::input(
+                "SMTP_HOST is required when EMAIL_MODE=smtp",
+            ));
+        }
+
+        Ok(Self {
+            mode,
+            smtp_host,
+            smtp_port: env_non_empty("SMTP_PORT")
+                .and_then(|value| value.parse().ok())
+                .unwrap_or(DEFAULT_SMTP_PORT),
+            smtp_username: env_non_empty("SMTP_USERNAME"),
+            smtp_password: env_non_empty("SMTP_PASSWORD"),
+            smtp_security,
+            from_address: env_non_empty("EMAIL_FROM")
+                .unwrap_or_else(|| "Arcaea Server <noreply@localhost>".to_string()),
+            server_name: env_non_empty("TITLE").unwrap_or_else(|| "Arcaea Server".to_string()),
+            admin_recipients: env_non_empty("EMAIL_ADMIN_RECIPIENTS")
+                .map(|value| {
+                    value
+                        .split(',')
+                        .map(|part| part.trim().to_string())
+                        .filter(|part| !part.is_empty())
+                        .collect()
+                })
+                .unwrap_or_default(),
+            max_retries: env_non_empty("EMAIL_MAX_RETRIES")
+                .and_then(|value| value.parse().ok())
+                .unwrap_or(DEFAULT_MAX_RETRIES),
+            retry_delay: Duration::from_secs(
+                env_non_empty("EMAIL_RETRY_DELAY_SECONDS")
+                    .and_then(|value| value.parse().ok())
+                    .unwrap_or(DEFAULT_RETRY_DELAY_SECONDS),
+            ),
+        })
+    }
+}
+
+/// A rendered email waiting in the send queue.
+#[derive(Debug, Clone)]
+pub struct OutgoingEmail {
+    pub to: String,
+    pub subject: String,
+    pub text_body: String,
+    pub html_body: Option<String>,
+}
+
+#[derive(Template)]
+#[template(path = "email/verification.txt")]
+struct VerificationText<'a> {
+    name: &'a str,
+    server_name: &'a str,
+    code: &'a str,
+    expire_minutes: i64,
+}
+
+#[derive(Template)]
+#[template(path = "email/verification.html")]
+struct VerificationHtml<'a> {
+    name: &'a str,
+    server_name: &'a str,
+    code: &'a str,
+    expire_minutes: i64,
+}
+
+#[derive(Template)]
+#[template(path = "email/password_reset.txt")]
+struct PasswordResetText<'a> {
+    name: &'a str,
+    server_name: &'a str,
+    link: &'a str,
+    expire_minutes: i64,
+}
+
+#[derive(Template)]
+#[template(path = "email/password_reset.html")]
+struct PasswordResetHtml<'a> {
+    name: &'a str,
+    server_name: &'a str,
+    link: &'a str,
+    expire_minutes: i64,
+}
+
+#[derive(Template)]
+#[template(path = "email/admin_alert.txt")]
+struct AdminAlertText<'a> {
+    server_name: &'a str,
+    title: &'a str,
+    body: &'a str,
+}
+
+/// Queued outgoing mail with SMTP delivery, retries and a log-only mode.
+///
+/// Messages are rendered on the caller side and handed to a background worker,
+/// so request handlers never wait on the SMTP relay.
+#[derive(Clone)]
+pub struct EmailService {
+    config: Arc<EmailConfig>,
+    sender: Option<mpsc::UnboundedSender<OutgoingEmail>>,
+}
+
+impl std::fmt::Debug for EmailService {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EmailService")
+            .field("mode", &self.config.mode)
+            .field("smtp_host", &self.config.smtp_host)
+            .finish()
+    }
+}
+
+impl EmailService {
+    pub fn from_env() -> ArcResult<Self> {
+        Self::from_config(EmailConfig::from_env()?)
+    }
+
+    pub fn from_config(config: EmailConfig) -> ArcResult<Self> {
+        let config = Arc::new(config);
+        let sender = match config.mode {
+            EmailMode::Disabled => None,
+            EmailMode::LogOnly => Some(spawn_worker(config.clone(), None)),
+            EmailMode::Smtp => {
+                let transport = build_transport(&config)?;
+                Some(spawn_worker(config.clone(), Some(transport)))
+            }
+        };
+
+        Ok(Self { config, sender })
+    }
+
+    pub fn mode(&self) -> EmailMode {
+        self.config.mode
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.sender.is_some()
+    }
+
+    /// Put a rendered email on the send queue.
+    pub fn enqueue(&self, email: OutgoingEmail) -> ArcResult<()> {
+        let Some(sender) = &self.sender else {
+            log::debug!("Email disabled, dropping mail to `{}`", email.to);
+            return Ok(());
+        };
+
+        sender
+            .send(email)
+            .map_err(|_| ArcError::internal("Email queue is closed"))
+    }
+
+    /// Queue an email with the code that confirms the user's address.
+    pub fn send_verification(
+        &self,
+        to: &str,
+        name: &str,
+        code: &str,
+        expire_minutes: i64,
+    ) -> ArcResult<()> {
+        let server_name = self.config.server_name.as_str();
+        let text_body = VerificationText {
+            name,
+            server_name,
+            code,
+            expire_minutes,
+        }
+        .render()
+        .map_err(render_error)?;
+        let html_body = VerificationHtml {
+            name,
+            server_name,
+            code,
+            expire_minutes,
+        }
+        .render()
+        .map_err(render_error)?;
+
+        self.enqueue(OutgoingEmail {
+            to: to.to_string(),
+            subject: format!("[{server_name}] Verify your email address"),
+            text_body,
+            html_body: Some(html_body),
+        })
+    }
+
+    /// Queue a password reset email.
+    pub fn send_password_reset(&self, to: &str, name: &str, link: &str) -> ArcResult<()> {
+        let server_name = self.config.server_name.as_str();
+        let text_body = PasswordResetText {
+            name,
+            server_name,
+            link,
+            expire_minutes: DEFAULT_LINK_EXPIRE_MINUTES,
+        }
+        .render()
+        .map_err(render_error)?;
+        let html_body = PasswordResetHtml {
+            name,
+            server_name,
+            link,
+            expire_minutes: DEFAULT_LINK_EXPIRE_MINUTES,
+        }
+        .render()
+        .map_err(render_error)?;
+
+        self.enqueue(OutgoingEmail {
+            to: to.to_string(),
+            subject: format!("[{server_name}] Reset your password"),
+            text_body,
+            html_body: Some(html_body),
+        })
+    }
+
+    /// Queue an alert to every address in `EMAIL_ADMIN_RECIPIENTS`.
+    pub fn send_admin_alert(&self, title: &str, body: &str) -> ArcResult<()> {
+        let server_name = self.config.server_name.as_str();
+        let text_body = AdminAlertText {
+            server_name,
+            title,
+            body,
+        }
+        .render()
+        .map_err(render_error)?;
+
+        for to in &self.config.admin_recipients {
+            self.enqueue(OutgoingEmail {
+                to: to.clone(),
+                subject: format!("[{server_name}] {title}"),
+                text_body: text_body.clone(),
+                html_body: None,
+            })?;
+        }
+        Ok(())
+    }
+}
+
+type SmtpTransport = AsyncSmtpTransport<Tokio1Executor>;
+
+fn build_transport(config: &EmailConfig) -> ArcResult<SmtpTransport> {
+    let builder = match config.smtp_security {
+        SmtpSecurity::None => SmtpTransport::builder_dangerous(&config.smtp_host),
+        SmtpSecurity::StartTls => {
+            SmtpTransport::starttls_relay(&config.smtp_host).map_err(smtp_error)?
+        }
+        SmtpSecurity::Tls => SmtpTransport::relay(&config.smtp_host).map_err(smtp_error)?,
+    };
+
+    let builder = builder.port(config.smtp_port);
+    let builder = match (&config.smtp_username, &config.smtp_password) {
+        (Some(username), Some(password)) => {
+            builder.credentials(Credentials::new(username.clone(), password.clone()))
+        }
+        _ => builder,
+    };
+
+    Ok(builder.build())
+}
+
+fn spawn_worker(
+    config: Arc<EmailConfig>,
+    transport: Option<SmtpTransport>,
+) -> mpsc::UnboundedSender<OutgoingEmail> {
+    let (sender, mut receiver) = mpsc::unbounded_channel::<OutgoingEmail>();
+
+    tokio::spawn(async move {
+        while let Some(email) = receiver.recv().await {
+            let Some(transport) = &transport else {
+                log::info!(
+                    "[email dry-run] to: {} | subject: {}\n{}",
+                    email.to,
+                    email.subject,
+                    email.text_body
+                );
+                continue;
+            };
+
+            let mut attempt = 0;
+            loop {
+                match deliver(&config, transport, &email).await {
+                    Ok(()) => {
+                        log::info!("Email sent to `{}`: {}", email.to, email.subject);
+                        break;
+                    }
+                    Err(e) if attempt < config.max_retries => {
+                        attempt += 1;
+                        log::warn!(
+                            "Email to `{}` failed (attempt {attempt}/{}): {e}",
+                            email.to,
+                            config.max_retries + 1
+                        );
+                        tokio::time::sleep(config.retry_delay * attempt).await;
+                    }
+                    Err(e) => {
+                        log::error!("Giving up on email to `{}`: {e}", email.to);
+                        break;
+                    }
+                }
+            }
+        }
+    });
+
+    sender
+}
+
+async fn deliver(
+    config: &EmailConfig,
+    transport: &SmtpTransport,
+    email: &OutgoingEmail,
+) -> ArcResult<()> {
+    let from: Mailbox = config
+        .from_address
+        .parse()
+        .map_err(|e| ArcError::input(format!("Invalid EMAIL_FROM: {e}")))?;
+    let to: Mailbox = email
+        .to
+        .parse()
+        .map_err(|e| ArcError::input(format!("Invalid recipient `{}`: {e}", email.to)))?;
+
+    let builder = Message::builder()
+        .from(from)
+        .to(to)
+        .subject(email.subject.clone());
+    let message = match &email.html_body {
+        Some(html) => builder.multipart(MultiPart::alternative_plain_html(
+            email.text_body.clone(),
+            html.clone(),
+        )),
+        None => builder.body(email.text_body.clone()),
+    }
+    .map_err(|e| ArcError::input(format!("Failed to build email: {e}")))?;
+
+    transport.send(message).await.map_err(smtp_error)?;
+    Ok(())
+}
+
+fn render_error(e: askama::Error) -> ArcError {
+    ArcError::internal(format!("Failed to render email template: {e}"))
+}
+
+fn smtp_error(e: lettre::transport::smtp::Error) -> ArcError {
+    ArcError::internal(format!("SMTP error: {e}"))
+}
+
+fn env_non_empty(key: &str) -> Option<String> {
+    env::var(key)
+        .ok()
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+}
@@ -0,0 +1,825 @@
+use crate::error::{ArcError, ArcResult};
+use crate::model::PresentItem;
+use crate::service::push::{PushGateway, PushTarget};
+use crate::service::runtime_assets::asset_path;
+use crate::service::{NotificationService, PresentService};
+use crate::DbPool;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::env;
+use std::path::Path;
+use std::sync::{Arc, OnceLock};
+
+/// Score needed for one event point, before the chart multiplier.
+const SCORE_PER_POINT: i32 = 10_000;
+pub const DEFAULT_LADDER_LIMIT: i64 = 50;
+pub const MAX_LADDER_LIMIT: i64 = 200;
+const LIMITED_EVENTS_ASSET: &str = "events.json";
+
+static LIMITED_EVENTS: OnceLock<LimitedEvents> = OnceLock::new();
+
+/// A chart that earns points in an event.
+#[derive(Debug, Clone, Serialize)]
+pub struct EventChart {
+    pub song_id: String,
+    pub difficulty: i32,
+    pub point_multiplier: i32,
+}
+
+/// Reward sent once a player's event points reach `points`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct EventRewardTier {
+    pub points: i32,
+    pub reward_type: String,
+    pub reward_item_id: String,
+    pub reward_amount: i32,
+}
+
+/// An event with its charts, reward tiers and the player's points.
+#[derive(Debug, Clone, Serialize)]
+pub struct EventInfo {
+    pub event_id: String,
+    pub name: String,
+    pub description: String,
+    pub start_ts: i64,
+    pub end_ts: i64,
+    pub charts: Vec<EventChart>,
+    pub reward_tiers: Vec<EventRewardTier>,
+    pub points: i32,
+}
+
+/// Server-wide counter that community milestones are measured against.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommunityMetric {
+    /// World mode progress earned by every play while the event runs.
+    Fragments,
+    /// Cleared plays on the event's charts.
+    Clears,
+}
+
+impl CommunityMetric {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Fragments => "fragments",
+            Self::Clears => "clears",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "fragments" => Some(Self::Fragments),
+            "clears" => Some(Self::Clears),
+            _ => None,
+        }
+    }
+}
+
+/// Reward sent to every player once the community counter reaches `target`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CommunityMilestone {
+    pub metric: String,
+    pub target: i64,
+    pub reward_type: String,
+    pub reward_item_id: String,
+    pub reward_amount: i32,
+    pub reached_at: Option<i64>,
+}
+
+/// Shared progress of all players in an event.
+#[derive(Debug, Clone, Serialize)]
+pub struct CommunityProgress {
+    pub event_id: String,
+    pub fragments: i64,
+    pub clears: i64,
+    pub milestones: Vec<CommunityMilestone>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EventLadderEntry {
+    pub rank: i32,
+    pub user_id: i32,
+    pub name: String,
+    pub points: i32,
+}
+
+/// Time-boxed events where plays on designated charts earn points.
+///
+/// Points are added after every successful score submission on an event
+/// chart while the event runs. Reward tiers are sent as presents when the
+/// player's total passes them, and the totals form the event ladder.
+///
+/// Every play also feeds the event's community counters; a community
+/// milestone is sent to all players as a present the first time its
+/// counter reaches the target.
+#[derive(Clone)]
+pub struct EventService {
+    pool: DbPool,
+    present_service: Arc<PresentService>,
+    notification_service: Arc<NotificationService>,
+}
+
+impl EventService {
+    pub fn new(pool: DbPool) -> Self {
+        Self {
+            present_service: Arc::new(PresentService::new(pool.clone())),
+            notification_service: Arc::new(NotificationService::new(pool.clone())),
+            pool,
+        }
+    }
+
+    /// Alert companion app devices when a community milestone is reached
+    pub fn with_push(mut self, push: PushGateway) -> Self {
+        self.notification_service =
+            Arc::new(NotificationService::new(self.pool.clone()).with_push(push));
+        self
+    }
+
+    /// Running events with the user's points.
+    pub async fn get_active_events(&self, user_id: i32) -> ArcResult<Vec<EventInfo>> {
+        let now = chrono::Utc::now().timestamp_millis();
+        let rows = sqlx::query!(
+            "SELECT e.event_id, e.name, e.description, e.start_ts, e.end_ts,
+                    ue.points AS `points?`
+             FROM event e
+             LEFT JOIN user_event ue ON ue.event_id = e.event_id AND ue.user_id = ?
+             WHERE e.is_available = 1 AND e.start_ts <= ? AND e.end_ts > ?
+             ORDER BY e.end_ts, e.event_id",
+            user_id,
+            now,
+            now
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut events = Vec::with_capacity(rows.len());
+        for row in rows {
+            events.push(EventInfo {
+                charts: self.get_event_charts(&row.event_id).await?,
+                reward_tiers: self.get_reward_tiers(&row.event_id).await?,
+                event_id: row.event_id,
+                name: row.name,
+                description: row.description.unwrap_or_default(),
+                start_ts: row.start_ts,
+                end_ts: row.end_ts,
+                points: row.points.unwrap_or(0),
+            });
+        }
+        Ok(events)
+    }
+
+    pub async fn get_event_charts(&self, event_id: &str) -> ArcResult<Vec<EventChart>> {
+        Ok(sqlx::query_as!(
+            EventChart,
+            "SELECT song_id, difficulty, point_multiplier FROM event_chart
+             WHERE event_id = ? ORDER BY song_id, difficulty",
+            event_id
+        )
+        .fetch_all(&self.pool)
+        .await?)
+    }
+
+    pub async fn get_reward_tiers(&self, event_id: &str) -> ArcResult<Vec<EventRewardTier>> {
+        Ok(sqlx::query_as!(
+            EventRewardTier,
+            "SELECT points, reward_type, reward_item_id, reward_amount FROM event_reward_tier
+             WHERE event_id = ? ORDER BY points",
+            event_id
+        )
+        .fetch_all(&self.pool)
+        .await?)
+    }
+
+    /// Top players of an event, highest points first; ties go to whoever
+    /// reached the total earlier. Shadow banned players are left out.
+    pub async fn get_ladder(&self, event_id: &str, limit: i64) -> ArcResult<Vec<EventLadderEntry>> {
+        let exists = sqlx::query_scalar!(
+            "SELECT COUNT(*) FROM event WHERE event_id = ? AND is_available = 1",
+            event_id
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        if exists == 0 {
+            return Err(ArcError::no_data("Event not found.", 108));
+        }
+
+        let rows = sqlx::query!(
+            "SELECT ue.user_id, u.name, ue.points
+             FROM user_event ue
+             JOIN user u ON u.user_id = ue.user_id
+             WHERE ue.event_id = ? AND ue.points > 0 AND u.is_shadow_banned = 0
+             ORDER BY ue.points DESC, ue.updated_at ASC, ue.user_id ASC
+             LIMIT ?",
+            event_id,
+            limit.clamp(1, MAX_LADDER_LIMIT)
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .enumerate()
+            .map(|(index, row)| EventLadderEntry {
+                rank: index as i32 + 1,
+                user_id: row.user_id,
+                name: row.name.unwrap_or_default(),
+                points: row.points,
+            })
+            .collect())
+    }
+
+    /// Shared progress and milestones of an event.
+    pub async fn get_community_progress(&self, event_id: &str) -> ArcResult<CommunityProgress> {
+        let exists = sqlx::query_scalar!(
+            "SELECT COUNT(*) FROM event WHERE event_id = ? AND is_available = 1",
+            event_id
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        if exists == 0 {
+            return Err(ArcError::no_data("Event not found.", 108));
+        }
+
+        let counters = sqlx::query!(
+            "SELECT metric, value FROM event_community_progress WHERE event_id = ?",
+            event_id
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        let counter = |metric: CommunityMetric| {
+            counters
+                .iter()
+                .find(|row| row.metric == metric.as_str())
+                .map_or(0, |row| row.value)
+        };
+
+        Ok(CommunityProgress {
+            event_id: event_id.to_string(),
+            fragments: counter(CommunityMetric::Fragments),
+            clears: counter(CommunityMetric::Clears),
+            milestones: self.get_community_milestones(event_id).await?,
+        })
+    }
+
+    pub async fn get_community_milestones(
+        &self,
+        event_id: &str,
+    ) -> ArcResult<Vec<CommunityMilestone>> {
+        Ok(sqlx::query_as!(
+            CommunityMilestone,
+            "SELECT metric, target, reward_type, reward_item_id, reward_amount, reached_at
+             FROM event_community_milestone
+             WHERE event_id = ? ORDER BY metric, target",
+            event_id
+        )
+        .fetch_all(&self.pool)
+        .await?)
+    }
+
+    /// Add event points for a submitted play and send any reward tiers
+    /// passed, then add the play to the community counters.
+    ///
+    /// `world_progress` is the world mode progress the play earned.
+    pub async fn record_play(
+        &self,
+        user_id: i32,
+        song_id: &str,
+        difficulty: i32,
+        score: i32,
+        clear_type: i32,
+        world_progress: f64,
+    ) -> ArcResult<()> {
+        let now = chrono::Utc::now().timestamp_millis();
+        let events = sqlx::query!(
+            "SELECT e.event_id, ec.point_multiplier
+             FROM event e
+             JOIN event_chart ec ON ec.event_id = e.event_id
+             WHERE e.is_available = 1 AND e.start_ts <= ? AND e.end_ts > ?
+               AND ec.song_id = ? AND ec.difficulty = ?",
+            now,
+            now,
+            song_id,
+            difficulty
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        for event in events {
+            let points = play_points(score, clear_type, event.point_multiplier);
+            if points <= 0 {
+                continue;
+            }
+            sqlx::query!(
+                "INSERT INTO user_event (user_id, event_id, points, updated_at)
+                 VALUES (?, ?, ?, ?)
+                 ON DUPLICATE KEY UPDATE points = points + VALUES(points),
+                    updated_at = VALUES(updated_at)",
+                user_id,
+                event.event_id,
+                points,
+                now
+            )
+            .execute(&self.pool)
+            .await?;
+
+            self.grant_reached_tiers(user_id, &event.event_id).await?;
+        }
+
+        self.record_community_play(song_id, difficulty, clear_type, world_progress, now)
+            .await
+    }
+
+    async fn record_community_play(
+        &self,
+        song_id: &str,
+        difficulty: i32,
+        clear_type: i32,
+        world_progress: f64,
+        now: i64,
+    ) -> ArcResult<()> {
+        let goals = sqlx::query!(
+            "SELECT DISTINCT m.event_id, m.metric,
+                    EXISTS(SELECT 1 FROM event_chart ec
+                           WHERE ec.event_id = m.event_id AND ec.song_id = ?
+                             AND ec.difficulty = ?) AS `on_event_chart!: i64`
+             FROM event_community_milestone m
+             JOIN event e ON e.event_id = m.event_id
+             WHERE e.is_available = 1 AND e.start_ts <= ? AND e.end_ts > ?",
+            song_id,
+            difficulty,
+            now,
+            now
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        for goal in goals {
+            let Some(metric) = CommunityMetric::parse(&goal.metric) else {
+                continue;
+            };
+            let amount = community_contribution(
+                metric,
+                clear_type,
+                goal.on_event_chart != 0,
+                world_progress,
+            );
+            if amount <= 0 {
+                continue;
+            }
+            sqlx::query!(
+                "INSERT INTO event_community_progress (event_id, metric, value)
+                 VALUES (?, ?, ?)
+                 ON DUPLICATE KEY UPDATE value = value + VALUES(value)",
+                goal.event_id,
+                metric.as_str(),
+                amount
+            )
+            .execute(&self.pool)
+            .await?;
+
+            self.distribute_reached_milestones(&goal.event_id, metric)
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn distribute_reached_milestones(
+        &self,
+        event_id: &str,
+        metric: CommunityMetric,
+    ) -> ArcResult<()> {
+        let value = sqlx::query_scalar!(
+            "SELECT value FROM event_community_progress WHERE event_id = ? AND metric = ?",
+            event_id,
+            metric.as_str()
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        let reached = sqlx::query!(
+            "SELECT target, reward_type, reward_item_id, reward_amount
+             FROM event_community_milestone
+             WHERE event_id = ? AND metric = ? AND reached_at IS NULL AND target <= ?
+             ORDER BY target",
+            event_id,
+            metric.as_str(),
+            value
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        for milestone in reached {
+            let mut tx = self.pool.begin().await?;
+            // The compare-and-set makes concurrent submissions send a milestone once.
+            let claimed = sqlx::query!(
+                "UPDATE event_community_milestone SET reached_at = ?
+                 WHERE event_id = ? AND metric = ? AND target = ? AND reached_at IS NULL",
+                chrono::Utc::now().timestamp_millis(),
+                event_id,
+                metric.as_str(),
+                milestone.target
+            )
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+            if claimed == 0 {
+                continue;
+            }
+
+            let present_id = format!("event_{event_id}_{}_{}", metric.as_str(), milestone.target);
+            sqlx::query!(
+                "INSERT INTO present (present_id, expire_ts, description) VALUES (?, NULL, ?)",
+                &present_id,
+                format!(
+                    "Community goal reached: {event_id} ({} {})",
+                    milestone.target,
+                    metric.as_str()
+                )
+            )
+            .execute(&mut *tx)
+            .await?;
+            sqlx::query!(
+                "INSERT IGNORE INTO item (item_id, type, is_available) VALUES (?, ?, 1)",
+                milestone.reward_item_id,
+                milestone.reward_type
+            )
+            .execute(&mut *tx)
+            .await?;
+            sqlx::query!(
+                "INSERT INTO present_item (present_id, item_id, type, amount) VALUES (?, ?, ?, ?)",
+                &present_id,
+                milestone.reward_item_id,
+                milestone.reward_type,
+                milestone.reward_amount
+            )
+            .execute(&mut *tx)
+            .await?;
+            sqlx::query!(
+                "INSERT INTO user_present (user_id, present_id)
+                 SELECT user_id, ? FROM user",
+                &present_id
+            )
+            .execute(&mut *tx)
+            .await?;
+            tx.commit().await?;
+
+            self.notification_service
+                .notify_present_delivered(PushTarget::AllUsers, &present_id)
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn grant_reached_tiers(&self, user_id: i32, event_id: &str) -> ArcResult<()> {
+        let progress = sqlx::query!(
+            "SELECT points, rewarded_points FROM user_event WHERE user_id = ? AND event_id = ?",
+            user_id,
+            event_id
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        if progress.points <= progress.rewarded_points {
+            return Ok(());
+        }
+
+        // The compare-and-set makes concurrent submissions reward a tier once.
+        let claimed = sqlx::query!(
+            "UPDATE user_event SET rewarded_points = ?
+             WHERE user_id = ? AND event_id = ? AND rewarded_points = ?",
+            progress.points,
+            user_id,
+            event_id,
+            progress.rewarded_points
+        )
+        .execute(&self.pool)
+        .await?
+        .rows_affected();
+        if claimed == 0 {
+            return Ok(());
+        }
+
+        let tiers = self.get_reward_tiers(event_id).await?;
+        for tier in tiers_passed(&tiers, progress.rewarded_points, progress.points) {
+            let present_id = format!("event_{event_id}_{}_{user_id}", tier.points);
+            let item = PresentItem {
+                present_id: present_id.clone(),
+                item_id: tier.reward_item_id.clone(),
+                item_type: tier.reward_type.clone(),
+                amount: tier.reward_amount,
+            };
+            self.present_service
+                .create_present(
+                    &present_id,
+                    None,
+                    &format!("Event reward: {event_id} ({} pt)", tier.points),
+                    vec![item],
+                    user_id,
+                )
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+/// Points earned by one play; failed plays earn nothing.
+fn play_points(score: i32, clear_type: i32, point_multiplier: i32) -> i32 {
+    if clear_type == 0 {
+        return 0;
+    }
+    (score.max(0) / SCORE_PER_POINT) * point_multiplier.max(0)
+}
+
+/// Amount one play adds to a community counter.
+fn community_contribution(
+    metric: CommunityMetric,
+    clear_type: i32,
+    on_event_chart: bool,
+    world_progress: f64,
+) -> i64 {
+    match metric {
+        CommunityMetric::Fragments => world_progress.max(0.0).round() as i64,
+        CommunityMetric::Clears => i64::from(on_event_chart && clear_type != 0),
+    }
+}
+
+/// Tiers with `from < points <= to`.
+fn tiers_passed(tiers: &[EventRewardTier], from: i32, to: i32) -> Vec<&EventRewardTier> {
+    tiers
+        .iter()
+        .filter(|tier| tier.points > from && tier.points <= to)
+        .collect()
+}
+
+/// What a limited-time event changes while it runs. Unset fields leave the
+/// game as it is.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EventModifiers {
+    /// Multiplier on the steps a world mode play advances.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub progress_multiplier: Option<f64>,
+    /// Multiplier on the stamina a world mode play costs, rounded up; 0 makes
+    /// plays free.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stamina_cost_multiplier: Option<f64>,
+    /// Purchases a pick ticket can be exchanged for during the event.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub pick_ticket_purchases: Vec<String>,
+}
+
+/// An event from the limited-time calendar, active from `start` until
+/// `end` (milliseconds).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LimitedEvent {
+    pub id: String,
+    #[serde(default)]
+    pub title: String,
+    pub start: i64,
+    pub end: i64,
+    #[serde(default)]
+    pub modifiers: EventModifiers,
+}
+
+impl LimitedEvent {
+    pub fn is_active_at(&self, now: i64) -> bool {
+        self.start <= now && now < self.end
+    }
+}
+
+/// Layout of `assets/events.json`.
+#[derive(Debug, Deserialize)]
+struct LimitedEventsFile {
+    events: Vec<LimitedEvent>,
+}
+
+/// Limited-time events such as pick ticket campaigns or cheaper world mode
+/// stamina, read once from a JSON calendar.
+///
+/// Unlike point events these need no tables: an event only changes game
+/// rules between its start and end. Modifiers of overlapping events stack
+/// by multiplying.
+#[derive(Debug, Clone, Default)]
+pub struct LimitedEvents {
+    events: Vec<LimitedEvent>,
+}
+
+impl LimitedEvents {
+    pub fn new(events: Vec<LimitedEvent>) -> Self {
+        Self { events }
+    }
+
+    /// Load the calendar from `LIMITED_EVENTS` (default `assets/events.json`).
+    /// A missing file means no events.
+    pub fn load() -> ArcResult<Self> {
+        let path = env::var("LIMITED_EVENTS")
+            .ok()
+            .filter(|value| !value.trim().is_empty())
+            .map(|value| value.trim().into())
+            .unwrap_or_else(|| asset_path(LIMITED_EVENTS_ASSET));
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        Self::from_file(&path)
+    }
+
+    fn from_file(path: &Path) -> ArcResult<Self> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| ArcError::input(format!("Failed to read {}: {e}", path.display())))?;
+        Self::from_json(&content)
+            .map_err(|e| ArcError::input(format!("Invalid {}: {e}", path.display())))
+    }
+
+    fn from_json(content: &str) -> ArcResult<Self> {
+        let file: LimitedEventsFile = serde_json::from_str(content)?;
+        let mut ids = HashSet::new();
+        for event in &file.events {
+            if event.id.trim().is_empty() {
+                return Err(ArcError::input("Event id must not be empty."));
+            }
+            if !ids.insert(event.id.as_str()) {
+                return Err(ArcError::input(format!("Duplicate event `{}`.", event.id)));
+            }
+            if event.start >= event.end {
+                return Err(ArcError::input(format!(
+                    "Event `{}` must end after it starts.",
+                    event.id
+                )));
+            }
+            let modifiers = &event.modifiers;
+            for multiplier in [
+                modifiers.progress_multiplier,
+                modifiers.stamina_cost_multiplier,
+            ]
+            .into_iter()
+            .flatten()
+            {
+                if !multiplier.is_finite() || multiplier < 0.0 {
+                    return Err(ArcError::input(format!(
+                        "Event `{}` has an invalid multiplier {multiplier}.",
+                        event.id
+                    )));
+                }
+            }
+        }
+        Ok(Self::new(file.events))
+    }
+
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    /// Events running at `now`.
+    pub fn active_at(&self, now: i64) -> impl Iterator<Item = &LimitedEvent> {
+        self.events
+            .iter()
+            .filter(move |event| event.is_active_at(now))
+    }
+
+    /// Combined world progress multiplier at `now`.
+    pub fn progress_multiplier(&self, now: i64) -> f64 {
+        self.active_at(now)
+            .filter_map(|event| event.modifiers.progress_multiplier)
+            .product()
+    }
+
+    /// Stamina a world mode play costing `cost` takes at `now`.
+    pub fn stamina_cost(&self, cost: i32, now: i64) -> i32 {
+        let multiplier: f64 = self
+            .active_at(now)
+            .filter_map(|event| event.modifiers.stamina_cost_multiplier)
+            .product();
+        (cost as f64 * multiplier).ceil() as i32
+    }
+
+    /// Discount window of the running pick ticket campaign covering
+    /// `purchase_name`, if any.
+    pub fn pick_ticket_window(&self, purchase_name: &str, now: i64) -> Option<(i64, i64)> {
+        self.active_at(now)
+            .find(|event| {
+                event
+                    .modifiers
+                    .pick_ticket_purchases
+                    .iter()
+                    .any(|name| name == purchase_name)
+            })
+            .map(|event| (event.start, event.end))
+    }
+}
+
+/// The limited-time event calendar, loaded on first use. A calendar that
+/// fails to load is logged and treated as empty.
+pub fn limited_events() -> &'static LimitedEvents {
+    LIMITED_EVENTS.get_or_init(|| {
+        LimitedEvents::load().unwrap_or_else(|e| {
+            log::error!("Failed to load limited-time events: {e}");
+            LimitedEvents::default()
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_play_points() {
+        assert_eq!(play_points(10_001_000, 3, 1), 1000);
+        assert_eq!(play_points(9_876_543, 1, 2), 1974);
+        assert_eq!(play_points(9_876_543, 0, 2), 0);
+        assert_eq!(play_points(5_000, 1, 1), 0);
+    }
+
+    #[test]
+    fn test_community_contribution() {
+        use CommunityMetric::{Clears, Fragments};
+        assert_eq!(community_contribution(Fragments, 0, false, 7.6), 8);
+        assert_eq!(community_contribution(Fragments, 1, true, -1.0), 0);
+        assert_eq!(community_contribution(Clears, 2, true, 7.6), 1);
+        assert_eq!(community_contribution(Clears, 0, true, 7.6), 0);
+        assert_eq!(community_contribution(Clears, 2, false, 7.6), 0);
+        assert_eq!(CommunityMetric::parse("clears"), Some(Clears));
+        assert_eq!(CommunityMetric::parse(Fragments.as_str()), Some(Fragments));
+        assert_eq!(CommunityMetric::parse("stars"), None);
+    }
+
+    #[test]
+    fn test_tiers_passed() {
+        let tier = |points| EventRewardTier {
+            points,
+            reward_type: "fragment".to_string(),
+            reward_item_id: "fragment".to_string(),
+            reward_amount: 100,
+        };
+        let tiers = vec![tier(500), tier(1000), tier(2000)];
+
+        let passed = tiers_passed(&tiers, 0, 1000);
+        assert_eq!(passed, vec![&tiers[0], &tiers[1]]);
+        assert!(tiers_passed(&tiers, 1000, 1999).is_empty());
+        assert_eq!(tiers_passed(&tiers, 1999, 5000), vec![&tiers[2]]);
+    }
+
+    #[test]
+    fn test_limited_events_from_json() {
+        let events = LimitedEvents::from_json(
+            r#"{"events": [{"id": "pick", "start": 1000, "end": 2000,
+                "modifiers": {"pick_ticket_purchases": ["grievouslady"]}}]}"#,
+        )
+        .unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(
+            events.pick_ticket_window("grievouslady", 1500),
+            Some((1000, 2000))
+        );
+        assert_eq!(events.pick_ticket_window("grievouslady", 2000), None);
+        assert_eq!(events.pick_ticket_window("fractureray", 1500), None);
+
+        let event = |id: &str, start, end, multiplier| {
+            format!(
+                r#"{{"id": "{id}", "start": {start}, "end": {end},
+                    "modifiers": {{"progress_multiplier": {multiplier}}}}}"#
+            )
+        };
+        let invalid = [
+            event("", 0, 1, 1.0),
+            event("a", 5, 5, 1.0),
+            event("a", 0, 1, -1.0),
+            format!("{}, {}", event("a", 0, 1, 1.0), event("a", 2, 3, 1.0)),
+        ];
+        for events in invalid {
+            let json = format!(r#"{{"events": [{events}]}}"#);
+            assert!(LimitedEvents::from_json(&json).is_err(), "{json}");
+        }
+    }
+
+    #[test]
+    fn test_limited_event_modifiers() {
+        let event = |start, end, progress, stamina| LimitedEvent {
+            id: format!("event_{start}"),
+            title: String::new(),
+            start,
+            end,
+            modifiers: EventModifiers {
+                progress_multiplier: progress,
+                stamina_cost_multiplier: stamina,
+                pick_ticket_purchases: Vec::new(),
+            },
+        };
+        let events = LimitedEvents::new(vec![
+            event(0, 100, Some(2.0), Some(0.5)),
+            event(50, 150, Some(1.5), None),
+            event(200, 300, None, Some(0.0)),
+        ]);
+
+        assert_eq!(events.progress_multiplier(10), 2.0);
+        assert_eq!(events.progress_multiplier(60), 3.0);
+        assert_eq!(events.progress_multiplier(120), 1.5);
+        assert_eq!(events.progress_multiplier(150), 1.0);
+
+        assert_eq!(events.stamina_cost(3, 10), 2);
+        assert_eq!(events.stamina_cost(3, 120), 3);
+        assert_eq!(events.stamina_cost(3, 250), 0);
+        assert_eq!(events.active_at(60).count(), 2);
+    }
+}
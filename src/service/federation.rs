@@ -0,0 +1,423 @@
+use crate::error::{ArcError, ArcResult};
+use crate::utils::{env_list, hex_encode, hmac_sha256_sign, hmac_sha256_verify};
+use crate::DbPool;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::env;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+
+/// Header carrying the hex HMAC-SHA256 of the request body.
+pub const SIGNATURE_HEADER: &str = "X-Federation-Signature";
+
+const DEFAULT_PUSH_INTERVAL_SECONDS: u64 = 600;
+const PUSH_BATCH_SIZE: i64 = 500;
+const MAX_BATCH_ENTRIES: usize = 5000;
+const MAX_BATCH_AGE_MS: i64 = 10 * 60 * 1000;
+const SONG_TOP_LIMIT: usize = 20;
+const REQUEST_TIMEOUT_SECONDS: u64 = 30;
+
+/// Opt-in leaderboard sharing between server instances.
+#[derive(Debug, Clone, Default)]
+pub struct FederationConfig {
+    /// Source tag of this instance; federation is off while empty.
+    pub server_id: String,
+    /// Shared HMAC secret of the federation group.
+    pub secret: String,
+    /// Base URLs of peers to push to, e.g. `https://peer.example.com`.
+    pub peers: Vec<String>,
+    /// Peer server ids accepted on ingest; empty accepts any signed peer.
+    pub accept_from: Vec<String>,
+    pub push_interval: Duration,
+}
+
+impl FederationConfig {
+    pub fn from_env() -> ArcResult<Self> {
+        let server_id = env::var("FEDERATION_SERVER_ID")
+            .unwrap_or_default()
+            .trim()
+            .to_string();
+        let secret = env::var("FEDERATION_SECRET").unwrap_or_default();
+        if server_id.is_empty() != secret.trim().is_empty() {
+            return Err(ArcError::input(
+                "FEDERATION_SERVER_ID and FEDERATION_SECRET must be set together",
+            ));
+        }
+        if server_id.len() > 64 {
+            return Err(ArcError::input(
+                "FEDERATION_SERVER_ID must be at most 64 characters",
+            ));
+        }
+
+        Ok(Self {
+            server_id,
+            secret,
+            peers: env_list("FEDERATION_PEERS")
+                .into_iter()
+                .map(|peer| peer.trim_end_matches('/').to_string())
+                .collect(),
+            accept_from: env_list("FEDERATION_ACCEPT_FROM"),
+            push_interval: Duration::from_secs(
+                env::var("FEDERATION_PUSH_INTERVAL_SECONDS")
+                    .ok()
+                    .and_then(|value| value.parse::<u64>().ok())
+                    .filter(|seconds| *seconds > 0)
+                    .unwrap_or(DEFAULT_PUSH_INTERVAL_SECONDS),
+            ),
+        })
+    }
+}
+
+/// One anonymized chart best exchanged between peers.
+///
+/// Players are identified by a per-server pseudonymous key; the display name
+/// is only shared for players with a public profile.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FederatedEntry {
+    pub player_key: String,
+    pub name: Option<String>,
+    pub song_id: String,
+    pub difficulty: i32,
+    pub score: i32,
+    pub shiny_perfect_count: i32,
+    pub perfect_count: i32,
+    pub near_count: i32,
+    pub miss_count: i32,
+    pub clear_type: i32,
+    pub rating: f64,
+    pub time_played: i64,
+}
+
+/// Signed payload pushed to `POST /federation/scores`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FederationBatch {
+    pub server_id: String,
+    pub generated_at: i64,
+    pub entries: Vec<FederatedEntry>,
+}
+
+/// Pushes local chart bests to peers, ingests theirs and merges remote
+/// entries into song rankings.
+#[derive(Clone)]
+pub struct FederationService {
+    pool: DbPool,
+    config: Arc<FederationConfig>,
+    http: reqwest::Client,
+    /// Per-peer `time_played` cursor of the last successful push.
+    push_cursors: Arc<Mutex<HashMap<String, i64>>>,
+}
+
+impl std::fmt::Debug for FederationService {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FederationService")
+            .field("server_id", &self.config.server_id)
+            .field("peers", &self.config.peers)
+            .finish()
+    }
+}
+
+impl FederationService {
+    pub fn new(pool: DbPool, config: FederationConfig) -> Self {
+        Self {
+            pool,
+            config: Arc::new(config),
+            http: reqwest::Client::builder()
+                .timeout(Duration::from_secs(REQUEST_TIMEOUT_SECONDS))
+                .build()
+                .unwrap_or_default(),
+            push_cursors: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    pub fn from_env(pool: DbPool) -> ArcResult<Self> {
+        Ok(Self::new(pool, FederationConfig::from_env()?))
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.config.server_id.is_empty()
+    }
+
+    pub fn server_id(&self) -> &str {
+        &self.config.server_id
+    }
+
+    pub fn peers(&self) -> &[String] {
+        &self.config.peers
+    }
+
+    pub fn push_interval(&self) -> Duration {
+        self.config.push_interval
+    }
+
+    /// Push chart bests changed since the last successful push to every peer.
+    pub async fn push_once(&self) {
+        for peer in &self.config.peers {
+            if let Err(e) = self.push_to_peer(peer).await {
+                log::warn!("Federation push to `{peer}` failed: {e}");
+            }
+        }
+    }
+
+    async fn push_to_peer(&self, peer: &str) -> ArcResult<()> {
+        let mut cursor = self
+            .push_cursors
+            .lock()
+            .await
+            .get(peer)
+            .copied()
+            .unwrap_or(0);
+        let url = format!("{peer}/federation/scores");
+
+        loop {
+            let entries = self.load_local_entries(cursor).await?;
+            let Some(last) = entries.last() else {
+                break;
+            };
+            let previous_cursor = cursor;
+            let next_cursor = last.time_played;
+            let count = entries.len();
+
+            let batch = FederationBatch {
+                server_id: self.config.server_id.clone(),
+                generated_at: chrono::Utc::now().timestamp_millis(),
+                entries,
+            };
+            let body = serde_json::to_string(&batch)?;
+            let response = self
+                .http
+                .post(&url)
+                .header("Content-Type", "application/json")
+                .header(
+                    SIGNATURE_HEADER,
+                    hmac_sha256_sign(&self.config.secret, body.as_bytes()),
+                )
+                .body(body)
+                .send()
+                .await
+                .map_err(|e| ArcError::input(format!("request failed: {e}")))?;
+            if !response.status().is_success() {
+                return Err(ArcError::input(format!(
+                    "peer answered {}",
+                    response.status()
+                )));
+            }
+
+            cursor = next_cursor;
+            self.push_cursors
+                .lock()
+                .await
+                .insert(peer.to_string(), cursor);
+            log::debug!("Federation pushed {count} entries to `{peer}`");
+            // Stop on a short batch, or when a full batch shares one timestamp
+            // and the cursor cannot advance.
+            if (count as i64) < PUSH_BATCH_SIZE || next_cursor == previous_cursor {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Local chart bests played at or after `since`, oldest first.
+    ///
+    /// Rows sharing the cursor timestamp are sent again on the next push;
+    /// ingest is an upsert, so repeats are harmless.
+    async fn load_local_entries(&self, since: i64) -> ArcResult<Vec<FederatedEntry>> {
+        let rows = sqlx::query!(
+            "SELECT bs.user_id, u.name, u.is_profile_public, bs.song_id, bs.difficulty,
+                    bs.score, bs.shiny_perfect_count, bs.perfect_count, bs.near_count,
+                    bs.miss_count, bs.best_clear_type, bs.rating, bs.time_played
+             FROM best_score bs
+             JOIN user u ON u.user_id = bs.user_id
+             WHERE bs.time_played >= ? AND u.is_shadow_banned = 0
+             ORDER BY bs.time_played ASC
+             LIMIT ?",
+            since,
+            PUSH_BATCH_SIZE
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| FederatedEntry {
+                player_key: self.player_key(row.user_id),
+                name: row.name.filter(|_| row.is_profile_public.unwrap_or(0) != 0),
+                song_id: row.song_id,
+                difficulty: row.difficulty,
+                score: row.score.unwrap_or(0),
+                shiny_perfect_count: row.shiny_perfect_count.unwrap_or(0),
+                perfect_count: row.perfect_count.unwrap_or(0),
+                near_count: row.near_count.unwrap_or(0),
+                miss_count: row.miss_count.unwrap_or(0),
+                clear_type: row.best_clear_type.unwrap_or(0),
+                rating: row.rating.unwrap_or(0.0),
+                time_played: row.time_played.unwrap_or(0),
+            })
+            .collect())
+    }
+
+    fn player_key(&self, user_id: i32) -> String {
+        let digest = Sha256::digest(
+            format!("{}:{user_id}:{}", self.config.server_id, self.config.secret).as_bytes(),
+        );
+        hex_encode(&digest[..12])
+    }
+
+    /// Verify and store a batch pushed by a peer; returns the stored count.
+    pub async fn ingest(&self, body: &[u8], signature: &str) -> ArcResult<usize> {
+        if !self.is_enabled() {
+            return Err(ArcError::no_access("Federation is disabled.", 108));
+        }
+        if !hmac_sha256_verify(&self.config.secret, body, signature) {
+            return Err(ArcError::no_access("Invalid federation signature.", 108));
+        }
+
+        let batch: FederationBatch = serde_json::from_slice(body)?;
+        let now = chrono::Utc::now().timestamp_millis();
+        if batch.server_id.is_empty()
+            || batch.server_id.len() > 64
+            || batch.server_id == self.config.server_id
+        {
+            return Err(ArcError::input("Invalid federation source."));
+        }
+        if !self.config.accept_from.is_empty()
+            && !self.config.accept_from.contains(&batch.server_id)
+        {
+            return Err(ArcError::no_access("Federation source not accepted.", 108));
+        }
+        if (now - batch.generated_at).abs() > MAX_BATCH_AGE_MS {
+            return Err(ArcError::input("Federation batch is stale."));
+        }
+        if batch.entries.len() > MAX_BATCH_ENTRIES {
+            return Err(ArcError::input("Federation batch is too large."));
+        }
+
+        let mut tx = self.pool.begin().await?;
+        for entry in &batch.entries {
+            let name = entry
+                .name
+                .as_deref()
+                .map(|name| name.chars().take(64).collect::<String>());
+            sqlx::query!(
+                "INSERT INTO federated_score (
+                    source, player_key, player_name, song_id, difficulty, score,
+                    shiny_perfect_count, perfect_count, near_count, miss_count,
+                    clear_type, rating, time_played, received_at
+                 ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+                 ON DUPLICATE KEY UPDATE
+                    player_name = VALUES(player_name), score = VALUES(score),
+                    shiny_perfect_count = VALUES(shiny_perfect_count),
+                    perfect_count = VALUES(perfect_count), near_count = VALUES(near_count),
+                    miss_count = VALUES(miss_count), clear_type = VALUES(clear_type),
+                    rating = VALUES(rating), time_played = VALUES(time_played),
+                    received_at = VALUES(received_at)",
+                batch.server_id,
+                entry.player_key,
+                name,
+                entry.song_id,
+                entry.difficulty,
+                entry.score,
+                entry.shiny_perfect_count,
+                entry.perfect_count,
+                entry.near_count,
+                entry.miss_count,
+                entry.clear_type,
+                entry.rating,
+                entry.time_played,
+                now
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+
+        Ok(batch.entries.len())
+    }
+
+    /// Merge remote entries into a local song top list.
+    ///
+    /// Every entry gets a `source` tag; remote players have `user_id` 0.
+    /// Returns the local list untouched while federation is disabled.
+    pub async fn merge_song_top(
+        &self,
+        song_id: &str,
+        difficulty: i32,
+        local: Vec<HashMap<String, Value>>,
+    ) -> ArcResult<Vec<HashMap<String, Value>>> {
+        if !self.is_enabled() {
+            return Ok(local);
+        }
+
+        let remote = sqlx::query!(
+            "SELECT source, player_key, player_name, score, shiny_perfect_count,
+                    perfect_count, near_count, miss_count, clear_type, rating, time_played
+             FROM federated_score
+             WHERE song_id = ? AND difficulty = ?
+             ORDER BY score DESC, time_played ASC
+             LIMIT ?",
+            song_id,
+            difficulty,
+            SONG_TOP_LIMIT as i64
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut merged = local
+            .into_iter()
+            .map(|mut entry| {
+                entry.insert(
+                    "source".to_string(),
+                    Value::from(self.config.server_id.clone()),
+                );
+                entry
+            })
+            .collect::<Vec<_>>();
+        merged.extend(remote.into_iter().map(|row| {
+            let name = row.player_name.unwrap_or_else(|| {
+                format!("Player#{}", &row.player_key[..6.min(row.player_key.len())])
+            });
+            HashMap::from([
+                ("user_id".to_string(), Value::from(0)),
+                ("name".to_string(), Value::from(name)),
+                ("song_id".to_string(), Value::from(song_id)),
+                ("difficulty".to_string(), Value::from(difficulty)),
+                ("score".to_string(), Value::from(row.score)),
+                (
+                    "shiny_perfect_count".to_string(),
+                    Value::from(row.shiny_perfect_count),
+                ),
+                ("perfect_count".to_string(), Value::from(row.perfect_count)),
+                ("near_count".to_string(), Value::from(row.near_count)),
+                ("miss_count".to_string(), Value::from(row.miss_count)),
+                ("health".to_string(), Value::from(0)),
+                ("modifier".to_string(), Value::from(0)),
+                ("time_played".to_string(), Value::from(row.time_played)),
+                ("clear_type".to_string(), Value::from(row.clear_type)),
+                ("best_clear_type".to_string(), Value::from(row.clear_type)),
+                ("rating".to_string(), Value::from(row.rating)),
+                ("character".to_string(), Value::from(0)),
+                ("is_skill_sealed".to_string(), Value::from(false)),
+                ("is_char_uncapped".to_string(), Value::from(false)),
+                ("source".to_string(), Value::from(row.source)),
+            ])
+        }));
+
+        let field = |entry: &HashMap<String, Value>, key: &str| {
+            entry.get(key).and_then(Value::as_i64).unwrap_or(0)
+        };
+        merged.sort_by(|a, b| {
+            field(b, "score")
+                .cmp(&field(a, "score"))
+                .then_with(|| field(a, "time_played").cmp(&field(b, "time_played")))
+        });
+        merged.truncate(SONG_TOP_LIMIT);
+        for (rank, entry) in merged.iter_mut().enumerate() {
+            entry.insert("rank".to_string(), Value::from(rank as i64 + 1));
+        }
+
+        Ok(merged)
+    }
+}
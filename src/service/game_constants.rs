@@ -0,0 +1,306 @@
+use crate::config::{Constants, CONFIG};
+use crate::error::{ArcError, ArcResult};
+use crate::DbPool;
+use serde::Serialize;
+use std::sync::{OnceLock, RwLock};
+
+/// Game balance values that can be tuned from the `game_constants` table.
+///
+/// Every field has a row name equal to the field name. Rows that are missing
+/// keep the default, which is the configured value for the potential and
+/// invasion weights and the official value for everything else.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GameConstants {
+    /// Stamina taken when starting a course.
+    pub course_stamina_cost: i32,
+    pub best30_weight: f64,
+    pub recent10_weight: f64,
+    /// Chance of a starting / hard invasion when playing with an invasion skill.
+    pub invasion_start_weight: f64,
+    pub invasion_hard_weight: f64,
+    /// Normal maps: `base + rating_multiplier * sqrt(rating)` steps per play.
+    pub world_base_progress: f64,
+    pub world_rating_progress_multiplier: f64,
+    /// Beyond maps: `rating_multiplier * sqrt(rating)` plus the clear or fail bonus.
+    pub beyond_rating_progress_multiplier: f64,
+    pub beyond_clear_progress: f64,
+    pub beyond_fail_progress: f64,
+    /// Character exp gained per point of play rating.
+    pub character_exp_multiplier: f64,
+    /// Character exp of one core used for leveling.
+    pub core_exp: f64,
+    /// Stamina regenerates up to this value.
+    pub max_stamina: i32,
+    /// Milliseconds to regenerate one stamina.
+    pub stamina_recover_tick: i64,
+    /// Stamina above which stamina purchases are refused.
+    pub stamina_limit: i32,
+    /// Milliseconds between two fragment stamina purchases.
+    pub fragment_stamina_cooldown: i64,
+    /// Milliseconds world mode stays locked after playing Hikari (Fatalis).
+    pub fatalis_world_locked_time: i64,
+}
+
+impl Default for GameConstants {
+    fn default() -> Self {
+        Self {
+            course_stamina_cost: 4,
+            best30_weight: CONFIG.best30_weight,
+            recent10_weight: CONFIG.recent10_weight,
+            invasion_start_weight: CONFIG.invasion_start_weight,
+            invasion_hard_weight: CONFIG.invasion_hard_weight,
+            world_base_progress: 2.5,
+            world_rating_progress_multiplier: 2.45,
+            beyond_rating_progress_multiplier: 0.43,
+            beyond_clear_progress: 75.0 / 28.0,
+            beyond_fail_progress: 25.0 / 28.0,
+            character_exp_multiplier: 6.0,
+            core_exp: Constants::CORE_EXP as f64,
+            max_stamina: Constants::MAX_STAMINA,
+            stamina_recover_tick: Constants::STAMINA_RECOVER_TICK,
+            stamina_limit: Constants::STAMINA_LIMIT,
+            fragment_stamina_cooldown: Constants::FRAGSTAM_RECOVER_TICK,
+            fatalis_world_locked_time: Constants::SKILL_FATALIS_WORLD_LOCKED_TIME,
+        }
+    }
+}
+
+impl GameConstants {
+    /// Names of every constant, in the order the admin panel lists them.
+    pub const NAMES: [&'static str; 17] = [
+        "course_stamina_cost",
+        "best30_weight",
+        "recent10_weight",
+        "invasion_start_weight",
+        "invasion_hard_weight",
+        "world_base_progress",
+        "world_rating_progress_multiplier",
+        "beyond_rating_progress_multiplier",
+        "beyond_clear_progress",
+        "beyond_fail_progress",
+        "character_exp_multiplier",
+        "core_exp",
+        "max_stamina",
+        "stamina_recover_tick",
+        "stamina_limit",
+        "fragment_stamina_cooldown",
+        "fatalis_world_locked_time",
+    ];
+
+    /// The value named `name`, `None` for unknown names.
+    pub fn get(&self, name: &str) -> Option<f64> {
+        let value = match name {
+            "course_stamina_cost" => self.course_stamina_cost as f64,
+            "best30_weight" => self.best30_weight,
+            "recent10_weight" => self.recent10_weight,
+            "invasion_start_weight" => self.invasion_start_weight,
+            "invasion_hard_weight" => self.invasion_hard_weight,
+            "world_base_progress" => self.world_base_progress,
+            "world_rating_progress_multiplier" => self.world_rating_progress_multiplier,
+            "beyond_rating_progress_multiplier" => self.beyond_rating_progress_multiplier,
+            "beyond_clear_progress" => self.beyond_clear_progress,
+            "beyond_fail_progress" => self.beyond_fail_progress,
+            "character_exp_multiplier" => self.character_exp_multiplier,
+            "core_exp" => self.core_exp,
+            "max_stamina" => self.max_stamina as f64,
+            "stamina_recover_tick" => self.stamina_recover_tick as f64,
+            "stamina_limit" => self.stamina_limit as f64,
+            "fragment_stamina_cooldown" => self.fragment_stamina_cooldown as f64,
+            "fatalis_world_locked_time" => self.fatalis_world_locked_time as f64,
+            _ => return None,
+        };
+        Some(value)
+    }
+
+    /// Check that `value` can be stored for the constant `name`.
+    pub fn validate(name: &str, value: f64) -> ArcResult<()> {
+        if !Self::NAMES.contains(&name) {
+            return Err(ArcError::input(format!("Unknown game constant `{name}`")));
+        }
+        let min = match name {
+            "max_stamina" | "stamina_recover_tick" => 1.0,
+            _ => 0.0,
+        };
+        if !value.is_finite() || value < min {
+            return Err(ArcError::input(format!(
+                "Game constant `{name}` must be a number of at least {min}"
+            )));
+        }
+        Ok(())
+    }
+
+    /// Set the value named `name`. Returns `false` for unknown names.
+    fn apply(&mut self, name: &str, value: f64) -> bool {
+        match name {
+            "course_stamina_cost" => self.course_stamina_cost = value.round() as i32,
+            "best30_weight" => self.best30_weight = value,
+            "recent10_weight" => self.recent10_weight = value,
+            "invasion_start_weight" => self.invasion_start_weight = value,
+            "invasion_hard_weight" => self.invasion_hard_weight = value,
+            "world_base_progress" => self.world_base_progress = value,
+            "world_rating_progress_multiplier" => self.world_rating_progress_multiplier = value,
+            "beyond_rating_progress_multiplier" => self.beyond_rating_progress_multiplier = value,
+            "beyond_clear_progress" => self.beyond_clear_progress = value,
+            "beyond_fail_progress" => self.beyond_fail_progress = value,
+            "character_exp_multiplier" => self.character_exp_multiplier = value,
+            "core_exp" => self.core_exp = value,
+            "max_stamina" => self.max_stamina = value.round() as i32,
+            "stamina_recover_tick" => self.stamina_recover_tick = value.round() as i64,
+            "stamina_limit" => self.stamina_limit = value.round() as i32,
+            "fragment_stamina_cooldown" => self.fragment_stamina_cooldown = value.round() as i64,
+            "fatalis_world_locked_time" => self.fatalis_world_locked_time = value.round() as i64,
+            _ => return false,
+        }
+        true
+    }
+}
+
+static GAME_CONSTANTS: OnceLock<RwLock<GameConstants>> = OnceLock::new();
+
+fn game_constants_lock() -> &'static RwLock<GameConstants> {
+    GAME_CONSTANTS.get_or_init(|| RwLock::new(GameConstants::default()))
+}
+
+/// Currently loaded game constants.
+pub fn game_constants() -> GameConstants {
+    match game_constants_lock().read() {
+        Ok(guard) => *guard,
+        Err(poisoned) => *poisoned.into_inner(),
+    }
+}
+
+/// A constant as listed in the admin panel.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GameConstantEntry {
+    pub name: String,
+    /// Value in effect on this instance
+    pub value: f64,
+    pub default_value: f64,
+    /// Value of the table row, `None` when the default applies
+    pub override_value: Option<f64>,
+}
+
+/// Loads [`GameConstants`] from the `game_constants` table and edits it.
+///
+/// Reads always go through the in-memory copy behind [`game_constants`];
+/// edits reload it right away on this instance, other instances pick them
+/// up on their next periodic reload.
+#[derive(Debug, Clone)]
+pub struct GameConstantsService {
+    pool: DbPool,
+}
+
+impl GameConstantsService {
+    pub fn new(pool: DbPool) -> Self {
+        Self { pool }
+    }
+
+    /// Rebuild the constants from defaults plus the table rows and swap them
+    /// in. Returns the number of rows applied.
+    pub async fn reload(&self) -> ArcResult<usize> {
+        let rows = sqlx::query!("SELECT name, value FROM game_constants")
+            .fetch_all(&self.pool)
+            .await?;
+
+        let mut constants = GameConstants::default();
+        let mut applied = 0;
+        for row in rows {
+            if constants.apply(&row.name, row.value) {
+                applied += 1;
+            } else {
+                log::warn!("Ignoring unknown game constant `{}`", row.name);
+            }
+        }
+
+        match game_constants_lock().write() {
+            Ok(mut guard) => *guard = constants,
+            Err(poisoned) => *poisoned.into_inner() = constants,
+        }
+        Ok(applied)
+    }
+
+    /// Every constant with its current, default and overridden value.
+    pub async fn list(&self) -> ArcResult<Vec<GameConstantEntry>> {
+        let rows = sqlx::query!("SELECT name, value FROM game_constants")
+            .fetch_all(&self.pool)
+            .await?;
+        let current = game_constants();
+        let defaults = GameConstants::default();
+        Ok(GameConstants::NAMES
+            .iter()
+            .map(|&name| GameConstantEntry {
+                name: name.to_string(),
+                value: current.get(name).unwrap_or_default(),
+                default_value: defaults.get(name).unwrap_or_default(),
+                override_value: rows
+                    .iter()
+                    .find(|row| row.name == name)
+                    .map(|row| row.value),
+            })
+            .collect())
+    }
+
+    /// Override the constant `name` with `value` and reload.
+    pub async fn set(&self, name: &str, value: f64) -> ArcResult<()> {
+        GameConstants::validate(name, value)?;
+        sqlx::query!(
+            "INSERT INTO game_constants (name, value) VALUES (?, ?)
+             ON DUPLICATE KEY UPDATE value = VALUES(value)",
+            name,
+            value
+        )
+        .execute(&self.pool)
+        .await?;
+        self.reload().await?;
+        Ok(())
+    }
+
+    /// Drop the override of `name`, going back to its default, and reload.
+    pub async fn reset(&self, name: &str) -> ArcResult<()> {
+        if !GameConstants::NAMES.contains(&name) {
+            return Err(ArcError::input(format!("Unknown game constant `{name}`")));
+        }
+        sqlx::query!("DELETE FROM game_constants WHERE name = ?", name)
+            .execute(&self.pool)
+            .await?;
+        self.reload().await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply_game_constant() {
+        let mut constants = GameConstants::default();
+        assert!(constants.apply("course_stamina_cost", 6.0));
+        assert!(constants.apply("world_base_progress", 3.0));
+        assert!(!constants.apply("unknown", 1.0));
+        assert_eq!(constants.course_stamina_cost, 6);
+        assert_eq!(constants.world_base_progress, 3.0);
+        assert_eq!(constants.beyond_rating_progress_multiplier, 0.43);
+    }
+
+    #[test]
+    fn test_every_name_round_trips() {
+        let mut constants = GameConstants::default();
+        for (i, name) in GameConstants::NAMES.iter().enumerate() {
+            let value = i as f64 + 2.0;
+            assert!(constants.apply(name, value), "{name}");
+            assert_eq!(constants.get(name), Some(value), "{name}");
+        }
+        assert_eq!(constants.get("unknown"), None);
+    }
+
+    #[test]
+    fn test_validate_game_constant() {
+        assert!(GameConstants::validate("max_stamina", 20.0).is_ok());
+        assert!(GameConstants::validate("max_stamina", 0.0).is_err());
+        assert!(GameConstants::validate("core_exp", -1.0).is_err());
+        assert!(GameConstants::validate("core_exp", f64::NAN).is_err());
+        assert!(GameConstants::validate("unknown", 1.0).is_err());
+    }
+}
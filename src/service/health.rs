@@ -0,0 +1,206 @@
+//! Liveness and readiness reporting for orchestrator probes.
+
+use crate::service::{AssetManager, BundleService, LinkplayClient};
+use crate::{Database, DbPool};
+use serde::Serialize;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Upper bound for a single dependency check, so a probe never hangs.
+const CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Result of one readiness check
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CheckStatus {
+    Ok,
+    /// The dependency is not configured, so it cannot block readiness.
+    Skipped,
+    Fail,
+}
+
+/// One dependency in a readiness report
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HealthCheck {
+    pub name: &'static str,
+    pub status: CheckStatus,
+    pub latency_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl HealthCheck {
+    fn ok(name: &'static str, started: Instant) -> Self {
+        Self::finish(name, started, CheckStatus::Ok, None)
+    }
+
+    fn fail(name: &'static str, started: Instant, error: impl Into<String>) -> Self {
+        Self::finish(name, started, CheckStatus::Fail, Some(error.into()))
+    }
+
+    fn skipped(name: &'static str) -> Self {
+        Self {
+            name,
+            status: CheckStatus::Skipped,
+            latency_ms: 0,
+            error: None,
+        }
+    }
+
+    /// Check for a readiness flag; `None` means the component is not wired in
+    fn from_flag(name: &'static str, ready: Option<bool>, error: &'static str) -> Self {
+        match ready {
+            None => Self::skipped(name),
+            Some(true) => Self::ok(name, Instant::now()),
+            Some(false) => Self::fail(name, Instant::now(), error),
+        }
+    }
+
+    fn finish(
+        name: &'static str,
+        started: Instant,
+        status: CheckStatus,
+        error: Option<String>,
+    ) -> Self {
+        Self {
+            name,
+            status,
+            latency_ms: started.elapsed().as_millis() as u64,
+            error,
+        }
+    }
+}
+
+/// Body of `/readyz`
+#[derive(Debug, Clone, Serialize)]
+pub struct ReadinessReport {
+    /// `ok` when no check failed, `fail` otherwise.
+    pub status: CheckStatus,
+    pub checks: Vec<HealthCheck>,
+}
+
+impl ReadinessReport {
+    pub fn from_checks(checks: Vec<HealthCheck>) -> Self {
+        let status = if checks.iter().any(|check| check.status == CheckStatus::Fail) {
+            CheckStatus::Fail
+        } else {
+            CheckStatus::Ok
+        };
+        Self { status, checks }
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.status == CheckStatus::Ok
+    }
+}
+
+/// Checks the dependencies the game API needs before it can take traffic
+#[derive(Clone)]
+pub struct HealthService {
+    pool: DbPool,
+    asset_manager: Option<Arc<AssetManager>>,
+    bundle_service: Option<BundleService>,
+    linkplay: Option<LinkplayClient>,
+}
+
+impl HealthService {
+    pub fn new(pool: DbPool) -> Self {
+        Self {
+            pool,
+            asset_manager: None,
+            bundle_service: None,
+            linkplay: None,
+        }
+    }
+
+    pub fn with_asset_manager(mut self, asset_manager: Arc<AssetManager>) -> Self {
+        self.asset_manager = Some(asset_manager);
+        self
+    }
+
+    pub fn with_bundle_service(mut self, bundle_service: BundleService) -> Self {
+        self.bundle_service = Some(bundle_service);
+        self
+    }
+
+    /// Probe the link play server; an unconfigured client is reported as skipped
+    pub fn with_linkplay(mut self, linkplay: LinkplayClient) -> Self {
+        self.linkplay = Some(linkplay);
+        self
+    }
+
+    /// Run every check and collect the results
+    pub async fn readiness(&self) -> ReadinessReport {
+        let (database, linkplay) = tokio::join!(self.check_database(), self.check_linkplay());
+        let checks = vec![
+            database,
+            HealthCheck::from_flag(
+                "assets",
+                self.asset_manager
+                    .as_ref()
+                    .map(|assets| assets.is_initialized()),
+                "asset cache is not initialized",
+            ),
+            HealthCheck::from_flag(
+                "bundles",
+                self.bundle_service
+                    .as_ref()
+                    .map(|bundles| bundles.is_initialized()),
+                "bundle cache is not initialized",
+            ),
+            linkplay,
+        ];
+        ReadinessReport::from_checks(checks)
+    }
+
+    async fn check_database(&self) -> HealthCheck {
+        let started = Instant::now();
+        match tokio::time::timeout(CHECK_TIMEOUT, Database::check_health(&self.pool)).await {
+            Ok(Ok(())) => HealthCheck::ok("database", started),
+            Ok(Err(e)) => HealthCheck::fail("database", started, e.to_string()),
+            Err(_) => HealthCheck::fail("database", started, "timed out"),
+        }
+    }
+
+    async fn check_linkplay(&self) -> HealthCheck {
+        let Some(linkplay) = self.linkplay.as_ref().filter(|c| c.is_available()) else {
+            return HealthCheck::skipped("linkplay");
+        };
+        let started = Instant::now();
+        match linkplay.check_reachable(CHECK_TIMEOUT).await {
+            Ok(()) => HealthCheck::ok("linkplay", started),
+            Err(e) => HealthCheck::fail("linkplay", started, e.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_readiness_status() {
+        let now = Instant::now();
+        let report = ReadinessReport::from_checks(vec![
+            HealthCheck::ok("database", now),
+            HealthCheck::skipped("linkplay"),
+        ]);
+        assert!(report.is_ready());
+
+        let report = ReadinessReport::from_checks(vec![
+            HealthCheck::ok("database", now),
+            HealthCheck::fail("bundles", now, "bundle cache is not initialized"),
+        ]);
+        assert!(!report.is_ready());
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["status"], "fail");
+        assert_eq!(json["checks"][0]["status"], "ok");
+        assert!(json["checks"][0].get("error").is_none());
+        assert_eq!(
+            json["checks"][1]["error"],
+            "bundle cache is not initialized"
+        );
+        assert!(json["checks"][1]["latencyMs"].is_u64());
+    }
+}
@@ -2,18 +2,19 @@ use crate::config::CONFIG;
 use crate::error::{ArcError, ArcResult};
 use crate::model::item::{CharacterMapping, Item, ItemTypes, UserTicket};
 
+use crate::service::user_cache::UserCache;
 use crate::service::UserService;
-use sqlx::{MySql, Pool};
+use crate::DbPool;
 use std::collections::HashMap;
 
 /// Item service for handling item operations
 pub struct ItemService {
-    pool: Pool<MySql>,
+    pool: DbPool,
 }
 
 impl ItemService {
     /// Create a new item service instance
-    pub fn new(pool: Pool<MySql>) -> Self {
+    pub fn new(pool: DbPool) -> Self {
         Self { pool }
     }
 
@@ -142,13 +143,7 @@ impl ItemService {
         let _is_available = match self.select_item(item_id, item_type).await {
             Ok(available) => {
                 if !available {
-                    return Err(ArcError::ItemUnavailable {
-                        message: "The item is unavailable.".to_string(),
-                        error_code: 108,
-                        api_error_code: -122,
-                        extra_data: None,
-                        status: 200,
-                    });
+                    return Err(ArcError::item_not_claimable("The item is unavailable."));
                 }
                 true
             }
@@ -227,13 +222,9 @@ impl ItemService {
 
         if current_amount > 0 {
             if current_amount + amount < 0 {
-                return Err(ArcError::ItemNotEnough {
-                    message: format!("The user does not have enough `{item_id}`."),
-                    error_code: 108,
-                    api_error_code: -122,
-                    extra_data: None,
-                    status: 200,
-                });
+                return Err(ArcError::item_not_enough(format!(
+                    "The user does not have enough `{item_id}`."
+                )));
             }
             sqlx::query!(
                 "UPDATE user_item SET amount = ? WHERE user_id = ? AND item_id = ? AND type = ?",
@@ -264,6 +255,35 @@ impl ItemService {
         Ok(())
     }
 
+    /// Take `amount` of a positive item from the user in one conditional
+    /// update, so concurrent requests cannot spend the same item twice.
+    pub async fn spend_positive_item(
+        &self,
+        user_id: i32,
+        item_id: &str,
+        item_type: &str,
+        amount: i32,
+    ) -> ArcResult<()> {
+        let result = sqlx::query!(
+            "UPDATE user_item SET amount = amount - ?
+             WHERE user_id = ? AND item_id = ? AND type = ? AND amount >= ?",
+            amount,
+            user_id,
+            item_id,
+            item_type,
+            amount
+        )
+        .execute(&self.pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(ArcError::item_not_enough(format!(
+                "The user does not have enough `{item_id}`."
+            )));
+        }
+        Ok(())
+    }
+
     /// Claim core item with reverse option
     pub async fn claim_core_item(
         &self,
@@ -345,6 +365,7 @@ impl ItemService {
                 )
                 .execute(&self.pool)
                 .await?;
+                UserCache::global().invalidate_user(user_id);
             }
             None => {
                 return Err(ArcError::no_data(
@@ -683,7 +704,7 @@ pub struct ItemFactory {
 
 impl ItemFactory {
     /// Create new item factory
-    pub fn new(pool: Pool<MySql>) -> Self {
+    pub fn new(pool: DbPool) -> Self {
         Self {
             service: ItemService::new(pool),
         }
@@ -735,14 +756,14 @@ impl ItemFactory {
 
 /// User item list for managing user's items
 pub struct UserItemList {
-    pool: Pool<MySql>,
+    pool: DbPool,
     user_id: Option<i32>,
     items: Vec<Item>,
 }
 
 impl UserItemList {
     /// Create new user item list
-    pub fn new(pool: Pool<MySql>, user_id: Option<i32>) -> Self {
+    pub fn new(pool: DbPool, user_id: Option<i32>) -> Self {
         Self {
             pool,
             user_id,
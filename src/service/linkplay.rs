@@ -0,0 +1,306 @@
+use crate::error::{ArcError, ArcResult};
+use aes_gcm::aead::generic_array::GenericArray;
+use aes_gcm::aead::{AeadInPlace, KeyInit};
+use aes_gcm::{Aes128Gcm, Nonce};
+use rand::RngCore;
+use serde_json::{json, Value};
+use std::env;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+const LINKPLAY_TIMEOUT_SEC: u64 = 5;
+const IV_LENGTH: usize = 12;
+const TAG_LENGTH: usize = 16;
+
+#[derive(Debug, Clone)]
+struct LinkplayClientConfig {
+    host: String,
+    tcp_port: u16,
+    display_host: String,
+    display_port: u16,
+    authentication: String,
+    tcp_aes_key: [u8; 16],
+}
+
+impl LinkplayClientConfig {
+    fn from_env() -> Self {
+        let host = env::var("LINKPLAY_HOST").unwrap_or_else(|_| "127.0.0.1".to_string());
+        let tcp_port = env::var("LINKPLAY_TCP_PORT")
+            .ok()
+            .and_then(|s| s.parse::<u16>().ok())
+            .unwrap_or(10901);
+        let udp_port = env::var("LINKPLAY_UDP_PORT")
+            .ok()
+            .and_then(|s| s.parse::<u16>().ok())
+            .unwrap_or(10900);
+        let display_host = env::var("LINKPLAY_DISPLAY_HOST").unwrap_or_default();
+        let display_port = env::var("LINKPLAY_DISPLAY_PORT")
+            .ok()
+            .and_then(|s| s.parse::<u16>().ok())
+            .unwrap_or(udp_port);
+        let authentication = env::var("LINKPLAY_AUTHENTICATION")
+            .unwrap_or_else(|_| "my_link_play_server".to_string());
+        let secret =
+            env::var("LINKPLAY_TCP_SECRET_KEY").unwrap_or_else(|_| "1145141919810".to_string());
+
+        Self {
+            host,
+            tcp_port,
+            display_host,
+            display_port,
+            authentication,
+            tcp_aes_key: padded_key_16(&secret),
+        }
+    }
+}
+
+/// Client for the TCP control plane of `linkplayd`
+///
+/// Every request opens a connection and sends
+/// `authentication | len (u64 LE) | iv | tag | AES-128-GCM(json)`, where the
+/// JSON body is `{"endpoint": ..., "data": ...}`; the reply uses the same
+/// framing without the authentication prefix.
+#[derive(Debug, Clone)]
+pub struct LinkplayClient {
+    cfg: LinkplayClientConfig,
+}
+
+impl LinkplayClient {
+    /// Read `LINKPLAY_*` settings from the environment
+    pub fn from_env() -> Self {
+        Self {
+            cfg: LinkplayClientConfig::from_env(),
+        }
+    }
+
+    /// Whether a link play server is configured at all
+    pub fn is_available(&self) -> bool {
+        !self.cfg.host.trim().is_empty()
+    }
+
+    /// UDP port the game client should connect to
+    pub fn display_port(&self) -> u16 {
+        self.cfg.display_port
+    }
+
+    /// Host the game client should connect to
+    ///
+    /// `LINKPLAY_DISPLAY_HOST` wins; otherwise the host the client used to
+    /// reach the HTTP API, and finally `LINKPLAY_HOST`.
+    pub fn endpoint_host(&self, request_host: Option<&str>) -> String {
+        if !self.cfg.display_host.is_empty() {
+            return self.cfg.display_host.clone();
+        }
+
+        if let Some(host) = request_host {
+            let host = host.trim();
+            if !host.is_empty() {
+                // Match Python baseline: request.host.split(':')[0]
+                if let Some(stripped) = host.strip_prefix('[') {
+                    if let Some((ipv6, _)) = stripped.split_once(']') {
+                        return ipv6.to_string();
+                    }
+                }
+                if let Some((name, _)) = host.split_once(':') {
+                    return name.to_string();
+                }
+                return host.to_string();
+            }
+        }
+
+        self.cfg.host.clone()
+    }
+
+    /// Open and drop a control plane connection without sending a request
+    pub async fn check_reachable(&self, timeout: Duration) -> ArcResult<()> {
+        let addr = format!("{}:{}", self.cfg.host, self.cfg.tcp_port);
+        tokio::time::timeout(timeout, TcpStream::connect(addr))
+            .await
+            .map_err(|_| linkplay_error("Timeout when connecting to link play server."))?
+            .map_err(|e| linkplay_error(format!("Link play connection failed: {e}")))?;
+        Ok(())
+    }
+
+    /// Call a control plane endpoint and return the decoded reply
+    ///
+    /// Replies with a non-zero `code` are turned into errors carrying that code.
+    pub async fn request(&self, endpoint: &str, data: Value) -> ArcResult<Value> {
+        let body = json!({
+            "endpoint": endpoint,
+            "data": data
+        });
+        let mut iv = [0u8; IV_LENGTH];
+        rand::thread_rng().fill_bytes(&mut iv);
+        let packet = encode_request(
+            &self.cfg.tcp_aes_key,
+            &self.cfg.authentication,
+            &iv,
+            serde_json::to_vec(&body)?,
+        )?;
+
+        let addr = format!("{}:{}", self.cfg.host, self.cfg.tcp_port);
+        let mut stream = tokio::time::timeout(
+            Duration::from_secs(LINKPLAY_TIMEOUT_SEC),
+            TcpStream::connect(addr),
+        )
+        .await
+        .map_err(|_| linkplay_error("Timeout when connecting to link play server."))?
+        .map_err(|e| linkplay_error(format!("Link play connection failed: {e}")))?;
+
+        tokio::time::timeout(
+            Duration::from_secs(LINKPLAY_TIMEOUT_SEC),
+            stream.write_all(&packet),
+        )
+        .await
+        .map_err(|_| linkplay_error("Timeout when sending to link play server."))?
+        .map_err(|e| linkplay_error(format!("Link play send failed: {e}")))?;
+
+        let mut len_buf = [0u8; 8];
+        tokio::time::timeout(
+            Duration::from_secs(LINKPLAY_TIMEOUT_SEC),
+            stream.read_exact(&mut len_buf),
+        )
+        .await
+        .map_err(|_| linkplay_error("Timeout when waiting for data from link play server."))?
+        .map_err(|e| linkplay_error(format!("Link play read length failed: {e}")))?;
+
+        let cipher_len = u64::from_le_bytes(len_buf) as usize;
+        let mut riv = [0u8; IV_LENGTH];
+        let mut rtag = [0u8; TAG_LENGTH];
+        let mut rcipher = vec![0u8; cipher_len];
+        stream
+            .read_exact(&mut riv)
+            .await
+            .map_err(|e| linkplay_error(format!("Link play read iv failed: {e}")))?;
+        stream
+            .read_exact(&mut rtag)
+            .await
+            .map_err(|e| linkplay_error(format!("Link play read tag failed: {e}")))?;
+        stream
+            .read_exact(&mut rcipher)
+            .await
+            .map_err(|e| linkplay_error(format!("Link play read cipher failed: {e}")))?;
+
+        let plaintext = decrypt_response(&self.cfg.tcp_aes_key, &riv, &rtag, rcipher)?;
+        let recv: Value = serde_json::from_slice(&plaintext)?;
+        let code = recv.get("code").and_then(Value::as_i64).unwrap_or(999) as i32;
+        if code != 0 {
+            return Err(ArcError::linkplay(
+                format!("Link Play error code: {code}"),
+                Some(code),
+            ));
+        }
+        Ok(recv)
+    }
+}
+
+fn linkplay_error<S: Into<String>>(message: S) -> ArcError {
+    ArcError::linkplay(message, None)
+}
+
+/// Encrypt a request body and frame it for `linkplayd`
+fn encode_request(
+    key: &[u8; 16],
+    authentication: &str,
+    iv: &[u8; IV_LENGTH],
+    plaintext: Vec<u8>,
+) -> ArcResult<Vec<u8>> {
+    let cipher = Aes128Gcm::new_from_slice(key)
+        .map_err(|e| ArcError::input(format!("Invalid linkplay key: {e}")))?;
+    let mut ciphertext = plaintext;
+    let tag = cipher
+        .encrypt_in_place_detached(Nonce::from_slice(iv), b"", &mut ciphertext)
+        .map_err(|e| ArcError::input(format!("Failed to encrypt linkplay request: {e}")))?;
+
+    let mut packet =
+        Vec::with_capacity(authentication.len() + 8 + IV_LENGTH + TAG_LENGTH + ciphertext.len());
+    packet.extend_from_slice(authentication.as_bytes());
+    packet.extend_from_slice(&(ciphertext.len() as u64).to_le_bytes());
+    packet.extend_from_slice(iv);
+    packet.extend_from_slice(tag.as_slice());
+    packet.extend_from_slice(&ciphertext);
+    Ok(packet)
+}
+
+/// Decrypt a reply body read from `linkplayd`
+fn decrypt_response(
+    key: &[u8; 16],
+    iv: &[u8; IV_LENGTH],
+    tag: &[u8; TAG_LENGTH],
+    mut ciphertext: Vec<u8>,
+) -> ArcResult<Vec<u8>> {
+    let cipher = Aes128Gcm::new_from_slice(key)
+        .map_err(|e| ArcError::input(format!("Invalid linkplay key: {e}")))?;
+    cipher
+        .decrypt_in_place_detached(
+            Nonce::from_slice(iv),
+            b"",
+            &mut ciphertext,
+            GenericArray::from_slice(tag),
+        )
+        .map_err(|e| linkplay_error(format!("Failed to decrypt link play response: {e}")))?;
+    Ok(ciphertext)
+}
+
+fn padded_key_16(input: &str) -> [u8; 16] {
+    let mut out = [0u8; 16];
+    let bytes = input.as_bytes();
+    let n = bytes.len().min(16);
+    out[..n].copy_from_slice(&bytes[..n]);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_packet_round_trip() {
+        let key = padded_key_16("1145141919810");
+        let iv = [7u8; IV_LENGTH];
+        let body = br#"{"endpoint":"select_room","data":{}}"#.to_vec();
+        let packet = encode_request(&key, "auth", &iv, body.clone()).unwrap();
+
+        let (auth, rest) = packet.split_at(4);
+        assert_eq!(auth, b"auth");
+        let (len, rest) = rest.split_at(8);
+        assert_eq!(
+            u64::from_le_bytes(len.try_into().unwrap()) as usize,
+            body.len()
+        );
+        let (packet_iv, rest) = rest.split_at(IV_LENGTH);
+        let (tag, ciphertext) = rest.split_at(TAG_LENGTH);
+        assert_eq!(packet_iv, iv);
+        assert_ne!(ciphertext, body.as_slice());
+
+        let plaintext = decrypt_response(
+            &key,
+            packet_iv.try_into().unwrap(),
+            tag.try_into().unwrap(),
+            ciphertext.to_vec(),
+        )
+        .unwrap();
+        assert_eq!(plaintext, body);
+    }
+
+    #[test]
+    fn test_decrypt_rejects_wrong_key() {
+        let iv = [1u8; IV_LENGTH];
+        let packet = encode_request(&padded_key_16("right"), "", &iv, b"{}".to_vec()).unwrap();
+        let (tag, ciphertext) = packet[8 + IV_LENGTH..].split_at(TAG_LENGTH);
+        assert!(decrypt_response(
+            &padded_key_16("wrong"),
+            &iv,
+            tag.try_into().unwrap(),
+            ciphertext.to_vec()
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_padded_key_16() {
+        assert_eq!(&padded_key_16("abc")[..4], b"abc\0");
+        assert_eq!(padded_key_16("0123456789abcdefXYZ"), *b"0123456789abcdef");
+    }
+}
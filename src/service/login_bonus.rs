@@ -0,0 +1,424 @@
+use crate::error::{ArcError, ArcResult};
+use crate::model::{LoginBonusItem, LoginBonusStatus, PresentItem};
+use crate::service::runtime_assets::asset_path;
+use crate::service::PresentService;
+use crate::DbPool;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::env;
+use std::path::Path;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+const DAY_MS: i64 = 24 * 60 * 60 * 1000;
+const DEFAULT_EXPIRE_DAYS: i64 = 7;
+const CALENDAR_ASSET: &str = "login_bonus.json";
+
+#[derive(Debug, Clone, Default)]
+pub struct LoginBonusConfig {
+    /// Rewards per consecutive day; the cycle repeats after the last day.
+    pub days: Vec<Vec<LoginBonusItem>>,
+    /// Offset from UTC, in hours, of the daily reset.
+    pub utc_offset_hours: i64,
+    /// How long an unclaimed bonus present stays in the present box.
+    pub expire_days: i64,
+}
+
+/// Layout of `assets/login_bonus.json`.
+#[derive(Debug, Deserialize)]
+struct CalendarFile {
+    #[serde(default)]
+    utc_offset_hours: Option<i64>,
+    #[serde(default)]
+    expire_days: Option<i64>,
+    days: Vec<Vec<CalendarItem>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CalendarItem {
+    #[serde(rename = "type")]
+    item_type: String,
+    /// Defaults to the item type, as for fragments and memories.
+    #[serde(default)]
+    id: Option<String>,
+    amount: i32,
+}
+
+impl LoginBonusConfig {
+    /// Load the reward calendar from `LOGIN_BONUS_CALENDAR` (default
+    /// `assets/login_bonus.json`), then apply the environment overrides.
+    ///
+    /// A missing calendar file leaves the bonus disabled unless
+    /// `LOGIN_BONUS_REWARDS` is set.
+    pub fn load() -> ArcResult<Self> {
+        let path = env::var("LOGIN_BONUS_CALENDAR")
+            .ok()
+            .filter(|value| !value.trim().is_empty())
+            .map(|value| value.trim().into())
+            .unwrap_or_else(|| asset_path(CALENDAR_ASSET));
+        let mut config = if path.exists() {
+            Self::from_file(&path)?
+        } else {
+            Self {
+                expire_days: DEFAULT_EXPIRE_DAYS,
+                ..Self::default()
+            }
+        };
+        config.apply_env()?;
+        Ok(config)
+    }
+
+    fn from_file(path: &Path) -> ArcResult<Self> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| ArcError::input(format!("Failed to read {}: {e}", path.display())))?;
+        Self::from_json(&content)
+            .map_err(|e| ArcError::input(format!("Invalid {}: {e}", path.display())))
+    }
+
+    fn from_json(content: &str) -> ArcResult<Self> {
+        let file: CalendarFile = serde_json::from_str(content)?;
+        let days = file
+            .days
+            .into_iter()
+            .map(|day| {
+                day.into_iter()
+                    .map(|item| {
+                        let item_id = item.id.unwrap_or_else(|| item.item_type.clone());
+                        validate_item(&item.item_type, &item_id, item.amount)
+                    })
+                    .collect()
+            })
+            .collect::<ArcResult<_>>()?;
+
+        Ok(Self {
+            days,
+            utc_offset_hours: valid_utc_offset(file.utc_offset_hours).unwrap_or(0),
+            expire_days: valid_expire_days(file.expire_days).unwrap_or(DEFAULT_EXPIRE_DAYS),
+        })
+    }
+
+    /// Apply `LOGIN_BONUS_REWARDS`, `LOGIN_BONUS_UTC_OFFSET_HOURS` and
+    /// `LOGIN_BONUS_EXPIRE_DAYS` on top of the calendar file.
+    ///
+    /// `LOGIN_BONUS_REWARDS` replaces the whole calendar, e.g.
+    /// `fragment:100;memory:5;core:core_generic:1,fragment:200`. Days are
+    /// separated by `;`, items of a day by `,`. An item is `type:amount`
+    /// (item id = type) or `type:item_id:amount`.
+    fn apply_env(&mut self) -> ArcResult<()> {
+        if let Some(rewards) = env::var("LOGIN_BONUS_REWARDS")
+            .ok()
+            .filter(|value| !value.trim().is_empty())
+        {
+            self.days = parse_rewards(&rewards)?;
+        }
+        let env_i64 = |key| {
+            env::var(key)
+                .ok()
+                .and_then(|value| value.trim().parse::<i64>().ok())
+        };
+        if let Some(hours) = valid_utc_offset(env_i64("LOGIN_BONUS_UTC_OFFSET_HOURS")) {
+            self.utc_offset_hours = hours;
+        }
+        if let Some(days) = valid_expire_days(env_i64("LOGIN_BONUS_EXPIRE_DAYS")) {
+            self.expire_days = days;
+        }
+        Ok(())
+    }
+}
+
+fn valid_utc_offset(hours: Option<i64>) -> Option<i64> {
+    hours.filter(|hours| (-12..=14).contains(hours))
+}
+
+fn valid_expire_days(days: Option<i64>) -> Option<i64> {
+    days.filter(|days| *days > 0)
+}
+
+fn parse_rewards(value: &str) -> ArcResult<Vec<Vec<LoginBonusItem>>> {
+    value
+        .split(';')
+        .map(str::trim)
+        .filter(|day| !day.is_empty())
+        .map(|day| {
+            day.split(',')
+                .map(str::trim)
+                .filter(|item| !item.is_empty())
+                .map(parse_item)
+                .collect()
+        })
+        .collect()
+}
+
+fn parse_item(value: &str) -> ArcResult<LoginBonusItem> {
+    let invalid = || ArcError::input(format!("Invalid login bonus item `{value}`"));
+    let parts = value.split(':').map(str::trim).collect::<Vec<_>>();
+    let (item_type, item_id, amount) = match parts.as_slice() {
+        [item_type, amount] => (*item_type, *item_type, *amount),
+        [item_type, item_id, amount] => (*item_type, *item_id, *amount),
+        _ => return Err(invalid()),
+    };
+    let amount = amount.parse::<i32>().map_err(|_| invalid())?;
+    validate_item(item_type, item_id, amount)
+}
+
+fn validate_item(item_type: &str, item_id: &str, amount: i32) -> ArcResult<LoginBonusItem> {
+    if item_type.is_empty() || item_id.is_empty() || amount <= 0 {
+        return Err(ArcError::input(format!(
+            "Invalid login bonus item `{item_type}:{item_id}:{amount}`"
+        )));
+    }
+
+    Ok(LoginBonusItem {
+        item_type: item_type.to_string(),
+        item_id: item_id.to_string(),
+        amount,
+    })
+}
+
+/// Daily login bonus delivered through the present system.
+///
+/// The first authenticated call of a day advances `login_streak` on the user
+/// row (or resets it after a missed day) and sends that day's rewards as a
+/// present. Users already handled today are remembered in memory.
+#[derive(Clone)]
+pub struct LoginBonusService {
+    pool: DbPool,
+    config: Arc<LoginBonusConfig>,
+    granted_day: Arc<RwLock<HashMap<i32, i64>>>,
+}
+
+impl LoginBonusService {
+    pub fn new(pool: DbPool, config: LoginBonusConfig) -> Self {
+        Self {
+            pool,
+            config: Arc::new(config),
+            granted_day: Arc::new(RwLock::new(HashMap::new())),
+        }
+    }
+
+    pub fn load(pool: DbPool) -> ArcResult<Self> {
+        Ok(Self::new(pool, LoginBonusConfig::load()?))
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.config.days.is_empty()
+    }
+
+    pub fn cycle_length(&self) -> usize {
+        self.config.days.len()
+    }
+
+    fn day_index(&self, now_ms: i64) -> i64 {
+        (now_ms + self.config.utc_offset_hours * 60 * 60 * 1000).div_euclid(DAY_MS)
+    }
+
+    /// Grant today's bonus if the user has not received it yet.
+    ///
+    /// Returns the new streak when a bonus was granted.
+    pub async fn grant_if_due(&self, user_id: i32) -> ArcResult<Option<i32>> {
+        if !self.is_enabled() {
+            return Ok(None);
+        }
+        let now = chrono::Utc::now().timestamp_millis();
+        let today = self.day_index(now);
+        if self.granted_day.read().await.get(&user_id) == Some(&today) {
+            return Ok(None);
+        }
+
+        // The conditional update makes concurrent first calls grant once; the
+        // streak, present and audit row commit together so a failed grant
+        // can be retried on the next login.
+        let mut tx = self.pool.begin().await?;
+        let advanced = sqlx::query!(
+            "UPDATE user
+             SET login_streak = IF(last_login_bonus_day = ?, login_streak + 1, 1),
+                 last_login_bonus_day = ?
+             WHERE user_id = ? AND (last_login_bonus_day IS NULL OR last_login_bonus_day < ?)",
+            today - 1,
+            today,
+            user_id,
+            today
+        )
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+        if advanced == 0 {
+            self.granted_day.write().await.insert(user_id, today);
+            return Ok(None);
+        }
+
+        let streak =
+            sqlx::query_scalar!("SELECT login_streak FROM user WHERE user_id = ?", user_id)
+                .fetch_one(&mut *tx)
+                .await?;
+
+        let day = reward_day(streak, self.config.days.len());
+        let present_id = format!("login_bonus_{user_id}_{today}");
+        let items = self.config.days[day]
+            .iter()
+            .map(|item| PresentItem {
+                present_id: present_id.clone(),
+                item_id: item.item_id.clone(),
+                item_type: item.item_type.clone(),
+                amount: item.amount,
+            })
+            .collect();
+        PresentService::create_present_in(
+            &mut tx,
+            &present_id,
+            Some(now + self.config.expire_days * DAY_MS),
+            &format!("Daily login bonus (day {streak})"),
+            items,
+            user_id,
+        )
+        .await?;
+        sqlx::query!(
+            "INSERT IGNORE INTO login_bonus (user_id, bonus_day, streak, present_id, granted_at)
+             VALUES (?, ?, ?, ?, ?)",
+            user_id,
+            today,
+            streak,
+            present_id,
+            now
+        )
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        self.granted_day.write().await.insert(user_id, today);
+
+        Ok(Some(streak))
+    }
+
+    /// Current streak and the rewards of today's bonus, `None` when the
+    /// bonus is disabled.
+    pub async fn status(&self, user_id: i32) -> ArcResult<Option<LoginBonusStatus>> {
+        if !self.is_enabled() {
+            return Ok(None);
+        }
+        let row = sqlx::query!(
+            "SELECT login_streak, last_login_bonus_day FROM user WHERE user_id = ?",
+            user_id
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+        let Some(row) = row else {
+            return Ok(None);
+        };
+
+        let today = self.day_index(chrono::Utc::now().timestamp_millis());
+        Ok(Some(status_for(
+            &self.config,
+            row.login_streak,
+            row.last_login_bonus_day.map(i64::from),
+            today,
+        )))
+    }
+}
+
+fn status_for(
+    config: &LoginBonusConfig,
+    streak: i32,
+    last_day: Option<i64>,
+    today: i64,
+) -> LoginBonusStatus {
+    let claimed_today = last_day == Some(today);
+    // Before today's grant, show the day the next grant will land on.
+    let streak = match last_day {
+        Some(day) if day == today => streak,
+        Some(day) if day == today - 1 => streak + 1,
+        _ => 1,
+    };
+    let day = reward_day(streak, config.days.len());
+
+    LoginBonusStatus {
+        streak,
+        day: day + 1,
+        cycle_length: config.days.len(),
+        claimed_today,
+        rewards: config.days[day].clone(),
+    }
+}
+
+/// Zero-based reward day for a one-based streak.
+fn reward_day(streak: i32, cycle_length: usize) -> usize {
+    (streak.max(1) as usize - 1) % cycle_length
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_rewards() {
+        let days =
+            parse_rewards("fragment:100; memory:5 ;core:core_generic:1, fragment:200;").unwrap();
+        assert_eq!(days.len(), 3);
+        assert_eq!(
+            days[0],
+            vec![LoginBonusItem {
+                item_type: "fragment".to_string(),
+                item_id: "fragment".to_string(),
+                amount: 100,
+            }]
+        );
+        assert_eq!(days[2].len(), 2);
+        assert_eq!(days[2][0].item_id, "core_generic");
+
+        assert!(parse_rewards("").unwrap().is_empty());
+        assert!(parse_rewards("fragment").is_err());
+        assert!(parse_rewards("fragment:-1").is_err());
+        assert!(parse_rewards("a:b:c:1").is_err());
+    }
+
+    #[test]
+    fn test_calendar_from_json() {
+        let config = LoginBonusConfig::from_json(
+            r#"{
+                "utc_offset_hours": 9,
+                "days": [
+                    [{ "type": "fragment", "amount": 100 }],
+                    [{ "type": "core", "id": "core_generic", "amount": 1 }]
+                ]
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(config.utc_offset_hours, 9);
+        assert_eq!(config.expire_days, DEFAULT_EXPIRE_DAYS);
+        assert_eq!(config.days[0][0].item_id, "fragment");
+        assert_eq!(config.days[1][0].item_id, "core_generic");
+
+        assert!(LoginBonusConfig::from_json(
+            r#"{"days": [[{ "type": "fragment", "amount": 0 }]]}"#
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_status_for_streak() {
+        let config = LoginBonusConfig {
+            days: parse_rewards("fragment:100;memory:5;fragment:300").unwrap(),
+            ..LoginBonusConfig::default()
+        };
+
+        let claimed = status_for(&config, 3, Some(10), 10);
+        assert!(claimed.claimed_today);
+        assert_eq!((claimed.streak, claimed.day), (3, 3));
+
+        let due = status_for(&config, 3, Some(9), 10);
+        assert!(!due.claimed_today);
+        assert_eq!((due.streak, due.day), (4, 1));
+        assert_eq!(due.rewards[0].item_id, "fragment");
+
+        let broken = status_for(&config, 3, Some(5), 10);
+        assert_eq!((broken.streak, broken.day), (1, 1));
+        assert_eq!(status_for(&config, 0, None, 10).streak, 1);
+    }
+
+    #[test]
+    fn test_reward_day_cycles() {
+        assert_eq!(reward_day(1, 3), 0);
+        assert_eq!(reward_day(3, 3), 2);
+        assert_eq!(reward_day(4, 3), 0);
+        assert_eq!(reward_day(0, 3), 0);
+    }
+}
@@ -1,46 +1,116 @@
+pub mod access;
+pub mod achievement;
 pub mod aggregate;
+pub mod anomaly;
+pub mod api_log;
+pub mod api_version;
 pub mod arc_data;
 pub mod asset_init;
 pub mod asset_manager;
 pub mod bundle;
 pub mod cache;
+pub mod captcha;
+pub mod cdn;
 pub mod character;
+pub mod chart_analytics;
+pub mod client_version;
 pub mod course;
 pub mod download;
+pub mod email;
+pub mod event;
+pub mod federation;
+pub mod game_constants;
+pub mod health;
 pub mod item;
+pub mod linkplay;
+pub mod login_bonus;
 pub mod mission;
 pub mod multiplayer;
 pub mod notification;
 pub mod operations;
+pub mod ownership;
+pub mod password;
 pub mod present;
+pub mod profile;
+pub mod ptt_history;
 pub mod purchase;
+pub mod push;
+pub mod python_import;
+pub mod replay;
 pub mod runtime_assets;
+pub mod save;
 pub mod score;
 pub mod score_image;
+pub mod score_import;
+pub mod score_queue;
+pub mod score_validator;
+pub mod song_info;
+pub mod song_package;
+pub mod stamina;
 pub mod storage;
+pub mod tos;
+pub mod unlock_bitmap;
 pub mod user;
+pub mod user_cache;
+pub mod user_export;
+pub mod verification;
+pub mod web_link;
+pub mod webhook;
 pub mod world;
 
 // Re-export commonly used service types for convenience
+pub use achievement::AchievementService;
+pub use anomaly::AnomalyService;
+pub use api_log::ApiLogService;
 pub use asset_init::AssetInitService;
 pub use asset_manager::AssetManager;
 pub use bundle::BundleService;
 pub use cache::CacheService;
+pub use captcha::CaptchaService;
+pub use cdn::CdnRegions;
 pub use character::CharacterService;
+pub use chart_analytics::ChartAnalyticsService;
 pub use course::CourseService;
 pub use download::DownloadService;
+pub use email::EmailService;
+pub use event::EventService;
+pub use federation::FederationService;
+pub use game_constants::{GameConstantEntry, GameConstants, GameConstantsService};
+pub use health::HealthService;
 pub use item::{ItemFactory, ItemService, UserItemList};
+pub use linkplay::LinkplayClient;
+pub use login_bonus::LoginBonusService;
 pub use mission::MissionService;
 pub use multiplayer::{MatchmakingJoinRequest, MultiplayerService, MultiplayerUpdateRequest};
-pub use notification::NotificationService;
+pub use notification::{LiveNotification, NotificationHub, NotificationService};
 pub use operations::OperationManager;
+pub use ownership::OwnershipService;
+pub use password::{PasswordCheck, PasswordHasher};
 pub use present::PresentService;
+pub use profile::ProfileService;
+pub use ptt_history::PttHistoryService;
 pub use purchase::PurchaseService;
+pub use push::PushGateway;
+pub use python_import::PythonImportService;
+pub use replay::ReplayService;
+pub use save::SaveService;
 pub use score::ScoreService;
 pub use score_image::{
     generate_score_image_png, generate_score_images, parse_score_image_mode, GeneratedScoreImage,
     ScoreImageMode,
 };
-pub use storage::StorageService;
+pub use score_import::ScoreImportService;
+pub use score_queue::ScoreWriteQueue;
+pub use score_validator::ScoreValidator;
+pub use song_info::SongInfoService;
+pub use stamina::{StaminaImpl, StaminaService};
+pub use storage::{AssetStorage, LocalStorage, S3Storage, StorageService};
+pub use tos::TosService;
+pub use unlock_bitmap::UnlockBitmapService;
 pub use user::UserService;
+pub use user_cache::UserCache;
+pub use user_export::UserExportService;
+pub use verification::VerificationService;
+pub use web_link::WebLinkService;
+pub use webhook::{WebhookEvent, WebhookNotifier};
 pub use world::WorldService;
@@ -1,100 +1,31 @@
-use crate::error::{ArcError, ArcResult};
+use crate::error::{ArcError, ArcResult, ErrorKind};
+use crate::service::linkplay::LinkplayClient;
+use crate::service::unlock_bitmap::{client_song_unlock, LINKPLAY_UNLOCK_LENGTH};
+use crate::service::UserService;
 use crate::DbPool;
-use aes_gcm::aead::{AeadInPlace, KeyInit};
-use aes_gcm::{Aes128Gcm, Nonce};
 use base64::engine::general_purpose::STANDARD as BASE64;
 use base64::Engine;
-use rand::RngCore;
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 use std::collections::HashMap;
-use std::env;
 use std::sync::Arc;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use tokio::net::TcpStream;
 use tokio::sync::Mutex;
 
-const LINKPLAY_TIMEOUT_SEC: u64 = 5;
-const LINKPLAY_UNLOCK_LENGTH: usize = 1024;
 const LINKPLAY_MATCH_GET_ROOMS_INTERVAL_SEC: i64 = 4;
 const LINKPLAY_MATCH_TIMEOUT_SEC: i64 = 15;
 const LINKPLAY_MATCH_MEMORY_CLEAN_INTERVAL_SEC: i64 = 60;
 const LINKPLAY_MATCH_PTT_ABS: [i32; 8] = [5, 20, 50, 100, 200, 500, 1000, 2000];
 const LINKPLAY_MATCH_UNLOCK_MIN: [i32; 8] = [1000, 800, 500, 300, 200, 100, 50, 1];
 
-#[derive(Debug, Clone)]
-struct LinkplayClientConfig {
-    host: String,
-    tcp_port: u16,
-    display_host: String,
-    display_port: u16,
-    authentication: String,
-    tcp_aes_key: [u8; 16],
-}
-
-impl LinkplayClientConfig {
-    fn from_env() -> Self {
-        let host = env::var("LINKPLAY_HOST").unwrap_or_else(|_| "127.0.0.1".to_string());
-        let tcp_port = env::var("LINKPLAY_TCP_PORT")
-            .ok()
-            .and_then(|s| s.parse::<u16>().ok())
-            .unwrap_or(10901);
-        let udp_port = env::var("LINKPLAY_UDP_PORT")
-            .ok()
-            .and_then(|s| s.parse::<u16>().ok())
-            .unwrap_or(10900);
-        let display_host = env::var("LINKPLAY_DISPLAY_HOST").unwrap_or_default();
-        let display_port = env::var("LINKPLAY_DISPLAY_PORT")
-            .ok()
-            .and_then(|s| s.parse::<u16>().ok())
-            .unwrap_or(udp_port);
-        let authentication = env::var("LINKPLAY_AUTHENTICATION")
-            .unwrap_or_else(|_| "my_link_play_server".to_string());
-        let secret =
-            env::var("LINKPLAY_TCP_SECRET_KEY").unwrap_or_else(|_| "1145141919810".to_string());
-
-        Self {
-            host,
-            tcp_port,
-            display_host,
-            display_port,
-            authentication,
-            tcp_aes_key: padded_key_16(&secret),
-        }
-    }
-
-    fn endpoint_host(&self, request_host: Option<&str>) -> String {
-        if !self.display_host.is_empty() {
-            return self.display_host.clone();
-        }
-
-        if let Some(host) = request_host {
-            let host = host.trim();
-            if !host.is_empty() {
-                // Match Python baseline: request.host.split(':')[0]
-                if let Some(stripped) = host.strip_prefix('[') {
-                    if let Some((ipv6, _)) = stripped.split_once(']') {
-                        return ipv6.to_string();
-                    }
-                }
-                if let Some((name, _)) = host.split_once(':') {
-                    return name.to_string();
-                }
-                return host.to_string();
-            }
-        }
-
-        self.host.clone()
-    }
-}
-
 #[derive(Debug, Clone)]
 struct MatchPlayer {
     user_id: i32,
     name: String,
     rating_ptt: i32,
     is_hide_rating: bool,
+    /// Shadow banned players wait in the queue but are never matched.
+    is_shadow_banned: bool,
     song_unlock: Vec<u8>,
     last_match_timestamp_sec: i64,
     match_times: i32,
@@ -131,7 +62,7 @@ struct MatchStoreState {
 #[derive(Debug, Clone)]
 pub struct MultiplayerService {
     pool: DbPool,
-    cfg: LinkplayClientConfig,
+    client: LinkplayClient,
     state: Arc<Mutex<MatchStoreState>>,
 }
 
@@ -139,7 +70,7 @@ impl MultiplayerService {
     pub fn new(pool: DbPool) -> Self {
         Self {
             pool,
-            cfg: LinkplayClientConfig::from_env(),
+            client: LinkplayClient::from_env(),
             state: Arc::new(Mutex::new(MatchStoreState::default())),
         }
     }
@@ -151,7 +82,7 @@ impl MultiplayerService {
         request_host: Option<&str>,
     ) -> ArcResult<Value> {
         self.ensure_linkplay_available()?;
-        let song_unlock = get_song_unlock(client_song_map);
+        let song_unlock = client_song_unlock(client_song_map);
         let (name, rating_ptt, is_hide_rating) = self.select_user_about_link_play(user_id).await?;
 
         let mut result = self
@@ -176,7 +107,7 @@ impl MultiplayerService {
         request_host: Option<&str>,
     ) -> ArcResult<Value> {
         self.ensure_linkplay_available()?;
-        let song_unlock = get_song_unlock(client_song_map);
+        let song_unlock = client_song_unlock(client_song_map);
         let (name, rating_ptt, is_hide_rating) = self.select_user_about_link_play(user_id).await?;
 
         let mut result = self
@@ -247,8 +178,11 @@ impl MultiplayerService {
         request_host: Option<&str>,
     ) -> ArcResult<Value> {
         self.ensure_linkplay_available()?;
-        let song_unlock = get_song_unlock(client_song_map);
+        let song_unlock = client_song_unlock(client_song_map);
         let (name, rating_ptt, is_hide_rating) = self.select_user_about_link_play(user_id).await?;
+        let is_shadow_banned = UserService::new(self.pool.clone())
+            .is_shadow_banned(user_id)
+            .await?;
 
         {
             let mut state = self.state.lock().await;
@@ -259,6 +193,7 @@ impl MultiplayerService {
                     name,
                     rating_ptt,
                     is_hide_rating,
+                    is_shadow_banned,
                     song_unlock,
                     last_match_timestamp_sec: now_sec(),
                     match_times: 0,
@@ -314,13 +249,8 @@ impl MultiplayerService {
         let mut user = match user {
             Some(u) => u,
             None => {
-                return Err(ArcError::Base {
-                    message: format!("User `{user_id}` not found in match queue."),
-                    error_code: 999,
-                    api_error_code: -999,
-                    extra_data: None,
-                    status: 200,
-                })
+                return Err(ErrorKind::NotInMatchQueue
+                    .error(format!("User `{user_id}` not found in match queue.")))
             }
         };
 
@@ -342,6 +272,14 @@ impl MultiplayerService {
             return Ok(Some(joined));
         }
 
+        if user.is_shadow_banned {
+            let mut state = self.state.lock().await;
+            if let Some(u) = state.player_queue.get_mut(&user_id) {
+                u.last_match_timestamp_sec = now_sec();
+            }
+            return Ok(None);
+        }
+
         self.refresh_rooms().await?;
 
         let (rule, ptt_abs, unlock_min, room_cache) = {
@@ -405,6 +343,7 @@ impl MultiplayerService {
             let mut found: Option<i32> = None;
             for p in state.player_queue.values() {
                 if p.user_id == user_id
+                    || p.is_shadow_banned
                     || now - p.last_match_timestamp_sec > LINKPLAY_MATCH_TIMEOUT_SEC
                 {
                     continue;
@@ -526,7 +465,7 @@ impl MultiplayerService {
             data["match_times"] = Value::Number(serde_json::Number::from(v));
         }
 
-        let r = self.remote_request("create_room", data).await?;
+        let r = self.client.request("create_room", data).await?;
         let rd = r
             .get("data")
             .ok_or_else(|| ArcError::input("Missing data from link play server"))?;
@@ -580,7 +519,7 @@ impl MultiplayerService {
             data["match_times"] = Value::Number(serde_json::Number::from(v));
         }
 
-        let r = self.remote_request("join_room", data).await?;
+        let r = self.client.request("join_room", data).await?;
         let rd = r
             .get("data")
             .ok_or_else(|| ArcError::input("Missing data from link play server"))?;
@@ -625,7 +564,8 @@ impl MultiplayerService {
         user_id: i32,
     ) -> ArcResult<Value> {
         let r = self
-            .remote_request(
+            .client
+            .request(
                 "update_room",
                 json!({
                     "token": token,
@@ -675,7 +615,8 @@ impl MultiplayerService {
         share_token: Option<&str>,
     ) -> ArcResult<Value> {
         let r = self
-            .remote_request(
+            .client
+            .request(
                 "select_room",
                 json!({
                     "room_code": room_code,
@@ -691,7 +632,8 @@ impl MultiplayerService {
 
     async fn remote_get_match_rooms(&self, limit: i32) -> ArcResult<Vec<MatchRoomCache>> {
         let r = self
-            .remote_request("get_match_rooms", json!({ "limit": limit }))
+            .client
+            .request("get_match_rooms", json!({ "limit": limit }))
             .await?;
 
         let rd = r
@@ -743,197 +685,19 @@ impl MultiplayerService {
         Ok(out)
     }
 
-    async fn remote_request(&self, endpoint: &str, data: Value) -> ArcResult<Value> {
-        let body = json!({
-            "endpoint": endpoint,
-            "data": data
-        });
-        let plaintext = serde_json::to_vec(&body)?;
-
-        let mut iv = [0u8; 12];
-        rand::thread_rng().fill_bytes(&mut iv);
-
-        let cipher = Aes128Gcm::new_from_slice(&self.cfg.tcp_aes_key)
-            .map_err(|e| ArcError::input(format!("Invalid linkplay key: {e}")))?;
-        let nonce = Nonce::from_slice(&iv);
-        let mut ciphertext = plaintext;
-        let tag = cipher
-            .encrypt_in_place_detached(nonce, b"", &mut ciphertext)
-            .map_err(|e| ArcError::input(format!("Failed to encrypt linkplay request: {e}")))?;
-
-        let mut packet =
-            Vec::with_capacity(self.cfg.authentication.len() + 8 + 12 + 16 + ciphertext.len());
-        packet.extend_from_slice(self.cfg.authentication.as_bytes());
-        packet.extend_from_slice(&(ciphertext.len() as u64).to_le_bytes());
-        packet.extend_from_slice(&iv);
-        packet.extend_from_slice(tag.as_slice());
-        packet.extend_from_slice(&ciphertext);
-
-        let addr = format!("{}:{}", self.cfg.host, self.cfg.tcp_port);
-        let mut stream = tokio::time::timeout(
-            Duration::from_secs(LINKPLAY_TIMEOUT_SEC),
-            TcpStream::connect(addr),
-        )
-        .await
-        .map_err(|_| ArcError::Base {
-            message: "Timeout when connecting to link play server.".to_string(),
-            error_code: 108,
-            api_error_code: -999,
-            extra_data: None,
-            status: 400,
-        })?
-        .map_err(|e| ArcError::Base {
-            message: format!("Link play connection failed: {e}"),
-            error_code: 108,
-            api_error_code: -999,
-            extra_data: None,
-            status: 400,
-        })?;
-
-        tokio::time::timeout(
-            Duration::from_secs(LINKPLAY_TIMEOUT_SEC),
-            stream.write_all(&packet),
-        )
-        .await
-        .map_err(|_| ArcError::Base {
-            message: "Timeout when sending to link play server.".to_string(),
-            error_code: 108,
-            api_error_code: -999,
-            extra_data: None,
-            status: 400,
-        })?
-        .map_err(|e| ArcError::Base {
-            message: format!("Link play send failed: {e}"),
-            error_code: 108,
-            api_error_code: -999,
-            extra_data: None,
-            status: 400,
-        })?;
-
-        let mut len_buf = [0u8; 8];
-        tokio::time::timeout(
-            Duration::from_secs(LINKPLAY_TIMEOUT_SEC),
-            stream.read_exact(&mut len_buf),
-        )
-        .await
-        .map_err(|_| ArcError::Base {
-            message: "Timeout when waiting for data from link play server.".to_string(),
-            error_code: 108,
-            api_error_code: -999,
-            extra_data: None,
-            status: 400,
-        })?
-        .map_err(|e| ArcError::Base {
-            message: format!("Link play read length failed: {e}"),
-            error_code: 108,
-            api_error_code: -999,
-            extra_data: None,
-            status: 400,
-        })?;
-
-        let cipher_len = u64::from_le_bytes(len_buf) as usize;
-        let mut riv = [0u8; 12];
-        let mut rtag = [0u8; 16];
-        let mut rcipher = vec![0u8; cipher_len];
-
-        stream
-            .read_exact(&mut riv)
-            .await
-            .map_err(|e| ArcError::Base {
-                message: format!("Link play read iv failed: {e}"),
-                error_code: 108,
-                api_error_code: -999,
-                extra_data: None,
-                status: 400,
-            })?;
-        stream
-            .read_exact(&mut rtag)
-            .await
-            .map_err(|e| ArcError::Base {
-                message: format!("Link play read tag failed: {e}"),
-                error_code: 108,
-                api_error_code: -999,
-                extra_data: None,
-                status: 400,
-            })?;
-        stream
-            .read_exact(&mut rcipher)
-            .await
-            .map_err(|e| ArcError::Base {
-                message: format!("Link play read cipher failed: {e}"),
-                error_code: 108,
-                api_error_code: -999,
-                extra_data: None,
-                status: 400,
-            })?;
-
-        let nonce = Nonce::from_slice(&riv);
-        let tag = aes_gcm::aead::generic_array::GenericArray::from_slice(&rtag);
-        cipher
-            .decrypt_in_place_detached(nonce, b"", &mut rcipher, tag)
-            .map_err(|e| ArcError::Base {
-                message: format!("Failed to decrypt link play response: {e}"),
-                error_code: 108,
-                api_error_code: -999,
-                extra_data: None,
-                status: 400,
-            })?;
-
-        let recv: Value = serde_json::from_slice(&rcipher)?;
-        let code = recv.get("code").and_then(Value::as_i64).unwrap_or(999) as i32;
-        if code != 0 {
-            return Err(ArcError::Base {
-                message: format!("Link Play error code: {code}"),
-                error_code: code,
-                api_error_code: -999,
-                extra_data: None,
-                status: 400,
-            });
-        }
-        Ok(recv)
-    }
-
     fn ensure_linkplay_available(&self) -> ArcResult<()> {
-        if self.cfg.host.trim().is_empty() {
-            return Err(ArcError::Base {
-                message: "The link play server is unavailable.".to_string(),
-                error_code: 151,
-                api_error_code: -999,
-                extra_data: None,
-                status: 404,
-            });
+        if !self.client.is_available() {
+            return Err(ArcError::not_found("The link play server is unavailable."));
         }
         Ok(())
     }
 
     fn add_endpoint_and_port(&self, value: &mut Value, request_host: Option<&str>) {
-        value["endPoint"] = Value::String(self.cfg.endpoint_host(request_host));
-        value["port"] = Value::Number(serde_json::Number::from(self.cfg.display_port));
+        value["endPoint"] = Value::String(self.client.endpoint_host(request_host));
+        value["port"] = Value::Number(serde_json::Number::from(self.client.display_port()));
     }
 }
 
-fn get_song_unlock(client_song_map: &HashMap<String, Vec<bool>>) -> Vec<u8> {
-    let mut unlock = vec![0u8; LINKPLAY_UNLOCK_LENGTH];
-    for (k, v) in client_song_map {
-        let song_idx = match k.parse::<usize>() {
-            Ok(n) => n,
-            Err(_) => continue,
-        };
-        for i in 0..5usize {
-            let enabled = v.get(i).copied().unwrap_or(false);
-            if !enabled {
-                continue;
-            }
-            let index = song_idx * 5 + i;
-            if index >= LINKPLAY_UNLOCK_LENGTH * 8 {
-                continue;
-            }
-            unlock[index / 8] |= 1 << (index % 8);
-        }
-    }
-    unlock
-}
-
 fn calc_available_chart_num(a: &[u8], b: &[u8]) -> i32 {
     a.iter()
         .zip(b.iter())
@@ -950,14 +714,6 @@ fn normalize_unlock(mut unlock: Vec<u8>, unlock_len: usize) -> Vec<u8> {
     unlock
 }
 
-fn padded_key_16(input: &str) -> [u8; 16] {
-    let mut out = [0u8; 16];
-    let bytes = input.as_bytes();
-    let n = bytes.len().min(16);
-    out[..n].copy_from_slice(&bytes[..n]);
-    out
-}
-
 fn now_sec() -> i64 {
     SystemTime::now()
         .duration_since(UNIX_EPOCH)
@@ -1,15 +1,154 @@
-use crate::error::ArcResult;
+use crate::error::{ArcError, ArcResult};
+use crate::i18n::notification_text;
 use crate::model::{NewNotification, Notification, NotificationResponse, RoomInviteNotification};
-use sqlx::MySqlPool;
+use crate::service::push::{
+    PushGateway, PushMessage, PushPlatform, PushTarget, MAX_PUSH_TOKENS_PER_USER,
+};
+use crate::utils::current_timestamp_ms;
+use crate::DbPool;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use tokio::sync::broadcast;
+
+/// Events buffered per connected client before the slowest one starts
+/// missing them.
+const LIVE_CHANNEL_CAPACITY: usize = 16;
+
+static NOTIFICATION_HUB: OnceLock<NotificationHub> = OnceLock::new();
+
+/// A notification pushed to clients connected to the notification stream
+#[derive(Debug, Clone)]
+pub struct LiveNotification {
+    /// Event name: the notification type, e.g. `room_inv` or `present`
+    pub event: String,
+    pub data: Value,
+}
+
+/// Registry of clients connected to the notification stream: one broadcast
+/// channel per user plus one for notifications addressed to everybody
+///
+/// Shared by every `NotificationService`, so a notification raised by any
+/// service instance reaches the connection of its receiver.
+pub struct NotificationHub {
+    users: Mutex<HashMap<i32, broadcast::Sender<LiveNotification>>>,
+    everyone: broadcast::Sender<LiveNotification>,
+}
+
+impl NotificationHub {
+    pub fn new() -> Self {
+        Self {
+            users: Mutex::new(HashMap::new()),
+            everyone: broadcast::channel(LIVE_CHANNEL_CAPACITY).0,
+        }
+    }
+
+    pub fn global() -> &'static NotificationHub {
+        NOTIFICATION_HUB.get_or_init(Self::new)
+    }
+
+    /// Start receiving the live notifications of `user_id`
+    pub fn subscribe(&self, user_id: i32) -> LiveSubscription {
+        let mut users = lock(&self.users);
+        users.retain(|_, sender| sender.receiver_count() > 0);
+        let user = users
+            .entry(user_id)
+            .or_insert_with(|| broadcast::channel(LIVE_CHANNEL_CAPACITY).0)
+            .subscribe();
+        LiveSubscription {
+            user,
+            everyone: self.everyone.subscribe(),
+        }
+    }
+
+    pub fn has_subscribers(&self, target: PushTarget) -> bool {
+        match target {
+            PushTarget::User(user_id) => lock(&self.users)
+                .get(&user_id)
+                .is_some_and(|sender| sender.receiver_count() > 0),
+            PushTarget::AllUsers => self.everyone.receiver_count() > 0,
+        }
+    }
+
+    /// Send to the connected clients of `target`; returns whether any client
+    /// received it
+    pub fn publish(&self, target: PushTarget, notification: LiveNotification) -> bool {
+        match target {
+            PushTarget::User(user_id) => {
+                let mut users = lock(&self.users);
+                let Some(sender) = users.get(&user_id) else {
+                    return false;
+                };
+                let delivered = sender.send(notification).is_ok();
+                if !delivered {
+                    users.remove(&user_id);
+                }
+                delivered
+            }
+            PushTarget::AllUsers => self.everyone.send(notification).is_ok(),
+        }
+    }
+}
+
+impl Default for NotificationHub {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Live notifications of one connected client
+pub struct LiveSubscription {
+    user: broadcast::Receiver<LiveNotification>,
+    everyone: broadcast::Receiver<LiveNotification>,
+}
+
+impl LiveSubscription {
+    /// Wait for the next notification. Events the client fell behind on are
+    /// skipped; it can poll `/notification/me` to catch up.
+    pub async fn recv(&mut self) -> Option<LiveNotification> {
+        loop {
+            let received = tokio::select! {
+                received = self.user.recv() => received,
+                received = self.everyone.recv() => received,
+            };
+            match received {
+                Ok(notification) => return Some(notification),
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    log::warn!("Notification stream lagged, skipped {skipped} events");
+                }
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    match mutex.lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner(),
+    }
+}
 
 pub struct NotificationService {
-    pool: MySqlPool,
+    pool: DbPool,
+    push: Option<PushGateway>,
+    hub: &'static NotificationHub,
 }
 
 impl NotificationService {
     /// Create a new notification service
-    pub fn new(pool: MySqlPool) -> Self {
-        Self { pool }
+    pub fn new(pool: DbPool) -> Self {
+        Self {
+            pool,
+            push: None,
+            hub: NotificationHub::global(),
+        }
+    }
+
+    /// Also alert registered companion app devices through FCM/APNs
+    pub fn with_push(mut self, push: PushGateway) -> Self {
+        self.push = Some(push);
+        self
     }
 
     /// Get all notifications for a user and mark them as read (delete them)
@@ -69,7 +208,8 @@ impl NotificationService {
         Ok(responses)
     }
 
-    /// Insert a new room invite notification
+    /// Deliver a room invite, live to a connected receiver or stored for
+    /// `/notification/me` otherwise
     pub async fn insert_room_invite_notification(
         &self,
         notification: &RoomInviteNotification,
@@ -86,6 +226,33 @@ impl NotificationService {
             return Ok(());
         }
 
+        let message = PushMessage::new(
+            notification_text("room_invite_title", &[]),
+            notification_text(
+                "room_invite_body",
+                &[("sender", notification.sender_name.as_str())],
+            ),
+        )
+        .with_data("type", "room_inv")
+        .with_data("share_token", notification.share_token.clone());
+        let live = NotificationResponse {
+            sender: notification.sender_name.clone(),
+            notification_type: "room_inv".to_string(),
+            share_token: notification.share_token.clone(),
+            send_ts: notification.timestamp,
+        };
+        let delivered = self.hub.publish(
+            PushTarget::User(notification.receiver_id),
+            LiveNotification {
+                event: "room_inv".to_string(),
+                data: json!(live),
+            },
+        );
+        if delivered {
+            self.push(PushTarget::User(notification.receiver_id), message);
+            return Ok(());
+        }
+
         // Get the next notification ID for this user
         let max_id = sqlx::query_scalar!(
             "SELECT MAX(id) FROM notification WHERE user_id = ?",
@@ -111,6 +278,8 @@ impl NotificationService {
         .execute(&self.pool)
         .await?;
 
+        self.push(PushTarget::User(notification.receiver_id), message);
+
         Ok(())
     }
 
@@ -186,6 +355,41 @@ impl NotificationService {
         Ok(count)
     }
 
+    /// Send a link play room invite from one friend to another
+    ///
+    /// The receiver must be on the sender's friend list; the share token is
+    /// the one returned by the link play server for the sender's room.
+    pub async fn send_room_invite(
+        &self,
+        sender_id: i32,
+        sender_name: String,
+        receiver_id: i32,
+        share_token: String,
+    ) -> ArcResult<()> {
+        if sender_id == receiver_id {
+            return Err(ArcError::input("Cannot invite yourself."));
+        }
+
+        let is_friend = sqlx::query_scalar!(
+            "SELECT EXISTS(SELECT 1 FROM friend WHERE user_id_me = ? AND user_id_other = ?) as `exists!: i64`",
+            sender_id,
+            receiver_id
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        if is_friend == 0 {
+            return Err(ArcError::friend(
+                "No user or the user is not your friend.",
+                401,
+                -1,
+            ));
+        }
+
+        self.create_room_invite(sender_id, sender_name, receiver_id, share_token)
+            .await
+    }
+
     /// Create a room invite notification and insert it
     pub async fn create_room_invite(
         &self,
@@ -198,4 +402,176 @@ impl NotificationService {
             RoomInviteNotification::new(sender_id, sender_name, receiver_id, share_token);
         self.insert_room_invite_notification(&notification).await
     }
+
+    /// Alert the receivers of a newly delivered present that it can be
+    /// claimed, on the notification stream and companion app devices
+    pub async fn notify_present_delivered(
+        &self,
+        target: PushTarget,
+        present_id: &str,
+    ) -> ArcResult<()> {
+        if self.push.is_none() && !self.hub.has_subscribers(target) {
+            return Ok(());
+        }
+
+        let description = sqlx::query_scalar!(
+            "SELECT description FROM present WHERE present_id = ?",
+            present_id
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .flatten()
+        .filter(|description| !description.trim().is_empty())
+        .unwrap_or_else(|| notification_text("present_body", &[]));
+
+        let message = PushMessage::new(notification_text("present_title", &[]), description)
+            .with_data("type", "present")
+            .with_data("present_id", present_id);
+        self.hub.publish(
+            target,
+            LiveNotification {
+                event: "present".to_string(),
+                data: json!({
+                    "title": message.title,
+                    "body": message.body,
+                    "present_id": present_id,
+                }),
+            },
+        );
+        self.push(target, message);
+        Ok(())
+    }
+
+    /// Tell `user_id` their requested data export can be downloaded
+    pub fn notify_export_ready(&self, user_id: i32, size: u64) {
+        let target = PushTarget::User(user_id);
+        let message = PushMessage::new(
+            notification_text("export_ready_title", &[]),
+            notification_text("export_ready_body", &[]),
+        )
+        .with_data("type", "export");
+        self.hub.publish(
+            target,
+            LiveNotification {
+                event: "export".to_string(),
+                data: json!({
+                    "title": message.title,
+                    "body": message.body,
+                    "size": size,
+                }),
+            },
+        );
+        self.push(target, message);
+    }
+
+    /// Register a companion app device token for push alerts
+    ///
+    /// A token moves to the registering user if another account held it; only
+    /// the most recently registered tokens of a user are kept.
+    pub async fn register_push_token(
+        &self,
+        user_id: i32,
+        platform: &str,
+        token: &str,
+    ) -> ArcResult<()> {
+        let platform = PushPlatform::parse(platform)?;
+        let token = token.trim();
+        if token.is_empty() || token.len() > 255 {
+            return Err(ArcError::input("Invalid push token."));
+        }
+
+        let now = current_timestamp_ms();
+        sqlx::query!(
+            "INSERT INTO user_push_token (platform, token, user_id, created_at, updated_at)
+             VALUES (?, ?, ?, ?, ?)
+             ON DUPLICATE KEY UPDATE user_id = VALUES(user_id), updated_at = VALUES(updated_at)",
+            platform.as_str(),
+            token,
+            user_id,
+            now,
+            now
+        )
+        .execute(&self.pool)
+        .await?;
+
+        let tokens = sqlx::query!(
+            "SELECT platform, token FROM user_push_token WHERE user_id = ?
+             ORDER BY updated_at DESC",
+            user_id
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        for row in tokens.into_iter().skip(MAX_PUSH_TOKENS_PER_USER) {
+            sqlx::query!(
+                "DELETE FROM user_push_token WHERE platform = ? AND token = ?",
+                row.platform,
+                row.token
+            )
+            .execute(&self.pool)
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Remove a device token of the user, e.g. when the companion app logs out
+    pub async fn unregister_push_token(
+        &self,
+        user_id: i32,
+        platform: &str,
+        token: &str,
+    ) -> ArcResult<()> {
+        let platform = PushPlatform::parse(platform)?;
+        sqlx::query!(
+            "DELETE FROM user_push_token WHERE platform = ? AND token = ? AND user_id = ?",
+            platform.as_str(),
+            token.trim(),
+            user_id
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    fn push(&self, target: PushTarget, message: PushMessage) {
+        if let Some(push) = &self.push {
+            push.enqueue(target, message);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn live(event: &str) -> LiveNotification {
+        LiveNotification {
+            event: event.to_string(),
+            data: Value::Null,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_hub_routes_to_subscribed_user() {
+        let hub = NotificationHub::new();
+        assert!(!hub.publish(PushTarget::User(1), live("room_inv")));
+
+        let mut subscription = hub.subscribe(1);
+        assert!(hub.has_subscribers(PushTarget::User(1)));
+        assert!(!hub.publish(PushTarget::User(2), live("room_inv")));
+        assert!(hub.publish(PushTarget::User(1), live("room_inv")));
+        assert!(hub.publish(PushTarget::AllUsers, live("present")));
+
+        assert_eq!(subscription.recv().await.unwrap().event, "room_inv");
+        assert_eq!(subscription.recv().await.unwrap().event, "present");
+    }
+
+    #[tokio::test]
+    async fn test_hub_forgets_disconnected_user() {
+        let hub = NotificationHub::new();
+        drop(hub.subscribe(1));
+        assert!(!hub.has_subscribers(PushTarget::User(1)));
+        assert!(!hub.publish(PushTarget::User(1), live("room_inv")));
+        assert!(lock(&hub.users).is_empty());
+    }
 }
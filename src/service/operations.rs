@@ -3,15 +3,30 @@
 //! This module provides operations for refreshing various caches and performing
 //! maintenance tasks, similar to the Python implementation's operation.py.
 
-use crate::config::CONFIG;
+use crate::config::{self, CONFIG};
 use crate::error::{ArcError, ArcResult};
+use crate::model::item::ItemTypes;
+use crate::model::score::Score;
+use crate::service::anomaly::AnomalyService;
 use crate::service::asset_manager::AssetManager;
 use crate::service::bundle::BundleService;
-use crate::utils::sql_placeholders;
-
+use crate::service::chart_analytics::ChartAnalyticsService;
+use crate::service::download::DownloadService;
+use crate::service::game_constants::GameConstantsService;
+use crate::service::item::ItemService;
+use crate::service::notification::NotificationService;
+use crate::service::push::PushGateway;
+use crate::service::score::ScoreService;
+use crate::service::user_export::UserExportService;
+use crate::service::world::get_map_parser;
+use crate::utils::{current_timestamp_ms, sql_placeholders};
+
+use crate::DbPool;
 use async_trait::async_trait;
-use sqlx::MySqlPool;
-use std::sync::Arc;
+use serde::Serialize;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
 
 /// Base trait for all operations
 #[async_trait]
@@ -26,6 +41,39 @@ pub trait Operation: Send + Sync {
     fn set_params(&mut self, _params: OperationParams) -> ArcResult<()> {
         Ok(())
     }
+
+    /// Hand over a progress handle; operations that can count their work
+    /// keep it and report through it (optional)
+    fn set_progress(&mut self, _progress: OperationProgress) {}
+}
+
+/// Shared counter an operation advances while a background job runs it
+#[derive(Debug, Clone, Default)]
+pub struct OperationProgress {
+    done: Arc<AtomicU64>,
+    total: Arc<AtomicU64>,
+}
+
+impl OperationProgress {
+    pub fn set_total(&self, total: u64) {
+        self.total.store(total, Ordering::Relaxed);
+    }
+
+    pub fn advance(&self, steps: u64) {
+        self.done.fetch_add(steps, Ordering::Relaxed);
+    }
+
+    /// Finished fraction in `0.0..=1.0`, `None` while the total is unknown
+    pub fn fraction(&self) -> Option<f64> {
+        progress_fraction(
+            self.done.load(Ordering::Relaxed),
+            self.total.load(Ordering::Relaxed),
+        )
+    }
+}
+
+fn progress_fraction(done: u64, total: u64) -> Option<f64> {
+    (total > 0).then(|| (done as f64 / total as f64).min(1.0))
 }
 
 /// Parameters that can be passed to operations
@@ -68,6 +116,30 @@ impl Operation for RefreshSongFileCache {
     }
 }
 
+/// Operation to refresh song file cache, rehashing only new or changed files
+pub struct RefreshSongFileCacheIncremental {
+    asset_manager: Arc<AssetManager>,
+}
+
+impl RefreshSongFileCacheIncremental {
+    pub fn new(asset_manager: Arc<AssetManager>) -> Self {
+        Self { asset_manager }
+    }
+}
+
+#[async_trait]
+impl Operation for RefreshSongFileCacheIncremental {
+    fn name(&self) -> &'static str {
+        "refresh_song_file_cache_incremental"
+    }
+
+    async fn execute(&self) -> ArcResult<()> {
+        log::info!("Executing operation: {}", self.name());
+        self.asset_manager.refresh_song_files_incremental().await?;
+        Ok(())
+    }
+}
+
 /// Operation to refresh bundle cache
 /// Equivalent to Python's RefreshBundleCache
 pub struct RefreshBundleCache {
@@ -98,12 +170,16 @@ impl Operation for RefreshBundleCache {
 /// Operation to refresh all score ratings
 /// Equivalent to Python's RefreshAllScoreRating
 pub struct RefreshAllScoreRating {
-    pool: MySqlPool,
+    pool: DbPool,
+    progress: OperationProgress,
 }
 
 impl RefreshAllScoreRating {
-    pub fn new(pool: MySqlPool) -> Self {
-        Self { pool }
+    pub fn new(pool: DbPool) -> Self {
+        Self {
+            pool,
+            progress: OperationProgress::default(),
+        }
     }
 }
 
@@ -113,97 +189,80 @@ impl Operation for RefreshAllScoreRating {
         "refresh_all_score_rating"
     }
 
+    fn set_progress(&mut self, progress: OperationProgress) {
+        self.progress = progress;
+    }
+
     async fn execute(&self) -> ArcResult<()> {
         log::info!("Executing operation: {}", self.name());
 
-        // Get all chart constants
-        let charts = sqlx::query!("SELECT * FROM chart")
-            .fetch_all(&self.pool)
+        let chart_count = sqlx::query_scalar!("SELECT COUNT(*) as `count!: i64` FROM chart")
+            .fetch_one(&self.pool)
             .await?;
-
-        // Create song_id filter for update
-        let song_ids: Vec<String> = charts.iter().map(|c| c.song_id.clone()).collect();
-
-        if !song_ids.is_empty() {
-            // Reset ratings for songs not in chart table
-            let song_placeholders = sql_placeholders(song_ids.len());
-            let query = format!(
-                "UPDATE best_score SET rating = 0 WHERE song_id NOT IN ({song_placeholders})"
-            );
-
-            let mut query_builder = sqlx::query(&query);
-            for song_id in &song_ids {
-                query_builder = query_builder.bind(song_id);
-            }
-            query_builder.execute(&self.pool).await?;
-
-            // Update ratings for each song and difficulty
-            for chart in &charts {
-                let ratings = [
-                    chart.rating_pst,
-                    chart.rating_prs,
-                    chart.rating_ftr,
-                    chart.rating_byn,
-                    chart.rating_etr,
-                ];
-
-                for (difficulty, rating_opt) in ratings.iter().enumerate() {
-                    let def_rating = rating_opt
-                        .filter(|rating| *rating > 0)
-                        .map(|rating| rating as f64 / 10.0)
-                        .unwrap_or(-10.0);
-
-                    // Update best_score ratings and score_v2
-                    sqlx::query!(
-                        "UPDATE best_score
-                         SET rating = GREATEST(
+        // best_score, recent30, rating_ptt and world rank score passes
+        self.progress.set_total(4);
+
+        if chart_count > 0 {
+            // One pass over best_score: songs without a chart row drop to rating 0,
+            // charts without a positive constant use defnum = -10 like Python.
+            let best_rows = sqlx::query!(
+                "UPDATE best_score b
+                 LEFT JOIN (
+                     SELECT song_id, 0 AS difficulty, IF(rating_pst > 0, rating_pst / 10.0, -10.0) AS def_rating FROM chart
+                     UNION ALL
+                     SELECT song_id, 1, IF(rating_prs > 0, rating_prs / 10.0, -10.0) FROM chart
+                     UNION ALL
+                     SELECT song_id, 2, IF(rating_ftr > 0, rating_ftr / 10.0, -10.0) FROM chart
+                     UNION ALL
+                     SELECT song_id, 3, IF(rating_byn > 0, rating_byn / 10.0, -10.0) FROM chart
+                     UNION ALL
+                     SELECT song_id, 4, IF(rating_etr > 0, rating_etr / 10.0, -10.0) FROM chart
+                 ) c ON c.song_id = b.song_id AND c.difficulty = b.difficulty
+                 SET b.rating = CASE
+                         WHEN c.song_id IS NULL THEN 0.0
+                         ELSE GREATEST(
                              CASE
-                                 WHEN score >= 10000000 THEN ? + 2.0
-                                 WHEN score >= 9800000 THEN ? + 1.0 + (score - 9800000) * 5.0 / 1000000.0
-                                 ELSE GREATEST(? + (score - 9500000) * 5.0 / 1500000.0, 0.0)
+                                 WHEN b.score >= 10000000 THEN c.def_rating + 2.0
+                                 WHEN b.score >= 9800000 THEN c.def_rating + 1.0 + (b.score - 9800000) * 5.0 / 1000000.0
+                                 ELSE GREATEST(c.def_rating + (b.score - 9500000) * 5.0 / 1500000.0, 0.0)
                              END,
                              0.0
-                         ),
-                             score_v2 = CASE
-                                 WHEN ? <= 0.0 THEN 0.0
-                                 WHEN (COALESCE(perfect_count, 0) + COALESCE(near_count, 0) + COALESCE(miss_count, 0)) <= 0 THEN 0.0
-                                 ELSE ? * (
-                                     LEAST(
-                                         GREATEST(
-                                             COALESCE(shiny_perfect_count, 0) * 1.0
-                                             / NULLIF(COALESCE(perfect_count, 0) + COALESCE(near_count, 0) + COALESCE(miss_count, 0), 0)
-                                             - 0.9,
-                                             0.0
-                                         ),
-                                         0.095
-                                     ) / 9.5 * 25.0
-                                     +
-                                     LEAST(
-                                         GREATEST(
-                                             (
-                                                 (COALESCE(perfect_count, 0) + COALESCE(near_count, 0) / 2.0) * 1.0
-                                                 / NULLIF(COALESCE(perfect_count, 0) + COALESCE(near_count, 0) + COALESCE(miss_count, 0), 0)
-                                                 + COALESCE(shiny_perfect_count, 0) / 10000000.0
-                                             ) - 0.99,
-                                             0.0
-                                         ),
-                                         0.01
-                                     ) * 75.0
-                                 )
-                             END
-                         WHERE song_id = ? AND difficulty = ?",
-                        def_rating,
-                        def_rating,
-                        def_rating,
-                        def_rating,
-                        def_rating,
-                        chart.song_id,
-                        difficulty as i32
-                    )
-                    .execute(&self.pool)
-                    .await?;
-                }
-            }
+                         )
+                     END,
+                     b.score_v2 = CASE
+                         WHEN c.song_id IS NULL THEN b.score_v2
+                         WHEN c.def_rating <= 0.0 THEN 0.0
+                         WHEN (COALESCE(b.perfect_count, 0) + COALESCE(b.near_count, 0) + COALESCE(b.miss_count, 0)) <= 0 THEN 0.0
+                         ELSE c.def_rating * (
+                             LEAST(
+                                 GREATEST(
+                                     COALESCE(b.shiny_perfect_count, 0) * 1.0
+                                     / NULLIF(COALESCE(b.perfect_count, 0) + COALESCE(b.near_count, 0) + COALESCE(b.miss_count, 0), 0)
+                                     - 0.9,
+                                     0.0
+                                 ),
+                                 0.095
+                             ) / 9.5 * 25.0
+                             +
+                             LEAST(
+                                 GREATEST(
+                                     (
+                                         (COALESCE(b.perfect_count, 0) + COALESCE(b.near_count, 0) / 2.0) * 1.0
+                                         / NULLIF(COALESCE(b.perfect_count, 0) + COALESCE(b.near_count, 0) + COALESCE(b.miss_count, 0), 0)
+                                         + COALESCE(b.shiny_perfect_count, 0) / 10000000.0
+                                     ) - 0.99,
+                                     0.0
+                                 ),
+                                 0.01
+                             ) * 75.0
+                         )
+                     END"
+            )
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+            self.progress.advance(1);
+            log::info!("best_score rating refresh completed, changed rows: {best_rows}");
 
             // Update recent30 ratings. Python treats missing chart rows as defnum = -10,
             // which clamps to 0 after rating calculation, so use LEFT JOIN.
@@ -249,56 +308,16 @@ impl Operation for RefreshAllScoreRating {
             )
             .execute(&self.pool)
             .await?;
+            self.progress.advance(1);
 
-            let user_rating_result = sqlx::query!(
-                "UPDATE user u
-                 LEFT JOIN (
-                     SELECT user_id, SUM(COALESCE(rating, 0)) AS best_30_sum
-                     FROM (
-                         SELECT user_id, rating,
-                                ROW_NUMBER() OVER (
-                                    PARTITION BY user_id
-                                    ORDER BY COALESCE(rating, 0) DESC
-                                ) AS rn
-                         FROM best_score
-                     ) ranked_best
-                     WHERE rn <= 30
-                     GROUP BY user_id
-                 ) b30 ON b30.user_id = u.user_id
-                 LEFT JOIN (
-                     SELECT user_id, SUM(rating) AS recent_10_sum
-                     FROM (
-                         SELECT user_id, rating,
-                                ROW_NUMBER() OVER (
-                                    PARTITION BY user_id
-                                    ORDER BY rating DESC
-                                ) AS rn
-                         FROM (
-                             SELECT user_id, song_id, difficulty, MAX(COALESCE(rating, 0)) AS rating
-                             FROM recent30
-                             WHERE song_id != ''
-                             GROUP BY user_id, song_id, difficulty
-                         ) recent_max
-                     ) ranked_recent
-                     WHERE rn <= 10
-                     GROUP BY user_id
-                 ) r10 ON r10.user_id = u.user_id
-                 SET u.rating_ptt = FLOOR(
-                     (
-                         COALESCE(b30.best_30_sum, 0) * ?
-                         + COALESCE(r10.recent_10_sum, 0) * ?
-                     ) * 100
-                 )",
-                CONFIG.best30_weight,
-                CONFIG.recent10_weight
-            )
-            .execute(&self.pool)
-            .await?;
+            let score_service = ScoreService::new(self.pool.clone());
+            let changed_rows = score_service.recalculate_potentials(None).await?;
+            self.progress.advance(1);
+            log::info!("User rating_ptt refresh completed, changed rows: {changed_rows}");
 
-            log::info!(
-                "User rating_ptt refresh completed, changed rows: {}",
-                user_rating_result.rows_affected()
-            );
+            let changed_rows = score_service.recalculate_world_rank_scores(None).await?;
+            self.progress.advance(1);
+            log::info!("User world_rank_score refresh completed, changed rows: {changed_rows}");
         }
 
         log::info!("All score rating refresh completed");
@@ -306,10 +325,100 @@ impl Operation for RefreshAllScoreRating {
     }
 }
 
+/// Operation to fill `score_v2` on best scores stored before it was computed
+///
+/// Only rows with a missing or zero `score_v2` on a rated chart are touched;
+/// the value comes from [`Score::calculate_score_v2`], the same formula as
+/// score submission, and world rank scores are recomputed afterwards.
+pub struct BackfillScoreV2 {
+    pool: DbPool,
+    progress: OperationProgress,
+}
+
+impl BackfillScoreV2 {
+    pub fn new(pool: DbPool) -> Self {
+        Self {
+            pool,
+            progress: OperationProgress::default(),
+        }
+    }
+}
+
+#[async_trait]
+impl Operation for BackfillScoreV2 {
+    fn name(&self) -> &'static str {
+        "backfill_score_v2"
+    }
+
+    fn set_progress(&mut self, progress: OperationProgress) {
+        self.progress = progress;
+    }
+
+    async fn execute(&self) -> ArcResult<()> {
+        log::info!("Executing operation: {}", self.name());
+
+        let rows = sqlx::query!(
+            "SELECT b.user_id, b.song_id, b.difficulty, b.shiny_perfect_count, b.perfect_count,
+                    b.near_count, b.miss_count, c.rating_pst, c.rating_prs, c.rating_ftr,
+                    c.rating_byn, c.rating_etr
+             FROM best_score b
+             JOIN chart c ON c.song_id = b.song_id
+             WHERE b.score_v2 IS NULL OR b.score_v2 = 0"
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        // One step per row plus the world rank pass
+        self.progress.set_total(rows.len() as u64 + 1);
+
+        let mut filled = 0u64;
+        let mut tx = self.pool.begin().await?;
+        for row in rows {
+            let rating = match row.difficulty {
+                0 => row.rating_pst,
+                1 => row.rating_prs,
+                2 => row.rating_ftr,
+                3 => row.rating_byn,
+                4 => row.rating_etr,
+                _ => None,
+            };
+            let score_v2 = Score::calculate_score_v2(
+                rating.unwrap_or(-1) as f64 / 10.0,
+                row.shiny_perfect_count.unwrap_or(0),
+                row.perfect_count.unwrap_or(0),
+                row.near_count.unwrap_or(0),
+                row.miss_count.unwrap_or(0),
+            );
+            if score_v2 > 0.0 {
+                sqlx::query!(
+                    "UPDATE best_score SET score_v2 = ?
+                     WHERE user_id = ? AND song_id = ? AND difficulty = ?",
+                    score_v2,
+                    row.user_id,
+                    row.song_id,
+                    row.difficulty
+                )
+                .execute(&mut *tx)
+                .await?;
+                filled += 1;
+            }
+            self.progress.advance(1);
+        }
+        tx.commit().await?;
+        log::info!("score_v2 backfill filled {filled} best_score rows");
+
+        let changed_rows = ScoreService::new(self.pool.clone())
+            .recalculate_world_rank_scores(None)
+            .await?;
+        self.progress.advance(1);
+        log::info!("User world_rank_score refresh completed, changed rows: {changed_rows}");
+        Ok(())
+    }
+}
+
 /// Operation to unlock/lock user items
 /// Equivalent to Python's UnlockUserItem
 pub struct UnlockUserItem {
-    pool: MySqlPool,
+    pool: DbPool,
     user_id: Option<i32>,
     method: UnlockMethod,
     item_types: Vec<String>,
@@ -322,7 +431,7 @@ pub enum UnlockMethod {
 }
 
 impl UnlockUserItem {
-    pub fn new(pool: MySqlPool) -> Self {
+    pub fn new(pool: DbPool) -> Self {
         Self {
             pool,
             user_id: None,
@@ -509,41 +618,720 @@ fn normalize_item_types(item_types: Vec<String>) -> Vec<String> {
     item_types
 }
 
+/// Operation to rebuild per-chart score analytics
+pub struct RefreshChartAnalytics {
+    chart_analytics: ChartAnalyticsService,
+}
+
+impl RefreshChartAnalytics {
+    pub fn new(pool: DbPool) -> Self {
+        Self {
+            chart_analytics: ChartAnalyticsService::new(pool),
+        }
+    }
+}
+
+#[async_trait]
+impl Operation for RefreshChartAnalytics {
+    fn name(&self) -> &'static str {
+        "refresh_chart_analytics"
+    }
+
+    async fn execute(&self) -> ArcResult<()> {
+        log::info!("Executing operation: {}", self.name());
+        let chart_count = self.chart_analytics.refresh().await?;
+
+        log::info!("Chart analytics refresh completed for {chart_count} charts");
+        Ok(())
+    }
+}
+
+/// Operation to propose constants for unrated charts
+pub struct EstimateChartConstants {
+    chart_analytics: ChartAnalyticsService,
+}
+
+impl EstimateChartConstants {
+    pub fn new(pool: DbPool) -> Self {
+        Self {
+            chart_analytics: ChartAnalyticsService::new(pool),
+        }
+    }
+}
+
+#[async_trait]
+impl Operation for EstimateChartConstants {
+    fn name(&self) -> &'static str {
+        "estimate_chart_constants"
+    }
+
+    async fn execute(&self) -> ArcResult<()> {
+        log::info!("Executing operation: {}", self.name());
+        let proposal_count = self.chart_analytics.estimate_unrated_constants().await?;
+
+        log::info!("Chart constant estimation proposed {proposal_count} constants");
+        Ok(())
+    }
+}
+
+/// Operation to rescan play logs for score anomalies
+pub struct ScanScoreAnomalies {
+    anomaly_service: AnomalyService,
+}
+
+impl ScanScoreAnomalies {
+    pub fn new(pool: DbPool) -> Self {
+        Self {
+            anomaly_service: AnomalyService::from_env(pool),
+        }
+    }
+}
+
+#[async_trait]
+impl Operation for ScanScoreAnomalies {
+    fn name(&self) -> &'static str {
+        "scan_score_anomalies"
+    }
+
+    async fn execute(&self) -> ArcResult<()> {
+        log::info!("Executing operation: {}", self.name());
+        let flagged = self.anomaly_service.scan().await?;
+
+        log::info!("Score anomaly scan flagged {flagged} accounts");
+        Ok(())
+    }
+}
+
+/// Operation to compare the songlist with the chart table and add
+/// placeholder charts for songs that are missing one
+pub struct ReconcileCharts {
+    asset_manager: Arc<AssetManager>,
+}
+
+impl ReconcileCharts {
+    pub fn new(asset_manager: Arc<AssetManager>) -> Self {
+        Self { asset_manager }
+    }
+}
+
+#[async_trait]
+impl Operation for ReconcileCharts {
+    fn name(&self) -> &'static str {
+        "reconcile_charts"
+    }
+
+    async fn execute(&self) -> ArcResult<()> {
+        log::info!("Executing operation: {}", self.name());
+        let report = self.asset_manager.reconcile_charts().await?;
+
+        log::info!(
+            "Chart reconciliation completed: {} missing, {} unlisted, {} placeholders created",
+            report.missing_charts.len(),
+            report.unlisted_charts.len(),
+            report.placeholders_created
+        );
+        Ok(())
+    }
+}
+
+/// Operation to re-read the songlist, sync chart constants and drop cached
+/// download hashes
+pub struct ReloadSonglist {
+    asset_manager: Arc<AssetManager>,
+}
+
+impl ReloadSonglist {
+    pub fn new(asset_manager: Arc<AssetManager>) -> Self {
+        Self { asset_manager }
+    }
+}
+
+#[async_trait]
+impl Operation for ReloadSonglist {
+    fn name(&self) -> &'static str {
+        "reload_songlist"
+    }
+
+    async fn execute(&self) -> ArcResult<()> {
+        log::info!("Executing operation: {}", self.name());
+        let report = self.asset_manager.reload_songlist().await?;
+
+        log::info!(
+            "Songlist reload completed: {} charts updated, {} placeholders created",
+            report.charts_updated,
+            report.placeholders_created
+        );
+        Ok(())
+    }
+}
+
+/// Operation to upsert chart names and constants from the songlist
+pub struct SyncChartsFromSonglist {
+    asset_manager: Arc<AssetManager>,
+}
+
+impl SyncChartsFromSonglist {
+    pub fn new(asset_manager: Arc<AssetManager>) -> Self {
+        Self { asset_manager }
+    }
+}
+
+#[async_trait]
+impl Operation for SyncChartsFromSonglist {
+    fn name(&self) -> &'static str {
+        "sync_charts_from_songlist"
+    }
+
+    async fn execute(&self) -> ArcResult<()> {
+        log::info!("Executing operation: {}", self.name());
+        self.asset_manager.sync_charts_from_songlist().await?;
+        Ok(())
+    }
+}
+
+/// Operation to reload game balance constants from the database
+pub struct ReloadGameConstants {
+    game_constants: GameConstantsService,
+}
+
+impl ReloadGameConstants {
+    pub fn new(pool: DbPool) -> Self {
+        Self {
+            game_constants: GameConstantsService::new(pool),
+        }
+    }
+}
+
+#[async_trait]
+impl Operation for ReloadGameConstants {
+    fn name(&self) -> &'static str {
+        "reload_game_constants"
+    }
+
+    async fn execute(&self) -> ArcResult<()> {
+        log::info!("Executing operation: {}", self.name());
+        let applied = self.game_constants.reload().await?;
+
+        log::info!("Game constants reloaded with {applied} overrides");
+        Ok(())
+    }
+}
+
+/// Play tokens older than this are considered abandoned
+const SONGPLAY_TOKEN_MAX_AGE_MS: i64 = 24 * 60 * 60 * 1000;
+
+/// Operation to delete abandoned song play tokens
+pub struct PurgeSongplayTokens {
+    pool: DbPool,
+}
+
+impl PurgeSongplayTokens {
+    pub fn new(pool: DbPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl Operation for PurgeSongplayTokens {
+    fn name(&self) -> &'static str {
+        "purge_songplay_tokens"
+    }
+
+    async fn execute(&self) -> ArcResult<()> {
+        log::info!("Executing operation: {}", self.name());
+        let cutoff = current_timestamp_ms() - SONGPLAY_TOKEN_MAX_AGE_MS;
+        let result = sqlx::query!(
+            "DELETE FROM songplay_token
+             WHERE created_at < ? OR user_id NOT IN (SELECT user_id FROM user)",
+            cutoff
+        )
+        .execute(&self.pool)
+        .await?;
+
+        log::info!("Purged {} stale songplay tokens", result.rows_affected());
+        Ok(())
+    }
+}
+
+/// Clamp stored map progress into the current map definition. Maps can lose
+/// steps or get cheaper steps between releases, which leaves players past the
+/// end or holding more capture than the step needs.
+fn clamp_world_progress(
+    step_captures: &[f64],
+    is_beyond: bool,
+    beyond_health: f64,
+    position: i32,
+    capture: f64,
+) -> (i32, f64) {
+    if step_captures.is_empty() {
+        return (0, 0.0);
+    }
+
+    let last = step_captures.len() as i32 - 1;
+    let position = position.clamp(0, last);
+    let capture = if is_beyond {
+        capture.clamp(0.0, beyond_health.max(0.0))
+    } else if position == last {
+        0.0
+    } else {
+        capture.clamp(0.0, step_captures[position as usize].max(0.0))
+    };
+    (position, capture)
+}
+
+/// Operation to recalculate one user's world progress against the current
+/// map files and grant any unlock rewards of steps already passed
+pub struct RecalculateWorldProgress {
+    pool: DbPool,
+    user_id: Option<i32>,
+    progress: OperationProgress,
+}
+
+impl RecalculateWorldProgress {
+    pub fn new(pool: DbPool) -> Self {
+        Self {
+            pool,
+            user_id: None,
+            progress: OperationProgress::default(),
+        }
+    }
+}
+
+#[async_trait]
+impl Operation for RecalculateWorldProgress {
+    fn name(&self) -> &'static str {
+        "recalculate_world_progress"
+    }
+
+    async fn execute(&self) -> ArcResult<()> {
+        let user_id = self
+            .user_id
+            .ok_or_else(|| ArcError::input("A user id is required"))?;
+        log::info!("Executing operation: {} (user {user_id})", self.name());
+
+        let rows = sqlx::query!(
+            "SELECT map_id, curr_position, curr_capture FROM user_world WHERE user_id = ?",
+            user_id
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let parser = get_map_parser();
+        let item_service = ItemService::new(self.pool.clone());
+        let mut updated = 0;
+        self.progress.set_total(rows.len() as u64);
+        for row in rows {
+            self.progress.advance(1);
+            let map = match parser.load_world_map(&row.map_id) {
+                Ok(map) => map,
+                Err(e) => {
+                    log::warn!("Skipping world map `{}`: {e}", row.map_id);
+                    continue;
+                }
+            };
+
+            let step_captures: Vec<f64> = map.steps.iter().map(|step| step.capture).collect();
+            let stored = (
+                row.curr_position.unwrap_or(0),
+                row.curr_capture.unwrap_or(0.0),
+            );
+            let (position, capture) = clamp_world_progress(
+                &step_captures,
+                map.is_beyond,
+                map.beyond_health_value(),
+                stored.0,
+                stored.1,
+            );
+            if (position, capture) != stored {
+                sqlx::query!(
+                    "UPDATE user_world SET curr_position = ?, curr_capture = ?
+                     WHERE user_id = ? AND map_id = ?",
+                    position,
+                    capture,
+                    user_id,
+                    row.map_id
+                )
+                .execute(&self.pool)
+                .await?;
+                updated += 1;
+            }
+
+            // Only rewards that can be granted twice without effect are replayed
+            for step in map.steps.iter().take(position as usize + 1) {
+                for item in &step.items {
+                    if matches!(
+                        item.item_type.as_str(),
+                        ItemTypes::WORLD_SONG | ItemTypes::WORLD_UNLOCK | ItemTypes::CHARACTER
+                    ) {
+                        item_service
+                            .claim_item(user_id, &item.item_id, &item.item_type, item.amount)
+                            .await?;
+                    }
+                }
+            }
+        }
+
+        log::info!("World progress recalculated for user {user_id}, {updated} maps corrected");
+        Ok(())
+    }
+
+    fn set_params(&mut self, params: OperationParams) -> ArcResult<()> {
+        if let Some(user_id) = params.user_id {
+            self.user_id = Some(user_id);
+        }
+        Ok(())
+    }
+
+    fn set_progress(&mut self, progress: OperationProgress) {
+        self.progress = progress;
+    }
+}
+
+/// Operation to rebuild recent30 from the play history in `user_score`,
+/// for one user or everyone
+pub struct RebuildRecent30 {
+    pool: DbPool,
+    user_id: Option<i32>,
+}
+
+impl RebuildRecent30 {
+    pub fn new(pool: DbPool) -> Self {
+        Self {
+            pool,
+            user_id: None,
+        }
+    }
+}
+
+#[async_trait]
+impl Operation for RebuildRecent30 {
+    fn name(&self) -> &'static str {
+        "rebuild_recent30"
+    }
+
+    async fn execute(&self) -> ArcResult<()> {
+        log::info!("Executing operation: {} ({:?})", self.name(), self.user_id);
+
+        let mut tx = self.pool.begin().await?;
+        sqlx::query!(
+            "DELETE FROM recent30 WHERE ? IS NULL OR user_id = ?",
+            self.user_id,
+            self.user_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        // The newest play gets the highest r_index, matching how plays are appended
+        let result = sqlx::query!(
+            "INSERT INTO recent30 (user_id, r_index, time_played, song_id, difficulty, score,
+             shiny_perfect_count, perfect_count, near_count, miss_count, health, modifier,
+             clear_type, rating)
+             SELECT user_id, LEAST(play_count, 30) - rn, time_played, song_id, difficulty, score,
+                    shiny_perfect_count, perfect_count, near_count, miss_count, health, modifier,
+                    clear_type, rating
+             FROM (
+                 SELECT s.*,
+                        ROW_NUMBER() OVER (PARTITION BY user_id ORDER BY time_played DESC) AS rn,
+                        COUNT(*) OVER (PARTITION BY user_id) AS play_count
+                 FROM user_score s
+                 WHERE ? IS NULL OR user_id = ?
+             ) plays
+             WHERE rn <= 30",
+            self.user_id,
+            self.user_id
+        )
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        ScoreService::new(self.pool.clone())
+            .recalculate_potentials(self.user_id)
+            .await?;
+
+        log::info!("Rebuilt {} recent30 rows", result.rows_affected());
+        Ok(())
+    }
+
+    fn set_params(&mut self, params: OperationParams) -> ArcResult<()> {
+        if let Some(user_id) = params.user_id {
+            self.user_id = Some(user_id);
+        }
+        Ok(())
+    }
+}
+
+/// Operation to delete expired presents together with their items and
+/// pending deliveries
+pub struct VacuumExpiredPresents {
+    pool: DbPool,
+}
+
+impl VacuumExpiredPresents {
+    pub fn new(pool: DbPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl Operation for VacuumExpiredPresents {
+    fn name(&self) -> &'static str {
+        "vacuum_expired_presents"
+    }
+
+    async fn execute(&self) -> ArcResult<()> {
+        log::info!("Executing operation: {}", self.name());
+        let now = current_timestamp_ms();
+
+        let mut tx = self.pool.begin().await?;
+        sqlx::query!(
+            "DELETE up FROM user_present up
+             INNER JOIN present p ON p.present_id = up.present_id
+             WHERE p.expire_ts IS NOT NULL AND p.expire_ts < ?",
+            now
+        )
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query!(
+            "DELETE pi FROM present_item pi
+             INNER JOIN present p ON p.present_id = pi.present_id
+             WHERE p.expire_ts IS NOT NULL AND p.expire_ts < ?",
+            now
+        )
+        .execute(&mut *tx)
+        .await?;
+        let result = sqlx::query!(
+            "DELETE FROM present WHERE expire_ts IS NOT NULL AND expire_ts < ?",
+            now
+        )
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        log::info!("Removed {} expired presents", result.rows_affected());
+        Ok(())
+    }
+}
+
+/// Operation to write a user's personal data export and tell them when it
+/// can be downloaded
+pub struct ExportUserData {
+    pool: DbPool,
+    push: Option<PushGateway>,
+    user_id: Option<i32>,
+}
+
+impl ExportUserData {
+    pub fn new(pool: DbPool, push: Option<PushGateway>) -> Self {
+        Self {
+            pool,
+            push,
+            user_id: None,
+        }
+    }
+}
+
+#[async_trait]
+impl Operation for ExportUserData {
+    fn name(&self) -> &'static str {
+        "export_user_data"
+    }
+
+    async fn execute(&self) -> ArcResult<()> {
+        let user_id = self
+            .user_id
+            .ok_or_else(|| ArcError::input("export_user_data requires a user_id"))?;
+        log::info!("Executing operation: {} (user {user_id})", self.name());
+
+        let export = UserExportService::new(
+            self.pool.clone(),
+            PathBuf::from(CONFIG.user_export_folder_path.trim()),
+        )
+        .export_user(user_id)
+        .await?;
+
+        let mut notifications = NotificationService::new(self.pool.clone());
+        if let Some(push) = &self.push {
+            notifications = notifications.with_push(push.clone());
+        }
+        notifications.notify_export_ready(user_id, export.size);
+
+        log::info!("Exported data of user {user_id} ({} bytes)", export.size);
+        Ok(())
+    }
+
+    fn set_params(&mut self, params: OperationParams) -> ArcResult<()> {
+        self.user_id = params.user_id;
+        Ok(())
+    }
+}
+
+/// Operation to re-read the configuration and apply its hot-reloadable keys
+pub struct ReloadConfig {
+    download_service: Option<DownloadService>,
+}
+
+impl ReloadConfig {
+    pub fn new(download_service: Option<DownloadService>) -> Self {
+        Self { download_service }
+    }
+}
+
+#[async_trait]
+impl Operation for ReloadConfig {
+    fn name(&self) -> &'static str {
+        "reload_config"
+    }
+
+    async fn execute(&self) -> ArcResult<()> {
+        log::info!("Executing operation: {}", self.name());
+        let changed = config::reload_config()
+            .map_err(|e| ArcError::input(format!("Invalid configuration: {e}")))?;
+
+        if let Some(download_service) = &self.download_service {
+            download_service.apply_reloaded_config(&config::reloadable_config());
+        }
+
+        if changed.is_empty() {
+            log::info!("Configuration reloaded, nothing changed");
+        } else {
+            log::info!("Configuration reloaded, changed: {}", changed.join(", "));
+        }
+        Ok(())
+    }
+}
+
+/// Finished jobs kept for status polling; older ones are forgotten
+const MAX_FINISHED_JOBS: usize = 50;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OperationJobStatus {
+    Running,
+    Succeeded,
+    Failed,
+}
+
+/// Snapshot of an operation running in the background
+#[derive(Debug, Clone, Serialize)]
+pub struct OperationJob {
+    pub id: String,
+    pub operation: String,
+    pub user_id: Option<i32>,
+    pub status: OperationJobStatus,
+    /// Finished fraction, `None` when the operation does not report progress
+    pub progress: Option<f64>,
+    pub error: Option<String>,
+    pub started_at: i64,
+    pub finished_at: Option<i64>,
+}
+
+struct JobEntry {
+    job: OperationJob,
+    progress: OperationProgress,
+}
+
+impl JobEntry {
+    fn snapshot(&self) -> OperationJob {
+        let mut job = self.job.clone();
+        job.progress = match job.status {
+            OperationJobStatus::Succeeded => Some(1.0),
+            _ => self.progress.fraction(),
+        };
+        job
+    }
+}
+
+/// Drop the oldest finished jobs beyond [`MAX_FINISHED_JOBS`]
+fn prune_finished_jobs(jobs: &mut Vec<JobEntry>) {
+    let finished = jobs
+        .iter()
+        .filter(|entry| entry.job.status != OperationJobStatus::Running)
+        .count();
+    let mut excess = finished.saturating_sub(MAX_FINISHED_JOBS);
+    jobs.retain(|entry| {
+        if excess > 0 && entry.job.status != OperationJobStatus::Running {
+            excess -= 1;
+            return false;
+        }
+        true
+    });
+}
+
 /// Operation manager to execute operations
 pub struct OperationManager {
     asset_manager: Arc<AssetManager>,
     bundle_service: Arc<BundleService>,
-    pool: MySqlPool,
+    pool: DbPool,
+    push: Option<PushGateway>,
+    download_service: Option<DownloadService>,
+    jobs: Arc<RwLock<Vec<JobEntry>>>,
 }
 
 impl OperationManager {
     pub fn new(
         asset_manager: Arc<AssetManager>,
         bundle_service: Arc<BundleService>,
-        pool: MySqlPool,
+        pool: DbPool,
     ) -> Self {
         Self {
             asset_manager,
             bundle_service,
             pool,
+            push: None,
+            download_service: None,
+            jobs: Arc::new(RwLock::new(Vec::new())),
         }
     }
 
-    /// Execute operation by name
-    pub async fn execute_operation(
+    /// Also alert companion app devices when an operation notifies a user
+    pub fn with_push(mut self, push: PushGateway) -> Self {
+        self.push = Some(push);
+        self
+    }
+
+    /// Hand reloaded download settings to the running download service
+    pub fn with_download_service(mut self, download_service: DownloadService) -> Self {
+        self.download_service = Some(download_service);
+        self
+    }
+
+    fn build_operation(
         &self,
         operation_name: &str,
         params: Option<OperationParams>,
-    ) -> ArcResult<()> {
+    ) -> ArcResult<Box<dyn Operation>> {
         let mut operation: Box<dyn Operation> = match operation_name {
             "refresh_song_file_cache" => {
                 Box::new(RefreshSongFileCache::new(self.asset_manager.clone()))
             }
+            "refresh_song_file_cache_incremental" => Box::new(
+                RefreshSongFileCacheIncremental::new(self.asset_manager.clone()),
+            ),
             "refresh_content_bundle_cache" => {
                 Box::new(RefreshBundleCache::new(self.bundle_service.clone()))
             }
             "refresh_all_score_rating" => Box::new(RefreshAllScoreRating::new(self.pool.clone())),
+            "backfill_score_v2" => Box::new(BackfillScoreV2::new(self.pool.clone())),
+            "refresh_chart_analytics" => Box::new(RefreshChartAnalytics::new(self.pool.clone())),
+            "estimate_chart_constants" => Box::new(EstimateChartConstants::new(self.pool.clone())),
+            "scan_score_anomalies" => Box::new(ScanScoreAnomalies::new(self.pool.clone())),
+            "reload_game_constants" => Box::new(ReloadGameConstants::new(self.pool.clone())),
+            "reconcile_charts" => Box::new(ReconcileCharts::new(self.asset_manager.clone())),
+            "reload_songlist" => Box::new(ReloadSonglist::new(self.asset_manager.clone())),
+            "sync_charts_from_songlist" => {
+                Box::new(SyncChartsFromSonglist::new(self.asset_manager.clone()))
+            }
+            "purge_songplay_tokens" => Box::new(PurgeSongplayTokens::new(self.pool.clone())),
+            "recalculate_world_progress" => {
+                Box::new(RecalculateWorldProgress::new(self.pool.clone()))
+            }
+            "rebuild_recent30" => Box::new(RebuildRecent30::new(self.pool.clone())),
+            "vacuum_expired_presents" => Box::new(VacuumExpiredPresents::new(self.pool.clone())),
             "unlock_user_item" => Box::new(UnlockUserItem::new(self.pool.clone())),
+            "export_user_data" => {
+                Box::new(ExportUserData::new(self.pool.clone(), self.push.clone()))
+            }
+            "reload_config" => Box::new(ReloadConfig::new(self.download_service.clone())),
             _ => {
                 return Err(ArcError::no_data(
                     format!("Unknown operation: {operation_name}"),
@@ -557,17 +1345,170 @@ impl OperationManager {
             operation.set_params(params)?;
         }
 
-        // Execute the operation
-        operation.execute().await
+        Ok(operation)
+    }
+
+    /// Execute operation by name
+    pub async fn execute_operation(
+        &self,
+        operation_name: &str,
+        params: Option<OperationParams>,
+    ) -> ArcResult<()> {
+        self.build_operation(operation_name, params)?
+            .execute()
+            .await
+    }
+
+    /// Run an operation as a background job and return its initial state.
+    /// Starting an operation that is already running for the same user
+    /// returns the running job instead of a second copy.
+    pub fn start_operation(
+        &self,
+        operation_name: &str,
+        params: Option<OperationParams>,
+    ) -> ArcResult<OperationJob> {
+        let user_id = params.as_ref().and_then(|params| params.user_id);
+        let mut jobs = self.jobs.write().unwrap();
+        if let Some(running) = jobs.iter().find(|entry| {
+            entry.job.status == OperationJobStatus::Running
+                && entry.job.operation == operation_name
+                && entry.job.user_id == user_id
+        }) {
+            return Ok(running.snapshot());
+        }
+
+        let mut operation = self.build_operation(operation_name, params)?;
+        let progress = OperationProgress::default();
+        operation.set_progress(progress.clone());
+
+        let job = OperationJob {
+            id: uuid::Uuid::new_v4().to_string(),
+            operation: operation_name.to_string(),
+            user_id,
+            status: OperationJobStatus::Running,
+            progress: None,
+            error: None,
+            started_at: current_timestamp_ms(),
+            finished_at: None,
+        };
+        jobs.push(JobEntry {
+            job: job.clone(),
+            progress,
+        });
+        prune_finished_jobs(&mut jobs);
+        drop(jobs);
+
+        let jobs = self.jobs.clone();
+        let job_id = job.id.clone();
+        tokio::spawn(async move {
+            let result = operation.execute().await;
+            if let Err(e) = &result {
+                log::error!("Operation {} failed: {e}", operation.name());
+            }
+
+            let mut jobs = jobs.write().unwrap();
+            if let Some(entry) = jobs.iter_mut().find(|entry| entry.job.id == job_id) {
+                entry.job.finished_at = Some(current_timestamp_ms());
+                match result {
+                    Ok(()) => entry.job.status = OperationJobStatus::Succeeded,
+                    Err(e) => {
+                        entry.job.status = OperationJobStatus::Failed;
+                        entry.job.error = Some(e.to_string());
+                    }
+                }
+            }
+        });
+
+        Ok(job)
+    }
+
+    /// Current state of a background job
+    pub fn job(&self, job_id: &str) -> Option<OperationJob> {
+        self.jobs
+            .read()
+            .unwrap()
+            .iter()
+            .find(|entry| entry.job.id == job_id)
+            .map(JobEntry::snapshot)
+    }
+
+    /// Most recent job of `operation_name` started for `user_id`
+    pub fn latest_job(&self, operation_name: &str, user_id: i32) -> Option<OperationJob> {
+        self.jobs
+            .read()
+            .unwrap()
+            .iter()
+            .rev()
+            .find(|entry| {
+                entry.job.operation == operation_name && entry.job.user_id == Some(user_id)
+            })
+            .map(JobEntry::snapshot)
+    }
+
+    /// Background jobs, newest first
+    pub fn jobs(&self) -> Vec<OperationJob> {
+        self.jobs
+            .read()
+            .unwrap()
+            .iter()
+            .rev()
+            .map(JobEntry::snapshot)
+            .collect()
     }
 
     /// Get list of available operations
     pub fn list_operations(&self) -> Vec<&'static str> {
         vec![
             "refresh_song_file_cache",
+            "refresh_song_file_cache_incremental",
             "refresh_content_bundle_cache",
             "refresh_all_score_rating",
+            "backfill_score_v2",
+            "refresh_chart_analytics",
+            "estimate_chart_constants",
+            "scan_score_anomalies",
+            "reload_game_constants",
+            "reconcile_charts",
+            "reload_songlist",
+            "sync_charts_from_songlist",
+            "purge_songplay_tokens",
+            "recalculate_world_progress",
+            "rebuild_recent30",
+            "vacuum_expired_presents",
             "unlock_user_item",
+            "export_user_data",
+            "reload_config",
         ]
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_progress_fraction() {
+        assert_eq!(progress_fraction(0, 0), None);
+        assert_eq!(progress_fraction(1, 4), Some(0.25));
+        assert_eq!(progress_fraction(5, 4), Some(1.0));
+
+        let progress = OperationProgress::default();
+        progress.set_total(2);
+        progress.advance(1);
+        assert_eq!(progress.fraction(), Some(0.5));
+    }
+
+    #[test]
+    fn test_clamp_world_progress() {
+        let steps = [10.0, 20.0, 30.0];
+        assert_eq!(clamp_world_progress(&steps, false, 0.0, 1, 5.0), (1, 5.0));
+        assert_eq!(clamp_world_progress(&steps, false, 0.0, 1, 25.0), (1, 20.0));
+        assert_eq!(clamp_world_progress(&steps, false, 0.0, 7, 5.0), (2, 0.0));
+        assert_eq!(clamp_world_progress(&steps, false, 0.0, -1, -3.0), (0, 0.0));
+        assert_eq!(
+            clamp_world_progress(&steps, true, 100.0, 1, 120.0),
+            (1, 100.0)
+        );
+        assert_eq!(clamp_world_progress(&[], false, 0.0, 3, 5.0), (0, 0.0));
+    }
+}
@@ -0,0 +1,141 @@
+use crate::config::CONFIG;
+use crate::error::ArcResult;
+use crate::model::item::ItemTypes;
+use crate::service::AssetManager;
+use crate::DbPool;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::sync::Arc;
+
+/// Owned and missing ids of one kind of unlockable content.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct OwnershipSet {
+    pub owned: Vec<String>,
+    pub missing: Vec<String>,
+}
+
+/// Which packs, singles and world songs a user owns versus what the server
+/// offers.
+#[derive(Debug, Clone, Serialize)]
+pub struct ContentOwnership {
+    pub packs: OwnershipSet,
+    pub singles: OwnershipSet,
+    pub world_songs: OwnershipSet,
+    /// Whether the songlist was loaded; without it only `purchase`
+    /// contents count as available.
+    pub has_songlist: bool,
+}
+
+/// Diffs a user's unlock items against the songlist and purchase table.
+#[derive(Clone)]
+pub struct OwnershipService {
+    pool: DbPool,
+    asset_manager: Arc<AssetManager>,
+}
+
+impl OwnershipService {
+    pub fn new(pool: DbPool, asset_manager: Arc<AssetManager>) -> Self {
+        Self {
+            pool,
+            asset_manager,
+        }
+    }
+
+    pub async fn get_content_ownership(&self, user_id: i32) -> ArcResult<ContentOwnership> {
+        let (mut packs, mut singles, mut world_songs) = self.asset_manager.get_songlist_content();
+
+        let purchasable = sqlx::query!(
+            "SELECT DISTINCT item_id, type AS item_type FROM purchase_item
+             WHERE type IN (?, ?)",
+            ItemTypes::PACK,
+            ItemTypes::SINGLE
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        for row in purchasable {
+            let (Some(item_id), Some(item_type)) = (row.item_id, row.item_type) else {
+                continue;
+            };
+            if item_type == ItemTypes::PACK {
+                packs.insert(item_id);
+            } else {
+                singles.insert(item_id);
+            }
+        }
+
+        let mut owned_packs = HashSet::new();
+        let mut owned_singles = HashSet::new();
+        let mut owned_world_songs = HashSet::new();
+        let owned = sqlx::query!(
+            "SELECT item_id, type AS item_type FROM user_item
+             WHERE user_id = ? AND type IN (?, ?, ?)",
+            user_id,
+            ItemTypes::PACK,
+            ItemTypes::SINGLE,
+            ItemTypes::WORLD_SONG
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        for row in owned {
+            let (Some(item_id), Some(item_type)) = (row.item_id, row.item_type) else {
+                continue;
+            };
+            match item_type.as_str() {
+                ItemTypes::PACK => owned_packs.insert(item_id),
+                ItemTypes::SINGLE => owned_singles.insert(item_id),
+                _ => owned_world_songs.insert(item_id),
+            };
+        }
+        world_songs.extend(self.world_song_items().await?);
+        if CONFIG.world_song_full_unlock {
+            owned_world_songs.extend(world_songs.iter().cloned());
+        }
+
+        Ok(ContentOwnership {
+            packs: diff_ownership(&packs, &owned_packs),
+            singles: diff_ownership(&singles, &owned_singles),
+            world_songs: diff_ownership(&world_songs, &owned_world_songs),
+            has_songlist: self.asset_manager.has_songlist(),
+        })
+    }
+
+    async fn world_song_items(&self) -> ArcResult<Vec<String>> {
+        Ok(sqlx::query_scalar!(
+            "SELECT item_id FROM item WHERE type = ? AND is_available = 1",
+            ItemTypes::WORLD_SONG
+        )
+        .fetch_all(&self.pool)
+        .await?)
+    }
+}
+
+/// Sorted owned ids, and available ids the user does not own.
+fn diff_ownership(available: &HashSet<String>, owned: &HashSet<String>) -> OwnershipSet {
+    let mut owned_ids = owned.iter().cloned().collect::<Vec<_>>();
+    owned_ids.sort();
+    let mut missing = available.difference(owned).cloned().collect::<Vec<_>>();
+    missing.sort();
+    OwnershipSet {
+        owned: owned_ids,
+        missing,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set(ids: &[&str]) -> HashSet<String> {
+        ids.iter().map(|id| id.to_string()).collect()
+    }
+
+    #[test]
+    fn test_diff_ownership() {
+        let diff = diff_ownership(&set(&["b", "a", "c"]), &set(&["c", "z"]));
+        assert_eq!(diff.owned, vec!["c", "z"]);
+        assert_eq!(diff.missing, vec!["a", "b"]);
+
+        let empty = diff_ownership(&set(&[]), &set(&[]));
+        assert_eq!(empty, OwnershipSet::default());
+    }
+}
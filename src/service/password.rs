@@ -0,0 +1,283 @@
+use crate::config::{Config, CONFIG};
+use crate::error::{ArcError, ArcResult};
+use crate::DbPool;
+use sha2::{Digest, Sha256};
+
+/// How `user.password` is hashed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PasswordAlgorithm {
+    /// Unsalted SHA-256 hex, as written by the Python server and older
+    /// versions of this one
+    Sha256,
+    Bcrypt,
+}
+
+impl PasswordAlgorithm {
+    /// Algorithm a stored hash was made with, `None` for unknown formats
+    pub fn of_hash(hash: &str) -> Option<Self> {
+        if ["$2a$", "$2b$", "$2x$", "$2y$"]
+            .iter()
+            .any(|prefix| hash.starts_with(prefix))
+        {
+            Some(Self::Bcrypt)
+        } else if hash.len() == 64 && hash.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+            Some(Self::Sha256)
+        } else {
+            None
+        }
+    }
+}
+
+/// Outcome of checking a password against a stored hash
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PasswordCheck {
+    Mismatch,
+    Match,
+    /// Matches a hash of another algorithm or cost than the configured
+    /// one, which should be replaced now that the password is known
+    Outdated,
+}
+
+impl PasswordCheck {
+    pub fn is_match(self) -> bool {
+        self != Self::Mismatch
+    }
+}
+
+/// Hashes passwords with the configured algorithm and verifies them
+/// against hashes of any supported algorithm.
+///
+/// Hashes of another algorithm or bcrypt cost still verify, reported as
+/// [`PasswordCheck::Outdated`], so logins can move accounts over to the
+/// configured algorithm one by one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PasswordHasher {
+    algorithm: PasswordAlgorithm,
+    bcrypt_cost: u32,
+}
+
+impl PasswordHasher {
+    pub fn new(algorithm: PasswordAlgorithm, bcrypt_cost: u32) -> Self {
+        Self {
+            algorithm,
+            bcrypt_cost,
+        }
+    }
+
+    /// Read the `password_hash_algorithm` and `password_bcrypt_cost` settings
+    pub fn from_config(config: &Config) -> ArcResult<Self> {
+        let algorithm = match config
+            .password_hash_algorithm
+            .trim()
+            .to_ascii_lowercase()
+            .as_str()
+        {
+            "" | "bcrypt" => PasswordAlgorithm::Bcrypt,
+            "sha256" => PasswordAlgorithm::Sha256,
+            other => {
+                return Err(ArcError::input(format!(
+                    "Invalid PASSWORD_HASH_ALGORITHM `{other}`, expected `bcrypt` or `sha256`"
+                )))
+            }
+        };
+        if !(4..=31).contains(&config.password_bcrypt_cost) {
+            return Err(ArcError::input(format!(
+                "Invalid PASSWORD_BCRYPT_COST {}, expected 4 to 31",
+                config.password_bcrypt_cost
+            )));
+        }
+        Ok(Self::new(algorithm, config.password_bcrypt_cost))
+    }
+
+    /// The hasher of the global config; startup rejects invalid settings,
+    /// so the fallback is never used by a running server
+    pub fn configured() -> Self {
+        Self::from_config(&CONFIG)
+            .unwrap_or_else(|_| Self::new(PasswordAlgorithm::Bcrypt, bcrypt::DEFAULT_COST))
+    }
+
+    pub fn algorithm(&self) -> PasswordAlgorithm {
+        self.algorithm
+    }
+
+    /// Hash `password`, off the async workers as bcrypt is slow on purpose
+    pub async fn hash(&self, password: &str) -> ArcResult<String> {
+        let hasher = *self;
+        let password = password.to_string();
+        tokio::task::spawn_blocking(move || hasher.hash_blocking(&password))
+            .await
+            .map_err(|e| ArcError::internal(format!("Password hashing task failed: {e}")))?
+    }
+
+    /// Check `password` against `stored`; an empty hash never matches
+    pub async fn verify(&self, password: &str, stored: &str) -> PasswordCheck {
+        let hasher = *self;
+        let password = password.to_string();
+        let stored = stored.to_string();
+        tokio::task::spawn_blocking(move || hasher.verify_blocking(&password, &stored))
+            .await
+            .unwrap_or(PasswordCheck::Mismatch)
+    }
+
+    fn hash_blocking(&self, password: &str) -> ArcResult<String> {
+        match self.algorithm {
+            PasswordAlgorithm::Sha256 => Ok(sha256_hex(password)),
+            PasswordAlgorithm::Bcrypt => bcrypt::hash(password, self.bcrypt_cost)
+                .map_err(|e| ArcError::internal(format!("Failed to hash password: {e}"))),
+        }
+    }
+
+    fn verify_blocking(&self, password: &str, stored: &str) -> PasswordCheck {
+        let matched = match PasswordAlgorithm::of_hash(stored) {
+            Some(PasswordAlgorithm::Sha256) => constant_time_eq(
+                sha256_hex(password).as_bytes(),
+                stored.to_ascii_lowercase().as_bytes(),
+            ),
+            // Compares in constant time itself
+            Some(PasswordAlgorithm::Bcrypt) => bcrypt::verify(password, stored).unwrap_or(false),
+            None => false,
+        };
+        if !matched {
+            PasswordCheck::Mismatch
+        } else if self.is_current(stored) {
+            PasswordCheck::Match
+        } else {
+            PasswordCheck::Outdated
+        }
+    }
+
+    /// Whether `stored` was made with the configured algorithm and cost
+    fn is_current(&self, stored: &str) -> bool {
+        match self.algorithm {
+            PasswordAlgorithm::Sha256 => {
+                PasswordAlgorithm::of_hash(stored) == Some(PasswordAlgorithm::Sha256)
+            }
+            PasswordAlgorithm::Bcrypt => stored
+                .get(4..6)
+                .and_then(|cost| cost.parse::<u32>().ok())
+                .is_some_and(|cost| {
+                    PasswordAlgorithm::of_hash(stored) == Some(PasswordAlgorithm::Bcrypt)
+                        && cost == self.bcrypt_cost
+                }),
+        }
+    }
+}
+
+/// Replace the hash of `user_id` with one of the configured algorithm after
+/// `password` matched an outdated hash, returning the new hash
+pub async fn upgrade_password_hash(
+    pool: &DbPool,
+    user_id: i32,
+    password: &str,
+) -> ArcResult<String> {
+    let hash = PasswordHasher::configured().hash(password).await?;
+    sqlx::query!(
+        "UPDATE user SET password = ? WHERE user_id = ?",
+        hash,
+        user_id
+    )
+    .execute(pool)
+    .await?;
+    Ok(hash)
+}
+
+fn sha256_hex(password: &str) -> String {
+    format!("{:x}", Sha256::digest(password.as_bytes()))
+}
+
+fn constant_time_eq(left: &[u8], right: &[u8]) -> bool {
+    left.len() == right.len()
+        && left
+            .iter()
+            .zip(right)
+            .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// SHA-256 of `admin`, the seeded admin password
+    const ADMIN_SHA256: &str = "8c6976e5b5410415bde908bd4dee15dfb167a9c873fc4bb8a81f6f2ab448a918";
+
+    #[test]
+    fn test_of_hash() {
+        assert_eq!(
+            PasswordAlgorithm::of_hash(ADMIN_SHA256),
+            Some(PasswordAlgorithm::Sha256)
+        );
+        let bcrypt_hash = bcrypt::hash("admin", 4).unwrap();
+        assert_eq!(
+            PasswordAlgorithm::of_hash(&bcrypt_hash),
+            Some(PasswordAlgorithm::Bcrypt)
+        );
+        assert_eq!(PasswordAlgorithm::of_hash(""), None);
+        assert_eq!(PasswordAlgorithm::of_hash("admin"), None);
+    }
+
+    #[test]
+    fn test_verify_bcrypt() {
+        let hasher = PasswordHasher::new(PasswordAlgorithm::Bcrypt, 4);
+        let hash = hasher.hash_blocking("arcaea2024").unwrap();
+        assert_ne!(hash, hasher.hash_blocking("arcaea2024").unwrap());
+        assert_eq!(
+            hasher.verify_blocking("arcaea2024", &hash),
+            PasswordCheck::Match
+        );
+        assert_eq!(
+            hasher.verify_blocking("arcaea2025", &hash),
+            PasswordCheck::Mismatch
+        );
+
+        // Another cost still verifies but asks for a re-hash
+        let stronger = PasswordHasher::new(PasswordAlgorithm::Bcrypt, 5);
+        assert_eq!(
+            stronger.verify_blocking("arcaea2024", &hash),
+            PasswordCheck::Outdated
+        );
+    }
+
+    #[test]
+    fn test_verify_legacy_sha256() {
+        let bcrypt = PasswordHasher::new(PasswordAlgorithm::Bcrypt, 4);
+        assert_eq!(
+            bcrypt.verify_blocking("admin", ADMIN_SHA256),
+            PasswordCheck::Outdated
+        );
+        assert_eq!(
+            bcrypt.verify_blocking("admin", &ADMIN_SHA256.to_uppercase()),
+            PasswordCheck::Outdated
+        );
+        assert_eq!(
+            bcrypt.verify_blocking("admin1", ADMIN_SHA256),
+            PasswordCheck::Mismatch
+        );
+
+        let sha256 = PasswordHasher::new(PasswordAlgorithm::Sha256, 4);
+        assert_eq!(sha256.hash_blocking("admin").unwrap(), ADMIN_SHA256);
+        assert_eq!(
+            sha256.verify_blocking("admin", ADMIN_SHA256),
+            PasswordCheck::Match
+        );
+        let bcrypt_hash = bcrypt.hash_blocking("admin").unwrap();
+        assert_eq!(
+            sha256.verify_blocking("admin", &bcrypt_hash),
+            PasswordCheck::Outdated
+        );
+    }
+
+    #[test]
+    fn test_verify_rejects_banned_accounts() {
+        let hasher = PasswordHasher::new(PasswordAlgorithm::Bcrypt, 4);
+        assert_eq!(hasher.verify_blocking("", ""), PasswordCheck::Mismatch);
+        assert_eq!(hasher.verify_blocking("admin", ""), PasswordCheck::Mismatch);
+    }
+
+    #[test]
+    fn test_constant_time_eq() {
+        assert!(constant_time_eq(b"abc", b"abc"));
+        assert!(!constant_time_eq(b"abc", b"abd"));
+        assert!(!constant_time_eq(b"abc", b"abcd"));
+    }
+}
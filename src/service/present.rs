@@ -1,36 +1,61 @@
 use crate::error::ArcError;
 use crate::model::item::ItemTypes;
 use crate::model::{Present, PresentItem};
-use crate::service::world::StaminaImpl;
-use sqlx::{MySql, MySqlPool, Transaction};
+use crate::service::stamina::StaminaImpl;
+use crate::service::user_cache::UserCache;
+use crate::{DbPool, DbTransaction};
+use std::collections::HashSet;
+
+/// Who receives a newly published present
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PresentRecipients {
+    AllUsers,
+    Users(Vec<i32>),
+}
 
 /// Present service for handling user present/gift system
 pub struct PresentService {
-    pool: MySqlPool,
+    pool: DbPool,
 }
 
 impl PresentService {
     /// Create a new present service instance
-    pub fn new(pool: MySqlPool) -> Self {
+    pub fn new(pool: DbPool) -> Self {
         Self { pool }
     }
 
     /// Get all non-expired presents for a user
     ///
     /// Returns a list of presents that are available to the user and not yet expired.
-    /// Expired presents are automatically filtered out.
+    /// Expired presents are automatically filtered out, as are web-only presents,
+    /// which are claimed from the player web dashboard instead of the game.
     pub async fn get_user_presents(&self, user_id: i32) -> Result<Vec<Present>, ArcError> {
+        self.load_user_presents(user_id, false).await
+    }
+
+    /// Get all non-expired web-only presents for a user
+    pub async fn get_user_web_presents(&self, user_id: i32) -> Result<Vec<Present>, ArcError> {
+        self.load_user_presents(user_id, true).await
+    }
+
+    async fn load_user_presents(
+        &self,
+        user_id: i32,
+        web_only: bool,
+    ) -> Result<Vec<Present>, ArcError> {
         let current_ts = chrono::Utc::now().timestamp_millis();
 
         // Get all presents for the user that haven't expired
         let present_records = sqlx::query!(
             r#"
-            SELECT p.*
+            SELECT p.present_id, p.expire_ts, p.description
             FROM present p
             INNER JOIN user_present up ON p.present_id = up.present_id
-            WHERE up.user_id = ? AND (p.expire_ts > ? OR p.expire_ts IS NULL)
+            WHERE up.user_id = ? AND p.is_web_only = ?
+              AND (p.expire_ts > ? OR p.expire_ts IS NULL)
             "#,
             user_id,
+            web_only,
             current_ts
         )
         .fetch_all(&self.pool)
@@ -196,7 +221,7 @@ impl PresentService {
     /// Grant an item to a user
     async fn grant_item_to_user(
         &self,
-        tx: &mut Transaction<'_, MySql>,
+        tx: &mut DbTransaction<'_>,
         user_id: i32,
         item_id: &str,
         item_type: &str,
@@ -234,6 +259,7 @@ impl PresentService {
                 )
                 .execute(&mut **tx)
                 .await?;
+                UserCache::global().invalidate_user(user_id);
                 Ok(())
             }
             ItemTypes::STAMINA6 => {
@@ -245,6 +271,7 @@ impl PresentService {
                 )
                 .execute(&mut **tx)
                 .await?;
+                UserCache::global().invalidate_user(user_id);
                 Ok(())
             }
             ItemTypes::STAMINA => self.grant_stamina_item_to_user(tx, user_id, amount).await,
@@ -256,7 +283,7 @@ impl PresentService {
 
     async fn grant_normal_item_to_user(
         &self,
-        tx: &mut Transaction<'_, MySql>,
+        tx: &mut DbTransaction<'_>,
         user_id: i32,
         item_id: &str,
         item_type: &str,
@@ -275,7 +302,7 @@ impl PresentService {
 
     async fn grant_positive_item_to_user(
         &self,
-        tx: &mut Transaction<'_, MySql>,
+        tx: &mut DbTransaction<'_>,
         user_id: i32,
         item_id: &str,
         item_type: &str,
@@ -293,13 +320,9 @@ impl PresentService {
         if let Some(row) = current_amount {
             let current_amount = row.amount.unwrap_or(0);
             if current_amount + amount < 0 {
-                return Err(ArcError::ItemNotEnough {
-                    message: format!("The user does not have enough `{item_id}`."),
-                    error_code: 108,
-                    api_error_code: -122,
-                    extra_data: None,
-                    status: 200,
-                });
+                return Err(ArcError::item_not_enough(format!(
+                    "The user does not have enough `{item_id}`."
+                )));
             }
 
             sqlx::query!(
@@ -334,7 +357,7 @@ impl PresentService {
 
     async fn grant_character_item_to_user(
         &self,
-        tx: &mut Transaction<'_, MySql>,
+        tx: &mut DbTransaction<'_>,
         user_id: i32,
         character_id: &str,
     ) -> Result<(), ArcError> {
@@ -365,7 +388,7 @@ impl PresentService {
 
     async fn grant_memory_item_to_user(
         &self,
-        tx: &mut Transaction<'_, MySql>,
+        tx: &mut DbTransaction<'_>,
         user_id: i32,
         amount: i32,
     ) -> Result<(), ArcError> {
@@ -383,13 +406,14 @@ impl PresentService {
         )
         .execute(&mut **tx)
         .await?;
+        UserCache::global().invalidate_user(user_id);
 
         Ok(())
     }
 
     async fn grant_stamina_item_to_user(
         &self,
-        tx: &mut Transaction<'_, MySql>,
+        tx: &mut DbTransaction<'_>,
         user_id: i32,
         amount: i32,
     ) -> Result<(), ArcError> {
@@ -418,10 +442,33 @@ impl PresentService {
         )
         .execute(&mut **tx)
         .await?;
+        UserCache::global().invalidate_user(user_id);
 
         Ok(())
     }
 
+    /// Claim a web-only present from the player web dashboard
+    pub async fn claim_web_present(&self, user_id: i32, present_id: &str) -> Result<(), ArcError> {
+        let is_web_only = sqlx::query_scalar!(
+            "SELECT EXISTS(SELECT 1 FROM present WHERE present_id = ? AND is_web_only = 1) as `exists!: i64`",
+            present_id
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| ArcError::Database {
+            message: format!("Failed to check web present: {e}"),
+        })?;
+
+        if is_web_only == 0 {
+            return Err(ArcError::no_data(
+                format!("Present '{present_id}' is not a web present"),
+                108,
+            ));
+        }
+
+        self.claim_present(user_id, present_id).await
+    }
+
     /// Check if a present exists
     pub async fn present_exists(&self, present_id: &str) -> Result<bool, ArcError> {
         let exists = sqlx::query_scalar!(
@@ -484,6 +531,25 @@ impl PresentService {
             message: format!("Failed to start transaction: {e}"),
         })?;
 
+        Self::create_present_in(&mut tx, present_id, expire_ts, description, items, user_id)
+            .await?;
+
+        tx.commit().await.map_err(|e| ArcError::Database {
+            message: format!("Failed to commit transaction: {e}"),
+        })?;
+
+        Ok(())
+    }
+
+    /// [`Self::create_present`] inside a caller's transaction
+    pub async fn create_present_in(
+        tx: &mut DbTransaction<'_>,
+        present_id: &str,
+        expire_ts: Option<i64>,
+        description: &str,
+        items: Vec<PresentItem>,
+        user_id: i32,
+    ) -> Result<(), ArcError> {
         // Insert present
         sqlx::query!(
             "INSERT INTO present (present_id, expire_ts, description) VALUES (?, ?, ?)",
@@ -491,7 +557,7 @@ impl PresentService {
             expire_ts,
             description
         )
-        .execute(&mut *tx)
+        .execute(&mut **tx)
         .await
         .map_err(|e| ArcError::Database {
             message: format!("Failed to create present: {e}"),
@@ -505,7 +571,7 @@ impl PresentService {
                 item.item_id,
                 item.item_type
             )
-            .execute(&mut *tx)
+            .execute(&mut **tx)
             .await
             .map_err(|e| ArcError::Database {
                 message: format!("Failed to insert item: {e}"),
@@ -519,7 +585,7 @@ impl PresentService {
                 item.item_type,
                 item.amount
             )
-            .execute(&mut *tx)
+            .execute(&mut **tx)
             .await
             .map_err(|e| ArcError::Database {
                 message: format!("Failed to insert present item: {e}"),
@@ -532,16 +598,161 @@ impl PresentService {
             user_id,
             present_id
         )
-        .execute(&mut *tx)
+        .execute(&mut **tx)
         .await
         .map_err(|e| ArcError::Database {
             message: format!("Failed to add present to user: {e}"),
         })?;
 
-        tx.commit().await.map_err(|e| ArcError::Database {
-            message: format!("Failed to commit transaction: {e}"),
-        })?;
-
         Ok(())
     }
+
+    /// Create a present with several items and deliver it to `recipients`
+    ///
+    /// Unlike [`Self::create_present`], every item must already exist in the
+    /// `item` table. Returns how many users received the present; unknown
+    /// user ids are skipped.
+    pub async fn publish_present(
+        &self,
+        present_id: &str,
+        expire_ts: Option<i64>,
+        description: &str,
+        items: &[PresentItem],
+        recipients: &PresentRecipients,
+        web_only: bool,
+    ) -> Result<u64, ArcError> {
+        validate_present_items(items)?;
+        if matches!(recipients, PresentRecipients::Users(users) if users.is_empty()) {
+            return Err(ArcError::input("A present needs at least one recipient."));
+        }
+        if self.present_exists(present_id).await? {
+            return Err(ArcError::input(format!(
+                "Present '{present_id}' already exists"
+            )));
+        }
+        for item in items {
+            let exists = sqlx::query_scalar!(
+                "SELECT EXISTS(SELECT 1 FROM item WHERE item_id = ? AND type = ?) as `exists!: i64`",
+                item.item_id,
+                item.item_type
+            )
+            .fetch_one(&self.pool)
+            .await?;
+            if exists == 0 {
+                return Err(ArcError::no_data(
+                    format!(
+                        "Item '{}' of type '{}' does not exist",
+                        item.item_id, item.item_type
+                    ),
+                    -2,
+                ));
+            }
+        }
+
+        let mut tx = self.pool.begin().await?;
+        sqlx::query!(
+            "INSERT INTO present (present_id, expire_ts, description, is_web_only) VALUES (?, ?, ?, ?)",
+            present_id,
+            expire_ts,
+            description,
+            web_only
+        )
+        .execute(&mut *tx)
+        .await?;
+        for item in items {
+            sqlx::query!(
+                "INSERT INTO present_item (present_id, item_id, type, amount) VALUES (?, ?, ?, ?)",
+                present_id,
+                item.item_id,
+                item.item_type,
+                item.amount
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        let delivered = match recipients {
+            PresentRecipients::AllUsers => sqlx::query!(
+                "INSERT INTO user_present (user_id, present_id) SELECT user_id, ? FROM user",
+                present_id
+            )
+            .execute(&mut *tx)
+            .await?
+            .rows_affected(),
+            PresentRecipients::Users(users) => {
+                let mut delivered = 0;
+                for user_id in users.iter().collect::<HashSet<_>>() {
+                    delivered += sqlx::query!(
+                        "INSERT INTO user_present (user_id, present_id)
+                         SELECT user_id, ? FROM user WHERE user_id = ?",
+                        present_id,
+                        user_id
+                    )
+                    .execute(&mut *tx)
+                    .await?
+                    .rows_affected();
+                }
+                delivered
+            }
+        };
+        tx.commit().await?;
+
+        Ok(delivered)
+    }
+}
+
+/// Reject empty item lists, non-positive amounts and repeated items.
+fn validate_present_items(items: &[PresentItem]) -> Result<(), ArcError> {
+    if items.is_empty() {
+        return Err(ArcError::input("A present needs at least one item."));
+    }
+    let mut seen = HashSet::new();
+    for item in items {
+        if item.item_id.trim().is_empty() || item.item_type.trim().is_empty() {
+            return Err(ArcError::input("Present items need an item id and type."));
+        }
+        if item.amount <= 0 {
+            return Err(ArcError::input(format!(
+                "Amount of item '{}' must be positive.",
+                item.item_id
+            )));
+        }
+        if !seen.insert((item.item_id.as_str(), item.item_type.as_str())) {
+            return Err(ArcError::input(format!(
+                "Item '{}' is listed more than once.",
+                item.item_id
+            )));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(item_id: &str, item_type: &str, amount: i32) -> PresentItem {
+        PresentItem {
+            present_id: "gift".to_string(),
+            item_id: item_id.to_string(),
+            item_type: item_type.to_string(),
+            amount,
+        }
+    }
+
+    #[test]
+    fn test_validate_present_items() {
+        assert!(validate_present_items(&[]).is_err());
+        assert!(validate_present_items(&[item("memory", "memory", 0)]).is_err());
+        assert!(validate_present_items(&[
+            item("fragment", "fragment", 100),
+            item("fragment", "fragment", 50)
+        ])
+        .is_err());
+        assert!(validate_present_items(&[
+            item("memory", "memory", 300),
+            item("core_generic", "core", 5)
+        ])
+        .is_ok());
+    }
 }
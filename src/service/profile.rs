@@ -0,0 +1,201 @@
+use crate::error::{ArcError, ArcResult};
+use crate::DbPool;
+use serde::Serialize;
+
+const DIFFICULTY_NAMES: [&str; 5] = ["PST", "PRS", "FTR", "BYD", "ETR"];
+const CLEAR_TYPE_NAMES: [&str; 6] = ["TL", "NC", "FR", "PM", "EC", "HC"];
+const PUBLIC_RECENT_PLAY_LIMIT: i64 = 10;
+
+/// One chart result shown on a public profile.
+#[derive(Debug, Clone, Serialize)]
+pub struct ProfileScore {
+    pub song_id: String,
+    pub song_name: String,
+    pub difficulty: String,
+    pub score: i32,
+    pub pure: i32,
+    pub shiny_pure: i32,
+    pub far: i32,
+    pub lost: i32,
+    pub clear_type: String,
+    pub rating: f64,
+    pub time_played: String,
+}
+
+/// Read-only player summary for the profile pages.
+#[derive(Debug, Clone, Serialize)]
+pub struct PublicProfile {
+    pub name: String,
+    pub user_code: String,
+    pub join_date: String,
+    /// Potential as shown in game, or `None` when the player hides it.
+    pub potential: Option<String>,
+    pub best_30_average: f64,
+    pub b30: Vec<ProfileScore>,
+    pub recent: Vec<ProfileScore>,
+}
+
+/// Builds public profile data for players who opted in through
+/// `is_profile_public`.
+#[derive(Debug, Clone)]
+pub struct ProfileService {
+    pool: DbPool,
+}
+
+impl ProfileService {
+    pub fn new(pool: DbPool) -> Self {
+        Self { pool }
+    }
+
+    /// Load a public profile by friend code.
+    ///
+    /// Unknown players and players with a private profile both yield the same
+    /// `NoData` error, so the page does not reveal whether a code exists.
+    pub async fn get_public_profile(&self, user_code: &str) -> ArcResult<PublicProfile> {
+        let user_id = sqlx::query!(
+            "SELECT user_id, is_profile_public FROM user WHERE user_code = ?",
+            user_code
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .filter(|row| row.is_profile_public.unwrap_or(0) != 0)
+        .map(|row| row.user_id)
+        .ok_or_else(|| ArcError::no_data_status("No public profile.", 401, 404))?;
+
+        self.load_profile(user_id, false).await
+    }
+
+    /// Load a player's own profile; potential is shown even when hidden.
+    pub async fn get_player_profile(&self, user_id: i32) -> ArcResult<PublicProfile> {
+        self.load_profile(user_id, true).await
+    }
+
+    async fn load_profile(
+        &self,
+        user_id: i32,
+        show_hidden_rating: bool,
+    ) -> ArcResult<PublicProfile> {
+        let user = sqlx::query!(
+            "SELECT name, user_code, join_date, rating_ptt, is_hide_rating
+             FROM user WHERE user_id = ?",
+            user_id
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or_else(|| ArcError::no_data_status("No public profile.", 401, 404))?;
+
+        let b30 = self.get_best_scores(user_id, 0, 30).await?;
+
+        let recent = sqlx::query!(
+            "SELECT us.song_id, c.name AS song_name, us.difficulty, us.score,
+                    us.shiny_perfect_count, us.perfect_count, us.near_count, us.miss_count,
+                    us.clear_type, us.rating, us.time_played
+             FROM user_score us
+             LEFT JOIN chart c ON c.song_id = us.song_id
+             WHERE us.user_id = ?
+             ORDER BY us.time_played DESC
+             LIMIT ?",
+            user_id,
+            PUBLIC_RECENT_PLAY_LIMIT
+        )
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(|row| ProfileScore {
+            song_name: row.song_name.unwrap_or_else(|| row.song_id.clone()),
+            song_id: row.song_id,
+            difficulty: difficulty_name(row.difficulty),
+            score: row.score.unwrap_or(0),
+            pure: row.perfect_count.unwrap_or(0),
+            shiny_pure: row.shiny_perfect_count.unwrap_or(0),
+            far: row.near_count.unwrap_or(0),
+            lost: row.miss_count.unwrap_or(0),
+            clear_type: clear_type_name(row.clear_type.unwrap_or(0)),
+            rating: row.rating.unwrap_or(0.0),
+            time_played: format_time(Some(row.time_played)),
+        })
+        .collect::<Vec<_>>();
+
+        let best_30_average = if b30.is_empty() {
+            0.0
+        } else {
+            b30.iter().map(|score| score.rating).sum::<f64>() / b30.len() as f64
+        };
+        let potential = (show_hidden_rating || user.is_hide_rating.unwrap_or(0) == 0)
+            .then(|| format!("{:.2}", user.rating_ptt.unwrap_or(0) as f64 / 100.0));
+
+        Ok(PublicProfile {
+            name: user.name.unwrap_or_default(),
+            user_code: user.user_code.unwrap_or_default(),
+            join_date: format_time(user.join_date),
+            potential,
+            best_30_average,
+            b30,
+            recent,
+        })
+    }
+
+    /// Best scores of a player ordered by rating, one page at a time.
+    pub async fn get_best_scores(
+        &self,
+        user_id: i32,
+        offset: i64,
+        limit: i64,
+    ) -> ArcResult<Vec<ProfileScore>> {
+        let scores = sqlx::query!(
+            "SELECT bs.song_id, c.name AS song_name, bs.difficulty, bs.score,
+                    bs.shiny_perfect_count, bs.perfect_count, bs.near_count, bs.miss_count,
+                    bs.best_clear_type, bs.rating, bs.time_played
+             FROM best_score bs
+             LEFT JOIN chart c ON c.song_id = bs.song_id
+             WHERE bs.user_id = ?
+             ORDER BY bs.rating DESC, bs.score DESC
+             LIMIT ? OFFSET ?",
+            user_id,
+            limit,
+            offset
+        )
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(|row| ProfileScore {
+            song_name: row.song_name.unwrap_or_else(|| row.song_id.clone()),
+            song_id: row.song_id,
+            difficulty: difficulty_name(row.difficulty),
+            score: row.score.unwrap_or(0),
+            pure: row.perfect_count.unwrap_or(0),
+            shiny_pure: row.shiny_perfect_count.unwrap_or(0),
+            far: row.near_count.unwrap_or(0),
+            lost: row.miss_count.unwrap_or(0),
+            clear_type: clear_type_name(row.best_clear_type.unwrap_or(0)),
+            rating: row.rating.unwrap_or(0.0),
+            time_played: format_time(row.time_played),
+        })
+        .collect();
+
+        Ok(scores)
+    }
+}
+
+fn difficulty_name(difficulty: i32) -> String {
+    usize::try_from(difficulty)
+        .ok()
+        .and_then(|index| DIFFICULTY_NAMES.get(index))
+        .map(|name| name.to_string())
+        .unwrap_or_else(|| difficulty.to_string())
+}
+
+fn clear_type_name(clear_type: i32) -> String {
+    usize::try_from(clear_type)
+        .ok()
+        .and_then(|index| CLEAR_TYPE_NAMES.get(index))
+        .map(|name| name.to_string())
+        .unwrap_or_else(|| clear_type.to_string())
+}
+
+pub(crate) fn format_time(timestamp_ms: Option<i64>) -> String {
+    timestamp_ms
+        .and_then(chrono::DateTime::from_timestamp_millis)
+        .map(|time| time.format("%Y-%m-%d %H:%M").to_string())
+        .unwrap_or_default()
+}
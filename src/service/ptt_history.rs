@@ -0,0 +1,326 @@
+use crate::error::{ArcError, ArcResult};
+use crate::utils::{current_timestamp_ms, today_timestamp_seconds};
+use crate::DbPool;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+const DAY_SECONDS: i64 = 24 * 60 * 60;
+
+/// One recent30 row as stored in a snapshot
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Recent30Entry {
+    pub song_id: String,
+    pub difficulty: i32,
+    pub score: i32,
+    pub clear_type: i32,
+    pub rating: f64,
+    pub time_played: i64,
+}
+
+impl Recent30Entry {
+    /// A play is the same play in two snapshots when chart and time match
+    fn key(&self) -> (&str, i32, i64) {
+        (&self.song_id, self.difficulty, self.time_played)
+    }
+}
+
+/// Potential of a player on one day
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PttHistoryPoint {
+    /// Local midnight in seconds
+    pub day: i64,
+    /// Potential times 100, as in `user.rating_ptt`
+    pub rating_ptt: i32,
+    pub best30: f64,
+    pub recent10: f64,
+}
+
+/// A day's snapshot with the recent 30 plays it was taken from
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PttSnapshot {
+    pub point: PttHistoryPoint,
+    pub recent30: Vec<Recent30Entry>,
+}
+
+/// Plays that entered and left recent 30 between two snapshots
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Recent30Diff {
+    /// In the later snapshot only, newest first
+    pub added: Vec<Recent30Entry>,
+    /// In the earlier snapshot only, newest first
+    pub removed: Vec<Recent30Entry>,
+    /// Plays in both snapshots
+    pub unchanged: usize,
+}
+
+/// Daily potential and recent 30 snapshots in `ptt_history`, charted from
+/// the admin panel.
+///
+/// [`take_snapshots`](Self::take_snapshots) runs once a day and only writes
+/// players who played since their last snapshot; running it again on the
+/// same day replaces that day's row. Rows older than the retention are
+/// removed by [`clear_expired`](Self::clear_expired).
+#[derive(Clone)]
+pub struct PttHistoryService {
+    pool: DbPool,
+    retention_days: i64,
+}
+
+impl PttHistoryService {
+    pub fn new(pool: DbPool, retention_days: i64) -> Self {
+        Self {
+            pool,
+            retention_days,
+        }
+    }
+
+    /// Whether snapshots are taken, i.e. the retention is not 0
+    pub fn is_enabled(&self) -> bool {
+        self.retention_days > 0
+    }
+
+    /// Snapshot every player whose recent 30 changed since their last
+    /// snapshot, returning the number of players written
+    pub async fn take_snapshots(&self) -> ArcResult<u64> {
+        let user_ids = sqlx::query_scalar!(
+            "SELECT r.user_id AS `user_id!: i32`
+             FROM recent30 r
+             LEFT JOIN (
+                 SELECT user_id, MAX(created_at) AS last_snapshot
+                 FROM ptt_history
+                 GROUP BY user_id
+             ) h ON h.user_id = r.user_id
+             WHERE r.song_id != ''
+             GROUP BY r.user_id
+             HAVING MAX(r.time_played) > COALESCE(MAX(h.last_snapshot), 0)"
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let day = today_timestamp_seconds();
+        let mut written = 0;
+        for user_id in user_ids {
+            match self.snapshot_user(user_id, day).await {
+                Ok(()) => written += 1,
+                Err(e) => log::warn!("PTT snapshot of user {user_id} failed: {e}"),
+            }
+        }
+        Ok(written)
+    }
+
+    async fn snapshot_user(&self, user_id: i32, day: i64) -> ArcResult<()> {
+        let recent30: Vec<Recent30Entry> = sqlx::query!(
+            "SELECT song_id, difficulty, score, clear_type, rating, time_played
+             FROM recent30
+             WHERE user_id = ? AND song_id != ''
+             ORDER BY time_played DESC",
+            user_id
+        )
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(|row| Recent30Entry {
+            song_id: row.song_id.unwrap_or_default(),
+            difficulty: row.difficulty.unwrap_or(0),
+            score: row.score.unwrap_or(0),
+            clear_type: row.clear_type.unwrap_or(0),
+            rating: row.rating.unwrap_or(0.0),
+            time_played: row.time_played.unwrap_or(0),
+        })
+        .collect();
+
+        let user = sqlx::query!(
+            "SELECT u.rating_ptt,
+                    (SELECT COALESCE(SUM(rating), 0) FROM (
+                        SELECT COALESCE(rating, 0) AS rating FROM best_score
+                        WHERE user_id = ? ORDER BY rating DESC LIMIT 30
+                    ) b30) AS `best_30_sum!: f64`
+             FROM user u
+             WHERE u.user_id = ?",
+            user_id,
+            user_id
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        let recent30_json = serde_json::to_string(&recent30)
+            .map_err(|e| ArcError::internal(format!("Failed to encode recent30: {e}")))?;
+        sqlx::query!(
+            "INSERT INTO ptt_history (user_id, day, rating_ptt, best30, recent10, recent30, created_at)
+             VALUES (?, ?, ?, ?, ?, ?, ?)
+             ON DUPLICATE KEY UPDATE rating_ptt = VALUES(rating_ptt), best30 = VALUES(best30),
+                recent10 = VALUES(recent10), recent30 = VALUES(recent30),
+                created_at = VALUES(created_at)",
+            user_id,
+            day,
+            user.rating_ptt.unwrap_or(0),
+            user.best_30_sum / 30.0,
+            recent10_average(&recent30),
+            recent30_json,
+            current_timestamp_ms()
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// A player's potential per day over the last `days` days, oldest first
+    pub async fn history(&self, user_id: i32, days: i64) -> ArcResult<Vec<PttHistoryPoint>> {
+        let since = today_timestamp_seconds() - days.max(0) * DAY_SECONDS;
+        Ok(sqlx::query_as!(
+            PttHistoryPoint,
+            "SELECT day, rating_ptt, best30, recent10
+             FROM ptt_history
+             WHERE user_id = ? AND day >= ?
+             ORDER BY day ASC",
+            user_id,
+            since
+        )
+        .fetch_all(&self.pool)
+        .await?)
+    }
+
+    /// The snapshot of `day`, if one was taken
+    pub async fn snapshot(&self, user_id: i32, day: i64) -> ArcResult<Option<PttSnapshot>> {
+        let Some(row) = sqlx::query!(
+            "SELECT day, rating_ptt, best30, recent10, recent30
+             FROM ptt_history
+             WHERE user_id = ? AND day = ?",
+            user_id,
+            day
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        else {
+            return Ok(None);
+        };
+
+        let recent30 = serde_json::from_str(&row.recent30).unwrap_or_else(|e| {
+            log::warn!("Corrupt recent30 snapshot of user {user_id} on {day}: {e}");
+            Vec::new()
+        });
+        Ok(Some(PttSnapshot {
+            point: PttHistoryPoint {
+                day: row.day,
+                rating_ptt: row.rating_ptt,
+                best30: row.best30,
+                recent10: row.recent10,
+            },
+            recent30,
+        }))
+    }
+
+    /// Delete snapshots older than the retention.
+    pub async fn clear_expired(&self) -> ArcResult<u64> {
+        let cutoff = today_timestamp_seconds() - self.retention_days * DAY_SECONDS;
+        Ok(
+            sqlx::query!("DELETE FROM ptt_history WHERE day < ?", cutoff)
+                .execute(&self.pool)
+                .await?
+                .rows_affected(),
+        )
+    }
+}
+
+/// Average of the best rating of the 10 best charts among `recent30`, as the
+/// recent 10 part of potential counts them
+pub fn recent10_average(recent30: &[Recent30Entry]) -> f64 {
+    let mut best: HashMap<(&str, i32), f64> = HashMap::new();
+    for entry in recent30 {
+        let rating = best
+            .entry((&entry.song_id, entry.difficulty))
+            .or_insert(entry.rating);
+        *rating = rating.max(entry.rating);
+    }
+    let mut ratings: Vec<f64> = best.into_values().collect();
+    ratings.sort_unstable_by(|a, b| b.total_cmp(a));
+    ratings.iter().take(10).sum::<f64>() / 10.0
+}
+
+/// Plays added to and removed from recent 30 going from `from` to `to`
+pub fn diff_recent30(from: &[Recent30Entry], to: &[Recent30Entry]) -> Recent30Diff {
+    let from_keys: HashSet<_> = from.iter().map(Recent30Entry::key).collect();
+    let to_keys: HashSet<_> = to.iter().map(Recent30Entry::key).collect();
+    let newest_first = |entries: Vec<Recent30Entry>| {
+        let mut entries = entries;
+        entries.sort_by(|a, b| b.time_played.cmp(&a.time_played));
+        entries
+    };
+
+    let added: Vec<_> = to
+        .iter()
+        .filter(|entry| !from_keys.contains(&entry.key()))
+        .cloned()
+        .collect();
+    let removed: Vec<_> = from
+        .iter()
+        .filter(|entry| !to_keys.contains(&entry.key()))
+        .cloned()
+        .collect();
+    Recent30Diff {
+        unchanged: to.len() - added.len(),
+        added: newest_first(added),
+        removed: newest_first(removed),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn play(song_id: &str, difficulty: i32, rating: f64, time_played: i64) -> Recent30Entry {
+        Recent30Entry {
+            song_id: song_id.to_string(),
+            difficulty,
+            score: 9_900_000,
+            clear_type: 1,
+            rating,
+            time_played,
+        }
+    }
+
+    #[test]
+    fn test_recent10_average() {
+        assert_eq!(recent10_average(&[]), 0.0);
+
+        // Only the best play of a chart counts
+        let plays = [
+            play("grievouslady", 2, 11.0, 1),
+            play("grievouslady", 2, 12.0, 2),
+            play("grievouslady", 3, 10.0, 3),
+        ];
+        assert!((recent10_average(&plays) - 2.2).abs() < 1e-9);
+
+        let many: Vec<_> = (0..15)
+            .map(|i| play(&format!("song{i}"), 2, i as f64, i))
+            .collect();
+        // Ratings 5..=14
+        assert!((recent10_average(&many) - 9.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_diff_recent30() {
+        let from = [
+            play("fractureray", 2, 11.0, 10),
+            play("tempestissimo", 3, 12.0, 20),
+        ];
+        let to = [
+            play("tempestissimo", 3, 12.0, 20),
+            play("grievouslady", 2, 11.5, 30),
+            play("fractureray", 2, 11.2, 40),
+        ];
+
+        let diff = diff_recent30(&from, &to);
+        assert_eq!(diff.unchanged, 1);
+        assert_eq!(
+            diff.added
+                .iter()
+                .map(|entry| entry.time_played)
+                .collect::<Vec<_>>(),
+            vec![40, 30]
+        );
+        assert_eq!(diff.removed, vec![play("fractureray", 2, 11.0, 10)]);
+
+        assert_eq!(diff_recent30(&to, &to).added, Vec::new());
+    }
+}
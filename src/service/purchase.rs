@@ -1,35 +1,231 @@
-use crate::config::Constants;
 use crate::error::{ArcError, ArcResult};
 use crate::model::item::ItemTypes;
+use crate::model::{PurchaseLogEntry, RedeemBatch, RedeemItem, RedeemKind};
 use crate::service::cache::{env_ttl_seconds, CacheService};
+use crate::service::event::limited_events;
+use crate::service::game_constants::game_constants;
+use crate::service::stamina::{StaminaService, STAMINA_PURCHASE_AMOUNT};
 use crate::service::{ItemService, UserService};
+use crate::utils::{hmac_sha256_sign, hmac_sha256_verify};
+use crate::DbPool;
+use async_trait::async_trait;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
-use sqlx::{MySql, Pool};
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::collections::HashSet;
+use std::env;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Most purchase history entries returned at once.
+pub const MAX_PURCHASE_HISTORY: i64 = 200;
+/// Most codes generated in one redeem batch.
+pub const MAX_REDEEM_BATCH_SIZE: usize = 10000;
+/// Header carrying the hex HMAC-SHA256 of a payment request or callback body.
+pub const PAYMENT_SIGNATURE_HEADER: &str = "X-Payment-Signature";
+const PAYMENT_REQUEST_TIMEOUT_SECONDS: u64 = 10;
+
+const ORDER_PENDING: &str = "pending";
+/// Claimed by one request while its items are granted.
+const ORDER_DELIVERING: &str = "delivering";
+const ORDER_PAID: &str = "paid";
+const ORDER_FAILED: &str = "failed";
+
+/// A pack or single purchase to be paid through a [`PaymentProvider`].
+#[derive(Debug, Clone, Serialize)]
+pub struct PaymentOrder {
+    pub order_id: String,
+    pub user_id: i32,
+    pub purchase_name: String,
+    /// Price in memories as configured for the purchase.
+    pub price: i32,
+}
+
+/// Result of starting a payment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PaymentOutcome {
+    /// Settled immediately; the purchase is granted right away.
+    Paid,
+    /// Settled later through `/purchase/callback/<provider>`.
+    Pending,
+}
+
+/// Settlement of an order reported by a provider callback.
+#[derive(Debug, Clone, Deserialize)]
+pub struct PaymentCallback {
+    pub order_id: String,
+    pub paid: bool,
+}
+
+/// External payment system that pack and single purchases are charged to
+/// instead of memories.
+#[async_trait]
+pub trait PaymentProvider: Send + Sync {
+    /// Name used by `PAYMENT_PROVIDER`, the callback route and the purchase log.
+    fn name(&self) -> &'static str;
+
+    /// Start charging the user for `order`.
+    async fn charge(&self, order: &PaymentOrder) -> ArcResult<PaymentOutcome>;
+
+    /// Authenticate and decode a callback body. Providers that settle every
+    /// payment in [`Self::charge`] do not accept callbacks.
+    fn parse_callback(&self, _body: &[u8], _signature: Option<&str>) -> ArcResult<PaymentCallback> {
+        Err(ArcError::no_access(
+            format!(
+                "Payment provider `{}` does not accept callbacks.",
+                self.name()
+            ),
+            108,
+        ))
+    }
+}
+
+/// Grants every purchase without charging anything.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FreePaymentProvider;
+
+#[async_trait]
+impl PaymentProvider for FreePaymentProvider {
+    fn name(&self) -> &'static str {
+        "free"
+    }
+
+    async fn charge(&self, _order: &PaymentOrder) -> ArcResult<PaymentOutcome> {
+        Ok(PaymentOutcome::Paid)
+    }
+}
+
+/// Forwards orders to an external payment system and waits for it to call
+/// back once the player has paid.
+///
+/// Orders are POSTed as JSON to `PAYMENT_WEBHOOK_URL`; callbacks are JSON
+/// objects `{"order_id": "...", "paid": true}`. Both directions carry
+/// [`PAYMENT_SIGNATURE_HEADER`] keyed with `PAYMENT_WEBHOOK_SECRET`.
+pub struct WebhookPaymentProvider {
+    url: String,
+    secret: String,
+    http: reqwest::Client,
+}
+
+impl WebhookPaymentProvider {
+    pub fn new(url: String, secret: String) -> Self {
+        Self {
+            url,
+            secret,
+            http: reqwest::Client::builder()
+                .timeout(Duration::from_secs(PAYMENT_REQUEST_TIMEOUT_SECONDS))
+                .build()
+                .unwrap_or_default(),
+        }
+    }
+}
+
+#[async_trait]
+impl PaymentProvider for WebhookPaymentProvider {
+    fn name(&self) -> &'static str {
+        "webhook"
+    }
+
+    async fn charge(&self, order: &PaymentOrder) -> ArcResult<PaymentOutcome> {
+        let body = serde_json::to_vec(order)?;
+        let response = self
+            .http
+            .post(&self.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(
+                PAYMENT_SIGNATURE_HEADER,
+                hmac_sha256_sign(&self.secret, &body),
+            )
+            .body(body)
+            .send()
+            .await
+            .map_err(|e| ArcError::input(format!("Payment provider request failed: {e}")))?;
+        if !response.status().is_success() {
+            return Err(ArcError::input(format!(
+                "Payment provider rejected the order: {}",
+                response.status()
+            )));
+        }
+        Ok(PaymentOutcome::Pending)
+    }
+
+    fn parse_callback(&self, body: &[u8], signature: Option<&str>) -> ArcResult<PaymentCallback> {
+        let signature =
+            signature.ok_or_else(|| ArcError::no_access("Missing payment signature.", 108))?;
+        if !hmac_sha256_verify(&self.secret, body, signature) {
+            return Err(ArcError::no_access("Invalid payment signature.", 108));
+        }
+        serde_json::from_slice(body)
+            .map_err(|e| ArcError::input(format!("Invalid payment callback: {e}")))
+    }
+}
+
+/// Payment provider selected by `PAYMENT_PROVIDER`; `None` keeps purchases
+/// paid with memories.
+pub fn payment_provider_from_env() -> ArcResult<Option<Arc<dyn PaymentProvider>>> {
+    match env::var("PAYMENT_PROVIDER").unwrap_or_default().trim() {
+        "" | "memory" => Ok(None),
+        "free" => Ok(Some(Arc::new(FreePaymentProvider))),
+        "webhook" => {
+            let url = env::var("PAYMENT_WEBHOOK_URL")
+                .unwrap_or_default()
+                .trim()
+                .to_string();
+            let secret = env::var("PAYMENT_WEBHOOK_SECRET").unwrap_or_default();
+            if url.is_empty() || secret.trim().is_empty() {
+                return Err(ArcError::input(
+                    "PAYMENT_WEBHOOK_URL and PAYMENT_WEBHOOK_SECRET must be set for the webhook payment provider",
+                ));
+            }
+            Ok(Some(Arc::new(WebhookPaymentProvider::new(url, secret))))
+        }
+        other => Err(ArcError::input(format!(
+            "Unknown PAYMENT_PROVIDER `{other}`"
+        ))),
+    }
+}
+
+/// One row of `purchase_item`.
+struct PurchaseItemRow {
+    item_id: String,
+    item_type: String,
+    amount: Option<i32>,
+}
 
 /// Purchase service for handling purchase system operations
 pub struct PurchaseService {
-    pool: Pool<MySql>,
+    pool: DbPool,
     item_service: ItemService,
     user_service: UserService,
+    stamina_service: StaminaService,
     cache: Option<CacheService>,
     purchase_cache_ttl_seconds: u64,
+    payment: Option<Arc<dyn PaymentProvider>>,
 }
 
 impl PurchaseService {
     /// Create a new purchase service instance
-    pub fn new(pool: Pool<MySql>) -> Self {
+    pub fn new(pool: DbPool) -> Self {
         let item_service = ItemService::new(pool.clone());
         let user_service = UserService::new(pool.clone());
+        let stamina_service = StaminaService::new(pool.clone());
         Self {
             pool,
             item_service,
             user_service,
+            stamina_service,
             cache: None,
             purchase_cache_ttl_seconds: env_ttl_seconds("REDIS_PURCHASE_TTL_SECONDS", 10),
+            payment: None,
         }
     }
 
+    /// Charge priced packs and singles through `provider` instead of memories
+    pub fn with_payment_provider(mut self, provider: Option<Arc<dyn PaymentProvider>>) -> Self {
+        self.payment = provider;
+        self
+    }
+
     pub fn with_cache(mut self, cache: Option<CacheService>) -> Self {
         self.cache = cache.clone();
         self.user_service = self.user_service.with_cache(cache);
@@ -55,6 +251,25 @@ impl PurchaseService {
         self.get_cached_purchases_by_type(user_id, "pack").await
     }
 
+    /// Get the packs currently on sale for user
+    ///
+    /// Only packs inside an open discount window are listed, priced for the
+    /// user, so a pack the user holds an `anni5tix` for shows up at 0.
+    pub async fn get_discounted_pack_purchases(&self, user_id: i32) -> ArcResult<Vec<Value>> {
+        let now = Self::current_timestamp();
+        let packs = self.get_pack_purchases(user_id).await?;
+        Ok(packs
+            .into_iter()
+            .filter(|pack| {
+                discount_active(
+                    pack["discount_from"].as_i64().unwrap_or(-1),
+                    pack["discount_to"].as_i64().unwrap_or(-1),
+                    now,
+                )
+            })
+            .collect())
+    }
+
     /// Get single song purchase information for user
     ///
     /// Returns available single song purchases with pricing and discount information.
@@ -139,9 +354,16 @@ impl PurchaseService {
         .fetch_optional(&self.pool)
         .await?;
 
-        let purchase_info = purchase_info.ok_or_else(|| {
+        let mut purchase_info = purchase_info.ok_or_else(|| {
             ArcError::no_data(format!("Purchase `{purchase_name}` does not exist."), 501)
         })?;
+        if let Some((from, to)) =
+            limited_events().pick_ticket_window(purchase_name, Self::current_timestamp())
+        {
+            purchase_info.discount_from = Some(from);
+            purchase_info.discount_to = Some(to);
+            purchase_info.discount_reason = Some(ItemTypes::PICK_TICKET.to_string());
+        }
 
         // Get purchase items
         let purchase_items = sqlx::query!(
@@ -176,13 +398,21 @@ impl PurchaseService {
         }
 
         // Calculate displayed price with discounts
+        let item_types = items
+            .iter()
+            .filter_map(|item| item["type"].as_str())
+            .collect::<Vec<_>>();
+        let discount_reason = effective_discount_reason(
+            purchase_info.discount_reason.as_deref().unwrap_or(""),
+            &item_types,
+        );
         let displayed_price = self
             .calculate_displayed_price(
                 purchase_info.price.unwrap_or(0),
                 purchase_info.orig_price.unwrap_or(0),
                 purchase_info.discount_from.unwrap_or(-1),
                 purchase_info.discount_to.unwrap_or(-1),
-                purchase_info.discount_reason.as_deref().unwrap_or(""),
+                discount_reason,
                 user_id,
             )
             .await?;
@@ -201,15 +431,7 @@ impl PurchaseService {
             purchase_json["discount_from"] = json!(purchase_info.discount_from);
             purchase_json["discount_to"] = json!(purchase_info.discount_to);
 
-            let default_reason = String::new();
-            let discount_reason = purchase_info
-                .discount_reason
-                .as_ref()
-                .unwrap_or(&default_reason);
-            if !discount_reason.is_empty()
-                && (discount_reason == "anni5tix" || discount_reason == "pick_ticket")
-                && displayed_price == 0
-            {
+            if !discount_reason.is_empty() && displayed_price == 0 {
                 purchase_json["discount_reason"] = json!(discount_reason);
             }
         }
@@ -218,6 +440,8 @@ impl PurchaseService {
     }
 
     /// Calculate displayed price considering discounts
+    ///
+    /// Looks up the user's ticket only while the discount window is open.
     async fn calculate_displayed_price(
         &self,
         price: i32,
@@ -227,34 +451,24 @@ impl PurchaseService {
         discount_reason: &str,
         user_id: i32,
     ) -> ArcResult<i32> {
-        if discount_from > 0 && discount_to > 0 {
-            let now = Self::current_timestamp();
-            if discount_from <= now && now <= discount_to {
-                match discount_reason {
-                    "anni5tix" => {
-                        let amount = self
-                            .item_service
-                            .get_user_positive_item_amount(user_id, "anni5tix", "anni5tix")
-                            .await?;
-                        if amount >= 1 {
-                            return Ok(0);
-                        }
-                    }
-                    "pick_ticket" => {
-                        let amount = self
-                            .item_service
-                            .get_user_positive_item_amount(user_id, "pick_ticket", "pick_ticket")
-                            .await?;
-                        if amount >= 1 {
-                            return Ok(0);
-                        }
-                    }
-                    _ => {}
-                }
-                return Ok(price);
-            }
-        }
-        Ok(orig_price)
+        let now = Self::current_timestamp();
+        let has_ticket =
+            if discount_active(discount_from, discount_to, now) && !discount_reason.is_empty() {
+                self.item_service
+                    .get_user_positive_item_amount(user_id, discount_reason, discount_reason)
+                    .await?
+                    >= 1
+            } else {
+                false
+            };
+        Ok(discounted_price(
+            price,
+            orig_price,
+            discount_from,
+            discount_to,
+            now,
+            has_ticket,
+        ))
     }
 
     /// Buy pack or single item
@@ -269,33 +483,33 @@ impl PurchaseService {
         .fetch_optional(&self.pool)
         .await?;
 
-        let purchase_info = purchase_info.ok_or_else(|| {
+        let mut purchase_info = purchase_info.ok_or_else(|| {
             ArcError::no_data(format!("Purchase `{purchase_name}` does not exist."), 501)
         })?;
+        if let Some((from, to)) =
+            limited_events().pick_ticket_window(purchase_name, Self::current_timestamp())
+        {
+            purchase_info.discount_from = Some(from);
+            purchase_info.discount_to = Some(to);
+            purchase_info.discount_reason = Some(ItemTypes::PICK_TICKET.to_string());
+        }
 
-        // Get purchase items
-        let purchase_items = sqlx::query!(
-            "SELECT item_id, type as item_type, amount FROM purchase_item WHERE purchase_name = ?",
-            purchase_name
-        )
-        .fetch_all(&self.pool)
-        .await?;
+        let purchase_items = self.purchase_items(purchase_name).await?;
 
-        if purchase_items.is_empty() {
-            return Err(ArcError::no_data(
-                format!("The items of the purchase `{purchase_name}` do not exist."),
-                501,
-            ));
+        // Buying something already owned again is a no-op, so a retried
+        // request never spends memories or a ticket twice.
+        if self.owns_all_unlocks(user_id, &purchase_items).await? {
+            return self.purchase_result(user_id).await;
         }
 
-        // Get user's current tickets
-        let user_tickets = sqlx::query!("SELECT ticket FROM user WHERE user_id = ?", user_id)
-            .fetch_optional(&self.pool)
-            .await?;
-
-        let current_tickets = user_tickets
-            .and_then(|row| row.ticket)
-            .ok_or_else(|| ArcError::no_data("User not found.", 108))?;
+        let item_types = purchase_items
+            .iter()
+            .map(|item| item.item_type.as_str())
+            .collect::<Vec<_>>();
+        let discount_reason = effective_discount_reason(
+            purchase_info.discount_reason.as_deref().unwrap_or(""),
+            &item_types,
+        );
 
         // Calculate actual price to pay
         let price_to_pay = self
@@ -304,76 +518,350 @@ impl PurchaseService {
                 purchase_info.orig_price.unwrap_or(0),
                 purchase_info.discount_from.unwrap_or(-1),
                 purchase_info.discount_to.unwrap_or(-1),
-                purchase_info.discount_reason.as_deref().unwrap_or(""),
+                discount_reason,
                 user_id,
             )
             .await?;
 
-        // Check if user has enough tickets
-        if current_tickets < price_to_pay {
-            return Err(ArcError::ticket_not_enough(
-                "The user does not have enough memories.",
-                -6,
-            ));
-        }
+        let kind = purchase_kind(&item_types);
+        let mut paid = (0, "memory");
 
         // Handle payment
         if !(purchase_info.orig_price.unwrap_or(0) == 0
             || (purchase_info.price.unwrap_or(0) == 0
-                && purchase_info.discount_from.unwrap_or(-1) <= Self::current_timestamp()
-                && Self::current_timestamp() <= purchase_info.discount_to.unwrap_or(-1)))
+                && discount_active(
+                    purchase_info.discount_from.unwrap_or(-1),
+                    purchase_info.discount_to.unwrap_or(-1),
+                    Self::current_timestamp(),
+                )))
         {
             if price_to_pay == 0 {
                 // Use special ticket
-                let discount_reason = purchase_info.discount_reason.unwrap_or_default();
-                if discount_reason == "anni5tix" || discount_reason == "pick_ticket" {
+                if !discount_reason.is_empty() {
                     self.item_service
-                        .claim_positive_item(user_id, &discount_reason, &discount_reason, -1)
+                        .spend_positive_item(user_id, discount_reason, discount_reason, 1)
                         .await?;
+                    paid = (1, discount_reason);
+                }
+            } else if let Some(provider) = &self.payment {
+                // A retried purchase pays the order it already opened
+                let order = match self
+                    .pending_order(user_id, purchase_name, provider.name())
+                    .await?
+                {
+                    Some(order) => order,
+                    None => {
+                        self.create_order(user_id, purchase_name, provider.name(), price_to_pay)
+                            .await?
+                    }
+                };
+                match provider.charge(&order).await {
+                    Ok(PaymentOutcome::Paid) => {
+                        self.deliver_order(&order, kind, purchase_items, provider.name())
+                            .await?;
+                        return self.purchase_result(user_id).await;
+                    }
+                    Ok(PaymentOutcome::Pending) => {
+                        return Err(ArcError::input(format!(
+                            "Payment for order `{}` is pending; the purchase unlocks once it is paid.",
+                            order.order_id
+                        )));
+                    }
+                    Err(e) => {
+                        self.update_order_status(&order.order_id, ORDER_PENDING, ORDER_FAILED)
+                            .await?;
+                        return Err(e);
+                    }
                 }
             } else {
-                // Deduct tickets
-                sqlx::query!(
-                    "UPDATE user SET ticket = ticket - ? WHERE user_id = ?",
+                // Deduct tickets, failing when the balance is too low
+                let result = sqlx::query!(
+                    "UPDATE user SET ticket = ticket - ? WHERE user_id = ? AND ticket >= ?",
                     price_to_pay,
-                    user_id
+                    user_id,
+                    price_to_pay
                 )
                 .execute(&self.pool)
                 .await?;
+                if result.rows_affected() == 0 {
+                    let ticket =
+                        sqlx::query_scalar!("SELECT ticket FROM user WHERE user_id = ?", user_id)
+                            .fetch_one(&self.pool)
+                            .await?
+                            .unwrap_or(0);
+                    return Err(ArcError::ticket_required(price_to_pay, ticket));
+                }
+                paid = (price_to_pay, "memory");
             }
         }
 
-        // Grant all items to user
+        self.deliver_purchase(user_id, kind, purchase_name, purchase_items, paid.0, paid.1)
+            .await?;
+
+        self.purchase_result(user_id).await
+    }
+
+    /// Items granted by a purchase; a purchase without items is an error
+    async fn purchase_items(&self, purchase_name: &str) -> ArcResult<Vec<PurchaseItemRow>> {
+        let purchase_items = sqlx::query_as!(
+            PurchaseItemRow,
+            "SELECT item_id, type as item_type, amount FROM purchase_item WHERE purchase_name = ?",
+            purchase_name
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        if purchase_items.is_empty() {
+            return Err(ArcError::no_data(
+                format!("The items of the purchase `{purchase_name}` do not exist."),
+                501,
+            ));
+        }
+        Ok(purchase_items)
+    }
+
+    /// Grant the items of a paid purchase and record it in the purchase log
+    async fn deliver_purchase(
+        &self,
+        user_id: i32,
+        kind: &str,
+        purchase_name: &str,
+        purchase_items: Vec<PurchaseItemRow>,
+        price: i32,
+        currency: &str,
+    ) -> ArcResult<()> {
         for item in purchase_items {
             let item_id = item.item_id;
             let item_type = item.item_type;
             let amount = item.amount.unwrap_or(1);
 
-            match item_type.as_str() {
-                ItemTypes::WORLD_SONG
-                | ItemTypes::WORLD_UNLOCK
-                | ItemTypes::COURSE_BANNER
-                | ItemTypes::ONLINE_BANNER
-                | ItemTypes::SINGLE
-                | ItemTypes::PACK => {
-                    self.item_service
-                        .claim_normal_item_python_compat(user_id, &item_id, &item_type)
-                        .await?;
-                }
-                _ => {
-                    self.item_service
-                        .claim_item(user_id, &item_id, &item_type, amount)
-                        .await?;
-                }
+            if is_unlock_item(&item_type) {
+                self.item_service
+                    .claim_normal_item_python_compat(user_id, &item_id, &item_type)
+                    .await?;
+            } else {
+                self.item_service
+                    .claim_item(user_id, &item_id, &item_type, amount)
+                    .await?;
             }
         }
 
+        self.log_purchase(user_id, kind, purchase_name, price, currency)
+            .await?;
         self.invalidate_user_purchase_cache(user_id).await;
         self.user_service
             .invalidate_user_collection_cache(user_id)
             .await;
+        Ok(())
+    }
+
+    /// Grant the items of a paid order exactly once
+    ///
+    /// The order is claimed as `delivering`, marked `paid` only once the items
+    /// are granted, and put back to `pending` when granting fails so that the
+    /// provider's retry delivers it. Returns whether this call delivered it.
+    async fn deliver_order(
+        &self,
+        order: &PaymentOrder,
+        kind: &str,
+        purchase_items: Vec<PurchaseItemRow>,
+        provider: &str,
+    ) -> ArcResult<bool> {
+        if !self
+            .update_order_status(&order.order_id, ORDER_PENDING, ORDER_DELIVERING)
+            .await?
+        {
+            return Ok(false);
+        }
+
+        if let Err(e) = self
+            .deliver_purchase(
+                order.user_id,
+                kind,
+                &order.purchase_name,
+                purchase_items,
+                order.price,
+                provider,
+            )
+            .await
+        {
+            self.update_order_status(&order.order_id, ORDER_DELIVERING, ORDER_PENDING)
+                .await?;
+            return Err(e);
+        }
+
+        self.update_order_status(&order.order_id, ORDER_DELIVERING, ORDER_PAID)
+            .await?;
+        Ok(true)
+    }
+
+    /// Newest pending order of the user for `purchase_name`
+    async fn pending_order(
+        &self,
+        user_id: i32,
+        purchase_name: &str,
+        provider: &str,
+    ) -> ArcResult<Option<PaymentOrder>> {
+        let order = sqlx::query!(
+            "SELECT order_id, price FROM purchase_order
+             WHERE user_id = ? AND purchase_name = ? AND provider = ? AND status = ?
+             ORDER BY created_at DESC
+             LIMIT 1",
+            user_id,
+            purchase_name,
+            provider,
+            ORDER_PENDING
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(order.map(|order| PaymentOrder {
+            order_id: order.order_id,
+            user_id,
+            purchase_name: purchase_name.to_string(),
+            price: order.price,
+        }))
+    }
+
+    /// Record a new pending order for the payment provider
+    async fn create_order(
+        &self,
+        user_id: i32,
+        purchase_name: &str,
+        provider: &str,
+        price: i32,
+    ) -> ArcResult<PaymentOrder> {
+        let order = PaymentOrder {
+            order_id: uuid::Uuid::new_v4().simple().to_string(),
+            user_id,
+            purchase_name: purchase_name.to_string(),
+            price,
+        };
+        sqlx::query!(
+            "INSERT INTO purchase_order (order_id, user_id, purchase_name, provider, price, status, created_at)
+             VALUES (?, ?, ?, ?, ?, ?, ?)",
+            order.order_id,
+            user_id,
+            purchase_name,
+            provider,
+            price,
+            ORDER_PENDING,
+            Self::current_timestamp()
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(order)
+    }
+
+    /// Move an order from status `from` to `to`; returns whether this call
+    /// moved it
+    async fn update_order_status(&self, order_id: &str, from: &str, to: &str) -> ArcResult<bool> {
+        let completed_at = (to != ORDER_PENDING).then(Self::current_timestamp);
+        let result = sqlx::query!(
+            "UPDATE purchase_order SET status = ?, completed_at = ?
+             WHERE order_id = ? AND status = ?",
+            to,
+            completed_at,
+            order_id,
+            from
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() == 1)
+    }
+
+    /// Settle an order from a payment provider callback
+    ///
+    /// A paid order grants its purchase once; repeated callbacks for an order
+    /// that is already settled are accepted and ignored. When granting fails
+    /// the order stays pending and the error is returned, so the provider's
+    /// retry delivers it.
+    pub async fn handle_payment_callback(
+        &self,
+        provider_name: &str,
+        body: &[u8],
+        signature: Option<&str>,
+    ) -> ArcResult<()> {
+        let provider = self
+            .payment
+            .as_ref()
+            .filter(|provider| provider.name() == provider_name)
+            .ok_or_else(|| {
+                ArcError::no_data(
+                    format!("Payment provider `{provider_name}` is not enabled."),
+                    404,
+                )
+            })?;
+        let callback = provider.parse_callback(body, signature)?;
+
+        let row = sqlx::query!(
+            "SELECT user_id, purchase_name, price FROM purchase_order
+             WHERE order_id = ? AND provider = ?",
+            callback.order_id,
+            provider_name
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or_else(|| {
+            ArcError::no_data(
+                format!("Order `{}` does not exist.", callback.order_id),
+                404,
+            )
+        })?;
+
+        if !callback.paid {
+            self.update_order_status(&callback.order_id, ORDER_PENDING, ORDER_FAILED)
+                .await?;
+            return Ok(());
+        }
+
+        let order = PaymentOrder {
+            order_id: callback.order_id,
+            user_id: row.user_id,
+            purchase_name: row.purchase_name,
+            price: row.price,
+        };
+        let purchase_items = self.purchase_items(&order.purchase_name).await?;
+        let item_types = purchase_items
+            .iter()
+            .map(|item| item.item_type.as_str())
+            .collect::<Vec<_>>();
+        let kind = purchase_kind(&item_types);
+        self.deliver_order(&order, kind, purchase_items, provider_name)
+            .await?;
+        Ok(())
+    }
+
+    /// Whether the user already owns every pack, single and other unlock the
+    /// purchase grants. Purchases without unlocks are never considered owned.
+    async fn owns_all_unlocks(
+        &self,
+        user_id: i32,
+        purchase_items: &[PurchaseItemRow],
+    ) -> ArcResult<bool> {
+        let mut has_unlock = false;
+        for item in purchase_items {
+            if !is_unlock_item(&item.item_type) {
+                continue;
+            }
+            has_unlock = true;
+            let owned = sqlx::query_scalar!(
+                "SELECT EXISTS(SELECT 1 FROM user_item WHERE user_id = ? AND item_id = ? AND type = ?) as `exists!: i64`",
+                user_id,
+                item.item_id,
+                item.item_type
+            )
+            .fetch_one(&self.pool)
+            .await?;
+            if owned == 0 {
+                return Ok(false);
+            }
+        }
+        Ok(has_unlock)
+    }
 
-        // Get updated user info
+    /// Response of a pack or single purchase: the updated memories and unlocks.
+    async fn purchase_result(&self, user_id: i32) -> ArcResult<Value> {
         let user_info = self.user_service.get_user_info(user_id).await?;
 
         Ok(json!({
@@ -402,10 +890,12 @@ impl PurchaseService {
 
         // Check if user has enough tickets
         if current_tickets < fixed_price {
-            return Err(ArcError::ticket_not_enough(
-                "The user does not have enough memories.",
-                -6,
-            ));
+            return Err(ArcError::ticket_required(fixed_price, current_tickets));
+        }
+        if item_id == "stamina6" {
+            self.stamina_service
+                .check_purchase(user_id, STAMINA_PURCHASE_AMOUNT)
+                .await?;
         }
 
         // Deduct tickets
@@ -421,6 +911,8 @@ impl PurchaseService {
         self.item_service
             .claim_item(user_id, item_id, item_id, 1)
             .await?;
+        self.log_purchase(user_id, "special", item_id, fixed_price, "memory")
+            .await?;
         self.user_service
             .invalidate_user_collection_cache(user_id)
             .await;
@@ -433,18 +925,10 @@ impl PurchaseService {
 
         // Add stamina info if it's stamina6
         if item_id == "stamina6" {
-            let stamina_info = sqlx::query!(
-                "SELECT stamina, max_stamina_ts FROM user WHERE user_id = ?",
-                user_id
-            )
-            .fetch_optional(&self.pool)
-            .await?;
-
-            if let Some(stamina) = stamina_info {
-                response["stamina"] = json!(stamina.stamina);
-                response["max_stamina_ts"] = json!(stamina.max_stamina_ts);
-                response["world_mode_locked_end_ts"] = json!(-1);
-            }
+            let stamina = self.stamina_service.get_stamina(user_id).await?;
+            response["stamina"] = json!(stamina.get_current_stamina());
+            response["max_stamina_ts"] = json!(stamina.max_stamina_ts());
+            response["world_mode_locked_end_ts"] = json!(-1);
         }
 
         Ok(response)
@@ -473,9 +957,12 @@ impl PurchaseService {
                 905,
             ));
         }
+        self.stamina_service
+            .check_purchase(user_id, STAMINA_PURCHASE_AMOUNT)
+            .await?;
 
         // Update next fragment stamina timestamp
-        let next_ts = now + Constants::FRAGSTAM_RECOVER_TICK;
+        let next_ts = now + game_constants().fragment_stamina_cooldown;
         sqlx::query!(
             "UPDATE user SET next_fragstam_ts = ? WHERE user_id = ?",
             next_ts,
@@ -488,59 +975,160 @@ impl PurchaseService {
         self.item_service
             .claim_item(user_id, "stamina6", "stamina6", 1)
             .await?;
+        self.log_purchase(user_id, "special", "stamina6", 0, "fragment")
+            .await?;
         self.user_service
             .invalidate_user_collection_cache(user_id)
             .await;
 
-        // Get updated user stamina info
-        let stamina_info = sqlx::query!(
-            "SELECT stamina, max_stamina_ts FROM user WHERE user_id = ?",
-            user_id
+        let stamina = self.stamina_service.get_stamina(user_id).await?;
+        Ok(json!({
+            "user_id": user_id,
+            "stamina": stamina.get_current_stamina(),
+            "max_stamina_ts": stamina.max_stamina_ts(),
+            "next_fragstam_ts": next_ts,
+            "world_mode_locked_end_ts": -1
+        }))
+    }
+
+    /// Record a completed purchase in `purchase_log`
+    async fn log_purchase(
+        &self,
+        user_id: i32,
+        kind: &str,
+        purchase_name: &str,
+        price: i32,
+        currency: &str,
+    ) -> ArcResult<()> {
+        sqlx::query!(
+            "INSERT INTO purchase_log (user_id, kind, purchase_name, price, currency, time)
+             VALUES (?, ?, ?, ?, ?, ?)",
+            user_id,
+            kind,
+            purchase_name,
+            price,
+            currency,
+            Self::current_timestamp()
         )
-        .fetch_optional(&self.pool)
+        .execute(&self.pool)
         .await?;
+        Ok(())
+    }
 
-        match stamina_info {
-            Some(info) => Ok(json!({
-                "user_id": user_id,
-                "stamina": info.stamina,
-                "max_stamina_ts": info.max_stamina_ts,
-                "next_fragstam_ts": next_ts,
-                "world_mode_locked_end_ts": -1
-            })),
-            None => Err(ArcError::no_data("User not found.", 108)),
-        }
+    /// Get the user's completed purchases, newest first
+    pub async fn get_purchase_history(
+        &self,
+        user_id: i32,
+        limit: i64,
+    ) -> ArcResult<Vec<PurchaseLogEntry>> {
+        let entries = sqlx::query_as!(
+            PurchaseLogEntry,
+            "SELECT kind, purchase_name, price, currency, time FROM purchase_log
+             WHERE user_id = ? ORDER BY time DESC, id DESC LIMIT ?",
+            user_id,
+            limit.clamp(1, MAX_PURCHASE_HISTORY)
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(entries)
     }
 
     /// Redeem code
     ///
-    /// Allows users to redeem codes for various rewards.
+    /// Allows users to redeem codes for various rewards. Single-use codes are
+    /// used up by their first claim; codes of a disabled batch are rejected.
     pub async fn redeem_code(&self, user_id: i32, code: &str) -> ArcResult<Value> {
-        // Check if code exists and is valid
+        // Locking the code row serializes concurrent claims of the same code
+        let mut tx = self.pool.begin().await?;
         let redeem_info = sqlx::query!(
-            "SELECT type as redeem_type FROM redeem WHERE code = ?",
+            "SELECT r.type as redeem_type, b.is_disabled as `is_disabled?`
+             FROM redeem r LEFT JOIN redeem_batch b ON b.batch_id = r.batch_id
+             WHERE r.code = ?
+             FOR UPDATE",
             code
         )
-        .fetch_optional(&self.pool)
-        .await?;
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| ArcError::no_data("Invalid redeem code.", 502))?;
 
-        let _redeem_type = redeem_info
-            .and_then(|row| row.redeem_type)
+        let redeem_kind = redeem_info
+            .redeem_type
+            .and_then(RedeemKind::from_i32)
             .ok_or_else(|| ArcError::no_data("Invalid redeem code.", 502))?;
+        if redeem_info.is_disabled.unwrap_or(0) != 0 {
+            return Err(ArcError::no_data("Invalid redeem code.", 502));
+        }
+
+        let used = if redeem_kind == RedeemKind::SingleUse {
+            sqlx::query_scalar!(
+                "SELECT EXISTS(SELECT 1 FROM user_redeem WHERE code = ?) as `exists!: i64`",
+                code
+            )
+            .fetch_one(&mut *tx)
+            .await?
+        } else {
+            sqlx::query_scalar!(
+                "SELECT EXISTS(SELECT 1 FROM user_redeem WHERE user_id = ? AND code = ?)
+                 as `exists!: i64`",
+                user_id,
+                code
+            )
+            .fetch_one(&mut *tx)
+            .await?
+        };
+        if used != 0 {
+            return Err(match redeem_kind {
+                RedeemKind::SingleUse => {
+                    ArcError::data_exist("The redeem code has been used.", 504, -1)
+                }
+                RedeemKind::MultiUse | RedeemKind::Fragment => {
+                    ArcError::data_exist("Code already redeemed.", 503, -1)
+                }
+            });
+        }
 
-        // Check if user has already redeemed this code
-        let already_redeemed = sqlx::query!(
-            "SELECT EXISTS(SELECT 1 FROM user_redeem WHERE user_id = ? AND code = ?) as `exists!: i64`",
+        // The mark is committed before granting, since items are granted on
+        // other connections, and taken back if a grant fails
+        sqlx::query!(
+            "INSERT INTO user_redeem (user_id, code) VALUES (?, ?)",
             user_id,
             code
         )
-        .fetch_one(&self.pool)
+        .execute(&mut *tx)
         .await?;
+        tx.commit().await?;
 
-        if already_redeemed.exists != 0 {
-            return Err(ArcError::data_exist("Code already redeemed.", 503, -1));
-        }
+        let fragment_amount = match self.grant_redeem_items(user_id, code).await {
+            Ok(fragment_amount) => fragment_amount,
+            Err(e) => {
+                sqlx::query!(
+                    "DELETE FROM user_redeem WHERE user_id = ? AND code = ?",
+                    user_id,
+                    code
+                )
+                .execute(&self.pool)
+                .await?;
+                return Err(e);
+            }
+        };
+        self.user_service
+            .invalidate_user_collection_cache(user_id)
+            .await;
 
+        // Return response with fragment info
+        let coupon = if fragment_amount > 0 {
+            format!("fragment{fragment_amount}")
+        } else {
+            String::new()
+        };
+
+        Ok(json!({
+            "coupon": coupon
+        }))
+    }
+
+    /// Grant the items of `code`, returning the fragments granted
+    async fn grant_redeem_items(&self, user_id: i32, code: &str) -> ArcResult<i32> {
         // Get redeem items
         let redeem_items = sqlx::query!(
             "SELECT item_id, type as item_type, amount FROM redeem_item WHERE code = ?",
@@ -566,28 +1154,448 @@ impl PurchaseService {
                 .claim_item(user_id, &item_id, &item_type, amount.unwrap_or(0))
                 .await?;
         }
-        self.user_service
-            .invalidate_user_collection_cache(user_id)
-            .await;
 
-        // Mark code as redeemed by user
+        Ok(fragment_amount)
+    }
+
+    /// Generate a batch of `count` random redeem codes granting `items`
+    ///
+    /// Returns the generated codes in creation order.
+    pub async fn create_redeem_batch(
+        &self,
+        batch_id: &str,
+        description: &str,
+        kind: RedeemKind,
+        count: usize,
+        items: &[RedeemItem],
+    ) -> ArcResult<Vec<String>> {
+        if batch_id.is_empty() || batch_id.len() > 64 {
+            return Err(ArcError::input("Batch id must be 1-64 characters."));
+        }
+        if !(1..=MAX_REDEEM_BATCH_SIZE).contains(&count) {
+            return Err(ArcError::input(format!(
+                "A batch holds 1-{MAX_REDEEM_BATCH_SIZE} codes."
+            )));
+        }
+        validate_redeem_items(kind, items)?;
+
+        let batch_exists = sqlx::query_scalar!(
+            "SELECT EXISTS(SELECT 1 FROM redeem_batch WHERE batch_id = ?) as `exists!: i64`",
+            batch_id
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        if batch_exists != 0 {
+            return Err(ArcError::data_exist(
+                format!("Redeem batch '{batch_id}' already exists."),
+                -1,
+                -1,
+            ));
+        }
+        for item in items {
+            let exists = sqlx::query_scalar!(
+                "SELECT EXISTS(SELECT 1 FROM item WHERE item_id = ? AND type = ?) as `exists!: i64`",
+                item.item_id,
+                item.item_type
+            )
+            .fetch_one(&self.pool)
+            .await?;
+            if exists == 0 {
+                return Err(ArcError::no_data(
+                    format!(
+                        "Item '{}' of type '{}' does not exist",
+                        item.item_id, item.item_type
+                    ),
+                    -2,
+                ));
+            }
+        }
+
+        let mut tx = self.pool.begin().await?;
         sqlx::query!(
-            "INSERT INTO user_redeem (user_id, code) VALUES (?, ?)",
-            user_id,
-            code
+            "INSERT INTO redeem_batch (batch_id, description, type, code_count, is_disabled, created_at)
+             VALUES (?, ?, ?, ?, 0, ?)",
+            batch_id,
+            description,
+            kind.as_i32(),
+            count as i32,
+            Self::current_timestamp()
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        let mut codes = Vec::with_capacity(count);
+        while codes.len() < count {
+            let code = generate_redeem_code();
+            // A collision with an existing code is simply retried
+            let inserted = sqlx::query!(
+                "INSERT IGNORE INTO redeem (code, type, batch_id) VALUES (?, ?, ?)",
+                code,
+                kind.as_i32(),
+                batch_id
+            )
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+            if inserted == 0 {
+                continue;
+            }
+            for item in items {
+                sqlx::query!(
+                    "INSERT INTO redeem_item (code, item_id, type, amount) VALUES (?, ?, ?, ?)",
+                    code,
+                    item.item_id,
+                    item.item_type,
+                    item.amount
+                )
+                .execute(&mut *tx)
+                .await?;
+            }
+            codes.push(code);
+        }
+        tx.commit().await?;
+
+        Ok(codes)
+    }
+
+    /// List redeem code batches, newest first
+    pub async fn list_redeem_batches(&self) -> ArcResult<Vec<RedeemBatch>> {
+        let rows = sqlx::query!(
+            "SELECT b.batch_id, b.description, b.type as redeem_type, b.code_count,
+                    b.is_disabled, b.created_at,
+                    (SELECT COUNT(DISTINCT ur.code) FROM user_redeem ur
+                     JOIN redeem r ON r.code = ur.code
+                     WHERE r.batch_id = b.batch_id) as `redeemed_count!: i64`
+             FROM redeem_batch b ORDER BY b.created_at DESC"
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut batches = Vec::with_capacity(rows.len());
+        for row in rows {
+            let Some(kind) = RedeemKind::from_i32(row.redeem_type) else {
+                continue;
+            };
+            let items = sqlx::query!(
+                "SELECT item_id, type as item_type, amount FROM redeem_item
+                 WHERE code = (SELECT MIN(code) FROM redeem WHERE batch_id = ?)",
+                row.batch_id
+            )
+            .fetch_all(&self.pool)
+            .await?
+            .into_iter()
+            .map(|item| RedeemItem {
+                item_id: item.item_id,
+                item_type: item.item_type,
+                amount: item.amount.unwrap_or(0),
+            })
+            .collect();
+            batches.push(RedeemBatch {
+                batch_id: row.batch_id,
+                description: row.description.unwrap_or_default(),
+                kind,
+                code_count: row.code_count as i64,
+                redeemed_count: row.redeemed_count,
+                is_disabled: row.is_disabled != 0,
+                created_at: row.created_at,
+                items,
+            });
+        }
+        Ok(batches)
+    }
+
+    /// Enable or disable every code of a batch
+    pub async fn set_redeem_batch_disabled(&self, batch_id: &str, disabled: bool) -> ArcResult<()> {
+        let result = sqlx::query!(
+            "UPDATE redeem_batch SET is_disabled = ? WHERE batch_id = ?",
+            disabled,
+            batch_id
         )
         .execute(&self.pool)
         .await?;
+        if result.rows_affected() == 0 {
+            let exists = sqlx::query_scalar!(
+                "SELECT EXISTS(SELECT 1 FROM redeem_batch WHERE batch_id = ?) as `exists!: i64`",
+                batch_id
+            )
+            .fetch_one(&self.pool)
+            .await?;
+            if exists == 0 {
+                return Err(ArcError::no_data(
+                    format!("Redeem batch '{batch_id}' does not exist."),
+                    -2,
+                ));
+            }
+        }
+        Ok(())
+    }
 
-        // Return response with fragment info
-        let coupon = if fragment_amount > 0 {
-            format!("fragment{fragment_amount}")
-        } else {
-            String::new()
-        };
+    /// Export the codes of a batch as CSV with their claim counts
+    pub async fn export_redeem_batch_csv(&self, batch_id: &str) -> ArcResult<String> {
+        let redeem_type =
+            sqlx::query_scalar!("SELECT type FROM redeem_batch WHERE batch_id = ?", batch_id)
+                .fetch_optional(&self.pool)
+                .await?
+                .ok_or_else(|| {
+                    ArcError::no_data(format!("Redeem batch '{batch_id}' does not exist."), -2)
+                })?;
+        let kind = RedeemKind::from_i32(redeem_type)
+            .ok_or_else(|| ArcError::input("Unknown redeem code type."))?;
+
+        let rows = sqlx::query!(
+            "SELECT r.code, COUNT(ur.user_id) as `redeemed!: i64`
+             FROM redeem r LEFT JOIN user_redeem ur ON ur.code = r.code
+             WHERE r.batch_id = ? GROUP BY r.code ORDER BY r.code",
+            batch_id
+        )
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(|row| (row.code, row.redeemed))
+        .collect::<Vec<_>>();
 
-        Ok(json!({
-            "coupon": coupon
-        }))
+        Ok(render_redeem_csv(kind, &rows))
+    }
+}
+
+/// Random 10 character alphanumeric redeem code
+pub fn generate_redeem_code() -> String {
+    const CHARS: &[u8] = b"AaBbCcDdEeFfGgHhIiJjKkLlMmNnOoPpQqRrSsTtUuVvWwXxYyZz0123456789";
+    let mut rng = rand::thread_rng();
+    (0..10)
+        .map(|_| CHARS[rng.gen_range(0..CHARS.len())] as char)
+        .collect()
+}
+
+fn validate_redeem_items(kind: RedeemKind, items: &[RedeemItem]) -> ArcResult<()> {
+    if items.is_empty() {
+        return Err(ArcError::input("A redeem code needs at least one item."));
+    }
+    let mut seen = HashSet::new();
+    for item in items {
+        if item.item_id.trim().is_empty() || item.item_type.trim().is_empty() {
+            return Err(ArcError::input("Redeem items need an item id and type."));
+        }
+        if item.amount <= 0 {
+            return Err(ArcError::input(format!(
+                "Amount of item '{}' must be positive.",
+                item.item_id
+            )));
+        }
+        if kind == RedeemKind::Fragment && item.item_type != "fragment" {
+            return Err(ArcError::input(
+                "Fragment redeem codes can only grant fragments.",
+            ));
+        }
+        if !seen.insert((item.item_id.as_str(), item.item_type.as_str())) {
+            return Err(ArcError::input(format!(
+                "Item '{}' is listed more than once.",
+                item.item_id
+            )));
+        }
+    }
+    Ok(())
+}
+
+fn render_redeem_csv(kind: RedeemKind, rows: &[(String, i64)]) -> String {
+    let kind = match kind {
+        RedeemKind::SingleUse => "single_use",
+        RedeemKind::MultiUse => "multi_use",
+        RedeemKind::Fragment => "fragment",
+    };
+    let mut csv = String::from("code,type,redeemed_count\n");
+    for (code, redeemed) in rows {
+        csv.push_str(&format!("{code},{kind},{redeemed}\n"));
+    }
+    csv
+}
+
+/// Items granted once and owned afterwards, as opposed to stackable items.
+fn is_unlock_item(item_type: &str) -> bool {
+    matches!(
+        item_type,
+        ItemTypes::WORLD_SONG
+            | ItemTypes::WORLD_UNLOCK
+            | ItemTypes::COURSE_BANNER
+            | ItemTypes::ONLINE_BANNER
+            | ItemTypes::SINGLE
+            | ItemTypes::PACK
+    )
+}
+
+/// Purchase log kind of a purchase granting `item_types`.
+fn purchase_kind(item_types: &[&str]) -> &'static str {
+    if item_types.contains(&ItemTypes::SINGLE) {
+        "single"
+    } else {
+        "pack"
+    }
+}
+
+/// The special ticket that may pay for a purchase granting `item_types`, or
+/// `""` when none applies. Pick tickets only exchange for singles; the
+/// anniversary ticket exchanges for one pack or single.
+/// Whether the discount window `[discount_from, discount_to]` is open at
+/// `now`; non-positive bounds mean the purchase has no discount
+fn discount_active(discount_from: i64, discount_to: i64, now: i64) -> bool {
+    discount_from > 0 && discount_to > 0 && discount_from <= now && now <= discount_to
+}
+
+/// Price the user pays: `orig_price` outside the discount window, `price`
+/// inside it, and nothing when the user holds the discount's ticket
+fn discounted_price(
+    price: i32,
+    orig_price: i32,
+    discount_from: i64,
+    discount_to: i64,
+    now: i64,
+    has_ticket: bool,
+) -> i32 {
+    if !discount_active(discount_from, discount_to, now) {
+        orig_price
+    } else if has_ticket {
+        0
+    } else {
+        price
+    }
+}
+
+fn effective_discount_reason<'a>(discount_reason: &'a str, item_types: &[&str]) -> &'a str {
+    let has_pack = item_types.contains(&ItemTypes::PACK);
+    let has_single = item_types.contains(&ItemTypes::SINGLE);
+    let eligible = match discount_reason {
+        ItemTypes::PICK_TICKET => has_single && !has_pack,
+        ItemTypes::ANNI5TIX => has_single || has_pack,
+        _ => false,
+    };
+    if eligible {
+        discount_reason
+    } else {
+        ""
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_effective_discount_reason() {
+        assert_eq!(
+            effective_discount_reason("pick_ticket", &["single"]),
+            "pick_ticket"
+        );
+        assert_eq!(
+            effective_discount_reason("pick_ticket", &["pack", "single"]),
+            ""
+        );
+        assert_eq!(
+            effective_discount_reason("anni5tix", &["pack", "core"]),
+            "anni5tix"
+        );
+        assert_eq!(effective_discount_reason("anni5tix", &["character"]), "");
+        assert_eq!(effective_discount_reason("sale", &["single"]), "");
+    }
+
+    #[test]
+    fn test_discounted_price() {
+        // Outside the window, or without one, the original price applies
+        assert_eq!(discounted_price(300, 500, 1000, 2000, 999, true), 500);
+        assert_eq!(discounted_price(300, 500, 1000, 2000, 2001, true), 500);
+        assert_eq!(discounted_price(300, 500, -1, -1, 1500, true), 500);
+        assert_eq!(discounted_price(300, 500, 0, 2000, 1500, false), 500);
+
+        // Inside it the sale price, or nothing with a ticket
+        assert_eq!(discounted_price(300, 500, 1000, 2000, 1000, false), 300);
+        assert_eq!(discounted_price(300, 500, 1000, 2000, 2000, false), 300);
+        assert_eq!(discounted_price(300, 500, 1000, 2000, 1500, true), 0);
+    }
+
+    fn redeem_item(item_id: &str, item_type: &str, amount: i32) -> RedeemItem {
+        RedeemItem {
+            item_id: item_id.to_string(),
+            item_type: item_type.to_string(),
+            amount,
+        }
+    }
+
+    #[test]
+    fn test_validate_redeem_items() {
+        assert!(validate_redeem_items(RedeemKind::MultiUse, &[]).is_err());
+        assert!(validate_redeem_items(
+            RedeemKind::SingleUse,
+            &[redeem_item("memory", "memory", 0)]
+        )
+        .is_err());
+        assert!(validate_redeem_items(
+            RedeemKind::Fragment,
+            &[redeem_item("core_generic", "core", 5)]
+        )
+        .is_err());
+        assert!(validate_redeem_items(
+            RedeemKind::Fragment,
+            &[redeem_item("fragment", "fragment", 300)]
+        )
+        .is_ok());
+        assert!(validate_redeem_items(
+            RedeemKind::MultiUse,
+            &[
+                redeem_item("memory", "memory", 100),
+                redeem_item("core_generic", "core", 5)
+            ]
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn test_redeem_kind_round_trips() {
+        for kind in [
+            RedeemKind::SingleUse,
+            RedeemKind::MultiUse,
+            RedeemKind::Fragment,
+        ] {
+            assert_eq!(RedeemKind::from_i32(kind.as_i32()), Some(kind));
+        }
+        assert_eq!(RedeemKind::from_i32(3), None);
+    }
+
+    #[test]
+    fn test_generated_redeem_codes_are_alphanumeric() {
+        let code = generate_redeem_code();
+        assert_eq!(code.len(), 10);
+        assert!(code.chars().all(|c| c.is_ascii_alphanumeric()));
+    }
+
+    #[test]
+    fn test_render_redeem_csv() {
+        let csv = render_redeem_csv(
+            RedeemKind::SingleUse,
+            &[("AbCdE12345".to_string(), 1), ("ZyXwV98765".to_string(), 0)],
+        );
+        assert_eq!(
+            csv,
+            "code,type,redeemed_count\nAbCdE12345,single_use,1\nZyXwV98765,single_use,0\n"
+        );
+    }
+
+    #[test]
+    fn test_webhook_callback_requires_valid_signature() {
+        let provider = WebhookPaymentProvider::new(
+            "https://pay.example.com/orders".to_string(),
+            "secret".to_string(),
+        );
+        let body = br#"{"order_id":"abc","paid":true}"#;
+
+        let callback = provider
+            .parse_callback(body, Some(&hmac_sha256_sign("secret", body)))
+            .unwrap();
+        assert_eq!(callback.order_id, "abc");
+        assert!(callback.paid);
+
+        assert!(provider.parse_callback(body, None).is_err());
+        assert!(provider
+            .parse_callback(body, Some(&hmac_sha256_sign("other", body)))
+            .is_err());
+        assert!(FreePaymentProvider.parse_callback(body, None).is_err());
     }
 }
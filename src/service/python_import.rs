@@ -0,0 +1,611 @@
+use crate::error::{ArcError, ArcResult};
+use crate::{Db, DbPool};
+use rand::Rng;
+use sqlx::mysql::MySqlArguments;
+use sqlx::query::Query;
+use sqlx::sqlite::{SqliteConnectOptions, SqliteConnection, SqliteRow};
+use sqlx::{Connection, Row, TypeInfo, ValueRef};
+use std::collections::{BTreeMap, HashSet};
+use std::path::Path;
+
+/// Player columns copied from the Python `user` table; `user_id` and
+/// `user_code` are replaced by the remapped values
+const USER_COLUMNS: &[&str] = &[
+    "name",
+    "password",
+    "join_date",
+    "rating_ptt",
+    "character_id",
+    "is_skill_sealed",
+    "is_char_uncapped",
+    "is_char_uncapped_override",
+    "is_hide_rating",
+    "song_id",
+    "difficulty",
+    "score",
+    "shiny_perfect_count",
+    "perfect_count",
+    "near_count",
+    "miss_count",
+    "health",
+    "modifier",
+    "time_played",
+    "clear_type",
+    "rating",
+    "favorite_character",
+    "max_stamina_notification_enabled",
+    "current_map",
+    "ticket",
+    "prog_boost",
+    "email",
+    "world_rank_score",
+    "ban_flag",
+    "next_fragstam_ts",
+    "max_stamina_ts",
+    "stamina",
+    "world_mode_locked_end_ts",
+    "beyond_boost_gauge",
+    "kanae_stored_prog",
+    "mp_notification_enabled",
+    "highest_rating_ptt",
+    "insight_state",
+];
+
+const BEST_SCORE_COLUMNS: &[&str] = &[
+    "song_id",
+    "difficulty",
+    "score",
+    "shiny_perfect_count",
+    "perfect_count",
+    "near_count",
+    "miss_count",
+    "health",
+    "modifier",
+    "time_played",
+    "best_clear_type",
+    "clear_type",
+    "rating",
+    "score_v2",
+];
+
+const RECENT30_COLUMNS: &[&str] = &[
+    "r_index",
+    "time_played",
+    "song_id",
+    "difficulty",
+    "score",
+    "shiny_perfect_count",
+    "perfect_count",
+    "near_count",
+    "miss_count",
+    "health",
+    "modifier",
+    "clear_type",
+    "rating",
+];
+
+const USER_CHAR_COLUMNS: &[&str] = &[
+    "character_id",
+    "level",
+    "exp",
+    "is_uncapped",
+    "is_uncapped_override",
+    "skill_flag",
+];
+
+/// First id the Python server hands out, kept as the floor here
+const FIRST_USER_ID: i32 = 2_000_001;
+
+/// A value read from SQLite, whose columns carry no fixed type
+#[derive(Debug, Clone, PartialEq)]
+enum SqlValue {
+    Null,
+    Int(i64),
+    Real(f64),
+    Text(String),
+    Blob(Vec<u8>),
+}
+
+impl SqlValue {
+    fn as_i64(&self) -> Option<i64> {
+        match self {
+            Self::Int(value) => Some(*value),
+            Self::Real(value) => Some(*value as i64),
+            Self::Text(text) => text.trim().parse().ok(),
+            _ => None,
+        }
+    }
+
+    fn as_text(&self) -> Option<&str> {
+        match self {
+            Self::Text(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    fn bind<'q>(&self, query: Query<'q, Db, MySqlArguments>) -> Query<'q, Db, MySqlArguments> {
+        match self {
+            Self::Null => query.bind(None::<String>),
+            Self::Int(value) => query.bind(*value),
+            Self::Real(value) => query.bind(*value),
+            Self::Text(text) => query.bind(text.clone()),
+            Self::Blob(bytes) => query.bind(bytes.clone()),
+        }
+    }
+}
+
+/// One row of a Python table, the selected columns in order
+type SourceRow = Vec<(&'static str, SqlValue)>;
+
+/// How far an import has come, reported after each player
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImportProgress {
+    pub users_done: usize,
+    pub users_total: usize,
+}
+
+/// What an import wrote
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PythonImportReport {
+    pub users_imported: usize,
+    /// Names that already exist here, imported with none of their data
+    pub users_skipped: Vec<String>,
+    /// Python user id to the id here, for imported players only
+    pub user_ids: BTreeMap<i32, i32>,
+    /// Players given a new user code because theirs was taken
+    pub user_codes_regenerated: usize,
+    pub best_scores: u64,
+    pub recent30: u64,
+    pub characters: u64,
+    pub friends: u64,
+    pub presents: u64,
+    pub user_presents: u64,
+}
+
+/// Ids, names and codes already in use, and the next free user id
+#[derive(Debug, Default)]
+struct ExistingUsers {
+    ids: HashSet<i32>,
+    /// Lowercased, as the `user.name` collation ignores case
+    names: HashSet<String>,
+    codes: HashSet<String>,
+    next_id: i32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum UserPlan {
+    /// The name is taken by another player
+    Skip,
+    Import {
+        user_id: i32,
+        user_code: String,
+        code_regenerated: bool,
+    },
+}
+
+impl ExistingUsers {
+    /// Where a Python player goes: their own id and code when free,
+    /// otherwise the next free id and a fresh code
+    fn plan(&mut self, user_id: i32, name: &str, user_code: &str) -> UserPlan {
+        if !self.names.insert(name.to_lowercase()) {
+            return UserPlan::Skip;
+        }
+
+        let user_id = if user_id > 0 && self.ids.insert(user_id) {
+            user_id
+        } else {
+            while !self.ids.insert(self.next_id) {
+                self.next_id += 1;
+            }
+            self.next_id
+        };
+
+        let code_free = is_user_code(user_code) && self.codes.insert(user_code.to_string());
+        let user_code = if code_free {
+            user_code.to_string()
+        } else {
+            loop {
+                let code: String = (0..9)
+                    .map(|_| char::from(b'0' + rand::thread_rng().gen_range(0..10)))
+                    .collect();
+                if self.codes.insert(code.clone()) {
+                    break code;
+                }
+            }
+        };
+
+        UserPlan::Import {
+            user_id,
+            user_code,
+            code_regenerated: !code_free,
+        }
+    }
+}
+
+/// Imports players from the SQLite `arcaea_database.db` of the Python
+/// server.
+///
+/// Players, their best scores, recent 30, characters, friends and presents
+/// are copied. A player keeps their user id and user code unless another
+/// player here already has it, in which case they get the next free id or a
+/// fresh code; players whose name is already taken are skipped. Each player
+/// is written in their own transaction, so an interrupted import can be run
+/// again and will skip the players it already wrote.
+pub struct PythonImportService {
+    pool: DbPool,
+}
+
+impl PythonImportService {
+    pub fn new(pool: DbPool) -> Self {
+        Self { pool }
+    }
+
+    /// Import the database at `path`, calling `progress` after each player
+    pub async fn import(
+        &self,
+        path: &Path,
+        mut progress: impl FnMut(ImportProgress),
+    ) -> ArcResult<PythonImportReport> {
+        let options = SqliteConnectOptions::new().filename(path).read_only(true);
+        let mut source = SqliteConnection::connect_with(&options)
+            .await
+            .map_err(|e| ArcError::input(format!("Failed to open {}: {e}", path.display())))?;
+
+        let mut report = PythonImportReport::default();
+        self.import_presents(&mut source, &mut report).await?;
+
+        let users = read_rows(
+            &mut source,
+            "user",
+            &[&["user_id", "user_code"], USER_COLUMNS].concat(),
+            "",
+        )
+        .await?;
+        let mut existing = self.existing_users().await?;
+        existing.next_id = existing.next_id.max(
+            users
+                .iter()
+                .filter_map(|row| field(row, "user_id").and_then(SqlValue::as_i64))
+                .max()
+                .map_or(FIRST_USER_ID, |id| id as i32 + 1),
+        );
+        let characters: HashSet<i32> = sqlx::query_scalar!("SELECT character_id FROM `character`")
+            .fetch_all(&self.pool)
+            .await?
+            .into_iter()
+            .collect();
+
+        let users_total = users.len();
+        for (done, user) in users.into_iter().enumerate() {
+            let old_id = field(&user, "user_id")
+                .and_then(SqlValue::as_i64)
+                .unwrap_or(0) as i32;
+            let name = field(&user, "name")
+                .and_then(SqlValue::as_text)
+                .unwrap_or_default()
+                .to_string();
+            let user_code = field(&user, "user_code")
+                .and_then(SqlValue::as_text)
+                .unwrap_or_default();
+
+            match existing.plan(old_id, &name, user_code) {
+                UserPlan::Skip => {
+                    log::info!("Skipped Python user {old_id} ({name}): name exists");
+                    report.users_skipped.push(name);
+                }
+                UserPlan::Import {
+                    user_id,
+                    user_code,
+                    code_regenerated,
+                } => {
+                    self.import_user(
+                        &mut source,
+                        old_id,
+                        user_id,
+                        &user_code,
+                        &user,
+                        &characters,
+                        &mut report,
+                    )
+                    .await?;
+                    report.users_imported += 1;
+                    report.user_codes_regenerated += usize::from(code_regenerated);
+                    report.user_ids.insert(old_id, user_id);
+                }
+            }
+            progress(ImportProgress {
+                users_done: done + 1,
+                users_total,
+            });
+        }
+
+        self.import_friends(&mut source, &mut report).await?;
+        Ok(report)
+    }
+
+    async fn existing_users(&self) -> ArcResult<ExistingUsers> {
+        let rows = sqlx::query!("SELECT user_id, name, user_code FROM user")
+            .fetch_all(&self.pool)
+            .await?;
+        let mut existing = ExistingUsers {
+            next_id: FIRST_USER_ID,
+            ..Default::default()
+        };
+        for row in rows {
+            existing.next_id = existing.next_id.max(row.user_id + 1);
+            existing.ids.insert(row.user_id);
+            if let Some(name) = row.name {
+                existing.names.insert(name.to_lowercase());
+            }
+            if let Some(code) = row.user_code {
+                existing.codes.insert(code);
+            }
+        }
+        Ok(existing)
+    }
+
+    /// Write one player and their rows in a single transaction
+    #[allow(clippy::too_many_arguments)]
+    async fn import_user(
+        &self,
+        source: &mut SqliteConnection,
+        old_id: i32,
+        user_id: i32,
+        user_code: &str,
+        user: &SourceRow,
+        characters: &HashSet<i32>,
+        report: &mut PythonImportReport,
+    ) -> ArcResult<()> {
+        let filter = format!("WHERE user_id = {old_id}");
+        let best_scores = read_rows(source, "best_score", BEST_SCORE_COLUMNS, &filter).await?;
+        let recent30 = read_rows(source, "recent30", RECENT30_COLUMNS, &filter).await?;
+        let user_chars: Vec<SourceRow> = read_rows(source, "user_char", USER_CHAR_COLUMNS, &filter)
+            .await?
+            .into_iter()
+            .filter(|row| {
+                field(row, "character_id")
+                    .and_then(SqlValue::as_i64)
+                    .is_some_and(|id| characters.contains(&(id as i32)))
+            })
+            .collect();
+        let present_ids = read_rows(source, "user_present", &["present_id"], &filter).await?;
+
+        let user_id_value = SqlValue::Int(user_id.into());
+        let mut tx = self.pool.begin().await?;
+
+        let mut user_row: SourceRow = user
+            .iter()
+            .filter(|(column, _)| !matches!(*column, "user_id" | "user_code"))
+            .cloned()
+            .collect();
+        user_row.push(("user_id", user_id_value.clone()));
+        user_row.push(("user_code", SqlValue::Text(user_code.to_string())));
+        insert_row(&mut tx, "INSERT", "user", &user_row).await?;
+
+        for (table, rows, count) in [
+            ("best_score", best_scores, &mut report.best_scores),
+            ("recent30", recent30, &mut report.recent30),
+            ("user_char", user_chars, &mut report.characters),
+            ("user_present", present_ids, &mut report.user_presents),
+        ] {
+            for mut row in rows {
+                row.push(("user_id", user_id_value.clone()));
+                *count += insert_row(&mut tx, "INSERT IGNORE", table, &row).await?;
+            }
+        }
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn import_presents(
+        &self,
+        source: &mut SqliteConnection,
+        report: &mut PythonImportReport,
+    ) -> ArcResult<()> {
+        let presents = read_rows(
+            source,
+            "present",
+            &["present_id", "expire_ts", "description"],
+            "",
+        )
+        .await?;
+        let items = read_rows(
+            source,
+            "present_item",
+            &["present_id", "item_id", "type", "amount"],
+            "",
+        )
+        .await?;
+
+        let mut tx = self.pool.begin().await?;
+        for row in &presents {
+            report.presents += insert_row(&mut tx, "INSERT IGNORE", "present", row).await?;
+        }
+        for row in &items {
+            insert_row(&mut tx, "INSERT IGNORE", "present_item", row).await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Friendships between two imported players
+    async fn import_friends(
+        &self,
+        source: &mut SqliteConnection,
+        report: &mut PythonImportReport,
+    ) -> ArcResult<()> {
+        let friends = read_rows(source, "friend", &["user_id_me", "user_id_other"], "").await?;
+        let mapped = |row: &SourceRow, column| {
+            field(row, column)
+                .and_then(SqlValue::as_i64)
+                .and_then(|id| report.user_ids.get(&(id as i32)).copied())
+        };
+        let pairs: Vec<(i32, i32)> = friends
+            .iter()
+            .filter_map(|row| Some((mapped(row, "user_id_me")?, mapped(row, "user_id_other")?)))
+            .collect();
+
+        let mut tx = self.pool.begin().await?;
+        for (me, other) in pairs {
+            report.friends += sqlx::query!(
+                "INSERT IGNORE INTO friend (user_id_me, user_id_other) VALUES (?, ?)",
+                me,
+                other
+            )
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+}
+
+/// The `columns` of `table` that the Python database has, empty when the
+/// table itself is missing, as in databases of older server versions
+async fn read_rows(
+    source: &mut SqliteConnection,
+    table: &str,
+    columns: &[&'static str],
+    filter: &str,
+) -> ArcResult<Vec<SourceRow>> {
+    let present: HashSet<String> = sqlx::query(&format!("PRAGMA table_info({table})"))
+        .fetch_all(&mut *source)
+        .await?
+        .iter()
+        .map(|row| row.try_get::<String, _>("name"))
+        .collect::<Result<_, _>>()?;
+    let columns: Vec<&'static str> = columns
+        .iter()
+        .copied()
+        .filter(|column| present.contains(*column))
+        .collect();
+    if columns.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let rows = sqlx::query(&format!(
+        "SELECT {} FROM {table} {filter}",
+        columns.join(", ")
+    ))
+    .fetch_all(&mut *source)
+    .await?;
+    rows.iter()
+        .map(|row| {
+            columns
+                .iter()
+                .enumerate()
+                .map(|(index, column)| Ok((*column, read_value(row, index)?)))
+                .collect()
+        })
+        .collect()
+}
+
+fn read_value(row: &SqliteRow, index: usize) -> ArcResult<SqlValue> {
+    let raw = row.try_get_raw(index)?;
+    if raw.is_null() {
+        return Ok(SqlValue::Null);
+    }
+    let kind = raw.type_info().name().to_string();
+    Ok(match kind.as_str() {
+        "INTEGER" => SqlValue::Int(row.try_get(index)?),
+        "REAL" => SqlValue::Real(row.try_get(index)?),
+        "BLOB" => SqlValue::Blob(row.try_get(index)?),
+        _ => SqlValue::Text(row.try_get(index)?),
+    })
+}
+
+fn field<'a>(row: &'a SourceRow, column: &str) -> Option<&'a SqlValue> {
+    row.iter()
+        .find(|(name, _)| *name == column)
+        .map(|(_, value)| value)
+}
+
+/// Insert `row` into `table`, returning the rows written
+async fn insert_row(
+    tx: &mut crate::DbTransaction<'_>,
+    verb: &str,
+    table: &str,
+    row: &SourceRow,
+) -> ArcResult<u64> {
+    let columns: Vec<_> = row.iter().map(|(column, _)| *column).collect();
+    let sql = format!(
+        "{verb} INTO `{table}` ({}) VALUES ({})",
+        columns.join(", "),
+        vec!["?"; columns.len()].join(", ")
+    );
+    let query = row
+        .iter()
+        .fold(sqlx::query(&sql), |query, (_, value)| value.bind(query));
+    Ok(query.execute(&mut **tx).await?.rows_affected())
+}
+
+fn is_user_code(code: &str) -> bool {
+    code.len() == 9 && code.bytes().all(|byte| byte.is_ascii_digit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn existing() -> ExistingUsers {
+        ExistingUsers {
+            ids: HashSet::from([2_000_001]),
+            names: HashSet::from(["admin".to_string()]),
+            codes: HashSet::from(["123456789".to_string()]),
+            next_id: 2_000_002,
+        }
+    }
+
+    #[test]
+    fn test_plan_keeps_free_ids_and_codes() {
+        let mut existing = existing();
+        assert_eq!(
+            existing.plan(2_000_005, "hikari", "000000001"),
+            UserPlan::Import {
+                user_id: 2_000_005,
+                user_code: "000000001".to_string(),
+                code_regenerated: false,
+            }
+        );
+    }
+
+    #[test]
+    fn test_plan_remaps_taken_ids_and_codes() {
+        let mut existing = existing();
+        let UserPlan::Import {
+            user_id,
+            user_code,
+            code_regenerated,
+        } = existing.plan(2_000_001, "tairitsu", "123456789")
+        else {
+            panic!("expected an import");
+        };
+        assert_eq!(user_id, 2_000_002);
+        assert!(code_regenerated);
+        assert!(is_user_code(&user_code));
+        assert_ne!(user_code, "123456789");
+
+        // The remapped id is taken now too
+        let UserPlan::Import { user_id, .. } = existing.plan(2_000_001, "kou", "000000002") else {
+            panic!("expected an import");
+        };
+        assert_eq!(user_id, 2_000_003);
+    }
+
+    #[test]
+    fn test_plan_skips_taken_names() {
+        let mut existing = existing();
+        assert_eq!(
+            existing.plan(2_000_009, "Admin", "000000003"),
+            UserPlan::Skip
+        );
+
+        existing.plan(2_000_010, "lagrange", "000000004");
+        assert_eq!(
+            existing.plan(2_000_011, "LAGRANGE", "000000005"),
+            UserPlan::Skip
+        );
+    }
+}
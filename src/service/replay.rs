@@ -0,0 +1,279 @@
+use crate::error::{ArcError, ArcResult};
+use crate::service::StorageService;
+use crate::utils::current_timestamp_ms;
+use crate::DbPool;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// Bucket prefix of replay objects on the S3 backend.
+const S3_REPLAY_PREFIX: &str = "replays/";
+/// How long the replay token returned by a score submission can be used.
+pub const REPLAY_TOKEN_TTL_MS: i64 = 24 * 60 * 60 * 1000;
+
+/// Replay stored for one play, identified like a `user_score` row.
+#[derive(Debug, Clone, Serialize)]
+pub struct ReplayInfo {
+    pub song_id: String,
+    pub difficulty: i32,
+    pub time_played: i64,
+    pub size: i32,
+    pub sha256: String,
+    pub compression: String,
+}
+
+/// Stores replay blobs that clients attach to submitted plays, on the local
+/// disk or in the S3 bucket when that backend is enabled.
+///
+/// Replays must be gzip, zlib or zstd compressed; the server only checks
+/// the container and keeps the blob as uploaded.
+#[derive(Debug, Clone)]
+pub struct ReplayService {
+    pool: DbPool,
+    folder: PathBuf,
+    storage: Option<Arc<StorageService>>,
+}
+
+impl ReplayService {
+    pub fn new(pool: DbPool, folder: PathBuf) -> Self {
+        Self {
+            pool,
+            folder,
+            storage: None,
+        }
+    }
+
+    pub fn with_storage(mut self, storage: Option<Arc<StorageService>>) -> Self {
+        self.storage = storage;
+        self
+    }
+
+    /// Attach `bytes` to a play of the user. Without `time_played` the latest
+    /// play of the chart is used. Uploading again replaces the old replay.
+    pub async fn upload(
+        &self,
+        user_id: i32,
+        song_id: &str,
+        difficulty: i32,
+        time_played: Option<i64>,
+        bytes: Vec<u8>,
+    ) -> ArcResult<ReplayInfo> {
+        let time_played = match time_played {
+            Some(time_played) => {
+                sqlx::query_scalar!(
+                    "SELECT time_played FROM user_score
+                 WHERE user_id = ? AND song_id = ? AND difficulty = ? AND time_played = ?",
+                    user_id,
+                    song_id,
+                    difficulty,
+                    time_played
+                )
+                .fetch_optional(&self.pool)
+                .await?
+            }
+            None => {
+                sqlx::query_scalar!(
+                    "SELECT time_played FROM user_score
+                 WHERE user_id = ? AND song_id = ? AND difficulty = ?
+                 ORDER BY time_played DESC
+                 LIMIT 1",
+                    user_id,
+                    song_id,
+                    difficulty
+                )
+                .fetch_optional(&self.pool)
+                .await?
+            }
+        }
+        .ok_or_else(|| ArcError::no_data("No such play.", 108))?;
+        self.store(user_id, song_id, difficulty, time_played, bytes)
+            .await
+    }
+
+    /// Attach `bytes` to the play a replay token was issued for. The token
+    /// is used up by a successful upload.
+    pub async fn upload_with_token(
+        &self,
+        user_id: i32,
+        token: &str,
+        bytes: Vec<u8>,
+    ) -> ArcResult<ReplayInfo> {
+        let play = sqlx::query!(
+            "SELECT song_id, difficulty, time_played FROM score_replay_token
+             WHERE token = ? AND user_id = ? AND expires_at > ?",
+            token,
+            user_id,
+            current_timestamp_ms()
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or_else(|| ArcError::no_data("Invalid or expired replay token.", 108))?;
+
+        let info = self
+            .store(
+                user_id,
+                &play.song_id,
+                play.difficulty,
+                play.time_played,
+                bytes,
+            )
+            .await?;
+        sqlx::query!("DELETE FROM score_replay_token WHERE token = ?", token)
+            .execute(&self.pool)
+            .await?;
+        Ok(info)
+    }
+
+    async fn store(
+        &self,
+        user_id: i32,
+        song_id: &str,
+        difficulty: i32,
+        time_played: i64,
+        bytes: Vec<u8>,
+    ) -> ArcResult<ReplayInfo> {
+        if bytes.is_empty() {
+            return Err(ArcError::input("Replay is empty."));
+        }
+        let compression = replay_compression(&bytes)
+            .ok_or_else(|| ArcError::input("Replay must be gzip, zlib or zstd compressed."))?;
+        let key = replay_object_key(user_id, song_id, difficulty, time_played)
+            .ok_or_else(|| ArcError::input("Invalid song id."))?;
+
+        let size = bytes.len() as i32;
+        let sha256 = format!("{:x}", Sha256::digest(&bytes));
+        match &self.storage {
+            Some(storage) => {
+                storage
+                    .put_object(&format!("{S3_REPLAY_PREFIX}{key}"), bytes)
+                    .await?
+            }
+            None => {
+                let path = self.folder.join(&key);
+                if let Some(parent) = path.parent() {
+                    tokio::fs::create_dir_all(parent).await?;
+                }
+                tokio::fs::write(&path, bytes).await?;
+            }
+        }
+
+        sqlx::query!(
+            "INSERT INTO score_replay
+             (user_id, song_id, difficulty, time_played, storage_key, size, sha256,
+              compression, uploaded_at)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+             ON DUPLICATE KEY UPDATE
+             storage_key = VALUES(storage_key),
+             size = VALUES(size),
+             sha256 = VALUES(sha256),
+             compression = VALUES(compression),
+             uploaded_at = VALUES(uploaded_at)",
+            user_id,
+            song_id,
+            difficulty,
+            time_played,
+            key,
+            size,
+            sha256,
+            compression,
+            current_timestamp_ms()
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(ReplayInfo {
+            song_id: song_id.to_string(),
+            difficulty,
+            time_played,
+            size,
+            sha256,
+            compression: compression.to_string(),
+        })
+    }
+
+    /// Read the replay attached to a play of `user_id`.
+    pub async fn download(
+        &self,
+        user_id: i32,
+        song_id: &str,
+        difficulty: i32,
+        time_played: i64,
+    ) -> ArcResult<Vec<u8>> {
+        let key = sqlx::query_scalar!(
+            "SELECT storage_key FROM score_replay
+             WHERE user_id = ? AND song_id = ? AND difficulty = ? AND time_played = ?",
+            user_id,
+            song_id,
+            difficulty,
+            time_played
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or_else(|| ArcError::no_data("No replay for this play.", 108))?;
+
+        match &self.storage {
+            Some(storage) => {
+                storage
+                    .get_object(&format!("{S3_REPLAY_PREFIX}{key}"))
+                    .await
+            }
+            None => Ok(tokio::fs::read(self.folder.join(&key)).await?),
+        }
+    }
+}
+
+/// Compression container of a replay blob, from its magic bytes.
+fn replay_compression(bytes: &[u8]) -> Option<&'static str> {
+    match bytes {
+        [0x1f, 0x8b, ..] => Some("gzip"),
+        [0x28, 0xb5, 0x2f, 0xfd, ..] => Some("zstd"),
+        [0x78, 0x01 | 0x5e | 0x9c | 0xda, ..] => Some("zlib"),
+        _ => None,
+    }
+}
+
+/// Storage key of a replay, `<user_id>/<song_id>/<difficulty>-<time_played>.bin`,
+/// relative to the replay folder or bucket prefix. Returns `None` for song ids
+/// that could escape it.
+fn replay_object_key(
+    user_id: i32,
+    song_id: &str,
+    difficulty: i32,
+    time_played: i64,
+) -> Option<String> {
+    let safe = !song_id.is_empty()
+        && song_id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    safe.then(|| format!("{user_id}/{song_id}/{difficulty}-{time_played}.bin"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_replay_object_key() {
+        assert_eq!(
+            replay_object_key(7, "grievouslady", 2, 1700000000000).as_deref(),
+            Some("7/grievouslady/2-1700000000000.bin")
+        );
+        assert_eq!(replay_object_key(7, "../etc", 2, 1), None);
+        assert_eq!(replay_object_key(7, "a/b", 2, 1), None);
+        assert_eq!(replay_object_key(7, "", 2, 1), None);
+    }
+
+    #[test]
+    fn test_replay_compression() {
+        assert_eq!(replay_compression(&[0x1f, 0x8b, 0x08, 0x00]), Some("gzip"));
+        assert_eq!(
+            replay_compression(&[0x28, 0xb5, 0x2f, 0xfd, 0x00]),
+            Some("zstd")
+        );
+        assert_eq!(replay_compression(&[0x78, 0x9c, 0x01]), Some("zlib"));
+        assert_eq!(replay_compression(&[0x78, 0x00]), None);
+        assert_eq!(replay_compression(b"raw replay"), None);
+        assert_eq!(replay_compression(&[0x1f]), None);
+    }
+}
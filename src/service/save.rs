@@ -0,0 +1,476 @@
+use crate::error::{ArcError, ArcResult};
+use crate::model::UserSaveVersion;
+use crate::route::user::CloudSaveRequest;
+use crate::service::user::{UserService, FINGERPRINT_INSTALL_ID};
+use crate::utils::current_timestamp_ms;
+use crate::{DbPool, DbTransaction};
+use serde_json::Value;
+
+/// Archived cloud save versions kept per user.
+const SAVE_HISTORY_LIMIT: i64 = 10;
+
+/// Cloud save (`user/me/save`) storage
+///
+/// Each upload carries every save section next to the MD5 of its payload;
+/// sections are verified and normalized before they are stored, and the
+/// replaced save is kept in `user_save_history` for admin rollback.
+#[derive(Debug, Clone)]
+pub struct SaveService {
+    pool: DbPool,
+}
+
+impl SaveService {
+    pub fn new(pool: DbPool) -> Self {
+        Self { pool }
+    }
+
+    /// Get user's cloud save data
+    ///
+    /// Returns the stored save in the shape the client restores from; a user
+    /// without a save gets empty fields and `createdAt` 0.
+    pub async fn get_save_data(&self, user_id: i32) -> ArcResult<serde_json::Value> {
+        let save_data = sqlx::query!("SELECT * FROM user_save WHERE user_id = ?", user_id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        let (
+            scores_data,
+            clearlamps_data,
+            clearedsongs_data,
+            unlocklist_data,
+            installid_data,
+            devicemodelname_data,
+            story_data,
+            created_at,
+            finalestate_data,
+        ) = if let Some(data) = save_data {
+            (
+                Self::load_cloud_array_value(data.scores_data.as_deref()),
+                Self::load_cloud_array_value(data.clearlamps_data.as_deref()),
+                Self::load_cloud_array_value(data.clearedsongs_data.as_deref()),
+                Self::load_cloud_array_value(data.unlocklist_data.as_deref()),
+                Self::load_cloud_val_string(data.installid_data.as_deref()),
+                Self::load_cloud_val_string(data.devicemodelname_data.as_deref()),
+                Self::load_cloud_array_value(data.story_data.as_deref()),
+                data.createdAt.unwrap_or(0),
+                data.finalestate_data.unwrap_or_default(),
+            )
+        } else {
+            (
+                serde_json::json!([]),
+                serde_json::json!([]),
+                serde_json::json!([]),
+                serde_json::json!([]),
+                String::new(),
+                String::new(),
+                serde_json::json!([]),
+                0,
+                String::new(),
+            )
+        };
+
+        let response = serde_json::json!({
+            "user_id": user_id,
+            "story": {
+                "": story_data
+            },
+            "devicemodelname": {
+                "val": devicemodelname_data
+            },
+            "installid": {
+                "val": installid_data
+            },
+            "unlocklist": {
+                "": unlocklist_data
+            },
+            "clearedsongs": {
+                "": clearedsongs_data
+            },
+            "clearlamps": {
+                "": clearlamps_data
+            },
+            "scores": {
+                "": scores_data
+            },
+            "version": {
+                "val": 1
+            },
+            "createdAt": created_at,
+            "finalestate": {
+                "val": finalestate_data
+            }
+        });
+
+        Ok(response)
+    }
+
+    /// Update user's cloud save data
+    ///
+    /// Updates the user's cloud save data with new values. When the client
+    /// says which save it started from (`base_created_at`) and the stored
+    /// save is newer, the upload is rejected as a conflict. The replaced save
+    /// is archived to `user_save_history`.
+    pub async fn update_save_data(
+        &self,
+        user_id: i32,
+        save_request: &CloudSaveRequest,
+    ) -> ArcResult<()> {
+        let current_time = current_timestamp_ms();
+        let scores_data =
+            Self::normalize_cloud_array(&save_request.scores_data, &save_request.scores_checksum)?;
+        let clearlamps_data = Self::normalize_cloud_array(
+            &save_request.clearlamps_data,
+            &save_request.clearlamps_checksum,
+        )?;
+        let clearedsongs_data = Self::normalize_cloud_array(
+            &save_request.clearedsongs_data,
+            &save_request.clearedsongs_checksum,
+        )?;
+        let unlocklist_data = Self::normalize_cloud_array(
+            &save_request.unlocklist_data,
+            &save_request.unlocklist_checksum,
+        )?;
+        let installid_data = Self::normalize_cloud_val(
+            &save_request.installid_data,
+            &save_request.installid_checksum,
+        )?;
+        let devicemodelname_data = Self::normalize_cloud_val(
+            &save_request.devicemodelname_data,
+            &save_request.devicemodelname_checksum,
+        )?;
+        let story_data =
+            Self::normalize_cloud_array(&save_request.story_data, &save_request.story_checksum)?;
+        let finalestate_data = Self::normalize_cloud_finalestate(
+            save_request.finalestate_data.as_deref(),
+            save_request.finalestate_checksum.as_deref(),
+        )?;
+
+        let mut tx = self.pool.begin().await?;
+        let stored_created_at = sqlx::query_scalar!(
+            "SELECT createdAt FROM user_save WHERE user_id = ? FOR UPDATE",
+            user_id
+        )
+        .fetch_optional(&mut *tx)
+        .await?;
+        if let Some(stored_created_at) = stored_created_at {
+            let stored_created_at = stored_created_at.unwrap_or(0);
+            if is_save_conflict(save_request.base_created_at, stored_created_at) {
+                return Err(ArcError::cloud_save_conflict(stored_created_at));
+            }
+            Self::archive_user_save(&mut tx, user_id, current_time).await?;
+        }
+
+        sqlx::query!(
+            "INSERT INTO user_save (user_id, scores_data, clearlamps_data, clearedsongs_data, unlocklist_data, installid_data, devicemodelname_data, story_data, createdAt, finalestate_data)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+             ON DUPLICATE KEY UPDATE
+             scores_data = VALUES(scores_data),
+             clearlamps_data = VALUES(clearlamps_data),
+             clearedsongs_data = VALUES(clearedsongs_data),
+             unlocklist_data = VALUES(unlocklist_data),
+             installid_data = VALUES(installid_data),
+             devicemodelname_data = VALUES(devicemodelname_data),
+             story_data = VALUES(story_data),
+             createdAt = VALUES(createdAt),
+             finalestate_data = VALUES(finalestate_data)",
+            user_id,
+            scores_data,
+            clearlamps_data,
+            clearedsongs_data,
+            unlocklist_data,
+            installid_data,
+            devicemodelname_data,
+            story_data,
+            current_time,
+            finalestate_data
+        )
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        if let Some(install_id) = cloud_val_text(&installid_data) {
+            let device_model = cloud_val_text(&devicemodelname_data);
+            if let Err(e) = UserService::new(self.pool.clone())
+                .record_device_fingerprint(
+                    user_id,
+                    FINGERPRINT_INSTALL_ID,
+                    &install_id,
+                    device_model.as_deref(),
+                    None,
+                )
+                .await
+            {
+                log::warn!("Failed to record install id for user {user_id}: {e}");
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Copy the current save into `user_save_history` and drop versions
+    /// beyond [`SAVE_HISTORY_LIMIT`].
+    async fn archive_user_save(
+        tx: &mut DbTransaction<'_>,
+        user_id: i32,
+        archived_at: i64,
+    ) -> ArcResult<()> {
+        sqlx::query!(
+            "INSERT INTO user_save_history (user_id, scores_data, clearlamps_data, clearedsongs_data, unlocklist_data, installid_data, devicemodelname_data, story_data, createdAt, finalestate_data, archived_at)
+             SELECT user_id, scores_data, clearlamps_data, clearedsongs_data, unlocklist_data, installid_data, devicemodelname_data, story_data, createdAt, finalestate_data, ?
+             FROM user_save WHERE user_id = ?",
+            archived_at,
+            user_id
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        let keep_from = sqlx::query_scalar!(
+            "SELECT history_id FROM user_save_history WHERE user_id = ?
+             ORDER BY history_id DESC LIMIT 1 OFFSET ?",
+            user_id,
+            SAVE_HISTORY_LIMIT - 1
+        )
+        .fetch_optional(&mut **tx)
+        .await?;
+        if let Some(keep_from) = keep_from {
+            sqlx::query!(
+                "DELETE FROM user_save_history WHERE user_id = ? AND history_id < ?",
+                user_id,
+                keep_from
+            )
+            .execute(&mut **tx)
+            .await?;
+        }
+        Ok(())
+    }
+
+    /// List archived cloud save versions, newest first.
+    pub async fn get_save_history(&self, user_id: i32) -> ArcResult<Vec<UserSaveVersion>> {
+        Ok(sqlx::query_as!(
+            UserSaveVersion,
+            r#"SELECT history_id, createdAt AS created_at, archived_at,
+                    CAST(COALESCE(LENGTH(scores_data), 0) + COALESCE(LENGTH(clearlamps_data), 0)
+                        + COALESCE(LENGTH(clearedsongs_data), 0) + COALESCE(LENGTH(unlocklist_data), 0)
+                        + COALESCE(LENGTH(story_data), 0) + COALESCE(LENGTH(finalestate_data), 0)
+                        AS SIGNED) AS `data_size!: i64`
+             FROM user_save_history WHERE user_id = ?
+             ORDER BY history_id DESC"#,
+            user_id
+        )
+        .fetch_all(&self.pool)
+        .await?)
+    }
+
+    /// Restore an archived save version.
+    ///
+    /// The current save is archived first, and the restored copy gets a new
+    /// `createdAt` so clients see it as the latest save and download it.
+    pub async fn rollback_save(&self, user_id: i32, history_id: i64) -> ArcResult<()> {
+        let now = current_timestamp_ms();
+        let mut tx = self.pool.begin().await?;
+        let exists = sqlx::query_scalar!(
+            "SELECT COUNT(*) FROM user_save_history WHERE user_id = ? AND history_id = ?",
+            user_id,
+            history_id
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+        if exists == 0 {
+            return Err(ArcError::no_data("Save version not found.", 108));
+        }
+
+        let has_current = sqlx::query_scalar!(
+            "SELECT COUNT(*) FROM user_save WHERE user_id = ? FOR UPDATE",
+            user_id
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+        if has_current > 0 {
+            Self::archive_user_save(&mut tx, user_id, now).await?;
+        }
+
+        sqlx::query!(
+            "INSERT INTO user_save (user_id, scores_data, clearlamps_data, clearedsongs_data, unlocklist_data, installid_data, devicemodelname_data, story_data, createdAt, finalestate_data)
+             SELECT user_id, scores_data, clearlamps_data, clearedsongs_data, unlocklist_data, installid_data, devicemodelname_data, story_data, ?, finalestate_data
+             FROM user_save_history WHERE user_id = ? AND history_id = ?
+             ON DUPLICATE KEY UPDATE
+             scores_data = VALUES(scores_data),
+             clearlamps_data = VALUES(clearlamps_data),
+             clearedsongs_data = VALUES(clearedsongs_data),
+             unlocklist_data = VALUES(unlocklist_data),
+             installid_data = VALUES(installid_data),
+             devicemodelname_data = VALUES(devicemodelname_data),
+             story_data = VALUES(story_data),
+             createdAt = VALUES(createdAt),
+             finalestate_data = VALUES(finalestate_data)",
+            now,
+            user_id,
+            history_id
+        )
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    fn verify_cloud_checksum(value: &str, checksum: &str) -> ArcResult<()> {
+        if md5_hex(value) != checksum.to_ascii_lowercase() {
+            return Err(ArcError::input("Hash value of cloud save data mismatches."));
+        }
+        Ok(())
+    }
+
+    fn parse_cloud_json(value: &str) -> ArcResult<serde_json::Value> {
+        serde_json::from_str::<serde_json::Value>(value)
+            .map_err(|_| ArcError::input("Invalid cloud save payload."))
+    }
+
+    fn normalize_cloud_array(value: &str, checksum: &str) -> ArcResult<String> {
+        if value.is_empty() {
+            return Ok(serde_json::json!({ "": [] }).to_string());
+        }
+
+        Self::verify_cloud_checksum(value, checksum)?;
+        let parsed = Self::parse_cloud_json(value)?;
+        let inner = parsed
+            .get("")
+            .cloned()
+            .ok_or_else(|| ArcError::input("Invalid cloud save payload."))?;
+
+        Ok(serde_json::json!({ "": inner }).to_string())
+    }
+
+    fn normalize_cloud_val(value: &str, checksum: &str) -> ArcResult<String> {
+        if value.is_empty() {
+            return Ok(serde_json::json!({ "val": "" }).to_string());
+        }
+
+        Self::verify_cloud_checksum(value, checksum)?;
+        let parsed = Self::parse_cloud_json(value)?;
+        let inner = parsed
+            .get("val")
+            .cloned()
+            .ok_or_else(|| ArcError::input("Invalid cloud save payload."))?;
+
+        Ok(serde_json::json!({ "val": inner }).to_string())
+    }
+
+    fn normalize_cloud_finalestate(
+        value: Option<&str>,
+        checksum: Option<&str>,
+    ) -> ArcResult<String> {
+        let Some(value) = value else {
+            return Ok(String::new());
+        };
+        if value.is_empty() {
+            return Ok(String::new());
+        }
+
+        let checksum = checksum.ok_or_else(|| ArcError::input("Missing finalestate checksum."))?;
+        Self::verify_cloud_checksum(value, checksum)?;
+        let parsed = Self::parse_cloud_json(value)?;
+        let inner = parsed
+            .get("val")
+            .cloned()
+            .ok_or_else(|| ArcError::input("Invalid cloud save payload."))?;
+
+        Ok(match inner {
+            Value::String(s) => s,
+            other => other.to_string(),
+        })
+    }
+
+    fn load_cloud_array_value(raw: Option<&str>) -> Value {
+        let Some(raw) = raw else {
+            return serde_json::json!([]);
+        };
+        if raw.is_empty() {
+            return serde_json::json!([]);
+        }
+
+        match serde_json::from_str::<Value>(raw) {
+            Ok(Value::Object(map)) => map.get("").cloned().unwrap_or(serde_json::json!([])),
+            Ok(value) => value,
+            Err(_) => serde_json::json!([]),
+        }
+    }
+
+    fn load_cloud_val_string(raw: Option<&str>) -> String {
+        let Some(raw) = raw else {
+            return String::new();
+        };
+        if raw.is_empty() {
+            return String::new();
+        }
+
+        match serde_json::from_str::<Value>(raw) {
+            Ok(Value::Object(map)) => map
+                .get("val")
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+                .to_string(),
+            Ok(Value::String(s)) => s,
+            Ok(other) => other.to_string(),
+            Err(_) => String::new(),
+        }
+    }
+}
+
+fn md5_hex(value: &str) -> String {
+    format!("{:x}", md5::compute(value.as_bytes()))
+}
+
+/// Text of a normalized `{"val": ...}` cloud save field, if non-empty.
+fn cloud_val_text(normalized: &str) -> Option<String> {
+    let parsed: Value = serde_json::from_str(normalized).ok()?;
+    let text = match parsed.get("val")? {
+        Value::String(text) => text.trim().to_string(),
+        Value::Number(number) => number.to_string(),
+        _ => return None,
+    };
+    (!text.is_empty()).then_some(text)
+}
+
+/// Whether an upload based on `base_created_at` would overwrite a newer
+/// stored save. Clients that do not send a base version are never rejected.
+fn is_save_conflict(base_created_at: Option<i64>, stored_created_at: i64) -> bool {
+    base_created_at.is_some_and(|base| stored_created_at > base)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_save_conflict() {
+        assert!(!is_save_conflict(None, 2000));
+        assert!(!is_save_conflict(Some(2000), 2000));
+        assert!(!is_save_conflict(Some(3000), 2000));
+        assert!(is_save_conflict(Some(1000), 2000));
+    }
+
+    #[test]
+    fn test_cloud_val_text() {
+        assert_eq!(
+            cloud_val_text(r#"{"val":" 1a2b-3c "}"#),
+            Some("1a2b-3c".to_string())
+        );
+        assert_eq!(cloud_val_text(r#"{"val":42}"#), Some("42".to_string()));
+        assert_eq!(cloud_val_text(r#"{"val":""}"#), None);
+        assert_eq!(cloud_val_text("not json"), None);
+    }
+
+    #[test]
+    fn test_normalize_cloud_array_verifies_checksum() {
+        let payload = r#"{"":[{"song_id":"tempestissimo"}]}"#;
+        let normalized =
+            SaveService::normalize_cloud_array(payload, &md5_hex(payload).to_uppercase()).unwrap();
+        assert_eq!(normalized, payload);
+        assert!(SaveService::normalize_cloud_array(payload, &md5_hex("other")).is_err());
+        assert_eq!(
+            SaveService::normalize_cloud_array("", "").unwrap(),
+            r#"{"":[]}"#
+        );
+    }
+}
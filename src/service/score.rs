@@ -2,41 +2,112 @@ use crate::error::{ArcError, ArcResult};
 // use crate::service::UserService;
 use serde_json::Value;
 
-use crate::config::{Constants, CONFIG};
+use crate::config::CONFIG;
 use crate::model::download::{
     CourseTokenRequest, CourseTokenResponse, ScoreSubmission, SongplayToken, WorldTokenRequest,
     WorldTokenResponse,
 };
+use crate::model::item::ItemTypes;
 use crate::model::score::{
-    Potential, RankingScoreRow, RankingScoreRowComplete, Recent30Tuple, Score, UserPlay, UserScore,
+    Potential, PotentialRankEntry, PotentialRanking, RankingScoreRow, RankingScoreRowComplete,
+    Recent30Tuple, Score, UserPlay, UserScore, UserWorldRank, WorldRankEntry, WorldRanking,
 };
 use crate::model::user::User;
-use crate::model::world::WorldStep;
+use crate::model::world::{WorldMap, WorldStep};
 use crate::service::cache::{env_ttl_seconds, CacheService};
-use crate::service::character::CharacterService;
+use crate::service::character::{
+    CharacterService, SkillAfterClimb, SkillClimb, SkillEngine, SkillPlay, SkillStatBonus,
+};
+use crate::service::event::limited_events;
+use crate::service::game_constants::game_constants;
 use crate::service::item::ItemService;
+use crate::service::replay::REPLAY_TOKEN_TTL_MS;
+use crate::service::score_queue::{ScoreWrite, ScoreWriteQueue};
+use crate::service::score_validator::ScoreValidator;
+use crate::service::stamina::{StaminaImpl, StaminaService};
 use crate::service::user::UserService;
-use crate::service::world::{get_map_parser, StaminaImpl, WorldService};
-use crate::utils::sql_placeholders;
+use crate::service::user_cache::UserCache;
+use crate::service::webhook::WebhookNotifier;
+use crate::service::world::{get_map_parser, WorldService};
+use crate::utils::{current_timestamp_ms, sql_placeholders, today_timestamp_seconds};
+use crate::{DbPool, ReadPool};
 use base64::{engine::general_purpose, Engine as _};
-use chrono::{Local, TimeZone};
 use md5;
 use rand::Rng;
 use serde_json::json;
-use sqlx::MySqlPool;
 use std::collections::HashMap;
 use std::path::Path;
+use std::sync::{OnceLock, RwLock};
 use std::time::{SystemTime, UNIX_EPOCH};
 
-/// Constants for score calculations
-const BEST30_WEIGHT: f64 = 1.0 / 40.0;
-const RECENT10_WEIGHT: f64 = 1.0 / 40.0;
 type SongKey = (String, i32);
 type SongEntry = (usize, i32, f64);
 type SongEntryMap = HashMap<SongKey, Vec<SongEntry>>;
 type JsonMap = HashMap<String, Value>;
 
 const TRACE_COMPLETE_BASE_TICKET_REWARD: i64 = 20;
+/// Page size of `score/song` when the client does not ask for one
+pub const LEADERBOARD_DEFAULT_LIMIT: i32 = 20;
+pub const LEADERBOARD_MAX_LIMIT: i32 = 100;
+
+/// Which slice of a chart leaderboard to return
+///
+/// Ranks in the result are absolute, so the rank of the last entry is the
+/// `offset` of the next page.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LeaderboardPage {
+    pub offset: Option<i32>,
+    pub limit: Option<i32>,
+    /// Center the page on this player's rank; wins over `offset`
+    pub around_user: Option<i32>,
+}
+
+impl LeaderboardPage {
+    /// Whether this is the plain top page served to the game client
+    pub fn is_default(&self) -> bool {
+        self.around_user.is_none()
+            && self.offset.unwrap_or(0) <= 0
+            && self.limit.unwrap_or(LEADERBOARD_DEFAULT_LIMIT) == LEADERBOARD_DEFAULT_LIMIT
+    }
+
+    fn clamped_limit(&self) -> i32 {
+        self.limit
+            .unwrap_or(LEADERBOARD_DEFAULT_LIMIT)
+            .clamp(1, LEADERBOARD_MAX_LIMIT)
+    }
+}
+
+static POTENTIAL_RANKING: OnceLock<RwLock<Option<PotentialRanking>>> = OnceLock::new();
+
+fn potential_ranking_lock() -> &'static RwLock<Option<PotentialRanking>> {
+    POTENTIAL_RANKING.get_or_init(|| RwLock::new(None))
+}
+
+static WORLD_RANKING: OnceLock<RwLock<Option<WorldRanking>>> = OnceLock::new();
+
+fn world_ranking_lock() -> &'static RwLock<Option<WorldRanking>> {
+    WORLD_RANKING.get_or_init(|| RwLock::new(None))
+}
+
+/// 1-based ranks of `scores` sorted from highest to lowest, where equal
+/// scores share the rank of the first of them, as `get_user_global_rank`
+/// counts them
+fn competition_ranks(scores: &[i32]) -> Vec<i32> {
+    let mut ranks = Vec::with_capacity(scores.len());
+    for (i, score) in scores.iter().enumerate() {
+        let rank = match (i.checked_sub(1), ranks.last()) {
+            (Some(prev), Some(&prev_rank)) if scores[prev] == *score => prev_rank,
+            _ => i as i32 + 1,
+        };
+        ranks.push(rank);
+    }
+    ranks
+}
+
+/// Offset of a page of `limit` entries with the 1-based `rank` in its middle
+fn leaderboard_offset_around(rank: i32, limit: i32) -> i32 {
+    (rank - 1 - (limit - 1) / 2).max(0)
+}
 
 fn calculate_trace_complete_ticket_reward(
     clear_type: i32,
@@ -84,7 +155,9 @@ fn calculate_trace_complete_ticket_reward(
 
 /// Score service for handling score submission, validation, and calculations
 pub struct ScoreService {
-    pool: MySqlPool,
+    pool: DbPool,
+    /// Rankings and leaderboards, possibly on a read replica
+    read_pool: DbPool,
     cache: Option<CacheService>,
     score_top_cache_ttl_seconds: u64,
     score_user_cache_ttl_seconds: u64,
@@ -93,12 +166,20 @@ pub struct ScoreService {
     user_rating_cache_ttl_seconds: u64,
     global_rank_cache_ttl_seconds: u64,
     zset_cache_ttl_seconds: u64,
+    user_cache: UserCache,
+    stamina_service: StaminaService,
+    score_validator: ScoreValidator,
+    write_queue: ScoreWriteQueue,
 }
 
 impl ScoreService {
     /// Create a new score service instance
-    pub fn new(pool: MySqlPool) -> Self {
+    pub fn new(pool: DbPool) -> Self {
         Self {
+            stamina_service: StaminaService::new(pool.clone()),
+            score_validator: ScoreValidator::new(pool.clone()),
+            write_queue: ScoreWriteQueue::inline(pool.clone()),
+            read_pool: pool.clone(),
             pool,
             cache: None,
             score_top_cache_ttl_seconds: env_ttl_seconds("REDIS_SCORE_TOP_TTL_SECONDS", 3),
@@ -111,6 +192,7 @@ impl ScoreService {
             user_rating_cache_ttl_seconds: env_ttl_seconds("REDIS_USER_RATING_TTL_SECONDS", 5),
             global_rank_cache_ttl_seconds: env_ttl_seconds("REDIS_GLOBAL_RANK_TTL_SECONDS", 10),
             zset_cache_ttl_seconds: env_ttl_seconds("REDIS_ZSET_RANK_TTL_SECONDS", 300),
+            user_cache: UserCache::global().clone(),
         }
     }
 
@@ -119,6 +201,30 @@ impl ScoreService {
         self
     }
 
+    /// Send the play log and rating history through `queue` instead of
+    /// writing them before the submit response.
+    pub fn with_write_queue(mut self, queue: ScoreWriteQueue) -> Self {
+        self.write_queue = queue;
+        self
+    }
+
+    /// Report flagged submissions through `webhook`.
+    pub fn with_webhook(mut self, webhook: WebhookNotifier) -> Self {
+        self.score_validator = self.score_validator.with_webhook(webhook);
+        self
+    }
+
+    /// Read leaderboards and rank counts from `read_pool`; the reader's own
+    /// best score still comes from the primary.
+    pub fn with_read_pool(mut self, read_pool: &ReadPool) -> Self {
+        self.read_pool = (**read_pool).clone();
+        self
+    }
+
+    pub fn write_queue(&self) -> &ScoreWriteQueue {
+        &self.write_queue
+    }
+
     fn score_top_cache_key(song_id: &str, difficulty: i32) -> String {
         format!("score:top:{song_id}:{difficulty}")
     }
@@ -245,6 +351,41 @@ impl ScoreService {
         Ok(())
     }
 
+    /// Set or lift the shadow ban of `user_id`, then drop the cached song
+    /// and global rankings so they are rebuilt with or without the player.
+    pub async fn set_user_shadow_ban(&self, user_id: i32, shadow_banned: bool) -> ArcResult<u64> {
+        let affected_rows = sqlx::query!(
+            "UPDATE user SET is_shadow_banned = ? WHERE user_id = ?",
+            shadow_banned as i8,
+            user_id
+        )
+        .execute(&self.pool)
+        .await?
+        .rows_affected();
+
+        if let Some(cache) = &self.cache {
+            let score_keys = sqlx::query!(
+                "SELECT song_id, difficulty FROM best_score WHERE user_id = ?",
+                user_id
+            )
+            .fetch_all(&self.pool)
+            .await?;
+            for row in score_keys {
+                cache
+                    .del(&Self::score_rank_zset_ready_key(
+                        &row.song_id,
+                        row.difficulty,
+                    ))
+                    .await;
+                self.invalidate_score_top_cache(&row.song_id, row.difficulty)
+                    .await;
+            }
+            cache.del(Self::global_rank_zset_ready_key()).await;
+        }
+
+        Ok(affected_rows)
+    }
+
     async fn invalidate_score_derived_caches(&self, user_id: i32) {
         if let Some(cache) = &self.cache {
             cache.del(&Self::score_potential_cache_key(user_id)).await;
@@ -294,6 +435,8 @@ impl ScoreService {
             }
         }
 
+        let current_map = self.get_user_current_map(user_id).await?;
+
         // Validate prog_boost and beyond_boost_gauge like Python version
         if prog_boost_multiply != 0 || beyond_boost_gauge_use != 0 {
             let boost_data = sqlx::query!(
@@ -309,11 +452,11 @@ impl ScoreService {
                 } else {
                     0
                 };
-                if data.beyond_boost_gauge.unwrap_or(0.0) < beyond_boost_gauge_use as f64
-                    || !matches!(beyond_boost_gauge_use, 100 | 200)
-                {
-                    beyond_boost_gauge_use = 0;
-                }
+                beyond_boost_gauge_use = valid_beyond_boost_gauge_use(
+                    beyond_boost_gauge_use,
+                    data.beyond_boost_gauge.unwrap_or(0.0),
+                    current_map.is_beyond,
+                );
             } else {
                 prog_boost_multiply = 0;
                 beyond_boost_gauge_use = 0;
@@ -321,7 +464,8 @@ impl ScoreService {
         }
 
         // Get user map and character info for stamina and skill processing
-        let stamina_cost = self.get_user_current_map(user_id).await?;
+        let stamina_cost =
+            limited_events().stamina_cost(current_map.stamina_cost_value(), current_timestamp_ms());
         let raw_stamina = user.stamina.unwrap_or(0);
         let raw_max_stamina_ts = user.max_stamina_ts.unwrap_or(0);
         let mut stamina = StaminaImpl::new(raw_stamina, raw_max_stamina_ts);
@@ -329,7 +473,7 @@ impl ScoreService {
         // Auto-repair legacy stamina state:
         // if overcap stamina exists while max_stamina_ts is still in the future, normalize it
         // to Python's stamina setter semantics before validation.
-        if raw_stamina > Constants::MAX_STAMINA && raw_max_stamina_ts > current_timestamp() {
+        if raw_stamina > game_constants().max_stamina && raw_max_stamina_ts > current_timestamp() {
             stamina.set_stamina(raw_stamina);
             sqlx::query!(
                 "UPDATE user SET stamina = ?, max_stamina_ts = ? WHERE user_id = ?",
@@ -344,13 +488,12 @@ impl ScoreService {
         let current_stamina = stamina.get_current_stamina();
 
         if current_stamina < stamina_cost * stamina_multiply {
-            return Err(ArcError::StaminaNotEnough {
-                message: "Stamina is not enough.".to_string(),
-                error_code: 107,
-                api_error_code: -999,
-                extra_data: None,
-                status: 200,
-            });
+            let required = stamina_cost * stamina_multiply;
+            return Err(ArcError::stamina_not_enough(
+                required,
+                current_stamina,
+                stamina.seconds_until(required),
+            ));
         }
 
         // Check character skill and invasion
@@ -370,12 +513,14 @@ impl ScoreService {
             let insight_state = user.insight_state.unwrap_or(4);
             if insight_state == 3 || insight_state == 5 {
                 // Use weighted choice like Python's choices([0, 1, 2], [weights])
+                let constants = game_constants();
                 let no_invasion_weight =
-                    (1.0 - CONFIG.invasion_start_weight - CONFIG.invasion_hard_weight).max(0.0f64);
+                    (1.0 - constants.invasion_start_weight - constants.invasion_hard_weight)
+                        .max(0.0f64);
                 let weights = [
                     no_invasion_weight,
-                    CONFIG.invasion_start_weight,
-                    CONFIG.invasion_hard_weight,
+                    constants.invasion_start_weight,
+                    constants.invasion_hard_weight,
                 ];
                 let mut cumulative = 0.0;
                 let rand_val: f64 = rand::thread_rng().gen();
@@ -411,7 +556,11 @@ impl ScoreService {
 
         // Insert new token
         sqlx::query!(
-            "INSERT INTO songplay_token VALUES (?, ?, ?, ?, '', -1, 0, 0, ?, ?, ?, ?, ?, ?, ?)",
+            "INSERT INTO songplay_token (token, user_id, song_id, difficulty, course_id,
+             course_state, course_score, course_clear_type, stamina_multiply, fragment_multiply,
+             prog_boost_multiply, beyond_boost_gauge_usage, skill_cytusii_flag,
+             skill_chinatsu_flag, invasion_flag, created_at)
+             VALUES (?, ?, ?, ?, '', -1, 0, 0, ?, ?, ?, ?, ?, ?, ?, ?)",
             token,
             user_id,
             request.song_id,
@@ -422,7 +571,8 @@ impl ScoreService {
             beyond_boost_gauge_use,
             skill_cytusii_flag,
             skill_chinatsu_flag,
-            invasion_flag
+            invasion_flag,
+            current_timestamp_ms()
         )
         .execute(&self.pool)
         .await?;
@@ -510,7 +660,7 @@ impl ScoreService {
                     "course_id is required for new course session",
                 ));
             }
-        } else if (0..=3).contains(&course_play_state) {
+        } else if (0..COURSE_CLEARED_STATE).contains(&course_play_state) {
             // Validate token and continue course
             if let Some(previous_token) = request.previous_token {
                 token = self.update_course_token(&previous_token, user_id).await?;
@@ -522,7 +672,7 @@ impl ScoreService {
         } else {
             // Course mode has ended
             self.clear_user_songplay_tokens(user_id).await?;
-            status = if course_play_state == 4 {
+            status = if course_play_state == COURSE_CLEARED_STATE {
                 "cleared".to_string()
             } else {
                 "failed".to_string()
@@ -608,7 +758,12 @@ impl ScoreService {
             Some(submission.miss_count),
             Some(submission.health),
             Some(submission.modifier),
-            Some(current_timestamp()),
+            // Milliseconds until `upload_score` has checked it.
+            Some(
+                submission
+                    .time_played
+                    .map_or_else(current_timestamp, |seconds| seconds.saturating_mul(1000)),
+            ),
             Some(submission.clear_type),
         );
 
@@ -617,13 +772,7 @@ impl ScoreService {
             .get_song_file_hash(&submission.song_id, submission.difficulty)
             .await;
         if !user_play.is_valid(expected_hash.as_deref()) {
-            return Err(ArcError::Input {
-                message: "Invalid score.".to_string(),
-                error_code: 107,
-                api_error_code: -100,
-                extra_data: None,
-                status: 200,
-            });
+            return Err(ArcError::invalid_score());
         }
 
         // Upload score (which handles rating calculation internally)
@@ -634,7 +783,7 @@ impl ScoreService {
 
         // Python baseline response: (world/course payload) + common fields
         let potential = self.calculate_user_potential(user_id).await?;
-        let ptt_value = potential.calculate_value(BEST30_WEIGHT, RECENT10_WEIGHT);
+        let ptt_value = potential.value();
         user_play.ptt = Some(potential);
 
         let user_rating = self.get_user_rating_ptt(user_id).await?;
@@ -650,10 +799,116 @@ impl ScoreService {
             "finale_play_value".to_string(),
             Value::from(9.065 * user_play.user_score.score.rating.sqrt()),
         );
+        let replay_token = self
+            .issue_replay_token(
+                user_id,
+                &submission.song_id,
+                submission.difficulty,
+                user_play.user_score.score.time_played,
+            )
+            .await?;
+        result.insert("replay_token".to_string(), Value::from(replay_token));
 
         Ok(result)
     }
 
+    /// Issue the token that links a replay upload to the play just recorded,
+    /// so that suspicious scores can be audited against their replay.
+    async fn issue_replay_token(
+        &self,
+        user_id: i32,
+        song_id: &str,
+        difficulty: i32,
+        time_played: i64,
+    ) -> ArcResult<String> {
+        let now = current_timestamp_ms();
+        sqlx::query!(
+            "DELETE FROM score_replay_token WHERE user_id = ? AND expires_at <= ?",
+            user_id,
+            now
+        )
+        .execute(&self.pool)
+        .await?;
+
+        let token = generate_replay_token();
+        sqlx::query!(
+            "INSERT INTO score_replay_token
+             (token, user_id, song_id, difficulty, time_played, expires_at)
+             VALUES (?, ?, ?, ?, ?, ?)",
+            token,
+            user_id,
+            song_id,
+            difficulty,
+            time_played,
+            now + REPLAY_TOKEN_TTL_MS
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(token)
+    }
+
+    /// Record a score for `user_id` outside of a play, e.g. to seed a
+    /// tournament
+    ///
+    /// The score goes through the same best score, recent 30 and potential
+    /// updates as a submission; play tokens, stamina, world and course
+    /// progress and the anomaly checks are skipped. With `dry_run` only the
+    /// rating is worked out. Returns the score with its rating set.
+    pub async fn import_score(
+        &self,
+        user_id: i32,
+        mut score: Score,
+        dry_run: bool,
+    ) -> ArcResult<Score> {
+        let user = self.get_user_info(user_id).await?;
+        if !score.is_valid() {
+            return Err(ArcError::input("Judge counts do not add up to the score."));
+        }
+
+        let chart_constant_tenths = self
+            .get_chart_constant_tenths(&score.song_id, score.difficulty)
+            .await?;
+        if chart_constant_tenths <= 0 {
+            return Err(ArcError::input(format!(
+                "No rated chart for {} difficulty {}.",
+                score.song_id, score.difficulty
+            )));
+        }
+        score.get_rating_by_calc(chart_constant_tenths as f64 / 10.0);
+        if dry_run {
+            return Ok(score);
+        }
+
+        self.write_queue
+            .enqueue(ScoreWrite::ScoreLog {
+                user_id,
+                score: score.clone(),
+            })
+            .await?;
+
+        let mut user_play = imported_user_play(UserScore {
+            score,
+            user_id,
+            name: user.name.unwrap_or_default(),
+            best_clear_type: 0,
+            character: user.character_id.unwrap_or(0),
+            is_char_uncapped: user.is_char_uncapped.unwrap_or(0),
+            is_skill_sealed: user.is_skill_sealed.unwrap_or(0),
+            rank: None,
+        });
+        self.update_best_score(&mut user_play).await?;
+        self.update_recent_30(&user_play).await?;
+
+        let score = user_play.user_score.score;
+        self.invalidate_score_caches(user_id, &score.song_id, score.difficulty)
+            .await;
+        self.invalidate_user_info_cache(user_id).await;
+        self.invalidate_score_derived_caches(user_id).await;
+        self.update_user_rating(user_id).await?;
+
+        Ok(score)
+    }
+
     /// Get top 20 scores for a song
     pub async fn get_song_top_scores(
         &self,
@@ -667,10 +922,65 @@ impl ScoreService {
             }
         }
 
+        let result = self
+            .get_song_score_page(song_id, difficulty, 0, LEADERBOARD_DEFAULT_LIMIT)
+            .await?;
+
+        if let Some(cache) = &self.cache {
+            cache
+                .set_json(&cache_key, &result, self.score_top_cache_ttl_seconds)
+                .await;
+        }
+
+        Ok(result)
+    }
+
+    /// Get one page of a song leaderboard
+    ///
+    /// The default page is the cached top 20; any other page is one ranged
+    /// read of the rank ZSET or one `LIMIT ? OFFSET ?` query.
+    pub async fn get_song_leaderboard(
+        &self,
+        song_id: &str,
+        difficulty: i32,
+        page: LeaderboardPage,
+    ) -> ArcResult<Vec<HashMap<String, serde_json::Value>>> {
+        if page.is_default() {
+            return self.get_song_top_scores(song_id, difficulty).await;
+        }
+
+        let limit = page.clamped_limit();
+        let offset = match page.around_user {
+            Some(user_id) => {
+                let rank = self
+                    .get_song_rank_position(user_id, song_id, difficulty)
+                    .await?
+                    .ok_or_else(|| {
+                        ArcError::no_data("The player has no score on this chart.", 108)
+                    })?;
+                leaderboard_offset_around(rank, limit)
+            }
+            None => page.offset.unwrap_or(0).max(0),
+        };
+
+        self.get_song_score_page(song_id, difficulty, offset, limit)
+            .await
+    }
+
+    async fn get_song_score_page(
+        &self,
+        song_id: &str,
+        difficulty: i32,
+        offset: i32,
+        limit: i32,
+    ) -> ArcResult<Vec<HashMap<String, serde_json::Value>>> {
         let scores = if self.warm_score_rank_zset(song_id, difficulty).await? {
             let cache = self.cache.as_ref().expect("zset_ready requires cache");
             let zset_key = Self::score_rank_zset_key(song_id, difficulty);
-            if let Some(user_ids) = cache.zrevrange(&zset_key, 0, 19).await {
+            if let Some(user_ids) = cache
+                .zrevrange(&zset_key, offset as isize, (offset + limit - 1) as isize)
+                .await
+            {
                 let user_ids = user_ids
                     .into_iter()
                     .filter_map(|id| id.parse::<i32>().ok())
@@ -687,36 +997,73 @@ impl ScoreService {
                     .filter_map(|user_id| rows_by_user.remove(&user_id))
                     .collect::<Vec<_>>()
             } else {
-                self.get_song_top_scores_from_db(song_id, difficulty)
+                self.get_song_top_scores_from_db(song_id, difficulty, offset, limit)
                     .await?
             }
         } else {
-            self.get_song_top_scores_from_db(song_id, difficulty)
+            self.get_song_top_scores_from_db(song_id, difficulty, offset, limit)
                 .await?
         };
 
-        let result = scores
+        Ok(scores
             .into_iter()
             .enumerate()
-            .map(|(rank, row)| {
-                row.to_user_score_with_rank(Some((rank + 1) as i32))
+            .map(|(i, row)| {
+                row.to_user_score_with_rank(Some(offset + i as i32 + 1))
                     .to_dict(true)
             })
-            .collect::<Vec<HashMap<String, serde_json::Value>>>();
+            .collect())
+    }
 
-        if let Some(cache) = &self.cache {
-            cache
-                .set_json(&cache_key, &result, self.score_top_cache_ttl_seconds)
-                .await;
+    /// 1-based rank of a player on a chart, `None` without a score
+    async fn get_song_rank_position(
+        &self,
+        user_id: i32,
+        song_id: &str,
+        difficulty: i32,
+    ) -> ArcResult<Option<i32>> {
+        if self.warm_score_rank_zset(song_id, difficulty).await? {
+            let cache = self.cache.as_ref().expect("zset_ready requires cache");
+            let zset_key = Self::score_rank_zset_key(song_id, difficulty);
+            if let Some(rank) = cache.zrevrank(&zset_key, &user_id.to_string()).await {
+                return Ok(Some(rank as i32 + 1));
+            }
         }
 
-        Ok(result)
+        let Some(user_row) = sqlx::query!(
+            "SELECT score, time_played FROM best_score WHERE user_id = ? AND song_id = ? AND difficulty = ?",
+            user_id,
+            song_id,
+            difficulty
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        else {
+            return Ok(None);
+        };
+
+        let ahead = sqlx::query_scalar!(
+            "SELECT COUNT(*) as `count!: i64` FROM best_score bs
+             JOIN user u ON u.user_id = bs.user_id
+             WHERE bs.song_id = ? AND bs.difficulty = ? AND u.is_shadow_banned = 0
+             AND (bs.score > ? OR (bs.score = ? AND bs.time_played > ?))",
+            song_id,
+            difficulty,
+            user_row.score,
+            user_row.score,
+            user_row.time_played
+        )
+        .fetch_one(&self.read_pool)
+        .await?;
+        Ok(Some(ahead as i32 + 1))
     }
 
     async fn get_song_top_scores_from_db(
         &self,
         song_id: &str,
         difficulty: i32,
+        offset: i32,
+        limit: i32,
     ) -> ArcResult<Vec<RankingScoreRow>> {
         if CONFIG.character_full_unlock {
             sqlx::query_as!(
@@ -731,13 +1078,15 @@ impl ScoreService {
                  FROM best_score bs
                  JOIN user u ON bs.user_id = u.user_id
                  LEFT JOIN user_char_full uc ON uc.user_id = u.user_id AND uc.character_id = u.favorite_character
-                 WHERE bs.song_id = ? AND bs.difficulty = ?
+                 WHERE bs.song_id = ? AND bs.difficulty = ? AND u.is_shadow_banned = 0
                  ORDER BY bs.score DESC, bs.time_played DESC
-                 LIMIT 20"#,
+                 LIMIT ? OFFSET ?"#,
                 song_id,
-                difficulty
+                difficulty,
+                limit,
+                offset
             )
-            .fetch_all(&self.pool)
+            .fetch_all(&self.read_pool)
             .await
             .map_err(Into::into)
         } else {
@@ -753,13 +1102,15 @@ impl ScoreService {
                  FROM best_score bs
                  JOIN user u ON bs.user_id = u.user_id
                  LEFT JOIN user_char uc ON uc.user_id = u.user_id AND uc.character_id = u.favorite_character
-                 WHERE bs.song_id = ? AND bs.difficulty = ?
+                 WHERE bs.song_id = ? AND bs.difficulty = ? AND u.is_shadow_banned = 0
                  ORDER BY bs.score DESC, bs.time_played DESC
-                 LIMIT 20"#,
+                 LIMIT ? OFFSET ?"#,
                 song_id,
-                difficulty
+                difficulty,
+                limit,
+                offset
             )
-            .fetch_all(&self.pool)
+            .fetch_all(&self.read_pool)
             .await
             .map_err(Into::into)
         }
@@ -805,7 +1156,7 @@ impl ScoreService {
             query = query.bind(user_id);
         }
 
-        let rows = query.fetch_all(&self.pool).await?;
+        let rows = query.fetch_all(&self.read_pool).await?;
         Ok(rows)
     }
 
@@ -851,7 +1202,7 @@ impl ScoreService {
             query = query.bind(user_id);
         }
 
-        let rows = query.fetch_all(&self.pool).await?;
+        let rows = query.fetch_all(&self.read_pool).await?;
         Ok(rows)
     }
 
@@ -912,6 +1263,10 @@ impl ScoreService {
             if cache.get_string(&ready_key).await.is_none() {
                 return;
             }
+            let user_service = UserService::new(self.pool.clone());
+            if matches!(user_service.is_shadow_banned(user_id).await, Ok(true)) {
+                return;
+            }
 
             cache
                 .zadd_f64(
@@ -945,7 +1300,13 @@ impl ScoreService {
             }
 
             let member = user_id.to_string();
-            if world_rank_score > 0 {
+            let shadow_banned = matches!(
+                UserService::new(self.pool.clone())
+                    .is_shadow_banned(user_id)
+                    .await,
+                Ok(true)
+            );
+            if world_rank_score > 0 && !shadow_banned {
                 cache
                     .zadd_f64(
                         Self::global_rank_zset_key(),
@@ -1060,7 +1421,10 @@ impl ScoreService {
         }
 
         let rows = sqlx::query!(
-            "SELECT user_id, score, time_played FROM best_score WHERE song_id = ? AND difficulty = ?",
+            "SELECT bs.user_id, bs.score, bs.time_played
+             FROM best_score bs
+             JOIN user u ON u.user_id = bs.user_id
+             WHERE bs.song_id = ? AND bs.difficulty = ? AND u.is_shadow_banned = 0",
             song_id,
             difficulty
         )
@@ -1102,10 +1466,12 @@ impl ScoreService {
             return Ok(true);
         }
 
-        let rows =
-            sqlx::query!("SELECT user_id, world_rank_score FROM user WHERE world_rank_score > 0")
-                .fetch_all(&self.pool)
-                .await?;
+        let rows = sqlx::query!(
+            "SELECT user_id, world_rank_score FROM user
+                 WHERE world_rank_score > 0 AND is_shadow_banned = 0"
+        )
+        .fetch_all(&self.pool)
+        .await?;
 
         cache.del(Self::global_rank_zset_key()).await;
         for row in rows {
@@ -1258,26 +1624,34 @@ impl ScoreService {
             return Ok(Vec::new());
         };
 
+        // Shadow banned players are hidden from everyone but themselves.
         let rank_result = sqlx::query!(
-            "SELECT COUNT(*) as `rank_count!: i64` FROM best_score
-             WHERE song_id = ? AND difficulty = ? AND
-             (score > ? OR (score = ? AND time_played > ?))",
+            "SELECT COUNT(*) as `rank_count!: i64` FROM best_score bs
+             JOIN user u ON u.user_id = bs.user_id
+             WHERE bs.song_id = ? AND bs.difficulty = ?
+             AND (u.is_shadow_banned = 0 OR u.user_id = ?) AND
+             (bs.score > ? OR (bs.score = ? AND bs.time_played > ?))",
             song_id,
             difficulty,
+            user_id,
             user_row.score,
             user_row.score,
             user_row.time_played
         )
-        .fetch_one(&self.pool)
+        .fetch_one(&self.read_pool)
         .await?;
         let my_rank = (rank_result.rank_count + 1) as i32;
 
         let total_result = sqlx::query!(
-            "SELECT COUNT(*) as total FROM best_score WHERE song_id = ? AND difficulty = ?",
+            "SELECT COUNT(*) as total FROM best_score bs
+             JOIN user u ON u.user_id = bs.user_id
+             WHERE bs.song_id = ? AND bs.difficulty = ?
+             AND (u.is_shadow_banned = 0 OR u.user_id = ?)",
             song_id,
-            difficulty
+            difficulty,
+            user_id
         )
-        .fetch_one(&self.pool)
+        .fetch_one(&self.read_pool)
         .await?;
         let total_count = total_result.total as i32;
 
@@ -1307,14 +1681,16 @@ impl ScoreService {
                  JOIN user u ON bs.user_id = u.user_id
                  LEFT JOIN user_char_full uc ON uc.user_id = u.user_id AND uc.character_id = u.favorite_character
                  WHERE bs.song_id = ? AND bs.difficulty = ?
+                 AND (u.is_shadow_banned = 0 OR u.user_id = ?)
                  ORDER BY bs.score DESC, bs.time_played DESC
                  LIMIT ? OFFSET ?"#,
                 song_id,
                 difficulty,
+                user_id,
                 sql_limit,
                 sql_offset
             )
-            .fetch_all(&self.pool)
+            .fetch_all(&self.read_pool)
             .await?
         } else {
             sqlx::query_as!(
@@ -1330,14 +1706,16 @@ impl ScoreService {
                  JOIN user u ON bs.user_id = u.user_id
                  LEFT JOIN user_char uc ON uc.user_id = u.user_id AND uc.character_id = u.favorite_character
                  WHERE bs.song_id = ? AND bs.difficulty = ?
+                 AND (u.is_shadow_banned = 0 OR u.user_id = ?)
                  ORDER BY bs.score DESC, bs.time_played DESC
                  LIMIT ? OFFSET ?"#,
                 song_id,
                 difficulty,
+                user_id,
                 sql_limit,
                 sql_offset
             )
-            .fetch_all(&self.pool)
+            .fetch_all(&self.read_pool)
             .await?
         };
 
@@ -1532,7 +1910,7 @@ impl ScoreService {
                 user_id,
                 user_id
             )
-            .fetch_all(&self.pool)
+            .fetch_all(&self.read_pool)
             .await?
         } else {
             sqlx::query_as!(
@@ -1558,7 +1936,7 @@ impl ScoreService {
                 user_id,
                 user_id
             )
-            .fetch_all(&self.pool)
+            .fetch_all(&self.read_pool)
             .await?
         };
 
@@ -1677,27 +2055,30 @@ impl ScoreService {
     }
 
     async fn get_user_info(&self, user_id: i32) -> ArcResult<User> {
-        sqlx::query_as!(User, "SELECT * FROM user WHERE user_id = ?", user_id)
+        if let Some(user) = self.user_cache.user(user_id) {
+            return Ok(user);
+        }
+
+        let user = sqlx::query_as!(User, "SELECT * FROM user WHERE user_id = ?", user_id)
             .fetch_one(&self.pool)
             .await
-            .map_err(|e| ArcError::no_data(format!("User not found: {}", e), 108))
+            .map_err(|e| ArcError::no_data(format!("User not found: {}", e), 108))?;
+        self.user_cache.put_user(&user);
+        Ok(user)
     }
 
     async fn get_user_stamina_info(&self, user_id: i32) -> ArcResult<(i32, i64)> {
-        let row = sqlx::query!(
-            "SELECT max_stamina_ts, stamina FROM user WHERE user_id = ?",
-            user_id
-        )
-        .fetch_one(&self.pool)
-        .await?;
-
-        let stamina = StaminaImpl::new(row.stamina.unwrap_or(0), row.max_stamina_ts.unwrap_or(0));
+        let stamina = self.stamina_service.get_stamina(user_id).await?;
         Ok((stamina.get_current_stamina(), stamina.max_stamina_ts()))
     }
 
     async fn get_play_state(&self, token: &str, user_id: i32) -> ArcResult<Option<SongplayToken>> {
         let result = sqlx::query!(
-            "SELECT * FROM songplay_token WHERE token = ? AND user_id = ?",
+            "SELECT token, user_id, song_id, difficulty, course_id, course_state, course_score,
+                    course_clear_type, stamina_multiply, fragment_multiply, prog_boost_multiply,
+                    beyond_boost_gauge_usage, skill_cytusii_flag, skill_chinatsu_flag,
+                    invasion_flag, created_at
+             FROM songplay_token WHERE token = ? AND user_id = ?",
             token,
             user_id
         )
@@ -1724,29 +2105,46 @@ impl ScoreService {
             skill_cytusii_flag: result.skill_cytusii_flag,
             skill_chinatsu_flag: result.skill_chinatsu_flag,
             invasion_flag: result.invasion_flag.unwrap_or(0),
+            created_at: result.created_at,
         }))
     }
 
     async fn get_chart_constant_tenths(&self, song_id: &str, difficulty: i32) -> ArcResult<i32> {
-        let chart = sqlx::query!(
-            "SELECT rating_pst, rating_prs, rating_ftr, rating_byn, rating_etr FROM chart WHERE song_id = ?",
-            song_id
-        )
-        .fetch_optional(&self.pool)
-        .await?;
+        let ratings = match self.user_cache.chart(song_id) {
+            Some(ratings) => Some(ratings),
+            None => {
+                let chart = sqlx::query!(
+                    "SELECT rating_pst, rating_prs, rating_ftr, rating_byn, rating_etr FROM chart WHERE song_id = ?",
+                    song_id
+                )
+                .fetch_optional(&self.pool)
+                .await?;
 
-        if let Some(chart) = chart {
-            let rating = match difficulty {
-                0 => chart.rating_pst,
-                1 => chart.rating_prs,
-                2 => chart.rating_ftr,
-                3 => chart.rating_byn,
-                4 => chart.rating_etr,
-                _ => None,
-            };
+                // Missing rows are not cached so a chart added later is picked up at once.
+                chart.map(|chart| {
+                    let ratings = [
+                        chart.rating_pst,
+                        chart.rating_prs,
+                        chart.rating_ftr,
+                        chart.rating_byn,
+                        chart.rating_etr,
+                    ];
+                    self.user_cache.put_chart(song_id, ratings);
+                    ratings
+                })
+            }
+        };
+
+        if let Some(ratings) = ratings {
+            let rating = usize::try_from(difficulty)
+                .ok()
+                .and_then(|index| ratings.get(index).copied().flatten());
 
             Ok(rating.unwrap_or(-1))
         } else {
+            log::warn!(
+                "Score submitted for `{song_id}`, which has no chart row; using constant -1"
+            );
             Ok(-1)
         }
     }
@@ -1775,30 +2173,6 @@ impl ScoreService {
         None
     }
 
-    #[allow(dead_code)]
-    async fn get_world_map_stamina_cost(&self, user_id: i32) -> ArcResult<i32> {
-        // Get user's current map
-        let user = sqlx::query!("SELECT current_map FROM user WHERE user_id = ?", user_id)
-            .fetch_optional(&self.pool)
-            .await?;
-
-        if let Some(user_row) = user {
-            if let Some(current_map) = user_row.current_map {
-                // TODO: Load map data from JSON files and get stamina cost
-                // For now, return default stamina cost based on map
-                if current_map.contains("beyond") {
-                    Ok(2)
-                } else {
-                    Ok(1)
-                }
-            } else {
-                Ok(1) // Default stamina cost if no current map
-            }
-        } else {
-            Err(ArcError::no_data("User not found".to_string(), 108))
-        }
-    }
-
     async fn clear_user_songplay_tokens(&self, user_id: i32) -> ArcResult<()> {
         sqlx::query!("DELETE FROM songplay_token WHERE user_id = ?", user_id)
             .execute(&self.pool)
@@ -1816,10 +2190,15 @@ impl ScoreService {
 
         // Python baseline: insert token first, then deduct stamina / consume skip item.
         sqlx::query!(
-            "INSERT INTO songplay_token VALUES (?, ?, '', 0, ?, 0, 0, 3, 1, 100, 0, 0, '', '', 0)",
+            "INSERT INTO songplay_token (token, user_id, song_id, difficulty, course_id,
+             course_state, course_score, course_clear_type, stamina_multiply, fragment_multiply,
+             prog_boost_multiply, beyond_boost_gauge_usage, skill_cytusii_flag,
+             skill_chinatsu_flag, invasion_flag, created_at)
+             VALUES (?, ?, '', 0, ?, 0, 0, 3, 1, 100, 0, 0, '', '', 0, ?)",
             token,
             user_id,
-            course_id
+            course_id,
+            current_timestamp_ms()
         )
         .execute(&self.pool)
         .await?;
@@ -1832,38 +2211,9 @@ impl ScoreService {
                 .await?;
             self.invalidate_user_collection_cache(user_id).await;
         } else {
-            let stamina_row = sqlx::query!(
-                "SELECT max_stamina_ts, stamina FROM user WHERE user_id = ?",
-                user_id
-            )
-            .fetch_one(&self.pool)
-            .await?;
-
-            let mut stamina = StaminaImpl::new(
-                stamina_row.stamina.unwrap_or(0),
-                stamina_row.max_stamina_ts.unwrap_or(0),
-            );
-            let current_stamina = stamina.get_current_stamina();
-
-            if current_stamina < Constants::COURSE_STAMINA_COST {
-                return Err(ArcError::StaminaNotEnough {
-                    message: "Stamina is not enough.".to_string(),
-                    error_code: 107,
-                    api_error_code: -999,
-                    extra_data: None,
-                    status: 200,
-                });
-            }
-
-            stamina.set_stamina(current_stamina - Constants::COURSE_STAMINA_COST);
-            sqlx::query!(
-                "UPDATE user SET stamina = ?, max_stamina_ts = ? WHERE user_id = ?",
-                stamina.get_current_stamina(),
-                stamina.max_stamina_ts(),
-                user_id
-            )
-            .execute(&self.pool)
-            .await?;
+            self.stamina_service
+                .consume_stamina(user_id, game_constants().course_stamina_cost)
+                .await?;
         }
 
         Ok(token)
@@ -1872,8 +2222,9 @@ impl ScoreService {
     async fn update_course_token(&self, previous_token: &str, user_id: i32) -> ArcResult<String> {
         let new_token = generate_course_token();
         sqlx::query!(
-            "UPDATE songplay_token SET token = ? WHERE token = ? AND user_id = ?",
+            "UPDATE songplay_token SET token = ?, created_at = ? WHERE token = ? AND user_id = ?",
             new_token,
+            current_timestamp_ms(),
             previous_token,
             user_id
         )
@@ -1886,6 +2237,7 @@ impl ScoreService {
         let user_id = user_play.user_score.user_id;
 
         // Get play state first (Python baseline: token may be missing; only used to detect world/course mode).
+        let mut token_created_at = None;
         if user_play.song_token == "1145141919810" {
             // Hardcoded bypass token
             user_play.is_world_mode = Some(false);
@@ -1895,6 +2247,7 @@ impl ScoreService {
             user_play.course_clear_type = 3;
         } else if let Some(play_state) = self.get_play_state(&user_play.song_token, user_id).await?
         {
+            token_created_at = Some(play_state.created_at);
             let course_id = play_state.course_id.clone().unwrap_or_default();
             if course_id.is_empty() {
                 // World mode: course_id is an empty string in Python
@@ -1905,6 +2258,13 @@ impl ScoreService {
                 user_play.course_clear_type = 3;
             } else {
                 // Course mode
+                self.validate_course_chart(
+                    &course_id,
+                    play_state.course_state,
+                    &user_play.user_score.score.song_id,
+                    user_play.user_score.score.difficulty,
+                )
+                .await?;
                 user_play.is_world_mode = Some(false);
                 user_play.course_id = Some(course_id);
                 user_play.course_play_state = play_state.course_state;
@@ -1958,15 +2318,40 @@ impl ScoreService {
             user_play.unrank_flag = false;
         }
 
-        // Set timestamp (Python baseline: best_score / recent30 uses seconds)
-        user_play.user_score.score.time_played = current_timestamp_seconds();
-
-        // Record score to log database
-        self.record_score(user_play).await?;
+        // Check the reported play time, then store the server time
+        // (Python baseline: best_score / recent30 uses seconds)
+        let played_at = user_play.user_score.score.time_played;
+        user_play.user_score.score.time_played = current_timestamp() / 1000;
 
-        // Update user recent score (like Python version)
-        sqlx::query!(
-            "UPDATE user SET song_id = ?, difficulty = ?, score = ?, shiny_perfect_count = ?,
+        let flags = self
+            .score_validator
+            .check(
+                user_id,
+                &user_play.user_score.score,
+                played_at,
+                token_created_at,
+            )
+            .await?;
+        if !flags.is_empty() {
+            self.score_validator
+                .record(user_id, &user_play.user_score.score, &flags)
+                .await?;
+            if flags.iter().any(|flag| flag.rejects()) {
+                return Err(ArcError::invalid_score());
+            }
+        }
+
+        // Record score to log database
+        self.write_queue
+            .enqueue(ScoreWrite::ScoreLog {
+                user_id,
+                score: user_play.user_score.score.clone(),
+            })
+            .await?;
+
+        // Update user recent score (like Python version)
+        sqlx::query!(
+            "UPDATE user SET song_id = ?, difficulty = ?, score = ?, shiny_perfect_count = ?,
              perfect_count = ?, near_count = ?, miss_count = ?, health = ?, modifier = ?,
              clear_type = ?, rating = ?, time_played = ?,
              ticket = COALESCE(ticket, 0) + ? WHERE user_id = ?",
@@ -2337,7 +2722,7 @@ impl ScoreService {
 
     async fn update_user_rating(&self, user_id: i32) -> ArcResult<()> {
         let potential = self.calculate_user_potential(user_id).await?;
-        let user_rating_ptt = potential.calculate_value(BEST30_WEIGHT, RECENT10_WEIGHT);
+        let user_rating_ptt = potential.value();
         let rating_ptt = (user_rating_ptt * 100.0) as i32;
 
         sqlx::query!(
@@ -2358,7 +2743,13 @@ impl ScoreService {
                 .await;
         }
 
-        self.record_rating_ptt(user_id, user_rating_ptt).await?;
+        self.write_queue
+            .enqueue(ScoreWrite::RatingHistory {
+                user_id,
+                day: today_timestamp_seconds(),
+                rating_ptt: user_rating_ptt,
+            })
+            .await?;
 
         Ok(())
     }
@@ -2371,40 +2762,25 @@ impl ScoreService {
             }
         }
 
-        // Calculate best 30
-        let best_30 = sqlx::query!(
-            "SELECT rating FROM best_score WHERE user_id = ? ORDER BY rating DESC LIMIT 30",
-            user_id
-        )
-        .fetch_all(&self.pool)
-        .await?;
-
-        let best_30_sum: f64 = best_30.iter().map(|r| r.rating.unwrap_or(0.0)).sum();
-
-        // Calculate recent 10 (simplified)
-        let recent_scores = sqlx::query!(
-            "SELECT song_id, difficulty, rating FROM recent30 WHERE user_id = ? AND song_id != '' ORDER BY time_played DESC",
+        // best30 and recent10 (best rating per chart among recent plays) in one round trip
+        let sums = sqlx::query!(
+            "SELECT
+                 (SELECT COALESCE(SUM(rating), 0) FROM (
+                     SELECT COALESCE(rating, 0) AS rating FROM best_score
+                     WHERE user_id = ? ORDER BY rating DESC LIMIT 30
+                 ) b30) AS `best_30_sum!: f64`,
+                 (SELECT COALESCE(SUM(rating), 0) FROM (
+                     SELECT MAX(COALESCE(rating, 0)) AS rating FROM recent30
+                     WHERE user_id = ? AND song_id != ''
+                     GROUP BY song_id, difficulty ORDER BY rating DESC LIMIT 10
+                 ) r10) AS `recent_10_sum!: f64`",
+            user_id,
             user_id
         )
-        .fetch_all(&self.pool)
+        .fetch_one(&self.pool)
         .await?;
-
-        let mut max_ratings: HashMap<(String, i32), f64> = HashMap::new();
-        for score in recent_scores {
-            if let (Some(song_id), Some(difficulty), Some(rating)) =
-                (score.song_id, score.difficulty, score.rating)
-            {
-                let key = (song_id, difficulty);
-                let current_max = max_ratings.get(&key).copied().unwrap_or(0.0);
-                if rating > current_max {
-                    max_ratings.insert(key, rating);
-                }
-            }
-        }
-
-        let mut recent_ratings: Vec<f64> = max_ratings.values().copied().collect();
-        recent_ratings.sort_by(|a, b| b.partial_cmp(a).unwrap());
-        let recent_10_sum: f64 = recent_ratings.iter().take(10).sum();
+        let best_30_sum = sums.best_30_sum;
+        let recent_10_sum = sums.recent_10_sum;
 
         let potential = Potential {
             user_id,
@@ -2428,6 +2804,305 @@ impl ScoreService {
         Ok(potential)
     }
 
+    /// Recompute `user.rating_ptt` from best30 and recent10 for one user, or
+    /// for every user when `user_id` is `None`, in a single statement
+    ///
+    /// Used by bulk rating refreshes; a score submit goes through
+    /// [`Self::calculate_user_potential`] instead.
+    pub async fn recalculate_potentials(&self, user_id: Option<i32>) -> ArcResult<u64> {
+        let constants = game_constants();
+        let result = sqlx::query!(
+            "UPDATE user u
+             LEFT JOIN (
+                 SELECT user_id, SUM(COALESCE(rating, 0)) AS best_30_sum
+                 FROM (
+                     SELECT user_id, rating,
+                            ROW_NUMBER() OVER (
+                                PARTITION BY user_id
+                                ORDER BY COALESCE(rating, 0) DESC
+                            ) AS rn
+                     FROM best_score
+                 ) ranked_best
+                 WHERE rn <= 30
+                 GROUP BY user_id
+             ) b30 ON b30.user_id = u.user_id
+             LEFT JOIN (
+                 SELECT user_id, SUM(rating) AS recent_10_sum
+                 FROM (
+                     SELECT user_id, rating,
+                            ROW_NUMBER() OVER (
+                                PARTITION BY user_id
+                                ORDER BY rating DESC
+                            ) AS rn
+                     FROM (
+                         SELECT user_id, song_id, difficulty, MAX(COALESCE(rating, 0)) AS rating
+                         FROM recent30
+                         WHERE song_id != ''
+                         GROUP BY user_id, song_id, difficulty
+                     ) recent_max
+                 ) ranked_recent
+                 WHERE rn <= 10
+                 GROUP BY user_id
+             ) r10 ON r10.user_id = u.user_id
+             SET u.rating_ptt = FLOOR(
+                 (
+                     COALESCE(b30.best_30_sum, 0) * ?
+                     + COALESCE(r10.recent_10_sum, 0) * ?
+                 ) * 100
+             )
+             WHERE ? IS NULL OR u.user_id = ?",
+            constants.best30_weight,
+            constants.recent10_weight,
+            user_id,
+            user_id
+        )
+        .execute(&self.pool)
+        .await?;
+
+        match user_id {
+            Some(user_id) => self.user_cache.invalidate_user(user_id),
+            None => self.user_cache.invalidate_all_users(),
+        }
+        Ok(result.rows_affected())
+    }
+
+    /// Top `limit` players of the global potential leaderboard
+    ///
+    /// Served from a process-wide snapshot that the background refresh keeps
+    /// current; a missing or stale snapshot is rebuilt on the spot.
+    pub async fn get_potential_ranking(&self, limit: Option<i32>) -> ArcResult<PotentialRanking> {
+        let max_age_ms = i64::try_from(
+            CONFIG
+                .potential_ranking_refresh_interval
+                .saturating_mul(1000),
+        )
+        .unwrap_or(i64::MAX);
+        let snapshot = potential_ranking_lock()
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone()
+            .filter(|ranking| current_timestamp_ms() - ranking.refreshed_at < max_age_ms);
+        let mut ranking = match snapshot {
+            Some(ranking) => ranking,
+            None => self.refresh_potential_ranking().await?,
+        };
+
+        if let Some(limit) = limit {
+            ranking.ranks.truncate(limit.max(0) as usize);
+        }
+        Ok(ranking)
+    }
+
+    /// Rebuild the global potential leaderboard snapshot
+    ///
+    /// Shadow banned players and players hiding their rating are left out.
+    pub async fn refresh_potential_ranking(&self) -> ArcResult<PotentialRanking> {
+        let size = CONFIG.potential_ranking_size.max(0);
+        let rows = sqlx::query!(
+            "WITH top_users AS (
+                 SELECT user_id, name, character_id, is_char_uncapped, is_char_uncapped_override,
+                        rating_ptt
+                 FROM user
+                 WHERE is_shadow_banned = 0 AND COALESCE(is_hide_rating, 0) = 0
+                       AND rating_ptt IS NOT NULL
+                 ORDER BY rating_ptt DESC, user_id ASC
+                 LIMIT ?
+             ),
+             ranked_best AS (
+                 SELECT bs.user_id, COALESCE(bs.rating, 0) AS rating,
+                        ROW_NUMBER() OVER (
+                            PARTITION BY bs.user_id ORDER BY COALESCE(bs.rating, 0) DESC
+                        ) AS rn
+                 FROM best_score bs
+                 JOIN top_users t ON t.user_id = bs.user_id
+             ),
+             recent_max AS (
+                 SELECT r.user_id, MAX(COALESCE(r.rating, 0)) AS rating
+                 FROM recent30 r
+                 JOIN top_users t ON t.user_id = r.user_id
+                 WHERE r.song_id != ''
+                 GROUP BY r.user_id, r.song_id, r.difficulty
+             ),
+             ranked_recent AS (
+                 SELECT user_id, rating,
+                        ROW_NUMBER() OVER (PARTITION BY user_id ORDER BY rating DESC) AS rn
+                 FROM recent_max
+             )
+             SELECT t.user_id, t.name, t.character_id, t.is_char_uncapped,
+                    t.is_char_uncapped_override, t.rating_ptt,
+                    (SELECT COALESCE(SUM(b.rating), 0) FROM ranked_best b
+                     WHERE b.user_id = t.user_id AND b.rn <= 30) AS `best_30_sum!: f64`,
+                    (SELECT COALESCE(SUM(r.rating), 0) FROM ranked_recent r
+                     WHERE r.user_id = t.user_id AND r.rn <= 10) AS `recent_10_sum!: f64`
+             FROM top_users t
+             ORDER BY t.rating_ptt DESC, t.user_id ASC",
+            size
+        )
+        .fetch_all(&self.read_pool)
+        .await?;
+
+        let ranks = rows
+            .into_iter()
+            .enumerate()
+            .map(|(i, row)| PotentialRankEntry {
+                rank: i as i32 + 1,
+                user_id: row.user_id,
+                name: row.name.unwrap_or_default(),
+                character: row.character_id.unwrap_or(0),
+                is_char_uncapped: row.is_char_uncapped.unwrap_or(0) != 0,
+                is_char_uncapped_override: row.is_char_uncapped_override.unwrap_or(0) != 0,
+                rating: row.rating_ptt.unwrap_or(0),
+                best30: row.best_30_sum / 30.0,
+                recent10: row.recent_10_sum / 10.0,
+            })
+            .collect();
+        let ranking = PotentialRanking {
+            refreshed_at: current_timestamp_ms(),
+            ranks,
+        };
+
+        *potential_ranking_lock()
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(ranking.clone());
+        Ok(ranking)
+    }
+
+    /// Recompute the world rank score of one user, or of every user when
+    /// `user_id` is `None`, from the `score_v2` of their best scores
+    ///
+    /// The score is the sum of `score_v2` over FTR, BYD and ETR charts with a
+    /// constant; score submissions only add the difference of the improved
+    /// chart, so this pass also picks up constant changes and rating
+    /// refreshes.
+    pub async fn recalculate_world_rank_scores(&self, user_id: Option<i32>) -> ArcResult<u64> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query!(
+            "INSERT INTO user_kvdata (user_id, class, `key`, idx, value)
+             SELECT u.user_id, 'score', 'world_rank_score_raw', 0,
+                    CAST(GREATEST(COALESCE(t.total, 0), 0) AS CHAR)
+             FROM user u
+             LEFT JOIN (
+                 SELECT b.user_id, SUM(b.score_v2) AS total
+                 FROM best_score b
+                 JOIN chart c ON c.song_id = b.song_id
+                 WHERE (b.difficulty = 2 AND c.rating_ftr > 0)
+                    OR (b.difficulty = 3 AND c.rating_byn > 0)
+                    OR (b.difficulty = 4 AND c.rating_etr > 0)
+                 GROUP BY b.user_id
+             ) t ON t.user_id = u.user_id
+             WHERE ? IS NULL OR u.user_id = ?
+             ON DUPLICATE KEY UPDATE value = VALUES(value)",
+            user_id,
+            user_id
+        )
+        .execute(&mut *tx)
+        .await?;
+        let result = sqlx::query!(
+            "UPDATE user u
+             JOIN user_kvdata k ON k.user_id = u.user_id AND k.class = 'score'
+                  AND k.`key` = 'world_rank_score_raw' AND k.idx = 0
+             SET u.world_rank_score = FLOOR(k.value + 0)
+             WHERE ? IS NULL OR u.user_id = ?",
+            user_id,
+            user_id
+        )
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        if let Some(cache) = &self.cache {
+            cache.del(Self::global_rank_zset_ready_key()).await;
+        }
+        match user_id {
+            Some(user_id) => self.user_cache.invalidate_user(user_id),
+            None => self.user_cache.invalidate_all_users(),
+        }
+        Ok(result.rows_affected())
+    }
+
+    /// A player's world rank with the top of the world rank leaderboard
+    ///
+    /// The list comes from a process-wide snapshot that the background
+    /// refresh keeps current, like [`Self::get_potential_ranking`].
+    pub async fn get_user_world_rank(
+        &self,
+        user_id: i32,
+        limit: Option<i32>,
+    ) -> ArcResult<UserWorldRank> {
+        let max_age_ms = i64::try_from(CONFIG.world_rank_refresh_interval.saturating_mul(1000))
+            .unwrap_or(i64::MAX);
+        let snapshot = world_ranking_lock()
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone()
+            .filter(|ranking| current_timestamp_ms() - ranking.refreshed_at < max_age_ms);
+        let mut ranking = match snapshot {
+            Some(ranking) => ranking,
+            None => self.refresh_world_ranking().await?,
+        };
+        if let Some(limit) = limit {
+            ranking.ranks.truncate(limit.max(0) as usize);
+        }
+
+        let world_rank_score = sqlx::query_scalar!(
+            "SELECT world_rank_score FROM user WHERE user_id = ?",
+            user_id
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .flatten()
+        .unwrap_or(0);
+        Ok(UserWorldRank {
+            rank: self.get_user_global_rank(user_id).await?,
+            world_rank_score,
+            ranking,
+        })
+    }
+
+    /// Rebuild the world rank leaderboard snapshot of the top
+    /// `world_rank_max` players
+    ///
+    /// Shadow banned players and players without a world rank score are left
+    /// out.
+    pub async fn refresh_world_ranking(&self) -> ArcResult<WorldRanking> {
+        let size = CONFIG.world_rank_max.max(0);
+        let rows = sqlx::query!(
+            "SELECT user_id, name, character_id, is_char_uncapped, is_char_uncapped_override,
+                    world_rank_score AS `world_rank_score!: i32`
+             FROM user
+             WHERE is_shadow_banned = 0 AND world_rank_score > 0
+             ORDER BY world_rank_score DESC, user_id ASC
+             LIMIT ?",
+            size
+        )
+        .fetch_all(&self.read_pool)
+        .await?;
+
+        let scores: Vec<i32> = rows.iter().map(|row| row.world_rank_score).collect();
+        let ranks = rows
+            .into_iter()
+            .zip(competition_ranks(&scores))
+            .map(|(row, rank)| WorldRankEntry {
+                rank,
+                user_id: row.user_id,
+                name: row.name.unwrap_or_default(),
+                character: row.character_id.unwrap_or(0),
+                is_char_uncapped: row.is_char_uncapped.unwrap_or(0) != 0,
+                is_char_uncapped_override: row.is_char_uncapped_override.unwrap_or(0) != 0,
+                world_rank_score: row.world_rank_score,
+            })
+            .collect();
+        let ranking = WorldRanking {
+            refreshed_at: current_timestamp_ms(),
+            ranks,
+        };
+
+        *world_ranking_lock()
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(ranking.clone());
+        Ok(ranking)
+    }
+
     async fn get_user_rating_ptt(&self, user_id: i32) -> ArcResult<i32> {
         let cache_key = Self::user_rating_cache_key(user_id);
         if let Some(cache) = &self.cache {
@@ -2492,7 +3167,8 @@ impl ScoreService {
         }
 
         let rank_result = sqlx::query!(
-            "SELECT COUNT(*) as count FROM user WHERE world_rank_score > ?",
+            "SELECT COUNT(*) as count FROM user
+             WHERE world_rank_score > ? AND is_shadow_banned = 0",
             world_rank_score
         )
         .fetch_one(&self.pool)
@@ -2514,76 +3190,6 @@ impl ScoreService {
         Ok(rank)
     }
 
-    /// Record score to log database
-    async fn record_score(&self, user_play: &UserPlay) -> ArcResult<()> {
-        let user_id = user_play.user_score.user_id;
-        let score = &user_play.user_score.score;
-        sqlx::query!(
-            "INSERT INTO user_score (
-                user_id, song_id, difficulty, time_played, score,
-                shiny_perfect_count, perfect_count, near_count, miss_count,
-                health, modifier, clear_type, rating
-            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
-            ON DUPLICATE KEY UPDATE
-                score = VALUES(score),
-                shiny_perfect_count = VALUES(shiny_perfect_count),
-                perfect_count = VALUES(perfect_count),
-                near_count = VALUES(near_count),
-                miss_count = VALUES(miss_count),
-                health = VALUES(health),
-                modifier = VALUES(modifier),
-                clear_type = VALUES(clear_type),
-                rating = VALUES(rating)",
-            user_id,
-            score.song_id,
-            score.difficulty,
-            score.time_played,
-            score.score,
-            score.shiny_perfect_count,
-            score.perfect_count,
-            score.near_count,
-            score.miss_count,
-            score.health,
-            score.modifier,
-            score.clear_type,
-            score.rating
-        )
-        .execute(&self.pool)
-        .await?;
-        Ok(())
-    }
-
-    /// Record user rating PTT changes to log database
-    async fn record_rating_ptt(&self, user_id: i32, user_rating_ptt: f64) -> ArcResult<()> {
-        let today_timestamp = today_timestamp_seconds();
-        let old_ptt = sqlx::query!(
-            "SELECT rating_ptt FROM user_rating WHERE user_id = ? AND time = ?",
-            user_id,
-            today_timestamp
-        )
-        .fetch_optional(&self.pool)
-        .await?;
-
-        let old_ptt = old_ptt.and_then(|row| row.rating_ptt);
-        let should_record = match old_ptt {
-            Some(ptt) => (ptt - user_rating_ptt).abs() > f64::EPSILON,
-            None => true,
-        };
-        if should_record {
-            sqlx::query!(
-                "INSERT INTO user_rating (user_id, time, rating_ptt)
-                 VALUES (?, ?, ?)
-                 ON DUPLICATE KEY UPDATE rating_ptt = VALUES(rating_ptt)",
-                user_id,
-                today_timestamp,
-                user_rating_ptt
-            )
-            .execute(&self.pool)
-            .await?;
-        }
-        Ok(())
-    }
-
     /// Handle world mode calculations and build Python-compatible payload.
     async fn handle_world_mode(&self, user_play: &mut UserPlay) -> ArcResult<JsonMap> {
         let user_id = user_play.user_score.user_id;
@@ -2634,13 +3240,7 @@ impl ScoreService {
         };
 
         if is_locked {
-            return Err(ArcError::MapLocked {
-                message: "The map is locked.".to_string(),
-                error_code: 108,
-                api_error_code: -100,
-                extra_data: None,
-                status: 200,
-            });
+            return Err(ArcError::map_locked("The map is locked."));
         }
 
         let prev_position = curr_position;
@@ -2686,24 +3286,58 @@ impl ScoreService {
         let prog_boost_multiply = user_play.prog_boost_multiply as f64;
         let beyond_boost_usage = user_play.beyond_boost_gauge_usage as f64;
 
-        let frag_value = character.frag_value();
-        let mut prog_value = character.prog_value();
-        let overdrive_value = character.overdrive_value();
+        let skill_id = skill_id_displayed.clone().unwrap_or_default();
+        let stat_bonus = if skill_id_displayed.is_some() {
+            let character_level_sum = if SkillEngine::uses_character_level_sum(&character)
+                && !map.is_beyond
+                && !CONFIG.character_full_unlock
+            {
+                Some(
+                    character_service
+                        .get_user_character_level_sum(user_id)
+                        .await?,
+                )
+            } else {
+                None
+            };
+            let score = &user_play.user_score.score;
+            let play = SkillPlay {
+                song_id: score.song_id.clone(),
+                health: score.health,
+                clear_type: score.clear_type,
+                song_grade: score.song_grade(),
+                is_beyond: map.is_beyond,
+                highest_health: user_play.highest_health,
+                lowest_health: user_play.lowest_health,
+                combo_interval_bonus: user_play.combo_interval_bonus,
+                skill_cytusii_flag: user_play.skill_cytusii_flag.clone(),
+                skill_chinatsu_flag: user_play.skill_chinatsu_flag.clone(),
+                character_level_sum,
+            };
+            SkillEngine::stat_bonus(&skill_id, &character, &play)
+        } else {
+            SkillStatBonus::default()
+        };
+
+        let constants = game_constants();
+        let frag_value = character.frag_value() + stat_bonus.frag;
+        let mut prog_value = character.prog_value() + stat_bonus.prog;
+        let overdrive_value = character.overdrive_value() + stat_bonus.overdrive;
 
         let (
             base_progress,
             progress_normalized,
             final_progress,
             mut partner_multiply,
-            _step_times,
+            step_times,
             affinity_multiply,
             new_law_multiply,
         ) = if map.is_beyond {
-            let base_progress = rating.sqrt() * 0.43
+            let base_progress = rating.sqrt() * constants.beyond_rating_progress_multiplier
                 + if user_play.user_score.score.clear_type == 0 {
-                    25.0 / 28.0
+                    constants.beyond_fail_progress
                 } else {
-                    75.0 / 28.0
+                    constants.beyond_clear_progress
                 };
             let step_times = stamina_multiply * fragment_multiply / 100.0
                 * (1.0 + prog_boost_multiply / 100.0 + beyond_boost_usage / 100.0);
@@ -2760,7 +3394,8 @@ impl ScoreService {
                 new_law_multiply,
             )
         } else {
-            let base_progress = 2.5 + 2.45 * rating.sqrt();
+            let base_progress = constants.world_base_progress
+                + constants.world_rating_progress_multiplier * rating.sqrt();
             let partner_multiply = prog_value / 50.0;
             let progress_normalized = base_progress * partner_multiply;
             let step_times =
@@ -2777,14 +3412,56 @@ impl ScoreService {
             )
         };
 
-        let (next_position, next_capture) = climb_user_map(
+        let progress_before_skill =
+            final_progress * limited_events().progress_multiplier(current_timestamp_ms());
+
+        let start_step = map.steps.get(prev_position.max(0) as usize);
+        let mut climb = SkillClimb {
+            start_step_types: start_step.map_or(&[][..], |step| step.step_type.as_slice()),
+            start_step_restricted: start_step.is_some_and(|step| {
+                step.restrict_type.is_some()
+                    || step.restrict_id.is_some()
+                    || step.restrict_ids.is_some()
+            }),
+            rewards_fragment: false,
+            song_grade: user_play.user_score.score.song_grade(),
+            skill_flag: character.skill_flag,
+            lucky: rand::thread_rng().gen_bool(0.5),
+        };
+        let mut final_progress =
+            SkillEngine::progress(&skill_id, progress_before_skill, step_times, &climb);
+        let (mut next_position, mut next_capture) = climb_user_map(
             &map.steps,
             map.is_beyond,
-            map.beyond_health.unwrap_or(0) as f64,
+            map.beyond_health_value(),
             prev_position,
             prev_capture,
             final_progress,
         );
+
+        // Skills rewarding fragments on the way climb again with the bonus
+        climb.rewards_fragment = steps_for_climbing(&map.steps, prev_position + 1, next_position)
+            .iter()
+            .any(|step| {
+                step.items
+                    .iter()
+                    .any(|item| item.item_type == ItemTypes::FRAGMENT)
+            });
+        if climb.rewards_fragment {
+            let progress =
+                SkillEngine::progress(&skill_id, progress_before_skill, step_times, &climb);
+            if progress != final_progress {
+                final_progress = progress;
+                (next_position, next_capture) = climb_user_map(
+                    &map.steps,
+                    map.is_beyond,
+                    map.beyond_health_value(),
+                    prev_position,
+                    prev_capture,
+                    final_progress,
+                );
+            }
+        }
         curr_position = next_position;
         curr_capture = next_capture;
 
@@ -2814,63 +3491,48 @@ impl ScoreService {
         if let Some(last_step) = steps_for_climbing_pre_reset.last() {
             if last_step.step_type.iter().any(|x| x == "plusstamina") {
                 if let Some(plus_stamina) = last_step.plus_stamina_value {
-                    let stamina_row = sqlx::query!(
-                        "SELECT max_stamina_ts, stamina FROM user WHERE user_id = ?",
-                        user_id
-                    )
-                    .fetch_one(&self.pool)
-                    .await?;
-                    let mut stamina = StaminaImpl::new(
-                        stamina_row.stamina.unwrap_or(0),
-                        stamina_row.max_stamina_ts.unwrap_or(0),
-                    );
-                    let current_stamina = stamina.get_current_stamina();
-                    stamina.set_stamina(current_stamina + plus_stamina);
-                    sqlx::query!(
-                        "UPDATE user SET stamina = ?, max_stamina_ts = ? WHERE user_id = ?",
-                        stamina.get_current_stamina(),
-                        stamina.max_stamina_ts(),
-                        user_id
-                    )
-                    .execute(&self.pool)
-                    .await?;
+                    self.stamina_service
+                        .add_stamina(user_id, plus_stamina)
+                        .await?;
                 }
             }
         }
 
         if !CONFIG.character_full_unlock && !is_skill_sealed {
-            let exp_addition =
-                stamina_multiply * (prog_boost_multiply / 100.0 + 1.0) * rating * 6.0;
+            let exp_addition = stamina_multiply
+                * (prog_boost_multiply / 100.0 + 1.0)
+                * rating
+                * constants.character_exp_multiplier;
             if exp_addition != 0.0 {
                 character = character_service
                     .upgrade_character(user_id, character.character_id, exp_addition)
                     .await?;
-                prog_value = character.prog_value();
+                prog_value = character.prog_value() + stat_bonus.prog;
                 if !map.is_beyond {
                     partner_multiply = prog_value / 50.0;
                 }
             }
         }
 
-        if !is_skill_sealed {
-            if let Some(skill_id) = skill_id_displayed.as_deref() {
-                if skill_id == "skill_fatalis" {
-                    world_mode_locked_end_ts =
-                        current_timestamp() + Constants::SKILL_FATALIS_WORLD_LOCKED_TIME;
-                    sqlx::query!(
-                        "UPDATE user SET world_mode_locked_end_ts = ? WHERE user_id = ?",
-                        world_mode_locked_end_ts,
-                        user_id
-                    )
-                    .execute(&self.pool)
+        match SkillEngine::after_climb(&skill_id) {
+            Some(SkillAfterClimb::LockWorldMode) => {
+                world_mode_locked_end_ts =
+                    current_timestamp() + game_constants().fatalis_world_locked_time;
+                sqlx::query!(
+                    "UPDATE user SET world_mode_locked_end_ts = ? WHERE user_id = ?",
+                    world_mode_locked_end_ts,
+                    user_id
+                )
+                .execute(&self.pool)
+                .await?;
+            }
+            Some(SkillAfterClimb::ToggleSkillState) => {
+                character_service
+                    .change_character_skill_state(user_id, character.character_id)
                     .await?;
-                } else if skill_id == "skill_maya" {
-                    character_service
-                        .change_character_skill_state(user_id, character.character_id)
-                        .await?;
-                    character.skill_flag = !character.skill_flag;
-                }
+                character.skill_flag = !character.skill_flag;
             }
+            None => {}
         }
 
         if map.is_beyond {
@@ -2955,6 +3617,7 @@ impl ScoreService {
         }
 
         let mut result = HashMap::new();
+        result.insert("current_map".to_string(), json!(current_map));
         result.insert("rewards".to_string(), Value::Array(rewards));
         result.insert("exp".to_string(), json!(character.level.exp));
         result.insert("level".to_string(), json!(character.level.level));
@@ -2975,6 +3638,12 @@ impl ScoreService {
         );
         result.insert("progress_sub_boost_amount".to_string(), json!(0));
         result.insert("partner_multiply".to_string(), json!(partner_multiply));
+        if final_progress != progress_before_skill && step_times > 0.0 {
+            result.insert(
+                "character_bonus_progress".to_string(),
+                json!((final_progress - progress_before_skill) / step_times),
+            );
+        }
 
         if user_play.stamina_multiply != 1 {
             result.insert(
@@ -3044,9 +3713,6 @@ impl ScoreService {
             return Ok(HashMap::new());
         };
 
-        let mut course_score = user_play.course_score + user_play.user_score.score.score;
-        let mut course_clear_type = user_play.course_clear_type;
-
         let user_course = sqlx::query!(
             "SELECT high_score, best_clear_type FROM user_course WHERE user_id = ? AND course_id = ?",
             user_id,
@@ -3065,62 +3731,36 @@ impl ScoreService {
         };
 
         let mut need_upsert = false;
-        if course_score > high_score {
-            high_score = course_score;
+        let accumulated_score = user_play.course_score + user_play.user_score.score.score;
+        if accumulated_score > high_score {
+            high_score = accumulated_score;
             need_upsert = true;
         }
 
-        if user_play.user_score.score.health < 0 {
-            user_play.course_play_state = 5;
-            course_score = 0;
-            course_clear_type = 0;
-
-            sqlx::query!(
-                "UPDATE songplay_token SET course_state = ?, course_score = ?, course_clear_type = ? WHERE token = ?",
-                user_play.course_play_state,
-                course_score,
-                course_clear_type,
-                &user_play.song_token
-            )
-            .execute(&self.pool)
-            .await?;
-
-            if need_upsert {
-                sqlx::query!(
-                    "INSERT INTO user_course (user_id, course_id, high_score, best_clear_type)
-                     VALUES (?, ?, ?, ?)
-                     ON DUPLICATE KEY UPDATE high_score = VALUES(high_score), best_clear_type = VALUES(best_clear_type)",
-                    user_id,
-                    &course_id,
-                    high_score,
-                    best_clear_type
-                )
-                .execute(&self.pool)
-                .await?;
-            }
-
-            return Ok(HashMap::new());
-        }
-
-        user_play.course_play_state += 1;
-        if Score::get_song_state(course_clear_type)
-            > Score::get_song_state(user_play.user_score.score.clear_type)
-        {
-            course_clear_type = user_play.user_score.score.clear_type;
-        }
+        let progress = advance_course(
+            CourseProgress {
+                state: user_play.course_play_state,
+                score: user_play.course_score,
+                clear_type: user_play.course_clear_type,
+            },
+            user_play.user_score.score.score,
+            user_play.user_score.score.clear_type,
+            user_play.user_score.score.health,
+        );
+        user_play.course_play_state = progress.state;
 
         sqlx::query!(
             "UPDATE songplay_token SET course_state = ?, course_score = ?, course_clear_type = ? WHERE token = ?",
-            user_play.course_play_state,
-            course_score,
-            course_clear_type,
+            progress.state,
+            progress.score,
+            progress.clear_type,
             &user_play.song_token
         )
         .execute(&self.pool)
         .await?;
 
         let mut rewards = Vec::new();
-        if user_play.course_play_state == 4 {
+        if progress.state == COURSE_CLEARED_STATE {
             if best_clear_type == 0 {
                 let course_items =
                     sqlx::query!("SELECT * FROM course_item WHERE course_id = ?", &course_id)
@@ -3144,8 +3784,8 @@ impl ScoreService {
                 self.invalidate_user_collection_cache(user_id).await;
             }
 
-            if Score::get_song_state(course_clear_type) > Score::get_song_state(best_clear_type) {
-                best_clear_type = course_clear_type;
+            if Score::get_song_state(progress.clear_type) > Score::get_song_state(best_clear_type) {
+                best_clear_type = progress.clear_type;
                 need_upsert = true;
             }
         }
@@ -3164,7 +3804,7 @@ impl ScoreService {
             .await?;
         }
 
-        if user_play.course_play_state == 4 {
+        if progress.state == COURSE_CLEARED_STATE {
             let (stamina, max_stamina_ts) = self.get_user_stamina_info(user_id).await?;
             let mut result = HashMap::new();
             result.insert("rewards".to_string(), Value::Array(rewards));
@@ -3180,6 +3820,38 @@ impl ScoreService {
         Ok(HashMap::new())
     }
 
+    /// Reject a course play whose session has ended or whose chart is not the
+    /// course's next song. Courses without chart rows are not checked.
+    async fn validate_course_chart(
+        &self,
+        course_id: &str,
+        course_state: i32,
+        song_id: &str,
+        difficulty: i32,
+    ) -> ArcResult<()> {
+        if !(0..COURSE_CLEARED_STATE).contains(&course_state) {
+            return Err(ArcError::input("The course session has ended."));
+        }
+
+        let expected = sqlx::query!(
+            "SELECT song_id, difficulty FROM course_chart WHERE course_id = ? AND song_index = ?",
+            course_id,
+            course_state
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+        if let Some(expected) = expected {
+            if expected.song_id.as_deref() != Some(song_id)
+                || expected.difficulty != Some(difficulty)
+            {
+                return Err(ArcError::input(
+                    "The chart is not the next song of the course.",
+                ));
+            }
+        }
+        Ok(())
+    }
+
     async fn get_user_course_banners(&self, user_id: i32) -> ArcResult<Vec<Value>> {
         let rows = sqlx::query!(
             "SELECT item_id FROM user_item WHERE user_id = ? AND type = 'course_banner'",
@@ -3195,8 +3867,9 @@ impl ScoreService {
         Ok(banners)
     }
 
-    /// Get user's current world map stamina cost
-    async fn get_user_current_map(&self, user_id: i32) -> ArcResult<i32> {
+    /// Get user's current world map, failing when the map is outside its
+    /// availability window
+    async fn get_user_current_map(&self, user_id: i32) -> ArcResult<WorldMap> {
         let user = sqlx::query!("SELECT current_map FROM user WHERE user_id = ?", user_id)
             .fetch_one(&self.pool)
             .await?;
@@ -3212,8 +3885,13 @@ impl ScoreService {
         let map = parser
             .load_world_map(&current_map)
             .map_err(|_| ArcError::rocket_err(format!("Map {current_map} not found")))?;
+        // Checked before stamina is spent, so no play starts on a map that
+        // is outside its availability window.
+        if !map.is_available_at(current_timestamp_ms()) {
+            return Err(ArcError::map_locked("The map is not available."));
+        }
 
-        Ok(map.stamina_cost.unwrap_or(1))
+        Ok(map)
     }
 
     /// Incrementally update user's global rank score after a best_score score_v2 change.
@@ -3340,6 +4018,69 @@ fn generate_course_token() -> String {
     format!("c_{}", general_purpose::STANDARD.encode(random_bytes))
 }
 
+/// Course session state once all four songs have been played.
+const COURSE_CLEARED_STATE: i32 = 4;
+/// Course session state after the gauge ran out.
+const COURSE_FAILED_STATE: i32 = 5;
+
+/// Course session carried between the songs of a course.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct CourseProgress {
+    /// Index of the next song, or one of the final states.
+    state: i32,
+    score: i32,
+    clear_type: i32,
+}
+
+/// Course session after one more song. The song's score adds to the course
+/// score and the course keeps the weakest clear type so far; a depleted gauge
+/// (`health < 0`) fails the course and resets both.
+fn advance_course(
+    progress: CourseProgress,
+    song_score: i32,
+    song_clear_type: i32,
+    health: i32,
+) -> CourseProgress {
+    if health < 0 {
+        return CourseProgress {
+            state: COURSE_FAILED_STATE,
+            score: 0,
+            clear_type: 0,
+        };
+    }
+
+    let clear_type =
+        if Score::get_song_state(progress.clear_type) > Score::get_song_state(song_clear_type) {
+            song_clear_type
+        } else {
+            progress.clear_type
+        };
+    CourseProgress {
+        state: (progress.state + 1).min(COURSE_CLEARED_STATE),
+        score: progress.score + song_score,
+        clear_type,
+    }
+}
+
+/// Beyond boost gauge charge a play may spend: one or two full charges of 100,
+/// only on beyond maps and only while the gauge holds them. Anything else
+/// plays without a boost.
+fn valid_beyond_boost_gauge_use(requested: i32, gauge: f64, is_beyond: bool) -> i32 {
+    if is_beyond && matches!(requested, 100 | 200) && gauge >= requested as f64 {
+        requested
+    } else {
+        0
+    }
+}
+
+/// Generate a random replay token
+fn generate_replay_token() -> String {
+    let mut rng = rand::thread_rng();
+    (0..16)
+        .map(|_| format!("{:02x}", rng.gen::<u8>()))
+        .collect()
+}
+
 /// Generate a random skill flag with specified length
 fn generate_random_skill_flag(length: usize) -> String {
     (0..length)
@@ -3367,6 +4108,44 @@ fn parse_bool_string(value: &str) -> Option<bool> {
     }
 }
 
+/// Play record for an imported score: an ordinary ranked play with no
+/// token, world map or course attached
+fn imported_user_play(user_score: UserScore) -> UserPlay {
+    UserPlay {
+        user_score,
+        song_token: String::new(),
+        song_hash: String::new(),
+        submission_hash: String::new(),
+        beyond_gauge: 0,
+        unrank_flag: false,
+        new_best_protect_flag: false,
+        is_world_mode: Some(false),
+        stamina_multiply: 1,
+        fragment_multiply: 100,
+        prog_boost_multiply: 0,
+        beyond_boost_gauge_usage: 0,
+        course_id: None,
+        course_play_state: -1,
+        course_score: 0,
+        course_clear_type: 3,
+        combo_interval_bonus: None,
+        hp_interval_bonus: None,
+        fever_bonus: None,
+        rank_bonus: None,
+        maya_gauge: None,
+        nextstage_bonus: None,
+        skill_cytusii_flag: None,
+        skill_chinatsu_flag: None,
+        highest_health: None,
+        lowest_health: None,
+        room_code: None,
+        room_total_score: None,
+        room_total_players: None,
+        invasion_flag: 0,
+        ptt: None,
+    }
+}
+
 /// Get current timestamp in milliseconds
 fn current_timestamp() -> i64 {
     SystemTime::now()
@@ -3375,22 +4154,6 @@ fn current_timestamp() -> i64 {
         .as_millis() as i64
 }
 
-fn current_timestamp_seconds() -> i64 {
-    SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap()
-        .as_secs() as i64
-}
-
-fn today_timestamp_seconds() -> i64 {
-    let today = Local::now().date_naive();
-    Local
-        .from_local_datetime(&today.and_hms_opt(0, 0, 0).unwrap())
-        .earliest()
-        .unwrap()
-        .timestamp()
-}
-
 /// Calculate MD5 hash of a string
 pub fn md5_hash(input: &str) -> String {
     format!("{:x}", md5::compute(input.as_bytes()))
@@ -3398,7 +4161,63 @@ pub fn md5_hash(input: &str) -> String {
 
 #[cfg(test)]
 mod tests {
-    use super::calculate_trace_complete_ticket_reward;
+    use super::{
+        advance_course, calculate_trace_complete_ticket_reward, competition_ranks,
+        leaderboard_offset_around, valid_beyond_boost_gauge_use, CourseProgress, LeaderboardPage,
+        COURSE_CLEARED_STATE, COURSE_FAILED_STATE, LEADERBOARD_MAX_LIMIT,
+    };
+
+    #[test]
+    fn course_accumulates_score_and_weakest_clear_until_cleared() {
+        let mut progress = CourseProgress {
+            state: 0,
+            score: 0,
+            clear_type: 3,
+        };
+        for (score, clear_type) in [(9_900_000, 3), (9_800_000, 1), (9_950_000, 2)] {
+            progress = advance_course(progress, score, clear_type, 50);
+        }
+        assert_eq!(
+            progress,
+            CourseProgress {
+                state: 3,
+                score: 29_650_000,
+                clear_type: 1,
+            }
+        );
+
+        progress = advance_course(progress, 10_000_000, 3, 1);
+        assert_eq!(progress.state, COURSE_CLEARED_STATE);
+        assert_eq!(progress.score, 39_650_000);
+        assert_eq!(progress.clear_type, 1);
+    }
+
+    #[test]
+    fn course_fails_when_the_gauge_runs_out() {
+        let progress = CourseProgress {
+            state: 2,
+            score: 19_000_000,
+            clear_type: 3,
+        };
+        assert_eq!(
+            advance_course(progress, 9_000_000, 1, -1),
+            CourseProgress {
+                state: COURSE_FAILED_STATE,
+                score: 0,
+                clear_type: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn beyond_boost_gauge_use_needs_full_charges_on_a_beyond_map() {
+        assert_eq!(valid_beyond_boost_gauge_use(100, 150.0, true), 100);
+        assert_eq!(valid_beyond_boost_gauge_use(200, 200.0, true), 200);
+        assert_eq!(valid_beyond_boost_gauge_use(200, 199.9, true), 0);
+        assert_eq!(valid_beyond_boost_gauge_use(150, 200.0, true), 0);
+        assert_eq!(valid_beyond_boost_gauge_use(100, 200.0, false), 0);
+        assert_eq!(valid_beyond_boost_gauge_use(0, 200.0, true), 0);
+    }
 
     #[test]
     fn trace_complete_ticket_reward_requires_a_clear_and_chart_constant() {
@@ -3470,4 +4289,50 @@ mod tests {
             900
         );
     }
+
+    #[test]
+    fn leaderboard_offset_centers_rank_in_page() {
+        assert_eq!(leaderboard_offset_around(1, 20), 0);
+        assert_eq!(leaderboard_offset_around(10, 20), 0);
+        assert_eq!(leaderboard_offset_around(11, 20), 1);
+        assert_eq!(leaderboard_offset_around(100, 20), 90);
+        assert_eq!(leaderboard_offset_around(100, 5), 97);
+    }
+
+    #[test]
+    fn leaderboard_page_defaults_to_client_top_page() {
+        assert!(LeaderboardPage::default().is_default());
+        assert!(LeaderboardPage {
+            offset: Some(0),
+            limit: Some(20),
+            around_user: None,
+        }
+        .is_default());
+        assert!(!LeaderboardPage {
+            offset: Some(20),
+            ..Default::default()
+        }
+        .is_default());
+        assert!(!LeaderboardPage {
+            around_user: Some(1),
+            ..Default::default()
+        }
+        .is_default());
+
+        let page = LeaderboardPage {
+            limit: Some(10_000),
+            ..Default::default()
+        };
+        assert_eq!(page.clamped_limit(), LEADERBOARD_MAX_LIMIT);
+    }
+
+    #[test]
+    fn competition_ranks_share_ranks_between_equal_scores() {
+        assert_eq!(competition_ranks(&[]), Vec::<i32>::new());
+        assert_eq!(competition_ranks(&[900, 800, 700]), vec![1, 2, 3]);
+        assert_eq!(
+            competition_ranks(&[900, 800, 800, 700, 700, 700, 600]),
+            vec![1, 2, 2, 4, 4, 4, 7]
+        );
+    }
 }
@@ -0,0 +1,437 @@
+//! Bulk score import for the admin panel, e.g. to seed a tournament.
+//!
+//! Rows come as CSV with a header line or as a JSON array of objects and
+//! are recorded one by one through [`ScoreService::import_score`]; a row
+//! that fails is reported and does not stop the others.
+
+use crate::error::{ArcError, ArcResult};
+use crate::model::score::Score;
+use crate::service::score::ScoreService;
+use crate::DbPool;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Rows accepted by one import request
+pub const MAX_IMPORT_ROWS: usize = 5000;
+
+/// Columns every row must have
+const REQUIRED_COLUMNS: [&str; 8] = [
+    "user_code",
+    "song_id",
+    "difficulty",
+    "score",
+    "shiny_perfect_count",
+    "perfect_count",
+    "near_count",
+    "miss_count",
+];
+
+/// One score to import; `clear_type`, `health`, `modifier` and
+/// `time_played` (seconds) may be left out
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ScoreImportRow {
+    pub user_code: String,
+    pub song_id: String,
+    pub difficulty: i32,
+    pub score: i32,
+    pub shiny_perfect_count: i32,
+    pub perfect_count: i32,
+    pub near_count: i32,
+    pub miss_count: i32,
+    #[serde(default)]
+    pub clear_type: Option<i32>,
+    #[serde(default)]
+    pub health: Option<i32>,
+    #[serde(default)]
+    pub modifier: Option<i32>,
+    #[serde(default)]
+    pub time_played: Option<i64>,
+}
+
+impl ScoreImportRow {
+    /// The row as a score played at `now` (seconds) unless it says otherwise
+    fn to_score(&self, now: i64) -> Score {
+        let mut score = Score::new();
+        score.set_chart(self.song_id.trim().to_string(), self.difficulty);
+        score.set_score(
+            Some(self.score),
+            Some(self.shiny_perfect_count),
+            Some(self.perfect_count),
+            Some(self.near_count),
+            Some(self.miss_count),
+            Some(self.health.unwrap_or(100)),
+            Some(self.modifier.unwrap_or(0)),
+            Some(self.time_played.unwrap_or(now)),
+            Some(
+                self.clear_type
+                    .unwrap_or_else(|| default_clear_type(self.near_count, self.miss_count)),
+            ),
+        );
+        score
+    }
+}
+
+/// Outcome of one row; `row` is 1-based and counts data rows only
+#[derive(Debug, Clone, Serialize)]
+pub struct ScoreImportRowResult {
+    pub row: usize,
+    pub user_code: Option<String>,
+    pub song_id: Option<String>,
+    pub difficulty: Option<i32>,
+    /// `imported`, `valid` (dry run) or `failed`
+    pub status: &'static str,
+    pub rating: Option<f64>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ScoreImportReport {
+    pub dry_run: bool,
+    pub total: usize,
+    pub imported: usize,
+    pub failed: usize,
+    pub rows: Vec<ScoreImportRowResult>,
+}
+
+/// Imports batches of scores for players looked up by their user code
+#[derive(Debug, Clone)]
+pub struct ScoreImportService {
+    pool: DbPool,
+}
+
+impl ScoreImportService {
+    pub fn new(pool: DbPool) -> Self {
+        Self { pool }
+    }
+
+    /// Parse `body` and record every valid row through `scores`; with
+    /// `dry_run` the rows are only checked
+    pub async fn import(
+        &self,
+        scores: &ScoreService,
+        body: &str,
+        dry_run: bool,
+    ) -> ArcResult<ScoreImportReport> {
+        let rows = parse_score_import(body)?;
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs() as i64;
+
+        let mut user_ids: HashMap<String, Option<i32>> = HashMap::new();
+        let mut results = Vec::with_capacity(rows.len());
+        for (index, parsed) in rows.into_iter().enumerate() {
+            let row = match parsed {
+                Ok(row) => row,
+                Err(error) => {
+                    results.push(ScoreImportRowResult {
+                        row: index + 1,
+                        user_code: None,
+                        song_id: None,
+                        difficulty: None,
+                        status: "failed",
+                        rating: None,
+                        error: Some(error),
+                    });
+                    continue;
+                }
+            };
+
+            let outcome = self
+                .import_row(scores, &row, now, dry_run, &mut user_ids)
+                .await;
+            let (status, rating, error) = match outcome {
+                Ok(score) if dry_run => ("valid", Some(score.rating), None),
+                Ok(score) => ("imported", Some(score.rating), None),
+                Err(error) => ("failed", None, Some(error)),
+            };
+            results.push(ScoreImportRowResult {
+                row: index + 1,
+                user_code: Some(row.user_code),
+                song_id: Some(row.song_id),
+                difficulty: Some(row.difficulty),
+                status,
+                rating,
+                error,
+            });
+        }
+
+        let failed = results.iter().filter(|row| row.status == "failed").count();
+        let report = ScoreImportReport {
+            dry_run,
+            total: results.len(),
+            imported: if dry_run { 0 } else { results.len() - failed },
+            failed,
+            rows: results,
+        };
+        if !dry_run {
+            log::info!(
+                "Score import recorded {} of {} rows",
+                report.imported,
+                report.total
+            );
+        }
+        Ok(report)
+    }
+
+    async fn import_row(
+        &self,
+        scores: &ScoreService,
+        row: &ScoreImportRow,
+        now: i64,
+        dry_run: bool,
+        user_ids: &mut HashMap<String, Option<i32>>,
+    ) -> Result<Score, String> {
+        check_row(row)?;
+
+        let user_code = row.user_code.trim();
+        let user_id = match user_ids.get(user_code) {
+            Some(user_id) => *user_id,
+            None => {
+                let user_id =
+                    sqlx::query_scalar!("SELECT user_id FROM user WHERE user_code = ?", user_code)
+                        .fetch_optional(&self.pool)
+                        .await
+                        .map_err(|e| e.to_string())?;
+                user_ids.insert(user_code.to_string(), user_id);
+                user_id
+            }
+        };
+        let user_id = user_id.ok_or_else(|| format!("No user with code {user_code}"))?;
+
+        scores
+            .import_score(user_id, row.to_score(now), dry_run)
+            .await
+            .map_err(|e| e.message().to_string())
+    }
+}
+
+/// Split an import body into rows: a JSON array when it starts with `[`,
+/// CSV with a header line otherwise
+///
+/// Fails as a whole when the body cannot be read at all; a row that does
+/// not fit is returned as an error message instead.
+pub fn parse_score_import(body: &str) -> ArcResult<Vec<Result<ScoreImportRow, String>>> {
+    let body = body.trim_start_matches('\u{feff}').trim();
+    let rows = if body.starts_with('[') {
+        parse_json_rows(body)?
+    } else {
+        parse_csv_rows(body)?
+    };
+    if rows.is_empty() {
+        return Err(ArcError::input("No scores to import."));
+    }
+    if rows.len() > MAX_IMPORT_ROWS {
+        return Err(ArcError::input(format!(
+            "At most {MAX_IMPORT_ROWS} scores can be imported at once."
+        )));
+    }
+    Ok(rows)
+}
+
+fn parse_json_rows(body: &str) -> ArcResult<Vec<Result<ScoreImportRow, String>>> {
+    let values: Vec<Value> =
+        serde_json::from_str(body).map_err(|e| ArcError::input(format!("Invalid JSON: {e}")))?;
+    Ok(values
+        .into_iter()
+        .map(|value| serde_json::from_value(value).map_err(|e| e.to_string()))
+        .collect())
+}
+
+fn parse_csv_rows(body: &str) -> ArcResult<Vec<Result<ScoreImportRow, String>>> {
+    let mut lines = body.lines().filter(|line| !line.trim().is_empty());
+    let header: Vec<String> = lines
+        .next()
+        .map(|line| {
+            split_csv_line(line)
+                .map(|name| name.to_ascii_lowercase())
+                .collect()
+        })
+        .unwrap_or_default();
+    if let Some(missing) = REQUIRED_COLUMNS
+        .iter()
+        .find(|column| !header.iter().any(|name| name == *column))
+    {
+        return Err(ArcError::input(format!(
+            "CSV header is missing the `{missing}` column."
+        )));
+    }
+
+    Ok(lines
+        .map(|line| {
+            let fields: HashMap<&str, &str> = header
+                .iter()
+                .map(String::as_str)
+                .zip(split_csv_line(line))
+                .collect();
+            csv_row(&fields)
+        })
+        .collect())
+}
+
+/// Fields of one CSV line, trimmed and without surrounding quotes
+fn split_csv_line(line: &str) -> impl Iterator<Item = &str> {
+    line.split(',')
+        .map(|field| field.trim().trim_matches('"').trim())
+}
+
+fn csv_row(fields: &HashMap<&str, &str>) -> Result<ScoreImportRow, String> {
+    Ok(ScoreImportRow {
+        user_code: csv_text(fields, "user_code")?.to_string(),
+        song_id: csv_text(fields, "song_id")?.to_string(),
+        difficulty: csv_number(fields, "difficulty")?,
+        score: csv_number(fields, "score")?,
+        shiny_perfect_count: csv_number(fields, "shiny_perfect_count")?,
+        perfect_count: csv_number(fields, "perfect_count")?,
+        near_count: csv_number(fields, "near_count")?,
+        miss_count: csv_number(fields, "miss_count")?,
+        clear_type: csv_optional_number(fields, "clear_type")?,
+        health: csv_optional_number(fields, "health")?,
+        modifier: csv_optional_number(fields, "modifier")?,
+        time_played: csv_optional_number(fields, "time_played")?,
+    })
+}
+
+fn csv_text<'a>(fields: &HashMap<&str, &'a str>, name: &str) -> Result<&'a str, String> {
+    fields
+        .get(name)
+        .copied()
+        .filter(|value| !value.is_empty())
+        .ok_or_else(|| format!("Missing {name}"))
+}
+
+fn csv_number<T: std::str::FromStr>(fields: &HashMap<&str, &str>, name: &str) -> Result<T, String> {
+    csv_text(fields, name)?
+        .parse()
+        .map_err(|_| format!("{name} is not a number"))
+}
+
+/// An empty cell counts as left out
+fn csv_optional_number<T: std::str::FromStr>(
+    fields: &HashMap<&str, &str>,
+    name: &str,
+) -> Result<Option<T>, String> {
+    match fields.get(name) {
+        Some(value) if !value.is_empty() => csv_number(fields, name).map(Some),
+        _ => Ok(None),
+    }
+}
+
+/// Checks that read better than the generic judge count mismatch
+fn check_row(row: &ScoreImportRow) -> Result<(), String> {
+    if row.user_code.trim().is_empty() || row.song_id.trim().is_empty() {
+        return Err("user_code and song_id must not be empty".to_string());
+    }
+    if !(0..=4).contains(&row.difficulty) {
+        return Err(format!("Unknown difficulty {}", row.difficulty));
+    }
+    if let Some(clear_type) = row.clear_type {
+        if !(0..=5).contains(&clear_type) {
+            return Err(format!("Unknown clear_type {clear_type}"));
+        }
+    }
+    if row.shiny_perfect_count > row.perfect_count {
+        return Err("shiny_perfect_count is larger than perfect_count".to_string());
+    }
+    if row.time_played.is_some_and(|time_played| time_played <= 0) {
+        return Err("time_played must be positive".to_string());
+    }
+    Ok(())
+}
+
+/// Clear type implied by the judges when a row does not give one: pure
+/// memory, full recall or a normal clear
+fn default_clear_type(near_count: i32, miss_count: i32) -> i32 {
+    match (near_count, miss_count) {
+        (0, 0) => 3,
+        (_, 0) => 2,
+        _ => 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_csv_import() {
+        let body = "user_code,song_id,difficulty,score,shiny_perfect_count,perfect_count,near_count,miss_count,clear_type\n\
+                    000000001, tempestissimo ,3,9900000,900,990,10,0,\n\
+                    \n\
+                    000000002,grievouslady,2,abc,1,1,0,0,3\n";
+        let rows = parse_score_import(body).unwrap();
+        assert_eq!(rows.len(), 2);
+
+        let first = rows[0].as_ref().unwrap();
+        assert_eq!(first.user_code, "000000001");
+        assert_eq!(first.song_id, "tempestissimo");
+        assert_eq!(first.difficulty, 3);
+        assert_eq!(first.clear_type, None);
+        assert_eq!(first.time_played, None);
+        assert_eq!(rows[1].as_ref().unwrap_err(), "score is not a number");
+
+        let error = parse_score_import("user_code,song_id\n1,a").unwrap_err();
+        assert!(error.message().contains("`difficulty`"));
+        assert!(parse_score_import(" ").is_err());
+    }
+
+    #[test]
+    fn test_parse_json_import() {
+        let body = r#"[
+            {"user_code": "000000001", "song_id": "fractureray", "difficulty": 2,
+             "score": 10000000, "shiny_perfect_count": 10, "perfect_count": 10,
+             "near_count": 0, "miss_count": 0, "time_played": 1700000000},
+            {"user_code": "000000001", "song_id": "fractureray"}
+        ]"#;
+        let rows = parse_score_import(body).unwrap();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].as_ref().unwrap().time_played, Some(1700000000));
+        assert!(rows[1].is_err());
+        assert!(parse_score_import("[").is_err());
+    }
+
+    #[test]
+    fn test_check_row() {
+        let row = ScoreImportRow {
+            user_code: "000000001".to_string(),
+            song_id: "tempestissimo".to_string(),
+            difficulty: 3,
+            score: 10000010,
+            shiny_perfect_count: 10,
+            perfect_count: 10,
+            near_count: 0,
+            miss_count: 0,
+            clear_type: None,
+            health: None,
+            modifier: None,
+            time_played: None,
+        };
+        assert_eq!(check_row(&row), Ok(()));
+
+        let score = row.to_score(1_700_000_000);
+        assert!(score.is_valid());
+        assert_eq!(score.clear_type, 3);
+        assert_eq!(score.health, 100);
+        assert_eq!(score.time_played, 1_700_000_000);
+
+        let invalid = ScoreImportRow {
+            difficulty: 5,
+            ..row.clone()
+        };
+        assert!(check_row(&invalid).is_err());
+        let invalid = ScoreImportRow {
+            shiny_perfect_count: 11,
+            ..row
+        };
+        assert!(check_row(&invalid).is_err());
+    }
+
+    #[test]
+    fn test_default_clear_type() {
+        assert_eq!(default_clear_type(0, 0), 3);
+        assert_eq!(default_clear_type(4, 0), 2);
+        assert_eq!(default_clear_type(0, 1), 1);
+    }
+}
@@ -0,0 +1,198 @@
+use crate::error::ArcResult;
+use crate::model::Score;
+use crate::DbPool;
+use std::future::Future;
+use std::pin::Pin;
+use tokio::sync::mpsc;
+
+pub type DeferredTask = Pin<Box<dyn Future<Output = ArcResult<()>> + Send>>;
+
+/// A write that does not affect the submit response.
+pub enum ScoreWrite {
+    /// Row of the full play log in `user_score`.
+    ScoreLog { user_id: i32, score: Score },
+    /// Daily potential history in `user_rating`; `day` is the local midnight
+    /// in seconds.
+    RatingHistory {
+        user_id: i32,
+        day: i64,
+        rating_ptt: f64,
+    },
+    /// Follow-up work of another service, such as event points or
+    /// achievements.
+    Task {
+        name: &'static str,
+        task: DeferredTask,
+    },
+}
+
+impl ScoreWrite {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::ScoreLog { .. } => "score log",
+            Self::RatingHistory { .. } => "rating history",
+            Self::Task { name, .. } => *name,
+        }
+    }
+
+    async fn apply(self, pool: &DbPool) -> ArcResult<()> {
+        match self {
+            Self::ScoreLog { user_id, score } => insert_score_log(pool, user_id, &score).await,
+            Self::RatingHistory {
+                user_id,
+                day,
+                rating_ptt,
+            } => record_rating_history(pool, user_id, day, rating_ptt).await,
+            Self::Task { task, .. } => task.await,
+        }
+    }
+}
+
+/// Write-behind queue for the non-critical writes of score submission.
+///
+/// Best scores, recent 30 and the user's rating are written before the submit
+/// response because the response and the next potential depend on them; the
+/// play log, rating history and other services' follow-up work go through a
+/// single background worker in submission order. The queue is bounded: when
+/// it is full, submissions wait for room instead of dropping writes. With a
+/// capacity of 0 every write runs inline.
+#[derive(Clone)]
+pub struct ScoreWriteQueue {
+    pool: DbPool,
+    sender: Option<mpsc::Sender<ScoreWrite>>,
+}
+
+impl ScoreWriteQueue {
+    /// Queue that writes inline, for tools and tests without a worker.
+    pub fn inline(pool: DbPool) -> Self {
+        Self { pool, sender: None }
+    }
+
+    /// Start the background worker.
+    pub fn spawn(pool: DbPool, capacity: usize) -> Self {
+        if capacity == 0 {
+            return Self::inline(pool);
+        }
+        let sender = spawn_worker(pool.clone(), capacity);
+        Self {
+            pool,
+            sender: Some(sender),
+        }
+    }
+
+    /// Hand a write to the worker, or run it now when the queue is disabled
+    /// or its worker has stopped.
+    pub async fn enqueue(&self, write: ScoreWrite) -> ArcResult<()> {
+        let write = match &self.sender {
+            Some(sender) => match sender.send(write).await {
+                Ok(()) => return Ok(()),
+                Err(mpsc::error::SendError(write)) => {
+                    log::warn!("Score write queue is closed, writing inline");
+                    write
+                }
+            },
+            None => write,
+        };
+        write.apply(&self.pool).await
+    }
+
+    /// Queue follow-up work that should run after this submission's writes.
+    /// Failures are logged, never returned.
+    pub async fn defer<F>(&self, name: &'static str, task: F)
+    where
+        F: Future<Output = ArcResult<()>> + Send + 'static,
+    {
+        let task = ScoreWrite::Task {
+            name,
+            task: Box::pin(task),
+        };
+        if let Err(e) = self.enqueue(task).await {
+            log::warn!("Deferred {name} failed: {e}");
+        }
+    }
+}
+
+fn spawn_worker(pool: DbPool, capacity: usize) -> mpsc::Sender<ScoreWrite> {
+    let (sender, mut receiver) = mpsc::channel::<ScoreWrite>(capacity);
+
+    tokio::spawn(async move {
+        while let Some(write) = receiver.recv().await {
+            let name = write.name();
+            if let Err(e) = write.apply(&pool).await {
+                log::warn!("Queued {name} write failed: {e}");
+            }
+        }
+    });
+
+    sender
+}
+
+async fn insert_score_log(pool: &DbPool, user_id: i32, score: &Score) -> ArcResult<()> {
+    sqlx::query!(
+        "INSERT INTO user_score (
+            user_id, song_id, difficulty, time_played, score,
+            shiny_perfect_count, perfect_count, near_count, miss_count,
+            health, modifier, clear_type, rating
+        ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+        ON DUPLICATE KEY UPDATE
+            score = VALUES(score),
+            shiny_perfect_count = VALUES(shiny_perfect_count),
+            perfect_count = VALUES(perfect_count),
+            near_count = VALUES(near_count),
+            miss_count = VALUES(miss_count),
+            health = VALUES(health),
+            modifier = VALUES(modifier),
+            clear_type = VALUES(clear_type),
+            rating = VALUES(rating)",
+        user_id,
+        score.song_id,
+        score.difficulty,
+        score.time_played,
+        score.score,
+        score.shiny_perfect_count,
+        score.perfect_count,
+        score.near_count,
+        score.miss_count,
+        score.health,
+        score.modifier,
+        score.clear_type,
+        score.rating
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+async fn record_rating_history(
+    pool: &DbPool,
+    user_id: i32,
+    day: i64,
+    rating_ptt: f64,
+) -> ArcResult<()> {
+    let old_ptt = sqlx::query!(
+        "SELECT rating_ptt FROM user_rating WHERE user_id = ? AND time = ?",
+        user_id,
+        day
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    let old_ptt = old_ptt.and_then(|row| row.rating_ptt);
+    let should_record = match old_ptt {
+        Some(ptt) => (ptt - rating_ptt).abs() > f64::EPSILON,
+        None => true,
+    };
+    if should_record {
+        sqlx::query!(
+            "INSERT INTO user_rating (user_id, time, rating_ptt)
+             VALUES (?, ?, ?)
+             ON DUPLICATE KEY UPDATE rating_ptt = VALUES(rating_ptt)",
+            user_id,
+            day,
+            rating_ptt
+        )
+        .execute(pool)
+        .await?;
+    }
+    Ok(())
+}
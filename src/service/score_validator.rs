@@ -0,0 +1,289 @@
+use crate::error::ArcResult;
+use crate::model::Score;
+use crate::service::webhook::{WebhookEvent, WebhookNotifier};
+use crate::utils::current_timestamp_ms;
+use crate::DbPool;
+
+/// Score of a pure memory before shiny perfects are added.
+const BASE_MAX_SCORE: i32 = 10_000_000;
+/// Other players' best scores that must agree on a chart's note count before
+/// the crowd-sourced count is used.
+pub(crate) const MIN_NOTE_SAMPLES: i64 = 3;
+/// Clock drift tolerated between a play's timestamp and the server clock.
+const MAX_CLOCK_SKEW_MS: i64 = 60_000;
+
+/// Reasons a submission is written to `score_audit`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScoreFlag {
+    /// More shiny perfects than perfects.
+    ShinyExceedsPerfect,
+    /// Judgement total differs from the note count other players agree on.
+    /// A heuristic: players who collude can set that count, so it is only
+    /// audited, never rejected.
+    NoteCountMismatch,
+    /// Score above the chart's theoretical maximum.
+    ScoreAboveMax,
+    /// Pure memory or full recall with judgements that rule it out.
+    ClearTypeMismatch,
+    /// Client-reported play time before the song token was issued, or in the
+    /// future. Rejected.
+    TimeTravel,
+}
+
+impl ScoreFlag {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::ShinyExceedsPerfect => "shiny_exceeds_perfect",
+            Self::NoteCountMismatch => "note_count_mismatch",
+            Self::ScoreAboveMax => "score_above_max",
+            Self::ClearTypeMismatch => "clear_type_mismatch",
+            Self::TimeTravel => "time_travel",
+        }
+    }
+
+    /// Whether the submission is refused rather than recorded and audited.
+    pub fn rejects(self) -> bool {
+        matches!(self, Self::TimeTravel)
+    }
+}
+
+/// Submission-time sanity checks on a single play. Impossible plays are
+/// logged to `score_audit` for review in the admin panel; plays the client
+/// stamps outside the window of their song token are refused outright.
+///
+/// There is no note count column on `chart`, so the expected note count is
+/// crowd-sourced from other players' best scores rather than chart data.
+#[derive(Clone)]
+pub struct ScoreValidator {
+    pool: DbPool,
+    webhook: WebhookNotifier,
+}
+
+impl ScoreValidator {
+    pub fn new(pool: DbPool) -> Self {
+        Self {
+            pool,
+            webhook: WebhookNotifier::disabled(),
+        }
+    }
+
+    /// Report every audited submission as a `flagged_score` event.
+    pub fn with_webhook(mut self, webhook: WebhookNotifier) -> Self {
+        self.webhook = webhook;
+        self
+    }
+
+    /// Check `score`, which the client reports as played at `played_at`
+    /// (milliseconds), against the crowd-sourced note count of the chart and
+    /// the creation time of its song token.
+    pub async fn check(
+        &self,
+        user_id: i32,
+        score: &Score,
+        played_at: i64,
+        token_created_at: Option<i64>,
+    ) -> ArcResult<Vec<ScoreFlag>> {
+        let chart_notes = self
+            .chart_note_count(user_id, &score.song_id, score.difficulty)
+            .await?;
+        Ok(check_score(
+            score,
+            chart_notes,
+            played_at,
+            token_created_at,
+            current_timestamp_ms(),
+        ))
+    }
+
+    /// Note count most other players' best scores on the chart add up to,
+    /// once at least [`MIN_NOTE_SAMPLES`] of them agree.
+    ///
+    /// This is a heuristic, not chart metadata: enough accounts submitting the
+    /// same wrong judgement total would get honest plays flagged.
+    async fn chart_note_count(
+        &self,
+        user_id: i32,
+        song_id: &str,
+        difficulty: i32,
+    ) -> ArcResult<Option<i32>> {
+        let row = sqlx::query!(
+            "SELECT perfect_count + near_count + miss_count AS `notes!: i64`,
+                    COUNT(*) AS `plays!: i64`
+             FROM best_score
+             WHERE song_id = ? AND difficulty = ? AND user_id <> ?
+             GROUP BY notes
+             ORDER BY plays DESC
+             LIMIT 1",
+            song_id,
+            difficulty,
+            user_id
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row
+            .filter(|row| row.plays >= MIN_NOTE_SAMPLES)
+            .and_then(|row| i32::try_from(row.notes).ok()))
+    }
+
+    /// Append a flagged submission to the audit log.
+    pub async fn record(&self, user_id: i32, score: &Score, flags: &[ScoreFlag]) -> ArcResult<()> {
+        let flag_names = flags
+            .iter()
+            .map(|flag| flag.as_str())
+            .collect::<Vec<_>>()
+            .join(",");
+        let rejected = flags.iter().any(|flag| flag.rejects());
+        sqlx::query!(
+            "INSERT INTO score_audit
+             (user_id, song_id, difficulty, score, shiny_perfect_count, perfect_count,
+              near_count, miss_count, clear_type, flags, rejected, created_at)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            user_id,
+            score.song_id,
+            score.difficulty,
+            score.score,
+            score.shiny_perfect_count,
+            score.perfect_count,
+            score.near_count,
+            score.miss_count,
+            score.clear_type,
+            flag_names,
+            rejected,
+            current_timestamp_ms()
+        )
+        .execute(&self.pool)
+        .await?;
+
+        self.webhook.notify(WebhookEvent::FlaggedScore {
+            user_id,
+            song_id: score.song_id.clone(),
+            difficulty: score.difficulty,
+            score: score.score,
+            flags: flags.iter().map(|flag| flag.as_str()).collect(),
+            rejected,
+        });
+        Ok(())
+    }
+
+    pub async fn mark_reviewed(&self, audit_id: i64, note: &str) -> ArcResult<u64> {
+        Ok(sqlx::query!(
+            "UPDATE score_audit SET reviewed_at = ?, review_note = ? WHERE audit_id = ?",
+            current_timestamp_ms(),
+            note,
+            audit_id
+        )
+        .execute(&self.pool)
+        .await?
+        .rows_affected())
+    }
+}
+
+fn check_score(
+    score: &Score,
+    chart_notes: Option<i32>,
+    played_at: i64,
+    token_created_at: Option<i64>,
+    now: i64,
+) -> Vec<ScoreFlag> {
+    let mut flags = Vec::new();
+    let notes = score.all_note_count();
+
+    if score.shiny_perfect_count > score.perfect_count {
+        flags.push(ScoreFlag::ShinyExceedsPerfect);
+    }
+    if chart_notes.is_some_and(|chart_notes| chart_notes != notes) {
+        flags.push(ScoreFlag::NoteCountMismatch);
+    }
+    if score.score > BASE_MAX_SCORE + chart_notes.unwrap_or(notes) {
+        flags.push(ScoreFlag::ScoreAboveMax);
+    }
+    let clear_type_possible = match score.clear_type {
+        3 => score.near_count == 0 && score.miss_count == 0,
+        2 => score.miss_count == 0,
+        _ => true,
+    };
+    if !clear_type_possible {
+        flags.push(ScoreFlag::ClearTypeMismatch);
+    }
+    if played_at > now + MAX_CLOCK_SKEW_MS
+        || token_created_at.is_some_and(|created_at| played_at < created_at)
+    {
+        flags.push(ScoreFlag::TimeTravel);
+    }
+
+    flags
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: i64 = 1_700_000_000_000;
+
+    fn play(shiny: i32, perfect: i32, near: i32, miss: i32, clear_type: i32) -> Score {
+        let notes = perfect + near + miss;
+        let score =
+            (10_000_000.0 / notes as f64 * (perfect as f64 + near as f64 / 2.0)) as i32 + shiny;
+        Score {
+            song_id: "tempestissimo".to_string(),
+            difficulty: 3,
+            score,
+            shiny_perfect_count: shiny,
+            perfect_count: perfect,
+            near_count: near,
+            miss_count: miss,
+            clear_type,
+            ..Score::default()
+        }
+    }
+
+    #[test]
+    fn test_check_score_accepts_plausible_play() {
+        let score = play(1200, 1500, 30, 10, 1);
+        assert!(check_score(&score, Some(1540), NOW, Some(NOW - 120_000), NOW).is_empty());
+        assert!(check_score(&score, None, NOW, None, NOW).is_empty());
+    }
+
+    #[test]
+    fn test_check_score_flags_impossible_judgements() {
+        let score = play(1600, 1500, 30, 10, 3);
+        assert_eq!(
+            check_score(&score, Some(1400), NOW, None, NOW),
+            vec![
+                ScoreFlag::ShinyExceedsPerfect,
+                ScoreFlag::NoteCountMismatch,
+                ScoreFlag::ClearTypeMismatch,
+            ]
+        );
+
+        let above_max = Score {
+            score: 10_001_541,
+            ..play(1540, 1540, 0, 0, 3)
+        };
+        assert_eq!(
+            check_score(&above_max, None, NOW, None, NOW),
+            vec![ScoreFlag::ScoreAboveMax]
+        );
+
+        let full_recall_with_miss = play(0, 1500, 0, 1, 2);
+        assert_eq!(
+            check_score(&full_recall_with_miss, None, NOW, None, NOW),
+            vec![ScoreFlag::ClearTypeMismatch]
+        );
+    }
+
+    #[test]
+    fn test_check_score_rejects_time_travel() {
+        let score = play(1200, 1500, 30, 10, 1);
+        let flags = check_score(&score, None, NOW, Some(NOW + 1), NOW);
+        assert_eq!(flags, vec![ScoreFlag::TimeTravel]);
+        assert!(flags[0].rejects());
+
+        assert_eq!(
+            check_score(&score, None, NOW + MAX_CLOCK_SKEW_MS + 1, None, NOW),
+            vec![ScoreFlag::TimeTravel]
+        );
+        assert!(!ScoreFlag::ShinyExceedsPerfect.rejects());
+    }
+}
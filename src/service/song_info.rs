@@ -0,0 +1,187 @@
+//! Read-only song data for the public `/api/song` endpoint.
+
+use crate::error::{ArcError, ArcResult};
+use crate::service::asset_manager::{is_song_id, SongAvailability};
+use crate::service::cache::{env_ttl_seconds, CacheService};
+use crate::service::score_validator::MIN_NOTE_SAMPLES;
+use crate::service::AssetManager;
+use crate::DbPool;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// One difficulty of a song
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChartInfo {
+    pub difficulty: i32,
+    /// Chart constant, `None` while the chart is unrated.
+    pub constant: Option<f64>,
+    /// Note count most players' best scores agree on, once enough of them do.
+    pub note_count: Option<i32>,
+}
+
+/// Body of `/api/song/<song_id>`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SongDetail {
+    pub song_id: String,
+    pub name: String,
+    pub charts: Vec<ChartInfo>,
+    pub availability: SongAvailability,
+}
+
+/// Charts listed for a song: difficulties with a constant or an agreed note
+/// count. `note_rows` are `(difficulty, notes, plays)` ordered by `plays`
+/// descending.
+fn chart_infos(ratings: [i32; 5], note_rows: &[(i32, i64, i64)]) -> Vec<ChartInfo> {
+    (0..ratings.len() as i32)
+        .filter_map(|difficulty| {
+            let constant = Some(ratings[difficulty as usize])
+                .filter(|rating| *rating > 0)
+                .map(|rating| rating as f64 / 10.0);
+            let note_count = note_rows
+                .iter()
+                .find(|(row_difficulty, _, _)| *row_difficulty == difficulty)
+                .filter(|(_, _, plays)| *plays >= MIN_NOTE_SAMPLES)
+                .and_then(|(_, notes, _)| i32::try_from(*notes).ok());
+            (constant.is_some() || note_count.is_some()).then_some(ChartInfo {
+                difficulty,
+                constant,
+                note_count,
+            })
+        })
+        .collect()
+}
+
+/// Chart constants, note counts and availability per song, cached in Redis
+#[derive(Clone)]
+pub struct SongInfoService {
+    pool: DbPool,
+    asset_manager: Option<Arc<AssetManager>>,
+    cache: Option<CacheService>,
+    song_info_cache_ttl_seconds: u64,
+}
+
+impl SongInfoService {
+    pub fn new(pool: DbPool) -> Self {
+        Self {
+            pool,
+            asset_manager: None,
+            cache: None,
+            song_info_cache_ttl_seconds: env_ttl_seconds("REDIS_SONG_INFO_TTL_SECONDS", 60),
+        }
+    }
+
+    /// Report songlist and song file availability; without it only the
+    /// `chart` table is used
+    pub fn with_asset_manager(mut self, asset_manager: Arc<AssetManager>) -> Self {
+        self.asset_manager = Some(asset_manager);
+        self
+    }
+
+    pub fn with_cache(mut self, cache: Option<CacheService>) -> Self {
+        self.cache = cache;
+        self
+    }
+
+    /// Look up a song by id; unknown songs are a 404
+    pub async fn get_song(&self, song_id: &str) -> ArcResult<SongDetail> {
+        if !is_song_id(song_id) {
+            return Err(ArcError::input("Invalid song id."));
+        }
+
+        let cache_key = format!("song_info:{song_id}");
+        if let Some(cache) = &self.cache {
+            if let Some(detail) = cache.get_json(&cache_key).await {
+                return Ok(detail);
+            }
+        }
+
+        let chart = sqlx::query!(
+            "SELECT name, rating_pst, rating_prs, rating_ftr, rating_byn, rating_etr
+             FROM chart WHERE song_id = ?",
+            song_id
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or_else(|| ArcError::no_data_status("No song info.", 108, 404))?;
+
+        let note_rows = sqlx::query!(
+            "SELECT difficulty, perfect_count + near_count + miss_count AS `notes!: i64`,
+                    COUNT(*) AS `plays!: i64`
+             FROM best_score
+             WHERE song_id = ?
+             GROUP BY difficulty, notes
+             ORDER BY plays DESC",
+            song_id
+        )
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(|row| (row.difficulty, row.notes, row.plays))
+        .collect::<Vec<_>>();
+
+        let ratings = [
+            chart.rating_pst,
+            chart.rating_prs,
+            chart.rating_ftr,
+            chart.rating_byn,
+            chart.rating_etr,
+        ]
+        .map(|rating| rating.unwrap_or(-1));
+        let detail = SongDetail {
+            song_id: song_id.to_string(),
+            name: chart.name.unwrap_or_else(|| song_id.to_string()),
+            charts: chart_infos(ratings, &note_rows),
+            availability: self
+                .asset_manager
+                .as_ref()
+                .map(|assets| assets.song_availability(song_id))
+                .unwrap_or_default(),
+        };
+
+        if let Some(cache) = &self.cache {
+            cache
+                .set_json(&cache_key, &detail, self.song_info_cache_ttl_seconds)
+                .await;
+        }
+        Ok(detail)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chart_infos() {
+        let note_rows = [(2, 1068, 5), (2, 1067, 1), (3, 1451, 1), (1, 700, 3)];
+        let charts = chart_infos([40, -1, 113, 116, -1], &note_rows);
+
+        assert_eq!(
+            charts,
+            vec![
+                ChartInfo {
+                    difficulty: 0,
+                    constant: Some(4.0),
+                    note_count: None,
+                },
+                ChartInfo {
+                    difficulty: 1,
+                    constant: None,
+                    note_count: Some(700),
+                },
+                ChartInfo {
+                    difficulty: 2,
+                    constant: Some(11.3),
+                    note_count: Some(1068),
+                },
+                ChartInfo {
+                    difficulty: 3,
+                    constant: Some(11.6),
+                    note_count: None,
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,329 @@
+//! Song packages uploaded from the admin panel: a zip of one song folder
+//! (`0.aff`..`4.aff`, `base.ogg`, ...), read without extracting to disk.
+//!
+//! Only what song folders need is supported: stored and deflated entries
+//! without encryption or zip64.
+
+use crate::error::{ArcError, ArcResult};
+use crate::service::asset_manager::ALLOWED_FILE_NAMES;
+use flate2::read::DeflateDecoder;
+use std::io::Read;
+
+const LOCAL_HEADER_SIGNATURE: u32 = 0x0403_4b50;
+const CENTRAL_HEADER_SIGNATURE: u32 = 0x0201_4b50;
+const END_OF_CENTRAL_DIRECTORY_SIGNATURE: u32 = 0x0605_4b50;
+const END_OF_CENTRAL_DIRECTORY_LEN: usize = 22;
+/// Largest unpacked song folder, guarding against zip bombs
+const MAX_UNPACKED_SIZE: u64 = 2 * 1024 * 1024 * 1024;
+
+/// Files of one song folder
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SongPackage {
+    /// Name of the folder the files were packed in, if they were
+    pub folder: Option<String>,
+    /// File name and contents, in archive order
+    pub files: Vec<(String, Vec<u8>)>,
+}
+
+impl SongPackage {
+    /// Read a song zip
+    ///
+    /// Files may sit at the archive root or in a single top-level folder.
+    /// Every file must be one the download API serves, and the package needs
+    /// `base.ogg` and at least one chart.
+    pub fn from_zip(bytes: &[u8]) -> ArcResult<Self> {
+        let mut folder: Option<String> = None;
+        let mut files: Vec<(String, Vec<u8>)> = Vec::new();
+        let mut unpacked = 0u64;
+
+        for entry in central_directory(bytes)? {
+            let (entry_folder, file_name) = match entry.name.rsplit_once('/') {
+                Some((dir, file)) => (Some(dir), file),
+                None => (None, entry.name.as_str()),
+            };
+            if file_name.is_empty()
+                || entry.name.starts_with("__MACOSX/")
+                || file_name.starts_with("._")
+                || file_name == ".DS_Store"
+            {
+                continue;
+            }
+            if entry_folder.is_some_and(|dir| dir.is_empty() || dir.contains('/') || dir == "..") {
+                return Err(ArcError::input(format!(
+                    "`{}` is nested too deep; pack a single song folder",
+                    entry.name
+                )));
+            }
+            match (&folder, entry_folder) {
+                (Some(current), Some(dir)) if current != dir => {
+                    return Err(ArcError::input("The zip contains more than one folder"));
+                }
+                (None, Some(dir)) if !files.is_empty() => {
+                    return Err(ArcError::input(format!(
+                        "`{dir}/` is mixed with files at the zip root"
+                    )));
+                }
+                (Some(_), None) => {
+                    return Err(ArcError::input(format!(
+                        "`{file_name}` is outside the song folder"
+                    )));
+                }
+                (None, Some(dir)) => folder = Some(dir.to_string()),
+                _ => {}
+            }
+            if !ALLOWED_FILE_NAMES.contains(&file_name) {
+                return Err(ArcError::input(format!("`{file_name}` is not a song file")));
+            }
+            if files.iter().any(|(name, _)| name == file_name) {
+                return Err(ArcError::input(format!("`{file_name}` appears twice")));
+            }
+
+            unpacked += entry.size;
+            if unpacked > MAX_UNPACKED_SIZE {
+                return Err(ArcError::input("The unpacked song is too large"));
+            }
+            files.push((file_name.to_string(), entry.read(bytes)?));
+        }
+
+        if !files.iter().any(|(name, _)| name == "base.ogg") {
+            return Err(ArcError::input("The song package has no base.ogg"));
+        }
+        if !files.iter().any(|(name, _)| name.ends_with(".aff")) {
+            return Err(ArcError::input("The song package has no chart"));
+        }
+        Ok(Self { folder, files })
+    }
+}
+
+/// One file of the central directory
+struct ZipEntry {
+    name: String,
+    method: u16,
+    crc32: u32,
+    compressed_size: u64,
+    size: u64,
+    local_header_offset: usize,
+}
+
+impl ZipEntry {
+    /// Unpack the entry and check its checksum
+    fn read(&self, bytes: &[u8]) -> ArcResult<Vec<u8>> {
+        let header = self.local_header_offset;
+        if read_u32(bytes, header)? != LOCAL_HEADER_SIGNATURE {
+            return Err(corrupt(&self.name));
+        }
+        let name_len = read_u16(bytes, header + 26)? as usize;
+        let extra_len = read_u16(bytes, header + 28)? as usize;
+        let start = header + 30 + name_len + extra_len;
+        let data = usize::try_from(self.compressed_size)
+            .ok()
+            .and_then(|len| bytes.get(start..start.checked_add(len)?))
+            .ok_or_else(|| corrupt(&self.name))?;
+
+        let contents = match self.method {
+            0 => data.to_vec(),
+            8 => {
+                let mut contents = Vec::with_capacity(self.size.min(64 * 1024 * 1024) as usize);
+                DeflateDecoder::new(data)
+                    .take(self.size + 1)
+                    .read_to_end(&mut contents)
+                    .map_err(|_| corrupt(&self.name))?;
+                contents
+            }
+            method => {
+                return Err(ArcError::input(format!(
+                    "`{}` uses unsupported compression method {method}",
+                    self.name
+                )))
+            }
+        };
+
+        let mut crc = flate2::Crc::new();
+        crc.update(&contents);
+        if contents.len() as u64 != self.size || crc.sum() != self.crc32 {
+            return Err(corrupt(&self.name));
+        }
+        Ok(contents)
+    }
+}
+
+/// Entries listed in the central directory, in archive order
+fn central_directory(bytes: &[u8]) -> ArcResult<Vec<ZipEntry>> {
+    let not_zip = || ArcError::input("The uploaded file is not a zip archive");
+    if bytes.len() < END_OF_CENTRAL_DIRECTORY_LEN {
+        return Err(not_zip());
+    }
+    // The record ends with a comment of up to 64 KiB.
+    let search_start = bytes
+        .len()
+        .saturating_sub(END_OF_CENTRAL_DIRECTORY_LEN + u16::MAX as usize);
+    let end = (search_start..=bytes.len() - END_OF_CENTRAL_DIRECTORY_LEN)
+        .rev()
+        .find(|&offset| read_u32(bytes, offset).ok() == Some(END_OF_CENTRAL_DIRECTORY_SIGNATURE))
+        .ok_or_else(not_zip)?;
+
+    let count = read_u16(bytes, end + 10)?;
+    let directory_offset = read_u32(bytes, end + 16)?;
+    if count == u16::MAX || directory_offset == u32::MAX {
+        return Err(ArcError::input("Zip64 archives are not supported"));
+    }
+
+    let mut entries = Vec::with_capacity(count as usize);
+    let mut offset = directory_offset as usize;
+    for _ in 0..count {
+        if read_u32(bytes, offset)? != CENTRAL_HEADER_SIGNATURE {
+            return Err(not_zip());
+        }
+        let flags = read_u16(bytes, offset + 8)?;
+        let name_len = read_u16(bytes, offset + 28)? as usize;
+        let extra_len = read_u16(bytes, offset + 30)? as usize;
+        let comment_len = read_u16(bytes, offset + 32)? as usize;
+        let name = bytes
+            .get(offset + 46..offset + 46 + name_len)
+            .ok_or_else(not_zip)?;
+        let name = String::from_utf8_lossy(name).replace('\\', "/");
+        if flags & 1 != 0 {
+            return Err(ArcError::input(format!("`{name}` is encrypted")));
+        }
+        let compressed_size = read_u32(bytes, offset + 20)?;
+        let size = read_u32(bytes, offset + 24)?;
+        if compressed_size == u32::MAX || size == u32::MAX {
+            return Err(ArcError::input("Zip64 archives are not supported"));
+        }
+
+        entries.push(ZipEntry {
+            method: read_u16(bytes, offset + 10)?,
+            crc32: read_u32(bytes, offset + 16)?,
+            compressed_size: compressed_size as u64,
+            size: size as u64,
+            local_header_offset: read_u32(bytes, offset + 42)? as usize,
+            name,
+        });
+        offset += 46 + name_len + extra_len + comment_len;
+    }
+    Ok(entries)
+}
+
+fn corrupt(name: &str) -> ArcError {
+    ArcError::input(format!("`{name}` is corrupt in the zip archive"))
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> ArcResult<u16> {
+    bytes
+        .get(offset..offset + 2)
+        .map(|b| u16::from_le_bytes([b[0], b[1]]))
+        .ok_or_else(|| ArcError::input("The zip archive is truncated"))
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> ArcResult<u32> {
+    bytes
+        .get(offset..offset + 4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or_else(|| ArcError::input("The zip archive is truncated"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::DeflateEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+
+    /// Zip with the given entries; `deflate` compresses every file
+    fn zip(entries: &[(&str, &[u8])], deflate: bool) -> Vec<u8> {
+        let mut out = Vec::new();
+        let mut directory = Vec::new();
+        for (name, contents) in entries {
+            let data = if deflate {
+                let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+                encoder.write_all(contents).unwrap();
+                encoder.finish().unwrap()
+            } else {
+                contents.to_vec()
+            };
+            let mut crc = flate2::Crc::new();
+            crc.update(contents);
+            let method: u16 = if deflate { 8 } else { 0 };
+            let offset = out.len() as u32;
+
+            let mut fields = Vec::new();
+            fields.extend_from_slice(&method.to_le_bytes());
+            fields.extend_from_slice(&[0; 4]);
+            fields.extend_from_slice(&crc.sum().to_le_bytes());
+            fields.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            fields.extend_from_slice(&(contents.len() as u32).to_le_bytes());
+            fields.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            fields.extend_from_slice(&0u16.to_le_bytes());
+
+            out.extend_from_slice(&LOCAL_HEADER_SIGNATURE.to_le_bytes());
+            out.extend_from_slice(&[20, 0, 0, 0]);
+            out.extend_from_slice(&fields);
+            out.extend_from_slice(name.as_bytes());
+            out.extend_from_slice(&data);
+
+            directory.extend_from_slice(&CENTRAL_HEADER_SIGNATURE.to_le_bytes());
+            directory.extend_from_slice(&[20, 0, 20, 0, 0, 0]);
+            directory.extend_from_slice(&fields);
+            directory.extend_from_slice(&[0; 10]);
+            directory.extend_from_slice(&offset.to_le_bytes());
+            directory.extend_from_slice(name.as_bytes());
+        }
+        let directory_offset = out.len() as u32;
+        out.extend_from_slice(&directory);
+        out.extend_from_slice(&END_OF_CENTRAL_DIRECTORY_SIGNATURE.to_le_bytes());
+        out.extend_from_slice(&[0; 4]);
+        out.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+        out.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+        out.extend_from_slice(&(directory.len() as u32).to_le_bytes());
+        out.extend_from_slice(&directory_offset.to_le_bytes());
+        out.extend_from_slice(&0u16.to_le_bytes());
+        out
+    }
+
+    #[test]
+    fn test_song_package_from_zip() {
+        let chart = b"AudioOffset:0\n-\ntiming(0,120.00,4.00);\n".repeat(20);
+        let package = SongPackage::from_zip(&zip(
+            &[
+                ("grievouslady/", b""),
+                ("grievouslady/2.aff", &chart),
+                ("grievouslady/base.ogg", b"OggS"),
+                ("__MACOSX/grievouslady/._base.ogg", b"junk"),
+            ],
+            true,
+        ))
+        .unwrap();
+        assert_eq!(package.folder.as_deref(), Some("grievouslady"));
+        assert_eq!(
+            package.files,
+            vec![
+                ("2.aff".to_string(), chart.clone()),
+                ("base.ogg".to_string(), b"OggS".to_vec()),
+            ]
+        );
+
+        let root =
+            SongPackage::from_zip(&zip(&[("0.aff", b"a"), ("base.ogg", b"b")], false)).unwrap();
+        assert_eq!(root.folder, None);
+        assert_eq!(root.files.len(), 2);
+    }
+
+    #[test]
+    fn test_song_package_rejects_invalid_zips() {
+        let invalid: [&[(&str, &[u8])]; 5] = [
+            &[("0.aff", b"a")],
+            &[("base.ogg", b"b")],
+            &[("0.aff", b"a"), ("base.ogg", b"b"), ("run.sh", b"c")],
+            &[("a/0.aff", b"a"), ("b/base.ogg", b"b")],
+            &[("a/b/0.aff", b"a"), ("a/b/base.ogg", b"b")],
+        ];
+        for entries in invalid {
+            assert!(SongPackage::from_zip(&zip(entries, false)).is_err());
+        }
+        assert!(SongPackage::from_zip(b"not a zip at all, just some bytes").is_err());
+
+        let mut corrupted = zip(&[("0.aff", b"chart"), ("base.ogg", b"audio")], false);
+        corrupted[30 + "0.aff".len()] ^= 0xff;
+        assert!(SongPackage::from_zip(&corrupted).is_err());
+    }
+}
@@ -0,0 +1,208 @@
+use crate::error::{ArcError, ArcResult};
+use crate::service::game_constants::game_constants;
+use crate::service::user_cache::UserCache;
+use crate::DbPool;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Stamina granted by one fragment or ticket stamina purchase.
+pub const STAMINA_PURCHASE_AMOUNT: i32 = 6;
+
+/// Stamina calculation logic
+#[derive(Debug, Clone)]
+pub struct StaminaImpl {
+    stamina: i32,
+    max_stamina_ts: i64,
+}
+
+impl StaminaImpl {
+    /// Create new stamina instance
+    pub fn new(stamina: i32, max_stamina_ts: i64) -> Self {
+        Self {
+            stamina: if stamina > 0 {
+                stamina
+            } else {
+                game_constants().max_stamina
+            },
+            max_stamina_ts: if max_stamina_ts > 0 {
+                max_stamina_ts
+            } else {
+                0
+            },
+        }
+    }
+
+    /// Get current stamina value based on time calculation
+    pub fn get_current_stamina(&self) -> i32 {
+        let constants = game_constants();
+        let current_time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as i64;
+
+        let recovery_ticks =
+            (self.max_stamina_ts - current_time) as f64 / constants.stamina_recover_tick as f64;
+        let calculated_stamina = constants.max_stamina - recovery_ticks.round() as i32;
+
+        if calculated_stamina >= constants.max_stamina {
+            if self.stamina >= constants.max_stamina {
+                self.stamina
+            } else {
+                constants.max_stamina
+            }
+        } else {
+            calculated_stamina
+        }
+    }
+
+    pub fn max_stamina_ts(&self) -> i64 {
+        self.max_stamina_ts
+    }
+
+    /// Whole seconds until stamina has recovered to `amount`, 0 if it already has
+    pub fn seconds_until(&self, amount: i32) -> i64 {
+        let constants = game_constants();
+        let current_time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as i64;
+        let reached_at = self.max_stamina_ts
+            - (constants.max_stamina - amount) as i64 * constants.stamina_recover_tick;
+        ((reached_at - current_time).max(0) + 999) / 1000
+    }
+
+    /// Set stamina value and update max_stamina_ts accordingly
+    pub fn set_stamina(&mut self, value: i32) {
+        self.stamina = value;
+        let constants = game_constants();
+        let current_time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as i64;
+        self.max_stamina_ts = current_time
+            - (self.stamina - constants.max_stamina) as i64 * constants.stamina_recover_tick;
+    }
+}
+
+/// Stamina reads and writes against the `user` row
+///
+/// Stamina is stored as the value at the last write plus `max_stamina_ts`,
+/// the time it will have regenerated back to `max_stamina`; every read goes
+/// through [`StaminaImpl`] so the regeneration since then is applied.
+#[derive(Debug, Clone)]
+pub struct StaminaService {
+    pool: DbPool,
+}
+
+impl StaminaService {
+    pub fn new(pool: DbPool) -> Self {
+        Self { pool }
+    }
+
+    /// Get the user's stamina with regeneration up to now applied
+    pub async fn get_stamina(&self, user_id: i32) -> ArcResult<StaminaImpl> {
+        let row = sqlx::query!(
+            "SELECT stamina, max_stamina_ts FROM user WHERE user_id = ?",
+            user_id
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or_else(|| ArcError::no_data("User not found.", 108))?;
+
+        Ok(StaminaImpl::new(
+            row.stamina.unwrap_or(0),
+            row.max_stamina_ts.unwrap_or(0),
+        ))
+    }
+
+    /// Add `amount` stamina, which may go past `max_stamina`
+    pub async fn add_stamina(&self, user_id: i32, amount: i32) -> ArcResult<StaminaImpl> {
+        let mut stamina = self.get_stamina(user_id).await?;
+        stamina.set_stamina(stamina.get_current_stamina() + amount);
+        self.save(user_id, &stamina).await?;
+        Ok(stamina)
+    }
+
+    /// Spend `cost` stamina, failing with the time until it has regenerated
+    /// when the user does not have enough
+    pub async fn consume_stamina(&self, user_id: i32, cost: i32) -> ArcResult<StaminaImpl> {
+        let mut stamina = self.get_stamina(user_id).await?;
+        let current = stamina.get_current_stamina();
+        if current < cost {
+            return Err(ArcError::stamina_not_enough(
+                cost,
+                current,
+                stamina.seconds_until(cost),
+            ));
+        }
+
+        stamina.set_stamina(current - cost);
+        self.save(user_id, &stamina).await?;
+        Ok(stamina)
+    }
+
+    /// Check that buying `amount` stamina keeps the user within
+    /// the `stamina_limit` game constant
+    pub async fn check_purchase(&self, user_id: i32, amount: i32) -> ArcResult<()> {
+        let stamina = self.get_stamina(user_id).await?;
+        check_purchase_limit(stamina.get_current_stamina(), amount)
+    }
+
+    async fn save(&self, user_id: i32, stamina: &StaminaImpl) -> ArcResult<()> {
+        sqlx::query!(
+            "UPDATE user SET stamina = ?, max_stamina_ts = ? WHERE user_id = ?",
+            stamina.get_current_stamina(),
+            stamina.max_stamina_ts(),
+            user_id
+        )
+        .execute(&self.pool)
+        .await?;
+        UserCache::global().invalidate_user(user_id);
+        Ok(())
+    }
+}
+
+fn check_purchase_limit(current: i32, amount: i32) -> ArcResult<()> {
+    if current + amount > game_constants().stamina_limit {
+        return Err(ArcError::item_unavailable(
+            "Stamina has reached its limit.",
+            309,
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_stamina_round_trips() {
+        let mut stamina = StaminaImpl::new(0, 0);
+        assert_eq!(stamina.get_current_stamina(), game_constants().max_stamina);
+
+        stamina.set_stamina(5);
+        assert_eq!(stamina.get_current_stamina(), 5);
+        assert!(stamina.seconds_until(6) > 0);
+        assert_eq!(stamina.seconds_until(5), 0);
+
+        // Overcap stamina is kept until it is spent.
+        stamina.set_stamina(game_constants().max_stamina + 6);
+        assert_eq!(
+            stamina.get_current_stamina(),
+            game_constants().max_stamina + 6
+        );
+    }
+
+    #[test]
+    fn test_check_purchase_limit() {
+        assert!(check_purchase_limit(0, STAMINA_PURCHASE_AMOUNT).is_ok());
+        assert!(
+            check_purchase_limit(game_constants().stamina_limit - STAMINA_PURCHASE_AMOUNT, 6)
+                .is_ok()
+        );
+
+        let err = check_purchase_limit(game_constants().stamina_limit - 1, STAMINA_PURCHASE_AMOUNT)
+            .unwrap_err();
+        assert_eq!(err.error_code(), 309);
+    }
+}
@@ -1,16 +1,21 @@
+use crate::config::Config;
 use crate::error::{ArcError, ArcResult};
 use crate::service::cache::{env_ttl_seconds, CacheService};
+use crate::service::runtime_assets::asset_dir;
+use async_trait::async_trait;
 use aws_config::{BehaviorVersion, Region};
 use aws_credential_types::Credentials;
 use aws_sdk_s3::presigning::PresigningConfig;
+use aws_sdk_s3::primitives::ByteStream;
 use aws_sdk_s3::Client;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::env;
+use std::path::{Component, Path, PathBuf};
 use std::sync::{Arc, RwLock};
 use std::time::Duration;
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum StorageBackend {
     Local,
     S3,
@@ -64,12 +69,126 @@ pub struct BundleFileMeta {
     pub bundle_size: u64,
     pub json_key: String,
     pub bundle_key: String,
+    #[serde(default)]
+    pub added_files: Vec<String>,
+    #[serde(default)]
+    pub removed_files: Vec<String>,
+}
+
+/// Where song files, content bundles and other objects are kept
+///
+/// Listing methods return `None` on backends without a manifest; callers then
+/// scan the local asset folders themselves and serve files through this server.
+#[async_trait]
+pub trait AssetStorage: Send + Sync {
+    fn backend(&self) -> StorageBackend;
+
+    /// Reload the object listing, if the backend keeps one
+    async fn refresh_manifest(&self) -> ArcResult<()>;
+
+    /// Sorted ids of every song with stored files
+    fn song_ids(&self) -> Option<Vec<String>>;
+
+    /// Sorted file names stored for a song
+    fn song_file_names(&self, song_id: &str) -> Option<Vec<String>>;
+
+    fn song_file(&self, song_id: &str, file_name: &str) -> Option<SongFileMeta>;
+
+    fn bundle_entries(&self) -> Option<Vec<BundleFileMeta>>;
+
+    /// Time-limited download URL for `key`, `None` when files are served by
+    /// this server instead
+    async fn presign_get(&self, key: &str) -> ArcResult<Option<String>>;
+
+    /// Lifetime of URLs returned by [`AssetStorage::presign_get`] in seconds
+    fn presign_expires_seconds(&self) -> u64;
+
+    async fn put_object(&self, key: &str, bytes: Vec<u8>) -> ArcResult<()>;
+
+    async fn get_object(&self, key: &str) -> ArcResult<Vec<u8>>;
+}
+
+/// Assets on the local disk below a root folder
+#[derive(Debug, Clone)]
+pub struct LocalStorage {
+    root: PathBuf,
 }
 
+impl LocalStorage {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    /// Path of `key` below the root, rejecting keys that would escape it
+    fn object_path(&self, key: &str) -> ArcResult<PathBuf> {
+        let relative = Path::new(key);
+        if key.is_empty()
+            || relative
+                .components()
+                .any(|component| !matches!(component, Component::Normal(_)))
+        {
+            return Err(ArcError::input(format!("Invalid storage key `{key}`")));
+        }
+        Ok(self.root.join(relative))
+    }
+}
+
+#[async_trait]
+impl AssetStorage for LocalStorage {
+    fn backend(&self) -> StorageBackend {
+        StorageBackend::Local
+    }
+
+    async fn refresh_manifest(&self) -> ArcResult<()> {
+        Ok(())
+    }
+
+    fn song_ids(&self) -> Option<Vec<String>> {
+        None
+    }
+
+    fn song_file_names(&self, _song_id: &str) -> Option<Vec<String>> {
+        None
+    }
+
+    fn song_file(&self, _song_id: &str, _file_name: &str) -> Option<SongFileMeta> {
+        None
+    }
+
+    fn bundle_entries(&self) -> Option<Vec<BundleFileMeta>> {
+        None
+    }
+
+    async fn presign_get(&self, _key: &str) -> ArcResult<Option<String>> {
+        Ok(None)
+    }
+
+    fn presign_expires_seconds(&self) -> u64 {
+        0
+    }
+
+    async fn put_object(&self, key: &str, bytes: Vec<u8>) -> ArcResult<()> {
+        let path = self.object_path(key)?;
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        tokio::fs::write(&path, bytes).await?;
+        Ok(())
+    }
+
+    async fn get_object(&self, key: &str) -> ArcResult<Vec<u8>> {
+        let path = self.object_path(key)?;
+        tokio::fs::read(&path)
+            .await
+            .map_err(|e| ArcError::no_data(format!("Failed to read `{key}`: {e}"), 404))
+    }
+}
+
+/// Asset storage used by the download, bundle and replay services, with
+/// presigned URLs cached in Redis when available
 #[derive(Clone)]
 pub struct StorageService {
-    config: StorageConfig,
-    s3: Option<S3Storage>,
+    backend: Arc<dyn AssetStorage>,
     cache: Option<CacheService>,
     presign_cache_ttl_seconds: u64,
 }
@@ -77,28 +196,23 @@ pub struct StorageService {
 impl std::fmt::Debug for StorageService {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("StorageService")
-            .field("backend", &self.config.backend)
-            .field("has_s3", &self.s3.is_some())
+            .field("backend", &self.backend.backend())
             .field("has_cache", &self.cache.is_some())
             .finish()
     }
 }
 
 #[derive(Clone)]
-struct S3Storage {
+pub struct S3Storage {
     config: S3StorageConfig,
     client: Client,
     manifest: Arc<RwLock<StorageManifest>>,
 }
 
 impl StorageConfig {
-    pub fn from_env() -> ArcResult<Self> {
-        let backend = match env::var("STORAGE_BACKEND")
-            .unwrap_or_else(|_| "local".to_string())
-            .trim()
-            .to_ascii_lowercase()
-            .as_str()
-        {
+    /// Read the `storage_backend` and `s3_*` settings
+    pub fn from_config(config: &Config) -> ArcResult<Self> {
+        let backend = match config.storage_backend.trim().to_ascii_lowercase().as_str() {
             "" | "local" => StorageBackend::Local,
             "s3" => StorageBackend::S3,
             other => {
@@ -109,7 +223,7 @@ impl StorageConfig {
         };
 
         let s3 = if backend == StorageBackend::S3 {
-            Some(S3StorageConfig::from_env()?)
+            Some(S3StorageConfig::from_config(config)?)
         } else {
             None
         };
@@ -119,59 +233,50 @@ impl StorageConfig {
 }
 
 impl S3StorageConfig {
-    fn from_env() -> ArcResult<Self> {
-        let bucket = required_env("S3_BUCKET")?;
-        let access_key_id = env::var("S3_ACCESS_KEY_ID")
-            .or_else(|_| env::var("AWS_ACCESS_KEY_ID"))
-            .map_err(|_| ArcError::input("S3_ACCESS_KEY_ID or AWS_ACCESS_KEY_ID is required"))?;
-        let secret_access_key = env::var("S3_SECRET_ACCESS_KEY")
-            .or_else(|_| env::var("AWS_SECRET_ACCESS_KEY"))
-            .map_err(|_| {
+    fn from_config(config: &Config) -> ArcResult<Self> {
+        let bucket =
+            non_empty(&config.s3_bucket).ok_or_else(|| ArcError::input("S3_BUCKET is required"))?;
+        let access_key_id = non_empty(&config.s3_access_key_id)
+            .or_else(|| env::var("AWS_ACCESS_KEY_ID").ok())
+            .ok_or_else(|| ArcError::input("S3_ACCESS_KEY_ID or AWS_ACCESS_KEY_ID is required"))?;
+        let secret_access_key = non_empty(&config.s3_secret_access_key)
+            .or_else(|| env::var("AWS_SECRET_ACCESS_KEY").ok())
+            .ok_or_else(|| {
                 ArcError::input("S3_SECRET_ACCESS_KEY or AWS_SECRET_ACCESS_KEY is required")
             })?;
 
         Ok(Self {
-            endpoint: env::var("S3_ENDPOINT")
-                .ok()
-                .filter(|value| !value.trim().is_empty()),
-            region: env::var("S3_REGION")
-                .ok()
-                .filter(|value| !value.trim().is_empty())
-                .unwrap_or_else(|| "us-east-1".to_string()),
+            endpoint: config.s3_endpoint.as_deref().and_then(non_empty),
+            region: non_empty(&config.s3_region).unwrap_or_else(|| "us-east-1".to_string()),
             bucket,
             access_key_id,
             secret_access_key,
-            force_path_style: env_bool("S3_FORCE_PATH_STYLE", false),
-            manifest_key: env::var("S3_MANIFEST_KEY")
-                .ok()
-                .filter(|value| !value.trim().is_empty())
+            force_path_style: config.s3_force_path_style,
+            manifest_key: non_empty(&config.s3_manifest_key)
                 .unwrap_or_else(|| "manifest.json".to_string()),
-            presign_expires_seconds: env::var("S3_PRESIGN_EXPIRES_SECONDS")
-                .ok()
-                .and_then(|value| value.parse().ok())
-                .unwrap_or(3600),
+            presign_expires_seconds: config.s3_presign_expires_seconds,
         })
     }
 }
 
 impl StorageService {
-    pub async fn from_env() -> ArcResult<Self> {
-        let config = StorageConfig::from_env()?;
-        Self::from_config(config).await
-    }
-
-    async fn from_config(config: StorageConfig) -> ArcResult<Self> {
-        let s3 = match config.s3.clone() {
-            Some(s3_config) => Some(S3Storage::new(s3_config).await?),
-            None => None,
+    /// Build the backend selected by `storage_backend`; the local backend is
+    /// rooted at the runtime asset folder
+    pub async fn from_config(config: &Config) -> ArcResult<Self> {
+        let storage_config = StorageConfig::from_config(config)?;
+        let backend: Arc<dyn AssetStorage> = match storage_config.s3 {
+            Some(s3_config) => Arc::new(S3Storage::new(s3_config).await?),
+            None => Arc::new(LocalStorage::new(asset_dir())),
         };
+        Ok(Self::new(backend))
+    }
 
-        Ok(Self {
-            config,
-            s3,
+    pub fn new(backend: Arc<dyn AssetStorage>) -> Self {
+        Self {
+            backend,
             cache: None,
             presign_cache_ttl_seconds: env_ttl_seconds("REDIS_PRESIGN_TTL_SECONDS", 300),
-        })
+        }
     }
 
     pub fn with_cache(mut self, cache: Option<CacheService>) -> Self {
@@ -184,54 +289,34 @@ impl StorageService {
     }
 
     pub fn presign_cache_ttl_seconds(&self) -> u64 {
-        let Some(s3) = &self.s3 else {
-            return 0;
-        };
-
         self.presign_cache_ttl_seconds
-            .min(s3.config.presign_expires_seconds.saturating_sub(60))
+            .min(self.backend.presign_expires_seconds().saturating_sub(60))
     }
 
     pub fn is_s3(&self) -> bool {
-        self.config.backend == StorageBackend::S3
+        self.backend.backend() == StorageBackend::S3
     }
 
     pub async fn refresh_manifest(&self) -> ArcResult<()> {
-        if let Some(s3) = &self.s3 {
-            s3.refresh_manifest().await?;
-        }
-        Ok(())
+        self.backend.refresh_manifest().await
     }
 
     pub fn all_song_ids(&self) -> Option<Vec<String>> {
-        let s3 = self.s3.as_ref()?;
-        let manifest = s3.manifest.read().unwrap();
-        let mut song_ids: Vec<String> = manifest.songs.keys().cloned().collect();
-        song_ids.sort();
-        Some(song_ids)
+        self.backend.song_ids()
     }
 
     pub fn song_file_names(&self, song_id: &str) -> Option<Vec<String>> {
-        let s3 = self.s3.as_ref()?;
-        let manifest = s3.manifest.read().unwrap();
-        let files = manifest.songs.get(song_id)?;
-        let mut file_names: Vec<String> = files.keys().cloned().collect();
-        file_names.sort();
-        Some(file_names)
+        self.backend.song_file_names(song_id)
     }
 
     pub fn song_file_md5(&self, song_id: &str, file_name: &str) -> Option<String> {
-        let s3 = self.s3.as_ref()?;
-        let manifest = s3.manifest.read().unwrap();
-        manifest
-            .songs
-            .get(song_id)
-            .and_then(|files| files.get(file_name))
-            .and_then(|file| file.md5.clone())
+        self.backend
+            .song_file(song_id, file_name)
+            .and_then(|file| file.md5)
     }
 
     pub async fn presign_song(&self, song_id: &str, file_name: &str) -> ArcResult<Option<String>> {
-        let Some(s3) = &self.s3 else {
+        let Some(file) = self.backend.song_file(song_id, file_name) else {
             return Ok(None);
         };
 
@@ -242,52 +327,40 @@ impl StorageService {
             }
         }
 
-        let key = {
-            let manifest = s3.manifest.read().unwrap();
-            manifest
-                .songs
-                .get(song_id)
-                .and_then(|files| files.get(file_name))
-                .map(|file| file.key.clone())
-        };
-
-        match key {
-            Some(key) => {
-                let url = s3.presign_get(&key).await?;
-                if let Some(cache) = &self.cache {
-                    cache
-                        .set_string(&cache_key, &url, self.presign_cache_ttl_seconds())
-                        .await;
-                }
-                Ok(Some(url))
-            }
-            None => Ok(None),
+        let url = self.backend.presign_get(&file.key).await?;
+        if let (Some(cache), Some(url)) = (&self.cache, &url) {
+            cache
+                .set_string(&cache_key, url, self.presign_cache_ttl_seconds())
+                .await;
         }
+        Ok(url)
     }
 
     pub fn bundle_entries(&self) -> Option<Vec<BundleFileMeta>> {
-        let s3 = self.s3.as_ref()?;
-        let manifest = s3.manifest.read().unwrap();
-        Some(manifest.bundles.clone())
+        self.backend.bundle_entries()
     }
 
     pub async fn presign_bundle_json(&self, bundle: &BundleFileMeta) -> ArcResult<Option<String>> {
-        let Some(s3) = &self.s3 else {
-            return Ok(None);
-        };
-        Ok(Some(s3.presign_get(&bundle.json_key).await?))
+        self.backend.presign_get(&bundle.json_key).await
     }
 
     pub async fn presign_bundle_file(&self, bundle: &BundleFileMeta) -> ArcResult<Option<String>> {
-        let Some(s3) = &self.s3 else {
-            return Ok(None);
-        };
-        Ok(Some(s3.presign_get(&bundle.bundle_key).await?))
+        self.backend.presign_get(&bundle.bundle_key).await
+    }
+
+    /// Store `bytes` under `key`
+    pub async fn put_object(&self, key: &str, bytes: Vec<u8>) -> ArcResult<()> {
+        self.backend.put_object(key, bytes).await
+    }
+
+    /// Read the object stored under `key`
+    pub async fn get_object(&self, key: &str) -> ArcResult<Vec<u8>> {
+        self.backend.get_object(key).await
     }
 }
 
 impl S3Storage {
-    async fn new(config: S3StorageConfig) -> ArcResult<Self> {
+    pub async fn new(config: S3StorageConfig) -> ArcResult<Self> {
         let credentials = Credentials::new(
             config.access_key_id.clone(),
             config.secret_access_key.clone(),
@@ -317,6 +390,13 @@ impl S3Storage {
         storage.refresh_manifest().await?;
         Ok(storage)
     }
+}
+
+#[async_trait]
+impl AssetStorage for S3Storage {
+    fn backend(&self) -> StorageBackend {
+        StorageBackend::S3
+    }
 
     async fn refresh_manifest(&self) -> ArcResult<()> {
         log::info!(
@@ -350,7 +430,65 @@ impl S3Storage {
         Ok(())
     }
 
-    async fn presign_get(&self, key: &str) -> ArcResult<String> {
+    fn song_ids(&self) -> Option<Vec<String>> {
+        let manifest = self.manifest.read().unwrap();
+        let mut song_ids: Vec<String> = manifest.songs.keys().cloned().collect();
+        song_ids.sort();
+        Some(song_ids)
+    }
+
+    fn song_file_names(&self, song_id: &str) -> Option<Vec<String>> {
+        let manifest = self.manifest.read().unwrap();
+        let mut file_names: Vec<String> = manifest.songs.get(song_id)?.keys().cloned().collect();
+        file_names.sort();
+        Some(file_names)
+    }
+
+    fn song_file(&self, song_id: &str, file_name: &str) -> Option<SongFileMeta> {
+        let manifest = self.manifest.read().unwrap();
+        manifest.songs.get(song_id)?.get(file_name).cloned()
+    }
+
+    fn bundle_entries(&self) -> Option<Vec<BundleFileMeta>> {
+        Some(self.manifest.read().unwrap().bundles.clone())
+    }
+
+    fn presign_expires_seconds(&self) -> u64 {
+        self.config.presign_expires_seconds
+    }
+
+    async fn put_object(&self, key: &str, bytes: Vec<u8>) -> ArcResult<()> {
+        self.client
+            .put_object()
+            .bucket(&self.config.bucket)
+            .key(key)
+            .body(ByteStream::from(bytes))
+            .send()
+            .await
+            .map_err(|e| ArcError::input(format!("Failed to upload S3 object `{key}`: {e}")))?;
+        Ok(())
+    }
+
+    async fn get_object(&self, key: &str) -> ArcResult<Vec<u8>> {
+        let output = self
+            .client
+            .get_object()
+            .bucket(&self.config.bucket)
+            .key(key)
+            .send()
+            .await
+            .map_err(|e| ArcError::input(format!("Failed to fetch S3 object `{key}`: {e}")))?;
+
+        let bytes = output
+            .body
+            .collect()
+            .await
+            .map_err(|e| ArcError::input(format!("Failed to read S3 object `{key}`: {e}")))?
+            .into_bytes();
+        Ok(bytes.to_vec())
+    }
+
+    async fn presign_get(&self, key: &str) -> ArcResult<Option<String>> {
         let config =
             PresigningConfig::expires_in(Duration::from_secs(self.config.presign_expires_seconds))
                 .map_err(|e| ArcError::input(format!("Invalid S3 presign expiry: {e}")))?;
@@ -364,23 +502,37 @@ impl S3Storage {
             .await
             .map_err(|e| ArcError::input(format!("Failed to presign S3 object `{key}`: {e}")))?;
 
-        Ok(request.uri().to_string())
+        Ok(Some(request.uri().to_string()))
     }
 }
 
-fn required_env(key: &str) -> ArcResult<String> {
-    env::var(key)
-        .ok()
-        .filter(|value| !value.trim().is_empty())
-        .ok_or_else(|| ArcError::input(format!("{key} is required")))
+fn non_empty(value: &str) -> Option<String> {
+    let trimmed = value.trim();
+    (!trimmed.is_empty()).then(|| trimmed.to_string())
 }
 
-fn env_bool(key: &str, default: bool) -> bool {
-    match env::var(key) {
-        Ok(value) => matches!(
-            value.trim().to_ascii_lowercase().as_str(),
-            "1" | "true" | "yes" | "on"
-        ),
-        Err(_) => default,
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_local_storage_round_trip() {
+        let root = std::env::temp_dir().join(format!("arc-storage-{}", rand::random::<u64>()));
+        let storage = LocalStorage::new(&root);
+
+        storage
+            .put_object("replays/1/a.bin", b"replay".to_vec())
+            .await
+            .unwrap();
+        assert_eq!(
+            storage.get_object("replays/1/a.bin").await.unwrap(),
+            b"replay"
+        );
+        assert!(storage.get_object("replays/1/missing.bin").await.is_err());
+        assert!(storage.put_object("../escape", Vec::new()).await.is_err());
+        assert!(storage.get_object("/etc/passwd").await.is_err());
+        assert_eq!(storage.presign_get("replays/1/a.bin").await.unwrap(), None);
+
+        tokio::fs::remove_dir_all(&root).await.unwrap();
     }
 }
@@ -0,0 +1,141 @@
+use crate::config::CONFIG;
+use crate::error::{ArcError, ArcResult};
+use crate::DbPool;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+/// Terms-of-service acceptance state for one user.
+#[derive(Debug, Clone, Serialize)]
+pub struct TosStatus {
+    pub version: String,
+    pub url: String,
+    pub accepted_version: Option<String>,
+    pub accepted_at: Option<i64>,
+    pub required: bool,
+}
+
+/// Tracks which terms-of-service version each user accepted.
+///
+/// Tracking is disabled while `tos_version` is empty. Users who accepted the
+/// current version are remembered in memory so the per-request check stays
+/// off the database.
+#[derive(Debug, Clone)]
+pub struct TosService {
+    pool: DbPool,
+    current_version: String,
+    url: String,
+    accepted: Arc<RwLock<HashMap<i32, String>>>,
+}
+
+impl TosService {
+    pub fn new(pool: DbPool) -> Self {
+        Self {
+            pool,
+            current_version: CONFIG.tos_version.trim().to_string(),
+            url: CONFIG.tos_url.trim().to_string(),
+            accepted: Arc::new(RwLock::new(HashMap::new())),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.current_version.is_empty()
+    }
+
+    pub fn current_version(&self) -> &str {
+        &self.current_version
+    }
+
+    /// Get the latest accepted version and whether re-acceptance is needed.
+    pub async fn get_status(&self, user_id: i32) -> ArcResult<TosStatus> {
+        let latest = sqlx::query!(
+            "SELECT version, accepted_at FROM user_tos_acceptance
+             WHERE user_id = ? ORDER BY accepted_at DESC LIMIT 1",
+            user_id
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        let (accepted_version, accepted_at) = match latest {
+            Some(row) => (Some(row.version), Some(row.accepted_at)),
+            None => (None, None),
+        };
+        let required =
+            self.is_enabled() && accepted_version.as_deref() != Some(self.current_version.as_str());
+
+        Ok(TosStatus {
+            version: self.current_version.clone(),
+            url: self.url.clone(),
+            accepted_version,
+            accepted_at,
+            required,
+        })
+    }
+
+    /// Record acceptance of `version`, which must be the current version.
+    pub async fn accept(&self, user_id: i32, version: &str, ip: Option<&str>) -> ArcResult<()> {
+        if !self.is_enabled() {
+            return Err(ArcError::no_data(
+                "Terms of service are not configured.",
+                108,
+            ));
+        }
+        if version.trim() != self.current_version {
+            return Err(ArcError::input(format!(
+                "Terms of service version `{version}` is not the current version."
+            )));
+        }
+
+        let now = chrono::Utc::now().timestamp_millis();
+        sqlx::query!(
+            "INSERT INTO user_tos_acceptance (user_id, version, accepted_at, ip)
+             VALUES (?, ?, ?, ?)
+             ON DUPLICATE KEY UPDATE accepted_at = VALUES(accepted_at), ip = VALUES(ip)",
+            user_id,
+            self.current_version,
+            now,
+            ip
+        )
+        .execute(&self.pool)
+        .await?;
+
+        self.accepted
+            .write()
+            .await
+            .insert(user_id, self.current_version.clone());
+        Ok(())
+    }
+
+    /// Fail with a `NoAccess` error when the user still has to accept the
+    /// current version.
+    pub async fn ensure_accepted(&self, user_id: i32) -> ArcResult<()> {
+        if !self.is_enabled() {
+            return Ok(());
+        }
+        if self.accepted.read().await.get(&user_id) == Some(&self.current_version) {
+            return Ok(());
+        }
+
+        let accepted = sqlx::query_scalar!(
+            "SELECT EXISTS(SELECT 1 FROM user_tos_acceptance WHERE user_id = ? AND version = ?) as `exists!: i64`",
+            user_id,
+            self.current_version
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        if accepted == 0 {
+            return Err(ArcError::tos_required(
+                self.current_version.clone(),
+                self.url.clone(),
+            ));
+        }
+
+        self.accepted
+            .write()
+            .await
+            .insert(user_id, self.current_version.clone());
+        Ok(())
+    }
+}
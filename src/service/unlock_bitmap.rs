@@ -0,0 +1,196 @@
+//! Link play `song_unlock` bitmaps: five bits per song, one per rating
+//! class, starting at bit `idx * 5` for the song's songlist `idx`.
+//!
+//! Clients send their own bitmap as `clientSongMap`; the bitmap computed here
+//! from the player's packs, singles and world songs is what the server thinks
+//! they can play.
+
+use crate::model::user::UserInfo;
+use crate::service::asset_manager::AssetManager;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Bytes in a `song_unlock` bitmap
+pub const LINKPLAY_UNLOCK_LENGTH: usize = 1024;
+
+/// A chart named by its songlist index and rating class
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct UnlockChart {
+    pub song_idx: usize,
+    pub rating_class: usize,
+    pub song_id: Option<String>,
+}
+
+/// Server-side `song_unlock` of a player, compared against the client's
+/// when one is given
+#[derive(Debug, Clone, Serialize)]
+pub struct SongUnlockReport {
+    /// Without a songlist nothing can be computed and the other fields are
+    /// empty
+    pub has_songlist: bool,
+    /// Base64 bitmap, as sent to the link play server
+    pub song_unlock: Option<String>,
+    pub chart_count: u32,
+    /// Charts the client claims but the server does not grant
+    pub client_only: Option<Vec<UnlockChart>>,
+    /// Charts the server grants but the client left out
+    pub server_only: Option<Vec<UnlockChart>>,
+}
+
+/// Computes `song_unlock` bitmaps from the songlist and a player's unlocks
+#[derive(Clone)]
+pub struct UnlockBitmapService {
+    asset_manager: Arc<AssetManager>,
+}
+
+impl UnlockBitmapService {
+    pub fn new(asset_manager: Arc<AssetManager>) -> Self {
+        Self { asset_manager }
+    }
+
+    /// Bitmap of the charts `user` can play, `None` without a songlist
+    pub fn song_unlock(&self, user: &UserInfo) -> Option<Vec<u8>> {
+        self.asset_manager
+            .song_unlock_bitmap(user, LINKPLAY_UNLOCK_LENGTH)
+    }
+
+    /// The bitmap of `user`, with the charts on which it differs from
+    /// `client_song_map` when that is given
+    pub fn report(
+        &self,
+        user: &UserInfo,
+        client_song_map: Option<&HashMap<String, Vec<bool>>>,
+    ) -> SongUnlockReport {
+        let Some(server) = self.song_unlock(user) else {
+            return SongUnlockReport {
+                has_songlist: false,
+                song_unlock: None,
+                chart_count: 0,
+                client_only: None,
+                server_only: None,
+            };
+        };
+
+        let (client_only, server_only) = match client_song_map {
+            Some(client_song_map) => {
+                let client = client_song_unlock(client_song_map);
+                (
+                    Some(self.named_charts(&bitmap_difference(&client, &server))),
+                    Some(self.named_charts(&bitmap_difference(&server, &client))),
+                )
+            }
+            None => (None, None),
+        };
+        SongUnlockReport {
+            has_songlist: true,
+            song_unlock: Some(BASE64.encode(&server)),
+            chart_count: chart_count(&server),
+            client_only,
+            server_only,
+        }
+    }
+
+    fn named_charts(&self, bitmap: &[u8]) -> Vec<UnlockChart> {
+        bitmap_charts(bitmap)
+            .into_iter()
+            .map(|(song_idx, rating_class)| UnlockChart {
+                song_idx,
+                rating_class,
+                song_id: self.asset_manager.song_id_by_index(song_idx),
+            })
+            .collect()
+    }
+}
+
+/// Set the bit of one chart; charts past the end of the bitmap are dropped
+pub fn set_chart_bit(bitmap: &mut [u8], song_idx: usize, rating_class: usize) {
+    let index = song_idx * 5 + rating_class;
+    if let Some(byte) = bitmap.get_mut(index / 8) {
+        *byte |= 1 << (index % 8);
+    }
+}
+
+/// Bitmap of a client's `clientSongMap`: song index -> unlocked rating classes
+pub fn client_song_unlock(client_song_map: &HashMap<String, Vec<bool>>) -> Vec<u8> {
+    let mut unlock = vec![0u8; LINKPLAY_UNLOCK_LENGTH];
+    for (k, v) in client_song_map {
+        let song_idx = match k.parse::<usize>() {
+            Ok(n) => n,
+            Err(_) => continue,
+        };
+        for rating_class in 0..5usize {
+            if v.get(rating_class).copied().unwrap_or(false) {
+                set_chart_bit(&mut unlock, song_idx, rating_class);
+            }
+        }
+    }
+    unlock
+}
+
+/// Number of charts set in a bitmap
+pub fn chart_count(bitmap: &[u8]) -> u32 {
+    bitmap.iter().map(|byte| byte.count_ones()).sum()
+}
+
+/// Charts set in `a` but not in `b`
+fn bitmap_difference(a: &[u8], b: &[u8]) -> Vec<u8> {
+    a.iter()
+        .enumerate()
+        .map(|(i, x)| x & !b.get(i).copied().unwrap_or(0))
+        .collect()
+}
+
+/// `(song_idx, rating_class)` of every chart set in a bitmap
+fn bitmap_charts(bitmap: &[u8]) -> Vec<(usize, usize)> {
+    bitmap
+        .iter()
+        .enumerate()
+        .flat_map(|(byte_index, byte)| {
+            (0..8)
+                .filter(move |bit| byte & (1 << bit) != 0)
+                .map(move |bit| byte_index * 8 + bit)
+        })
+        .map(|index| (index / 5, index % 5))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_client_song_unlock() {
+        let client_song_map = HashMap::from([
+            ("0".to_string(), vec![true, true, true, false, false]),
+            ("3".to_string(), vec![false, false, false, true]),
+            ("song".to_string(), vec![true]),
+        ]);
+        let unlock = client_song_unlock(&client_song_map);
+        assert_eq!(unlock.len(), LINKPLAY_UNLOCK_LENGTH);
+        assert_eq!(chart_count(&unlock), 4);
+        assert_eq!(bitmap_charts(&unlock), vec![(0, 0), (0, 1), (0, 2), (3, 3)]);
+    }
+
+    #[test]
+    fn test_bitmap_difference() {
+        let mut server = vec![0u8; 4];
+        let mut client = vec![0u8; 4];
+        set_chart_bit(&mut server, 1, 2);
+        set_chart_bit(&mut server, 2, 3);
+        set_chart_bit(&mut client, 2, 3);
+        set_chart_bit(&mut client, 5, 0);
+        set_chart_bit(&mut client, 100, 0);
+
+        assert_eq!(
+            bitmap_charts(&bitmap_difference(&server, &client)),
+            vec![(1, 2)]
+        );
+        assert_eq!(
+            bitmap_charts(&bitmap_difference(&client, &server)),
+            vec![(5, 0)]
+        );
+    }
+}
@@ -2,24 +2,60 @@ use crate::config::{Constants, CONFIG};
 use crate::error::{ArcError, ArcResult};
 use crate::model::user::{UserCoreInfo, UserRecentScore};
 use crate::model::{
-    UpdateCharacter, User, UserAuth, UserCodeMapping, UserCredentials, UserExists, UserInfo,
-    UserLoginDevice, UserLoginDto, UserRegisterDto,
+    AccountDeletionRequest, ImpersonationToken, UpdateCharacter, User, UserAuth, UserCodeMapping,
+    UserCredentials, UserDevice, UserExists, UserInfo, UserLoginDevice, UserLoginDto,
+    UserLoginSession, UserRegisterDto,
 };
 use crate::service::cache::{env_ttl_seconds, CacheService};
+use crate::service::login_bonus::LoginBonusService;
+use crate::service::password::{upgrade_password_hash, PasswordCheck, PasswordHasher};
 use crate::service::score::ScoreService;
-use crate::service::world::StaminaImpl;
+use crate::service::stamina::StaminaService;
+use crate::service::user_cache::UserCache;
+use crate::service::user_export::UserExportService;
+use crate::service::webhook::{WebhookEvent, WebhookNotifier};
 use crate::service::CharacterService;
+use crate::DbPool;
 use base64::{engine::general_purpose, Engine as _};
 use rand::Rng;
 use serde_json::Value;
 use sha2::{Digest, Sha256};
-use sqlx::{MySql, Pool};
-use std::collections::HashMap;
+use std::path::PathBuf;
 use std::time::{SystemTime, UNIX_EPOCH};
 
+/// Fingerprint kind for the login `DeviceId` header.
+pub const FINGERPRINT_DEVICE_ID: &str = "device_id";
+/// Fingerprint kind for the install id stored in cloud saves.
+pub const FINGERPRINT_INSTALL_ID: &str = "install_id";
+
+/// Lifetime of an admin impersonation token, in milliseconds.
+pub const IMPERSONATION_TOKEN_TTL_MS: i64 = 30 * 60 * 1000;
+
+/// Insight progression step reported by the client.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InsightStep {
+    /// Eden Append 1 completed; grants Insight (Ascendant - 8th Seeker).
+    Unlock,
+    /// Lephon ascent completed; invasion becomes available.
+    Ascend,
+    /// Cycle through the invasion toggle states.
+    Toggle,
+}
+
+impl InsightStep {
+    /// Step completed by clearing the story of `pack_id`.
+    pub fn from_pack_id(pack_id: &str) -> Option<Self> {
+        match pack_id {
+            "eden_append_1" => Some(Self::Unlock),
+            "lephon" => Some(Self::Ascend),
+            _ => None,
+        }
+    }
+}
+
 /// User service for handling user operations
 pub struct UserService {
-    pool: Pool<MySql>,
+    pool: DbPool,
     character_service: CharacterService,
     cache: Option<CacheService>,
     auth_cache_ttl_seconds: u64,
@@ -28,6 +64,9 @@ pub struct UserService {
     user_detail_cache_ttl_seconds: u64,
     global_rank_cache_ttl_seconds: u64,
     zset_cache_ttl_seconds: u64,
+    user_cache: UserCache,
+    login_bonus: Option<LoginBonusService>,
+    webhook: WebhookNotifier,
 }
 
 struct FriendListRow {
@@ -61,7 +100,7 @@ struct FriendListRow {
 
 impl UserService {
     /// Create a new user service instance
-    pub fn new(pool: Pool<MySql>) -> Self {
+    pub fn new(pool: DbPool) -> Self {
         let character_service = CharacterService::new(pool.clone());
         Self {
             pool,
@@ -73,6 +112,9 @@ impl UserService {
             user_detail_cache_ttl_seconds: env_ttl_seconds("REDIS_USER_DETAIL_TTL_SECONDS", 15),
             global_rank_cache_ttl_seconds: env_ttl_seconds("REDIS_GLOBAL_RANK_TTL_SECONDS", 10),
             zset_cache_ttl_seconds: env_ttl_seconds("REDIS_ZSET_RANK_TTL_SECONDS", 300),
+            user_cache: UserCache::global().clone(),
+            login_bonus: None,
+            webhook: WebhookNotifier::disabled(),
         }
     }
 
@@ -81,6 +123,17 @@ impl UserService {
         self
     }
 
+    pub fn with_login_bonus(mut self, login_bonus: LoginBonusService) -> Self {
+        self.login_bonus = Some(login_bonus);
+        self
+    }
+
+    /// Report new accounts as `registration` events
+    pub fn with_webhook(mut self, webhook: WebhookNotifier) -> Self {
+        self.webhook = webhook;
+        self
+    }
+
     fn auth_token_key(token: &str) -> String {
         format!("auth:token:{token}")
     }
@@ -120,7 +173,8 @@ impl UserService {
             }
 
             let member = user_id.to_string();
-            if world_rank_score > 0 {
+            let shadow_banned = matches!(self.is_shadow_banned(user_id).await, Ok(true));
+            if world_rank_score > 0 && !shadow_banned {
                 cache
                     .zadd_f64(
                         Self::global_rank_zset_key(),
@@ -150,10 +204,12 @@ impl UserService {
             return Ok(true);
         }
 
-        let rows =
-            sqlx::query!("SELECT user_id, world_rank_score FROM user WHERE world_rank_score > 0")
-                .fetch_all(&self.pool)
-                .await?;
+        let rows = sqlx::query!(
+            "SELECT user_id, world_rank_score FROM user
+                 WHERE world_rank_score > 0 AND is_shadow_banned = 0"
+        )
+        .fetch_all(&self.pool)
+        .await?;
 
         cache.del(Self::global_rank_zset_key()).await;
         for row in rows {
@@ -208,6 +264,7 @@ impl UserService {
     }
 
     pub async fn invalidate_user_info_cache(&self, user_id: i32) {
+        self.user_cache.invalidate_user(user_id);
         if let Some(cache) = &self.cache {
             cache.del(&Self::user_info_cache_key(user_id)).await;
         }
@@ -305,18 +362,17 @@ impl UserService {
             .as_millis() as i64
     }
 
-    fn md5_hex(value: &str) -> String {
-        format!("{:x}", md5::compute(value.as_bytes()))
-    }
-
-    /// Hash password using SHA-256.
+    /// Hash password with the configured algorithm.
     ///
     /// Shared between the game auth path and the web admin auth path so both
     /// sides agree on how `user.password` is hashed.
-    pub(crate) fn hash_password(password: &str) -> String {
-        let mut hasher = Sha256::new();
-        hasher.update(password.as_bytes());
-        format!("{:x}", hasher.finalize())
+    pub(crate) async fn hash_password(password: &str) -> ArcResult<String> {
+        PasswordHasher::configured().hash(password).await
+    }
+
+    /// Check a password against a stored hash of any supported algorithm.
+    pub(crate) async fn verify_password(password: &str, stored: &str) -> PasswordCheck {
+        PasswordHasher::configured().verify(password, stored).await
     }
 
     /// Generate access token using SHA-256 and random data
@@ -419,13 +475,7 @@ impl UserService {
             }
         }
 
-        Err(ArcError::Base {
-            message: "No available user code.".to_string(),
-            error_code: 108,
-            api_error_code: -999,
-            extra_data: None,
-            status: 500,
-        })
+        Err(ArcError::internal("No available user code."))
     }
 
     /// Generate a new user ID
@@ -513,7 +563,7 @@ impl UserService {
 
         let user_id = self.generate_user_id().await?;
         let join_date = Self::current_timestamp();
-        let hashed_password = Self::hash_password(&user_data.password);
+        let hashed_password = Self::hash_password(&user_data.password).await?;
 
         // Insert user
         sqlx::query!(
@@ -521,8 +571,9 @@ impl UserService {
                 user_id, name, password, join_date, user_code, rating_ptt,
                 character_id, is_skill_sealed, is_char_uncapped, is_char_uncapped_override,
                 is_hide_rating, favorite_character, max_stamina_notification_enabled,
-                current_map, ticket, prog_boost, email, is_allow_marketing_email
-            ) VALUES (?, ?, ?, ?, ?, 0, 0, 0, 0, 0, 0, -1, 0, '', ?, 0, ?, ?)"#,
+                current_map, ticket, prog_boost, email, is_allow_marketing_email,
+                is_email_verified
+            ) VALUES (?, ?, ?, ?, ?, 0, 0, 0, 0, 0, 0, -1, 0, '', ?, 0, ?, ?, ?)"#,
             user_id,
             user_data.name,
             hashed_password,
@@ -534,6 +585,11 @@ impl UserService {
                 1
             } else {
                 0
+            },
+            if CONFIG.email_verification_enabled {
+                0
+            } else {
+                1
             }
         )
         .execute(&self.pool)
@@ -542,6 +598,11 @@ impl UserService {
         // Insert initial characters
         self.insert_initial_characters(user_id).await?;
 
+        self.webhook.notify(WebhookEvent::Registration {
+            user_id,
+            name: user_data.name,
+        });
+
         // Generate token for immediate login
         let token = Self::generate_token(user_id, join_date);
 
@@ -552,6 +613,9 @@ impl UserService {
     async fn check_device_limits(&self, user_id: i32, device_id: &str) -> ArcResult<()> {
         let current_time = Self::current_timestamp();
 
+        // Expired sessions do not count towards the device limit.
+        self.purge_expired_sessions(user_id).await?;
+
         // Get existing login devices
         let devices = sqlx::query_as!(
             UserLoginDevice,
@@ -607,19 +671,21 @@ impl UserService {
 
                 if login_count.count >= CONFIG.login_device_number_limit as i64 {
                     let remaining_ts = self.auto_ban_user(user_id, current_time).await?;
-                    let mut extra_data = HashMap::new();
-                    extra_data.insert(
-                        "remaining_ts".to_string(),
-                        Value::Number(serde_json::Number::from(remaining_ts)),
-                    );
-                    return Err(ArcError::user_ban(
+                    return Err(ArcError::temporary_ban(
                         "Too many devices logging in during 24 hours.",
                         105,
-                        Some(extra_data),
+                        remaining_ts,
                     ));
                 }
             }
 
+            if CONFIG.reject_login_over_device_limit {
+                return Err(ArcError::no_access(
+                    "Too many devices are logged in. Log out another device first.",
+                    105,
+                ));
+            }
+
             let old_tokens = sqlx::query!(
                 "SELECT access_token FROM login WHERE user_id = ? ORDER BY login_time ASC LIMIT ?",
                 user_id,
@@ -727,21 +793,93 @@ impl UserService {
 
         let current_time = Self::current_timestamp();
 
+        let user_id = self
+            .check_credentials(&login_data.name, &login_data.password)
+            .await?;
+
+        // Generate token
+        let token = Self::generate_token(user_id, current_time);
+
+        // Check device limits
+        if let Some(device_id) = &login_data.device_id {
+            self.check_device_limits(user_id, device_id).await?;
+        }
+
+        // Insert login record
+        sqlx::query!(
+            "INSERT INTO login (access_token, user_id, login_time, login_ip, login_device) VALUES (?, ?, ?, ?, ?)",
+            token,
+            user_id,
+            current_time,
+            ip,
+            login_data.device_id
+        )
+        .execute(&self.pool)
+        .await?;
+
+        if let Some(device_id) = &login_data.device_id {
+            if let Err(e) = self
+                .record_device_fingerprint(
+                    user_id,
+                    FINGERPRINT_DEVICE_ID,
+                    device_id,
+                    login_data.device_model.as_deref(),
+                    ip,
+                )
+                .await
+            {
+                log::warn!("Failed to record device fingerprint for user {user_id}: {e}");
+            }
+        }
+
+        if let Some(cache) = &self.cache {
+            cache
+                .set_i32(
+                    &Self::auth_token_key(&token),
+                    user_id,
+                    self.auth_cache_ttl_seconds,
+                )
+                .await;
+        }
+
+        self.grant_login_bonus(user_id).await;
+
+        Ok(UserAuth { user_id, token })
+    }
+
+    /// Grant today's login bonus if it is still due
+    ///
+    /// Called on login and by the auth guard; failures are logged so they
+    /// never block the request.
+    pub async fn grant_login_bonus(&self, user_id: i32) {
+        let Some(login_bonus) = &self.login_bonus else {
+            return;
+        };
+        match login_bonus.grant_if_due(user_id).await {
+            Ok(Some(_)) => self.invalidate_user_info_cache(user_id).await,
+            Ok(None) => {}
+            Err(e) => log::warn!("Failed to grant login bonus to user {user_id}: {e}"),
+        }
+    }
+
+    /// Validate username and password without creating a login session
+    ///
+    /// Applies the same ban checks as [`Self::login_user`]; used by the game
+    /// login and the player web dashboard.
+    pub async fn check_credentials(&self, name: &str, password: &str) -> ArcResult<i32> {
+        let current_time = Self::current_timestamp();
+
         // Get user credentials
         let user = sqlx::query_as!(
             UserCredentials,
-            "SELECT user_id, password, ban_flag FROM user WHERE name = ?",
-            login_data.name
+            "SELECT user_id, password, ban_flag, is_email_verified FROM user WHERE name = ?",
+            name
         )
         .fetch_optional(&self.pool)
         .await?;
 
-        let user = user.ok_or_else(|| {
-            ArcError::no_data(
-                format!("Username `{}` does not exist.", login_data.name),
-                104,
-            )
-        })?;
+        let user = user
+            .ok_or_else(|| ArcError::no_data(format!("Username `{name}` does not exist."), 104))?;
 
         // Check for ban
         if let Some(ban_flag) = &user.ban_flag {
@@ -749,20 +887,13 @@ impl UserService {
                 if let Some(ban_timestamp_str) = ban_flag.split(':').nth(1) {
                     if let Ok(ban_timestamp) = ban_timestamp_str.parse::<i64>() {
                         if ban_timestamp > current_time {
-                            let mut extra_data = HashMap::new();
-                            extra_data.insert(
-                                "remaining_ts".to_string(),
-                                Value::Number(serde_json::Number::from(
-                                    ban_timestamp - current_time,
-                                )),
-                            );
-                            return Err(ArcError::user_ban(
+                            return Err(ArcError::temporary_ban(
                                 format!(
                                     "Too many devices user `{}` logging in during 24 hours.",
                                     user.user_id
                                 ),
                                 105,
-                                Some(extra_data),
+                                ban_timestamp - current_time,
                             ));
                         }
                     }
@@ -771,65 +902,42 @@ impl UserService {
         }
 
         // Check for account ban (empty password)
-        let password = user.password.as_ref().ok_or_else(|| {
+        let stored_password = user.password.as_ref().ok_or_else(|| {
             ArcError::user_ban(
                 format!("The account `{}` has been banned.", user.user_id),
                 106,
-                None,
             )
         })?;
 
-        if password.is_empty() {
+        if stored_password.is_empty() {
             return Err(ArcError::user_ban(
                 format!("The account `{}` has been banned.", user.user_id),
                 106,
-                None,
             ));
         }
 
         // Verify password
-        let hashed_input = Self::hash_password(&login_data.password);
-        if password != &hashed_input {
+        let check = Self::verify_password(password, stored_password).await;
+        if !check.is_match() {
             return Err(ArcError::no_access(
                 format!("Wrong password of user `{}`", user.user_id),
                 104,
             ));
         }
-
-        // Generate token
-        let token = Self::generate_token(user.user_id, current_time);
-
-        // Check device limits
-        if let Some(device_id) = &login_data.device_id {
-            self.check_device_limits(user.user_id, device_id).await?;
+        if check == PasswordCheck::Outdated {
+            if let Err(e) = upgrade_password_hash(&self.pool, user.user_id, password).await {
+                log::warn!("Failed to re-hash password of user {}: {e}", user.user_id);
+            }
         }
 
-        // Insert login record
-        sqlx::query!(
-            "INSERT INTO login (access_token, user_id, login_time, login_ip, login_device) VALUES (?, ?, ?, ?, ?)",
-            token,
-            user.user_id,
-            current_time,
-            ip,
-            login_data.device_id
-        )
-        .execute(&self.pool)
-        .await?;
-
-        if let Some(cache) = &self.cache {
-            cache
-                .set_i32(
-                    &Self::auth_token_key(&token),
-                    user.user_id,
-                    self.auth_cache_ttl_seconds,
-                )
-                .await;
+        if CONFIG.email_verification_enabled && user.is_email_verified == 0 {
+            return Err(ArcError::no_access(
+                format!("The email of user `{}` is not verified.", user.user_id),
+                151,
+            ));
         }
 
-        Ok(UserAuth {
-            user_id: user.user_id,
-            token,
-        })
+        Ok(user.user_id)
     }
 
     /// Get user ID from access token
@@ -843,29 +951,171 @@ impl UserService {
             }
         }
 
-        let result = sqlx::query_as!(
-            UserCodeMapping,
-            "SELECT user_id FROM login WHERE access_token = ?",
+        let Some(session) = sqlx::query!(
+            "SELECT user_id, login_time FROM login WHERE access_token = ?",
             token
         )
         .fetch_optional(&self.pool)
-        .await?;
-
-        let user_id = result
-            .map(|r| r.user_id)
+        .await?
+        else {
+            return self.authenticate_impersonation_token(token).await;
+        };
+        let user_id = session
+            .user_id
             .ok_or_else(|| ArcError::no_access("Wrong token.", -4))?;
 
+        let now = Self::current_timestamp();
+        let login_time = session.login_time.unwrap_or(0);
+        let remaining_seconds = token_remaining_seconds(login_time, now, CONFIG.login_token_ttl);
+        if remaining_seconds == Some(0) {
+            sqlx::query!("DELETE FROM login WHERE access_token = ?", token)
+                .execute(&self.pool)
+                .await?;
+            return Err(ArcError::no_access("The token has expired.", 108));
+        }
+
+        if let Some(cache) = &self.cache {
+            // Never cache a token past its expiry.
+            let ttl = remaining_seconds.map_or(self.auth_cache_ttl_seconds, |remaining| {
+                self.auth_cache_ttl_seconds.min(remaining)
+            });
+            cache
+                .set_i32(&Self::auth_token_key(token), user_id, ttl)
+                .await;
+        }
+
+        Ok(user_id)
+    }
+
+    /// Authenticate a token minted by `create_impersonation_token`
+    async fn authenticate_impersonation_token(&self, token: &str) -> ArcResult<i32> {
+        let row = sqlx::query!(
+            "SELECT user_id, expires_at FROM impersonation_token WHERE access_token = ?",
+            token
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or_else(|| ArcError::no_access("Wrong token.", -4))?;
+
+        let now = Self::current_timestamp();
+        if row.expires_at <= now {
+            return Err(ArcError::no_access("The token has expired.", 108));
+        }
+
         if let Some(cache) = &self.cache {
+            let remaining = ((row.expires_at - now + 999) / 1000) as u64;
             cache
                 .set_i32(
                     &Self::auth_token_key(token),
-                    user_id,
-                    self.auth_cache_ttl_seconds,
+                    row.user_id,
+                    self.auth_cache_ttl_seconds.min(remaining),
                 )
                 .await;
         }
 
-        Ok(user_id)
+        Ok(row.user_id)
+    }
+
+    /// Mint a short-lived access token for `user_id` on behalf of an admin
+    ///
+    /// The token is not a login session: it does not count towards the
+    /// device limit, cannot be refreshed and leaves the player's own
+    /// sessions alone. Every token is kept in `impersonation_token` as the
+    /// audit log.
+    pub async fn create_impersonation_token(
+        &self,
+        user_id: i32,
+        admin_user_id: i32,
+        reason: &str,
+        ip: Option<&str>,
+    ) -> ArcResult<ImpersonationToken> {
+        let current_time = Self::current_timestamp();
+        let token = Self::generate_token(user_id, current_time);
+        let expires_at = current_time + IMPERSONATION_TOKEN_TTL_MS;
+
+        sqlx::query!(
+            "INSERT INTO impersonation_token
+                (access_token, user_id, admin_user_id, reason, created_at, expires_at, ip)
+             VALUES (?, ?, ?, ?, ?, ?, ?)",
+            token,
+            user_id,
+            admin_user_id,
+            reason,
+            current_time,
+            expires_at,
+            ip
+        )
+        .execute(&self.pool)
+        .await?;
+
+        log::warn!(
+            "Admin {admin_user_id} minted an impersonation token for user {user_id}: {reason}"
+        );
+
+        Ok(ImpersonationToken {
+            user_id,
+            token,
+            expires_at,
+        })
+    }
+
+    /// Exchange a valid access token for a new one
+    ///
+    /// The new token keeps the session's device and IP but starts a fresh
+    /// `login_token_ttl` lifetime; the old token stops working at once.
+    pub async fn refresh_token(&self, token: &str) -> ArcResult<UserAuth> {
+        let user_id = self.authenticate_token(token).await?;
+        let current_time = Self::current_timestamp();
+        let new_token = Self::generate_token(user_id, current_time);
+
+        let result = sqlx::query!(
+            "UPDATE login SET access_token = ?, login_time = ? WHERE access_token = ? AND user_id = ?",
+            new_token,
+            current_time,
+            token,
+            user_id
+        )
+        .execute(&self.pool)
+        .await?;
+        if result.rows_affected() == 0 {
+            return Err(ArcError::no_access("Wrong token.", -4));
+        }
+
+        self.invalidate_tokens([token.to_string()]).await;
+        Ok(UserAuth {
+            user_id,
+            token: new_token,
+        })
+    }
+
+    /// Drop the user's sessions whose token has outlived `login_token_ttl`
+    async fn purge_expired_sessions(&self, user_id: i32) -> ArcResult<()> {
+        if CONFIG.login_token_ttl <= 0 {
+            return Ok(());
+        }
+
+        let cutoff = Self::current_timestamp() - CONFIG.login_token_ttl * 1000;
+        let expired = sqlx::query!(
+            "SELECT access_token FROM login WHERE user_id = ? AND login_time <= ?",
+            user_id,
+            cutoff
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        if expired.is_empty() {
+            return Ok(());
+        }
+
+        sqlx::query!(
+            "DELETE FROM login WHERE user_id = ? AND login_time <= ?",
+            user_id,
+            cutoff
+        )
+        .execute(&self.pool)
+        .await?;
+        self.invalidate_tokens(expired.into_iter().map(|row| row.access_token))
+            .await;
+        Ok(())
     }
 
     /// Get user information by user ID
@@ -879,11 +1129,17 @@ impl UserService {
             }
         }
 
-        let user = sqlx::query_as!(User, "SELECT * FROM user WHERE user_id = ?", user_id)
-            .fetch_optional(&self.pool)
-            .await?;
-
-        let user = user.ok_or_else(|| ArcError::no_data("User not found.", 401))?;
+        let user = match self.user_cache.user(user_id) {
+            Some(user) => user,
+            None => {
+                let user = sqlx::query_as!(User, "SELECT * FROM user WHERE user_id = ?", user_id)
+                    .fetch_optional(&self.pool)
+                    .await?;
+                let user = user.ok_or_else(|| ArcError::no_data("User not found.", 401))?;
+                self.user_cache.put_user(&user);
+                user
+            }
+        };
 
         // Load additional user data
         let mut user_info = UserInfo::from(user);
@@ -910,6 +1166,10 @@ impl UserService {
         user_info.recent_score = self.get_user_recent_scores(user_id).await?;
         user_info.global_rank = Some(self.get_global_rank(user_id).await?);
 
+        if let Some(login_bonus) = &self.login_bonus {
+            user_info.login_bonus = login_bonus.status(user_id).await?;
+        }
+
         if let Some(cache) = &self.cache {
             cache
                 .set_json(&cache_key, &user_info, self.user_info_cache_ttl_seconds)
@@ -1044,31 +1304,36 @@ impl UserService {
     ///
     /// Cycles through the insight state values according to the game logic.
     pub async fn toggle_invasion(&self, user_id: i32) -> ArcResult<UserInfo> {
-        // Get current insight state
+        self.advance_insight_state(user_id, InsightStep::Toggle)
+            .await?;
+        self.get_user_info(user_id).await
+    }
+
+    /// Apply an Insight progression step and return the new insight state.
+    ///
+    /// Unlocking grants the Insight character. Steps never move a user who
+    /// already ascended back to an earlier state, so replaying the story is
+    /// harmless.
+    pub async fn advance_insight_state(&self, user_id: i32, step: InsightStep) -> ArcResult<i32> {
         let current_state =
-            sqlx::query!("SELECT insight_state FROM user WHERE user_id = ?", user_id)
+            sqlx::query_scalar!("SELECT insight_state FROM user WHERE user_id = ?", user_id)
                 .fetch_optional(&self.pool)
                 .await?
-                .ok_or_else(|| ArcError::no_data("No user.", 108))?;
-
-        // Python baseline cycles Constant.INSIGHT_TOGGLE_STATES: 3 -> 4 -> 5 -> 6 -> 3.
-        let insight_toggle_states = Constants::INSIGHT_TOGGLE_STATES;
-        let current_index = insight_toggle_states
-            .iter()
-            .position(|&x| x == current_state.insight_state.unwrap_or(4))
-            .unwrap_or(0);
-        let new_state = insight_toggle_states[(current_index + 1) % insight_toggle_states.len()];
-
-        sqlx::query!(
-            "UPDATE user SET insight_state = ? WHERE user_id = ?",
-            new_state,
-            user_id
-        )
-        .execute(&self.pool)
-        .await?;
+                .ok_or_else(|| ArcError::no_data("No user.", 108))?
+                .unwrap_or(crate::constants::DEFAULT_INSIGHT_STATE);
+        let new_state = next_insight_state(current_state, step)
+            .ok_or_else(|| ArcError::input("Insight is not ascended yet."))?;
 
+        if step == InsightStep::Unlock {
+            self.character_service
+                .grant_insight_ascendant(user_id)
+                .await?;
+        }
+        if new_state != current_state {
+            self.update_user_insight_state(user_id, new_state).await?;
+        }
         self.invalidate_user_info_cache(user_id).await;
-        self.get_user_info(user_id).await
+        Ok(new_state)
     }
 
     /// Change user's character and skill sealed state
@@ -1202,85 +1467,6 @@ impl UserService {
         ))
     }
 
-    /// Get user's cloud save data
-    ///
-    /// Retrieves all cloud save data for the user.
-    pub async fn get_user_save_data(&self, user_id: i32) -> ArcResult<serde_json::Value> {
-        let save_data = sqlx::query!("SELECT * FROM user_save WHERE user_id = ?", user_id)
-            .fetch_optional(&self.pool)
-            .await?;
-
-        let (
-            scores_data,
-            clearlamps_data,
-            clearedsongs_data,
-            unlocklist_data,
-            installid_data,
-            devicemodelname_data,
-            story_data,
-            created_at,
-            finalestate_data,
-        ) = if let Some(data) = save_data {
-            (
-                Self::load_cloud_array_value(data.scores_data.as_deref()),
-                Self::load_cloud_array_value(data.clearlamps_data.as_deref()),
-                Self::load_cloud_array_value(data.clearedsongs_data.as_deref()),
-                Self::load_cloud_array_value(data.unlocklist_data.as_deref()),
-                Self::load_cloud_val_string(data.installid_data.as_deref()),
-                Self::load_cloud_val_string(data.devicemodelname_data.as_deref()),
-                Self::load_cloud_array_value(data.story_data.as_deref()),
-                data.createdAt.unwrap_or(0),
-                data.finalestate_data.unwrap_or_default(),
-            )
-        } else {
-            (
-                serde_json::json!([]),
-                serde_json::json!([]),
-                serde_json::json!([]),
-                serde_json::json!([]),
-                String::new(),
-                String::new(),
-                serde_json::json!([]),
-                0,
-                String::new(),
-            )
-        };
-
-        let response = serde_json::json!({
-            "user_id": user_id,
-            "story": {
-                "": story_data
-            },
-            "devicemodelname": {
-                "val": devicemodelname_data
-            },
-            "installid": {
-                "val": installid_data
-            },
-            "unlocklist": {
-                "": unlocklist_data
-            },
-            "clearedsongs": {
-                "": clearedsongs_data
-            },
-            "clearlamps": {
-                "": clearlamps_data
-            },
-            "scores": {
-                "": scores_data
-            },
-            "version": {
-                "val": 1
-            },
-            "createdAt": created_at,
-            "finalestate": {
-                "val": finalestate_data
-            }
-        });
-
-        Ok(response)
-    }
-
     /// Get user cores information
     async fn get_cached_user_detail<T, F, Fut>(
         &self,
@@ -1535,176 +1721,62 @@ impl UserService {
         Ok(Vec::new())
     }
 
-    /// Update user's cloud save data
-    ///
-    /// Updates the user's cloud save data with new values.
-    pub async fn update_user_save_data(
-        &self,
+    /// Whether `user_id` is shadow banned: the player keeps playing normally
+    /// but is left out of public rankings and link play matchmaking.
+    pub async fn is_shadow_banned(&self, user_id: i32) -> ArcResult<bool> {
+        let flag = sqlx::query_scalar!(
+            "SELECT is_shadow_banned FROM user WHERE user_id = ?",
+            user_id
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(flag.unwrap_or(0) != 0)
+    }
+
+    /// Remember a device identifier seen for `user_id`, so accounts sharing
+    /// devices can be linked by admins.
+    pub async fn record_device_fingerprint(
+        &self,
         user_id: i32,
-        save_request: &crate::route::user::CloudSaveRequest,
+        kind: &str,
+        value: &str,
+        device_model: Option<&str>,
+        ip: Option<&str>,
     ) -> ArcResult<()> {
-        let current_time = Self::current_timestamp();
-        let scores_data =
-            Self::normalize_cloud_array(&save_request.scores_data, &save_request.scores_checksum)?;
-        let clearlamps_data = Self::normalize_cloud_array(
-            &save_request.clearlamps_data,
-            &save_request.clearlamps_checksum,
-        )?;
-        let clearedsongs_data = Self::normalize_cloud_array(
-            &save_request.clearedsongs_data,
-            &save_request.clearedsongs_checksum,
-        )?;
-        let unlocklist_data = Self::normalize_cloud_array(
-            &save_request.unlocklist_data,
-            &save_request.unlocklist_checksum,
-        )?;
-        let installid_data = Self::normalize_cloud_val(
-            &save_request.installid_data,
-            &save_request.installid_checksum,
-        )?;
-        let devicemodelname_data = Self::normalize_cloud_val(
-            &save_request.devicemodelname_data,
-            &save_request.devicemodelname_checksum,
-        )?;
-        let story_data =
-            Self::normalize_cloud_array(&save_request.story_data, &save_request.story_checksum)?;
-        let finalestate_data = Self::normalize_cloud_finalestate(
-            save_request.finalestate_data.as_deref(),
-            save_request.finalestate_checksum.as_deref(),
-        )?;
-
+        let value = value.trim();
+        if value.is_empty() {
+            return Ok(());
+        }
+        let value: String = value.chars().take(255).collect();
+        let device_model: String = device_model
+            .unwrap_or("")
+            .trim()
+            .chars()
+            .take(255)
+            .collect();
+        let now = Self::current_timestamp();
         sqlx::query!(
-            "INSERT INTO user_save (user_id, scores_data, clearlamps_data, clearedsongs_data, unlocklist_data, installid_data, devicemodelname_data, story_data, createdAt, finalestate_data)
-             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            "INSERT INTO user_device_fingerprint
+             (user_id, kind, value, device_model, first_seen, last_seen, last_ip)
+             VALUES (?, ?, ?, ?, ?, ?, ?)
              ON DUPLICATE KEY UPDATE
-             scores_data = VALUES(scores_data),
-             clearlamps_data = VALUES(clearlamps_data),
-             clearedsongs_data = VALUES(clearedsongs_data),
-             unlocklist_data = VALUES(unlocklist_data),
-             installid_data = VALUES(installid_data),
-             devicemodelname_data = VALUES(devicemodelname_data),
-             story_data = VALUES(story_data),
-             createdAt = VALUES(createdAt),
-             finalestate_data = VALUES(finalestate_data)",
+             device_model = IF(VALUES(device_model) = '', device_model, VALUES(device_model)),
+             last_seen = VALUES(last_seen),
+             last_ip = COALESCE(VALUES(last_ip), last_ip),
+             seen_count = seen_count + 1",
             user_id,
-            scores_data,
-            clearlamps_data,
-            clearedsongs_data,
-            unlocklist_data,
-            installid_data,
-            devicemodelname_data,
-            story_data,
-            current_time,
-            finalestate_data
+            kind,
+            value,
+            device_model,
+            now,
+            now,
+            ip
         )
         .execute(&self.pool)
         .await?;
-
-        Ok(())
-    }
-
-    fn verify_cloud_checksum(value: &str, checksum: &str) -> ArcResult<()> {
-        if Self::md5_hex(value) != checksum.to_ascii_lowercase() {
-            return Err(ArcError::input("Hash value of cloud save data mismatches."));
-        }
         Ok(())
     }
 
-    fn parse_cloud_json(value: &str) -> ArcResult<serde_json::Value> {
-        serde_json::from_str::<serde_json::Value>(value)
-            .map_err(|_| ArcError::input("Invalid cloud save payload."))
-    }
-
-    fn normalize_cloud_array(value: &str, checksum: &str) -> ArcResult<String> {
-        if value.is_empty() {
-            return Ok(serde_json::json!({ "": [] }).to_string());
-        }
-
-        Self::verify_cloud_checksum(value, checksum)?;
-        let parsed = Self::parse_cloud_json(value)?;
-        let inner = parsed
-            .get("")
-            .cloned()
-            .ok_or_else(|| ArcError::input("Invalid cloud save payload."))?;
-
-        Ok(serde_json::json!({ "": inner }).to_string())
-    }
-
-    fn normalize_cloud_val(value: &str, checksum: &str) -> ArcResult<String> {
-        if value.is_empty() {
-            return Ok(serde_json::json!({ "val": "" }).to_string());
-        }
-
-        Self::verify_cloud_checksum(value, checksum)?;
-        let parsed = Self::parse_cloud_json(value)?;
-        let inner = parsed
-            .get("val")
-            .cloned()
-            .ok_or_else(|| ArcError::input("Invalid cloud save payload."))?;
-
-        Ok(serde_json::json!({ "val": inner }).to_string())
-    }
-
-    fn normalize_cloud_finalestate(
-        value: Option<&str>,
-        checksum: Option<&str>,
-    ) -> ArcResult<String> {
-        let Some(value) = value else {
-            return Ok(String::new());
-        };
-        if value.is_empty() {
-            return Ok(String::new());
-        }
-
-        let checksum = checksum.ok_or_else(|| ArcError::input("Missing finalestate checksum."))?;
-        Self::verify_cloud_checksum(value, checksum)?;
-        let parsed = Self::parse_cloud_json(value)?;
-        let inner = parsed
-            .get("val")
-            .cloned()
-            .ok_or_else(|| ArcError::input("Invalid cloud save payload."))?;
-
-        Ok(match inner {
-            Value::String(s) => s,
-            other => other.to_string(),
-        })
-    }
-
-    fn load_cloud_array_value(raw: Option<&str>) -> Value {
-        let Some(raw) = raw else {
-            return serde_json::json!([]);
-        };
-        if raw.is_empty() {
-            return serde_json::json!([]);
-        }
-
-        match serde_json::from_str::<Value>(raw) {
-            Ok(Value::Object(map)) => map.get("").cloned().unwrap_or(serde_json::json!([])),
-            Ok(value) => value,
-            Err(_) => serde_json::json!([]),
-        }
-    }
-
-    fn load_cloud_val_string(raw: Option<&str>) -> String {
-        let Some(raw) = raw else {
-            return String::new();
-        };
-        if raw.is_empty() {
-            return String::new();
-        }
-
-        match serde_json::from_str::<Value>(raw) {
-            Ok(Value::Object(map)) => map
-                .get("val")
-                .and_then(Value::as_str)
-                .unwrap_or_default()
-                .to_string(),
-            Ok(Value::String(s)) => s,
-            Ok(other) => other.to_string(),
-            Err(_) => String::new(),
-        }
-    }
-
     /// Update user setting
     ///
     /// Updates a specific user setting based on the setting argument.
@@ -1839,11 +1911,133 @@ impl UserService {
         }))
     }
 
-    /// Delete user account
+    /// Verify the current password of a user
+    async fn verify_user_password(&self, user_id: i32, password: &str) -> ArcResult<()> {
+        let stored = sqlx::query_scalar!("SELECT password FROM user WHERE user_id = ?", user_id)
+            .fetch_optional(&self.pool)
+            .await?
+            .flatten()
+            .unwrap_or_default();
+
+        if !Self::verify_password(password, &stored).await.is_match() {
+            return Err(ArcError::no_access(
+                format!("Wrong password of user `{user_id}`"),
+                104,
+            ));
+        }
+        Ok(())
+    }
+
+    /// Change password after verifying the current one
+    ///
+    /// All login sessions are revoked so other devices must log in again.
+    pub async fn change_password(
+        &self,
+        user_id: i32,
+        current_password: &str,
+        new_password: &str,
+    ) -> ArcResult<()> {
+        self.verify_user_password(user_id, current_password).await?;
+        Self::validate_password(new_password)?;
+
+        sqlx::query!(
+            "UPDATE user SET password = ? WHERE user_id = ?",
+            Self::hash_password(new_password).await?,
+            user_id
+        )
+        .execute(&self.pool)
+        .await?;
+
+        self.revoke_all_login_sessions(user_id).await
+    }
+
+    /// Change email address after verifying the password
+    pub async fn change_email(&self, user_id: i32, password: &str, email: &str) -> ArcResult<()> {
+        self.verify_user_password(user_id, password).await?;
+        self.validate_email(email).await?;
+
+        sqlx::query!(
+            "UPDATE user SET email = ? WHERE user_id = ?",
+            email,
+            user_id
+        )
+        .execute(&self.pool)
+        .await?;
+
+        self.invalidate_user_info_cache(user_id).await;
+        Ok(())
+    }
+
+    /// List active login sessions, newest first
+    pub async fn get_login_sessions(&self, user_id: i32) -> ArcResult<Vec<UserLoginSession>> {
+        let sessions = sqlx::query_as!(
+            UserLoginSession,
+            "SELECT login_time, login_ip, login_device FROM login
+             WHERE user_id = ? ORDER BY login_time DESC",
+            user_id
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(sessions)
+    }
+
+    /// List devices with active login sessions, most recently used first
+    pub async fn list_login_devices(&self, user_id: i32) -> ArcResult<Vec<UserDevice>> {
+        let sessions = self.get_login_sessions(user_id).await?;
+        Ok(group_login_devices(sessions))
+    }
+
+    /// Log one device out by invalidating all of its access tokens
+    pub async fn kick_login_device(&self, user_id: i32, device: Option<&str>) -> ArcResult<()> {
+        if self.revoke_login_device(user_id, device).await? == 0 {
+            return Err(ArcError::no_data("The device is not logged in.", 108));
+        }
+        Ok(())
+    }
+
+    /// Revoke every login session of one device
     ///
-    /// Deletes a user account and all associated data.
-    pub async fn delete_user_account(&self, user_id: i32) -> ArcResult<()> {
-        // Check if account deletion is allowed based on config
+    /// `None` matches sessions created without a device id.
+    pub async fn revoke_login_device(&self, user_id: i32, device: Option<&str>) -> ArcResult<u64> {
+        let old_tokens = sqlx::query!(
+            "SELECT access_token FROM login WHERE user_id = ? AND login_device <=> ?",
+            user_id,
+            device
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let affected = sqlx::query!(
+            "DELETE FROM login WHERE user_id = ? AND login_device <=> ?",
+            user_id,
+            device
+        )
+        .execute(&self.pool)
+        .await?
+        .rows_affected();
+        self.invalidate_tokens(old_tokens.into_iter().map(|row| row.access_token))
+            .await;
+
+        Ok(affected)
+    }
+
+    /// Revoke every login session of a user
+    pub async fn revoke_all_login_sessions(&self, user_id: i32) -> ArcResult<()> {
+        let old_tokens = sqlx::query!("SELECT access_token FROM login WHERE user_id = ?", user_id)
+            .fetch_all(&self.pool)
+            .await?;
+
+        sqlx::query!("DELETE FROM login WHERE user_id = ?", user_id)
+            .execute(&self.pool)
+            .await?;
+        self.invalidate_tokens(old_tokens.into_iter().map(|row| row.access_token))
+            .await;
+
+        Ok(())
+    }
+
+    fn ensure_self_delete_allowed() -> ArcResult<()> {
         if !CONFIG.allow_self_account_delete {
             return Err(ArcError::no_data_status(
                 "Cannot delete the account.",
@@ -1851,7 +2045,111 @@ impl UserService {
                 404,
             ));
         }
+        Ok(())
+    }
+
+    /// The user's account deletion request, unless it has lapsed
+    pub async fn get_account_deletion_request(
+        &self,
+        user_id: i32,
+    ) -> ArcResult<Option<AccountDeletionRequest>> {
+        let request = sqlx::query_as!(
+            AccountDeletionRequest,
+            "SELECT requested_at, confirmable_at, expires_at
+             FROM account_deletion_request WHERE user_id = ?",
+            user_id
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(request.filter(|request| {
+            deletion_request_state(request, Self::current_timestamp())
+                != DeletionRequestState::Expired
+        }))
+    }
+
+    /// Request deletion of the account, the first of two steps
+    ///
+    /// The deletion can be confirmed once `account_delete_cooldown_hours`
+    /// have passed. Requesting again while a request is pending returns it
+    /// unchanged.
+    pub async fn request_account_deletion(
+        &self,
+        user_id: i32,
+    ) -> ArcResult<AccountDeletionRequest> {
+        Self::ensure_self_delete_allowed()?;
+        if let Some(request) = self.get_account_deletion_request(user_id).await? {
+            return Ok(request);
+        }
+
+        let request = new_deletion_request(
+            Self::current_timestamp(),
+            CONFIG.account_delete_cooldown_hours,
+            CONFIG.account_delete_confirm_window_hours,
+        );
+        sqlx::query!(
+            "REPLACE INTO account_deletion_request
+                (user_id, requested_at, confirmable_at, expires_at)
+             VALUES (?, ?, ?, ?)",
+            user_id,
+            request.requested_at,
+            request.confirmable_at,
+            request.expires_at
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(request)
+    }
+
+    /// Withdraw a pending account deletion request
+    pub async fn cancel_account_deletion(&self, user_id: i32) -> ArcResult<()> {
+        let affected = sqlx::query!(
+            "DELETE FROM account_deletion_request WHERE user_id = ?",
+            user_id
+        )
+        .execute(&self.pool)
+        .await?
+        .rows_affected();
+
+        if affected == 0 {
+            return Err(ArcError::no_data("No account deletion was requested.", 108));
+        }
+        Ok(())
+    }
+
+    /// Confirm a requested account deletion, the second of two steps
+    ///
+    /// Needs the password and a request whose cooldown has passed; the
+    /// account is then anonymized.
+    pub async fn confirm_account_deletion(&self, user_id: i32, password: &str) -> ArcResult<()> {
+        Self::ensure_self_delete_allowed()?;
+        self.verify_user_password(user_id, password).await?;
+
+        let request = self
+            .get_account_deletion_request(user_id)
+            .await?
+            .ok_or_else(|| ArcError::no_data("No account deletion was requested.", 108))?;
+        let now = Self::current_timestamp();
+        if deletion_request_state(&request, now) == DeletionRequestState::CoolingDown {
+            return Err(ArcError::no_access(
+                format!(
+                    "The account deletion can be confirmed in {} minutes.",
+                    (request.confirmable_at - now + 59_999) / 60_000
+                ),
+                151,
+            ));
+        }
 
+        self.anonymize_user_account(user_id).await
+    }
+
+    /// Turn the account into a tombstone
+    ///
+    /// Credentials, contact details, sessions, devices, friends and saves are
+    /// removed in one transaction. The row itself stays under a placeholder
+    /// name so scores, world progress and rankings keep a valid owner.
+    async fn anonymize_user_account(&self, user_id: i32) -> ArcResult<()> {
         let friend_rows = sqlx::query!(
             "SELECT user_id_me, user_id_other FROM friend WHERE user_id_me = ? OR user_id_other = ?",
             user_id,
@@ -1872,67 +2170,58 @@ impl UserService {
             .fetch_all(&self.pool)
             .await?;
 
-        // Start a transaction for atomic deletion
         let mut transaction = self.pool.begin().await?;
 
-        // Delete from all related tables
-        sqlx::query!("DELETE FROM login WHERE user_id = ?", user_id)
-            .execute(&mut *transaction)
-            .await?;
-
         sqlx::query!(
-            "DELETE FROM friend WHERE user_id_me = ? OR user_id_other = ?",
-            user_id,
+            "UPDATE user SET name = ?, password = '', email = NULL, user_code = NULL,
+                    is_allow_marketing_email = 0
+             WHERE user_id = ?",
+            tombstone_name(user_id),
             user_id
         )
         .execute(&mut *transaction)
         .await?;
 
-        sqlx::query!("DELETE FROM best_score WHERE user_id = ?", user_id)
-            .execute(&mut *transaction)
-            .await?;
-
-        sqlx::query!("DELETE FROM user_char WHERE user_id = ?", user_id)
-            .execute(&mut *transaction)
-            .await?;
-
-        sqlx::query!("DELETE FROM user_char_full WHERE user_id = ?", user_id)
-            .execute(&mut *transaction)
-            .await?;
-
-        sqlx::query!("DELETE FROM recent30 WHERE user_id = ?", user_id)
+        sqlx::query!("DELETE FROM login WHERE user_id = ?", user_id)
             .execute(&mut *transaction)
             .await?;
 
-        sqlx::query!("DELETE FROM user_world WHERE user_id = ?", user_id)
+        sqlx::query!("DELETE FROM api_login WHERE user_id = ?", user_id)
             .execute(&mut *transaction)
             .await?;
 
-        sqlx::query!("DELETE FROM user_item WHERE user_id = ?", user_id)
-            .execute(&mut *transaction)
-            .await?;
+        sqlx::query!(
+            "DELETE FROM friend WHERE user_id_me = ? OR user_id_other = ?",
+            user_id,
+            user_id
+        )
+        .execute(&mut *transaction)
+        .await?;
 
-        sqlx::query!("DELETE FROM user_save WHERE user_id = ?", user_id)
+        sqlx::query!("DELETE FROM user_push_token WHERE user_id = ?", user_id)
             .execute(&mut *transaction)
             .await?;
 
-        sqlx::query!("DELETE FROM user_present WHERE user_id = ?", user_id)
-            .execute(&mut *transaction)
-            .await?;
+        sqlx::query!(
+            "DELETE FROM user_device_fingerprint WHERE user_id = ?",
+            user_id
+        )
+        .execute(&mut *transaction)
+        .await?;
 
-        sqlx::query!("DELETE FROM user_redeem WHERE user_id = ?", user_id)
+        sqlx::query!("DELETE FROM email_verification WHERE user_id = ?", user_id)
             .execute(&mut *transaction)
             .await?;
 
-        sqlx::query!("DELETE FROM user_role WHERE user_id = ?", user_id)
+        sqlx::query!("DELETE FROM download_token WHERE user_id = ?", user_id)
             .execute(&mut *transaction)
             .await?;
 
-        sqlx::query!("DELETE FROM user_course WHERE user_id = ?", user_id)
+        sqlx::query!("DELETE FROM user_save WHERE user_id = ?", user_id)
             .execute(&mut *transaction)
             .await?;
 
-        sqlx::query!("DELETE FROM user_mission WHERE user_id = ?", user_id)
+        sqlx::query!("DELETE FROM user_save_history WHERE user_id = ?", user_id)
             .execute(&mut *transaction)
             .await?;
 
@@ -1940,21 +2229,33 @@ impl UserService {
             .execute(&mut *transaction)
             .await?;
 
-        sqlx::query!("DELETE FROM user_custom_course WHERE user_id = ?", user_id)
+        sqlx::query!("DELETE FROM notification WHERE user_id = ?", user_id)
             .execute(&mut *transaction)
             .await?;
 
-        sqlx::query!("DELETE FROM download_token WHERE user_id = ?", user_id)
+        sqlx::query!("DELETE FROM user_role WHERE user_id = ?", user_id)
             .execute(&mut *transaction)
             .await?;
 
-        // Finally delete from user table
-        sqlx::query!("DELETE FROM user WHERE user_id = ?", user_id)
-            .execute(&mut *transaction)
-            .await?;
+        sqlx::query!(
+            "DELETE FROM account_deletion_request WHERE user_id = ?",
+            user_id
+        )
+        .execute(&mut *transaction)
+        .await?;
 
         transaction.commit().await?;
 
+        if let Err(e) = UserExportService::new(
+            self.pool.clone(),
+            PathBuf::from(CONFIG.user_export_folder_path.trim()),
+        )
+        .delete_export(user_id)
+        .await
+        {
+            log::warn!("Failed to remove the data export of user {user_id}: {e}");
+        }
+
         self.invalidate_tokens(old_tokens.into_iter().map(|row| row.access_token))
             .await;
         self.invalidate_user_info_cache(user_id).await;
@@ -2037,6 +2338,7 @@ impl UserService {
             }
         }
 
+        self.user_cache.invalidate_user(user_id);
         self.invalidate_profile_visibility_cache(user_id).await;
         self.invalidate_user_character_cache(user_id).await;
         Ok(())
@@ -2046,38 +2348,9 @@ impl UserService {
     ///
     /// Increases the user's stamina by the specified amount.
     pub async fn add_stamina(&self, user_id: i32, amount: i32) -> ArcResult<()> {
-        let current_stamina = sqlx::query!(
-            "SELECT max_stamina_ts, stamina FROM user WHERE user_id = ?",
-            user_id
-        )
-        .fetch_optional(&self.pool)
-        .await?;
-
-        match current_stamina {
-            Some(stamina_row) => {
-                let mut stamina = StaminaImpl::new(
-                    stamina_row.stamina.unwrap_or(0),
-                    stamina_row.max_stamina_ts.unwrap_or(0),
-                );
-                let next_stamina = stamina.get_current_stamina() + amount;
-                stamina.set_stamina(next_stamina);
-
-                sqlx::query!(
-                    "UPDATE user SET stamina = ?, max_stamina_ts = ? WHERE user_id = ?",
-                    stamina.get_current_stamina(),
-                    stamina.max_stamina_ts(),
-                    user_id
-                )
-                .execute(&self.pool)
-                .await?;
-            }
-            None => {
-                return Err(ArcError::no_data(
-                    "User not found for stamina update".to_string(),
-                    108,
-                ));
-            }
-        }
+        StaminaService::new(self.pool.clone())
+            .add_stamina(user_id, amount)
+            .await?;
 
         self.invalidate_user_info_cache(user_id).await;
         self.invalidate_user_character_cache(user_id).await;
@@ -2086,18 +2359,9 @@ impl UserService {
 
     /// get user's stamina
     async fn get_user_stamina(&self, user_id: i32) -> ArcResult<i32> {
-        let stamina_info = sqlx::query!(
-            "select max_stamina_ts, stamina from user where user_id = ?",
-            user_id
-        )
-        .fetch_one(&self.pool)
-        .await?;
-
-        let stamina = StaminaImpl::new(
-            stamina_info.stamina.unwrap_or(12),
-            stamina_info.max_stamina_ts.unwrap_or(0),
-        );
-
+        let stamina = StaminaService::new(self.pool.clone())
+            .get_stamina(user_id)
+            .await?;
         Ok(stamina.get_current_stamina())
     }
 
@@ -2122,6 +2386,14 @@ impl UserService {
             return Err(ArcError::friend("The user has been your friend.", 602, -1));
         }
 
+        let friend_count =
+            sqlx::query_scalar!("SELECT COUNT(*) FROM friend WHERE user_id_me = ?", user_id)
+                .fetch_one(&self.pool)
+                .await?;
+        if friend_count >= i64::from(CONFIG.max_friend_count) {
+            return Err(ArcError::friend_limit(CONFIG.max_friend_count));
+        }
+
         // Add friend relationship
         sqlx::query!(
             "INSERT INTO friend (user_id_me, user_id_other) VALUES (?, ?)",
@@ -2220,7 +2492,8 @@ impl UserService {
 
                 // Count how many users have higher scores
                 let rank_result = sqlx::query!(
-                    "SELECT COUNT(*) as count FROM user WHERE world_rank_score > ?",
+                    "SELECT COUNT(*) as count FROM user
+                     WHERE world_rank_score > ? AND is_shadow_banned = 0",
                     world_rank_score
                 )
                 .fetch_one(&self.pool)
@@ -2575,3 +2848,171 @@ fn mission_status_name(status: i32) -> &'static str {
         _ => "locked",
     }
 }
+
+/// Insight state after `step`, or `None` when the step is not available yet.
+///
+/// Toggling cycles `INSIGHT_TOGGLE_STATES` (3 -> 4 -> 5 -> 6 -> 3) and needs
+/// the Lephon ascent; unlock and ascent keep states that are already past them.
+fn next_insight_state(current: i32, step: InsightStep) -> Option<i32> {
+    let toggle_states = Constants::INSIGHT_TOGGLE_STATES;
+    let toggle_index = toggle_states.iter().position(|&state| state == current);
+    match step {
+        InsightStep::Unlock if toggle_index.is_some() => Some(current),
+        InsightStep::Unlock => Some(Constants::INSIGHT_UNLOCKED_STATE),
+        InsightStep::Ascend if toggle_index.is_some() => Some(current),
+        InsightStep::Ascend => Some(Constants::INSIGHT_ASCENDED_STATE),
+        InsightStep::Toggle => {
+            toggle_index.map(|index| toggle_states[(index + 1) % toggle_states.len()])
+        }
+    }
+}
+
+/// Seconds an access token issued at `login_time` has left, `None` when
+/// tokens never expire (`ttl_seconds` <= 0) and `Some(0)` once expired.
+fn token_remaining_seconds(login_time: i64, now: i64, ttl_seconds: i64) -> Option<u64> {
+    if ttl_seconds <= 0 {
+        return None;
+    }
+    let expires_at = login_time.saturating_add(ttl_seconds.saturating_mul(1000));
+    Some(((expires_at - now).max(0) + 999) as u64 / 1000)
+}
+
+/// Where an account deletion request stands at a point in time
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DeletionRequestState {
+    CoolingDown,
+    Confirmable,
+    Expired,
+}
+
+fn deletion_request_state(request: &AccountDeletionRequest, now: i64) -> DeletionRequestState {
+    if now >= request.expires_at {
+        DeletionRequestState::Expired
+    } else if now < request.confirmable_at {
+        DeletionRequestState::CoolingDown
+    } else {
+        DeletionRequestState::Confirmable
+    }
+}
+
+fn new_deletion_request(
+    now: i64,
+    cooldown_hours: i64,
+    confirm_window_hours: i64,
+) -> AccountDeletionRequest {
+    const HOUR_MS: i64 = 60 * 60 * 1000;
+    let confirmable_at = now + cooldown_hours.max(0) * HOUR_MS;
+    AccountDeletionRequest {
+        requested_at: now,
+        confirmable_at,
+        // Always leave at least an hour to confirm
+        expires_at: confirmable_at + confirm_window_hours.max(1) * HOUR_MS,
+    }
+}
+
+/// Name of an anonymized account; longer than registration allows, so it
+/// can never be taken by a new player.
+fn tombstone_name(user_id: i32) -> String {
+    format!("deleted-account-{user_id}")
+}
+
+/// Group login sessions (newest first) by device, keeping that order.
+fn group_login_devices(sessions: Vec<UserLoginSession>) -> Vec<UserDevice> {
+    let mut devices: Vec<UserDevice> = Vec::new();
+    for session in sessions {
+        match devices
+            .iter_mut()
+            .find(|device| device.device_id == session.login_device)
+        {
+            Some(device) => device.session_count += 1,
+            None => devices.push(UserDevice {
+                device_id: session.login_device,
+                last_login_time: session.login_time,
+                last_login_ip: session.login_ip,
+                session_count: 1,
+            }),
+        }
+    }
+    devices
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_next_insight_state() {
+        assert_eq!(next_insight_state(0, InsightStep::Unlock), Some(1));
+        assert_eq!(next_insight_state(1, InsightStep::Ascend), Some(3));
+        assert_eq!(next_insight_state(5, InsightStep::Unlock), Some(5));
+        assert_eq!(next_insight_state(6, InsightStep::Ascend), Some(6));
+        assert_eq!(next_insight_state(3, InsightStep::Toggle), Some(4));
+        assert_eq!(next_insight_state(6, InsightStep::Toggle), Some(3));
+        assert_eq!(next_insight_state(1, InsightStep::Toggle), None);
+    }
+
+    #[test]
+    fn test_deletion_request_state() {
+        let request = new_deletion_request(1_000, 24, 72);
+        assert_eq!(request.confirmable_at, 1_000 + 24 * 3_600_000);
+        assert_eq!(request.expires_at, request.confirmable_at + 72 * 3_600_000);
+
+        assert_eq!(
+            deletion_request_state(&request, 1_000),
+            DeletionRequestState::CoolingDown
+        );
+        assert_eq!(
+            deletion_request_state(&request, request.confirmable_at),
+            DeletionRequestState::Confirmable
+        );
+        assert_eq!(
+            deletion_request_state(&request, request.expires_at),
+            DeletionRequestState::Expired
+        );
+
+        let immediate = new_deletion_request(1_000, 0, 0);
+        assert_eq!(
+            deletion_request_state(&immediate, 1_000),
+            DeletionRequestState::Confirmable
+        );
+        assert_eq!(immediate.expires_at, 1_000 + 3_600_000);
+    }
+
+    #[test]
+    fn test_tombstone_name() {
+        let name = tombstone_name(2000001);
+        assert_eq!(name, "deleted-account-2000001");
+        assert!(name.len() > 16);
+    }
+
+    #[test]
+    fn test_group_login_devices() {
+        let session = |time: i64, device: Option<&str>| UserLoginSession {
+            login_time: Some(time),
+            login_ip: Some(format!("10.0.0.{time}")),
+            login_device: device.map(str::to_string),
+        };
+        let devices = group_login_devices(vec![
+            session(3, Some("phone")),
+            session(2, None),
+            session(1, Some("phone")),
+        ]);
+
+        assert_eq!(devices.len(), 2);
+        assert_eq!(devices[0].device_id.as_deref(), Some("phone"));
+        assert_eq!(devices[0].last_login_time, Some(3));
+        assert_eq!(devices[0].last_login_ip.as_deref(), Some("10.0.0.3"));
+        assert_eq!(devices[0].session_count, 2);
+        assert_eq!(devices[1].device_id, None);
+        assert_eq!(devices[1].session_count, 1);
+    }
+
+    #[test]
+    fn test_token_remaining_seconds() {
+        assert_eq!(token_remaining_seconds(0, 1_000_000, 0), None);
+        assert_eq!(token_remaining_seconds(1_000, 1_000, 60), Some(60));
+        assert_eq!(token_remaining_seconds(1_000, 31_500, 60), Some(30));
+        assert_eq!(token_remaining_seconds(1_000, 61_000, 60), Some(0));
+        assert_eq!(token_remaining_seconds(1_000, 90_000, 60), Some(0));
+    }
+}
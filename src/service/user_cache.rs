@@ -0,0 +1,234 @@
+use crate::model::user::User;
+use crate::service::cache::env_ttl_seconds;
+use rocket_prometheus::prometheus::{IntCounterVec, Opts, Registry};
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+/// Ratings of one song, indexed by difficulty (`rating_pst` .. `rating_etr`)
+pub type ChartRatings = [Option<i32>; 5];
+
+const USER_CACHE_CAPACITY: usize = 10_000;
+const CHART_CACHE_CAPACITY: usize = 5_000;
+
+static USER_CACHE: OnceLock<UserCache> = OnceLock::new();
+
+/// Bounded map whose entries expire `ttl` after insertion
+struct TtlMap<K, V> {
+    ttl: Duration,
+    capacity: usize,
+    entries: HashMap<K, (Instant, V)>,
+}
+
+impl<K: Eq + Hash, V: Clone> TtlMap<K, V> {
+    fn new(ttl: Duration, capacity: usize) -> Self {
+        Self {
+            ttl,
+            capacity,
+            entries: HashMap::new(),
+        }
+    }
+
+    fn is_enabled(&self) -> bool {
+        !self.ttl.is_zero() && self.capacity > 0
+    }
+
+    fn get<Q>(&mut self, key: &Q, now: Instant) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let (inserted_at, value) = self.entries.get(key)?;
+        if now.duration_since(*inserted_at) < self.ttl {
+            return Some(value.clone());
+        }
+        self.entries.remove(key);
+        None
+    }
+
+    fn insert(&mut self, key: K, value: V, now: Instant) {
+        if !self.is_enabled() {
+            return;
+        }
+        if self.entries.len() >= self.capacity {
+            let ttl = self.ttl;
+            self.entries
+                .retain(|_, (inserted_at, _)| now.duration_since(*inserted_at) < ttl);
+            if self.entries.len() >= self.capacity {
+                // Still full of live entries: start over rather than track LRU order.
+                self.entries.clear();
+            }
+        }
+        self.entries.insert(key, (now, value));
+    }
+
+    fn remove<Q>(&mut self, key: &Q)
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.entries.remove(key);
+    }
+
+    fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+struct UserCacheInner {
+    users: Mutex<TtlMap<i32, User>>,
+    charts: Mutex<TtlMap<String, ChartRatings>>,
+    lookups: IntCounterVec,
+}
+
+/// Process-local cache of `user` rows and chart ratings
+///
+/// Sits in front of the hottest lookups of score submission. Entries expire
+/// after `USER_CACHE_TTL_SECONDS` / `CHART_CACHE_TTL_SECONDS` and are dropped
+/// explicitly whenever the service layer writes the underlying rows; a TTL of
+/// 0 disables that half of the cache.
+#[derive(Clone)]
+pub struct UserCache {
+    inner: Arc<UserCacheInner>,
+}
+
+impl UserCache {
+    pub fn new(user_ttl_seconds: u64, chart_ttl_seconds: u64) -> Self {
+        let lookups = IntCounterVec::new(
+            Opts::new(
+                "user_cache_lookups_total",
+                "In-memory user/chart cache lookups",
+            ),
+            &["cache", "result"],
+        )
+        .expect("valid metric");
+
+        Self {
+            inner: Arc::new(UserCacheInner {
+                users: Mutex::new(TtlMap::new(
+                    Duration::from_secs(user_ttl_seconds),
+                    USER_CACHE_CAPACITY,
+                )),
+                charts: Mutex::new(TtlMap::new(
+                    Duration::from_secs(chart_ttl_seconds),
+                    CHART_CACHE_CAPACITY,
+                )),
+                lookups,
+            }),
+        }
+    }
+
+    /// Cache shared by every service instance, so invalidation from one
+    /// request is seen by all others
+    pub fn global() -> &'static UserCache {
+        USER_CACHE.get_or_init(|| {
+            Self::new(
+                env_ttl_seconds("USER_CACHE_TTL_SECONDS", 5),
+                env_ttl_seconds("CHART_CACHE_TTL_SECONDS", 300),
+            )
+        })
+    }
+
+    /// Expose the hit/miss counters on `/metrics`
+    pub fn register_metrics(&self, registry: &Registry) -> Result<(), String> {
+        registry
+            .register(Box::new(self.inner.lookups.clone()))
+            .map_err(|e| e.to_string())
+    }
+
+    pub fn user(&self, user_id: i32) -> Option<User> {
+        let cached = lock(&self.inner.users).get(&user_id, Instant::now());
+        self.record("user", cached.is_some());
+        cached
+    }
+
+    pub fn put_user(&self, user: &User) {
+        lock(&self.inner.users).insert(user.user_id, user.clone(), Instant::now());
+    }
+
+    pub fn invalidate_user(&self, user_id: i32) {
+        lock(&self.inner.users).remove(&user_id);
+    }
+
+    /// Drop every cached user, for bulk writes such as a rating refresh
+    pub fn invalidate_all_users(&self) {
+        lock(&self.inner.users).clear();
+    }
+
+    pub fn chart(&self, song_id: &str) -> Option<ChartRatings> {
+        let cached = lock(&self.inner.charts).get(song_id, Instant::now());
+        self.record("chart", cached.is_some());
+        cached
+    }
+
+    pub fn put_chart(&self, song_id: &str, ratings: ChartRatings) {
+        lock(&self.inner.charts).insert(song_id.to_string(), ratings, Instant::now());
+    }
+
+    pub fn invalidate_chart(&self, song_id: &str) {
+        lock(&self.inner.charts).remove(song_id);
+    }
+
+    /// Drop every cached chart, for bulk writes such as a songlist sync
+    pub fn invalidate_all_charts(&self) {
+        lock(&self.inner.charts).clear();
+    }
+
+    fn record(&self, cache: &str, hit: bool) {
+        let result = if hit { "hit" } else { "miss" };
+        self.inner.lookups.with_label_values(&[cache, result]).inc();
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    match mutex.lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ttl_map_expires_entries() {
+        let start = Instant::now();
+        let mut map = TtlMap::new(Duration::from_secs(5), 10);
+        map.insert(1, "a", start);
+
+        assert_eq!(map.get(&1, start + Duration::from_secs(4)), Some("a"));
+        assert_eq!(map.get(&1, start + Duration::from_secs(5)), None);
+        assert!(map.entries.is_empty());
+    }
+
+    #[test]
+    fn test_ttl_map_zero_ttl_disables_cache() {
+        let now = Instant::now();
+        let mut map = TtlMap::new(Duration::ZERO, 10);
+        map.insert(1, "a", now);
+        assert_eq!(map.get(&1, now), None);
+    }
+
+    #[test]
+    fn test_ttl_map_capacity() {
+        let start = Instant::now();
+        let mut map = TtlMap::new(Duration::from_secs(5), 2);
+        map.insert(1, "a", start);
+        map.insert(2, "b", start + Duration::from_secs(3));
+
+        // Entry 1 has expired, so making room only evicts it.
+        let later = start + Duration::from_secs(6);
+        map.insert(3, "c", later);
+        assert_eq!(map.get(&1, later), None);
+        assert_eq!(map.get(&2, later), Some("b"));
+        assert_eq!(map.get(&3, later), Some("c"));
+
+        // All entries are live, so the map is reset.
+        map.insert(4, "d", later);
+        assert_eq!(map.entries.len(), 1);
+        assert_eq!(map.get(&4, later), Some("d"));
+    }
+}
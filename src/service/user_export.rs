@@ -0,0 +1,263 @@
+//! Personal data exports, assembled in the background by the
+//! `export_user_data` operation and downloaded by the player.
+
+use crate::error::{ArcError, ArcResult};
+use crate::utils::current_timestamp_ms;
+use crate::DbPool;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::path::PathBuf;
+
+/// Version of the export document layout.
+const EXPORT_FORMAT_VERSION: i32 = 1;
+
+/// A finished export waiting to be downloaded
+#[derive(Debug, Clone, Serialize)]
+pub struct UserExportInfo {
+    pub size: u64,
+    pub created_at: i64,
+}
+
+#[derive(Debug, Serialize)]
+struct ExportProfile {
+    user_id: i32,
+    name: Option<String>,
+    email: Option<String>,
+    user_code: Option<String>,
+    join_date: Option<i64>,
+    rating_ptt: Option<i32>,
+    highest_rating_ptt: Option<i32>,
+    character_id: Option<i32>,
+    ticket: Option<i32>,
+    world_rank_score: Option<i32>,
+    current_map: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct ExportScore {
+    song_id: String,
+    difficulty: i32,
+    score: Option<i32>,
+    shiny_perfect_count: Option<i32>,
+    perfect_count: Option<i32>,
+    near_count: Option<i32>,
+    miss_count: Option<i32>,
+    health: Option<i32>,
+    modifier: Option<i32>,
+    time_played: Option<i64>,
+    best_clear_type: Option<i32>,
+    clear_type: Option<i32>,
+    rating: Option<f64>,
+    score_v2: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+struct ExportWorldMap {
+    map_id: String,
+    curr_position: Option<i32>,
+    curr_capture: Option<f64>,
+    is_locked: Option<i8>,
+}
+
+#[derive(Debug, Serialize)]
+struct ExportPresent {
+    present_id: String,
+    expire_ts: Option<i64>,
+    description: Option<String>,
+}
+
+/// A login session; the access token itself is left out.
+#[derive(Debug, Serialize)]
+struct ExportLogin {
+    login_time: Option<i64>,
+    login_ip: Option<String>,
+    login_device: Option<String>,
+}
+
+/// Writes and serves one JSON export per user in the export folder
+#[derive(Debug, Clone)]
+pub struct UserExportService {
+    pool: DbPool,
+    folder: PathBuf,
+}
+
+impl UserExportService {
+    pub fn new(pool: DbPool, folder: PathBuf) -> Self {
+        Self { pool, folder }
+    }
+
+    fn export_path(&self, user_id: i32) -> PathBuf {
+        self.folder.join(export_file_name(user_id))
+    }
+
+    /// Collect everything stored about `user_id` and replace their export file
+    pub async fn export_user(&self, user_id: i32) -> ArcResult<UserExportInfo> {
+        let profile: ExportProfile = sqlx::query_as!(
+            ExportProfile,
+            "SELECT user_id, name, email, user_code, join_date, rating_ptt,
+                    highest_rating_ptt, character_id, ticket, world_rank_score, current_map
+             FROM user WHERE user_id = ?",
+            user_id
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or_else(|| ArcError::no_data("No user.", 108))?;
+
+        let scores: Vec<ExportScore> = sqlx::query_as!(
+            ExportScore,
+            "SELECT song_id, difficulty, score, shiny_perfect_count, perfect_count,
+                    near_count, miss_count, health, modifier, time_played,
+                    best_clear_type, clear_type, rating, score_v2
+             FROM best_score WHERE user_id = ?
+             ORDER BY song_id, difficulty",
+            user_id
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let world: Vec<ExportWorldMap> = sqlx::query_as!(
+            ExportWorldMap,
+            "SELECT map_id, curr_position, curr_capture, is_locked
+             FROM user_world WHERE user_id = ?
+             ORDER BY map_id",
+            user_id
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let presents: Vec<ExportPresent> = sqlx::query_as!(
+            ExportPresent,
+            "SELECT up.present_id, p.expire_ts, p.description
+             FROM user_present up JOIN present p ON p.present_id = up.present_id
+             WHERE up.user_id = ?
+             ORDER BY up.present_id",
+            user_id
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let logins: Vec<ExportLogin> = sqlx::query_as!(
+            ExportLogin,
+            "SELECT login_time, login_ip, login_device
+             FROM login WHERE user_id = ?
+             ORDER BY login_time DESC",
+            user_id
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let document = export_document(
+            user_id,
+            current_timestamp_ms(),
+            vec![
+                ("profile", serde_json::to_value(profile)?),
+                ("scores", serde_json::to_value(scores)?),
+                ("world", serde_json::to_value(world)?),
+                ("presents", serde_json::to_value(presents)?),
+                ("logins", serde_json::to_value(logins)?),
+            ],
+        );
+        let bytes = serde_json::to_vec_pretty(&document)?;
+
+        // Write next to the old export and rename, so a download never sees
+        // a half-written file
+        tokio::fs::create_dir_all(&self.folder).await?;
+        let path = self.export_path(user_id);
+        let partial = path.with_extension("json.part");
+        tokio::fs::write(&partial, &bytes).await?;
+        tokio::fs::rename(&partial, &path).await?;
+
+        self.export_info(user_id)
+            .await?
+            .ok_or_else(|| ArcError::no_data("No data export.", 108))
+    }
+
+    /// The current export of `user_id`, if one was made
+    pub async fn export_info(&self, user_id: i32) -> ArcResult<Option<UserExportInfo>> {
+        let metadata = match tokio::fs::metadata(self.export_path(user_id)).await {
+            Ok(metadata) => metadata,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let created_at = metadata
+            .modified()
+            .ok()
+            .and_then(|time| time.duration_since(std::time::UNIX_EPOCH).ok())
+            .map(|elapsed| elapsed.as_millis() as i64)
+            .unwrap_or_default();
+        Ok(Some(UserExportInfo {
+            size: metadata.len(),
+            created_at,
+        }))
+    }
+
+    /// Read the export file of `user_id`
+    pub async fn read_export(&self, user_id: i32) -> ArcResult<Vec<u8>> {
+        match tokio::fs::read(self.export_path(user_id)).await {
+            Ok(bytes) => Ok(bytes),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                Err(ArcError::no_data_status("No data export.", 108, 404))
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Remove the export file of `user_id`, if there is one
+    pub async fn delete_export(&self, user_id: i32) -> ArcResult<()> {
+        match tokio::fs::remove_file(self.export_path(user_id)).await {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// File name of the export of `user_id`, also used for the download.
+pub fn export_file_name(user_id: i32) -> String {
+    format!("arcaea-export-{user_id}.json")
+}
+
+/// Top-level export document with one key per section
+fn export_document(user_id: i32, exported_at: i64, sections: Vec<(&str, Value)>) -> Value {
+    let mut document = json!({
+        "format_version": EXPORT_FORMAT_VERSION,
+        "user_id": user_id,
+        "exported_at": exported_at,
+    });
+    for (name, section) in sections {
+        document[name] = section;
+    }
+    document
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_export_document() {
+        let document = export_document(
+            2000001,
+            1_700_000_000_000,
+            vec![
+                ("profile", json!({ "name": "hikari" })),
+                (
+                    "scores",
+                    json!([{ "song_id": "tempestissimo", "difficulty": 3 }]),
+                ),
+                ("logins", json!([])),
+            ],
+        );
+
+        assert_eq!(document["format_version"], EXPORT_FORMAT_VERSION);
+        assert_eq!(document["user_id"], 2000001);
+        assert_eq!(document["exported_at"], 1_700_000_000_000i64);
+        assert_eq!(document["profile"]["name"], "hikari");
+        assert_eq!(document["scores"][0]["song_id"], "tempestissimo");
+        assert!(document["logins"].as_array().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_export_file_name() {
+        assert_eq!(export_file_name(2000001), "arcaea-export-2000001.json");
+    }
+}
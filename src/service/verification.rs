@@ -0,0 +1,237 @@
+use crate::config::CONFIG;
+use crate::error::{ArcError, ArcResult};
+use crate::service::EmailService;
+use crate::utils::current_timestamp_ms;
+use crate::DbPool;
+use rand::Rng;
+use sha2::{Digest, Sha256};
+
+const CODE_LENGTH: usize = 6;
+/// Minimum delay between two codes mailed to the same account.
+const RESEND_COOLDOWN_MS: i64 = 60 * 1000;
+/// Wrong guesses allowed before the code has to be re-sent.
+const MAX_ATTEMPTS: i32 = 5;
+
+/// A code waiting to be confirmed, as stored in `email_verification`.
+#[derive(Debug, Clone)]
+struct PendingCode {
+    code_hash: String,
+    expires_at: i64,
+    attempts: i32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CodeCheck {
+    Accepted,
+    Wrong,
+    Expired,
+    Exhausted,
+}
+
+/// Confirms account email addresses with short codes sent by mail.
+///
+/// Only the SHA-256 of a code is stored. While `email_verification_enabled`
+/// is set, new accounts start unverified and cannot log in until a code is
+/// redeemed; accounts created before the feature was enabled stay verified.
+#[derive(Debug)]
+pub struct VerificationService {
+    pool: DbPool,
+    email: EmailService,
+}
+
+impl VerificationService {
+    pub fn new(pool: DbPool, email: EmailService) -> Self {
+        Self { pool, email }
+    }
+
+    /// Whether registration should wait for email confirmation
+    pub fn is_enabled(&self) -> bool {
+        CONFIG.email_verification_enabled
+    }
+
+    /// Mail a fresh code to the user, replacing any pending one
+    pub async fn issue(&self, user_id: i32) -> ArcResult<()> {
+        let user = sqlx::query!("SELECT name, email FROM user WHERE user_id = ?", user_id)
+            .fetch_optional(&self.pool)
+            .await?
+            .ok_or_else(|| ArcError::no_data("No user.", 108))?;
+        let email = user.email.unwrap_or_default();
+        if email.is_empty() {
+            return Err(ArcError::input("The account has no email address."));
+        }
+
+        let now = current_timestamp_ms();
+        let last_sent = sqlx::query_scalar!(
+            "SELECT sent_at FROM email_verification WHERE user_id = ?",
+            user_id
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+        if last_sent.is_some_and(|sent_at| now - sent_at < RESEND_COOLDOWN_MS) {
+            return Err(ArcError::rate_limit(
+                "Please wait before requesting another code.",
+                -1,
+            ));
+        }
+
+        let code = generate_code();
+        let ttl_minutes = CONFIG.email_verification_code_ttl_minutes.max(1);
+        let expires_at = now + ttl_minutes * 60 * 1000;
+        sqlx::query!(
+            "INSERT INTO email_verification (user_id, code_hash, expires_at, sent_at, attempts)
+             VALUES (?, ?, ?, ?, 0)
+             ON DUPLICATE KEY UPDATE code_hash = VALUES(code_hash),
+                expires_at = VALUES(expires_at), sent_at = VALUES(sent_at), attempts = 0",
+            user_id,
+            hash_code(&code),
+            expires_at,
+            now
+        )
+        .execute(&self.pool)
+        .await?;
+
+        self.email.send_verification(
+            &email,
+            user.name.as_deref().unwrap_or_default(),
+            &code,
+            ttl_minutes,
+        )
+    }
+
+    /// Re-send the code for an unverified account
+    ///
+    /// Unknown or already verified addresses succeed silently so the endpoint
+    /// cannot be used to probe for registered emails.
+    pub async fn resend_for_email(&self, email: &str) -> ArcResult<()> {
+        let user_id = sqlx::query_scalar!(
+            "SELECT user_id FROM user WHERE email = ? AND is_email_verified = 0",
+            email.trim()
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+        match user_id {
+            Some(user_id) => self.issue(user_id).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Redeem a code and mark the account's email as verified
+    pub async fn verify(&self, email: &str, code: &str) -> ArcResult<()> {
+        let user_id = sqlx::query_scalar!("SELECT user_id FROM user WHERE email = ?", email.trim())
+            .fetch_optional(&self.pool)
+            .await?
+            .ok_or_else(|| ArcError::input("Invalid verification code."))?;
+
+        let pending = sqlx::query!(
+            "SELECT code_hash, expires_at, attempts FROM email_verification WHERE user_id = ?",
+            user_id
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .map(|row| PendingCode {
+            code_hash: row.code_hash,
+            expires_at: row.expires_at,
+            attempts: row.attempts,
+        })
+        .ok_or_else(|| ArcError::input("Invalid verification code."))?;
+
+        match check_code(&pending, code.trim(), current_timestamp_ms()) {
+            CodeCheck::Accepted => {
+                let mut tx = self.pool.begin().await?;
+                sqlx::query!(
+                    "UPDATE user SET is_email_verified = 1 WHERE user_id = ?",
+                    user_id
+                )
+                .execute(&mut *tx)
+                .await?;
+                sqlx::query!("DELETE FROM email_verification WHERE user_id = ?", user_id)
+                    .execute(&mut *tx)
+                    .await?;
+                tx.commit().await?;
+                Ok(())
+            }
+            CodeCheck::Wrong => {
+                sqlx::query!(
+                    "UPDATE email_verification SET attempts = attempts + 1 WHERE user_id = ?",
+                    user_id
+                )
+                .execute(&self.pool)
+                .await?;
+                Err(ArcError::input("Invalid verification code."))
+            }
+            CodeCheck::Expired => Err(ArcError::input(
+                "The verification code has expired, please request a new one.",
+            )),
+            CodeCheck::Exhausted => Err(ArcError::input(
+                "Too many wrong attempts, please request a new code.",
+            )),
+        }
+    }
+}
+
+fn generate_code() -> String {
+    let mut rng = rand::thread_rng();
+    (0..CODE_LENGTH)
+        .map(|_| char::from(b'0' + rng.gen_range(0..10)))
+        .collect()
+}
+
+fn hash_code(code: &str) -> String {
+    format!("{:x}", Sha256::digest(code.as_bytes()))
+}
+
+fn check_code(pending: &PendingCode, code: &str, now: i64) -> CodeCheck {
+    if pending.attempts >= MAX_ATTEMPTS {
+        CodeCheck::Exhausted
+    } else if pending.expires_at <= now {
+        CodeCheck::Expired
+    } else if hash_code(code) == pending.code_hash {
+        CodeCheck::Accepted
+    } else {
+        CodeCheck::Wrong
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pending(code: &str, expires_at: i64, attempts: i32) -> PendingCode {
+        PendingCode {
+            code_hash: hash_code(code),
+            expires_at,
+            attempts,
+        }
+    }
+
+    #[test]
+    fn generated_codes_are_six_digits() {
+        let code = generate_code();
+        assert_eq!(code.len(), CODE_LENGTH);
+        assert!(code.chars().all(|c| c.is_ascii_digit()));
+    }
+
+    #[test]
+    fn check_code_accepts_matching_code_before_expiry() {
+        assert_eq!(
+            check_code(&pending("123456", 1_000, 0), "123456", 999),
+            CodeCheck::Accepted
+        );
+        assert_eq!(
+            check_code(&pending("123456", 1_000, 0), "654321", 999),
+            CodeCheck::Wrong
+        );
+    }
+
+    #[test]
+    fn check_code_rejects_expired_and_exhausted_codes() {
+        assert_eq!(
+            check_code(&pending("123456", 1_000, 0), "123456", 1_000),
+            CodeCheck::Expired
+        );
+        assert_eq!(
+            check_code(&pending("123456", 1_000, MAX_ATTEMPTS), "123456", 0),
+            CodeCheck::Exhausted
+        );
+    }
+}
@@ -0,0 +1,187 @@
+use crate::error::{ArcError, ArcResult};
+use rand::Rng;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+/// Unambiguous characters used for short codes (no 0/O, 1/I/L).
+const CODE_ALPHABET: &[u8] = b"ABCDEFGHJKMNPQRSTUVWXYZ23456789";
+const CODE_LENGTH: usize = 8;
+const SECRET_LENGTH: usize = 32;
+const LINK_TTL_MS: i64 = 5 * 60 * 1000;
+
+/// A pending short-code login started from the web dashboard.
+#[derive(Debug, Clone)]
+struct PendingLink {
+    secret: String,
+    expires_at: i64,
+    user_id: Option<i32>,
+}
+
+/// Short code shown on the website plus the secret kept by the browser.
+#[derive(Debug, Clone)]
+pub struct WebLinkTicket {
+    pub code: String,
+    pub secret: String,
+    pub expires_at: i64,
+}
+
+/// State of a pending link as seen by the browser that started it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WebLinkStatus {
+    Pending,
+    Approved(i32),
+    Expired,
+}
+
+/// Binds a web session to a game account without typing a password.
+///
+/// The website starts a link and shows a short code; an authenticated game
+/// session redeems the code, after which the browser holding the matching
+/// secret is logged in. Codes are single-use and live in memory only.
+#[derive(Debug, Clone, Default)]
+pub struct WebLinkService {
+    pending: Arc<Mutex<HashMap<String, PendingLink>>>,
+}
+
+impl WebLinkService {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start a new link and return its code and browser secret.
+    pub async fn start(&self) -> WebLinkTicket {
+        self.start_at(chrono::Utc::now().timestamp_millis()).await
+    }
+
+    async fn start_at(&self, now: i64) -> WebLinkTicket {
+        let mut pending = self.pending.lock().await;
+        pending.retain(|_, link| link.expires_at > now);
+
+        let mut code = random_string(CODE_ALPHABET, CODE_LENGTH);
+        while pending.contains_key(&code) {
+            code = random_string(CODE_ALPHABET, CODE_LENGTH);
+        }
+        let ticket = WebLinkTicket {
+            code: code.clone(),
+            secret: random_string(CODE_ALPHABET, SECRET_LENGTH),
+            expires_at: now + LINK_TTL_MS,
+        };
+        pending.insert(
+            code,
+            PendingLink {
+                secret: ticket.secret.clone(),
+                expires_at: ticket.expires_at,
+                user_id: None,
+            },
+        );
+        ticket
+    }
+
+    /// Redeem a code from an authenticated game session.
+    pub async fn approve(&self, code: &str, user_id: i32) -> ArcResult<()> {
+        self.approve_at(code, user_id, chrono::Utc::now().timestamp_millis())
+            .await
+    }
+
+    async fn approve_at(&self, code: &str, user_id: i32, now: i64) -> ArcResult<()> {
+        let code = normalize_code(code);
+        let mut pending = self.pending.lock().await;
+        match pending.get_mut(&code) {
+            Some(link) if link.expires_at > now && link.user_id.is_none() => {
+                link.user_id = Some(user_id);
+                Ok(())
+            }
+            _ => Err(ArcError::no_data("Invalid or expired link code.", 108)),
+        }
+    }
+
+    /// Check a link from the browser; an approved link is consumed.
+    pub async fn poll(&self, code: &str, secret: &str) -> WebLinkStatus {
+        self.poll_at(code, secret, chrono::Utc::now().timestamp_millis())
+            .await
+    }
+
+    async fn poll_at(&self, code: &str, secret: &str, now: i64) -> WebLinkStatus {
+        let mut pending = self.pending.lock().await;
+        let Some(link) = pending.get(code) else {
+            return WebLinkStatus::Expired;
+        };
+        if link.secret != secret {
+            return WebLinkStatus::Expired;
+        }
+        if link.expires_at <= now {
+            pending.remove(code);
+            return WebLinkStatus::Expired;
+        }
+        match link.user_id {
+            Some(user_id) => {
+                pending.remove(code);
+                WebLinkStatus::Approved(user_id)
+            }
+            None => WebLinkStatus::Pending,
+        }
+    }
+}
+
+fn normalize_code(code: &str) -> String {
+    code.chars()
+        .filter(|c| !c.is_whitespace() && *c != '-')
+        .collect::<String>()
+        .to_ascii_uppercase()
+}
+
+fn random_string(alphabet: &[u8], len: usize) -> String {
+    let mut rng = rand::thread_rng();
+    (0..len)
+        .map(|_| alphabet[rng.gen_range(0..alphabet.len())] as char)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_link_approve_and_poll() {
+        let service = WebLinkService::new();
+        let ticket = service.start_at(1_000).await;
+        assert_eq!(
+            service.poll_at(&ticket.code, &ticket.secret, 1_001).await,
+            WebLinkStatus::Pending
+        );
+
+        let typed = format!(" {}-{} ", &ticket.code[..4], &ticket.code[4..]).to_lowercase();
+        service.approve_at(&typed, 7, 1_002).await.unwrap();
+        assert!(service.approve_at(&ticket.code, 8, 1_003).await.is_err());
+
+        assert_eq!(
+            service.poll_at(&ticket.code, "wrong", 1_004).await,
+            WebLinkStatus::Expired
+        );
+        assert_eq!(
+            service.poll_at(&ticket.code, &ticket.secret, 1_004).await,
+            WebLinkStatus::Approved(7)
+        );
+        assert_eq!(
+            service.poll_at(&ticket.code, &ticket.secret, 1_005).await,
+            WebLinkStatus::Expired
+        );
+    }
+
+    #[tokio::test]
+    async fn test_link_expires() {
+        let service = WebLinkService::new();
+        let ticket = service.start_at(0).await;
+        assert!(service
+            .approve_at(&ticket.code, 7, LINK_TTL_MS + 1)
+            .await
+            .is_err());
+        assert_eq!(
+            service
+                .poll_at(&ticket.code, &ticket.secret, LINK_TTL_MS + 1)
+                .await,
+            WebLinkStatus::Expired
+        );
+    }
+}
@@ -0,0 +1,308 @@
+//! Operator notifications posted to a Discord-compatible webhook.
+
+use crate::error::{ArcError, ArcResult};
+use crate::utils::current_timestamp_ms;
+use serde_json::{json, Value};
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+const REQUEST_TIMEOUT_SECONDS: u64 = 10;
+
+/// Kinds of operator events, as named in `webhook_events`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WebhookEventKind {
+    Registration,
+    FlaggedScore,
+    MigrationFailed,
+    BundleRefresh,
+}
+
+impl WebhookEventKind {
+    pub const ALL: [Self; 4] = [
+        Self::Registration,
+        Self::FlaggedScore,
+        Self::MigrationFailed,
+        Self::BundleRefresh,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Registration => "registration",
+            Self::FlaggedScore => "flagged_score",
+            Self::MigrationFailed => "migration_failed",
+            Self::BundleRefresh => "bundle_refresh",
+        }
+    }
+
+    pub fn parse(value: &str) -> ArcResult<Self> {
+        let value = value.trim();
+        Self::ALL
+            .into_iter()
+            .find(|kind| kind.as_str() == value)
+            .ok_or_else(|| {
+                ArcError::input(format!(
+                    "Invalid webhook event `{value}`, expected one of: {}",
+                    Self::ALL.map(Self::as_str).join(", ")
+                ))
+            })
+    }
+}
+
+/// Something an operator may want to hear about.
+#[derive(Debug, Clone)]
+pub enum WebhookEvent {
+    Registration {
+        user_id: i32,
+        name: String,
+    },
+    /// A submission written to `score_audit`.
+    FlaggedScore {
+        user_id: i32,
+        song_id: String,
+        difficulty: i32,
+        score: i32,
+        flags: Vec<&'static str>,
+        rejected: bool,
+    },
+    MigrationFailed {
+        error: String,
+    },
+    /// The served content bundles were reloaded or a bundle was uploaded.
+    BundleRefresh {
+        bundles: usize,
+    },
+}
+
+impl WebhookEvent {
+    pub fn kind(&self) -> WebhookEventKind {
+        match self {
+            Self::Registration { .. } => WebhookEventKind::Registration,
+            Self::FlaggedScore { .. } => WebhookEventKind::FlaggedScore,
+            Self::MigrationFailed { .. } => WebhookEventKind::MigrationFailed,
+            Self::BundleRefresh { .. } => WebhookEventKind::BundleRefresh,
+        }
+    }
+
+    /// One-line message shown in chat clients.
+    fn summary(&self) -> String {
+        match self {
+            Self::Registration { user_id, name } => {
+                format!("New player `{name}` registered (user {user_id})")
+            }
+            Self::FlaggedScore {
+                user_id,
+                song_id,
+                difficulty,
+                score,
+                flags,
+                rejected,
+            } => format!(
+                "{} score {score} on `{song_id}` [{difficulty}] by user {user_id}: {}",
+                if *rejected { "Rejected" } else { "Flagged" },
+                flags.join(", ")
+            ),
+            Self::MigrationFailed { error } => format!("Database migration failed: {error}"),
+            Self::BundleRefresh { bundles } => {
+                format!("Content bundles refreshed, {bundles} bundles served")
+            }
+        }
+    }
+
+    fn data(&self) -> Value {
+        match self {
+            Self::Registration { user_id, name } => json!({ "userId": user_id, "name": name }),
+            Self::FlaggedScore {
+                user_id,
+                song_id,
+                difficulty,
+                score,
+                flags,
+                rejected,
+            } => json!({
+                "userId": user_id,
+                "songId": song_id,
+                "difficulty": difficulty,
+                "score": score,
+                "flags": flags,
+                "rejected": rejected,
+            }),
+            Self::MigrationFailed { error } => json!({ "error": error }),
+            Self::BundleRefresh { bundles } => json!({ "bundles": bundles }),
+        }
+    }
+
+    /// Request body: Discord reads `content`, other receivers can use the
+    /// structured `event` and `data`.
+    pub fn payload(&self, timestamp: i64) -> Value {
+        json!({
+            "content": self.summary(),
+            "event": self.kind().as_str(),
+            "data": self.data(),
+            "timestamp": timestamp,
+        })
+    }
+}
+
+/// Parse the `webhook_events` filter; an empty list selects every event.
+fn parse_event_filter(events: &[String]) -> ArcResult<HashSet<WebhookEventKind>> {
+    if events.is_empty() {
+        return Ok(WebhookEventKind::ALL.into_iter().collect());
+    }
+    events
+        .iter()
+        .map(|event| WebhookEventKind::parse(event))
+        .collect()
+}
+
+/// Posts operator events to `webhook_url`
+///
+/// Like the push gateway, events go through a background worker so requests
+/// never wait on the webhook; failed deliveries are only logged.
+#[derive(Clone)]
+pub struct WebhookNotifier {
+    url: String,
+    events: Arc<HashSet<WebhookEventKind>>,
+    http: reqwest::Client,
+    sender: Option<mpsc::UnboundedSender<WebhookEvent>>,
+}
+
+impl std::fmt::Debug for WebhookNotifier {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WebhookNotifier")
+            .field("enabled", &self.is_enabled())
+            .field("events", &self.events)
+            .finish()
+    }
+}
+
+impl WebhookNotifier {
+    /// A notifier that drops every event
+    pub fn disabled() -> Self {
+        Self {
+            url: String::new(),
+            events: Arc::new(HashSet::new()),
+            http: reqwest::Client::new(),
+            sender: None,
+        }
+    }
+
+    /// Start the delivery worker; an empty `url` disables notifications
+    pub fn from_config(url: &str, events: &[String]) -> ArcResult<Self> {
+        let url = url.trim();
+        if url.is_empty() {
+            return Ok(Self::disabled());
+        }
+
+        let mut notifier = Self {
+            url: url.to_string(),
+            events: Arc::new(parse_event_filter(events)?),
+            http: reqwest::Client::builder()
+                .timeout(Duration::from_secs(REQUEST_TIMEOUT_SECONDS))
+                .build()
+                .unwrap_or_default(),
+            sender: None,
+        };
+        let (sender, mut receiver) = mpsc::unbounded_channel::<WebhookEvent>();
+        let worker = notifier.clone();
+        tokio::spawn(async move {
+            while let Some(event) = receiver.recv().await {
+                worker.deliver(&event).await;
+            }
+        });
+        notifier.sender = Some(sender);
+        Ok(notifier)
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.sender.is_some()
+    }
+
+    fn wants(&self, kind: WebhookEventKind) -> bool {
+        self.is_enabled() && self.events.contains(&kind)
+    }
+
+    /// Queue an event if it passes the filter.
+    pub fn notify(&self, event: WebhookEvent) {
+        if !self.wants(event.kind()) {
+            return;
+        }
+        if let Some(sender) = &self.sender {
+            if sender.send(event).is_err() {
+                log::warn!("Webhook queue is closed, dropping event");
+            }
+        }
+    }
+
+    /// Post an event right away, for failures the process exits on.
+    pub async fn notify_now(&self, event: WebhookEvent) {
+        if self.wants(event.kind()) {
+            self.deliver(&event).await;
+        }
+    }
+
+    async fn deliver(&self, event: &WebhookEvent) {
+        let result = self
+            .http
+            .post(&self.url)
+            .json(&event.payload(current_timestamp_ms()))
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status);
+        if let Err(e) = result {
+            log::warn!(
+                "Webhook delivery of `{}` failed: {e}",
+                event.kind().as_str()
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_event_filter() {
+        assert_eq!(parse_event_filter(&[]).unwrap().len(), 4);
+
+        let filter =
+            parse_event_filter(&["flagged_score".to_string(), " registration".to_string()])
+                .unwrap();
+        assert!(filter.contains(&WebhookEventKind::FlaggedScore));
+        assert!(filter.contains(&WebhookEventKind::Registration));
+        assert!(!filter.contains(&WebhookEventKind::BundleRefresh));
+
+        assert!(parse_event_filter(&["scores".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_webhook_payload() {
+        let event = WebhookEvent::FlaggedScore {
+            user_id: 2000001,
+            song_id: "grievouslady".to_string(),
+            difficulty: 2,
+            score: 10_001_200,
+            flags: vec!["score_above_max"],
+            rejected: false,
+        };
+        let payload = event.payload(1_700_000_000_000);
+
+        assert_eq!(payload["event"], "flagged_score");
+        assert_eq!(
+            payload["content"],
+            "Flagged score 10001200 on `grievouslady` [2] by user 2000001: score_above_max"
+        );
+        assert_eq!(payload["data"]["songId"], "grievouslady");
+        assert_eq!(payload["data"]["flags"][0], "score_above_max");
+        assert_eq!(payload["timestamp"], 1_700_000_000_000i64);
+    }
+
+    #[test]
+    fn test_disabled_notifier_drops_events() {
+        let notifier = WebhookNotifier::from_config("  ", &[]).unwrap();
+        assert!(!notifier.is_enabled());
+        notifier.notify(WebhookEvent::BundleRefresh { bundles: 1 });
+    }
+}
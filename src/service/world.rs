@@ -1,25 +1,29 @@
-use crate::config::Constants;
 use crate::error::ArcError;
 use crate::service::cache::{env_ttl_seconds, CacheService};
+use crate::service::event::limited_events;
 use crate::service::runtime_assets::asset_path;
 
 use crate::model::world::*;
+use crate::utils::current_timestamp_ms;
+use crate::DbPool;
 use serde_json;
-use sqlx::MySqlPool;
 use std::collections::HashMap;
 use std::sync::OnceLock;
-use std::time::{SystemTime, UNIX_EPOCH};
 
 /// Global map parser instance
 static MAP_PARSER: OnceLock<MapParser> = OnceLock::new();
 
 /// Map parser for loading and caching world map data
+///
+/// Every `map/*.json` asset is read and converted once at startup; lookups
+/// afterwards are served from memory.
 #[derive(Debug, Clone)]
 pub struct MapParser {
     pub map_id_path: HashMap<String, String>,
     pub world_info: HashMap<String, WorldMapInfo>,
     pub chapter_info: HashMap<i32, Vec<String>>,
     pub chapter_info_without_repeatable: HashMap<i32, Vec<String>>,
+    maps: HashMap<String, WorldMap>,
 }
 
 impl MapParser {
@@ -30,6 +34,7 @@ impl MapParser {
             world_info: HashMap::new(),
             chapter_info: HashMap::new(),
             chapter_info_without_repeatable: HashMap::new(),
+            maps: HashMap::new(),
         };
         parser.parse();
         parser
@@ -39,145 +44,88 @@ impl MapParser {
     pub fn parse(&mut self) {
         let map_path = asset_path("map");
 
-        if let Ok(entries) = std::fs::read_dir(&map_path) {
-            for entry in entries.flatten() {
-                if let Some(file_name) = entry.file_name().to_str() {
-                    if file_name.ends_with(".json") {
-                        let map_id = file_name.trim_end_matches(".json").to_string();
-                        let path = entry.path().to_string_lossy().to_string();
-                        self.map_id_path.insert(map_id.clone(), path);
-
-                        if let Ok(map_data) = self.get_world_info(&map_id) {
-                            if let Some(chapter) = map_data.chapter {
-                                self.chapter_info
-                                    .entry(chapter)
-                                    .or_default()
-                                    .push(map_id.clone());
-
-                                if !map_data.is_repeatable {
-                                    self.chapter_info_without_repeatable
-                                        .entry(chapter)
-                                        .or_default()
-                                        .push(map_id.clone());
-                                }
-                            }
-
-                            self.world_info.insert(map_id, map_data);
-                        }
-                    }
+        let Ok(entries) = std::fs::read_dir(&map_path) else {
+            log::warn!("Failed to read world map directory: {}", map_path.display());
+            return;
+        };
+
+        for entry in entries.flatten() {
+            let Some(file_name) = entry.file_name().to_str().map(str::to_string) else {
+                continue;
+            };
+            let Some(map_id) = file_name.strip_suffix(".json") else {
+                continue;
+            };
+            let path = entry.path().to_string_lossy().to_string();
+            self.map_id_path.insert(map_id.to_string(), path.clone());
+
+            let world_map = match read_world_map(map_id, &path) {
+                Ok(world_map) => world_map,
+                Err(e) => {
+                    log::warn!("Skipping world map {map_id}: {e}");
+                    continue;
+                }
+            };
+
+            if let Some(chapter) = world_map.chapter {
+                self.chapter_info
+                    .entry(chapter)
+                    .or_default()
+                    .push(map_id.to_string());
+
+                if !world_map.is_repeatable {
+                    self.chapter_info_without_repeatable
+                        .entry(chapter)
+                        .or_default()
+                        .push(map_id.to_string());
                 }
             }
-        } else {
-            log::warn!("Failed to read world map directory: {}", map_path.display());
+
+            self.world_info.insert(
+                map_id.to_string(),
+                WorldMapInfo {
+                    map_id: map_id.to_string(),
+                    chapter: world_map.chapter,
+                    is_repeatable: world_map.is_repeatable,
+                    is_beyond: world_map.is_beyond,
+                    is_legacy: world_map.is_legacy,
+                    step_count: world_map.step_count,
+                },
+            );
+            self.maps.insert(map_id.to_string(), world_map);
         }
     }
 
     /// Get world info for a specific map ID
     pub fn get_world_info(&self, map_id: &str) -> Result<WorldMapInfo, ArcError> {
-        if let Some(cached) = self.world_info.get(map_id) {
-            return Ok(cached.clone());
-        }
-
-        if let Some(path) = self.map_id_path.get(map_id) {
-            let content = std::fs::read_to_string(path).map_err(|e| ArcError::Database {
-                message: format!("Failed to read map file {path}: {e}"),
-            })?;
-
-            let map_data: WorldMapFile =
-                serde_json::from_str(&content).map_err(|e| ArcError::Database {
-                    message: format!("Failed to parse map JSON {path}: {e}"),
-                })?;
-
-            let world_map_info = WorldMapInfo {
-                map_id: map_id.to_string(),
-                chapter: map_data.chapter,
-                is_repeatable: map_data.is_repeatable,
-                is_beyond: map_data.is_beyond,
-                is_legacy: map_data.is_legacy,
-                step_count: map_data.steps.len() as i32,
-            };
-
-            return Ok(world_map_info);
-        }
-
-        Err(ArcError::no_data(format!("Map {map_id} not found"), 404))
+        self.world_info
+            .get(map_id)
+            .cloned()
+            .ok_or_else(|| ArcError::no_data(format!("Map {map_id} not found"), 404))
     }
 
-    /// Load full world map data from JSON file
+    /// Get full world map data, including steps and their restrictions
     pub fn load_world_map(&self, map_id: &str) -> Result<WorldMap, ArcError> {
-        if let Some(path) = self.map_id_path.get(map_id) {
-            let content = std::fs::read_to_string(path).map_err(|e| ArcError::Database {
-                message: format!("Failed to read map file {path}: {e}"),
-            })?;
-
-            let map_data: WorldMapFile =
-                serde_json::from_str(&content).map_err(|e| ArcError::Database {
-                    message: format!("Failed to parse map JSON {path}: {e}"),
-                })?;
-
-            let mut world_map = WorldMap {
-                map_id: map_id.to_string(),
-                is_legacy: map_data.is_legacy,
-                is_beyond: map_data.is_beyond,
-                is_breached: map_data.is_breached,
-                beyond_health: map_data.beyond_health,
-                character_affinity: map_data.character_affinity,
-                affinity_multiplier: map_data.affinity_multiplier,
-                chapter: map_data.chapter,
-                available_from: map_data.available_from,
-                available_to: map_data.available_to,
-                is_repeatable: map_data.is_repeatable,
-                require_id: map_data.require_id,
-                require_type: map_data.require_type,
-                require_value: map_data.require_value,
-                coordinate: map_data.coordinate,
-                custom_bg: map_data.custom_bg,
-                stamina_cost: map_data.stamina_cost,
-                step_count: 0,
-                require_localunlock_songid: Some(
-                    map_data.require_localunlock_songid.unwrap_or_default(),
-                ),
-                require_localunlock_challengeid: Some(
-                    map_data.require_localunlock_challengeid.unwrap_or_default(),
-                ),
-                chain_info: map_data.chain_info,
-                disable_over: map_data.disable_over,
-                new_law: map_data.new_law,
-                requires_any: map_data.requires_any,
-                steps: Vec::new(),
-            };
-
-            world_map.steps = map_data
-                .steps
-                .into_iter()
-                .enumerate()
-                .map(|(index, step)| WorldStep {
-                    position: index as i32,
-                    capture: step.capture,
-                    items: step
-                        .items
-                        .into_iter()
-                        .map(|item| StepItem {
-                            item_id: item.id,
-                            item_type: item.item_type,
-                            amount: item.amount,
-                        })
-                        .collect(),
-                    restrict_id: step.restrict_id,
-                    restrict_ids: step.restrict_ids,
-                    restrict_type: step.restrict_type,
-                    restrict_difficulty: step.restrict_difficulty,
-                    step_type: step.step_type,
-                    speed_limit_value: step.speed_limit_value,
-                    plus_stamina_value: step.plus_stamina_value,
-                })
-                .collect();
+        self.maps
+            .get(map_id)
+            .cloned()
+            .ok_or_else(|| ArcError::no_data(format!("Map {map_id} not found"), 404))
+    }
 
-            world_map.step_count = world_map.steps.len() as i32;
-            return Ok(world_map);
-        }
+    /// Stamina spent per play on `map_id`
+    pub fn stamina_cost(&self, map_id: &str) -> Result<i32, ArcError> {
+        self.maps
+            .get(map_id)
+            .map(WorldMap::stamina_cost_value)
+            .ok_or_else(|| ArcError::no_data(format!("Map {map_id} not found"), 404))
+    }
 
-        Err(ArcError::no_data(format!("Map {map_id} not found"), 404))
+    /// Get a single step of a map by position
+    pub fn get_step(&self, map_id: &str, position: i32) -> Option<&WorldStep> {
+        self.maps
+            .get(map_id)?
+            .steps
+            .get(usize::try_from(position).ok()?)
     }
 
     /// Get all map IDs
@@ -192,6 +140,74 @@ impl Default for MapParser {
     }
 }
 
+/// Read and convert one map JSON file
+fn read_world_map(map_id: &str, path: &str) -> Result<WorldMap, ArcError> {
+    let content = std::fs::read_to_string(path).map_err(|e| ArcError::Database {
+        message: format!("Failed to read map file {path}: {e}"),
+    })?;
+
+    let map_data: WorldMapFile =
+        serde_json::from_str(&content).map_err(|e| ArcError::Database {
+            message: format!("Failed to parse map JSON {path}: {e}"),
+        })?;
+
+    let steps: Vec<WorldStep> = map_data
+        .steps
+        .into_iter()
+        .enumerate()
+        .map(|(index, step)| WorldStep {
+            position: index as i32,
+            capture: step.capture,
+            items: step
+                .items
+                .into_iter()
+                .map(|item| StepItem {
+                    item_id: item.id,
+                    item_type: item.item_type,
+                    amount: item.amount,
+                })
+                .collect(),
+            restrict_id: step.restrict_id,
+            restrict_ids: step.restrict_ids,
+            restrict_type: step.restrict_type,
+            restrict_difficulty: step.restrict_difficulty,
+            step_type: step.step_type,
+            speed_limit_value: step.speed_limit_value,
+            plus_stamina_value: step.plus_stamina_value,
+        })
+        .collect();
+
+    Ok(WorldMap {
+        map_id: map_id.to_string(),
+        is_legacy: map_data.is_legacy,
+        is_beyond: map_data.is_beyond,
+        is_breached: map_data.is_breached,
+        beyond_health: map_data.beyond_health,
+        character_affinity: map_data.character_affinity,
+        affinity_multiplier: map_data.affinity_multiplier,
+        chapter: map_data.chapter,
+        available_from: map_data.available_from,
+        available_to: map_data.available_to,
+        is_repeatable: map_data.is_repeatable,
+        require_id: map_data.require_id,
+        require_type: map_data.require_type,
+        require_value: map_data.require_value,
+        coordinate: map_data.coordinate,
+        custom_bg: map_data.custom_bg,
+        stamina_cost: map_data.stamina_cost,
+        step_count: steps.len() as i32,
+        require_localunlock_songid: Some(map_data.require_localunlock_songid.unwrap_or_default()),
+        require_localunlock_challengeid: Some(
+            map_data.require_localunlock_challengeid.unwrap_or_default(),
+        ),
+        chain_info: map_data.chain_info,
+        disable_over: map_data.disable_over,
+        new_law: map_data.new_law,
+        requires_any: map_data.requires_any,
+        steps,
+    })
+}
+
 /// Get the global map parser instance
 pub fn get_map_parser() -> &'static MapParser {
     MAP_PARSER.get_or_init(MapParser::new)
@@ -258,7 +274,7 @@ impl UserMapImpl {
 
         if self.map.is_beyond {
             // Beyond map logic
-            let beyond_health = self.map.beyond_health.unwrap_or(100) as f64;
+            let beyond_health = self.map.beyond_health_value();
             let dt = beyond_health - self.curr_capture;
             self.curr_capture = if dt >= step_value {
                 self.curr_capture + step_value
@@ -329,78 +345,16 @@ impl UserMapImpl {
     }
 }
 
-/// Stamina calculation logic
-#[derive(Debug, Clone)]
-pub struct StaminaImpl {
-    stamina: i32,
-    max_stamina_ts: i64,
-}
-
-impl StaminaImpl {
-    /// Create new stamina instance
-    pub fn new(stamina: i32, max_stamina_ts: i64) -> Self {
-        Self {
-            stamina: if stamina > 0 {
-                stamina
-            } else {
-                Constants::MAX_STAMINA
-            },
-            max_stamina_ts: if max_stamina_ts > 0 {
-                max_stamina_ts
-            } else {
-                0
-            },
-        }
-    }
-
-    /// Get current stamina value based on time calculation
-    pub fn get_current_stamina(&self) -> i32 {
-        let current_time = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_millis() as i64;
-
-        let recovery_ticks =
-            (self.max_stamina_ts - current_time) as f64 / Constants::STAMINA_RECOVER_TICK as f64;
-        let calculated_stamina = Constants::MAX_STAMINA - recovery_ticks.round() as i32;
-
-        if calculated_stamina >= Constants::MAX_STAMINA {
-            if self.stamina >= Constants::MAX_STAMINA {
-                self.stamina
-            } else {
-                Constants::MAX_STAMINA
-            }
-        } else {
-            calculated_stamina
-        }
-    }
-
-    pub fn max_stamina_ts(&self) -> i64 {
-        self.max_stamina_ts
-    }
-
-    /// Set stamina value and update max_stamina_ts accordingly
-    pub fn set_stamina(&mut self, value: i32) {
-        self.stamina = value;
-        let current_time = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_millis() as i64;
-        self.max_stamina_ts = current_time
-            - (self.stamina - Constants::MAX_STAMINA) as i64 * Constants::STAMINA_RECOVER_TICK;
-    }
-}
-
 /// World service for handling world map system
 pub struct WorldService {
-    pool: MySqlPool,
+    pool: DbPool,
     cache: Option<CacheService>,
     world_cache_ttl_seconds: u64,
 }
 
 impl WorldService {
     /// Create a new world service instance
-    pub fn new(pool: MySqlPool) -> Self {
+    pub fn new(pool: DbPool) -> Self {
         Self {
             pool,
             cache: None,
@@ -504,7 +458,14 @@ impl WorldService {
     /// Load user map data
     async fn load_user_map(&self, user_id: i32, map_id: &str) -> Result<UserMap, ArcError> {
         let parser = get_map_parser();
-        let world_map = parser.load_world_map(map_id)?;
+        let mut world_map = parser.load_world_map(map_id)?;
+        // Show the stamina cost of running events, as charged by the score token.
+        let stamina_cost = world_map.stamina_cost_value();
+        let event_stamina_cost =
+            limited_events().stamina_cost(stamina_cost, current_timestamp_ms());
+        if event_stamina_cost != stamina_cost {
+            world_map.stamina_cost = Some(event_stamina_cost);
+        }
 
         // Get user progress for this map
         let user_world = sqlx::query!(
@@ -627,6 +588,11 @@ impl WorldService {
         user_id: i32,
         map_id: &str,
     ) -> Result<serde_json::Value, ArcError> {
+        let world_map = get_map_parser().load_world_map(map_id)?;
+        if !world_map.is_available_at(current_timestamp_ms()) {
+            return Err(ArcError::map_locked("The map is not available."));
+        }
+
         let mut tx = self.pool.begin().await.map_err(|e| ArcError::Database {
             message: format!("Failed to start transaction: {e}"),
         })?;